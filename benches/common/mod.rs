@@ -0,0 +1,13 @@
+/// Bytes which are the same on every run, but don't compress or repeat like zeroes would
+pub fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
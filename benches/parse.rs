@@ -0,0 +1,123 @@
+//! The parsing and packing paths which every tool goes through, so that refactors which slow them
+//! down are caught. Inputs are generated, as game files can't be shipped with the crate.
+
+mod common;
+
+use std::{collections::HashMap, hint::black_box};
+
+use bnl::{
+    AssetMetadata, BNLFile, RawAsset, VirtualResource,
+    asset::{
+        AssetType,
+        loctext::LoctextResource,
+        model::nd::{ModelReadContext, Nd},
+    },
+};
+use common::noise;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+/// The size of the archive read by the from_bytes benchmark
+const ARCHIVE_SIZE: usize = 100 * 1024 * 1024;
+const ARCHIVE_CHUNK_SIZE: usize = 256 * 1024;
+
+fn bnl_from_bytes(c: &mut Criterion) {
+    // Each chunk starts with noise and ends with zeroes, so the archive compresses about as well
+    // as real textures and models do
+    let chunk: Vec<u8> = noise(ARCHIVE_CHUNK_SIZE / 2)
+        .into_iter()
+        .chain(std::iter::repeat_n(0, ARCHIVE_CHUNK_SIZE / 2))
+        .collect();
+
+    let mut bnl = BNLFile::default();
+
+    for i in 0..ARCHIVE_SIZE / ARCHIVE_CHUNK_SIZE {
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new(&format!("aid_bench_{}", i), AssetType::ResMisc, 0, 0),
+            noise(64),
+            Some(vec![chunk.clone()]),
+        ))
+        .expect("Unable to add benchmark asset");
+    }
+
+    let bytes = bnl.to_bytes();
+
+    let mut group = c.benchmark_group("bnl");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(ARCHIVE_SIZE as u64));
+
+    group.bench_function("from_bytes_100mb", |b| {
+        b.iter(|| BNLFile::from_bytes(black_box(&bytes)).unwrap())
+    });
+
+    let bnl = BNLFile::from_bytes(&bytes).unwrap();
+
+    group.bench_function("to_bytes_100mb", |b| b.iter(|| black_box(&bnl).to_bytes()));
+
+    group.finish();
+}
+
+fn nd_hierarchy(c: &mut Criterion) {
+    let bytes = include_bytes!("../src/asset/model/nd/test_meshes/test_mesh_0");
+    let key_value_map = HashMap::new();
+
+    c.bench_function("nd_hierarchy", |b| {
+        b.iter(|| {
+            Nd::from_bytes(
+                &mut ModelReadContext::new(&key_value_map),
+                black_box(bytes),
+                0x34,
+            )
+            .unwrap()
+        })
+    });
+}
+
+fn virtual_resource_get_bytes(c: &mut Criterion) {
+    // Mesh parsing reads many small values out of a resource split into hundreds of data views
+    let bytes = noise(512 * 1024);
+    let slices: Vec<&[u8]> = bytes.chunks(1024).collect();
+    let virtual_res = VirtualResource::from_slices(&slices);
+
+    let reads: Vec<usize> = (0..10_000)
+        .map(|i| (i * 52_361) % (bytes.len() - 12))
+        .collect();
+
+    let mut group = c.benchmark_group("virtual_resource");
+    group.throughput(Throughput::Elements(reads.len() as u64));
+
+    group.bench_function("get_bytes_512_slices", |b| {
+        b.iter(|| {
+            for &offset in &reads {
+                black_box(virtual_res.get_bytes(offset, 12).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn loctext_dump(c: &mut Criterion) {
+    let values: HashMap<String, String> = (0..5000)
+        .map(|i| {
+            (
+                format!("menu_option_{}", i),
+                format!("Option number {}, which the player can pick", i),
+            )
+        })
+        .collect();
+
+    let loctext = LoctextResource::from_hashmap(values).unwrap();
+
+    c.bench_function("loctext_dump_5000", |b| {
+        b.iter(|| black_box(&loctext).dump().unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    bnl_from_bytes,
+    nd_hierarchy,
+    virtual_resource_get_bytes,
+    loctext_dump
+);
+criterion_main!(benches);
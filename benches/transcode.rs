@@ -0,0 +1,47 @@
+//! Texture decoding, the slowest part of extracting a BNL file with PNGs. Run with and without the
+//! `simd` feature to compare the fast paths against the scalar code.
+
+mod common;
+
+use std::hint::black_box;
+
+use bnl::{
+    asset::texture::{Texture, TextureDescriptor},
+    d3d::{D3DFormat, StandardFormat, Swizzled},
+};
+use common::noise;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+const SIZE: u16 = 1024;
+
+fn texture(format: D3DFormat) -> Texture {
+    let len = format.image_size(SIZE.into(), SIZE.into());
+
+    Texture::new(
+        TextureDescriptor::new(format, 0x1c, SIZE, SIZE, 1, 0, 0, len as u32),
+        noise(len),
+    )
+}
+
+fn transcode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transcode");
+    group.throughput(Throughput::Elements(SIZE as u64 * SIZE as u64));
+
+    for (name, format) in [
+        ("dxt1", D3DFormat::Standard(StandardFormat::DXT1)),
+        ("dxt3", D3DFormat::Standard(StandardFormat::DXT2Or3)),
+        ("b8g8r8a8", D3DFormat::Swizzled(Swizzled::B8G8R8A8)),
+        ("a8r8g8b8", D3DFormat::Swizzled(Swizzled::A8R8G8B8)),
+    ] {
+        let texture = texture(format);
+
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&texture).to_rgba_image().unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, transcode);
+criterion_main!(benches);
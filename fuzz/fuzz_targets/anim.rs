@@ -0,0 +1,5 @@
+#![no_main]
+
+mod common;
+
+common::asset_fuzz_target!(bnl::asset::anim::Anim);
@@ -0,0 +1,32 @@
+//! The body shared by the asset fuzz targets, which only differ in the type of asset they parse
+
+/// Defines a fuzz target which reads its input as a descriptor of `$target` followed by resource
+/// bytes. The first 4 bytes pick where the input is split into descriptor and resource bytes.
+macro_rules! asset_fuzz_target {
+    ($target:ty) => {
+        libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+            use bnl::{
+                VirtualResource,
+                asset::{AssetDescriptor, AssetLike},
+            };
+
+            let Some((split, rest)) = data.split_first_chunk::<4>() else {
+                return;
+            };
+
+            let split = (u32::from_le_bytes(*split) as usize).min(rest.len());
+            let (descriptor_bytes, resource_bytes) = rest.split_at(split);
+
+            let Ok(descriptor) =
+                <$target as AssetLike>::Descriptor::from_bytes(descriptor_bytes)
+            else {
+                return;
+            };
+
+            let slices = [resource_bytes];
+            let _ = <$target>::new(&descriptor, &VirtualResource::from_slices(&slices));
+        });
+    };
+}
+
+pub(crate) use asset_fuzz_target;
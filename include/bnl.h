@@ -0,0 +1,54 @@
+/*
+ * C interface to the bnl crate. Build the library with:
+ *
+ *     cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * See src/capi.rs for the ownership rules of each function.
+ */
+
+#ifndef BNL_H
+#define BNL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum BnlStatus {
+    BNL_OK = 0,
+    BNL_NULL_POINTER = 1,
+    BNL_INVALID_DATA = 2,
+    BNL_NOT_FOUND = 3,
+    BNL_BUFFER_TOO_SMALL = 4,
+    BNL_TYPE_MISMATCH = 5,
+    BNL_PANIC = 6,
+} BnlStatus;
+
+typedef struct BnlHandle BnlHandle;
+
+/* 4 bytes per pixel, in RGBA order */
+typedef struct BnlImage {
+    uint32_t width;
+    uint32_t height;
+    uint8_t *data;
+    size_t len;
+} BnlImage;
+
+BnlStatus bnl_open(const uint8_t *data, size_t len, BnlHandle **out_handle);
+void bnl_close(BnlHandle *handle);
+
+size_t bnl_asset_count(const BnlHandle *handle);
+BnlStatus bnl_get_asset_name(const BnlHandle *handle, size_t index, char *buf, size_t buf_len,
+                             size_t *out_len);
+BnlStatus bnl_get_asset_type(const BnlHandle *handle, size_t index, uint32_t *out_type);
+
+BnlStatus bnl_extract_texture_rgba(const BnlHandle *handle, size_t index, BnlImage *out_image);
+void bnl_free_image(BnlImage *image);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BNL_H */
@@ -1,18 +1,18 @@
+#[cfg(feature = "fs")]
 use std::{
     fs::{File, read_to_string},
     io::{BufWriter, Write},
     path::Path,
 };
 
+#[cfg(feature = "fs")]
+use crate::asset::{Dump, Parse};
 use crate::{
-    VirtualResource,
-    asset::{
-        AssetDescriptor, AssetLike, AssetName, AssetParseError, AssetType, Dump,
-        MAX_ASSET_NAME_LENGTH, Parse,
-    },
+    AssetMetadata, BNLFile, VirtualResource,
+    asset::{AssetDescriptor, AssetLike, AssetName, AssetParseError, AssetType},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AidListDescriptor {
     asset_ids: Vec<AssetName>,
 }
@@ -22,9 +22,48 @@ impl AidListDescriptor {}
 #[derive(Debug, Clone)]
 pub struct AidList {
     asset_ids: Vec<String>,
+    /// The names the ids were read from, which keep whatever follows their null terminator
+    original_ids: Vec<AssetName>,
 }
 
 impl AidList {
+    /**
+    Builds a list of the names of the assets in `bnl` which `filter` accepts, sorted by name with
+    duplicates removed. This is the same order that [`AssetOrder::NameSorted`] lays assets out in.
+
+    [`AssetOrder::NameSorted`]: crate::AssetOrder::NameSorted
+
+    # Examples
+    ```no_run
+    use bnl::{BNLFile, asset::{AssetType, aidlist::AidList}};
+
+    let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+    let bnl = BNLFile::from_bytes(&bytes).expect("Unable to parse BNL.");
+
+    // Every asset except for the aid lists themselves
+    let aidlist = AidList::from_bnl(&bnl, |metadata| {
+        metadata.asset_type() != AssetType::ResAidList
+    });
+    ```
+    */
+    pub fn from_bnl(bnl: &BNLFile, filter: impl Fn(&AssetMetadata) -> bool) -> Self {
+        let mut asset_ids: Vec<String> = bnl
+            .get_raw_assets()
+            .iter()
+            .map(|asset| asset.metadata())
+            .filter(|metadata| filter(metadata))
+            .map(|metadata| metadata.name().to_string())
+            .collect();
+
+        asset_ids.sort();
+        asset_ids.dedup();
+
+        Self {
+            asset_ids,
+            original_ids: vec![],
+        }
+    }
+
     pub fn asset_ids(&self) -> &Vec<String> {
         &self.asset_ids
     }
@@ -35,7 +74,7 @@ impl AidList {
 
 impl AssetDescriptor for AidListDescriptor {
     fn from_bytes(data: &[u8]) -> Result<Self, AssetParseError> {
-        if data.len() % 128 != 0 {
+        if data.len() % size_of::<AssetName>() != 0 {
             return Err(AssetParseError::InvalidDataViews(format!(
                 "Input bytes were expected to be a multiple of 128 (received {})",
                 data.len()
@@ -44,10 +83,10 @@ impl AssetDescriptor for AidListDescriptor {
 
         Ok(Self {
             asset_ids: data
-                .chunks_exact(128)
+                .chunks_exact(size_of::<AssetName>())
                 .map(|chunk| {
-                    chunk[0..128]
-                        .try_into()
+                    <[u8; size_of::<AssetName>()]>::try_from(chunk)
+                        .map(AssetName::from)
                         .map_err(|_| AssetParseError::ErrorParsingDescriptor)
                 })
                 .collect::<Result<Vec<AssetName>, _>>()?,
@@ -77,24 +116,20 @@ impl AssetLike for AidList {
         let mut strings: Vec<String> = Vec::new();
 
         for asset_id in &descriptor.asset_ids {
-            match asset_id.iter().position(|c| *c == 0) {
-                None => {
-                    return Err(AssetParseError::InvalidDataViews(format!(
-                        "No null terminating char in asset id {}",
-                        String::from_utf8(asset_id.to_vec()).unwrap_or("STRING ERROR".to_string())
-                    )));
-                }
-
-                Some(length) => {
-                    strings.push(
-                        String::from_utf8(asset_id[..length].to_vec())
-                            .map_err(|_| AssetParseError::ErrorParsingDescriptor)?,
-                    );
-                }
+            if !asset_id.is_terminated() {
+                return Err(AssetParseError::InvalidDataViews(format!(
+                    "No null terminating char in asset id {}",
+                    asset_id
+                )));
             }
+
+            strings.push(asset_id.to_string());
         }
 
-        Ok(Self { asset_ids: strings })
+        Ok(Self {
+            asset_ids: strings,
+            original_ids: descriptor.asset_ids.clone(),
+        })
     }
 
     fn get_descriptor(&self) -> Self::Descriptor {
@@ -102,23 +137,15 @@ impl AssetLike for AidList {
             asset_ids: self
                 .asset_ids
                 .iter()
-                .map(|asset_id_str| {
-                    let mut new_chars = [0u8; 128];
-
-                    let len = asset_id_str.len();
-
-                    new_chars[0..len].copy_from_slice(
-                        &asset_id_str
-                            .chars()
-                            .take(len)
-                            .map(|c| c as u8)
-                            .collect::<Vec<u8>>(),
-                    );
-
-                    Ok(new_chars)
+                .enumerate()
+                .map(|(i, asset_id)| {
+                    // Ids which haven't changed are written back with their original bytes
+                    match self.original_ids.get(i) {
+                        Some(original) if original.as_str() == asset_id => *original,
+                        _ => AssetName::from_str_truncated(asset_id),
+                    }
                 })
-                .collect::<Result<Vec<AssetName>, AssetParseError>>()
-                .unwrap(),
+                .collect(),
         }
     }
 
@@ -127,6 +154,7 @@ impl AssetLike for AidList {
     }
 }
 
+#[cfg(feature = "fs")]
 impl Dump for AidList {
     fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
         {
@@ -148,22 +176,112 @@ impl Dump for AidList {
     }
 }
 
+#[cfg(feature = "fs")]
 impl Parse for AidList {
     fn parse<P: AsRef<Path>>(parse_path: P) -> Result<Self, AssetParseError> {
         let asset_ids: Vec<String> = read_to_string(parse_path)?
             .lines()
             .filter(|line| !line.is_empty())
             .map(|asset_id| -> Result<String, AssetParseError> {
-                if asset_id.len() <= (MAX_ASSET_NAME_LENGTH) {
-                    Ok(asset_id.to_string())
-                } else {
-                    Err(AssetParseError::InvalidDataViews(
-                        "Input too large.".to_string(),
-                    ))
-                }
+                AssetName::try_from(asset_id)
+                    .map(|name| name.to_string())
+                    .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(Self { asset_ids })
+        Ok(Self {
+            asset_ids,
+            original_ids: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn aidlist_descriptor_roundtrip(
+            names in prop::collection::vec(prop::collection::vec(any::<u8>(), 128), 0..16)
+        ) {
+            let descriptor = AidListDescriptor {
+                asset_ids: names
+                    .into_iter()
+                    .map(|bytes| {
+                        AssetName::from(
+                            <[u8; size_of::<AssetName>()]>::try_from(bytes)
+                                .expect("Names are generated with the right size"),
+                        )
+                    })
+                    .collect(),
+            };
+
+            let bytes = descriptor.to_bytes().expect("Aid lists can always be written");
+
+            prop_assert_eq!(bytes.len(), descriptor.size());
+            prop_assert_eq!(AidListDescriptor::from_bytes(&bytes).ok(), Some(descriptor));
+        }
+    }
+
+    #[test]
+    fn aidlist_from_bnl() {
+        use crate::{
+            d3d::{D3DFormat, StandardFormat},
+            testing::BnlFixture,
+        };
+
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let bnl = BnlFixture::new()
+            .texture("aid_texture_b", 4, 4, dxt1)
+            .script("aid_script_a", "EndScript")
+            .texture("aid_texture_a", 4, 4, dxt1)
+            .build();
+
+        let all = AidList::from_bnl(&bnl, |_| true);
+        assert_eq!(
+            all.asset_ids(),
+            &["aid_script_a", "aid_texture_a", "aid_texture_b"]
+        );
+
+        let textures = AidList::from_bnl(&bnl, |metadata| {
+            metadata.asset_type() == AssetType::ResTexture
+        });
+        assert_eq!(textures.asset_ids(), &["aid_texture_a", "aid_texture_b"]);
+
+        // Every id is written as a full asset name
+        assert_eq!(textures.get_descriptor().size(), 2 * size_of::<AssetName>());
+    }
+
+    #[test]
+    fn aidlist_keeps_original_names() -> Result<(), String> {
+        let mut bytes = vec![0; size_of::<AssetName>() * 2];
+        bytes[..9].copy_from_slice(b"aid_a\0xyz");
+        bytes[128..133].copy_from_slice(b"aid_b");
+
+        let descriptor = AidListDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        let mut aidlist = AidList::new(&descriptor, &VirtualResource::from_slices(&[]))
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            aidlist
+                .get_descriptor()
+                .to_bytes()
+                .map_err(|e| e.to_string())?,
+            bytes
+        );
+
+        aidlist.asset_ids_mut()[0] = "aid_c".to_string();
+        let written = aidlist
+            .get_descriptor()
+            .to_bytes()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(&written[..9], b"aid_c\0\0\0\0");
+        assert_eq!(written[128..], bytes[128..]);
+
+        Ok(())
     }
 }
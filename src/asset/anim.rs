@@ -1,23 +1,43 @@
-use std::io::{Cursor, Read};
+pub mod compression;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
 use gltf_writer::gltf::{NodeTransform, Quaternion};
 
 use crate::{
     VirtualResource,
-    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType},
-    utils::bitstream::BitStream,
+    asset::{
+        AssetDescriptor, AssetLike, AssetParseError, AssetType, OriginalBytes,
+        fields::{DescriptorField, DescriptorFields, FieldValue},
+        model::nd::Bone,
+    },
+    utils::{
+        bitstream::{BitStream, BitWriter},
+        reader::Le,
+    },
 };
 
-#[derive(Debug, Clone, PartialEq)]
+/// The size of the fixed part of an anim descriptor, before the pack formats
+const ANIM_HEADER_SIZE: usize = 0x4c;
+
+// Bits of a pack format byte
+const X_USED: u8 = 0b10000000;
+const INTERPOLATED: u8 = 0b00010000;
+const HAS_TRANSLATION: u8 = 0b00000010;
+const HAS_SCALE: u8 = 0b00000001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AnimValueUsageType {
+    /// Flagged as interpolated in the pack format. What this changes about the stored values
+    /// hasn't been checked against poses captured from the game, so they are decoded the same as
+    /// raw values.
     Interpolated,
     Raw,
     Unused,
 }
 
-#[expect(unused)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Vec3UsageType {
     x: AnimValueUsageType,
     y: AnimValueUsageType,
@@ -26,6 +46,39 @@ pub struct Vec3UsageType {
     bit0: bool,
 }
 
+/// Packs the usages into the top bits of a pack format byte
+fn usage_bits(usages: [&AnimValueUsageType; 3]) -> u8 {
+    let mut bits = usages
+        .iter()
+        .enumerate()
+        .filter(|(_, usage)| ***usage != AnimValueUsageType::Unused)
+        .fold(0u8, |acc, (i, _)| acc | (X_USED >> i));
+
+    if usages.contains(&&AnimValueUsageType::Interpolated) {
+        bits |= INTERPOLATED;
+    }
+
+    bits
+}
+
+impl Vec3UsageType {
+    fn all(usage: AnimValueUsageType) -> Self {
+        Self {
+            x: usage.clone(),
+            y: usage.clone(),
+            z: usage,
+            bit1: false,
+            bit0: false,
+        }
+    }
+}
+
+impl From<&Vec3UsageType> for u8 {
+    fn from(value: &Vec3UsageType) -> Self {
+        usage_bits([&value.x, &value.y, &value.z]) | ((value.bit1 as u8) << 1) | (value.bit0 as u8)
+    }
+}
+
 impl From<u8> for Vec3UsageType {
     fn from(value: u8) -> Self {
         let usages = (0..3)
@@ -54,7 +107,7 @@ impl From<u8> for Vec3UsageType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PrecisionSpecifiers {
     unknown_u8: u8,
     /// u5
@@ -90,7 +143,8 @@ impl PrecisionSpecifiers {
 
     pub fn quat_divisor(&self) -> u32 {
         // 2^(n-1) - 1
-        2u32.pow(self.quat_divisor as u32 - 1) - 1
+        2u32.pow((self.quat_divisor as u32).saturating_sub(1))
+            .saturating_sub(1)
     }
     #[inline]
     pub fn quat_constant(&self) -> f32 {
@@ -122,7 +176,7 @@ impl From<PrecisionSpecifiers> for u32 {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PackFormat {
     qx: AnimValueUsageType,
     qy: AnimValueUsageType,
@@ -133,8 +187,8 @@ pub struct PackFormat {
 }
 
 impl PackFormat {
-    pub fn from_mut_cursor(cur: &mut Cursor<&[u8]>) -> Result<Self, AssetParseError> {
-        let q_format = cur.read_u8()?;
+    pub fn from_reader(reader: &mut Le) -> Result<Self, AssetParseError> {
+        let q_format = reader.u8()?;
 
         let usages = (0..3)
             .map(|i| {
@@ -153,13 +207,13 @@ impl PackFormat {
         let [qx, qy, qz] = usages.try_into().unwrap();
 
         let translation = if q_format & 0b10 == 0b10 {
-            Some(Vec3UsageType::from(cur.read_u8()?))
+            Some(Vec3UsageType::from(reader.u8()?))
         } else {
             None
         };
 
         let scale = if q_format & 0b01 == 0b01 {
-            Some(Vec3UsageType::from(cur.read_u8()?))
+            Some(Vec3UsageType::from(reader.u8()?))
         } else {
             None
         };
@@ -172,9 +226,69 @@ impl PackFormat {
             scale,
         })
     }
+
+    /// The number of bytes the pack format occupies in the descriptor
+    pub fn size(&self) -> usize {
+        1 + self.translation.is_some() as usize + self.scale.is_some() as usize
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut q_format = usage_bits([&self.qx, &self.qy, &self.qz]);
+
+        if self.translation.is_some() {
+            q_format |= HAS_TRANSLATION;
+        }
+        if self.scale.is_some() {
+            q_format |= HAS_SCALE;
+        }
+
+        let mut bytes = vec![q_format];
+        bytes.extend(self.translation.as_ref().map(u8::from));
+        bytes.extend(self.scale.as_ref().map(u8::from));
+
+        bytes
+    }
 }
 
-#[derive(Clone)]
+/**
+The data at an anim's `tail_data_ptr`, which follows its keyframes. Its layout isn't known yet,
+but it is likely to hold animation events or root motion, so it is kept as it was read and written
+back after the keyframes.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimTail {
+    bytes: Vec<u8>,
+}
+
+impl AnimTail {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The tail as little endian u32s, which the rest of the header is made of. Bytes after the
+    /// last whole u32 are left out.
+    pub fn words(&self) -> Vec<u32> {
+        self.bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+}
+
+// Floats are compared by value, so this can't be Eq
+#[derive(Clone, PartialEq)]
 pub struct AnimDescriptor {
     magic: [u8; 4],
     inverse_divisor: f32,
@@ -217,6 +331,9 @@ pub struct AnimDescriptor {
 
     bits_per_channel: Vec<u8>,
     keyframe_bytes: Vec<u8>,
+    /// Everything from `tail_data_ptr` to the end of the descriptor
+    tail: Option<AnimTail>,
+    original_bytes: OriginalBytes,
 }
 
 impl AnimDescriptor {
@@ -256,6 +373,85 @@ impl AnimDescriptor {
     pub fn num_keyframes(&self) -> u16 {
         self.num_keyframes
     }
+
+    pub fn tail(&self) -> Option<&AnimTail> {
+        self.tail.as_ref()
+    }
+
+    /// Replaces the data written after the keyframes, which `tail_data_ptr` is set to point at
+    pub fn set_tail(&mut self, tail: Option<AnimTail>) {
+        self.tail = tail;
+    }
+
+    /// The offset the keyframes start at, after the pack formats, shorts and bit counts
+    fn keyframes_offset(&self) -> usize {
+        ANIM_HEADER_SIZE
+            + self
+                .pack_formats
+                .iter()
+                .map(PackFormat::size)
+                .sum::<usize>()
+            + self.shorts.len() * 2
+            + self.bits_per_channel.len().div_ceil(2)
+    }
+
+    /**
+    Moves a pointer to match where the tail is written. Pointers at or past the tail as it was
+    read are taken to point into it, so keep the same offset from its start.
+    */
+    fn relocate(&self, ptr: u32) -> u32 {
+        if self.tail.is_none() || self.tail_data_ptr == 0 || ptr < self.tail_data_ptr {
+            return ptr;
+        }
+
+        let tail_offset = self.keyframes_offset() + self.keyframe_bytes.len();
+
+        (ptr - self.tail_data_ptr).wrapping_add(tail_offset as u32)
+    }
+
+    /// Keeps the tail of `original`, along with the unknown values and any pointers into the
+    /// tail, which are likely to describe it
+    fn keep_tail_from(&mut self, original: &AnimDescriptor) {
+        let Some(tail) = &original.tail else {
+            return;
+        };
+
+        let into_tail = |ptr: u32| {
+            if original.tail_data_ptr != 0 && ptr >= original.tail_data_ptr {
+                ptr
+            } else {
+                0
+            }
+        };
+
+        self.c_vals_ptr = into_tail(original.c_vals_ptr);
+        self.some_ptr_1 = into_tail(original.some_ptr_1);
+        self.some_ptr_2 = into_tail(original.some_ptr_2);
+        self.tail_data_ptr = original.tail_data_ptr;
+
+        self.some_u32_1 = original.some_u32_1;
+        self.some_u32_2 = original.some_u32_2;
+        self.some_u32_3 = original.some_u32_3;
+        self.some_u32_4 = original.some_u32_4;
+        self.some_u32_5 = original.some_u32_5;
+        self.some_u32_6 = original.some_u32_6;
+
+        self.tail = Some(tail.clone());
+    }
+
+    fn decode_keyframes(&self) -> Result<Vec<AnimKeyframe>, AssetParseError> {
+        if self.keyframe_bytes.is_empty()
+            || self.keyframe_size == 0
+            || self.keyframe_size as usize > self.keyframe_bytes.len()
+        {
+            return Err(AssetParseError::ErrorParsingDescriptor);
+        }
+
+        self.keyframe_bytes
+            .chunks_exact(self.keyframe_size as usize)
+            .map(|chunk| AnimKeyframe::new(self, chunk))
+            .collect()
+    }
 }
 
 impl std::fmt::Debug for AnimDescriptor {
@@ -287,6 +483,7 @@ impl std::fmt::Debug for AnimDescriptor {
             .field("pack_formats", &self.pack_formats)
             .field("shorts", &self.shorts)
             .field("bits_per_channel", &self.bits_per_channel)
+            .field("tail", &self.tail)
             .finish()
     }
 }
@@ -464,6 +661,51 @@ impl AnimKeyframe {
     }
 }
 
+/// A single value channel after quantisation, as stored in the shorts, bits per channel and
+/// keyframe sections of the descriptor
+struct QuantisedChannel {
+    short: i16,
+    bits: u8,
+    deltas: Vec<u32>,
+}
+
+impl QuantisedChannel {
+    /// Quantises `values` to multiples of `constant`, storing the smallest as the short and the
+    /// rest as unsigned deltas from it
+    fn new(values: &[f32], constant: f32) -> Result<Self, AnimError> {
+        if !constant.is_finite() || constant <= 0.0 || values.iter().any(|v| !v.is_finite()) {
+            return Err(AnimError::InvalidInput);
+        }
+
+        let quantised = values
+            .iter()
+            .map(|v| (v / constant).round() as i64)
+            .collect::<Vec<_>>();
+
+        let min = quantised.iter().copied().min().unwrap_or(0);
+        let short = i16::try_from(min).map_err(|_| AnimError::InvalidInput)?;
+
+        let deltas = quantised
+            .iter()
+            .map(|q| u32::try_from(q - min).map_err(|_| AnimError::InvalidInput))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // At least 1 bit is always stored, and at most 16 fit in a nibble
+        let max_delta = deltas.iter().copied().max().unwrap_or(0);
+        let bits = (u32::BITS - max_delta.leading_zeros()).max(1);
+
+        if bits > 16 {
+            return Err(AnimError::InvalidInput);
+        }
+
+        Ok(Self {
+            short,
+            bits: bits as u8,
+            deltas,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum AnimError {
     SizeMismatch,
@@ -484,6 +726,26 @@ pub struct BoneAnimChannel {
     pub scale: Option<Vec<[f32; 3]>>,
 }
 
+/// The transform of one bone at a point in an animation, see [`Anim::sample`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BonePose {
+    pub translation: Option<[f32; 3]>,
+    pub rotation: Option<[f32; 4]>,
+    pub scale: Option<[f32; 3]>,
+}
+
+/// Blends linearly between `values[i]` and `values[next]`
+fn lerp_keyframes<const N: usize>(
+    values: &[[f32; N]],
+    i: usize,
+    next: usize,
+    t: f32,
+) -> Option<[f32; N]> {
+    let (a, b) = (values.get(i)?, values.get(next)?);
+
+    Some(std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t))
+}
+
 impl Anim {
     pub fn new(descriptor: AnimDescriptor) -> Self {
         Anim {
@@ -500,6 +762,231 @@ impl Anim {
         &self.keyframes
     }
 
+    /// The data after the keyframes, see [`AnimTail`]
+    pub fn tail(&self) -> Option<&AnimTail> {
+        self.descriptor.tail()
+    }
+
+    /**
+    Builds an animation from one [`BoneAnimChannel`] per bone, quantising the values using the
+    constants of `precision`.
+
+    Every channel must have the same number of keyframes. Rotations are normalised and stored
+    with a positive w, as only x, y and z are kept in the file. All channels are stored as raw
+    values, and the pointers and unknown values of the header are left as 0.
+
+    # Errors
+    - [`AnimError::SizeMismatch`] if the channels have differing numbers of keyframes, or there
+      are too many bones or keyframes to store
+    - [`AnimError::InvalidInput`] if there are no keyframes, `duration` isn't a positive finite
+      number, or a value can't be represented with the given precision
+    */
+    pub fn from_channels(
+        channels: &[BoneAnimChannel],
+        duration: f32,
+        precision: PrecisionSpecifiers,
+    ) -> Result<Anim, AnimError> {
+        if !duration.is_finite() || duration <= 0.0 {
+            return Err(AnimError::InvalidInput);
+        }
+
+        let lengths = channels
+            .iter()
+            .flat_map(|channel| {
+                [
+                    channel.rotation.as_ref().map(Vec::len),
+                    channel.translation.as_ref().map(Vec::len),
+                    channel.scale.as_ref().map(Vec::len),
+                ]
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let num_keyframes = *lengths.first().ok_or(AnimError::InvalidInput)?;
+
+        if lengths.iter().any(|len| *len != num_keyframes) {
+            return Err(AnimError::SizeMismatch);
+        }
+        if num_keyframes == 0 {
+            return Err(AnimError::InvalidInput);
+        }
+
+        let mut pack_formats = vec![];
+        let mut quantised = vec![];
+
+        for channel in channels {
+            let used = |present: bool| {
+                if present {
+                    AnimValueUsageType::Raw
+                } else {
+                    AnimValueUsageType::Unused
+                }
+            };
+
+            pack_formats.push(PackFormat {
+                qx: used(channel.rotation.is_some()),
+                qy: used(channel.rotation.is_some()),
+                qz: used(channel.rotation.is_some()),
+                translation: channel
+                    .translation
+                    .as_ref()
+                    .map(|_| Vec3UsageType::all(AnimValueUsageType::Raw)),
+                scale: channel
+                    .scale
+                    .as_ref()
+                    .map(|_| Vec3UsageType::all(AnimValueUsageType::Raw)),
+            });
+
+            // Same order as they are read back in AnimKeyframe::new
+            if let Some(rotation) = &channel.rotation {
+                let rotation = rotation
+                    .iter()
+                    .map(|q| {
+                        let len = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+                        if len == 0.0 || !len.is_finite() {
+                            return Err(AnimError::InvalidInput);
+                        }
+
+                        // w is reconstructed as positive when reading
+                        let sign = if q[3] < 0.0 { -1.0 } else { 1.0 };
+                        Ok(q.map(|v| v * sign / len))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                for i in 0..3 {
+                    let values = rotation.iter().map(|q| q[i]).collect::<Vec<_>>();
+                    quantised.push(QuantisedChannel::new(&values, precision.quat_constant())?);
+                }
+            }
+
+            for (vecs, constant) in [
+                (&channel.translation, precision.pos_constant()),
+                (&channel.scale, precision.scale_constant()),
+            ] {
+                if let Some(vecs) = vecs {
+                    for i in 0..3 {
+                        let values = vecs.iter().map(|v| v[i]).collect::<Vec<_>>();
+                        quantised.push(QuantisedChannel::new(&values, constant)?);
+                    }
+                }
+            }
+        }
+
+        let shorts = quantised.iter().map(|c| c.short).collect::<Vec<_>>();
+
+        // Bit counts are stored in pairs, so an odd number of channels gets a 1 bit padding channel
+        let mut bits_per_channel = quantised.iter().map(|c| c.bits).collect::<Vec<_>>();
+        if bits_per_channel.len() % 2 == 1 {
+            bits_per_channel.push(1);
+        }
+
+        let keyframe_size = bits_per_channel
+            .iter()
+            .map(|bits| *bits as usize)
+            .sum::<usize>()
+            .div_ceil(8);
+
+        let mut keyframe_bytes = Vec::with_capacity(keyframe_size * num_keyframes);
+
+        for keyframe in 0..num_keyframes {
+            let mut writer = BitWriter::new();
+
+            for channel in &quantised {
+                writer
+                    .write(channel.deltas[keyframe], channel.bits as usize)
+                    .map_err(|_| AnimError::InvalidInput)?;
+            }
+
+            let mut bytes = writer.into_bytes();
+            bytes.resize(keyframe_size, 0);
+
+            keyframe_bytes.extend(bytes);
+        }
+
+        let pack_formats_size = pack_formats.iter().map(PackFormat::size).sum::<usize>();
+
+        let to_u16 = |v: usize| u16::try_from(v).map_err(|_| AnimError::SizeMismatch);
+
+        let descriptor = AnimDescriptor {
+            magic: *b"MINA",
+            inverse_divisor: duration.recip(),
+            duration,
+            c_vals_ptr: 0,
+            some_ptr_1: 0,
+            num_bones: to_u16(channels.len())?,
+            unused_1: 0,
+            num_keyframes: to_u16(num_keyframes)?,
+            unused_2: 0,
+            precision_specifiers: precision,
+            some_ptr_2: 0,
+            some_u32_1: 0,
+            tail_data_ptr: 0,
+            some_u32_2: 0,
+            some_u32_3: 0,
+            some_u32_4: 0,
+            some_u32_5: 0,
+            some_u32_6: 0,
+            // Assumed to be the offset of the shorts
+            header_size: to_u16(ANIM_HEADER_SIZE + pack_formats_size)?,
+            section1_size: to_u16(shorts.len() * 2)?,
+            section2_size: to_u16(bits_per_channel.len() / 2)?,
+            keyframe_size: to_u16(keyframe_size)?,
+            some_float: 0.0,
+            pack_formats,
+            shorts,
+            bits_per_channel,
+            keyframe_bytes,
+            tail: None,
+            original_bytes: OriginalBytes::default(),
+        };
+
+        let keyframes = descriptor
+            .decode_keyframes()
+            .map_err(|_| AnimError::InvalidInput)?;
+
+        Ok(Anim {
+            descriptor,
+            keyframes,
+        })
+    }
+
+    /**
+    Replaces the keyframes of a single bone, re-quantising the whole animation with its existing
+    duration and precision.
+
+    The new channel must have the same number of keyframes as the animation. The [`AnimTail`] is
+    kept, along with the unknown header values that may describe it.
+
+    # Errors
+    - [`AnimError::InvalidInput`] if `bone_index` is out of range, or see [`Anim::from_channels`]
+    */
+    pub fn set_channel(
+        &mut self,
+        bone_index: usize,
+        channel: BoneAnimChannel,
+    ) -> Result<(), AnimError> {
+        let mut channels = self.get_bone_anim_channels();
+
+        *channels
+            .get_mut(bone_index)
+            .ok_or(AnimError::InvalidInput)? = channel;
+
+        let mut anim = Anim::from_channels(
+            &channels,
+            self.descriptor.duration,
+            self.descriptor.precision_specifiers.clone(),
+        )?;
+
+        anim.descriptor.inverse_divisor = self.descriptor.inverse_divisor;
+        anim.descriptor.some_float = self.descriptor.some_float;
+        anim.descriptor.keep_tail_from(&self.descriptor);
+
+        *self = anim;
+
+        Ok(())
+    }
+
     // pub fn get_channels(&self) -> Vec<Vec<NodeTransform>> {
     //     let num_channels = self
     //         .keyframes
@@ -517,6 +1004,52 @@ impl Anim {
     //     channels
     // }
 
+    /**
+    The pose of every bone `time` seconds into the animation, blending linearly between the two
+    nearest keyframes. Keyframes are taken to be evenly spaced over the duration, and times outside
+    of it are clamped to the first or last keyframe. Rotations are blended with a normalised lerp.
+    */
+    pub fn sample(&self, time: f32) -> Vec<BonePose> {
+        let channels = self.get_bone_anim_channels();
+        let last = self.keyframes.len().saturating_sub(1);
+
+        let fraction = time / self.descriptor.duration;
+        let position = match fraction.is_finite() {
+            true => fraction.clamp(0.0, 1.0) * last as f32,
+            false => 0.0,
+        };
+
+        let i = (position as usize).min(last);
+        let next = (i + 1).min(last);
+        let t = position - i as f32;
+
+        channels
+            .iter()
+            .map(|channel| BonePose {
+                translation: channel
+                    .translation
+                    .as_deref()
+                    .and_then(|values| lerp_keyframes(values, i, next, t)),
+                rotation: channel
+                    .rotation
+                    .as_deref()
+                    .and_then(|values| lerp_keyframes(values, i, next, t))
+                    .map(|q| {
+                        let len = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+                        match len > 0.0 {
+                            true => q.map(|v| v / len),
+                            false => q,
+                        }
+                    }),
+                scale: channel
+                    .scale
+                    .as_deref()
+                    .and_then(|values| lerp_keyframes(values, i, next, t)),
+            })
+            .collect()
+    }
+
     pub fn get_bone_anim_channels(&self) -> Vec<BoneAnimChannel> {
         let num_channels = self
             .keyframes
@@ -603,67 +1136,204 @@ impl Anim {
     }
 }
 
+/// How the bones of one skeleton are matched to the bones of another by [`retarget`]
+#[derive(Debug, Clone)]
+pub enum BoneMap {
+    /// Bones with the same name are matched. Unnamed bones are never matched.
+    ByName,
+    /// Bones with the same index are matched
+    ByIndex,
+    /// Pairs of (source bone index, destination bone index)
+    Explicit(Vec<(usize, usize)>),
+}
+
+impl BoneMap {
+    /// Finds the source bone for each destination bone
+    fn resolve(&self, src: &[Bone], dst: &[Bone]) -> Result<Vec<Option<usize>>, AnimError> {
+        match self {
+            BoneMap::ByName => Ok(dst
+                .iter()
+                .map(|dst_bone| {
+                    dst_bone.name.as_ref().and_then(|name| {
+                        src.iter()
+                            .position(|src_bone| src_bone.name.as_ref() == Some(name))
+                    })
+                })
+                .collect()),
+            BoneMap::ByIndex => Ok((0..dst.len())
+                .map(|i| (i < src.len()).then_some(i))
+                .collect()),
+            BoneMap::Explicit(pairs) => {
+                let mut sources = vec![None; dst.len()];
+
+                for (src_index, dst_index) in pairs {
+                    if *src_index >= src.len() {
+                        return Err(AnimError::InvalidInput);
+                    }
+
+                    *sources.get_mut(*dst_index).ok_or(AnimError::InvalidInput)? = Some(*src_index);
+                }
+
+                Ok(sources)
+            }
+        }
+    }
+}
+
+/**
+Retargets `anim`, which animates `src_skeleton`, onto `dst_skeleton`.
+
+Channel `i` of the animation belongs to bone `i` of `src_skeleton`. Each channel is moved to the
+matching bone of `dst_skeleton` according to `bone_map`, and its translations are shifted by the
+difference between the rest poses of the two bones. Rotations and scales are copied as they are.
+Bones of `dst_skeleton` without a match are left unanimated.
+
+The new animation keeps the duration and precision of `anim`.
+
+# Errors
+- [`AnimError::SizeMismatch`] if a matched source bone has no channel in `anim`
+- [`AnimError::InvalidInput`] if `bone_map` references bones which don't exist, or see
+  [`Anim::from_channels`]
+*/
+pub fn retarget(
+    anim: &Anim,
+    src_skeleton: &[Bone],
+    dst_skeleton: &[Bone],
+    bone_map: &BoneMap,
+) -> Result<Anim, AnimError> {
+    let src_channels = anim.get_bone_anim_channels();
+
+    let channels = bone_map
+        .resolve(src_skeleton, dst_skeleton)?
+        .into_iter()
+        .zip(dst_skeleton)
+        .map(|(src_index, dst_bone)| {
+            let Some(src_index) = src_index else {
+                return Ok(BoneAnimChannel::default());
+            };
+
+            let mut channel = src_channels
+                .get(src_index)
+                .cloned()
+                .ok_or(AnimError::SizeMismatch)?;
+
+            let src_rest = src_skeleton[src_index].local_translation;
+            let dst_rest = dst_bone.local_translation;
+
+            for translation in channel.translation.iter_mut().flatten() {
+                for (value, (dst, src)) in
+                    translation.iter_mut().zip(dst_rest.iter().zip(&src_rest))
+                {
+                    *value += dst - src;
+                }
+            }
+
+            Ok(channel)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Anim::from_channels(
+        &channels,
+        anim.descriptor.duration,
+        anim.descriptor.precision_specifiers.clone(),
+    )
+}
+
+impl DescriptorFields for AnimDescriptor {
+    fn fields(&self) -> Vec<DescriptorField> {
+        vec![
+            DescriptorField::new("magic", 0x0, FieldValue::Bytes(self.magic.to_vec())),
+            DescriptorField::new(
+                "inverse_divisor",
+                0x4,
+                FieldValue::F32(self.inverse_divisor),
+            ),
+            DescriptorField::new("duration", 0x8, FieldValue::F32(self.duration)),
+            DescriptorField::new("c_vals_ptr", 0xc, FieldValue::U32(self.c_vals_ptr)),
+            DescriptorField::unknown("some_ptr_1", 0x10, FieldValue::U32(self.some_ptr_1)),
+            DescriptorField::new("num_bones", 0x14, FieldValue::U16(self.num_bones)),
+            DescriptorField::new("unused_1", 0x16, FieldValue::U16(self.unused_1)),
+            DescriptorField::new("num_keyframes", 0x18, FieldValue::U16(self.num_keyframes)),
+            DescriptorField::new("unused_2", 0x1a, FieldValue::U16(self.unused_2)),
+            DescriptorField::new(
+                "precision_specifiers",
+                0x1c,
+                FieldValue::U32(self.precision_specifiers.clone().into()),
+            ),
+            DescriptorField::unknown("some_ptr_2", 0x20, FieldValue::U32(self.some_ptr_2)),
+            DescriptorField::unknown("some_u32_1", 0x24, FieldValue::U32(self.some_u32_1)),
+            DescriptorField::new("tail_data_ptr", 0x28, FieldValue::U32(self.tail_data_ptr)),
+            DescriptorField::unknown("some_u32_2", 0x2c, FieldValue::U32(self.some_u32_2)),
+            DescriptorField::unknown("some_u32_3", 0x30, FieldValue::U32(self.some_u32_3)),
+            DescriptorField::unknown("some_u32_4", 0x34, FieldValue::U32(self.some_u32_4)),
+            DescriptorField::unknown("some_u32_5", 0x38, FieldValue::U32(self.some_u32_5)),
+            DescriptorField::unknown("some_u32_6", 0x3c, FieldValue::U32(self.some_u32_6)),
+            DescriptorField::new("header_size", 0x40, FieldValue::U16(self.header_size)),
+            DescriptorField::new("section1_size", 0x42, FieldValue::U16(self.section1_size)),
+            DescriptorField::new("section2_size", 0x44, FieldValue::U16(self.section2_size)),
+            DescriptorField::new("keyframe_size", 0x46, FieldValue::U16(self.keyframe_size)),
+            DescriptorField::unknown("some_float", 0x48, FieldValue::F32(self.some_float)),
+        ]
+    }
+}
+
 impl AssetDescriptor for AnimDescriptor {
     fn from_bytes(data: &[u8]) -> Result<Self, AssetParseError> {
-        let mut cur = Cursor::new(data);
-
-        let mut magic = [0u8; 4];
+        let mut reader = Le::new(data);
 
-        cur.read_exact(&mut magic)?;
+        let magic: [u8; 4] = reader.array()?;
 
         // Stored backwards in asset
         if &magic != b"MINA" {
             return Err(AssetParseError::ErrorParsingDescriptor);
         }
 
-        let inverse_divisor = cur.read_f32::<LittleEndian>()?;
-        let duration = cur.read_f32::<LittleEndian>()?;
-        let c_vals_ptr: u32 = cur.read_u32::<LittleEndian>()?;
-        let some_ptr_1: u32 = cur.read_u32::<LittleEndian>()?;
+        let inverse_divisor = reader.f32()?;
+        let duration = reader.f32()?;
+        let [c_vals_ptr, some_ptr_1] = reader.array::<u32, 2>()?;
 
-        let transforms_per_keyframe = cur.read_u16::<LittleEndian>()?;
-        let unused_1 = cur.read_u16::<LittleEndian>()?;
-        let num_keyframes = cur.read_u16::<LittleEndian>()?;
-        let unused_2 = cur.read_u16::<LittleEndian>()?;
+        let [transforms_per_keyframe, unused_1, num_keyframes, unused_2] =
+            reader.array::<u16, 4>()?;
 
-        let precision_specifiers = cur.read_u32::<LittleEndian>()?.into();
+        let precision_specifiers = reader.u32()?.into();
 
-        let some_ptr_2 = cur.read_u32::<LittleEndian>()?;
-        let some_u32_1 = cur.read_u32::<LittleEndian>()?;
-        let tail_data_ptr = cur.read_u32::<LittleEndian>()?;
-        let some_u32_2 = cur.read_u32::<LittleEndian>()?;
+        let [some_ptr_2, some_u32_1, tail_data_ptr, some_u32_2] = reader.array::<u32, 4>()?;
 
-        let some_u32_3 = cur.read_u32::<LittleEndian>()?;
-        let some_u32_4 = cur.read_u32::<LittleEndian>()?;
-        let some_u32_5 = cur.read_u32::<LittleEndian>()?;
-        let some_u32_6 = cur.read_u32::<LittleEndian>()?;
+        let [some_u32_3, some_u32_4, some_u32_5, some_u32_6] = reader.array::<u32, 4>()?;
 
-        let header_size = cur.read_u16::<LittleEndian>()?;
-        let section1_size = cur.read_u16::<LittleEndian>()?;
-        let section2_size = cur.read_u16::<LittleEndian>()?;
+        let [header_size, section1_size, section2_size, keyframe_size] =
+            reader.array::<u16, 4>()?;
 
-        let keyframe_size: u16 = cur.read_u16::<LittleEndian>()?;
-
-        let some_float: f32 = cur.read_f32::<LittleEndian>()?;
+        let some_float = reader.f32()?;
 
         let pack_formats = (0..transforms_per_keyframe)
-            .map(|_| PackFormat::from_mut_cursor(&mut cur))
+            .map(|_| PackFormat::from_reader(&mut reader))
             .collect::<Result<Vec<_>, _>>()?;
 
         let shorts = (0..(section1_size / 2))
-            .map(|_| cur.read_i16::<LittleEndian>())
+            .map(|_| reader.struct_of::<i16>())
             .collect::<Result<Vec<_>, _>>()?;
 
-        let bits_per_channel = (0..(section2_size))
-            .map(|_| cur.read_u8())
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
+        let bits_per_channel = reader
+            .bytes(section2_size as usize)?
+            .iter()
             .flat_map(|v| [(v & 0b1111) + 1, ((v >> 4) & 0b1111) + 1])
             .collect::<Vec<u8>>();
 
-        let mut keyframe_bytes = vec![];
+        let keyframes_start = reader.position();
 
-        cur.read_to_end(&mut keyframe_bytes)?;
+        // The keyframes run up to the tail if there is one, or the end of the descriptor if not
+        let tail_start = tail_data_ptr as usize;
+
+        let (keyframe_bytes, tail) =
+            if tail_start != 0 && (keyframes_start..=data.len()).contains(&tail_start) {
+                (
+                    &data[keyframes_start..tail_start],
+                    Some(AnimTail::new(data[tail_start..].to_vec())),
+                )
+            } else {
+                (&data[keyframes_start..], None)
+            };
 
         Ok(AnimDescriptor {
             magic,
@@ -692,20 +1362,86 @@ impl AssetDescriptor for AnimDescriptor {
             pack_formats,
             shorts,
             bits_per_channel,
-            keyframe_bytes,
+            keyframe_bytes: keyframe_bytes.to_vec(),
+            tail,
+            original_bytes: OriginalBytes::new(data),
         })
     }
 
     fn size(&self) -> usize {
-        todo!();
+        self.keyframes_offset()
+            + self.keyframe_bytes.len()
+            + self.tail.as_ref().map_or(0, AnimTail::len)
     }
 
     fn asset_type() -> AssetType {
         AssetType::ResAnim
     }
 
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(self.original_bytes.as_bytes())
+    }
+
     fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
-        todo!();
+        let mut bytes = Cursor::new(self.original_bytes.base(self.size()));
+
+        bytes.write_all(&self.magic)?;
+        bytes.write_f32::<LittleEndian>(self.inverse_divisor)?;
+        bytes.write_f32::<LittleEndian>(self.duration)?;
+        bytes.write_u32::<LittleEndian>(self.relocate(self.c_vals_ptr))?;
+        bytes.write_u32::<LittleEndian>(self.relocate(self.some_ptr_1))?;
+
+        bytes.write_u16::<LittleEndian>(self.num_bones)?;
+        bytes.write_u16::<LittleEndian>(self.unused_1)?;
+        bytes.write_u16::<LittleEndian>(self.num_keyframes)?;
+        bytes.write_u16::<LittleEndian>(self.unused_2)?;
+
+        bytes.write_u32::<LittleEndian>(self.precision_specifiers.clone().into())?;
+
+        let tail_data_ptr = match &self.tail {
+            Some(_) => (self.keyframes_offset() + self.keyframe_bytes.len()) as u32,
+            None => self.tail_data_ptr,
+        };
+
+        bytes.write_u32::<LittleEndian>(self.relocate(self.some_ptr_2))?;
+        bytes.write_u32::<LittleEndian>(self.some_u32_1)?;
+        bytes.write_u32::<LittleEndian>(tail_data_ptr)?;
+        bytes.write_u32::<LittleEndian>(self.some_u32_2)?;
+
+        bytes.write_u32::<LittleEndian>(self.some_u32_3)?;
+        bytes.write_u32::<LittleEndian>(self.some_u32_4)?;
+        bytes.write_u32::<LittleEndian>(self.some_u32_5)?;
+        bytes.write_u32::<LittleEndian>(self.some_u32_6)?;
+
+        bytes.write_u16::<LittleEndian>(self.header_size)?;
+        bytes.write_u16::<LittleEndian>(self.section1_size)?;
+        bytes.write_u16::<LittleEndian>(self.section2_size)?;
+        bytes.write_u16::<LittleEndian>(self.keyframe_size)?;
+        bytes.write_f32::<LittleEndian>(self.some_float)?;
+
+        for pack_format in &self.pack_formats {
+            bytes.write_all(&pack_format.to_bytes())?;
+        }
+
+        for short in &self.shorts {
+            bytes.write_i16::<LittleEndian>(*short)?;
+        }
+
+        // Two channels per byte, each stored as (bits - 1)
+        for pair in self.bits_per_channel.chunks(2) {
+            let low = pair[0].saturating_sub(1) & 0b1111;
+            let high = pair.get(1).map_or(0, |v| v.saturating_sub(1) & 0b1111);
+
+            bytes.write_u8(low | (high << 4))?;
+        }
+
+        bytes.write_all(&self.keyframe_bytes)?;
+
+        if let Some(tail) = &self.tail {
+            bytes.write_all(tail.bytes())?;
+        }
+
+        Ok(bytes.into_inner())
     }
 }
 
@@ -713,22 +1449,9 @@ impl AssetLike for Anim {
     type Descriptor = AnimDescriptor;
 
     fn new(descriptor: &Self::Descriptor, _: &VirtualResource) -> Result<Self, AssetParseError> {
-        if descriptor.keyframe_bytes.is_empty()
-            || descriptor.keyframe_size == 0
-            || descriptor.keyframe_size as usize > descriptor.keyframe_bytes.len()
-        {
-            return Err(AssetParseError::ErrorParsingDescriptor);
-        }
-
-        let keyframes = descriptor
-            .keyframe_bytes
-            .chunks_exact(descriptor.keyframe_size as usize)
-            .map(|chunk| AnimKeyframe::new(descriptor, chunk))
-            .collect::<Result<Vec<_>, _>>()?;
-
         Ok(Self {
             descriptor: descriptor.clone(),
-            keyframes,
+            keyframes: descriptor.decode_keyframes()?,
         })
     }
 
@@ -740,3 +1463,395 @@ impl AssetLike for Anim {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{AssetMetadata, BNLFile, RawAsset};
+
+    fn test_precision() -> PrecisionSpecifiers {
+        // Scale and position to 1/256, quaternions to 1/2047
+        PrecisionSpecifiers::from((8 << 8) | (8 << 18) | (12 << 23))
+    }
+
+    fn assert_close<const N: usize>(a: &[[f32; N]], b: &[[f32; N]], epsilon: f32) {
+        assert_eq!(a.len(), b.len());
+
+        for (a, b) in a.iter().zip(b) {
+            for (a, b) in a.iter().zip(b) {
+                assert!((a - b).abs() <= epsilon, "{} != {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn anim_from_channels_roundtrip() -> Result<(), String> {
+        let channels = vec![
+            BoneAnimChannel {
+                translation: Some(vec![[0.0, 1.0, -2.5], [0.5, 1.25, -2.0], [1.0, 1.5, 3.0]]),
+                rotation: Some(vec![
+                    [0.0, 0.0, 0.0, 1.0],
+                    [0.0, 0.38268343, 0.0, 0.9238795],
+                    [0.0, 0.70710677, 0.0, 0.70710677],
+                ]),
+                scale: None,
+            },
+            BoneAnimChannel {
+                translation: None,
+                rotation: None,
+                scale: Some(vec![[1.0, 1.0, 1.0], [2.0, 2.0, 2.0], [1.0, 0.5, 1.0]]),
+            },
+        ];
+
+        let anim = Anim::from_channels(&channels, 1.5, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        let bytes = anim.descriptor().to_bytes().map_err(|e| e.to_string())?;
+        assert_eq!(bytes.len(), anim.descriptor().size());
+
+        let descriptor = AnimDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        let parsed = <Anim as AssetLike>::new(&descriptor, &VirtualResource::from_slices(&[]))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(parsed.keyframes().len(), 3);
+
+        let decoded = parsed.get_bone_anim_channels();
+
+        assert_close(
+            decoded[0].translation.as_deref().unwrap(),
+            channels[0].translation.as_deref().unwrap(),
+            1.0 / 256.0,
+        );
+        assert_close(
+            decoded[0].rotation.as_deref().unwrap(),
+            channels[0].rotation.as_deref().unwrap(),
+            2.0 / 2047.0,
+        );
+        assert_close(
+            decoded[1].scale.as_deref().unwrap(),
+            channels[1].scale.as_deref().unwrap(),
+            1.0 / 256.0,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn anim_set_channel() -> Result<(), String> {
+        let channel = |x: f32| BoneAnimChannel {
+            translation: Some(vec![[x, 0.0, 0.0], [x + 1.0, 0.0, 0.0]]),
+            rotation: None,
+            scale: None,
+        };
+
+        let mut anim = Anim::from_channels(&[channel(0.0), channel(0.0)], 1.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        anim.set_channel(1, channel(4.0))
+            .map_err(|e| format!("{:?}", e))?;
+
+        let channels = anim.get_bone_anim_channels();
+        assert_eq!(
+            channels[0].translation,
+            Some(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]])
+        );
+        assert_eq!(
+            channels[1].translation,
+            Some(vec![[4.0, 0.0, 0.0], [5.0, 0.0, 0.0]])
+        );
+
+        assert!(anim.set_channel(2, channel(0.0)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn anim_from_channels_bad_duration() {
+        let channels = [BoneAnimChannel {
+            translation: Some(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]),
+            rotation: None,
+            scale: None,
+        }];
+
+        for duration in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                Anim::from_channels(&channels, duration, test_precision()),
+                Err(AnimError::InvalidInput)
+            ));
+        }
+    }
+
+    #[test]
+    fn anim_modify_preserves_bytes() -> Result<(), String> {
+        let channel = BoneAnimChannel {
+            translation: Some(vec![[0.5, 1.0, 0.0], [1.5, 1.0, 0.0]]),
+            rotation: None,
+            scale: None,
+        };
+
+        let anim = Anim::from_channels(&[channel.clone(), channel], 1.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        let descriptor = anim
+            .get_descriptor()
+            .to_bytes()
+            .map_err(|e| e.to_string())?;
+
+        let mut bnl = BNLFile::default();
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_anim_test", AssetType::ResAnim, 0, 0),
+            descriptor.clone(),
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        bnl.modify_asset::<Anim, _>("aid_anim_test", |anim| {
+            anim.asset_mut().descriptor.some_u32_6 = 0x12345678;
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+
+        let written = bnl.get_raw_asset("aid_anim_test").ok_or("Asset missing")?;
+
+        let mut expected = descriptor.clone();
+        expected[0x3c..0x40].copy_from_slice(&0x12345678u32.to_le_bytes());
+
+        assert_eq!(written.descriptor_bytes(), &expected[..]);
+        assert_eq!(
+            AnimDescriptor::from_bytes(&descriptor)
+                .map_err(|e| e.to_string())?
+                .original_bytes(),
+            Some(&descriptor[..])
+        );
+        assert_eq!(written.resource_chunks(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn anim_compression_report() -> Result<(), String> {
+        use compression::AnimChannelKind;
+
+        let channels = vec![
+            BoneAnimChannel {
+                translation: Some(vec![[0.0, 1.0, 0.0], [0.5, 1.0, 0.0], [3.0, 1.0, 0.0]]),
+                rotation: None,
+                scale: None,
+            },
+            BoneAnimChannel {
+                translation: None,
+                rotation: None,
+                scale: Some(vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 1.0]]),
+            },
+        ];
+
+        let anim = Anim::from_channels(&channels, 1.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+        let report = anim
+            .descriptor()
+            .compression_report()
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(report.num_keyframes, 3);
+
+        // x spans 3.0 at 1/256, which takes 10 bits, and the constant channels take 1 bit each
+        assert_eq!(report.bits_histogram[10], 1);
+        assert_eq!(report.bits_histogram[1], 5);
+
+        assert_eq!(report.bones.len(), 2);
+        assert_eq!(report.bones[0].bits_per_keyframe, 12);
+        assert_eq!(
+            report.bones[0].steps,
+            [(AnimChannelKind::Translation, 1.0 / 256.0)]
+        );
+
+        let translation = |exponent| {
+            report
+                .requantised
+                .iter()
+                .find(|r| r.kind == AnimChannelKind::Translation && r.exponent == exponent)
+        };
+
+        // The current divisor reproduces the values exactly
+        let current = translation(8).ok_or("Missing current divisor")?;
+        assert_eq!(current.bits_per_keyframe, Some(12));
+        assert_eq!(current.max_error, 0.0);
+
+        // Every value is a multiple of 1/16, so a coarser divisor stores them exactly in fewer bits
+        let coarser = translation(4).ok_or("Missing coarser divisor")?;
+        assert_eq!(coarser.bits_per_keyframe, Some(8));
+        assert_eq!(coarser.max_error, 0.0);
+
+        let finer = translation(12).ok_or("Missing finer divisor")?;
+        assert_eq!(finer.bits_per_keyframe, Some(16));
+
+        assert!(report.to_string().contains("translation exponent  8"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn anim_interpolated_sampling() -> Result<(), String> {
+        let walk = BoneAnimChannel {
+            translation: Some(
+                (1..=4)
+                    .map(|i| [i as f32 * 10.0, i as f32 * 2.0, 0.0])
+                    .collect(),
+            ),
+            rotation: None,
+            scale: None,
+        };
+
+        let anim = Anim::from_channels(std::slice::from_ref(&walk), 3.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        // Written channels are always raw
+        let mut descriptor = anim.descriptor().clone();
+        assert_eq!(
+            descriptor.pack_formats[0].translation,
+            Some(Vec3UsageType::all(AnimValueUsageType::Raw))
+        );
+
+        descriptor.pack_formats[0].translation =
+            Some(Vec3UsageType::all(AnimValueUsageType::Interpolated));
+
+        // Parsing reads them the same as raw channels
+        let interpolated =
+            <Anim as AssetLike>::new(&descriptor, &VirtualResource::from_slices(&[]))
+                .map_err(|e| e.to_string())?;
+        assert_close(
+            interpolated.get_bone_anim_channels()[0]
+                .translation
+                .as_deref()
+                .ok_or("Missing translation")?,
+            walk.translation.as_deref().ok_or("Missing translation")?,
+            1.0 / 256.0,
+        );
+
+        // Keyframes are a second apart, so 1.5s is half way between the second and third
+        let pose = interpolated.sample(1.5);
+        assert_close(
+            &[pose[0].translation.ok_or("Missing translation")?],
+            &[[25.0, 5.0, 0.0]],
+            1.0 / 256.0,
+        );
+        assert_eq!(interpolated.sample(100.0), interpolated.sample(3.0));
+        assert_eq!(interpolated.sample(f32::NAN), interpolated.sample(0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn anim_tail_kept() -> Result<(), String> {
+        let channel = |x: f32| BoneAnimChannel {
+            translation: Some(vec![[x, 0.0, 0.0], [x + 1.0, 0.0, 0.0]]),
+            rotation: None,
+            scale: None,
+        };
+
+        let anim = Anim::from_channels(&[channel(0.0)], 1.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        // Add a tail by hand, with some_ptr_2 pointing at its second word
+        let mut bytes = anim.descriptor().to_bytes().map_err(|e| e.to_string())?;
+        let tail_start = bytes.len() as u32;
+        bytes[0x20..0x24].copy_from_slice(&(tail_start + 4).to_le_bytes());
+        bytes[0x28..0x2c].copy_from_slice(&tail_start.to_le_bytes());
+        bytes.extend([1, 0, 0, 0, 0xaa, 0xbb, 0xcc, 0xdd]);
+
+        let descriptor = AnimDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(
+            descriptor.tail().map(AnimTail::words),
+            Some(vec![1, 0xddccbbaa])
+        );
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        let mut anim = <Anim as AssetLike>::new(&descriptor, &VirtualResource::from_slices(&[]))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(anim.keyframes().len(), 2);
+
+        // Re-quantising changes the size of the keyframes, which the tail pointers follow
+        let wider = BoneAnimChannel {
+            translation: Some(vec![[0.0, 0.0, 0.0], [100.0, 0.0, 0.0]]),
+            rotation: None,
+            scale: None,
+        };
+        anim.set_channel(0, wider).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(anim.tail(), descriptor.tail());
+
+        let edited = anim.descriptor().to_bytes().map_err(|e| e.to_string())?;
+        let new_start = edited.len() as u32 - 8;
+        assert_ne!(new_start, tail_start);
+        assert_eq!(edited[0x20..0x24], (new_start + 4).to_le_bytes());
+        assert_eq!(edited[0x28..0x2c], new_start.to_le_bytes());
+        assert_eq!(edited[new_start as usize..], bytes[tail_start as usize..]);
+
+        Ok(())
+    }
+
+    fn bone(name: &str, id: u16, local_translation: [f32; 3]) -> Bone {
+        Bone {
+            name: Some(name.to_string()),
+            parent_id: 0,
+            id,
+            local_translation,
+            global_translation: local_translation,
+            sentinel: [0; 4],
+        }
+    }
+
+    #[test]
+    fn anim_retarget_by_name() -> Result<(), String> {
+        let src_skeleton = [
+            bone("base", 0, [0.0, 0.0, 0.0]),
+            bone("arm", 1, [1.0, 0.0, 0.0]),
+        ];
+        let dst_skeleton = [
+            bone("base", 0, [0.0, 0.0, 0.0]),
+            bone("leg", 1, [0.0, -1.0, 0.0]),
+            bone("arm", 2, [2.0, 0.0, 0.0]),
+        ];
+
+        let still = BoneAnimChannel {
+            translation: Some(vec![[0.0, 0.0, 0.0]; 2]),
+            rotation: None,
+            scale: None,
+        };
+        let arm = BoneAnimChannel {
+            translation: Some(vec![[1.0, 0.0, 0.0], [1.0, 0.5, 0.0]]),
+            rotation: Some(vec![[0.0, 0.0, 0.0, 1.0]; 2]),
+            scale: None,
+        };
+
+        let anim = Anim::from_channels(&[still, arm], 1.0, test_precision())
+            .map_err(|e| format!("{:?}", e))?;
+
+        let retargeted = retarget(&anim, &src_skeleton, &dst_skeleton, &BoneMap::ByName)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let channels = retargeted.get_bone_anim_channels();
+        assert_eq!(channels.len(), 3);
+
+        // The leg has no match, and the arm is moved by the difference in rest poses
+        assert!(channels[1].translation.is_none() && channels[1].rotation.is_none());
+        assert_eq!(
+            channels[2].translation,
+            Some(vec![[2.0, 0.0, 0.0], [2.0, 0.5, 0.0]])
+        );
+        assert!(channels[2].rotation.is_some());
+
+        assert!(
+            retarget(
+                &anim,
+                &src_skeleton,
+                &dst_skeleton,
+                &BoneMap::Explicit(vec![(5, 0)])
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+}
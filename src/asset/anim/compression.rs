@@ -0,0 +1,343 @@
+/*!
+How well the keyframes of an animation are compressed, see [`AnimDescriptor::compression_report`].
+
+Each channel is stored as a short plus a delta per keyframe, with as many bits as its largest delta
+needs, so a finer divisor costs bits on every keyframe. The report re-quantises the decoded values
+at nearby divisors to show what each one would cost and how far the values would move.
+*/
+
+use std::fmt;
+
+use crate::asset::AssetParseError;
+
+use super::{AnimDescriptor, PartialTransform, PrecisionSpecifiers, QuantisedChannel};
+
+/// How many divisor exponents either side of the current one are tried when re-quantising
+pub const REQUANTISE_RANGE: u8 = 4;
+
+/// The largest divisor exponent, which is stored in 5 bits
+const MAX_EXPONENT: u8 = 31;
+
+/// Which of the three precision settings a channel is quantised with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimChannelKind {
+    Rotation,
+    Translation,
+    Scale,
+}
+
+impl AnimChannelKind {
+    const ALL: [AnimChannelKind; 3] = [
+        AnimChannelKind::Rotation,
+        AnimChannelKind::Translation,
+        AnimChannelKind::Scale,
+    ];
+
+    fn exponent(&self, precision: &PrecisionSpecifiers) -> u8 {
+        match self {
+            AnimChannelKind::Rotation => precision.quat_divisor,
+            AnimChannelKind::Translation => precision.pos_divisor,
+            AnimChannelKind::Scale => precision.scale_divisor,
+        }
+    }
+
+    /// The size of one quantisation step with the divisor set to `exponent`
+    fn step(&self, precision: &PrecisionSpecifiers, exponent: u8) -> f32 {
+        let mut precision = precision.clone();
+
+        match self {
+            AnimChannelKind::Rotation => {
+                precision.quat_divisor = exponent;
+                precision.quat_constant()
+            }
+            AnimChannelKind::Translation => {
+                precision.pos_divisor = exponent;
+                precision.pos_constant()
+            }
+            AnimChannelKind::Scale => {
+                precision.scale_divisor = exponent;
+                precision.scale_constant()
+            }
+        }
+    }
+}
+
+impl fmt::Display for AnimChannelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AnimChannelKind::Rotation => "rotation",
+            AnimChannelKind::Translation => "translation",
+            AnimChannelKind::Scale => "scale",
+        })
+    }
+}
+
+/// The precision of the channels of one bone
+#[derive(Debug, Clone, PartialEq)]
+pub struct BonePrecision {
+    pub bone: usize,
+    /// The number of stored channels
+    pub channels: usize,
+    /// The bits the bone takes up in each keyframe
+    pub bits_per_keyframe: usize,
+    /// The smallest change each kind of channel the bone has can store
+    pub steps: Vec<(AnimChannelKind, f32)>,
+}
+
+/// The result of quantising every channel of one kind with a different divisor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requantisation {
+    pub kind: AnimChannelKind,
+    /// The exponent stored in the [`PrecisionSpecifiers`]
+    pub exponent: u8,
+    pub step: f32,
+    /// The bits the channels would take up in each keyframe, or None if a value can't be stored
+    /// with this divisor
+    pub bits_per_keyframe: Option<usize>,
+    /// The largest difference between a decoded value and its re-quantised value
+    pub max_error: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionReport {
+    pub num_keyframes: usize,
+    /// The bytes each keyframe takes up
+    pub keyframe_size: usize,
+    /// The number of channels stored with each number of bits, indexed by the bit count
+    pub bits_histogram: [usize; 17],
+    pub bones: Vec<BonePrecision>,
+    /// The current bits per keyframe of each kind of channel, for comparing with
+    /// [`CompressionReport::requantised`]
+    pub current_bits: Vec<(AnimChannelKind, usize)>,
+    /// Every kind of channel the animation has, at each exponent within [`REQUANTISE_RANGE`] of
+    /// its current one
+    pub requantised: Vec<Requantisation>,
+}
+
+/// The decoded values of one stored channel, across every keyframe
+struct ChannelValues {
+    bone: usize,
+    kind: AnimChannelKind,
+    bits: u8,
+    values: Vec<f32>,
+}
+
+/// The values of a transform in the order their channels are stored
+fn stored_values(transform: &PartialTransform) -> Vec<(AnimChannelKind, f32)> {
+    let rotation = [transform.qx, transform.qy, transform.qz];
+    let translation = [transform.tx, transform.ty, transform.tz];
+    let scale = [transform.sx, transform.sy, transform.sz];
+
+    [
+        (AnimChannelKind::Rotation, rotation),
+        (AnimChannelKind::Translation, translation),
+        (AnimChannelKind::Scale, scale),
+    ]
+    .into_iter()
+    .flat_map(|(kind, values)| values.into_iter().flatten().map(move |v| (kind, v)))
+    .collect()
+}
+
+impl AnimDescriptor {
+    fn channel_values(&self) -> Result<Vec<ChannelValues>, AssetParseError> {
+        let keyframes = self.decode_keyframes()?;
+
+        let mut channels: Vec<ChannelValues> = vec![];
+
+        for keyframe in &keyframes {
+            let values = keyframe
+                .transforms()
+                .iter()
+                .enumerate()
+                .flat_map(|(bone, transform)| {
+                    stored_values(transform)
+                        .into_iter()
+                        .map(move |(kind, v)| (bone, kind, v))
+                });
+
+            for (i, (bone, kind, value)) in values.enumerate() {
+                match channels.get_mut(i) {
+                    Some(channel) => channel.values.push(value),
+                    None => channels.push(ChannelValues {
+                        bone,
+                        kind,
+                        bits: self.bits_per_channel.get(i).copied().unwrap_or(0),
+                        values: vec![value],
+                    }),
+                }
+            }
+        }
+
+        Ok(channels)
+    }
+
+    /**
+    Summarises how the keyframes are compressed: how many bits each channel uses, how precise each
+    bone is, and what re-quantising each kind of channel with a nearby divisor would cost in bits
+    and error.
+
+    # Errors
+    - [`AssetParseError::ErrorParsingDescriptor`] if the keyframes can't be decoded
+    */
+    pub fn compression_report(&self) -> Result<CompressionReport, AssetParseError> {
+        let channels = self.channel_values()?;
+        let precision = &self.precision_specifiers;
+
+        let mut bits_histogram = [0; 17];
+        for channel in &channels {
+            bits_histogram[(channel.bits as usize).min(16)] += 1;
+        }
+
+        let mut bones: Vec<BonePrecision> = vec![];
+
+        for channel in &channels {
+            if bones.last().is_none_or(|bone| bone.bone != channel.bone) {
+                bones.push(BonePrecision {
+                    bone: channel.bone,
+                    channels: 0,
+                    bits_per_keyframe: 0,
+                    steps: vec![],
+                });
+            }
+
+            let Some(bone) = bones.last_mut() else {
+                continue;
+            };
+
+            bone.channels += 1;
+            bone.bits_per_keyframe += channel.bits as usize;
+
+            if !bone.steps.iter().any(|(kind, _)| *kind == channel.kind) {
+                let exponent = channel.kind.exponent(precision);
+                bone.steps
+                    .push((channel.kind, channel.kind.step(precision, exponent)));
+            }
+        }
+
+        let mut current_bits = vec![];
+        let mut requantised = vec![];
+
+        for kind in AnimChannelKind::ALL {
+            let of_kind: Vec<&ChannelValues> = channels
+                .iter()
+                .filter(|channel| channel.kind == kind)
+                .collect();
+
+            if of_kind.is_empty() {
+                continue;
+            }
+
+            current_bits.push((
+                kind,
+                of_kind.iter().map(|channel| channel.bits as usize).sum(),
+            ));
+
+            let current = kind.exponent(precision);
+            let lowest = current.saturating_sub(REQUANTISE_RANGE);
+            let highest = current.saturating_add(REQUANTISE_RANGE).min(MAX_EXPONENT);
+
+            for exponent in lowest..=highest {
+                let step = kind.step(precision, exponent);
+
+                let mut bits_per_keyframe = Some(0);
+                let mut max_error: f32 = 0.0;
+
+                for channel in &of_kind {
+                    let Ok(quantised) = QuantisedChannel::new(&channel.values, step) else {
+                        bits_per_keyframe = None;
+                        continue;
+                    };
+
+                    bits_per_keyframe =
+                        bits_per_keyframe.map(|bits| bits + quantised.bits as usize);
+
+                    for (value, delta) in channel.values.iter().zip(&quantised.deltas) {
+                        let requantised = (quantised.short as f32 + *delta as f32) * step;
+                        max_error = max_error.max((requantised - value).abs());
+                    }
+                }
+
+                requantised.push(Requantisation {
+                    kind,
+                    exponent,
+                    step,
+                    bits_per_keyframe,
+                    max_error,
+                });
+            }
+        }
+
+        Ok(CompressionReport {
+            num_keyframes: channels.first().map_or(0, |channel| channel.values.len()),
+            keyframe_size: self.keyframe_size as usize,
+            bits_histogram,
+            bones,
+            current_bits,
+            requantised,
+        })
+    }
+}
+
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} keyframes of {} bytes",
+            self.num_keyframes, self.keyframe_size
+        )?;
+
+        writeln!(f, "Channels by bit count:")?;
+        for (bits, count) in self.bits_histogram.iter().enumerate() {
+            if *count > 0 {
+                writeln!(f, "  {:>2} bits: {}", bits, count)?;
+            }
+        }
+
+        writeln!(f, "Bones:")?;
+        for bone in &self.bones {
+            let steps: Vec<String> = bone
+                .steps
+                .iter()
+                .map(|(kind, step)| format!("{} step {}", kind, step))
+                .collect();
+
+            writeln!(
+                f,
+                "  {:>3}: {} channels, {} bits per keyframe, {}",
+                bone.bone,
+                bone.channels,
+                bone.bits_per_keyframe,
+                steps.join(", ")
+            )?;
+        }
+
+        writeln!(f, "Re-quantised:")?;
+        for requantised in &self.requantised {
+            let current = self
+                .current_bits
+                .iter()
+                .find(|(kind, _)| *kind == requantised.kind)
+                .map_or(0, |(_, bits)| *bits);
+
+            match requantised.bits_per_keyframe {
+                Some(bits) => writeln!(
+                    f,
+                    "  {} exponent {:>2} (step {}): {} bits per keyframe ({:+}), max error {}",
+                    requantised.kind,
+                    requantised.exponent,
+                    requantised.step,
+                    bits,
+                    bits as isize - current as isize,
+                    requantised.max_error
+                )?,
+                None => writeln!(
+                    f,
+                    "  {} exponent {:>2} (step {}): values can't be stored",
+                    requantised.kind, requantised.exponent, requantised.step
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
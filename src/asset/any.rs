@@ -0,0 +1,70 @@
+/*!
+[`AnyAsset`], for reading assets without knowing their type up front, see
+[`BNLFile::parse_any`](crate::BNLFile::parse_any).
+*/
+
+use crate::{
+    AssetMetadata, RawAsset,
+    asset::{
+        Asset, AssetError, AssetType, aidlist::AidList, anim::Anim, cuelist::CueList,
+        cutscene::Cutscene, font::Font, model::Model, opaque::Opaque, script::Script,
+        texture::Texture,
+    },
+};
+
+/// An asset parsed as the type matching its [`AssetType`]
+pub enum AnyAsset {
+    Texture(Asset<Texture>),
+    Model(Asset<Model>),
+    Anim(Asset<Anim>),
+    Script(Asset<Script>),
+    AidList(Asset<AidList>),
+    CueList(Asset<CueList>),
+    Font(Asset<Font>),
+    Cutscene(Asset<Cutscene>),
+    /// An asset whose type doesn't have a parser yet, kept as its verbatim bytes
+    Other(Asset<Opaque>),
+}
+
+impl AnyAsset {
+    pub fn metadata(&self) -> &AssetMetadata {
+        match self {
+            AnyAsset::Texture(asset) => asset.metadata(),
+            AnyAsset::Model(asset) => asset.metadata(),
+            AnyAsset::Anim(asset) => asset.metadata(),
+            AnyAsset::Script(asset) => asset.metadata(),
+            AnyAsset::AidList(asset) => asset.metadata(),
+            AnyAsset::CueList(asset) => asset.metadata(),
+            AnyAsset::Font(asset) => asset.metadata(),
+            AnyAsset::Cutscene(asset) => asset.metadata(),
+            AnyAsset::Other(asset) => asset.metadata(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.metadata().name()
+    }
+
+    pub fn asset_type(&self) -> AssetType {
+        self.metadata().asset_type()
+    }
+
+    /// Whether the asset was parsed as anything other than [`AnyAsset::Other`]
+    pub fn is_parsed(&self) -> bool {
+        !matches!(self, AnyAsset::Other(_))
+    }
+
+    pub fn to_raw_asset(self) -> Result<RawAsset, AssetError> {
+        match self {
+            AnyAsset::Texture(asset) => asset.to_raw_asset(),
+            AnyAsset::Model(asset) => asset.to_raw_asset(),
+            AnyAsset::Anim(asset) => asset.to_raw_asset(),
+            AnyAsset::Script(asset) => asset.to_raw_asset(),
+            AnyAsset::AidList(asset) => asset.to_raw_asset(),
+            AnyAsset::CueList(asset) => asset.to_raw_asset(),
+            AnyAsset::Font(asset) => asset.to_raw_asset(),
+            AnyAsset::Cutscene(asset) => asset.to_raw_asset(),
+            AnyAsset::Other(asset) => asset.to_raw_asset(),
+        }
+    }
+}
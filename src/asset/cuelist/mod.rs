@@ -1,6 +1,19 @@
+/*!
+Cue lists, which name cues in groups.
+
+The descriptor is UTF-8 text with one cue per line, as its group and name separated by a tab, such
+as `"G1\tcue_a\nG1\tcue_b\nG2\tcue_c"`. Blank lines are skipped, and consecutive lines with the
+same group make up one [`CueGroup`]. The text may be followed by NUL terminators, which are kept
+when the cues are rewritten.
+
+Only this text layout has been seen in the archives so far. No binary layout is known, so a
+descriptor which isn't text is read as [`CueListLayout::Unknown`] and kept as it is rather than
+guessed at. Text which doesn't split into two columns is rejected.
+*/
+
 use crate::{
     VirtualResource,
-    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType},
+    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType, OriginalBytes},
 };
 
 #[derive(Debug, Clone)]
@@ -9,7 +22,7 @@ pub struct CueList {
     data: Vec<Vec<u8>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CueGroup {
     name: String,
     cues: Vec<String>,
@@ -24,9 +37,22 @@ impl CueGroup {
     }
 }
 
-#[derive(Debug, Clone)]
+/// How a cue list descriptor is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CueListLayout {
+    /// Tab separated text, as described in the [module docs](self)
+    Text,
+    /// Anything which isn't valid UTF-8, or has control characters other than tabs, line breaks
+    /// and NUL terminators. These have no groups, and are written back unchanged.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CueListDescriptor {
     groups: Vec<CueGroup>,
+    layout: CueListLayout,
+    /// Written back as they are while the cues are unchanged, keeping line endings and blank lines
+    original_bytes: OriginalBytes,
 }
 
 /// Example
@@ -41,6 +67,11 @@ impl CueListDescriptor {
             .map(|group| (group.name.clone(), s))
     }
 
+    /// The layout the descriptor was read from
+    pub fn layout(&self) -> CueListLayout {
+        self.layout
+    }
+
     pub fn validate(&self) -> bool {
         self.groups
             .iter()
@@ -110,9 +141,37 @@ impl CueListDescriptor {
     }
 }
 
+/// `data` without the NUL terminators at its end
+fn trim_terminators(data: &[u8]) -> &[u8] {
+    let len = data
+        .iter()
+        .rposition(|&b| b != 0)
+        .map_or(0, |last| last + 1);
+    &data[..len]
+}
+
+/// Whether `data` is text which could be a cue list, rather than some other layout. NUL
+/// terminators at the end don't count.
+fn is_text(data: &[u8]) -> bool {
+    let data = trim_terminators(data);
+
+    std::str::from_utf8(data).is_ok_and(|s| {
+        !s.chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\r' | '\n'))
+    })
+}
+
 impl AssetDescriptor for CueListDescriptor {
     fn from_bytes(data: &[u8]) -> Result<Self, AssetParseError> {
-        let s = String::from_utf8(data.to_owned())
+        if !is_text(data) {
+            return Ok(CueListDescriptor {
+                groups: vec![],
+                layout: CueListLayout::Unknown,
+                original_bytes: OriginalBytes::new(data),
+            });
+        }
+
+        let s = String::from_utf8(trim_terminators(data).to_owned())
             .map_err(|_| AssetParseError::ErrorParsingDescriptor)?;
 
         let lines: Vec<(String, String)> = s
@@ -130,7 +189,11 @@ impl AssetDescriptor for CueListDescriptor {
             })
             .collect::<Result<Vec<(String, String)>, AssetParseError>>()?;
 
-        let mut descriptor = CueListDescriptor { groups: vec![] };
+        let mut descriptor = CueListDescriptor {
+            groups: vec![],
+            layout: CueListLayout::Text,
+            original_bytes: OriginalBytes::new(data),
+        };
 
         let mut group = CueGroup {
             name: "".to_string(),
@@ -149,11 +212,23 @@ impl AssetDescriptor for CueListDescriptor {
             group.cues.push(entry)
         }
 
+        if !group.cues.is_empty() {
+            descriptor.groups.push(group);
+        }
+
         Ok(descriptor)
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
-        // let mut bytes = Vec::new();
+        if self.layout == CueListLayout::Unknown {
+            return Ok(self.original_bytes.as_bytes().to_vec());
+        }
+
+        if !self.original_bytes.is_empty()
+            && Self::from_bytes(self.original_bytes.as_bytes()).is_ok_and(|read| read == *self)
+        {
+            return Ok(self.original_bytes.as_bytes().to_vec());
+        }
 
         let mut lines = vec![];
 
@@ -169,7 +244,16 @@ impl AssetDescriptor for CueListDescriptor {
             }
         }
 
-        Ok(lines.join("\n").chars().map(|c| c as u8).collect())
+        let mut bytes: Vec<u8> = lines.join("\n").chars().map(|c| c as u8).collect();
+
+        // Keep the terminators the original text had
+        let original = self.original_bytes.as_bytes();
+        bytes.resize(
+            bytes.len() + original.len() - trim_terminators(original).len(),
+            0,
+        );
+
+        Ok(bytes)
     }
 
     fn asset_type() -> AssetType {
@@ -177,7 +261,11 @@ impl AssetDescriptor for CueListDescriptor {
     }
 
     fn size(&self) -> usize {
-        self.to_bytes().iter().len()
+        self.to_bytes().map_or(0, |bytes| bytes.len())
+    }
+
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(self.original_bytes.as_bytes())
     }
 }
 
@@ -215,6 +303,99 @@ pub mod tests {
     use super::*;
 
     use ntest::timeout;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn cue_list_descriptor_roundtrip(
+            // Adjacent groups with the same name would be merged, so each name is only used once
+            groups in prop::collection::btree_map(
+                "[A-Za-z0-9_]{1,16}",
+                prop::collection::vec("[A-Za-z0-9_ ]{1,16}", 1..8),
+                1..8,
+            )
+        ) {
+            let descriptor = CueListDescriptor {
+                groups: groups
+                    .into_iter()
+                    .map(|(name, cues)| CueGroup::new(name, Some(cues)))
+                    .collect(),
+                layout: CueListLayout::Text,
+                original_bytes: OriginalBytes::default(),
+            };
+
+            let bytes = descriptor.to_bytes().expect("Valid cue lists can be written");
+
+            prop_assert_eq!(CueListDescriptor::from_bytes(&bytes).ok(), Some(descriptor));
+        }
+    }
+
+    #[test]
+    fn cue_list_original_bytes() -> Result<(), String> {
+        let bytes = b"G1\ta\r\nG1\tb\r\n\r\nG2\tc\n";
+
+        let mut descriptor = CueListDescriptor::from_bytes(bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+        assert_eq!(descriptor.size(), bytes.len());
+
+        descriptor.groups[0].cues[0] = "z".to_string();
+        assert_eq!(
+            descriptor.to_bytes().map_err(|e| e.to_string())?,
+            b"G1\tz\nG1\tb\nG2\tc"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cue_list_unknown_layout() -> Result<(), String> {
+        let bytes = [0x02, 0x00, 0x00, 0x00, 0x10, 0x00, 0xff, 0xfe];
+
+        let descriptor = CueListDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.layout(), CueListLayout::Unknown);
+        assert_eq!(descriptor.cues().count(), 0);
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        let text = CueListDescriptor::from_bytes(b"G1\ta").map_err(|e| e.to_string())?;
+        assert_eq!(text.layout(), CueListLayout::Text);
+
+        // Text which doesn't split into columns is still an error
+        assert!(CueListDescriptor::from_bytes(b"G1 a").is_err());
+
+        // NULs only make it unknown when they aren't terminators
+        assert_eq!(
+            CueListDescriptor::from_bytes(b"G1\t\0a")
+                .map_err(|e| e.to_string())?
+                .layout(),
+            CueListLayout::Unknown
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn cue_list_nul_terminated() -> Result<(), String> {
+        let bytes = b"G1\ta\nG2\tb\0\0";
+
+        let mut descriptor = CueListDescriptor::from_bytes(bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.layout(), CueListLayout::Text);
+        assert_eq!(
+            descriptor
+                .cues()
+                .map(|(_, cue)| cue.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        descriptor.groups[1].cues[0] = "c".to_string();
+        assert_eq!(
+            descriptor.to_bytes().map_err(|e| e.to_string())?,
+            b"G1\ta\nG2\tc\0\0"
+        );
+
+        Ok(())
+    }
 
     #[test]
     #[timeout(1000)] // Make sure test runs in under 1 second
@@ -247,7 +428,11 @@ pub mod tests {
             })
             .collect();
 
-        let cue_list_descriptor = CueListDescriptor { groups };
+        let cue_list_descriptor = CueListDescriptor {
+            groups,
+            layout: CueListLayout::Text,
+            original_bytes: OriginalBytes::default(),
+        };
 
         assert_eq!(
             cue_list_descriptor
@@ -1,13 +1,32 @@
-use std::io::Read;
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "fs")]
+use crate::asset::Dump;
+use crate::{
+    asset::{
+        OriginalBytes,
+        fields::{DescriptorField, DescriptorFields, FieldValue},
+    },
+    utils::reader::Le,
+};
+
+/// The size of the fixed part of a [`CutsceneDescriptor`], before [`CutsceneDescriptor::rest_raw`]
+pub const CUTSCENE_HEADER_SIZE: usize = 8;
+
+/// The shortest run of printable characters which will be identified as an asset reference
+const MIN_ASSET_REF_LEN: usize = 5;
 
 #[derive(Debug, Clone)]
 pub struct Cutscene {
     pub descriptor: CutsceneDescriptor,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CutsceneDescriptor {
     pub count_1: u8,
     pub count_2: u8,
@@ -15,21 +34,15 @@ pub struct CutsceneDescriptor {
     pub num_animations: u8,
     pub length: f32,
     pub rest_raw: Vec<u8>,
+    original_bytes: OriginalBytes,
 }
 
 impl super::AssetDescriptor for CutsceneDescriptor {
     fn from_bytes(data: &[u8]) -> Result<Self, super::AssetParseError> {
-        let mut cur = std::io::Cursor::new(&data);
-        let count_1 = cur.read_u8()?;
-        let count_2 = cur.read_u8()?;
-        let num_cameras = cur.read_u8()?;
-        let num_animations = cur.read_u8()?;
-
-        let length = cur.read_f32::<LittleEndian>()?;
-
-        let mut raw = vec![];
+        let mut reader = Le::new(data);
+        let [count_1, count_2, num_cameras, num_animations] = reader.array::<u8, 4>()?;
 
-        cur.read_to_end(&mut raw)?;
+        let length = reader.f32()?;
 
         Ok(CutsceneDescriptor {
             count_1,
@@ -37,7 +50,8 @@ impl super::AssetDescriptor for CutsceneDescriptor {
             num_cameras,
             num_animations,
             length,
-            rest_raw: raw,
+            rest_raw: reader.remaining().to_vec(),
+            original_bytes: OriginalBytes::new(data),
         })
     }
 
@@ -56,12 +70,161 @@ impl super::AssetDescriptor for CutsceneDescriptor {
     }
 
     fn size(&self) -> usize {
-        8 + self.rest_raw.len()
+        CUTSCENE_HEADER_SIZE + self.rest_raw.len()
     }
 
     fn asset_type() -> super::AssetType {
         super::AssetType::ResCutscene
     }
+
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(self.original_bytes.as_bytes())
+    }
+}
+
+/// What a [`CutsceneSection`] is believed to contain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CutsceneSectionKind {
+    /// The counts and length at the start of the descriptor
+    Header,
+    /// A null terminated asset id (eg. the camera or animations played by the cutscene), as used
+    /// by `PlayWalkinCutscene`
+    AssetRef(String),
+    /// Data which hasn't been identified yet
+    Unknown,
+}
+
+/// A region of a cutscene descriptor, at an offset relative to the start of the descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutsceneSection {
+    pub kind: CutsceneSectionKind,
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl CutsceneDescriptor {
+    /**
+    Splits the descriptor into its identified sections. The event and track layout of cutscenes is
+    not known yet, so only the header and any embedded asset ids are identified, with everything
+    in between being returned as [`CutsceneSectionKind::Unknown`].
+
+    The sections cover the whole descriptor, in order, without overlapping.
+    */
+    pub fn sections(&self) -> Vec<CutsceneSection> {
+        let mut header = vec![
+            self.count_1,
+            self.count_2,
+            self.num_cameras,
+            self.num_animations,
+        ];
+        header.extend(self.length.to_le_bytes());
+
+        let mut sections = vec![CutsceneSection {
+            kind: CutsceneSectionKind::Header,
+            offset: 0,
+            bytes: header,
+        }];
+
+        let rest = &self.rest_raw;
+        let mut unknown_start = 0;
+        let mut i = 0;
+
+        while i < rest.len() {
+            let Some(name) = asset_ref_at(&rest[i..]) else {
+                i += 1;
+                continue;
+            };
+
+            if unknown_start < i {
+                sections.push(CutsceneSection {
+                    kind: CutsceneSectionKind::Unknown,
+                    offset: CUTSCENE_HEADER_SIZE + unknown_start,
+                    bytes: rest[unknown_start..i].to_vec(),
+                });
+            }
+
+            // Include the null terminator in the section
+            let end = i + name.len() + 1;
+
+            sections.push(CutsceneSection {
+                kind: CutsceneSectionKind::AssetRef(name),
+                offset: CUTSCENE_HEADER_SIZE + i,
+                bytes: rest[i..end].to_vec(),
+            });
+
+            i = end;
+            unknown_start = end;
+        }
+
+        if unknown_start < rest.len() {
+            sections.push(CutsceneSection {
+                kind: CutsceneSectionKind::Unknown,
+                offset: CUTSCENE_HEADER_SIZE + unknown_start,
+                bytes: rest[unknown_start..].to_vec(),
+            });
+        }
+
+        sections
+    }
+
+    /// The asset ids referenced by the cutscene, in the order they appear
+    pub fn referenced_assets(&self) -> Vec<String> {
+        self.sections()
+            .into_iter()
+            .filter_map(|section| match section.kind {
+                CutsceneSectionKind::AssetRef(name) => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Returns the asset id starting at the beginning of `bytes`, if there is one
+fn asset_ref_at(bytes: &[u8]) -> Option<String> {
+    if !bytes.starts_with(b"aid_") {
+        return None;
+    }
+
+    let len = bytes.iter().position(|&b| b == 0)?;
+    let name = &bytes[..len];
+
+    if len < MIN_ASSET_REF_LEN || !name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+impl DescriptorFields for CutsceneDescriptor {
+    fn fields(&self) -> Vec<DescriptorField> {
+        let mut fields = vec![
+            DescriptorField::new("count_1", 0x0, FieldValue::U8(self.count_1)),
+            DescriptorField::new("count_2", 0x1, FieldValue::U8(self.count_2)),
+            DescriptorField::new("num_cameras", 0x2, FieldValue::U8(self.num_cameras)),
+            DescriptorField::new("num_animations", 0x3, FieldValue::U8(self.num_animations)),
+            DescriptorField::new("length", 0x4, FieldValue::F32(self.length)),
+        ];
+
+        fields.extend(
+            self.sections()
+                .into_iter()
+                .filter_map(|section| match section.kind {
+                    CutsceneSectionKind::Header => None,
+                    CutsceneSectionKind::AssetRef(name) => Some(DescriptorField::new(
+                        format!("asset_ref ({})", name),
+                        section.offset,
+                        FieldValue::Bytes(section.bytes),
+                    )),
+                    CutsceneSectionKind::Unknown => Some(DescriptorField::unknown(
+                        format!("unknown_{:x}", section.offset),
+                        section.offset,
+                        FieldValue::Bytes(section.bytes),
+                    )),
+                }),
+        );
+
+        fields
+    }
 }
 
 impl super::AssetLike for Cutscene {
@@ -84,6 +247,46 @@ impl super::AssetLike for Cutscene {
     }
 }
 
+#[cfg(feature = "fs")]
+impl Dump for Cutscene {
+    /// Writes an annotated hexdump of the descriptor, one identified section at a time
+    fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
+        let file = File::create(dump_path)?;
+        let w = &mut BufWriter::new(file);
+
+        let desc = &self.descriptor;
+
+        writeln!(w, "length: {}", desc.length)?;
+        writeln!(w, "count_1: {}", desc.count_1)?;
+        writeln!(w, "count_2: {}", desc.count_2)?;
+        writeln!(w, "num_cameras: {}", desc.num_cameras)?;
+        writeln!(w, "num_animations: {}", desc.num_animations)?;
+
+        for section in desc.sections() {
+            let label = match &section.kind {
+                CutsceneSectionKind::Header => "header".to_string(),
+                CutsceneSectionKind::AssetRef(name) => format!("asset ref {}", name),
+                CutsceneSectionKind::Unknown => "unknown".to_string(),
+            };
+
+            writeln!(
+                w,
+                "\n[{:#06x}..{:#06x}] {}",
+                section.offset,
+                section.offset + section.bytes.len(),
+                label
+            )?;
+
+            for (i, row) in section.bytes.chunks(16).enumerate() {
+                let hex: Vec<String> = row.iter().map(|b| format!("{:02x}", b)).collect();
+                writeln!(w, "  {:#06x}  {}", section.offset + i * 16, hex.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CutsceneMod {
     pub length: Option<f32>,
@@ -100,3 +303,48 @@ impl crate::modding::ModLike for CutsceneMod {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::AssetDescriptor;
+
+    #[test]
+    fn cutscene_sections() -> Result<(), String> {
+        let mut bytes = vec![1, 2, 1, 1];
+        bytes.extend(2.5f32.to_le_bytes());
+        bytes.extend([0xaa, 0xbb, 0xcc, 0xdd]);
+        bytes.extend(b"aid_camera_intro\0");
+        bytes.extend([0x11, 0x22]);
+        bytes.extend(b"aid_anim_walk\0");
+
+        let desc = CutsceneDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(desc.to_bytes().map_err(|e| e.to_string())?, bytes);
+        assert_eq!(desc.original_bytes(), Some(&bytes[..]));
+
+        let sections = desc.sections();
+
+        // The sections must cover the whole descriptor
+        assert_eq!(
+            sections
+                .iter()
+                .flat_map(|s| s.bytes.clone())
+                .collect::<Vec<u8>>(),
+            bytes
+        );
+
+        for pair in sections.windows(2) {
+            assert_eq!(pair[0].offset + pair[0].bytes.len(), pair[1].offset);
+        }
+
+        assert_eq!(
+            desc.referenced_assets(),
+            vec!["aid_camera_intro".to_string(), "aid_anim_walk".to_string()]
+        );
+        assert_eq!(sections[1].kind, CutsceneSectionKind::Unknown);
+        assert_eq!(sections[2].offset, 0xc);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// The value of a single field of a descriptor, as stored in the file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    F32(f32),
+    Bytes(Vec<u8>),
+}
+
+impl FieldValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FieldValue::U8(_) => "u8",
+            FieldValue::U16(_) => "u16",
+            FieldValue::U32(_) => "u32",
+            FieldValue::F32(_) => "f32",
+            FieldValue::Bytes(_) => "bytes",
+        }
+    }
+
+    /// The number of bytes the field occupies in the descriptor
+    pub fn size(&self) -> usize {
+        match self {
+            FieldValue::U8(_) => 1,
+            FieldValue::U16(_) => 2,
+            FieldValue::U32(_) | FieldValue::F32(_) => 4,
+            FieldValue::Bytes(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::U8(v) => write!(f, "{:#04x} ({})", v, v),
+            FieldValue::U16(v) => write!(f, "{:#06x} ({})", v, v),
+            FieldValue::U32(v) => write!(f, "{:#010x} ({})", v, v),
+            FieldValue::F32(v) => write!(f, "{}", v),
+            FieldValue::Bytes(bytes) => bytes.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+        }
+    }
+}
+
+/// A named field of a descriptor, at an offset relative to the start of the structure it belongs
+/// to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DescriptorField {
+    pub name: String,
+    pub offset: usize,
+    pub value: FieldValue,
+    /// Whether the purpose of the field hasn't been figured out yet
+    pub unknown: bool,
+}
+
+impl DescriptorField {
+    pub fn new<S: Into<String>>(name: S, offset: usize, value: FieldValue) -> Self {
+        Self {
+            name: name.into(),
+            offset,
+            value,
+            unknown: false,
+        }
+    }
+
+    /// A field whose purpose hasn't been figured out yet
+    pub fn unknown<S: Into<String>>(name: S, offset: usize, value: FieldValue) -> Self {
+        Self {
+            unknown: true,
+            ..Self::new(name, offset, value)
+        }
+    }
+
+    pub fn field_type(&self) -> &'static str {
+        self.value.type_name()
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.unknown
+    }
+
+    /// The field moved `offset` bytes further in, with `prefix` added to its name, for including
+    /// the fields of a structure in those of the one containing it
+    pub fn nested(self, prefix: &str, offset: usize) -> Self {
+        Self {
+            name: format!("{}{}", prefix, self.name),
+            offset: self.offset + offset,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for DescriptorField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#06x}  {:<5}  {:<24}  {}",
+            self.offset,
+            self.field_type(),
+            self.name,
+            self.value
+        )
+    }
+}
+
+/**
+Describes the raw layout of a descriptor (or a structure inside of one) as an ordered list of
+fields, including the ones whose purpose is still unknown.
+
+This allows tools to show an annotated view of any descriptor without knowing its type.
+*/
+pub trait DescriptorFields {
+    fn fields(&self) -> Vec<DescriptorField>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::{
+        AssetDescriptor,
+        model::sub_main::{MESH_BOUNDS_OFFSET, ModelSubresource},
+        texture::TextureDescriptor,
+    };
+
+    #[test]
+    fn texture_descriptor_fields() -> Result<(), String> {
+        let bytes = include_bytes!("test_data/texture0_descriptor");
+        let desc = TextureDescriptor::from_bytes(bytes).map_err(|e| e.to_string())?;
+
+        let fields = desc.fields();
+
+        // Fields must be ordered and must not overlap
+        for pair in fields.windows(2) {
+            assert!(pair[0].offset + pair[0].value.size() <= pair[1].offset);
+        }
+
+        let width = fields
+            .iter()
+            .find(|field| field.name == "width")
+            .ok_or("No width field")?;
+
+        assert_eq!(width.offset, 0x8);
+        assert_eq!(width.value, FieldValue::U16(desc.width()));
+        assert!(!width.is_unknown());
+
+        Ok(())
+    }
+
+    #[test]
+    fn model_subresource_fields() -> Result<(), String> {
+        let subresource = ModelSubresource {
+            unknown1: 1,
+            unknown2: 2,
+            primitive_ptrs_start: 0x28,
+            primitive_count: 1,
+            key_values_ptr: 0,
+            unknown3: 3,
+            floats: [4.0, 1.0, 2.0, 3.0],
+            primitives: vec![],
+            key_value_map: Default::default(),
+        };
+
+        let fields: Vec<_> = subresource
+            .fields()
+            .into_iter()
+            .map(|field| field.nested("model_subresource.", 0x100))
+            .collect();
+
+        for pair in fields.windows(2) {
+            assert!(pair[0].offset + pair[0].value.size() <= pair[1].offset);
+        }
+
+        let unknown: Vec<_> = fields
+            .iter()
+            .filter(|field| field.is_unknown())
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(
+            unknown,
+            vec![
+                "model_subresource.unknown1",
+                "model_subresource.unknown2",
+                "model_subresource.unknown3"
+            ]
+        );
+
+        let radius = fields
+            .iter()
+            .find(|field| field.name == "model_subresource.bounds_radius")
+            .ok_or("No bounds_radius field")?;
+        assert_eq!(radius.offset, 0x100 + MESH_BOUNDS_OFFSET);
+        assert_eq!(radius.value, FieldValue::F32(4.0));
+
+        Ok(())
+    }
+}
@@ -1,8 +1,29 @@
-use std::io::{Seek as _, SeekFrom};
+/*!
+Fonts, which store a small texture for each glyph rather than sharing texture pages.
 
-use byteorder::{LittleEndian, ReadBytesExt as _};
+The descriptor starts with a header giving the range of glyphs and a pointer to the glyph table.
+Each entry of the table points to a texture descriptor elsewhere in the asset descriptor, which
+gives the size and format of the glyph and the offset of its image in the resource. A glyph has one
+image for each variant of the font, stored one after another from that offset.
 
-#[derive(Debug, Clone)]
+See [`bmfont`] for converting fonts to and from a format that font tools can edit.
+*/
+
+pub mod bmfont;
+
+use std::io::{Cursor, Write as _};
+
+use byteorder::{LittleEndian, WriteBytesExt as _};
+
+use super::{AssetDescriptor as _, OriginalBytes};
+use crate::utils::reader::Le;
+
+/// The size of the header at the start of a font descriptor
+const FONT_HEADER_SIZE: usize = 0x18;
+/// The size of each entry of the glyph table
+const GLYPH_ENTRY_SIZE: usize = 0x18;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RawFontDescriptor {
     pub start_glyph: u32,
     pub end_glyph: u32,
@@ -14,7 +35,7 @@ pub struct RawFontDescriptor {
     pub entries_start_ptr: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RawGlyph {
     // Not part of original struct
     pub glyph_index: u32,
@@ -27,12 +48,14 @@ pub struct RawGlyph {
     pub unknown_u32_4: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FontDescriptor {
     pub num_variants: u32,
     pub text_x: u32,
     pub text_y: u32,
     pub glyphs: Vec<RawGlyph>,
+    /// The glyph table keeps whatever is in the entries of missing glyphs
+    original_bytes: OriginalBytes,
 }
 
 impl FontDescriptor {
@@ -51,6 +74,36 @@ impl FontDescriptor {
             .map(|glyph| glyph.glyph_index)
             .unwrap_or(0)
     }
+
+    /// The number of entries in the glyph table, which covers every index from the first glyph to
+    /// the last
+    fn entry_count(&self) -> usize {
+        (self.last_glyph() - self.first_glyph()) as usize + 1
+    }
+
+    /// The entry of `glyph_index` in the bytes the descriptor was read from, if it had no glyph
+    fn original_empty_entry(&self, glyph_index: u32) -> Option<&[u8]> {
+        let original = Le::new(self.original_bytes.as_bytes());
+
+        let [start_glyph, end_glyph] = original.at(0).array::<u32, 2>().ok()?;
+        let entries_start = original.at(0x14).u32().ok()?;
+
+        if !(start_glyph..=end_glyph).contains(&glyph_index) {
+            return None;
+        }
+
+        let mut entry = original
+            .at(entries_start as usize + (glyph_index - start_glyph) as usize * GLYPH_ENTRY_SIZE);
+        let entry_bytes = entry.remaining().get(..GLYPH_ENTRY_SIZE)?;
+        let [tex_ptr, ptr_2] = entry.array::<u32, 2>().ok()?;
+
+        is_empty_entry(tex_ptr, ptr_2).then_some(entry_bytes)
+    }
+}
+
+/// Whether a glyph table entry has no glyph
+fn is_empty_entry(tex_ptr: u32, ptr_2: u32) -> bool {
+    tex_ptr == 0 || ptr_2 == 0xffffffff
 }
 
 // if (entriesStart != 0) {
@@ -59,40 +112,53 @@ impl FontDescriptor {
 
 impl super::AssetDescriptor for FontDescriptor {
     fn from_bytes(data: &[u8]) -> Result<Self, super::AssetParseError> {
-        let mut cur = std::io::Cursor::new(data);
+        let mut reader = Le::new(data);
+
+        let [
+            start_glyph,
+            end_glyph,
+            num_variants,
+            text_x,
+            text_y,
+            entries_start_ptr,
+        ] = reader.array::<u32, 6>()?;
 
         let raw_descriptor = RawFontDescriptor {
-            start_glyph: cur.read_u32::<LittleEndian>()?,
-            end_glyph: cur.read_u32::<LittleEndian>()?,
-            num_variants: cur.read_u32::<LittleEndian>()?,
-            text_x: cur.read_u32::<LittleEndian>()?,
-            text_y: cur.read_u32::<LittleEndian>()?,
-            entries_start_ptr: cur.read_u32::<LittleEndian>()?,
+            start_glyph,
+            end_glyph,
+            num_variants,
+            text_x,
+            text_y,
+            entries_start_ptr,
         };
 
-        let num_glyphs = raw_descriptor.end_glyph - raw_descriptor.start_glyph + 1;
+        let num_glyphs = raw_descriptor
+            .end_glyph
+            .checked_sub(raw_descriptor.start_glyph)
+            .and_then(|diff| diff.checked_add(1))
+            .ok_or(super::AssetParseError::ErrorParsingDescriptor)?;
 
-        cur.seek(SeekFrom::Start(raw_descriptor.entries_start_ptr.into()))?;
+        let mut entries = reader.at(raw_descriptor.entries_start_ptr as usize);
 
         let mut glyphs = vec![];
 
         for i in 0..num_glyphs {
-            let tex_ptr = cur.read_u32::<LittleEndian>()?;
-            let ptr_2 = cur.read_u32::<LittleEndian>()?;
-            let unknown_u32_1 = cur.read_u32::<LittleEndian>()?;
-            let unknown_u32_2 = cur.read_u32::<LittleEndian>()?;
-            let unknown_u32_3 = cur.read_u32::<LittleEndian>()?;
-            let unknown_u32_4 = cur.read_u32::<LittleEndian>()?;
-
-            if tex_ptr == 0 || ptr_2 == 0xffffffff {
+            let [
+                tex_ptr,
+                ptr_2,
+                unknown_u32_1,
+                unknown_u32_2,
+                unknown_u32_3,
+                unknown_u32_4,
+            ] = entries.array::<u32, 6>()?;
+
+            if is_empty_entry(tex_ptr, ptr_2) {
                 continue;
             }
 
-            let mut tex_cur = cur.clone();
-            tex_cur.seek(SeekFrom::Start(tex_ptr.into()))?;
-
-            let tex_descriptor =
-                crate::asset::texture::TextureDescriptor::from_bytes(&data[tex_ptr as usize..])?;
+            let tex_descriptor = crate::asset::texture::TextureDescriptor::from_bytes(
+                reader.at(tex_ptr as usize).remaining(),
+            )?;
 
             glyphs.push(RawGlyph {
                 glyph_index: raw_descriptor.start_glyph + i,
@@ -110,19 +176,74 @@ impl super::AssetDescriptor for FontDescriptor {
             text_x: raw_descriptor.text_x,
             text_y: raw_descriptor.text_y,
             glyphs,
+            original_bytes: OriginalBytes::new(data),
         })
     }
 
     fn size(&self) -> usize {
-        todo!()
+        FONT_HEADER_SIZE
+            + self.entry_count() * GLYPH_ENTRY_SIZE
+            + self
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.texture_descriptor.size())
+                .sum::<usize>()
     }
 
     fn asset_type() -> super::AssetType {
         super::AssetType::ResFont
     }
 
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(self.original_bytes.as_bytes())
+    }
+
+    /// Writes the header, then the glyph table, then the texture descriptor of each glyph, over
+    /// the bytes the descriptor was read from. Indices between the first and last glyph which have
+    /// no glyph keep the entry they were read with if it was already empty, and are zeroed
+    /// otherwise, as their glyph has been removed.
     fn to_bytes(&self) -> Result<Vec<u8>, super::AssetParseError> {
-        todo!()
+        let first_glyph = self.first_glyph();
+        let last_glyph = self.last_glyph();
+
+        let textures_start = FONT_HEADER_SIZE + self.entry_count() * GLYPH_ENTRY_SIZE;
+
+        let mut bytes = Cursor::new(self.original_bytes.base(self.size()));
+        let mut texture_bytes = vec![];
+
+        bytes.write_u32::<LittleEndian>(first_glyph)?;
+        bytes.write_u32::<LittleEndian>(last_glyph)?;
+        bytes.write_u32::<LittleEndian>(self.num_variants)?;
+        bytes.write_u32::<LittleEndian>(self.text_x)?;
+        bytes.write_u32::<LittleEndian>(self.text_y)?;
+        bytes.write_u32::<LittleEndian>(FONT_HEADER_SIZE as u32)?;
+
+        for glyph_index in first_glyph..=last_glyph {
+            let Some(glyph) = self
+                .glyphs
+                .iter()
+                .find(|glyph| glyph.glyph_index == glyph_index)
+            else {
+                bytes.write_all(
+                    self.original_empty_entry(glyph_index)
+                        .unwrap_or(&[0; GLYPH_ENTRY_SIZE]),
+                )?;
+                continue;
+            };
+
+            bytes.write_u32::<LittleEndian>((textures_start + texture_bytes.len()) as u32)?;
+            bytes.write_u32::<LittleEndian>(glyph.num_somethings)?;
+            bytes.write_u32::<LittleEndian>(glyph.unknown_u32_1)?;
+            bytes.write_u32::<LittleEndian>(glyph.unknown_u32_2)?;
+            bytes.write_u32::<LittleEndian>(glyph.unknown_u32_3)?;
+            bytes.write_u32::<LittleEndian>(glyph.unknown_u32_4)?;
+
+            texture_bytes.extend(glyph.texture_descriptor.to_bytes()?);
+        }
+
+        bytes.write_all(&texture_bytes)?;
+
+        Ok(bytes.into_inner())
     }
 }
 
@@ -160,16 +281,20 @@ impl crate::asset::AssetLike for Font {
 
                 let textures = (0..descriptor.num_variants as usize)
                     .map(|i| {
-                        let start = tex_start + i * tex_size;
+                        let start = tex_start.saturating_add(i.saturating_mul(tex_size));
+
+                        let tex_bytes = res_bytes
+                            .get(start..start.saturating_add(tex_size))
+                            .ok_or(super::AssetParseError::InputTooSmall)?;
 
-                        super::texture::Texture::new(
+                        Ok(super::texture::Texture::new(
                             raw.texture_descriptor.clone(),
-                            res_bytes[start..start + tex_size].to_vec(),
-                        )
+                            tex_bytes.to_vec(),
+                        ))
                     })
-                    .collect();
+                    .collect::<Result<Vec<_>, super::AssetParseError>>()?;
 
-                Glyph {
+                Ok(Glyph {
                     glyph_index: raw.glyph_index,
                     textures,
                     num_somethings: raw.num_somethings,
@@ -177,9 +302,9 @@ impl crate::asset::AssetLike for Font {
                     unknown_u32_2: raw.unknown_u32_2,
                     unknown_u32_3: raw.unknown_u32_3,
                     unknown_u32_4: raw.unknown_u32_4,
-                }
+                })
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, super::AssetParseError>>()?;
 
         Ok(Self {
             descriptor: descriptor.clone(),
@@ -187,11 +312,64 @@ impl crate::asset::AssetLike for Font {
         })
     }
 
+    /// The descriptor for the glyphs as they are now, with the images of each glyph placed after
+    /// the last in the order of [`Font::get_resource_chunks`]
     fn get_descriptor(&self) -> Self::Descriptor {
-        todo!()
+        let mut offset = 0usize;
+
+        let glyphs = self
+            .glyphs
+            .iter()
+            .filter_map(|glyph| {
+                // A glyph without images keeps the texture descriptor it was read with
+                let mut texture_descriptor = match glyph.textures.first() {
+                    Some(texture) => texture.descriptor().clone(),
+                    None => self
+                        .descriptor
+                        .glyphs
+                        .iter()
+                        .find(|raw| raw.glyph_index == glyph.glyph_index)?
+                        .texture_descriptor
+                        .clone(),
+                };
+
+                texture_descriptor.set_texture_offset(offset as u32);
+                offset += glyph
+                    .textures
+                    .iter()
+                    .map(|texture| texture.bytes().len())
+                    .sum::<usize>();
+
+                Some(RawGlyph {
+                    glyph_index: glyph.glyph_index,
+                    texture_descriptor,
+                    num_somethings: glyph.num_somethings,
+                    unknown_u32_1: glyph.unknown_u32_1,
+                    unknown_u32_2: glyph.unknown_u32_2,
+                    unknown_u32_3: glyph.unknown_u32_3,
+                    unknown_u32_4: glyph.unknown_u32_4,
+                })
+            })
+            .collect();
+
+        FontDescriptor {
+            num_variants: self.descriptor.num_variants,
+            text_x: self.descriptor.text_x,
+            text_y: self.descriptor.text_y,
+            glyphs,
+            original_bytes: self.descriptor.original_bytes.clone(),
+        }
     }
 
+    /// Every image of every glyph in a single resource chunk
     fn get_resource_chunks(&self) -> Option<Vec<Vec<u8>>> {
-        todo!()
+        Some(vec![
+            self.glyphs
+                .iter()
+                .flat_map(|glyph| &glyph.textures)
+                .flat_map(|texture| texture.bytes())
+                .copied()
+                .collect(),
+        ])
     }
 }
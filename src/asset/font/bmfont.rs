@@ -0,0 +1,648 @@
+/*!
+Conversion between [`Font`]s and the text format of [BMFont](https://www.angelcode.com/products/bmfont/),
+which most font tools and engines can read and write.
+
+The game stores a separate texture for every glyph of every variant, so exporting packs the glyphs
+of one variant onto a single texture page, and importing cuts them back out. Glyph indices are used
+as character ids as they are, so fonts which cover more characters can be made by adding chars to
+the `.fnt` file.
+
+The metrics of each glyph haven't been worked out yet, so glyphs are exported with no offset and
+an advance of their width, and the metrics in a `.fnt` file other than the size of each char are
+ignored when importing.
+*/
+
+use std::{collections::HashMap, fmt};
+
+use crate::asset::{
+    AssetLike as _,
+    texture::{ImageFit, RGBAImage, TextureError},
+};
+
+use super::{Font, Glyph};
+
+/// The narrowest page that glyphs are packed onto
+const MIN_PAGE_WIDTH: usize = 256;
+/// The gap left between glyphs on a page, so that filtering doesn't bleed between them
+const PAGE_PADDING: usize = 1;
+
+#[derive(Debug)]
+pub enum FontError {
+    /// The `.fnt` file couldn't be read, with a description of the problem
+    InvalidFnt(String),
+    /// A char is on a page which wasn't given
+    UnsupportedPage(u32),
+    /// The font doesn't have this many variants
+    InvalidVariant(usize),
+    /// The font has no glyphs to export, or to copy the format of when importing
+    NoGlyphs,
+    Io(std::io::Error),
+    Texture(TextureError),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::InvalidFnt(reason) => write!(f, "Invalid .fnt file: {}", reason),
+            FontError::UnsupportedPage(page) => write!(f, "No image was given for page {}", page),
+            FontError::InvalidVariant(variant) => {
+                write!(f, "The font has no variant {}", variant)
+            }
+            FontError::NoGlyphs => write!(f, "The font has no glyphs"),
+            FontError::Io(e) => write!(f, "{}", e),
+            FontError::Texture(e) => write!(f, "Unable to convert glyph texture: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(e) => Some(e),
+            FontError::Texture(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(value: std::io::Error) -> Self {
+        FontError::Io(value)
+    }
+}
+
+impl From<TextureError> for FontError {
+    fn from(value: TextureError) -> Self {
+        FontError::Texture(value)
+    }
+}
+
+/// A `char` line of a `.fnt` file, giving where a character is on its page
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BmFontChar {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub xoffset: i32,
+    pub yoffset: i32,
+    pub xadvance: i32,
+    pub page: u32,
+}
+
+/// The parts of a BMFont `.fnt` file which are needed to convert fonts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BmFont {
+    pub face: String,
+    pub line_height: u32,
+    /// The width of each page
+    pub scale_w: u32,
+    /// The height of each page
+    pub scale_h: u32,
+    /// The image file of each page, relative to the `.fnt` file
+    pub pages: Vec<String>,
+    pub chars: Vec<BmFontChar>,
+}
+
+impl BmFont {
+    /**
+    Reads the text format of a `.fnt` file. Unknown lines and keys are skipped.
+
+    # Errors
+    - [`FontError::InvalidFnt`] if a line has a value which isn't a number where one is expected,
+      or a char is missing its position or size
+    */
+    pub fn parse(text: &str) -> Result<Self, FontError> {
+        let mut font = BmFont {
+            face: String::new(),
+            line_height: 0,
+            scale_w: 0,
+            scale_h: 0,
+            pages: vec![],
+            chars: vec![],
+        };
+
+        let mut pages: Vec<(u32, String)> = vec![];
+
+        for (line_number, line) in text.lines().enumerate() {
+            let Some((tag, values)) = parse_line(line) else {
+                continue;
+            };
+
+            let number = |key: &str| -> Result<Option<i64>, FontError> {
+                values
+                    .get(key)
+                    .map(|value| {
+                        value.parse().map_err(|_| {
+                            FontError::InvalidFnt(format!(
+                                "{} on line {} is not a number",
+                                key,
+                                line_number + 1
+                            ))
+                        })
+                    })
+                    .transpose()
+            };
+
+            let required = |key: &str| -> Result<i64, FontError> {
+                number(key)?.ok_or_else(|| {
+                    FontError::InvalidFnt(format!(
+                        "{} is missing from line {}",
+                        key,
+                        line_number + 1
+                    ))
+                })
+            };
+
+            match tag {
+                "info" => {
+                    font.face = values.get("face").cloned().unwrap_or_default();
+                }
+                "common" => {
+                    font.line_height = number("lineHeight")?.unwrap_or(0) as u32;
+                    font.scale_w = number("scaleW")?.unwrap_or(0) as u32;
+                    font.scale_h = number("scaleH")?.unwrap_or(0) as u32;
+                }
+                "page" => {
+                    pages.push((
+                        required("id")? as u32,
+                        values.get("file").cloned().unwrap_or_default(),
+                    ));
+                }
+                "char" => {
+                    font.chars.push(BmFontChar {
+                        id: required("id")? as u32,
+                        x: required("x")? as u32,
+                        y: required("y")? as u32,
+                        width: required("width")? as u32,
+                        height: required("height")? as u32,
+                        xoffset: number("xoffset")?.unwrap_or(0) as i32,
+                        yoffset: number("yoffset")?.unwrap_or(0) as i32,
+                        xadvance: number("xadvance")?.unwrap_or(0) as i32,
+                        page: number("page")?.unwrap_or(0) as u32,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        pages.sort_by_key(|(id, _)| *id);
+        font.pages = pages.into_iter().map(|(_, file)| file).collect();
+
+        Ok(font)
+    }
+
+    /// Writes the text format of a `.fnt` file
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=0 aa=1 padding=0,0,0,0 spacing={},{}",
+                self.face, self.line_height, PAGE_PADDING, PAGE_PADDING
+            ),
+            format!(
+                "common lineHeight={} base={} scaleW={} scaleH={} pages={} packed=0",
+                self.line_height,
+                self.line_height,
+                self.scale_w,
+                self.scale_h,
+                self.pages.len()
+            ),
+        ];
+
+        for (id, file) in self.pages.iter().enumerate() {
+            lines.push(format!("page id={} file=\"{}\"", id, file));
+        }
+
+        lines.push(format!("chars count={}", self.chars.len()));
+
+        for c in &self.chars {
+            lines.push(format!(
+                "char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page={} chnl=15",
+                c.id, c.x, c.y, c.width, c.height, c.xoffset, c.yoffset, c.xadvance, c.page
+            ));
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Splits a line into its tag and `key=value` pairs, removing the quotes around values
+fn parse_line(line: &str) -> Option<(&str, HashMap<String, String>)> {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    if tag.is_empty() {
+        return None;
+    }
+
+    let mut values = HashMap::new();
+
+    loop {
+        rest = rest.trim_start();
+
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            },
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+
+        values.insert(key.trim().to_string(), value.to_string());
+        rest = remaining;
+    }
+
+    Some((tag, values))
+}
+
+impl Font {
+    /**
+    Packs the glyphs of `variant` onto a single page, returning the `.fnt` description of the page
+    and the page itself. `page_file` is the name the page will be saved with.
+
+    # Errors
+    - [`FontError::InvalidVariant`] if the font doesn't have `variant`
+    - [`FontError::NoGlyphs`] if the font has no glyphs
+    - [`FontError::Io`] if a glyph can't be decoded
+    */
+    pub fn to_bmfont(
+        &self,
+        variant: usize,
+        face: &str,
+        page_file: &str,
+    ) -> Result<(BmFont, RGBAImage), FontError> {
+        if self.glyphs.is_empty() {
+            return Err(FontError::NoGlyphs);
+        }
+
+        let images = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let texture = glyph
+                    .textures
+                    .get(variant)
+                    .ok_or(FontError::InvalidVariant(variant))?;
+
+                Ok((glyph.glyph_index, texture.to_rgba_image()?))
+            })
+            .collect::<Result<Vec<_>, FontError>>()?;
+
+        let page_width = images
+            .iter()
+            .map(|(_, image)| image.width() + PAGE_PADDING)
+            .max()
+            .unwrap_or(0)
+            .max(MIN_PAGE_WIDTH)
+            .next_power_of_two();
+
+        // Shelf packing, with each glyph placed to the right of the last until the row is full
+        let mut chars = Vec::with_capacity(images.len());
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for (id, image) in &images {
+            if x + image.width() > page_width {
+                x = 0;
+                y += row_height + PAGE_PADDING;
+                row_height = 0;
+            }
+
+            chars.push(BmFontChar {
+                id: *id,
+                x: x as u32,
+                y: y as u32,
+                width: image.width() as u32,
+                height: image.height() as u32,
+                xoffset: 0,
+                yoffset: 0,
+                xadvance: image.width() as i32,
+                page: 0,
+            });
+
+            x += image.width() + PAGE_PADDING;
+            row_height = row_height.max(image.height());
+        }
+
+        let page_height = y + row_height;
+
+        let mut page = RGBAImage::new(
+            page_width,
+            page_height,
+            vec![0; page_width * page_height * 4],
+        )?;
+
+        for (c, (_, image)) in chars.iter().zip(&images) {
+            page.blit(c.x as usize, c.y as usize, image)?;
+        }
+
+        Ok((
+            BmFont {
+                face: face.to_string(),
+                line_height: self.descriptor.text_y,
+                scale_w: page_width as u32,
+                scale_h: page_height as u32,
+                pages: vec![page_file.to_string()],
+                chars,
+            },
+            page,
+        ))
+    }
+
+    /**
+    Cuts the chars of `bmfont` out of `pages` and writes them into the font, returning the number
+    of glyphs which were imported. Chars with no size are skipped.
+
+    Glyphs which are the same size as before only have `variant` replaced. As every variant of a
+    glyph must be the same size, glyphs which have changed size have all of their variants
+    replaced, as do new glyphs. New glyphs use the format and unknown values of the first glyph.
+
+    # Errors
+    - [`FontError::InvalidVariant`] if the font doesn't have `variant`
+    - [`FontError::NoGlyphs`] if the font has no glyphs to copy the format of
+    - [`FontError::UnsupportedPage`] if a char is on a page missing from `pages`
+    - [`FontError::Texture`] if a char doesn't fit on its page, or can't be converted to the
+      format of the font
+    */
+    pub fn import_bmfont(
+        &mut self,
+        bmfont: &BmFont,
+        pages: &[RGBAImage],
+        variant: usize,
+    ) -> Result<usize, FontError> {
+        if variant >= self.descriptor.num_variants as usize {
+            return Err(FontError::InvalidVariant(variant));
+        }
+
+        let template = self
+            .glyphs
+            .iter()
+            .find(|glyph| !glyph.textures.is_empty())
+            .ok_or(FontError::NoGlyphs)?;
+
+        let template_texture = template.textures[0].clone();
+        let template_glyph = Glyph {
+            glyph_index: 0,
+            textures: vec![],
+            num_somethings: template.num_somethings,
+            unknown_u32_1: template.unknown_u32_1,
+            unknown_u32_2: template.unknown_u32_2,
+            unknown_u32_3: template.unknown_u32_3,
+            unknown_u32_4: template.unknown_u32_4,
+        };
+
+        let mut imported = 0;
+
+        for c in bmfont.chars.iter().filter(|c| c.width > 0 && c.height > 0) {
+            let page = pages
+                .get(c.page as usize)
+                .ok_or(FontError::UnsupportedPage(c.page))?;
+
+            let image = page.crop(
+                c.x as usize,
+                c.y as usize,
+                c.width as usize,
+                c.height as usize,
+            )?;
+
+            match self
+                .glyphs
+                .iter_mut()
+                .find(|glyph| glyph.glyph_index == c.id)
+            {
+                Some(glyph) => {
+                    let same_size = glyph.textures.get(variant).is_some_and(|texture| {
+                        (
+                            texture.descriptor().width() as usize,
+                            texture.descriptor().height() as usize,
+                        ) == (image.width(), image.height())
+                    });
+
+                    match same_size {
+                        true => glyph.textures[variant].set_from_image(&image, ImageFit::Exact)?,
+                        false => {
+                            for texture in &mut glyph.textures {
+                                texture.set_from_image(&image, ImageFit::Adapt)?;
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let mut texture = template_texture.clone();
+                    texture.set_from_image(&image, ImageFit::Adapt)?;
+
+                    self.glyphs.push(Glyph {
+                        glyph_index: c.id,
+                        textures: vec![texture; self.descriptor.num_variants as usize],
+                        num_somethings: template_glyph.num_somethings,
+                        unknown_u32_1: template_glyph.unknown_u32_1,
+                        unknown_u32_2: template_glyph.unknown_u32_2,
+                        unknown_u32_3: template_glyph.unknown_u32_3,
+                        unknown_u32_4: template_glyph.unknown_u32_4,
+                    });
+                }
+            }
+
+            imported += 1;
+        }
+
+        self.glyphs.sort_by_key(|glyph| glyph.glyph_index);
+        self.descriptor = self.get_descriptor();
+
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        VirtualResource,
+        asset::{
+            AssetDescriptor, AssetLike,
+            font::{FONT_HEADER_SIZE, FontDescriptor, GLYPH_ENTRY_SIZE, RawGlyph},
+            texture::{Texture, TextureDescriptor},
+        },
+        d3d::{D3DFormat, Swizzled},
+    };
+
+    fn test_font() -> Font {
+        let format = D3DFormat::Swizzled(Swizzled::B8G8R8A8);
+
+        let glyphs = (0x41..0x44)
+            .map(|glyph_index| {
+                let size = 4 + glyph_index as u16 - 0x41;
+                let len = format.image_size(size.into(), size.into());
+
+                let texture = Texture::new(
+                    TextureDescriptor::new(format, 0x1c, size, size, 1, 0, 0, len as u32),
+                    vec![glyph_index as u8; len],
+                );
+
+                Glyph {
+                    glyph_index,
+                    textures: vec![texture.clone(), texture],
+                    num_somethings: 1,
+                    unknown_u32_1: 2,
+                    unknown_u32_2: 3,
+                    unknown_u32_3: 4,
+                    unknown_u32_4: 5,
+                }
+            })
+            .collect();
+
+        let mut font = Font {
+            descriptor: FontDescriptor {
+                num_variants: 2,
+                text_x: 1,
+                text_y: 8,
+                glyphs: vec![],
+                original_bytes: Default::default(),
+            },
+            glyphs,
+        };
+
+        font.descriptor = font.get_descriptor();
+        font
+    }
+
+    #[test]
+    fn font_glyph_removed() -> Result<(), String> {
+        let font = test_font();
+
+        let mut bytes = font.descriptor.to_bytes().map_err(|e| e.to_string())?;
+        let entry = |glyph_index: usize| {
+            let start = FONT_HEADER_SIZE + (glyph_index - 0x41) * GLYPH_ENTRY_SIZE;
+            start..start + GLYPH_ENTRY_SIZE
+        };
+
+        // Removing the middle glyph zeroes its entry, as it no longer has a texture
+        let mut descriptor = FontDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        descriptor.glyphs.retain(|glyph| glyph.glyph_index != 0x42);
+
+        let removed = descriptor.to_bytes().map_err(|e| e.to_string())?;
+        assert_eq!(&removed[entry(0x42)], &[0; GLYPH_ENTRY_SIZE]);
+
+        let read_back = FontDescriptor::from_bytes(&removed).map_err(|e| e.to_string())?;
+        assert_eq!(read_back, descriptor);
+        assert_eq!(read_back.to_bytes().map_err(|e| e.to_string())?, removed);
+
+        // An entry which was already empty is written back as it was read
+        bytes[entry(0x42)].copy_from_slice(&[0; GLYPH_ENTRY_SIZE]);
+        bytes[entry(0x42).start + 8] = 0x7f;
+
+        let descriptor = FontDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.glyphs.len(), 2);
+        assert_eq!(
+            &descriptor.to_bytes().map_err(|e| e.to_string())?[entry(0x42)],
+            &bytes[entry(0x42)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn bmfont_roundtrip() -> Result<(), String> {
+        let mut font = test_font();
+
+        // The descriptor and resource read back as the same font
+        let descriptor_bytes = font.descriptor.to_bytes().map_err(|e| e.to_string())?;
+        assert_eq!(descriptor_bytes.len(), font.descriptor.size());
+
+        let descriptor =
+            FontDescriptor::from_bytes(&descriptor_bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor, font.descriptor);
+
+        let chunks = font.get_resource_chunks().unwrap_or_default();
+        let slices: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.as_slice()).collect();
+        let read_back = Font::new(&descriptor, &VirtualResource::from_slices(&slices))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(read_back.glyphs.len(), 3);
+        assert_eq!(
+            read_back.glyphs[2].textures[1].bytes(),
+            font.glyphs[2].textures[1].bytes()
+        );
+
+        let (bmfont, page) = font
+            .to_bmfont(1, "test", "test_1.png")
+            .map_err(|e| e.to_string())?;
+        assert_eq!(bmfont.chars.len(), 3);
+        assert_eq!((page.width(), page.height()), (256, 6));
+
+        let mut parsed = BmFont::parse(&bmfont.to_text()).map_err(|e| e.to_string())?;
+        assert_eq!(parsed, bmfont);
+
+        // A char beyond the original range, which takes all variants
+        parsed.chars.push(BmFontChar {
+            id: 0x100,
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 2,
+            xoffset: 0,
+            yoffset: 0,
+            xadvance: 3,
+            page: 0,
+        });
+
+        let imported = font
+            .import_bmfont(&parsed, &[page], 1)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(imported, 4);
+        assert_eq!(font.glyphs.len(), 4);
+        assert_eq!(font.descriptor.last_glyph(), 0x100);
+
+        let added = &font.glyphs[3];
+        assert_eq!(added.textures.len(), 2);
+        assert_eq!(added.textures[0].descriptor().width(), 3);
+        assert_eq!(added.unknown_u32_4, 5);
+
+        assert!(matches!(
+            font.import_bmfont(&parsed, &[], 2),
+            Err(FontError::InvalidVariant(2))
+        ));
+        assert!(matches!(
+            font.import_bmfont(&parsed, &[], 0),
+            Err(FontError::UnsupportedPage(0))
+        ));
+
+        // Unused entries of the glyph table are written empty, and skipped when read back
+        let descriptor_bytes = font.descriptor.to_bytes().map_err(|e| e.to_string())?;
+        let descriptor =
+            FontDescriptor::from_bytes(&descriptor_bytes).map_err(|e| e.to_string())?;
+        assert_eq!(
+            descriptor
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.glyph_index)
+                .collect::<Vec<_>>(),
+            vec![0x41, 0x42, 0x43, 0x100]
+        );
+        assert!(descriptor.glyphs.iter().all(|glyph: &RawGlyph| {
+            glyph.texture_descriptor.format() == D3DFormat::Swizzled(Swizzled::B8G8R8A8)
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn font_error_source() {
+        use std::error::Error as _;
+
+        let e = FontError::from(TextureError::SizeMismatch);
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "Unable to convert glyph texture: {}",
+                TextureError::SizeMismatch
+            )
+        );
+        assert!(e.source().is_some_and(|source| source.is::<TextureError>()));
+        assert!(FontError::NoGlyphs.source().is_none());
+    }
+}
@@ -0,0 +1,248 @@
+/*!
+Readable placeholders for the control codes embedded in loctext values.
+
+Values can contain characters which the game treats specially, such as line breaks, colour changes
+and button icons. When exporting to text, these are replaced with escapes that survive being edited
+by translators:
+
+- `\n` is a line break, and `\\` and `\{` are a literal `\` and `{`
+- `{name}` is a control code with a name in the [`ControlCodes`] table, like `{color:red}` or
+  `{btn:A}`
+- `{U+E001}` is any other control or private use character
+
+Not every code used by the game is known yet, so names are supplied through the table, which can
+be loaded from JSON like:
+
+```json
+{ "color:red": "\ue001", "btn:A": "\ue100" }
+```
+*/
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// Names for the control codes of loctext values. Deserialising checks each name as
+/// [`ControlCodes::insert`] does.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "BTreeMap<String, char>", try_from = "BTreeMap<String, char>")]
+pub struct ControlCodes {
+    names: BTreeMap<String, char>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCodeError {
+    /// A placeholder wasn't in the table, and isn't a `U+XXXX` code
+    UnknownPlaceholder(String),
+    /// A `{` without a matching `}`
+    UnterminatedPlaceholder,
+    /// A `\` followed by something other than `n`, `\` or `{`
+    InvalidEscape(Option<char>),
+    /// A name can't be used for a placeholder, or two names were given to the same character
+    InvalidName(String),
+}
+
+impl fmt::Display for ControlCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ControlCodeError::UnknownPlaceholder(name) => {
+                write!(f, "Unknown control code {{{}}}", name)
+            }
+            ControlCodeError::UnterminatedPlaceholder => write!(f, "Unterminated control code"),
+            ControlCodeError::InvalidEscape(Some(c)) => write!(f, "Invalid escape \\{}", c),
+            ControlCodeError::InvalidEscape(None) => write!(f, "Escape at the end of the value"),
+            ControlCodeError::InvalidName(name) => write!(f, "Invalid control code name {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ControlCodeError {}
+
+impl TryFrom<BTreeMap<String, char>> for ControlCodes {
+    type Error = ControlCodeError;
+
+    fn try_from(names: BTreeMap<String, char>) -> Result<Self, Self::Error> {
+        let mut codes = ControlCodes::default();
+
+        for (name, code) in names {
+            codes.insert(&name, code)?;
+        }
+
+        Ok(codes)
+    }
+}
+
+impl From<ControlCodes> for BTreeMap<String, char> {
+    fn from(codes: ControlCodes) -> Self {
+        codes.names
+    }
+}
+
+impl ControlCodes {
+    /**
+    Gives `code` the placeholder `{name}`.
+
+    # Errors
+    - [`ControlCodeError::InvalidName`] if `name` is empty, contains `{` or `}`, looks like a
+      `U+XXXX` code, or `code` already has a name
+    */
+    pub fn insert(&mut self, name: &str, code: char) -> Result<(), ControlCodeError> {
+        if name.is_empty()
+            || name.contains(['{', '}'])
+            || name.starts_with("U+")
+            || self.name_of(code).is_some_and(|n| n != name)
+        {
+            return Err(ControlCodeError::InvalidName(name.to_string()));
+        }
+
+        self.names.insert(name.to_string(), code);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<char> {
+        self.names.get(name).copied()
+    }
+
+    pub fn name_of(&self, code: char) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, c)| **c == code)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Replaces the control codes of `value` with placeholders
+    pub fn escape(&self, value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+
+        for c in value.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '{' => escaped.push_str("\\{"),
+                '\n' => escaped.push_str("\\n"),
+                c => match self.name_of(c) {
+                    Some(name) => {
+                        escaped.push('{');
+                        escaped.push_str(name);
+                        escaped.push('}');
+                    }
+                    None if is_control_code(c) => {
+                        escaped.push_str(&format!("{{U+{:04X}}}", c as u32));
+                    }
+                    None => escaped.push(c),
+                },
+            }
+        }
+
+        escaped
+    }
+
+    /**
+    Replaces the placeholders written by [`ControlCodes::escape`] with their control codes.
+
+    # Errors
+    - [`ControlCodeError`] if an escape or placeholder is invalid
+    */
+    pub fn unescape(&self, escaped: &str) -> Result<String, ControlCodeError> {
+        let mut value = String::with_capacity(escaped.len());
+        let mut chars = escaped.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('\\') => value.push('\\'),
+                    Some('{') => value.push('{'),
+                    Some('n') => value.push('\n'),
+                    other => return Err(ControlCodeError::InvalidEscape(other)),
+                },
+                '{' => {
+                    let mut name = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(ControlCodeError::UnterminatedPlaceholder),
+                        }
+                    }
+
+                    value.push(
+                        self.get(&name)
+                            .or_else(|| parse_code_point(&name))
+                            .ok_or(ControlCodeError::UnknownPlaceholder(name))?,
+                    );
+                }
+                c => value.push(c),
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Characters which are escaped as `U+XXXX` when they have no name
+fn is_control_code(c: char) -> bool {
+    c.is_control() || ('\u{e000}'..='\u{f8ff}').contains(&c)
+}
+
+fn parse_code_point(name: &str) -> Option<char> {
+    let hex = name.strip_prefix("U+")?;
+
+    if hex.len() < 4 {
+        return None;
+    }
+
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_code_roundtrip() -> Result<(), String> {
+        let mut codes: ControlCodes =
+            serde_json::from_str(r#"{ "color:red": "\ue001" }"#).map_err(|e| e.to_string())?;
+        codes
+            .insert("btn:A", '\u{e100}')
+            .map_err(|e| e.to_string())?;
+
+        let value = "Press \u{e100} to {\u{e001}run\u{e002}}\nC:\\ghoulies\u{7}";
+        let escaped = codes.escape(value);
+
+        assert_eq!(
+            escaped,
+            "Press {btn:A} to \\{{color:red}run{U+E002}}\\nC:\\\\ghoulies{U+0007}"
+        );
+        assert_eq!(codes.unescape(&escaped).map_err(|e| e.to_string())?, value);
+
+        assert_eq!(
+            codes.unescape("{btn:B}"),
+            Err(ControlCodeError::UnknownPlaceholder("btn:B".to_string()))
+        );
+        assert_eq!(
+            codes.unescape("{btn:A"),
+            Err(ControlCodeError::UnterminatedPlaceholder)
+        );
+        assert_eq!(
+            codes.unescape("\\t"),
+            Err(ControlCodeError::InvalidEscape(Some('t')))
+        );
+        assert!(codes.insert("btn:Z", '\u{e100}').is_err());
+        assert!(codes.insert("U+E003", '\u{e003}').is_err());
+
+        assert!(serde_json::from_str::<ControlCodes>(r#"{ "a{b}": "\ue001" }"#).is_err());
+        assert!(
+            serde_json::from_str::<ControlCodes>(r#"{ "btn:A": "\ue100", "btn:Z": "\ue100" }"#)
+                .is_err()
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlCodes>(
+                &serde_json::to_string(&codes).map_err(|e| e.to_string())?
+            )
+            .map_err(|e| e.to_string())?,
+            codes
+        );
+
+        Ok(())
+    }
+}
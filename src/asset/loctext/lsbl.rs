@@ -0,0 +1,576 @@
+/*!
+The LSBL container which holds the strings of a loctext resource.
+
+An LSBL file starts with a header of section pointers, relative to the start of the file:
+
+```text
+0x00  "LSBL"
+0x04  values_ptr
+0x08  unknown_u32 (always 4 so far)
+0x0c  values_ptr (again)
+0x10  keys_ptr
+0x14  unknown_section_ptr (0 when there isn't one)
+0x18  hash_list_ptr (0 when there isn't one)
+```
+
+Every section starts with its size in bytes, including the size itself. [`LsblFile`] keeps the
+sections as they are stored, with strings referred to by their offsets into the character pools,
+so that files can be written back without changes. Decoding the strings is left to the user of
+the container, see [`super::LoctextResource`].
+
+The game binary searches the values by hash, so they must be sorted by hash with no two the same.
+[`LsblFile::verify_invariants`] checks this along with the other things a lookup relies on.
+*/
+
+use std::fmt;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{asset::AssetParseError, utils::reader::Le};
+
+const LSBL_SIGNATURE: [u8; 4] = *b"LSBL";
+const HEADER_SIZE: u32 = 0x1c;
+
+/// Marks the end of the value locators
+const VALUES_SENTINEL: u16 = 0xffff;
+
+/// Where a value is found in [`LsblFile::value_chars`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LsblValue {
+    pub hash: u16,
+    /// The offset of the value in UTF-16 code units
+    pub char_offset: u32,
+}
+
+/// Where a key is found in [`LsblFile::key_chars`], and which value it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LsblKey {
+    pub hash: u16,
+    /// The index of the value, starting from 1
+    pub value_index: u16,
+    /// The offset of the key in bytes
+    pub char_offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsblFile {
+    /// The u32 at 0x8 of the header, which has always been 4
+    pub unknown_u32: u32,
+
+    pub values: Vec<LsblValue>,
+    /// The null terminated UTF-16 values, one after the other
+    pub value_chars: Vec<u16>,
+
+    pub keys: Vec<LsblKey>,
+    /// The null terminated keys, one after the other
+    pub key_chars: Vec<u8>,
+
+    /// The section between the keys and the hash list, including its size. Its layout isn't known,
+    /// so it is kept as it was read.
+    pub unknown_section: Vec<u8>,
+
+    pub hash_list: Vec<u16>,
+}
+
+/// Something about an [`LsblFile`] which would break a lookup by the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LsblViolation {
+    /// The value at `index` doesn't have a larger hash than the one before it, so a binary search
+    /// can miss it
+    ValuesNotSorted {
+        index: usize,
+        previous: u16,
+        hash: u16,
+    },
+    /// The characters of the value at `index` start past the end of [`LsblFile::value_chars`]
+    ValueOutOfBounds { index: usize, char_offset: u32 },
+    /// The key at `index` refers to a value which doesn't exist
+    KeyValueIndexOutOfRange { index: usize, value_index: u16 },
+    /// The characters of the key at `index` start past the end of [`LsblFile::key_chars`]
+    KeyOutOfBounds { index: usize, char_offset: u32 },
+}
+
+impl fmt::Display for LsblViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LsblViolation::ValuesNotSorted {
+                index,
+                previous,
+                hash,
+            } => write!(
+                f,
+                "Value {} has hash 0x{:04x}, which isn't greater than the previous hash 0x{:04x}",
+                index, hash, previous
+            ),
+            LsblViolation::ValueOutOfBounds { index, char_offset } => write!(
+                f,
+                "Value {} starts at char {}, which is out of bounds",
+                index, char_offset
+            ),
+            LsblViolation::KeyValueIndexOutOfRange { index, value_index } => write!(
+                f,
+                "Key {} refers to value {}, which doesn't exist",
+                index, value_index
+            ),
+            LsblViolation::KeyOutOfBounds { index, char_offset } => write!(
+                f,
+                "Key {} starts at byte {}, which is out of bounds",
+                index, char_offset
+            ),
+        }
+    }
+}
+
+impl Default for LsblFile {
+    fn default() -> Self {
+        Self {
+            unknown_u32: 4,
+            values: vec![],
+            value_chars: vec![],
+            keys: vec![],
+            key_chars: vec![],
+            unknown_section: vec![],
+            hash_list: vec![],
+        }
+    }
+}
+
+impl LsblFile {
+    /**
+    Reads an LSBL file from the start of `bytes`. Anything after the last section is ignored.
+
+    # Errors
+    - [`AssetParseError::InvalidDataViews`] if the signature doesn't match, or a section is
+      malformed
+    - [`AssetParseError::InputTooSmall`] if a section is larger than `bytes`
+    */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AssetParseError> {
+        Self::from_bytes_with_violations(bytes).map(|(lsbl, _)| lsbl)
+    }
+
+    /**
+    Reads an LSBL file like [`LsblFile::from_bytes`], also returning anything which would break a
+    lookup by the game, see [`LsblFile::verify_invariants`]. The strings can still be read from
+    such a file, so these aren't errors.
+
+    # Errors
+    - The same as [`LsblFile::from_bytes`]
+    */
+    pub fn from_bytes_with_violations(
+        bytes: &[u8],
+    ) -> Result<(Self, Vec<LsblViolation>), AssetParseError> {
+        let mut reader = Le::new(bytes);
+
+        if reader.bytes(4)? != LSBL_SIGNATURE {
+            return Err(AssetParseError::InvalidDataViews(
+                "LSBL file signature does not match".to_string(),
+            ));
+        }
+
+        let [
+            values_ptr,
+            unknown_u32,
+            _values_ptr_copy,
+            keys_ptr,
+            unknown_section_ptr,
+            hash_list_ptr,
+        ] = reader.array::<u32, 6>()?;
+
+        let mut lsbl = LsblFile {
+            unknown_u32,
+            ..Default::default()
+        };
+
+        // Values: the locators, a sentinel, then the characters they point into
+        let mut cur = reader.at(values_ptr as usize);
+
+        let [_values_section_size, num_values] = cur.array::<u32, 2>()?;
+
+        if num_values as usize > bytes.len() / 6 {
+            return Err(AssetParseError::InputTooSmall);
+        }
+
+        for _ in 0..num_values {
+            lsbl.values.push(LsblValue {
+                hash: cur.u16()?,
+                char_offset: cur.u32()?,
+            });
+        }
+
+        let sentinel = cur.u16()?;
+        if sentinel != VALUES_SENTINEL {
+            return Err(AssetParseError::InvalidDataViews(format!(
+                "Sentinel not found after values in LSBL file (found 0x{:04x} instead)",
+                sentinel
+            )));
+        }
+
+        let num_chars = cur.u32()?;
+
+        if num_chars as usize > bytes.len() / size_of::<u16>() {
+            return Err(AssetParseError::InputTooSmall);
+        }
+
+        lsbl.value_chars = cur
+            .bytes(num_chars as usize * 2)?
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect();
+
+        // Keys: the locators, then the characters they point into
+        let mut cur = reader.at(keys_ptr as usize);
+
+        let [keys_section_size, num_keys] = cur.array::<u32, 2>()?;
+
+        let minimum_size = num_keys as u64 * 8 + 8;
+        if (keys_section_size as u64) < minimum_size {
+            return Err(AssetParseError::InvalidDataViews(format!(
+                "Keys list in LSBL file has {} entries, but only {} bytes (expected at least {} bytes)",
+                num_keys, keys_section_size, minimum_size
+            )));
+        }
+
+        if keys_section_size as usize > bytes.len() {
+            return Err(AssetParseError::InputTooSmall);
+        }
+
+        for _ in 0..num_keys {
+            lsbl.keys.push(LsblKey {
+                hash: cur.u16()?,
+                value_index: cur.u16()?,
+                char_offset: cur.u32()?,
+            });
+        }
+
+        lsbl.key_chars = cur
+            .bytes((keys_section_size as u64 - minimum_size) as usize)?
+            .to_vec();
+
+        if unknown_section_ptr != 0 {
+            lsbl.unknown_section = sized_section(bytes, unknown_section_ptr, "Unknown")?.to_vec();
+        }
+
+        if hash_list_ptr != 0 {
+            let mut cur = reader.at(hash_list_ptr as usize);
+
+            let [hash_list_size, num_hashes] = cur.array::<u32, 2>()?;
+
+            let expected_size = 8 + (size_of::<u16>() as u64 * num_hashes as u64);
+
+            if hash_list_size as u64 != expected_size {
+                return Err(AssetParseError::InvalidDataViews(format!(
+                    "Hash list in LSBL file has {} entries, but {} bytes (expected {} bytes)",
+                    num_hashes, hash_list_size, expected_size
+                )));
+            }
+
+            if num_hashes as usize > bytes.len() / size_of::<u16>() {
+                return Err(AssetParseError::InputTooSmall);
+            }
+
+            for _ in 0..num_hashes {
+                lsbl.hash_list.push(cur.u16()?);
+            }
+        }
+
+        let violations = lsbl.verify_invariants().err().unwrap_or_default();
+
+        Ok((lsbl, violations))
+    }
+
+    /**
+    Checks that the game can look up every string: the values are sorted by hash without
+    duplicates, every key refers to a value that exists, and every offset is within its character
+    pool.
+
+    # Errors
+    Every violation found, in the order of the values and then the keys.
+    */
+    pub fn verify_invariants(&self) -> Result<(), Vec<LsblViolation>> {
+        let mut violations = vec![];
+
+        for (index, pair) in self.values.windows(2).enumerate() {
+            if pair[1].hash <= pair[0].hash {
+                violations.push(LsblViolation::ValuesNotSorted {
+                    index: index + 1,
+                    previous: pair[0].hash,
+                    hash: pair[1].hash,
+                });
+            }
+        }
+
+        for (index, value) in self.values.iter().enumerate() {
+            if value.char_offset as usize >= self.value_chars.len() {
+                violations.push(LsblViolation::ValueOutOfBounds {
+                    index,
+                    char_offset: value.char_offset,
+                });
+            }
+        }
+
+        for (index, key) in self.keys.iter().enumerate() {
+            if key.value_index == 0 || key.value_index as usize > self.values.len() {
+                violations.push(LsblViolation::KeyValueIndexOutOfRange {
+                    index,
+                    value_index: key.value_index,
+                });
+            }
+
+            if key.char_offset as usize >= self.key_chars.len() {
+                violations.push(LsblViolation::KeyOutOfBounds {
+                    index,
+                    char_offset: key.char_offset,
+                });
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    /// Writes the file with its sections in the order values, keys, unknown, hash list
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut values_section: Vec<u8> = vec![];
+
+        values_section.extend((self.values.len() as u32).to_le_bytes());
+        for value in &self.values {
+            values_section.extend(value.hash.to_le_bytes());
+            values_section.extend(value.char_offset.to_le_bytes());
+        }
+        values_section.extend(VALUES_SENTINEL.to_le_bytes());
+        values_section.extend((self.value_chars.len() as u32).to_le_bytes());
+        values_section.extend(self.value_chars.iter().flat_map(|c| c.to_le_bytes()));
+        // The end of the values is marked with an extra empty character
+        values_section.extend(0u16.to_le_bytes());
+
+        let mut keys_section: Vec<u8> = vec![];
+
+        keys_section.extend((self.keys.len() as u32).to_le_bytes());
+        for key in &self.keys {
+            keys_section.extend(key.hash.to_le_bytes());
+            keys_section.extend(key.value_index.to_le_bytes());
+            keys_section.extend(key.char_offset.to_le_bytes());
+        }
+        keys_section.extend(&self.key_chars);
+
+        let mut hash_list_section: Vec<u8> = vec![];
+
+        if !self.hash_list.is_empty() {
+            hash_list_section.extend((self.hash_list.len() as u32).to_le_bytes());
+            hash_list_section.extend(self.hash_list.iter().flat_map(|hash| hash.to_le_bytes()));
+        }
+
+        // The unknown section already includes its size
+        let values_section = with_size(values_section);
+        let keys_section = with_size(keys_section);
+        let hash_list_section = match hash_list_section.is_empty() {
+            true => hash_list_section,
+            false => with_size(hash_list_section),
+        };
+
+        let values_ptr = HEADER_SIZE;
+        let keys_ptr = values_ptr + values_section.len() as u32;
+        let unknown_section_ptr = keys_ptr + keys_section.len() as u32;
+        let hash_list_ptr = unknown_section_ptr + self.unknown_section.len() as u32;
+
+        let mut bytes: Vec<u8> = LSBL_SIGNATURE.to_vec();
+
+        for value in [
+            values_ptr,
+            self.unknown_u32,
+            values_ptr,
+            keys_ptr,
+            match self.unknown_section.is_empty() {
+                true => 0,
+                false => unknown_section_ptr,
+            },
+            match hash_list_section.is_empty() {
+                true => 0,
+                false => hash_list_ptr,
+            },
+        ] {
+            bytes
+                .write_u32::<LittleEndian>(value)
+                .expect("Writing to a Vec can't fail");
+        }
+
+        bytes.extend(values_section);
+        bytes.extend(keys_section);
+        bytes.extend(&self.unknown_section);
+        bytes.extend(hash_list_section);
+
+        bytes
+    }
+
+    /// The characters of the value at `index` in [`LsblFile::values`], up to its terminator
+    pub fn value_chars_at(&self, index: usize) -> Option<&[u16]> {
+        let chars = self
+            .value_chars
+            .get(self.values.get(index)?.char_offset as usize..)?;
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+
+        Some(&chars[..len])
+    }
+
+    /// The bytes of the key at `index` in [`LsblFile::keys`], up to its terminator
+    pub fn key_bytes_at(&self, index: usize) -> Option<&[u8]> {
+        let chars = self
+            .key_chars
+            .get(self.keys.get(index)?.char_offset as usize..)?;
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+
+        Some(&chars[..len])
+    }
+}
+
+/// Prefixes `section` with its size, including the size itself
+fn with_size(section: Vec<u8>) -> Vec<u8> {
+    let mut sized = ((section.len() + 4) as u32).to_le_bytes().to_vec();
+    sized.extend(section);
+    sized
+}
+
+/// The section at `ptr` whose first u32 is its size, including the size itself
+fn sized_section<'a>(bytes: &'a [u8], ptr: u32, name: &str) -> Result<&'a [u8], AssetParseError> {
+    let ptr = ptr as usize;
+
+    let size = bytes
+        .get(ptr..ptr + 4)
+        .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+        .ok_or(AssetParseError::InputTooSmall)?;
+
+    if size < 4 {
+        return Err(AssetParseError::InvalidDataViews(format!(
+            "{} section in LSBL file has a size of {}",
+            name, size
+        )));
+    }
+
+    bytes
+        .get(ptr..ptr + size)
+        .ok_or(AssetParseError::InputTooSmall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lsbl_roundtrip() -> Result<(), String> {
+        let lsbl = LsblFile {
+            values: vec![
+                LsblValue {
+                    hash: 0x1d1,
+                    char_offset: 0,
+                },
+                LsblValue {
+                    hash: 0x1d2,
+                    char_offset: 3,
+                },
+            ],
+            value_chars: "hi\0yo\0".encode_utf16().collect(),
+            keys: vec![
+                LsblKey {
+                    hash: 0x1d1,
+                    value_index: 1,
+                    char_offset: 0,
+                },
+                LsblKey {
+                    hash: 0x1d2,
+                    value_index: 2,
+                    char_offset: 2,
+                },
+            ],
+            key_chars: b"a\0b\0".to_vec(),
+            unknown_section: vec![8, 0, 0, 0, 0xde, 0xad, 0xbe, 0xef],
+            hash_list: vec![0x1d1, 0x1d2],
+            ..Default::default()
+        };
+
+        let bytes = lsbl.to_bytes();
+        let read = LsblFile::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        assert_eq!(read, lsbl);
+        assert_eq!(read.to_bytes(), bytes);
+
+        assert_eq!(
+            read.value_chars_at(1),
+            Some(&"yo".encode_utf16().collect::<Vec<_>>()[..])
+        );
+        assert_eq!(read.key_bytes_at(1), Some(&b"b"[..]));
+        assert_eq!(read.key_bytes_at(2), None);
+
+        assert_eq!(read.verify_invariants(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lsbl_invariants() -> Result<(), String> {
+        let value = |hash, char_offset| LsblValue { hash, char_offset };
+
+        let lsbl = LsblFile {
+            values: vec![
+                value(0x10, 0),
+                value(0x30, 2),
+                value(0x20, 4),
+                value(0x20, 9),
+            ],
+            value_chars: "a\0b\0c\0".encode_utf16().collect(),
+            keys: vec![
+                LsblKey {
+                    hash: 0x10,
+                    value_index: 1,
+                    char_offset: 0,
+                },
+                LsblKey {
+                    hash: 0x40,
+                    value_index: 5,
+                    char_offset: 2,
+                },
+            ],
+            key_chars: b"a\0".to_vec(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            lsbl.verify_invariants(),
+            Err(vec![
+                LsblViolation::ValuesNotSorted {
+                    index: 2,
+                    previous: 0x30,
+                    hash: 0x20
+                },
+                LsblViolation::ValuesNotSorted {
+                    index: 3,
+                    previous: 0x20,
+                    hash: 0x20
+                },
+                LsblViolation::ValueOutOfBounds {
+                    index: 3,
+                    char_offset: 9
+                },
+                LsblViolation::KeyValueIndexOutOfRange {
+                    index: 1,
+                    value_index: 5
+                },
+                LsblViolation::KeyOutOfBounds {
+                    index: 1,
+                    char_offset: 2
+                },
+            ])
+        );
+
+        // Reading the file gives back the same violations rather than failing
+        let (_, violations) =
+            LsblFile::from_bytes_with_violations(&lsbl.to_bytes()).map_err(|e| e.to_string())?;
+        assert_eq!(Err(violations), lsbl.verify_invariants());
+
+        // An empty file has nothing to look up
+        assert_eq!(LsblFile::default().verify_invariants(), Ok(()));
+
+        Ok(())
+    }
+}
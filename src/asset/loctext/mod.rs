@@ -1,23 +1,43 @@
+pub mod codes;
+pub mod lsbl;
 mod serialisation;
+pub mod text;
+
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
-    io::{BufRead, Cursor, Read, Seek, SeekFrom},
 };
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use lsbl::{LsblFile, LsblKey, LsblValue, LsblViolation};
 use serde::Serialize;
 use serialisation::*;
 
-use crate::asset::AssetParseError;
+use crate::{
+    RawAsset,
+    asset::AssetParseError,
+    utils::{reader::Le, string_table::StringTable},
+};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize)]
 pub struct LoctextResource {
     #[serde(
         flatten,
         serialize_with = "serde_ordered_collections::map::sorted_serialize"
     )]
     values: HashMap<String, String>,
+
+    /// The unknown section of the LSBL file, which is written back as it was read
+    #[serde(skip)]
+    unknown_section: Vec<u8>,
+}
+
+/// A key whose hash was already taken by another key, so a substitute hash was written instead
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoctextCollision {
+    pub key: String,
+    pub original_hash: u16,
+    pub substituted_hash: u16,
 }
 
 impl LoctextResource {
@@ -27,7 +47,7 @@ impl LoctextResource {
         let mut hash: u32 = 0;
 
         bytes.iter().for_each(|b| {
-            hash = hash.wrapping_mul(0x10) + (*b as u32);
+            hash = hash.wrapping_mul(0x10).wrapping_add(*b as u32);
 
             let masked: u32 = hash & 0xf000;
 
@@ -40,183 +60,76 @@ impl LoctextResource {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<LoctextResource, AssetParseError> {
-        let mut cur = Cursor::new(bytes);
-        let demand_header = DemandHeader::from_cursor(&mut cur)?;
-
-        cur.seek(SeekFrom::Start(
-            demand_header.loctext_resource_header_ptr as u64,
-        ))?;
+        Self::from_bytes_with_violations(bytes).map(|(loctext, _)| loctext)
+    }
 
-        let lsbl_ptr = cur.read_u32::<LittleEndian>()?;
+    /// Reads the resource, also returning anything about its LSBL file which would break a
+    /// lookup by the game, see [`LsblFile::verify_invariants`]
+    pub fn from_bytes_with_violations(
+        bytes: &[u8],
+    ) -> Result<(LoctextResource, Vec<LsblViolation>), AssetParseError> {
+        let mut reader = Le::new(bytes);
+        let demand_header = DemandHeader::from_reader(&mut reader)?;
 
-        let lsbl_slice =
-            &bytes[demand_header.loctext_resource_header_ptr as usize + lsbl_ptr as usize..];
+        let lsbl_ptr = reader
+            .at(demand_header.loctext_resource_header_ptr as usize)
+            .u32()?;
 
-        let mut hashes = vec![];
+        let lsbl_slice = bytes
+            .get(demand_header.loctext_resource_header_ptr as usize + lsbl_ptr as usize..)
+            .ok_or(AssetParseError::InvalidDataViews(
+                "LSBL pointer is out of bounds of the loctext resource.".to_string(),
+            ))?;
 
-        // let loctext_map: HashMap<String, String> = HashMap::new();
+        let (lsbl, violations) = LsblFile::from_bytes_with_violations(lsbl_slice)?;
 
-        let mut keys_map: HashMap<String, u16> = HashMap::new();
+        // Find all values and the associated hash for each one
         let mut values_map: HashMap<u16, String> = HashMap::new();
 
-        {
-            let mut cur = Cursor::new(lsbl_slice);
-
-            let mut lsbl_signature = [0u8; 4];
-            cur.read_exact(&mut lsbl_signature)?;
-
-            if lsbl_signature != ['L', 'S', 'B', 'L'].map(|v| v as u8) {
-                return Err(AssetParseError::InvalidDataViews(
-                    "LSBL file signature does not match".to_string(),
-                ));
-            }
-
-            let values_ptr = cur.read_u32::<LittleEndian>()?;
-
-            // lsbl                 4 bytes
-            // values_start         4 bytes
-
-            // unknown_count_1      2 bytes
-            // unknown_count_2      2 bytes
-            // unknown_u32_1        4 bytes
-            cur.seek_relative(8)?; // Skip 8 bytes
-
-            let keys_ptr = cur.read_u32::<LittleEndian>()?;
-
-            // unknown_u32_2        4 bytes
-            let _unused_u32 = cur.read_u32::<LittleEndian>()?;
-
-            let hash_list_ptr = cur.read_u32::<LittleEndian>()?;
-
-            let mut hash_list_cur = cur.clone();
-
-            hash_list_cur.seek(SeekFrom::Start(hash_list_ptr as u64))?;
-
-            // Find all hashes first
-            let hash_list_size_bytes = hash_list_cur.read_u32::<LittleEndian>()?;
-            let hash_list_length = hash_list_cur.read_u32::<LittleEndian>()?;
-
-            let expected_size: u32 = 8 + (size_of::<u16>() * hash_list_length as usize) as u32;
-
-            if hash_list_size_bytes != expected_size {
-                return Err(AssetParseError::InvalidDataViews(format!(
-                    "Hash list in LSBL file has {} entries, but {} bytes (expected {} bytes)",
-                    hash_list_length, hash_list_size_bytes, expected_size
-                )));
-            }
-
-            hashes.resize(hash_list_length as usize, 0);
-
-            for hash in hashes.iter_mut().take(hash_list_length as usize) {
-                *hash = cur.read_u16::<LittleEndian>()?;
-            }
-
-            // Find all values and the associated hash for each one
-            cur.seek(SeekFrom::Start(values_ptr as u64))?;
-
-            let values_section_size = cur.read_u32::<LittleEndian>()?;
-            let num_values = cur.read_u32::<LittleEndian>()?;
-
-            let mut chars: Vec<u16> = vec![];
-
-            // Get the chars out of the file
-            {
-                let mut chars_cur = cur.clone();
-                chars_cur.seek_relative((num_values * 6) as i64)?;
-
-                let sentinel = chars_cur.read_u16::<LittleEndian>()?;
-                if sentinel != 0xFFFF {
-                    return Err(AssetParseError::InvalidDataViews(format!(
-                        "Sentinel not found after values in LSBL file (found 0x{:04x} instead)",
-                        sentinel
-                    )));
-                }
-
-                let num_chars = chars_cur.read_u32::<LittleEndian>()?;
+        for (i, value) in lsbl.values.iter().enumerate() {
+            let value_chars = lsbl.value_chars_at(i).ok_or_else(|| {
+                AssetParseError::InvalidDataViews(format!(
+                    "Value offset {} is out of bounds for {} chars.",
+                    value.char_offset,
+                    lsbl.value_chars.len()
+                ))
+            })?;
 
-                let mut raw_chars = vec![0u8; (num_chars * 2) as usize];
-
-                chars_cur.read_exact(&mut raw_chars)?;
-
-                chars = raw_chars
-                    .chunks_exact(2)
-                    .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-                    .collect();
-            }
+            values_map.insert(value.hash, decode_value(value_chars)?);
+        }
 
-            for _ in 0..num_values {
-                let hash = cur.read_u16::<LittleEndian>()?;
-                let chars_offset = cur.read_u32::<LittleEndian>()?;
+        // Find all keys and make sure each hash is matched
+        let keys_map = lsbl
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(i, locator)| -> Result<_, AssetParseError> {
+                let key_bytes = match lsbl.key_bytes_at(i) {
+                    None => {
+                        return Err(AssetParseError::InvalidDataViews(
+                            "Key string offset is out of bounds.".to_string(),
+                        ));
+                    }
+                    Some([]) => {
+                        return Err(AssetParseError::InvalidDataViews(
+                            "Failed to read key string (null terminated instantly).".to_string(),
+                        ));
+                    }
+                    Some(key_bytes) => key_bytes,
+                };
 
-                // TODO: Add bounds check
-                let val = String::from_utf16(&chars[(chars_offset as usize)..]).map_err(|e| {
+                let key = String::from_utf8(key_bytes.to_vec()).map_err(|e| {
                     AssetParseError::InvalidDataViews(format!(
-                        "Failed to read UTF16 LE string from value bytes. Error: {}",
+                        "Failed to read key string from loctext. Error: {}",
                         e
                     ))
                 })?;
 
-                values_map.insert(hash, val.split_once('\0').unwrap().0.to_string());
-            }
-
-            // Find all keys and make sure each hash is matched
-            cur.seek(SeekFrom::Start(keys_ptr as u64))?;
+                Ok((key, locator.hash))
+            })
+            .collect::<Result<HashMap<_, _>, AssetParseError>>()?;
 
-            let keys_section_size = cur.read_u32::<LittleEndian>()?;
-            let keys_list_length = cur.read_u32::<LittleEndian>()?;
-
-            let minimum_size = keys_list_length * 8 + 8;
-            if keys_section_size < minimum_size {
-                return Err(AssetParseError::InvalidDataViews(format!(
-                    "Keys list in LSBL file has {} entries, but only {} bytes (expected at least {} bytes)",
-                    keys_list_length, keys_section_size, minimum_size
-                )));
-            }
-
-            let mut key_chars = vec![0u8; (keys_section_size - 8 - keys_list_length * 8) as usize];
-
-            let mut str_cur = cur.clone();
-
-            str_cur.seek_relative((keys_list_length * 8) as i64)?;
-            str_cur.read_exact(&mut key_chars)?;
-
-            keys_map = (0..keys_list_length as usize)
-                .map(|_| -> Result<_, AssetParseError> {
-                    let hash = cur.read_u16::<LittleEndian>()?;
-                    let value_index = cur.read_u16::<LittleEndian>()?;
-                    let chars_offset = cur.read_u32::<LittleEndian>()?;
-
-                    let mut str_cur = Cursor::new(&key_chars);
-                    str_cur.seek_relative(chars_offset as i64)?;
-                    let mut new_str: Vec<u8> = vec![];
-                    str_cur.read_until(0u8, &mut new_str)?;
-
-                    match new_str.len() {
-                        0 => unreachable!(),
-                        1 => {
-                            return Err(AssetParseError::InvalidDataViews(
-                                "Failed to read key string (null terminated instantly)."
-                                    .to_string(),
-                            ));
-                        }
-                        _ => (),
-                    }
-
-                    new_str.pop();
-
-                    let key = String::from_utf8(new_str).map_err(|e| {
-                        AssetParseError::InvalidDataViews(format!(
-                            "Failed to read key string from loctext. Error: {}",
-                            e
-                        ))
-                    })?;
-
-                    Ok((key, hash))
-                })
-                .collect::<Result<HashMap<_, _>, AssetParseError>>()?;
-        }
-
-        Ok(Self {
+        let loctext = Self {
             values: keys_map
                 .into_iter()
                 .map(|(key, hash)| {
@@ -229,49 +142,102 @@ impl LoctextResource {
                         )))
                 })
                 .collect::<Result<HashMap<_, _>, _>>()?,
-        })
+            unknown_section: lsbl.unknown_section,
+        };
 
-        // Parse keys first, and get their hashes
+        Ok((loctext, violations))
     }
 
     pub fn from_hashmap(hashmap: HashMap<String, String>) -> Result<Self, AssetParseError> {
-        // TODO: Validate the chars as UTF8 and UTF16LE
-        Ok(Self { values: hashmap })
+        for (key, value) in &hashmap {
+            if key.is_empty() || key.contains('\0') {
+                return Err(AssetParseError::InvalidDataViews(format!(
+                    "Key {:?} must be non-empty and can't contain null characters.",
+                    key
+                )));
+            }
+
+            if value.contains('\0') {
+                return Err(AssetParseError::InvalidDataViews(format!(
+                    "The value of {} can't contain null characters.",
+                    key
+                )));
+            }
+        }
+
+        Ok(Self {
+            values: hashmap,
+            unknown_section: vec![],
+        })
     }
 
-    pub fn dump(&self) -> Result<Vec<u8>, AssetParseError> {
-        let mut values_section: Vec<u8> = vec![];
-        let mut keys_section: Vec<u8> = vec![];
-        let unknown_section: Vec<u8> = vec![];
-        let mut hash_list_section: Vec<u8> = vec![];
+    /// Reads the loctext resource of a ResLoctext asset, from all of its resource chunks
+    pub fn from_raw_asset(raw_asset: &RawAsset) -> Result<Self, AssetParseError> {
+        Self::from_raw_asset_with_violations(raw_asset).map(|(loctext, _)| loctext)
+    }
 
-        #[repr(C)]
-        struct ValueLocator {
-            hash: u16,
-            char_offset: u32,
-        }
+    /// Reads the loctext resource of a ResLoctext asset like [`LoctextResource::from_raw_asset`],
+    /// also returning the violations of [`LoctextResource::from_bytes_with_violations`]
+    pub fn from_raw_asset_with_violations(
+        raw_asset: &RawAsset,
+    ) -> Result<(Self, Vec<LsblViolation>), AssetParseError> {
+        let bytes: Vec<u8> = raw_asset
+            .resource_chunks()
+            .ok_or(AssetParseError::InvalidDataViews(format!(
+                "Loctext {} has no resource data.",
+                raw_asset.name()
+            )))?
+            .concat();
+
+        Self::from_bytes_with_violations(&bytes)
+    }
 
-        let mut value_locators = vec![];
-        let mut value_chars: Vec<u16> = vec![];
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
 
-        #[repr(C)]
-        struct KeyLocator {
-            hash: u16,
-            value_index: u16,
-            char_offset: u32,
+    /// The unknown section of the LSBL file, including its size. Empty if the file didn't have
+    /// one.
+    pub fn unknown_section(&self) -> &[u8] {
+        &self.unknown_section
+    }
+
+    /**
+    Replaces the unknown section of the LSBL file. An empty section removes it.
+
+    # Errors
+    - [`AssetParseError::InvalidDataViews`] if the section doesn't start with its own size
+    */
+    pub fn set_unknown_section(&mut self, section: Vec<u8>) -> Result<(), AssetParseError> {
+        if !section.is_empty()
+            && section
+                .get(0..4)
+                .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+                != Some(section.len())
+        {
+            return Err(AssetParseError::InvalidDataViews(
+                "The unknown LSBL section must start with its size.".to_string(),
+            ));
         }
 
-        let mut key_locators: Vec<KeyLocator> = vec![];
-        let mut key_chars: Vec<u8> = vec![];
+        self.unknown_section = section;
+        Ok(())
+    }
 
-        let mut hashes = HashSet::<u16>::new();
+    pub fn dump(&self) -> Result<Vec<u8>, AssetParseError> {
+        self.dump_with_collisions().map(|(bytes, _)| bytes)
+    }
 
-        #[repr(C)]
-        struct HashCollision {
-            name: String,
-            original_hash: u16,
-            substituted_hash: u16,
-        }
+    /// Dumps the resource, also returning every key which had to be given a substitute hash
+    pub fn dump_with_collisions(
+        &self,
+    ) -> Result<(Vec<u8>, Vec<LoctextCollision>), AssetParseError> {
+        let mut lsbl = LsblFile {
+            unknown_section: self.unknown_section.clone(),
+            ..Default::default()
+        };
+
+        let mut hashes = HashSet::<u16>::new();
 
         #[repr(C)]
         struct CollisionTableEntry {
@@ -280,7 +246,7 @@ impl LoctextResource {
             substituted_hash: u16,
         }
 
-        let mut collisions = Vec::<HashCollision>::new();
+        let mut collisions = Vec::<LoctextCollision>::new();
 
         #[derive(Debug)]
         struct KeyPair {
@@ -291,25 +257,21 @@ impl LoctextResource {
         let mut substituted_hash: u16 = 0;
 
         let mut hash_to_pair = HashMap::<u16, KeyPair>::new();
-        for (k, v) in self.values.clone() {
-            let mut key: Vec<u8> = k.chars().map(|c| c as u8).collect();
-            let mut hash = LoctextResource::hash_loctext_key(&key);
+        let mut key_strings = StringTable::default();
+        // Keys are visited in order so that the same keys always get the same substitutes
+        let mut pairs: Vec<_> = self.values.clone().into_iter().collect();
+        pairs.sort();
 
-            // Add null terminator
-            key.push(0u8);
+        for (k, v) in pairs {
+            let mut hash = LoctextResource::hash_loctext_key(&k);
 
             if hash_to_pair.contains_key(&hash) {
                 while hash_to_pair.contains_key(&substituted_hash) {
                     substituted_hash += 1;
                 }
 
-                println!(
-                    "Key {} resolves to duplicate hash: 0x{:04x}. Using substituted hash 0x{:04x} instead.",
-                    k, hash, substituted_hash
-                );
-
-                collisions.push(HashCollision {
-                    name: k.clone(),
+                collisions.push(LoctextCollision {
+                    key: k.clone(),
                     original_hash: hash,
                     substituted_hash,
                 });
@@ -317,12 +279,12 @@ impl LoctextResource {
                 hash = substituted_hash;
             }
 
-            // If it STILL contains the hash, print an error
+            // If it STILL contains the hash, the substitute wasn't free
             if let Some(old_val) = hash_to_pair.insert(hash, KeyPair { key: k, value: v }) {
-                eprintln!(
+                return Err(AssetParseError::InvalidDataViews(format!(
                     "Fatal hash collision on collision table insertion. Old value: {:?}",
                     old_val
-                );
+                )));
             }
         }
 
@@ -339,11 +301,6 @@ impl LoctextResource {
         });
 
         for (i, (hash, kp)) in sorted_values.iter().enumerate() {
-            let mut key: Vec<u8> = kp.key.chars().map(|c| c as u8).collect();
-
-            // Add null terminator
-            key.push(0u8);
-
             // Insert fail => already in set
             if !hashes.insert(**hash) {
                 return Err(AssetParseError::InvalidDataViews(
@@ -355,37 +312,41 @@ impl LoctextResource {
             value.push(0u16);
 
             // Write value chars
-            value_locators.push(ValueLocator {
+            lsbl.values.push(LsblValue {
                 hash: **hash,
-                char_offset: value_chars.len() as u32,
+                char_offset: lsbl.value_chars.len() as u32,
             });
-
-            value_chars.extend(value);
+            lsbl.value_chars.extend(value);
 
             // Write key chars
-            key_locators.push(KeyLocator {
+            lsbl.keys.push(LsblKey {
                 hash: **hash,
                 value_index: (i + 1) as u16,
-                char_offset: key_chars.len() as u32,
+                char_offset: key_strings
+                    .insert(&kp.key)
+                    .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))?,
             });
-            key_chars.extend(key);
+
+            lsbl.hash_list.push(**hash);
         }
 
+        lsbl.key_chars = key_strings.into_bytes();
+
+        lsbl.verify_invariants()
+            .map_err(AssetParseError::MalformedLsbl)?;
+
         // Write collision chars
         let mut col_table_entries = Vec::<CollisionTableEntry>::new();
-        let mut collision_chars = Vec::<u8>::new();
+        let mut collision_strings = StringTable::default();
 
         for collision in &collisions {
-            let mut collision_key: Vec<u8> = collision.name.chars().map(|c| c as u8).collect();
-            collision_key.push(0);
-
             col_table_entries.push(CollisionTableEntry {
-                name_offset: collision_chars.len() as u32,
+                name_offset: collision_strings
+                    .insert(&collision.key)
+                    .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))?,
                 original_hash: collision.original_hash,
                 substituted_hash: collision.substituted_hash,
             });
-
-            collision_chars.extend(collision_key);
         }
 
         let mut collisions_section: Vec<u8> = vec![];
@@ -399,93 +360,9 @@ impl LoctextResource {
             collisions_section.write_u16::<LittleEndian>(entry.substituted_hash)?;
         }
 
-        collisions_section.extend(collision_chars);
-
-        {
-            // The size
-            values_section.write_u32::<LittleEndian>(0x00)?;
-
-            // Create values section
-            values_section.write_u32::<LittleEndian>(hashes.len() as u32)?;
-            for value_locator in value_locators {
-                values_section.write_u16::<LittleEndian>(value_locator.hash)?;
-                values_section.write_u32::<LittleEndian>(value_locator.char_offset)?;
-            }
-            // Write end of locators sentinel
-            values_section.write_u16::<LittleEndian>(0xFFFF)?;
-            values_section.write_u32::<LittleEndian>(value_chars.len() as u32)?;
-            values_section.extend(value_chars.iter().flat_map(|v| v.to_le_bytes()));
-            // Write end of values sentinel (an extra empty wchar_t)
-            values_section.write_u16::<LittleEndian>(0x0000)?;
-
-            let len = values_section.len() as u32;
-            values_section[0..4].copy_from_slice(&(len.to_le_bytes()));
-        }
-
-        if !hashes.is_empty() {
-            // The size
-            hash_list_section.write_u32::<LittleEndian>(0x00)?;
-
-            hash_list_section.write_u32::<LittleEndian>(hashes.len() as u32)?;
-
-            for hash in &hashes {
-                hash_list_section.write_u16::<LittleEndian>(*hash)?;
-            }
-
-            let len = hash_list_section.len() as u32;
-            hash_list_section[0..4].copy_from_slice(&(len.to_le_bytes()));
-        }
-
-        {
-            // The size
-            keys_section.write_u32::<LittleEndian>(0x00)?;
-
-            // Create keys section
-            keys_section.write_u32::<LittleEndian>(hashes.len() as u32)?;
-            for key_locator in key_locators {
-                keys_section.write_u16::<LittleEndian>(key_locator.hash)?;
-                keys_section.write_u16::<LittleEndian>(key_locator.value_index)?;
-                keys_section.write_u32::<LittleEndian>(key_locator.char_offset)?;
-            }
-            keys_section.extend(key_chars);
-
-            let len = keys_section.len() as u32;
-            keys_section[0..4].copy_from_slice(&(len.to_le_bytes()));
-        }
-
-        let mut lsbl_bytes: Vec<u8> = vec![b'L', b'S', b'B', b'L'];
-
-        let mut section_ptr = 0x1c;
+        collisions_section.extend(collision_strings.into_bytes());
 
-        // Values section ptr
-        lsbl_bytes.write_u32::<LittleEndian>(section_ptr)?;
-        lsbl_bytes.write_u32::<LittleEndian>(0x4)?;
-        lsbl_bytes.write_u32::<LittleEndian>(section_ptr)?;
-        section_ptr += values_section.len() as u32;
-
-        // Keys section ptr
-        lsbl_bytes.write_u32::<LittleEndian>(section_ptr)?;
-        section_ptr += keys_section.len() as u32;
-
-        // Unknown section ptr
-        if unknown_section.is_empty() {
-            lsbl_bytes.write_u32::<LittleEndian>(0x00)?;
-        } else {
-            lsbl_bytes.write_u32::<LittleEndian>(section_ptr)?;
-        }
-        section_ptr += unknown_section.len() as u32;
-
-        // Hash list section ptr
-        if hash_list_section.is_empty() {
-            lsbl_bytes.write_u32::<LittleEndian>(0x00)?;
-        } else {
-            lsbl_bytes.write_u32::<LittleEndian>(section_ptr)?;
-        }
-
-        lsbl_bytes.extend(values_section);
-        lsbl_bytes.extend(keys_section);
-        lsbl_bytes.extend(unknown_section);
-        lsbl_bytes.extend(hash_list_section);
+        let lsbl_bytes = lsbl.to_bytes();
 
         let mut out_bytes: Vec<u8> = Vec::new();
 
@@ -517,14 +394,89 @@ impl LoctextResource {
         out_bytes.extend(collisions_section);
         out_bytes.extend(lsbl_bytes);
 
-        Ok(out_bytes)
+        Ok((out_bytes, collisions))
     }
 }
 
+/// Decodes a UTF-16 value, which must not contain unpaired surrogates
+fn decode_value(chars: &[u16]) -> Result<String, AssetParseError> {
+    char::decode_utf16(chars.iter().copied())
+        .enumerate()
+        .map(|(i, c)| {
+            c.map_err(|e| {
+                AssetParseError::InvalidDataViews(format!(
+                    "Unpaired surrogate 0x{:04x} in loctext value (char {}).",
+                    e.unpaired_surrogate(),
+                    i
+                ))
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use crate::asset::loctext::LoctextResource;
 
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn loctext_roundtrip(
+            // Keys are written as null terminated UTF-8, and values as null terminated UTF-16
+            values in prop::collection::hash_map("[a-zぁ-ん][a-z0-9_ぁ-ん]{0,31}", "\\PC{0,32}", 1..32)
+        ) {
+            let loctext = LoctextResource::from_hashmap(values).expect("Loctext can be created");
+            let bytes = loctext.dump().expect("Loctext can always be written");
+
+            prop_assert_eq!(LoctextResource::from_bytes(&bytes).ok(), Some(loctext));
+        }
+    }
+
+    #[test]
+    fn non_ascii_roundtrip() -> Result<(), String> {
+        let values = HashMap::from([
+            (
+                "chaptername__1".to_string(),
+                "幽霊屋敷へようこそ".to_string(),
+            ),
+            (
+                "title__emoji".to_string(),
+                "Ghoulies 👻🎃 Ghoulies".to_string(),
+            ),
+            ("タイトル".to_string(), "𠮷野家".to_string()),
+        ]);
+
+        let loctext = LoctextResource::from_hashmap(values.clone()).map_err(|e| e.to_string())?;
+        let read = LoctextResource::from_bytes(&loctext.dump().map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(read.values(), &values);
+
+        // Keys are hashed from their UTF-8 bytes
+        assert_eq!(
+            LoctextResource::hash_loctext_key("タイトル"),
+            LoctextResource::hash_loctext_key("タイトル".as_bytes())
+        );
+
+        // Surrogate pairs are decoded, but unpaired surrogates are rejected
+        assert_eq!(
+            super::decode_value(&[0xd83d, 0xdc7b]).map_err(|e| e.to_string())?,
+            "👻"
+        );
+        assert!(super::decode_value(&[0x41, 0xd83d, 0x41]).is_err());
+        assert!(super::decode_value(&[0xdc7b]).is_err());
+
+        assert!(
+            LoctextResource::from_hashmap(HashMap::from([("a\0b".to_string(), "".to_string())]))
+                .is_err()
+        );
+
+        Ok(())
+    }
+
     #[test]
     pub fn chapter_names_hash_correctly() -> Result<(), String> {
         assert_eq!(LoctextResource::hash_loctext_key("chaptername__1"), 0x1d1);
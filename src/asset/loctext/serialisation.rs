@@ -1,6 +1,4 @@
-use crate::asset::AssetParseError;
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+use crate::{asset::AssetParseError, utils::reader::Le};
 
 /*
 pub struct LoctextKey {
@@ -41,21 +39,6 @@ pub struct LoctextLocatorList {
 
 */
 
-#[expect(unused)]
-pub struct LoctextFile {
-    lsbl: [u8; 4],
-    values_ptr: u32,
-
-    unknown_count_1: u16,
-    unknown_count_2: u16,
-    unknown_u16_1: u16,
-    unknown_u32_1: u32,
-
-    keys_ptr: u32,
-    unknown_u32_2: u32,
-    hash_list_ptr: u32,
-}
-
 #[expect(unused)]
 pub struct DemandHeader {
     /// TODO: Replace with an enum later once the values are known
@@ -69,15 +52,25 @@ pub struct DemandHeader {
 }
 
 impl DemandHeader {
-    pub fn from_cursor(cur: &mut Cursor<&[u8]>) -> Result<Self, AssetParseError> {
+    pub fn from_reader(reader: &mut Le) -> Result<Self, AssetParseError> {
+        let [
+            demand_asset_type,
+            unknown_u32_1,
+            unknown_u32_2,
+            unknown_u32_3,
+            loctext_resource_header_ptr,
+            loctext_file_size,
+            unknown_u32_4,
+        ] = reader.array::<u32, 7>()?;
+
         Ok(Self {
-            demand_asset_type: cur.read_u32::<LittleEndian>()?,
-            unknown_u32_1: cur.read_u32::<LittleEndian>()?,
-            unknown_u32_2: cur.read_u32::<LittleEndian>()?,
-            unknown_u32_3: cur.read_u32::<LittleEndian>()?,
-            loctext_resource_header_ptr: cur.read_u32::<LittleEndian>()?,
-            loctext_file_size: cur.read_u32::<LittleEndian>()?,
-            unknown_u32_4: cur.read_u32::<LittleEndian>()?,
+            demand_asset_type,
+            unknown_u32_1,
+            unknown_u32_2,
+            unknown_u32_3,
+            loctext_resource_header_ptr,
+            loctext_file_size,
+            unknown_u32_4,
         })
     }
 }
@@ -0,0 +1,485 @@
+/*!
+Text formats for loctext resources, so that they can be translated with common tools.
+
+- JSON is a single object mapping each key to its value, the same as `loctext_ripper` writes.
+- CSV has a `key,value` header, followed by one quoted row per key.
+- PO (gettext) uses the key as the `msgctxt` and the value as the `msgid`. The `msgstr` is left
+  empty when exporting, and is used instead of the `msgid` when importing if a translation was
+  filled in.
+
+In every format, the control codes of values are replaced with placeholders, see [`super::codes`].
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+use crate::asset::loctext::{LoctextResource, codes::ControlCodes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum LoctextFormat {
+    Json,
+    Csv,
+    Po,
+}
+
+impl LoctextFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LoctextFormat::Json => "json",
+            LoctextFormat::Csv => "csv",
+            LoctextFormat::Po => "po",
+        }
+    }
+
+    /// Guesses the format from the extension of `path`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoctextTextError {
+    /// The line the error was found on, starting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LoctextTextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LoctextTextError {}
+
+/// Writes every key and value of `loctext` in `format`, sorted by key, escaping the control codes
+/// of the values with `codes`
+pub fn export(loctext: &LoctextResource, format: LoctextFormat, codes: &ControlCodes) -> String {
+    let sorted: BTreeMap<&String, String> = loctext
+        .values()
+        .iter()
+        .map(|(key, value)| (key, codes.escape(value)))
+        .collect();
+
+    match format {
+        LoctextFormat::Json => {
+            // Maps of strings can always be serialised
+            serde_json::to_string_pretty(&sorted).unwrap_or_default()
+        }
+        LoctextFormat::Csv => {
+            let mut text = String::from("key,value\n");
+
+            for (key, value) in sorted {
+                text.push_str(&format!("{},{}\n", csv_quote(key), csv_quote(&value)));
+            }
+
+            text
+        }
+        LoctextFormat::Po => {
+            let mut text =
+                String::from("msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+
+            for (key, value) in sorted {
+                text.push_str(&format!(
+                    "\nmsgctxt {}\nmsgid {}\nmsgstr \"\"\n",
+                    po_quote(key),
+                    po_quote(&value)
+                ));
+            }
+
+            text
+        }
+    }
+}
+
+/**
+Parses text written by [`export`] (or edited from it) back into a [`LoctextResource`].
+
+# Errors
+- [`LoctextTextError`] if the text isn't valid for `format`, a key appears more than once, or a
+  value has an invalid placeholder
+*/
+pub fn import(
+    text: &str,
+    format: LoctextFormat,
+    codes: &ControlCodes,
+) -> Result<LoctextResource, LoctextTextError> {
+    let entries = match format {
+        LoctextFormat::Json => {
+            let values: BTreeMap<String, String> =
+                serde_json::from_str(text).map_err(|e| LoctextTextError {
+                    line: e.line(),
+                    message: e.to_string(),
+                })?;
+
+            values.into_iter().map(|entry| (0, entry)).collect()
+        }
+        LoctextFormat::Csv => parse_csv(text)?,
+        LoctextFormat::Po => parse_po(text)?,
+    };
+
+    let mut values = HashMap::new();
+
+    for (line, (key, value)) in entries {
+        if values.contains_key(&key) {
+            return Err(LoctextTextError {
+                line,
+                message: format!("Duplicate key {}", key),
+            });
+        }
+
+        let value = codes.unescape(&value).map_err(|e| LoctextTextError {
+            line,
+            message: format!("{} (in the value of {})", e, key),
+        })?;
+
+        values.insert(key, value);
+    }
+
+    LoctextResource::from_hashmap(values).map_err(|e| LoctextTextError {
+        line: 0,
+        message: e.to_string(),
+    })
+}
+
+/**
+Parses text like [`import`], keeping everything of `existing` which the text formats can't hold,
+which is currently its unknown LSBL section. Use this when the text replaces `existing`, so that
+exporting and importing a resource without changes gives back the same bytes.
+
+# Errors
+- [`LoctextTextError`] if the text can't be imported
+*/
+pub fn import_over(
+    text: &str,
+    format: LoctextFormat,
+    codes: &ControlCodes,
+    existing: &LoctextResource,
+) -> Result<LoctextResource, LoctextTextError> {
+    let mut loctext = import(text, format, codes)?;
+
+    // The section was valid when it was read, so it is still valid to set
+    loctext
+        .set_unknown_section(existing.unknown_section().to_vec())
+        .map_err(|e| LoctextTextError {
+            line: 0,
+            message: e.to_string(),
+        })?;
+
+    Ok(loctext)
+}
+
+pub(crate) fn csv_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// Returns each `(key, value)` row of a CSV file, with the line that it starts on
+fn parse_csv(text: &str) -> Result<Vec<(usize, (String, String))>, LoctextTextError> {
+    let mut rows: Vec<(usize, Vec<String>)> = vec![];
+
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut row_line = 1;
+
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => (),
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+
+                line += 1;
+                row_line = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+
+                field.push(c);
+            }
+        }
+    }
+
+    if in_quotes {
+        return Err(LoctextTextError {
+            line: row_line,
+            message: "Unterminated quoted field".to_string(),
+        });
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+
+    rows.into_iter()
+        // Skip the header and any blank lines
+        .filter(|(_, row)| !(row.len() == 1 && row[0].is_empty()))
+        .skip(1)
+        .map(|(line, row)| match <[String; 2]>::try_from(row) {
+            Ok([key, value]) => Ok((line, (key, value))),
+            Err(row) => Err(LoctextTextError {
+                line,
+                message: format!("Expected 2 columns, found {}", row.len()),
+            }),
+        })
+        .collect()
+}
+
+fn po_quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+fn po_unquote(s: &str) -> Result<String, String> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("Expected a quoted string, found {}", s))?;
+
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('"') => unquoted.push('"'),
+            Some('\\') => unquoted.push('\\'),
+            Some('n') => unquoted.push('\n'),
+            Some('t') => unquoted.push('\t'),
+            other => return Err(format!("Invalid escape \\{}", other.unwrap_or(' '))),
+        }
+    }
+
+    Ok(unquoted)
+}
+
+#[derive(Default)]
+struct PoEntry {
+    line: usize,
+    msgctxt: Option<String>,
+    msgid: String,
+    msgstr: String,
+}
+
+/// Returns each entry of a PO file, keyed by its `msgctxt`
+fn parse_po(text: &str) -> Result<Vec<(usize, (String, String))>, LoctextTextError> {
+    #[derive(Clone, Copy)]
+    enum Field {
+        Msgctxt,
+        Msgid,
+        Msgstr,
+    }
+
+    let mut entries = vec![];
+    let mut current = PoEntry::default();
+    let mut field = None;
+
+    let finish = |entry: PoEntry, entries: &mut Vec<PoEntry>| {
+        // Skip the header, and any entries that aren't loctext keys
+        if entry.msgctxt.is_some() {
+            entries.push(entry);
+        }
+    };
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| LoctextTextError {
+            line: line_number,
+            message,
+        };
+
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) if !keyword.starts_with('"') => (Some(keyword), rest.trim()),
+            _ => (None, line),
+        };
+
+        let value = po_unquote(rest).map_err(error)?;
+
+        match keyword {
+            Some("msgctxt") => {
+                finish(std::mem::take(&mut current), &mut entries);
+                current.line = line_number;
+                current.msgctxt = Some(value);
+                field = Some(Field::Msgctxt);
+            }
+            Some("msgid") => {
+                // Entries without a context start with their msgid
+                if !matches!(field, Some(Field::Msgctxt)) {
+                    finish(std::mem::take(&mut current), &mut entries);
+                    current.line = line_number;
+                }
+
+                current.msgid = value;
+                field = Some(Field::Msgid);
+            }
+            Some("msgstr") => {
+                current.msgstr = value;
+                field = Some(Field::Msgstr);
+            }
+            Some(keyword) => return Err(error(format!("Unsupported keyword {}", keyword))),
+            None => match field {
+                Some(Field::Msgctxt) => current.msgctxt.get_or_insert_default().push_str(&value),
+                Some(Field::Msgid) => current.msgid.push_str(&value),
+                Some(Field::Msgstr) => current.msgstr.push_str(&value),
+                None => return Err(error("String outside of an entry".to_string())),
+            },
+        }
+    }
+
+    finish(current, &mut entries);
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let value = if entry.msgstr.is_empty() {
+                entry.msgid
+            } else {
+                entry.msgstr
+            };
+
+            (entry.line, (entry.msgctxt.unwrap_or_default(), value))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{AssetMetadata, RawAsset, asset::AssetType, testing::BnlFixture};
+
+    #[test]
+    fn loctext_text_roundtrip() -> Result<(), String> {
+        let loctext = LoctextResource::from_hashmap(HashMap::from([
+            (
+                "chaptername__1".to_string(),
+                "The \"First\" Chapter".to_string(),
+            ),
+            ("menu_quit".to_string(), "Quit,\nreally?\\".to_string()),
+            (
+                "menu_start".to_string(),
+                "Press \u{e100} {to} start".to_string(),
+            ),
+        ]))
+        .map_err(|e| e.to_string())?;
+
+        let mut codes = ControlCodes::default();
+        codes
+            .insert("btn:A", '\u{e100}')
+            .map_err(|e| e.to_string())?;
+
+        for format in [LoctextFormat::Json, LoctextFormat::Csv, LoctextFormat::Po] {
+            let text = export(&loctext, format, &codes);
+            assert!(text.contains("Press {btn:A}"), "{}", format);
+
+            let imported =
+                import(&text, format, &codes).map_err(|e| format!("{}: {}", format, e))?;
+
+            assert_eq!(imported.values(), loctext.values(), "{}", format);
+        }
+
+        // Translations in the msgstr replace the original text
+        let translated = export(&loctext, LoctextFormat::Po, &codes).replacen(
+            "msgstr \"\"\n",
+            "msgstr \"Le \\\"Premier\\\" Chapitre\"\n",
+            1,
+        );
+        let imported = import(&translated, LoctextFormat::Po, &codes).map_err(|e| e.to_string())?;
+        assert_eq!(
+            imported.values().get("chaptername__1").map(String::as_str),
+            Some("Le \"Premier\" Chapitre")
+        );
+
+        assert_eq!(
+            import("key,value\na,b\na,c\n", LoctextFormat::Csv, &codes)
+                .err()
+                .map(|e| e.line),
+            Some(3)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_import_keeps_bytes() -> Result<(), String> {
+        let mut original = LoctextResource::from_hashmap(HashMap::from([
+            ("chaptername__1".to_string(), "The Haunting".to_string()),
+            ("chaptername__2".to_string(), "Ghoulies".to_string()),
+        ]))
+        .map_err(|e| e.to_string())?;
+
+        // The section must start with its own size
+        assert!(original.set_unknown_section(vec![8, 0, 0, 0, 1]).is_err());
+        original
+            .set_unknown_section(vec![0xc, 0, 0, 0, 1, 0, 0, 0, 0xd1, 0x01, 0, 0])
+            .map_err(|e| e.to_string())?;
+        let resource = original.dump().map_err(|e| e.to_string())?;
+
+        let bnl = BnlFixture::new()
+            .raw_asset(RawAsset::new(
+                AssetMetadata::new("aid_loctext_test", AssetType::ResLoctext, 0, 0),
+                vec![],
+                Some(vec![resource.clone()]),
+            ))
+            .build();
+
+        let existing = LoctextResource::from_raw_asset(
+            bnl.get_raw_asset("aid_loctext_test")
+                .ok_or("The loctext is missing")?,
+        )
+        .map_err(|e| e.to_string())?;
+        let codes = ControlCodes::default();
+
+        for format in [LoctextFormat::Json, LoctextFormat::Csv, LoctextFormat::Po] {
+            let text = export(&existing, format, &codes);
+
+            let imported = import_over(&text, format, &codes, &existing)
+                .map_err(|e| format!("{}: {}", format, e))?;
+            assert_eq!(imported.dump().map_err(|e| e.to_string())?, resource);
+
+            // Importing on its own loses the unknown section
+            let bare = import(&text, format, &codes).map_err(|e| e.to_string())?;
+            assert_ne!(bare.dump().map_err(|e| e.to_string())?, resource);
+        }
+
+        Ok(())
+    }
+}
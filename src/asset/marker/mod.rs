@@ -6,13 +6,14 @@ use std::{
     path::Path,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 
 use crate::{
     VirtualResource, VirtualResourceError,
     asset::{Asset, AssetDescriptor, AssetParseError},
     d3d::{D3DFormat, LinearColour, StandardFormat, Swizzled},
     game::AssetType,
+    utils::reader::Le,
 };
 
 
@@ -64,9 +65,9 @@ impl AssetDescriptor for Descriptor {
             return Err(AssetParseError::InputTooSmall);
         }
 
-        let mut cur = Cursor::new(&data[..]);
+        let mut reader = Le::new(data);
 
-        let format = match cur.read_u32::<LittleEndian>()? {
+        let format = match reader.u32()? {
             0x00000012 => D3DFormat::Swizzled(Swizzled::B8G8R8A8),
             0x0000003f => D3DFormat::Swizzled(Swizzled::A8B8G8R8),
             0x00000040 => D3DFormat::Linear(LinearColour::A8R8G8B8),
@@ -82,13 +83,9 @@ impl AssetDescriptor for Descriptor {
             }
         };
 
-        let header_size = cur.read_u32::<LittleEndian>()?;
-        let width = cur.read_u16::<LittleEndian>()?;
-        let height = cur.read_u16::<LittleEndian>()?;
-        let flags = cur.read_u32::<LittleEndian>()?;
-        let unknown_3a = cur.read_u32::<LittleEndian>()?;
-        let texture_offset = cur.read_u32::<LittleEndian>()?;
-        let texture_size = cur.read_u32::<LittleEndian>()?;
+        let header_size = reader.u32()?;
+        let [width, height] = reader.array::<u16, 2>()?;
+        let [flags, unknown_3a, texture_offset, texture_size] = reader.array::<u32, 4>()?;
 
         Ok(Descriptor {
             format,
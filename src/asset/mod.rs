@@ -2,19 +2,26 @@ use std::{
     cmp,
     fmt::{self, Display},
     io::{self, Cursor, Read, Write},
-    path::Path,
+    ops::Range,
 };
 
 use crate::{
-    AssetMetadata, DataView, RawAsset, VirtualResource, VirtualResourceError,
-    asset::model::sub_main::SubresourceError,
+    AssetMetadata, BnlVariant, DataView, RawAsset, VirtualResource, VirtualResourceError,
+    asset::{loctext::lsbl::LsblViolation, model::sub_main::SubresourceError},
+    utils::reader::ReadError,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+pub mod fields;
 pub mod param;
 
+mod name;
+pub use name::{AssetName, FixedString, FixedStringError, MAX_ASSET_NAME_LENGTH};
+
+mod any;
+pub use any::AnyAsset;
+
 // pub mod marker;
 pub mod aidlist;
 pub mod anim;
@@ -23,6 +30,7 @@ pub mod cutscene;
 pub mod font;
 pub mod loctext;
 pub mod model;
+pub mod opaque;
 pub mod script;
 pub mod texture;
 
@@ -53,7 +61,8 @@ impl<AL: AssetLike> Asset<AL> {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The views of the buffer section which make up the resource data of an asset, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DataViewList {
     pub(crate) size: u32,
     pub(crate) num_views: u32,
@@ -61,7 +70,23 @@ pub struct DataViewList {
 }
 
 impl DataViewList {
+    pub fn new(views: Vec<DataView>) -> Self {
+        DataViewList {
+            size: (8 + views.len() * size_of::<DataView>()) as u32,
+            num_views: views.len() as u32,
+            views,
+        }
+    }
+
     pub fn from_bytes(view_bytes: &[u8]) -> Result<DataViewList, Box<io::Error>> {
+        Self::from_bytes_as(view_bytes, BnlVariant::default())
+    }
+
+    /// Parses a [`DataViewList`] stored using the byte order of the given [`BnlVariant`]
+    pub fn from_bytes_as(
+        view_bytes: &[u8],
+        variant: BnlVariant,
+    ) -> Result<DataViewList, Box<io::Error>> {
         if view_bytes.len() < 8 {
             return Err(Box::new(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -69,21 +94,20 @@ impl DataViewList {
             )));
         };
 
-        let b = view_bytes[0..4]
-            .try_into()
-            .expect("slice with incorrect length");
-        let size = u32::from_le_bytes(b);
+        let mut cur = Cursor::new(view_bytes);
+
+        let size = variant.read_u32(&mut cur)?;
+        let num_views = variant.read_u32(&mut cur)?;
 
-        let b = view_bytes[4..8]
-            .try_into()
-            .expect("slice with incorrect length");
-        let num_views = u32::from_le_bytes(b);
+        let expected_size = (num_views as usize)
+            .checked_mul(size_of::<DataView>())
+            .and_then(|views_size| views_size.checked_add(8));
 
-        if num_views == 0 || size != num_views * size_of::<DataView>() as u32 + 8 {
+        if num_views == 0 || expected_size != Some(size as usize) {
             return Err(Box::new(io::Error::other("Invalid size.")));
         }
 
-        if view_bytes.len() < num_views as usize * size_of::<DataView>() {
+        if view_bytes.len() < size as usize {
             return Err(
                 io::Error::new(io::ErrorKind::InvalidData, "Input is not large enough.").into(),
             );
@@ -91,18 +115,8 @@ impl DataViewList {
 
         let mut views = Vec::with_capacity(num_views as usize);
 
-        let mut chunks = view_bytes[8..].chunks(size_of::<DataView>());
-
         for _ in 0..num_views {
-            let chunk = chunks.next().unwrap();
-
-            let view_offset = u32::from_le_bytes(chunk[0..4].try_into().unwrap());
-            let view_size = u32::from_le_bytes(chunk[4..8].try_into().unwrap());
-
-            views.push(DataView {
-                offset: view_offset,
-                size: view_size,
-            });
+            views.push(DataView::from_reader_as(&mut cur, variant)?);
         }
 
         Ok(DataViewList {
@@ -124,15 +138,23 @@ impl DataViewList {
             ));
         }
 
-        Ok(self
-            .views
+        self.views
             .iter()
-            .map(|view| -> &[u8] {
+            .map(|view| -> Result<&[u8], io::Error> {
                 let start = view.offset as usize;
                 let end = start + view.size as usize;
-                &data[start..end]
+
+                data.get(start..end).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "DataView [{start}, {end}) is out of bounds for a buffer of {} bytes",
+                            data.len()
+                        ),
+                    )
+                })
             })
-            .collect())
+            .collect()
     }
 
     pub fn write_bytes(
@@ -162,8 +184,9 @@ impl DataViewList {
             let view_size = view.size as usize;
 
             // If this slice is part of the copy in any way
-            let res_slice =
-                &mut resource[view.offset as usize..view.offset as usize + view.size as usize];
+            let res_slice = resource
+                .get_mut(view.offset as usize..view.offset as usize + view_size)
+                .ok_or(VirtualResourceError::OffsetOutOfBounds)?;
 
             let desired_cp_size = write_size - total_written;
             let cp_size = cmp::min(desired_cp_size, view_size);
@@ -187,6 +210,11 @@ impl DataViewList {
     }
 
     pub fn bytes_required(&self) -> usize {
+        self.total_size()
+    }
+
+    /// The combined size of every view, which is the size of the asset's resource data
+    pub fn total_size(&self) -> usize {
         self.views().iter().map(|view| view.size as usize).sum()
     }
 
@@ -202,14 +230,48 @@ impl DataViewList {
         self.size
     }
 
+    /// Whether any view of `self` shares bytes with any view of `other`
     pub fn overlaps(&self, other: &DataViewList) -> bool {
         self.views
             .iter()
-            .zip(&other.views)
-            .any(|(v1, v2)| v1.overlaps(v2))
+            .any(|v1| other.views.iter().any(|v2| v1.overlaps(v2)))
+    }
+
+    /// The ranges of the buffer section which are used by both `self` and `other`, sorted and
+    /// merged
+    pub fn overlapping_ranges(&self, other: &DataViewList) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .views
+            .iter()
+            .flat_map(|v1| other.views.iter().map(move |v2| (v1, v2)))
+            .filter(|(v1, v2)| v1.overlaps(v2))
+            .map(|(v1, v2)| {
+                let (r1, r2) = (v1.as_range::<u32>(), v2.as_range::<u32>());
+
+                r1.start.max(r2.start) as usize..r1.end.min(r2.end) as usize
+            })
+            .collect();
+
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        merged
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_as(BnlVariant::default())
+    }
+
+    /// Serialises the [`DataViewList`] using the byte order of the given [`BnlVariant`]
+    pub fn to_bytes_as(&self, variant: BnlVariant) -> Vec<u8> {
         let num_views = self.views.len();
 
         let size = 8 + 8 * num_views;
@@ -218,13 +280,15 @@ impl DataViewList {
 
         let mut cur = Cursor::new(&mut v[..]);
 
-        cur.write_u32::<LittleEndian>(size as u32).unwrap();
-        cur.write_u32::<LittleEndian>(num_views as u32).unwrap();
+        variant.write_u32(&mut cur, size as u32).unwrap();
+        variant.write_u32(&mut cur, num_views as u32).unwrap();
 
         self.views.iter().for_each(|view| {
-            cur.write_u32::<LittleEndian>(view.offset)
+            variant
+                .write_u32(&mut cur, view.offset)
                 .expect("View offset should've been accounted for.");
-            cur.write_u32::<LittleEndian>(view.size)
+            variant
+                .write_u32(&mut cur, view.size)
                 .expect("View size should've been accounted for.");
         });
 
@@ -242,6 +306,8 @@ pub enum AssetParseError {
     InputTooSmall,
     InvalidDataViews(String),
     FileNotFound(String),
+    /// An LSBL file was built which the game couldn't look strings up in
+    MalformedLsbl(Vec<LsblViolation>),
 }
 
 impl std::error::Error for AssetParseError {}
@@ -254,6 +320,12 @@ impl From<std::io::Error> for AssetParseError {
     }
 }
 
+impl From<ReadError> for AssetParseError {
+    fn from(e: ReadError) -> Self {
+        AssetParseError::InvalidDataViews(e.to_string())
+    }
+}
+
 impl From<SubresourceError> for AssetParseError {
     fn from(_: SubresourceError) -> Self {
         Self::ErrorParsingDescriptor
@@ -271,6 +343,14 @@ impl fmt::Display for AssetParseError {
                 Self::InputTooSmall => "Input too small".to_string(),
                 Self::InvalidDataViews(e) => format!("Invalid data views: {e}"),
                 Self::FileNotFound(e) => format!("File not found: {e}"),
+                Self::MalformedLsbl(violations) => format!(
+                    "Malformed LSBL file: {}",
+                    violations
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
             }
         )
     }
@@ -284,6 +364,87 @@ pub enum AssetError {
     TypeMismatch,
     /// The asset could not be found by name
     NotFound,
+    /// An asset with this name already exists
+    Duplicate(String),
+    /// The edit would change resource data which other assets also use, and the
+    /// [`EditPolicy`](crate::EditPolicy) doesn't allow it
+    SharedData {
+        name: String,
+        shared_with: Vec<String>,
+    },
+    /// A [`AssetError::ParseError`] from reading a specific asset, with which asset it was and
+    /// which stage of parsing failed
+    InAsset {
+        name: String,
+        asset_type: AssetType,
+        stage: AssetStage,
+        error: AssetParseError,
+    },
+}
+
+impl AssetError {
+    pub(crate) fn in_asset(
+        metadata: &AssetMetadata,
+        stage: AssetStage,
+        error: AssetParseError,
+    ) -> Self {
+        AssetError::InAsset {
+            name: metadata.name().to_string(),
+            asset_type: metadata.asset_type(),
+            stage,
+            error,
+        }
+    }
+
+    /// The name of the asset the error came from, if it is known
+    pub fn asset_name(&self) -> Option<&str> {
+        match self {
+            AssetError::Duplicate(name)
+            | AssetError::SharedData { name, .. }
+            | AssetError::InAsset { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The underlying parse error, with or without the context of [`AssetError::InAsset`]
+    pub fn parse_error(&self) -> Option<&AssetParseError> {
+        match self {
+            AssetError::ParseError(error) | AssetError::InAsset { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The step of parsing an asset which failed, see [`AssetError::InAsset`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetStage {
+    /// Reading the descriptor bytes
+    Descriptor,
+    /// Reading the resource data which the descriptor points to. Parsers report this as
+    /// [`AssetParseError::InvalidDataViews`].
+    Resource,
+    /// Building the asset from its descriptor and resource data
+    Construction,
+}
+
+impl AssetStage {
+    /// The stage an error from [`AssetLike::new`] or [`AssetRefLike::new_ref`] happened in
+    pub(crate) fn of_construction(error: &AssetParseError) -> Self {
+        match error {
+            AssetParseError::InvalidDataViews(_) => AssetStage::Resource,
+            _ => AssetStage::Construction,
+        }
+    }
+}
+
+impl Display for AssetStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetStage::Descriptor => write!(f, "descriptor"),
+            AssetStage::Resource => write!(f, "resource data"),
+            AssetStage::Construction => write!(f, "construction"),
+        }
+    }
 }
 
 impl fmt::Display for AssetError {
@@ -293,6 +454,23 @@ impl fmt::Display for AssetError {
             AssetError::ParseError(asset_parse_error) => write!(f, "{asset_parse_error}"),
             AssetError::TypeMismatch => write!(f, "Type mismatch"),
             AssetError::NotFound => write!(f, "Not found"),
+            AssetError::Duplicate(name) => write!(f, "An asset named {} already exists", name),
+            AssetError::SharedData { name, shared_with } => write!(
+                f,
+                "The resource data of {} is shared with {}, so it can't be edited without changing them too",
+                name,
+                shared_with.join(", ")
+            ),
+            AssetError::InAsset {
+                name,
+                asset_type,
+                stage,
+                error,
+            } => write!(
+                f,
+                "Unable to parse {} ({}), failed at the {} stage: {}",
+                name, asset_type, stage, error
+            ),
         }
     }
 }
@@ -303,19 +481,22 @@ impl From<AssetParseError> for AssetError {
     }
 }
 
+#[cfg(feature = "fs")]
 pub trait DumpToDir: Dump {
-    fn dump_to_dir<P: AsRef<Path>>(&self, dump_dir: P) -> Result<(), std::io::Error>;
+    fn dump_to_dir<P: AsRef<std::path::Path>>(&self, dump_dir: P) -> Result<(), std::io::Error>;
 }
 
+#[cfg(feature = "fs")]
 pub trait Dump {
-    fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error>;
+    fn dump<P: AsRef<std::path::Path>>(&self, dump_path: P) -> Result<(), std::io::Error>;
 }
 
 /// Parses a naturally serialised version of an asset. This is NOT used with descriptors, but
 /// instead with any human readable output formats. For example, an AidList resource as a text file
 /// with each AID being on a separate line.
+#[cfg(feature = "fs")]
 pub trait Parse: Sized {
-    fn parse<P: AsRef<Path>>(parse_path: P) -> Result<Self, AssetParseError>;
+    fn parse<P: AsRef<std::path::Path>>(parse_path: P) -> Result<Self, AssetParseError>;
 }
 
 /// Describes how a given asset is structured. Typically, an AssetDescriptor has information about how
@@ -333,6 +514,58 @@ pub trait AssetDescriptor: Sized + Clone {
     fn size(&self) -> usize;
 
     fn asset_type() -> AssetType;
+
+    /// The bytes the descriptor was read from, for descriptors which keep them to write back the
+    /// parts they don't parse. See [`OriginalBytes`].
+    fn original_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/**
+The bytes a descriptor was read from. Descriptors which don't understand every byte they read write
+their known fields over these, so that the rest is written back as it was read rather than as
+zeroes. Descriptors created from scratch have none, and are written over zeroes.
+
+These compare equal whatever they contain, so that descriptors still compare by their fields.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct OriginalBytes(Vec<u8>);
+
+impl OriginalBytes {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The bytes to write a descriptor of `size` bytes over. The parts of a descriptor which has
+    /// changed size can't be matched up with the bytes it was read from, so it is written over
+    /// zeroes instead.
+    pub(crate) fn base(&self, size: usize) -> Vec<u8> {
+        match self.0.len() == size {
+            true => self.0.clone(),
+            false => vec![0; size],
+        }
+    }
+}
+
+impl PartialEq for OriginalBytes {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for OriginalBytes {}
+
+impl std::hash::Hash for OriginalBytes {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
 }
 
 pub trait AssetLike: Sized {
@@ -347,16 +580,63 @@ pub trait AssetLike: Sized {
         Self::Descriptor::asset_type()
     }
 
+    /// Whether assets of `asset_type` can be read as this type. By default, only
+    /// [`AssetLike::asset_type`] is accepted.
+    fn accepts_type(asset_type: AssetType) -> bool {
+        asset_type == Self::asset_type()
+    }
+
     fn get_descriptor(&self) -> Self::Descriptor;
     fn get_resource_chunks(&self) -> Option<Vec<Vec<u8>>>;
 }
 
-pub type AssetName = [u8; 128];
-pub const MAX_ASSET_NAME_LENGTH: usize = size_of::<AssetName>() - 1;
+/// A borrowed counterpart of an [`AssetLike`], which references the resource data of a
+/// [`DecompressedBnl`](crate::DecompressedBnl) rather than copying it. See
+/// [`RawAssetRef::to_asset_ref`](crate::RawAssetRef::to_asset_ref).
+pub trait AssetRefLike<'a>: Sized {
+    type Descriptor: AssetDescriptor;
+
+    fn new_ref(
+        descriptor: &Self::Descriptor,
+        virtual_res: &VirtualResource<'a>,
+    ) -> Result<Self, AssetParseError>;
+
+    fn asset_type() -> AssetType {
+        Self::Descriptor::asset_type()
+    }
+}
+
+/**
+Checks that `descriptor_bytes` can be read as the descriptor of an asset of `asset_type`. Types
+without a descriptor parser are always accepted.
+
+# Errors
+- [`AssetParseError`] from the descriptor parser of `asset_type`
+*/
+pub fn check_descriptor(
+    asset_type: AssetType,
+    descriptor_bytes: &[u8],
+) -> Result<(), AssetParseError> {
+    match asset_type {
+        AssetType::ResTexture => texture::TextureDescriptor::from_bytes(descriptor_bytes).map(drop),
+        AssetType::ResAnim => anim::AnimDescriptor::from_bytes(descriptor_bytes).map(drop),
+        AssetType::ResModel => model::ModelDescriptor::from_bytes(descriptor_bytes).map(drop),
+        AssetType::ResCutscene => {
+            cutscene::CutsceneDescriptor::from_bytes(descriptor_bytes).map(drop)
+        }
+        AssetType::ResAidList => aidlist::AidListDescriptor::from_bytes(descriptor_bytes).map(drop),
+        AssetType::ResXCueList => {
+            cuelist::CueListDescriptor::from_bytes(descriptor_bytes).map(drop)
+        }
+        AssetType::ResFont => font::FontDescriptor::from_bytes(descriptor_bytes).map(drop),
+        AssetType::ResScript => script::ScriptDescriptor::from_bytes(descriptor_bytes).map(drop),
+        _ => Ok(()),
+    }
+}
 
 pub const ASSET_DESCRIPTION_SIZE: usize = 0xa0;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct AssetDescription {
     pub(crate) metadata: AssetMetadata,
 
@@ -497,16 +777,22 @@ impl TryFrom<&str> for AssetType {
 
 impl AssetDescription {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        Self::from_bytes_as(bytes, BnlVariant::default())
+    }
+
+    /// Parses an [`AssetDescription`] stored using the byte order of the given [`BnlVariant`]
+    pub fn from_bytes_as(bytes: &[u8], variant: BnlVariant) -> Result<Self, std::io::Error> {
         let mut cur = Cursor::new(&bytes);
 
-        let mut name: AssetName = [0u8; 0x80];
-        cur.read_exact(&mut name)?;
+        let mut name_bytes = [0u8; size_of::<AssetName>()];
+        cur.read_exact(&mut name_bytes)?;
+        let name = AssetName::from(name_bytes);
 
-        let asset_type = AssetType::try_from(cur.read_u32::<LittleEndian>()?)
+        let asset_type = AssetType::try_from(variant.read_u32(&mut cur)?)
             .map_err(|_| std::io::Error::other("Unable to parse asset type from BNL."))?;
 
-        let unk_1 = cur.read_u32::<LittleEndian>()?;
-        let unk_2 = cur.read_u32::<LittleEndian>()?;
+        let unk_1 = variant.read_u32(&mut cur)?;
+        let unk_2 = variant.read_u32(&mut cur)?;
 
         let metadata = AssetMetadata {
             name,
@@ -517,35 +803,42 @@ impl AssetDescription {
 
         let asset_description = AssetDescription {
             metadata,
-            chunk_count: cur.read_u32::<LittleEndian>()?,
-            descriptor_ptr: cur.read_u32::<LittleEndian>()?,
-            descriptor_size: cur.read_u32::<LittleEndian>()?,
-            dataview_list_ptr: cur.read_u32::<LittleEndian>()?,
-            resource_size: cur.read_u32::<LittleEndian>()?,
+            chunk_count: variant.read_u32(&mut cur)?,
+            descriptor_ptr: variant.read_u32(&mut cur)?,
+            descriptor_size: variant.read_u32(&mut cur)?,
+            dataview_list_ptr: variant.read_u32(&mut cur)?,
+            resource_size: variant.read_u32(&mut cur)?,
         };
 
         Ok(asset_description)
     }
 
     pub fn to_bytes(&self) -> [u8; ASSET_DESCRIPTION_SIZE] {
+        self.to_bytes_as(BnlVariant::default())
+    }
+
+    /// Serialises the [`AssetDescription`] using the byte order of the given [`BnlVariant`]
+    pub fn to_bytes_as(&self, variant: BnlVariant) -> [u8; ASSET_DESCRIPTION_SIZE] {
         let mut bytes = [0x00; ASSET_DESCRIPTION_SIZE];
 
         let mut cur = Cursor::new(&mut bytes[..]);
 
         // Ensure the size of the name is 128 so that we can safely unwrap
         assert_eq!(size_of_val(&self.metadata.name), 0x80);
-        cur.write_all(&self.metadata.name).unwrap();
-
-        cur.write_u32::<LittleEndian>(self.metadata.asset_type.into())
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.metadata.unk_1).unwrap();
-        cur.write_u32::<LittleEndian>(self.metadata.unk_2).unwrap();
-        cur.write_u32::<LittleEndian>(self.chunk_count).unwrap();
-        cur.write_u32::<LittleEndian>(self.descriptor_ptr).unwrap();
-        cur.write_u32::<LittleEndian>(self.descriptor_size).unwrap();
-        cur.write_u32::<LittleEndian>(self.dataview_list_ptr)
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.resource_size).unwrap();
+        cur.write_all(self.metadata.name.as_bytes()).unwrap();
+
+        [
+            self.metadata.asset_type.into(),
+            self.metadata.unk_1,
+            self.metadata.unk_2,
+            self.chunk_count,
+            self.descriptor_ptr,
+            self.descriptor_size,
+            self.dataview_list_ptr,
+            self.resource_size,
+        ]
+        .into_iter()
+        .for_each(|val| variant.write_u32(&mut cur, val).unwrap());
 
         bytes
     }
@@ -672,5 +965,10 @@ mod tests {
         assert!(dvl1.overlaps(&dvl2), "(1) These should overlap.");
         assert!(!dvl2.overlaps(&dvl3), "(2) These should not overlap.");
         assert!(dvl1.overlaps(&dvl4), "(3) These should overlap.");
+
+        // Views are compared regardless of their position in the list
+        assert!(dvl1.overlaps(&dvl3), "(4) These should overlap.");
+        assert_eq!(dvl1.overlapping_ranges(&dvl4), vec![1999..2499]);
+        assert_eq!(dvl1.total_size(), 2000);
     }
 }
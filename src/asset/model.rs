@@ -1,5 +1,6 @@
 pub mod gltf;
 pub mod nd;
+pub mod obj;
 pub mod sub_colliders;
 pub mod sub_main;
 
@@ -9,17 +10,24 @@ use std::{
 };
 
 use binrw::{BinRead, BinReaderExt, binrw};
-use byteorder::{LittleEndian, ReadBytesExt};
-
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::Serialize;
 
 use crate::{
     VirtualResource,
     asset::{
         AssetDescriptor, AssetLike, AssetParseError, AssetType,
-        model::{sub_colliders::CollisionSubresource, sub_main::ModelSubresource},
+        fields::{DescriptorField, DescriptorFields, FieldValue},
+        model::{
+            nd::NdData,
+            obj::ObjWriter,
+            sub_colliders::CollisionSubresource,
+            sub_main::{MESH_BOUNDS_OFFSET, ModelSubresource},
+        },
         texture::{Texture, TextureDescriptor},
     },
+    d3d::D3DPrimitiveType,
+    utils::reader::Le,
 };
 
 #[derive(Debug)]
@@ -29,13 +37,15 @@ pub struct Model {
     // meshes: Vec<Mesh>,
     textures: Vec<Texture>,
     resource: Vec<u8>,
+    /// The size of each resource chunk `resource` was read from, so that they can be split again
+    chunk_sizes: Vec<usize>,
 }
 
 #[binrw]
 #[bw(repr = u32)]
 #[repr(u32)]
 #[br(repr = u32)]
-#[derive(Debug, Clone, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 pub enum ModelSubresType {
     Mesh = 0x00,
     Unknown1 = 0x01,
@@ -61,10 +71,37 @@ pub enum ModelSubresType {
     Unknown21 = 0x15,
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct RawModelSubresource {
+/**
+A subresource whose layout isn't known, kept as the bytes it points to so that they survive
+repacking. Their length isn't stored anywhere, so they are taken to run until the next subresource,
+the footer or the end of the descriptor, whichever comes first.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawModelSubresource {
     subres_type: ModelSubresType,
     subres_param: u32,
+    bytes: Vec<u8>,
+}
+
+impl RawModelSubresource {
+    pub fn subres_type(&self) -> ModelSubresType {
+        self.subres_type
+    }
+
+    /// The value stored alongside the type in the footer, which is the offset of the
+    /// subresource in the descriptor
+    pub fn param(&self) -> u32 {
+        self.subres_param
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The bytes can be changed but not resized, as the descriptor around them can't be rebuilt
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
 }
 
 #[binrw]
@@ -103,7 +140,14 @@ pub struct ModelDescriptor {
     pub model_subresource: Option<ModelSubresource>,
     pub texture_subresource: Vec<TextureDescriptor>,
     pub collision_subresource: Option<CollisionSubresource>,
-    pub other_subresources: Vec<RawModelSubresource>,
+    other_subresources: Vec<RawModelSubresource>,
+
+    /// The offset of `model_subresource` in the descriptor
+    model_subresource_ptr: Option<u32>,
+
+    /// The bytes the descriptor was read from. Subresources can't be rebuilt yet, so the known
+    /// fields are written over these when serialising.
+    raw_bytes: Vec<u8>,
 }
 
 impl ModelDescriptor {
@@ -111,11 +155,101 @@ impl ModelDescriptor {
         self.model_subresource.as_ref()
     }
 
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+    pub fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+    }
+
+    /// The subresources whose layouts aren't known, in the order of the footer
+    pub fn other_subresources(&self) -> &[RawModelSubresource] {
+        &self.other_subresources
+    }
+
+    pub fn other_subresources_mut(&mut self) -> &mut [RawModelSubresource] {
+        &mut self.other_subresources
+    }
+
     pub fn key_value_map(&self) -> Option<&HashMap<String, Vec<u8>>> {
         self.model_subresource
             .iter()
             .find_map(|mesh| (!mesh.key_value_map.is_empty()).then_some(&mesh.key_value_map))
     }
+
+    /**
+    Writes the bounding sphere of the model subresource into `descriptor_bytes`, which must be the
+    bytes this descriptor was read from. Used by [`AssetDescriptor::to_bytes`] to keep the bounds up
+    to date after [`ModelSubresource::recompute_bounds`].
+    */
+    pub fn write_bounds(&self, descriptor_bytes: &mut [u8]) -> Result<(), AssetParseError> {
+        let (Some(model_subresource), Some(ptr)) =
+            (&self.model_subresource, self.model_subresource_ptr)
+        else {
+            return Ok(());
+        };
+
+        let start = ptr as usize + MESH_BOUNDS_OFFSET;
+
+        let floats = descriptor_bytes
+            .get_mut(start..start + 16)
+            .ok_or(AssetParseError::InputTooSmall)?;
+
+        for (bytes, value) in floats
+            .chunks_exact_mut(4)
+            .zip(model_subresource.bounding_sphere().to_floats())
+        {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+impl DescriptorFields for ModelDescriptor {
+    /// The fields of the header, followed by those of the model subresource at its offset
+    fn fields(&self) -> Vec<DescriptorField> {
+        let mut fields = vec![
+            DescriptorField::new("flags", 0x8, FieldValue::U32(self.flags)),
+            DescriptorField::unknown("unknown_u32_1", 0xc, FieldValue::U32(self.unknown_u32_1)),
+            DescriptorField::unknown("unknown_u32_2", 0x14, FieldValue::U32(self.unknown_u32_2)),
+        ];
+
+        if let (Some(model_subresource), Some(ptr)) =
+            (&self.model_subresource, self.model_subresource_ptr)
+        {
+            fields.extend(
+                model_subresource
+                    .fields()
+                    .into_iter()
+                    .map(|field| field.nested("model_subresource.", ptr as usize)),
+            );
+        }
+
+        fields
+    }
+}
+
+impl ModelDescriptor {
+    /// The bytes used to compare descriptors, as the subresources are only partly parsed
+    fn comparison_bytes(&self) -> Vec<u8> {
+        self.to_bytes().unwrap_or_else(|_| self.raw_bytes.clone())
+    }
+}
+
+/// Descriptors are equal when they would be written as the same bytes
+impl PartialEq for ModelDescriptor {
+    fn eq(&self, other: &Self) -> bool {
+        self.comparison_bytes() == other.comparison_bytes()
+    }
+}
+
+impl Eq for ModelDescriptor {}
+
+impl std::hash::Hash for ModelDescriptor {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.comparison_bytes().hash(state);
+    }
 }
 
 impl AssetDescriptor for ModelDescriptor {
@@ -139,34 +273,50 @@ impl AssetDescriptor for ModelDescriptor {
             return Err(AssetParseError::InputTooSmall);
         }
 
+        // The footer and every subresource pointer, including the ones reached through the mesh
+        // and texture lists, which bound the unknown subresources
+        let footer_ptr = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let mut boundaries: Vec<u32> = footer_entries
+            .iter()
+            .map(|header| header.ptr)
+            .chain([footer_ptr, data_size])
+            .collect();
+        let mut unknown_subresources = vec![];
+
         let mut model_subresource = None;
+        let mut model_subresource_ptr = None;
         let mut texture_subresource = vec![];
         let mut collision_subresource = None;
         let mut other_subresources = vec![];
 
+        let reader = Le::new(data);
+
         for ModelSubresHeader { subres_type, ptr } in footer_entries {
-            let mut cur = Cursor::new(data);
-            cur.seek(SeekFrom::Start(ptr.into()))?;
+            let mut subres_reader = reader.at(ptr as usize);
 
             match subres_type {
                 ModelSubresType::Texture => {
-                    let texture_list_count = cur.read_u32::<LittleEndian>()?;
-                    let texture_list_offset = cur.read_u32::<LittleEndian>()?;
+                    let [texture_list_count, texture_list_offset] =
+                        subres_reader.array::<u32, 2>()?;
 
-                    cur.seek(SeekFrom::Start(texture_list_offset as u64))?;
+                    let mut texture_ptrs = reader.at(texture_list_offset as usize);
+                    boundaries.push(texture_list_offset);
 
                     for _ in 0..texture_list_count {
-                        let ptr = cur.read_u32::<LittleEndian>()? as usize;
-                        texture_subresource.push(TextureDescriptor::from_bytes(&data[ptr..])?);
+                        let ptr = texture_ptrs.u32()? as usize;
+                        boundaries.push(ptr as u32);
+                        let texture_bytes = data
+                            .get(ptr..)
+                            .ok_or(AssetParseError::ErrorParsingDescriptor)?;
+
+                        texture_subresource.push(TextureDescriptor::from_bytes(texture_bytes)?);
                     }
                 }
                 ModelSubresType::Mesh => {
-                    cur.seek(SeekFrom::Start(ptr as u64))?;
-
                     let mut mesh_ptrs = Vec::new();
 
                     loop {
-                        let ptr = cur.read_u32::<LittleEndian>()? as usize;
+                        let ptr = subres_reader.u32()? as usize;
                         if ptr == 0 {
                             break;
                         }
@@ -174,8 +324,13 @@ impl AssetDescriptor for ModelDescriptor {
                     }
 
                     for ptr in mesh_ptrs {
-                        // TODO: Bounds check for ptr
-                        model_subresource = Some(ModelSubresource::from_bytes(&data[ptr..])?);
+                        let mesh_bytes = data
+                            .get(ptr..)
+                            .ok_or(AssetParseError::ErrorParsingDescriptor)?;
+
+                        model_subresource = Some(ModelSubresource::from_bytes(mesh_bytes)?);
+                        model_subresource_ptr = Some(ptr as u32);
+                        boundaries.push(ptr as u32);
                     }
                 }
                 ModelSubresType::Collision => {
@@ -205,15 +360,29 @@ impl AssetDescriptor for ModelDescriptor {
                 | ModelSubresType::Unknown18
                 | ModelSubresType::Unknown19
                 | ModelSubresType::Unknown20
-                | ModelSubresType::Unknown21 => {
-                    other_subresources.push(RawModelSubresource {
-                        subres_type,
-                        subres_param: ptr,
-                    });
-                }
+                | ModelSubresType::Unknown21 => unknown_subresources.push((subres_type, ptr)),
             };
         }
 
+        boundaries.sort_unstable();
+
+        for (subres_type, ptr) in unknown_subresources {
+            let end = boundaries
+                .iter()
+                .copied()
+                .find(|&boundary| boundary > ptr)
+                .unwrap_or(data_size);
+
+            other_subresources.push(RawModelSubresource {
+                subres_type,
+                subres_param: ptr,
+                bytes: data
+                    .get(ptr as usize..end as usize)
+                    .ok_or(AssetParseError::ErrorParsingDescriptor)?
+                    .to_vec(),
+            });
+        }
+
         Ok(ModelDescriptor {
             flags,
             unknown_u32_1,
@@ -222,15 +391,47 @@ impl AssetDescriptor for ModelDescriptor {
             other_subresources,
             texture_subresource,
             collision_subresource,
+            model_subresource_ptr,
+            raw_bytes: data.to_vec(),
         })
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
-        todo!()
+        let mut bytes = self.raw_bytes.clone();
+
+        // The unknown subresources go first, so that the fields which are understood always win
+        // if one overlaps them
+        for subresource in &self.other_subresources {
+            let start = subresource.subres_param as usize;
+
+            bytes
+                .get_mut(start..start + subresource.bytes.len())
+                .ok_or(AssetParseError::InputTooSmall)?
+                .copy_from_slice(&subresource.bytes);
+        }
+
+        for (offset, value) in [
+            (0x8, self.flags),
+            (0xc, self.unknown_u32_1),
+            (0x14, self.unknown_u32_2),
+        ] {
+            bytes
+                .get_mut(offset..offset + 4)
+                .ok_or(AssetParseError::InputTooSmall)?
+                .copy_from_slice(&value.to_le_bytes());
+        }
+
+        self.write_bounds(&mut bytes)?;
+
+        Ok(bytes)
     }
 
     fn size(&self) -> usize {
-        todo!()
+        self.raw_bytes.len()
+    }
+
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(&self.raw_bytes)
     }
 
     fn asset_type() -> AssetType {
@@ -255,6 +456,11 @@ impl AssetLike for Model {
             descriptor: descriptor.clone(),
             textures: vec![],
             resource: virtual_res.get_all_bytes(),
+            chunk_sizes: virtual_res
+                .slices()
+                .iter()
+                .map(|slice| slice.len())
+                .collect(),
         };
 
         for subtex_desc in &model.descriptor.texture_subresource {
@@ -280,13 +486,524 @@ impl AssetLike for Model {
     }
 
     fn get_resource_chunks(&self) -> Option<Vec<Vec<u8>>> {
-        Some(vec![self.resource.clone()])
+        let mut start = 0;
+
+        Some(
+            self.chunk_sizes
+                .iter()
+                .map(|&size| {
+                    let chunk = self.resource[start..start + size].to_vec();
+                    start += size;
+                    chunk
+                })
+                .collect(),
+        )
     }
 }
 
 impl Model {
+    pub fn descriptor(&self) -> &ModelDescriptor {
+        &self.descriptor
+    }
+    pub fn descriptor_mut(&mut self) -> &mut ModelDescriptor {
+        &mut self.descriptor
+    }
+
     /// Returns a list of textures if the model has any, and None otherwise.
     pub fn textures(&self) -> Option<&Vec<Texture>> {
         Some(&self.textures)
     }
+
+    /// Summarises the geometry, textures and skeleton of the model without converting it
+    pub fn stats(&self) -> ModelStats {
+        let mut stats = ModelStats {
+            texture_memory: self
+                .descriptor
+                .texture_subresource
+                .iter()
+                .map(|texture| texture.texture_size() as usize)
+                .sum(),
+            ..Default::default()
+        };
+
+        let Some(model_subresource) = self.descriptor.model_subresource() else {
+            return stats;
+        };
+
+        stats.bone_count = model_subresource
+            .skeleton()
+            .map(|skeleton| skeleton.bones().len())
+            .unwrap_or_default();
+
+        let positions = model_subresource.vertex_positions(&self.resource);
+        stats.vertex_count = positions.len();
+
+        for nd in model_subresource
+            .primitives()
+            .iter()
+            .flat_map(|primitive| primitive.heirarchy())
+        {
+            match nd.data.as_ref() {
+                NdData::PushBuffer(push_buffer) | NdData::BGPushBuffer { push_buffer, .. } => {
+                    for draw_call in &push_buffer.draw_calls {
+                        stats.draw_call_count += 1;
+
+                        if !draw_call.is_drawable() {
+                            stats.invalid_draw_call_count += 1;
+                            continue;
+                        }
+
+                        let triangles = push_buffer
+                            .draw_indices(draw_call)
+                            .and_then(|indices| draw_call.prim_type.triangulate(&indices))
+                            .map(|triangles| triangles.len() / 3)
+                            .unwrap_or_default();
+
+                        *stats
+                            .triangles_per_topology
+                            .entry(draw_call.prim_type)
+                            .or_default() += triangles;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        stats.bounding_box = BoundingBox::from_points(&positions);
+
+        stats
+    }
+
+    /// Writes the nd trees of the model subresource as pretty printed JSON, with the offsets and
+    /// pointers of every nd and a summary of its payload. Models without a model subresource are
+    /// written as an empty list.
+    pub fn dump_hierarchy_json(&self) -> serde_json::Result<String> {
+        let primitives = self
+            .descriptor
+            .model_subresource()
+            .map(ModelSubresource::primitives)
+            .unwrap_or_default();
+
+        serde_json::to_string_pretty(primitives)
+    }
+
+    /**
+    Writes the geometry of the model as an OBJ file, for comparing the collision mesh with what is
+    drawn. The render geometry is written as the object `render` (see
+    [`ModelSubresource::render_triangles`]) unless `include_render` is false, and the collision
+    mesh as the object `collision` with a group for each body.
+    */
+    pub fn to_obj(&self, include_render: bool) -> String {
+        let mut obj = ObjWriter::new();
+
+        if let Some(model_subresource) = self
+            .descriptor
+            .model_subresource()
+            .filter(|_| include_render)
+        {
+            let (positions, triangles) = model_subresource.render_triangles(&self.resource);
+
+            obj.object("render");
+            let base = obj.vertices(&positions);
+            obj.faces(base, &triangles);
+        }
+
+        if let Some(collision) = &self.descriptor.collision_subresource {
+            collision.add_to_obj(&mut obj, "collision");
+        }
+
+        obj.finish()
+    }
+
+    /// Recomputes the bounding sphere of the model subresource from its vertices, see
+    /// [`ModelSubresource::recompute_bounds`]
+    pub fn recompute_bounds(&mut self) -> Option<BoundingSphere> {
+        self.descriptor
+            .model_subresource
+            .as_mut()?
+            .recompute_bounds(&self.resource)
+    }
+}
+
+/// An axis aligned box containing a set of points
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl BoundingBox {
+    /// The smallest box containing every point, or None if there are no points
+    pub fn from_points(points: &[[f32; 3]]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+
+        Some(rest.iter().fold(
+            BoundingBox {
+                min: *first,
+                max: *first,
+            },
+            |bounds, point| BoundingBox {
+                min: std::array::from_fn(|i| bounds.min[i].min(point[i])),
+                max: std::array::from_fn(|i| bounds.max[i].max(point[i])),
+            },
+        ))
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        std::array::from_fn(|i| self.max[i] - self.min[i])
+    }
+
+    pub fn centre(&self) -> [f32; 3] {
+        std::array::from_fn(|i| (self.min[i] + self.max[i]) / 2.0)
+    }
+}
+
+/// A sphere containing a set of points, as used by the game for culling
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoundingSphere {
+    pub centre: [f32; 3],
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Reads a sphere stored as (radius, x, y, z)
+    pub fn from_floats(floats: [f32; 4]) -> Self {
+        Self {
+            centre: [floats[1], floats[2], floats[3]],
+            radius: floats[0],
+        }
+    }
+
+    pub fn to_floats(&self) -> [f32; 4] {
+        [self.radius, self.centre[0], self.centre[1], self.centre[2]]
+    }
+
+    /// A sphere around the centre of the bounding box of `points`. This isn't the smallest
+    /// possible sphere, but it always contains every point.
+    pub fn from_points(points: &[[f32; 3]]) -> Option<Self> {
+        let centre = BoundingBox::from_points(points)?.centre();
+
+        let radius = points
+            .iter()
+            .map(|point| {
+                (0..3)
+                    .map(|i| (point[i] - centre[i]).powi(2))
+                    .sum::<f32>()
+                    .sqrt()
+            })
+            .fold(0.0, f32::max);
+
+        Some(Self { centre, radius })
+    }
+
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3)
+            .map(|i| (point[i] - self.centre[i]).powi(2))
+            .sum::<f32>()
+            .sqrt()
+            <= self.radius
+    }
+}
+
+/// A quick summary of a [`Model`], see [`Model::stats`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ModelStats {
+    /// The number of vertices across every vertex buffer
+    pub vertex_count: usize,
+    /// The number of triangles drawn with each topology, once quads and polygons are split
+    pub triangles_per_topology: HashMap<D3DPrimitiveType, usize>,
+    pub draw_call_count: usize,
+    /// Draw calls which are skipped because they couldn't be read, see [`DrawCallValidity`](nd::DrawCallValidity)
+    pub invalid_draw_call_count: usize,
+    /// The total size of the model's texture data, in bytes
+    pub texture_memory: usize,
+    pub bone_count: usize,
+    /// The bounds of every vertex position, or None if the model has no vertices
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl ModelStats {
+    pub fn triangle_count(&self) -> usize {
+        self.triangles_per_topology.values().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        AssetMetadata, BNLFile, RawAsset,
+        asset::model::nd::{ModelReadContext, ModelSlice, Nd},
+    };
+
+    #[test]
+    fn model_descriptor_equality() -> Result<(), String> {
+        let mut bytes: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+        bytes[0x0..0x4].copy_from_slice(&0x18u32.to_le_bytes());
+        bytes[0x4..0x8].copy_from_slice(&0u32.to_le_bytes());
+
+        let descriptor = ModelDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        let mut changed = descriptor.clone();
+        assert_eq!(descriptor, changed);
+
+        changed.set_flags(descriptor.flags() ^ 1);
+        assert_ne!(descriptor, changed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_subresources_kept() -> Result<(), String> {
+        // A footer at 0x18 with a single unknown subresource at 0x100
+        let mut bytes: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+        bytes[0x0..0x4].copy_from_slice(&0x18u32.to_le_bytes());
+        bytes[0x4..0x8].copy_from_slice(&1u32.to_le_bytes());
+        bytes[0x18..0x1c].copy_from_slice(&3u32.to_le_bytes());
+        bytes[0x1c..0x20].copy_from_slice(&0x100u32.to_le_bytes());
+
+        let mut descriptor = ModelDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        let [subresource] = descriptor.other_subresources() else {
+            return Err("Expected one unknown subresource".to_string());
+        };
+        assert_eq!(subresource.subres_type(), ModelSubresType::Unknown3);
+        assert_eq!(subresource.param(), 0x100);
+        assert_eq!(subresource.bytes(), &bytes[0x100..]);
+
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        descriptor.other_subresources_mut()[0].bytes_mut()[0x10] = 0xaa;
+        bytes[0x110] = 0xaa;
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bounds_edited_next_to_unknown_subresource() -> Result<(), String> {
+        // A footer at 0x18 with a mesh list at 0x40 and an unknown subresource at 0x60. The mesh
+        // it lists is at 0x80, after the unknown subresource.
+        let mut bytes = vec![0u8; 0x200];
+        bytes[0x0..0x4].copy_from_slice(&0x18u32.to_le_bytes());
+        bytes[0x4..0x8].copy_from_slice(&2u32.to_le_bytes());
+        bytes[0x18..0x1c].copy_from_slice(&(ModelSubresType::Mesh as u32).to_le_bytes());
+        bytes[0x1c..0x20].copy_from_slice(&0x40u32.to_le_bytes());
+        bytes[0x20..0x24].copy_from_slice(&(ModelSubresType::Unknown3 as u32).to_le_bytes());
+        bytes[0x24..0x28].copy_from_slice(&0x60u32.to_le_bytes());
+        bytes[0x40..0x44].copy_from_slice(&0x80u32.to_le_bytes());
+        bytes[0x60..0x80].fill(0xcc);
+
+        let mut descriptor = ModelDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        // The unknown subresource ends where the mesh starts
+        let [subresource] = descriptor.other_subresources() else {
+            return Err("Expected one unknown subresource".to_string());
+        };
+        assert_eq!(subresource.bytes(), &bytes[0x60..0x80]);
+
+        let sphere = BoundingSphere {
+            centre: [1.0, 2.0, 3.0],
+            radius: 4.0,
+        };
+        descriptor
+            .model_subresource
+            .as_mut()
+            .ok_or("Expected a mesh")?
+            .set_bounding_sphere(sphere);
+
+        let written = descriptor.to_bytes().map_err(|e| e.to_string())?;
+        let start = 0x80 + MESH_BOUNDS_OFFSET;
+
+        for (i, value) in sphere.to_floats().into_iter().enumerate() {
+            assert_eq!(
+                written[start + i * 4..start + i * 4 + 4],
+                value.to_le_bytes()
+            );
+        }
+        assert_eq!(written[0x60..0x80], bytes[0x60..0x80]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn model_modify_preserves_bytes() -> Result<(), String> {
+        // A descriptor without any subresources, padded with bytes that aren't understood
+        let mut descriptor: Vec<u8> = (0..0x200).map(|i| i as u8).collect();
+        descriptor[0x0..0x4].copy_from_slice(&0x18u32.to_le_bytes());
+        descriptor[0x4..0x8].copy_from_slice(&0u32.to_le_bytes());
+
+        let raw = RawAsset::new(
+            AssetMetadata::new("aid_model_test", AssetType::ResModel, 0, 0),
+            descriptor.clone(),
+            Some(vec![vec![0x11; 0x40], vec![0x22; 0x10]]),
+        );
+
+        let mut bnl = BNLFile::default();
+        bnl.append_raw_asset(raw.clone())
+            .map_err(|e| e.to_string())?;
+
+        bnl.modify_asset::<Model, _>("aid_model_test", |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+
+        let written = bnl.get_raw_asset("aid_model_test").ok_or("Asset missing")?;
+        assert_eq!(written.descriptor_bytes(), raw.descriptor_bytes());
+        assert_eq!(written.resource_chunks(), raw.resource_chunks());
+
+        bnl.modify_asset::<Model, _>("aid_model_test", |model| {
+            model.asset_mut().descriptor_mut().set_flags(0xdeadbeef);
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+
+        descriptor[0x8..0xc].copy_from_slice(&0xdeadbeefu32.to_le_bytes());
+
+        let written = bnl.get_raw_asset("aid_model_test").ok_or("Asset missing")?;
+        assert_eq!(written.descriptor_bytes(), &descriptor[..]);
+        assert_eq!(written.resource_chunks(), raw.resource_chunks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mesh_bounding_sphere() {
+        let bytes = include_bytes!("model/nd/test_meshes/test_mesh_0");
+
+        let floats: [f32; 4] = std::array::from_fn(|i| {
+            let start = MESH_BOUNDS_OFFSET + i * 4;
+            f32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+        });
+
+        let sphere = BoundingSphere::from_floats(floats);
+        assert_eq!(sphere.to_floats(), floats);
+
+        let nd = Nd::new(
+            &mut ModelReadContext::new(&Default::default()),
+            ModelSlice {
+                slice: bytes,
+                read_start: 0x34,
+            },
+        )
+        .expect("Unable to create ND");
+
+        let NdData::Skeleton(skeleton) = &*nd.data else {
+            panic!("Expected an ndSkeleton");
+        };
+
+        for bone in skeleton.bones() {
+            assert!(sphere.contains(bone.global_translation));
+        }
+
+        let points = [[0.0, 0.0, 0.0], [2.0, 4.0, -2.0], [1.0, 1.0, 1.0]];
+        let computed = BoundingSphere::from_points(&points).unwrap();
+
+        assert_eq!(computed.centre, [1.0, 2.0, -0.5]);
+        assert!(points.iter().all(|point| computed.contains(*point)));
+        assert_eq!(BoundingSphere::from_points(&[]), None);
+    }
+
+    #[test]
+    fn bounding_box_from_points() {
+        let points = [[0.0, 0.0, 0.0], [2.0, 4.0, -2.0], [1.0, 1.0, 1.0]];
+        let bounds = BoundingBox::from_points(&points).unwrap();
+
+        assert_eq!(bounds.min, [0.0, 0.0, -2.0]);
+        assert_eq!(bounds.max, [2.0, 4.0, 1.0]);
+        assert_eq!(bounds.size(), [2.0, 4.0, 3.0]);
+        assert_eq!(bounds.centre(), [1.0, 2.0, -0.5]);
+
+        assert_eq!(
+            BoundingBox::from_points(&points[..1]).map(|bounds| bounds.size()),
+            Some([0.0; 3])
+        );
+        assert_eq!(BoundingBox::from_points(&[]), None);
+    }
+
+    #[test]
+    fn model_stats() -> Result<(), String> {
+        // An ndPushBuffer with its name at 0x40, and 3 draws with data pointers at 0x50, types at
+        // 0x5c and counts at 0x68. The indices 0, 1, 2, 3 are stored at 0x74.
+        let mut nd_bytes = vec![0u8; 0x7c];
+        nd_bytes[0x00..0x04].copy_from_slice(&0x40u32.to_le_bytes());
+        nd_bytes[0x40..0x4c].copy_from_slice(b"ndPushBuffer");
+        nd_bytes[0x20..0x24].copy_from_slice(&3u32.to_le_bytes());
+        nd_bytes[0x30..0x34].copy_from_slice(&0x50u32.to_le_bytes());
+        nd_bytes[0x34..0x38].copy_from_slice(&0x5cu32.to_le_bytes());
+        nd_bytes[0x38..0x3c].copy_from_slice(&0x68u32.to_le_bytes());
+
+        for (i, index) in (0u16..4).enumerate() {
+            nd_bytes[0x74 + i * 2..0x76 + i * 2].copy_from_slice(&index.to_le_bytes());
+        }
+
+        // A triangle, a strip of one triangle and a list which is out of bounds
+        let draws: [(u32, u32, u32); 3] = [(0x74, 5, 3), (0x76, 6, 3), (0x1000, 5, 3)];
+
+        for (i, (data_ptr, prim_type, num_vertices)) in draws.iter().enumerate() {
+            nd_bytes[0x50 + i * 4..0x54 + i * 4].copy_from_slice(&data_ptr.to_le_bytes());
+            nd_bytes[0x5c + i * 4..0x60 + i * 4].copy_from_slice(&prim_type.to_le_bytes());
+            nd_bytes[0x68 + i * 4..0x6c + i * 4].copy_from_slice(&num_vertices.to_le_bytes());
+        }
+
+        let push_buffer = Nd::new(
+            &mut ModelReadContext::new(&Default::default()),
+            ModelSlice {
+                slice: &nd_bytes,
+                read_start: 0,
+            },
+        )
+        .map_err(|e| format!("{:?}", e))?;
+
+        // A descriptor without any subresources, which are then filled in directly
+        let mut descriptor_bytes = vec![0u8; 0x200];
+        descriptor_bytes[0x0..0x4].copy_from_slice(&0x18u32.to_le_bytes());
+
+        let mut descriptor =
+            ModelDescriptor::from_bytes(&descriptor_bytes).map_err(|e| e.to_string())?;
+
+        let texture =
+            TextureDescriptor::from_bytes(include_bytes!("test_data/texture0_descriptor"))
+                .map_err(|e| e.to_string())?;
+        let texture_size = texture.texture_size() as usize;
+
+        descriptor.texture_subresource = vec![texture.clone(), texture];
+        descriptor.model_subresource = Some(ModelSubresource {
+            unknown1: 0,
+            unknown2: 0,
+            primitive_ptrs_start: 0,
+            primitive_count: 1,
+            key_values_ptr: 0,
+            unknown3: 0,
+            floats: [0.0; 4],
+            primitives: vec![push_buffer],
+            key_value_map: Default::default(),
+        });
+
+        let model = Model {
+            descriptor,
+            textures: vec![],
+            resource: vec![],
+            chunk_sizes: vec![],
+        };
+
+        let stats = model.stats();
+
+        assert_eq!(stats.draw_call_count, 3);
+        assert_eq!(stats.invalid_draw_call_count, 1);
+        assert_eq!(
+            stats.triangles_per_topology,
+            HashMap::from([
+                (D3DPrimitiveType::TriangleList, 1),
+                (D3DPrimitiveType::TriangleStrip, 1)
+            ])
+        );
+        assert_eq!(stats.triangle_count(), 2);
+        assert_eq!(stats.texture_memory, texture_size * 2);
+
+        // Without any vertex buffers there are no vertices to bound
+        assert_eq!(stats.vertex_count, 0);
+        assert_eq!(stats.bone_count, 0);
+        assert_eq!(stats.bounding_box, None);
+
+        Ok(())
+    }
 }
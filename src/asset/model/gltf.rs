@@ -1,28 +1,61 @@
-use std::{
-    collections::HashMap,
-    path::{self, Path},
-};
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::{self, Path};
 
 use gltf_writer::gltf::{self, Gltf, GltfIndex, serialisation::GltfExportType};
 
 use crate::{
     VirtualResource,
     asset::{
-        AssetLike, AssetParseError, Dump,
+        AssetLike, AssetParseError,
         model::{
             ModelDescriptor,
-            nd::{Nd, NdData, res_view::VertexBufferViewType},
+            nd::{Nd, NdData, SkippedDraw, res_view::VertexBufferViewType},
         },
         texture::Texture,
     },
 };
 
+#[cfg(feature = "fs")]
+use crate::asset::Dump;
+
+/// How the textures of a model are stored when it is converted to glTF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GltfTextureMode {
+    /// Each texture is written as a PNG next to the exported .gltf and referenced by uri
+    #[default]
+    External,
+    /// Each texture is stored as a PNG in a buffer of the glTF document itself
+    Embedded,
+}
+
+/// How a model is converted to glTF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GltfOptions {
+    pub texture_mode: GltfTextureMode,
+    /// Exports the views believed to hold normals and vertex colours as `NORMAL` and `COLOR_0`.
+    /// Their encodings are guessed (see [`VertexBufferResourceView::decode_normals`]), so this is
+    /// off by default.
+    ///
+    /// [`VertexBufferResourceView::decode_normals`]:
+    ///     crate::asset::model::nd::res_view::VertexBufferResourceView::decode_normals
+    pub guessed_attributes: bool,
+}
+
 #[derive(Debug)]
 pub struct GLTFModel {
     descriptor: ModelDescriptor,
 
     // subresource_descriptors: Vec<ModelSubresourceDescriptor>,
     gltf: Gltf,
+
+    /// PNGs to be written alongside the glTF, as (uri, bytes)
+    external_images: Vec<(String, Vec<u8>)>,
+
+    skipped_draws: Vec<SkippedDraw>,
+
+    /// The resource chunks the model was converted from, which are written back unchanged
+    resource_chunks: Vec<Vec<u8>>,
 }
 
 impl GLTFModel {
@@ -30,11 +63,77 @@ impl GLTFModel {
         &self.gltf
     }
 
+    /// The draw calls which were left out of the glTF because they couldn't be drawn
+    pub fn skipped_draws(&self) -> &[SkippedDraw] {
+        &self.skipped_draws
+    }
+
+    /// Converts a model to glTF, storing its textures according to `texture_mode`. Materials are
+    /// named after `model_name`, usually the model's aid, or after their texture index without one.
+    /// [`AssetLike::new`] uses [`GltfTextureMode::External`] and no name.
+    pub fn new_with_texture_mode(
+        descriptor: &ModelDescriptor,
+        virtual_res: &VirtualResource,
+        texture_mode: GltfTextureMode,
+        model_name: Option<&str>,
+    ) -> Result<Self, AssetParseError> {
+        let options = GltfOptions {
+            texture_mode,
+            ..Default::default()
+        };
+
+        Self::new_with_options(descriptor, virtual_res, options, model_name)
+    }
+
+    /// Converts a model to glTF like [`GLTFModel::new_with_texture_mode`], with every option of
+    /// [`GltfOptions`]
+    pub fn new_with_options(
+        descriptor: &ModelDescriptor,
+        virtual_res: &VirtualResource,
+        options: GltfOptions,
+        model_name: Option<&str>,
+    ) -> Result<Self, AssetParseError> {
+        let mut gltf = Gltf::default();
+        let mut external_images = vec![];
+
+        let added = add_model_to_gltf(
+            &mut gltf,
+            descriptor,
+            virtual_res,
+            options,
+            "",
+            model_name,
+            &mut external_images,
+        )?;
+
+        for (i, root_index) in added.root_nodes.into_iter().enumerate() {
+            let mut scene = gltf::Scene::new(format!("model_{}", i + 1));
+            scene.add_node(root_index);
+            gltf.add_scene(scene);
+        }
+
+        gltf.prepare_for_export()
+            .map_err(|e| AssetParseError::InvalidDataViews(format!("{:?}", e)))?;
+
+        Ok(Self {
+            descriptor: descriptor.clone(),
+            gltf,
+            external_images,
+            skipped_draws: added.skipped_draws,
+            resource_chunks: virtual_res
+                .slices()
+                .iter()
+                .map(|slice| slice.to_vec())
+                .collect(),
+        })
+    }
+
     pub fn to_gltf_bytes(&self) -> serde_json::Result<Vec<u8>> {
         serde_json::to_vec_pretty(&self.gltf)
     }
 }
 
+#[cfg(feature = "fs")]
 impl Dump for GLTFModel {
     fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
         let export_path = path::absolute(dump_path.as_ref())?;
@@ -43,27 +142,228 @@ impl Dump for GLTFModel {
             .export(&export_path, GltfExportType::JSON)
             .map_err(|e| std::io::Error::other(format!("Error dumping GLTF model: {:?}", e)))?;
 
+        // Write the textures next to the .gltf so that their uris resolve
+        if let Some(parent) = export_path.parent() {
+            for (uri, png) in &self.external_images {
+                std::fs::write(parent.join(uri), png)?;
+            }
+        }
+
         Ok(())
     }
 }
 
+/// What [`add_model_to_gltf`] added to a glTF document
+#[derive(Debug, Clone, Default)]
+pub struct AddedModel {
+    /// One root node per model subresource, named `{name_prefix}model_{n}`
+    pub root_nodes: Vec<GltfIndex>,
+    /// The draw calls which were left out because they couldn't be drawn
+    pub skipped_draws: Vec<SkippedDraw>,
+}
+
+/**
+Adds the textures and nd trees of a model to an existing glTF document, without adding any
+scenes. Returns the root node of each model subresource, and any draw calls which were skipped.
+
+Image uris are also prefixed with `name_prefix` so that multiple models can be exported next to
+each other. When using [`GltfTextureMode::External`], the PNGs which need to be written alongside
+the document are appended to `external_images` as (uri, bytes). See [`GltfOptions`] for the other
+options.
+
+Materials are named after `model_name`, usually the model's aid, falling back to the index of their
+texture when the model has no name.
+*/
+pub fn add_model_to_gltf(
+    gltf: &mut Gltf,
+    descriptor: &ModelDescriptor,
+    virtual_res: &VirtualResource,
+    options: GltfOptions,
+    name_prefix: &str,
+    model_name: Option<&str>,
+    external_images: &mut Vec<(String, Vec<u8>)>,
+) -> Result<AddedModel, AssetParseError> {
+    let mut pngs = Vec::with_capacity(descriptor.texture_subresource.len());
+
+    // Load all textures first, because we need to assign them based on index
+    for tex_desc in &descriptor.texture_subresource {
+        let image_bytes = virtual_res
+            .get_bytes(
+                tex_desc.texture_offset() as usize,
+                tex_desc.texture_size() as usize,
+            )
+            .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))?;
+
+        let tex = Texture::new(tex_desc.clone(), image_bytes);
+        let rgba_image = tex.to_rgba_image()?;
+
+        let mut png = vec![];
+        rgba_image
+            .dump_png_bytes(&mut png)
+            .map_err(|e| AssetParseError::InvalidDataViews(format!("{:?}", e)))?;
+
+        pngs.push(png);
+    }
+
+    // Embedded images all share one buffer, each padded to 4 bytes, with a view per image
+    let mut image_offsets = Vec::with_capacity(pngs.len());
+    let image_buffer_index = match options.texture_mode {
+        GltfTextureMode::Embedded if !pngs.is_empty() => {
+            let mut image_buffer = vec![];
+
+            for png in &pngs {
+                image_offsets.push(image_buffer.len());
+                image_buffer.extend_from_slice(png);
+                image_buffer.resize(image_buffer.len().next_multiple_of(4), 0);
+            }
+
+            Some(gltf.add_buffer(gltf::Buffer::new(&image_buffer)))
+        }
+        _ => None,
+    };
+
+    let mut texture_base = None;
+
+    for (i, png) in pngs.into_iter().enumerate() {
+        let image = match (options.texture_mode, image_buffer_index) {
+            (GltfTextureMode::Embedded, Some(buffer_index)) => {
+                let view_index = gltf.add_buffer_view(gltf::BufferView {
+                    buffer_index,
+                    byte_offset: image_offsets[i],
+                    byte_length: png.len(),
+                    byte_stride: None,
+                    target: None,
+                });
+
+                gltf::Image {
+                    uri: None,
+                    data: vec![],
+                    name: format!("Image {}", i),
+                    mime_type: Some("image/png".to_string()),
+                    buffer_view_index: Some(view_index),
+                }
+            }
+            _ => {
+                // The PNG is only written next to the document, so the image is just its uri
+                let uri = format!("{}image{}.png", name_prefix, i);
+                external_images.push((uri.clone(), png));
+
+                gltf::Image {
+                    uri: Some(uri),
+                    data: vec![],
+                    name: format!("Image {}", i),
+                    mime_type: None,
+                    buffer_view_index: None,
+                }
+            }
+        };
+
+        let image_index = gltf.add_image(image);
+
+        let texture_index = gltf.add_texture(gltf::Texture {
+            image_index: Some(image_index),
+            name: format!("{}texture{}", name_prefix, i),
+        });
+
+        texture_base.get_or_insert(texture_index);
+    }
+
+    let mut ctx = NdGltfContext {
+        gltf: std::mem::take(gltf),
+        key_value_map: descriptor.key_value_map().cloned().unwrap_or_default(),
+        texture_base: texture_base.unwrap_or_default(),
+        num_textures: descriptor.texture_subresource.len() as u32,
+        name_prefix: name_prefix.to_string(),
+        model_name: model_name.map(str::to_string),
+        guessed_attributes: options.guessed_attributes,
+        ..Default::default()
+    };
+
+    let mut root_nodes = vec![];
+
+    for (i, mesh_desc) in descriptor.model_subresource.iter().enumerate() {
+        // Every top level nd (and its siblings) is parented to a single root per mesh
+        let root_index = ctx.gltf.add_node(gltf::Node::new(Some(format!(
+            "{}model_{}",
+            name_prefix,
+            i + 1
+        ))));
+        ctx.push_node(root_index);
+        ctx.push_accessor_scope();
+
+        let res = mesh_desc
+            .primitives
+            .iter()
+            .try_for_each(|nd| insert_into_gltf_heirarchy(nd, virtual_res, &mut ctx).map(|_| ()));
+
+        ctx.pop_accessor_scope();
+        ctx.pop_node();
+
+        if let Err(e) = res {
+            *gltf = ctx.gltf;
+            return Err(e);
+        }
+
+        root_nodes.push(root_index);
+    }
+
+    *gltf = ctx.gltf;
+
+    Ok(AddedModel {
+        root_nodes,
+        skipped_draws: ctx.skipped_draws,
+    })
+}
+
+/// The accessors of the vertex buffer which push buffers draw from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VertexAccessors {
+    pub(crate) positions: Option<GltfIndex>,
+    /// The number of vertices behind `positions`
+    pub(crate) vertex_count: Option<usize>,
+    pub(crate) uv: Option<GltfIndex>,
+    pub(crate) skin: Option<GltfIndex>,
+    pub(crate) skin_weight: Option<GltfIndex>,
+    pub(crate) normal: Option<GltfIndex>,
+    pub(crate) colour: Option<GltfIndex>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct NdGltfContext {
     pub(crate) key_value_map: HashMap<String, Vec<u8>>,
 
     pub(crate) gltf: Gltf,
-    pub(crate) positions_accessor: Option<GltfIndex>,
-    pub(crate) uv_accessor: Option<GltfIndex>,
-    pub(crate) skin_accessor: Option<GltfIndex>,
-    pub(crate) skin_weight_accessor: Option<GltfIndex>,
-    pub(crate) normal_accessor: Option<GltfIndex>,
+
+    /// The accessors of the last vertex buffer seen among the current nd's ancestors and their
+    /// earlier siblings
+    pub(crate) accessors: VertexAccessors,
+    /// The accessors to restore as each nd's children are left, see
+    /// [`NdGltfContext::push_accessor_scope`]
+    pub(crate) accessor_stack: Vec<VertexAccessors>,
 
     pub(crate) current_skin: Option<GltfIndex>,
 
     pub(crate) current_material: Option<GltfIndex>,
-    pub(crate) current_scene: GltfIndex,
+
+    /// The gltf index of the model's first texture, and the number of textures the model has
+    pub(crate) texture_base: GltfIndex,
+    pub(crate) num_textures: u32,
+
+    /// Prepended to the names of unnamed materials, to keep them unique when several models share
+    /// a gltf
+    pub(crate) name_prefix: String,
+    /// The name materials are named after, usually the model's aid
+    pub(crate) model_name: Option<String>,
+    /// See [`GltfOptions::guessed_attributes`]
+    pub(crate) guessed_attributes: bool,
+
+    /// Materials already added to the gltf, keyed by texture index and shader constants hash
+    pub(crate) materials: HashMap<(u32, u64), GltfIndex>,
 
     pub(crate) node_stack: Vec<GltfIndex>,
+
+    /// Draw calls which were left out of the gltf, see [`AddedModel::skipped_draws`]
+    pub(crate) skipped_draws: Vec<SkippedDraw>,
 }
 
 impl NdGltfContext {
@@ -84,6 +384,19 @@ impl NdGltfContext {
         None
     }
 
+    /// Saves the current accessors before visiting the children of an nd, so that a vertex buffer
+    /// among them only applies to the push buffers of that subtree
+    pub fn push_accessor_scope(&mut self) {
+        self.accessor_stack.push(self.accessors);
+    }
+
+    /// Restores the accessors saved by the matching [`NdGltfContext::push_accessor_scope`]
+    pub fn pop_accessor_scope(&mut self) {
+        if let Some(accessors) = self.accessor_stack.pop() {
+            self.accessors = accessors;
+        }
+    }
+
     pub fn current_node(&mut self) -> Option<&mut gltf::Node> {
         match self.node_stack.last() {
             Some(index) => self.gltf.nodes_mut().get_mut(*index as usize),
@@ -91,6 +404,64 @@ impl NdGltfContext {
         }
     }
 
+    /// Returns the material for a texture and set of shader constants, adding it to the gltf if
+    /// an identical one hasn't been added yet. Returns `None` if the texture doesn't exist.
+    pub fn get_or_add_material(
+        &mut self,
+        texture_index: u32,
+        constants_hash: u64,
+    ) -> Option<GltfIndex> {
+        if texture_index >= self.num_textures {
+            return None;
+        }
+
+        if let Some(material_index) = self.materials.get(&(texture_index, constants_hash)) {
+            return Some(*material_index);
+        }
+
+        // Nds are named after their type, so the only name a material can take is its model's.
+        // Without one, materials are told apart by texture, and those sharing a texture but with
+        // different constants get a numbered suffix.
+        let name = match &self.model_name {
+            Some(model_name) => match self.materials.len() {
+                0 => format!("{}_material", model_name),
+                n => format!("{}_material_{}", model_name, n),
+            },
+            None => {
+                let num_variants = self
+                    .materials
+                    .keys()
+                    .filter(|(tex, _)| *tex == texture_index)
+                    .count();
+
+                match num_variants {
+                    0 => format!("{}texture{}_material", self.name_prefix, texture_index),
+                    n => format!(
+                        "{}texture{}_material_{}",
+                        self.name_prefix, texture_index, n
+                    ),
+                }
+            }
+        };
+
+        let material_index = self.gltf.add_material(gltf::Material {
+            name,
+            pbr_metallic_roughness: Some(gltf::PBRMetallicRoughness {
+                base_color_texture: Some(gltf::TextureInfo {
+                    texture_index: self.texture_base + texture_index,
+                    texcoords_accessor: None,
+                }),
+                metallic_factor: Some(0.0),
+                ..Default::default()
+            }),
+        });
+
+        self.materials
+            .insert((texture_index, constants_hash), material_index);
+
+        Some(material_index)
+    }
+
     pub fn current_node_index(&self) -> Option<GltfIndex> {
         if self.node_stack.is_empty() {
             return None;
@@ -111,89 +482,11 @@ impl AssetLike for GLTFModel {
         descriptor: &Self::Descriptor,
         virtual_res: &VirtualResource,
     ) -> Result<Self, AssetParseError> {
-        let mut gltf = Gltf::default();
-
-        // Load all textures first, because we need to assign them based on index
-        for (i, tex_desc) in descriptor.texture_subresource.iter().enumerate() {
-            let image_bytes = virtual_res
-                .get_bytes(
-                    tex_desc.texture_offset() as usize,
-                    tex_desc.texture_size() as usize,
-                )
-                .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))?;
-
-            let tex = Texture::new(tex_desc.clone(), image_bytes);
-            let rgba_image = tex.to_rgba_image()?;
-
-            let mut png = vec![];
-            rgba_image
-                .dump_png_bytes(&mut png)
-                .map_err(|e| AssetParseError::InvalidDataViews(format!("{:?}", e)))?;
-
-            let image_index = gltf.add_image(gltf::Image {
-                uri: Some(format!("image{}.png", i)),
-                data: png,
-                name: format!("Image {}", i),
-                // Empty values
-                mime_type: None,
-                buffer_view_index: None,
-            });
-
-            gltf.add_texture(gltf::Texture {
-                image_index: Some(image_index),
-                name: format!("texture{}", i),
-            });
-        }
-
-        /*
-
-            let material_index = gltf.add_material(Material {
-                name: format!("material{}", i),
-                pbr_metallic_roughness: Some(PBRMetallicRoughness {
-                    base_color_texture: TextureInfo {
-                        texture_index,
-                        texcoords_accessor: todo!(),
-                    },
-                }),
-            });
-        */
-
-        let mut ctx = NdGltfContext {
-            gltf,
-            key_value_map: descriptor.key_value_map().cloned().unwrap_or_default(),
-            ..Default::default()
-        };
-
-        for (i, mesh_desc) in descriptor.model_subresource.iter().enumerate() {
-            let scene_name = format!("model_{}", i + 1);
-
-            let mut scene = gltf::Scene::new(scene_name);
-
-            ctx.current_scene = ctx.gltf.scenes().len() as u32;
-
-            for nd in &mesh_desc.primitives {
-                println!("FOUND ND");
-
-                if let Some(new_index) = insert_into_gltf_heirarchy(nd, virtual_res, &mut ctx)? {
-                    scene.add_node(new_index);
-                }
-            }
-            ctx.gltf.add_scene(scene);
-        }
-
-        ctx.gltf
-            .prepare_for_export()
-            .map_err(|e| AssetParseError::InvalidDataViews(format!("{:?}", e)))?;
-
-        Ok(Self {
-            descriptor: descriptor.clone(),
-            gltf: ctx.gltf,
-        })
+        Self::new_with_texture_mode(descriptor, virtual_res, GltfTextureMode::default(), None)
     }
 
     fn get_resource_chunks(&self) -> Option<Vec<Vec<u8>>> {
-        // TODO: Create this function
-        todo!();
+        Some(self.resource_chunks.clone())
     }
 }
 
@@ -203,21 +496,26 @@ pub fn create_gltf_node(
     ctx: &mut NdGltfContext,
 ) -> Result<Option<GltfIndex>, AssetParseError> {
     match nd.data.as_ref() {
-        NdData::Skeleton { bones } => {
+        NdData::Skeleton(skeleton) => {
             if ctx.current_skin.is_some() {
                 return Err(AssetParseError::ErrorParsingDescriptor);
             }
 
             let skeleton_index = ctx
                 .gltf
-                .add_node(gltf::Node::new(Some(nd.nd_type().to_string())));
+                .add_node(gltf::Node::new(Some(nd.name().to_string())));
 
             let root_index = ctx.gltf.add_node(gltf::Node::new(Some("BASE".to_string())));
+            ctx.gltf
+                .nodes_mut()
+                .get_mut(skeleton_index as usize)
+                .ok_or(AssetParseError::ErrorParsingDescriptor)?
+                .add_child(root_index);
 
             let mut new_skin = gltf::Skin::default();
             new_skin.joints.push(root_index);
 
-            for (i, bone) in bones.iter().enumerate().skip(1) {
+            for (i, bone) in skeleton.bones().iter().enumerate().skip(1) {
                 // If bone doesn't match expected index
                 if bone.id as usize != i {
                     return Err(AssetParseError::InvalidDataViews(format!(
@@ -235,7 +533,7 @@ pub fn create_gltf_node(
                     bone.name.clone().unwrap_or(format!("unnamed_joint_{i}")),
                 ));
                 bone_node.set_transform(Some(gltf::NodeTransform::TRS(
-                    bone.local_transform,
+                    bone.local_translation,
                     [0f32, 0f32, 0f32],
                     [1f32, 1f32, 1f32],
                 )));
@@ -280,7 +578,14 @@ pub fn create_gltf_node(
                         )
                     });
 
-            let res_size = (max - min) as usize;
+            let res_size = max.saturating_sub(min) as usize;
+
+            if res_size == 0 {
+                return Ok(None);
+            }
+
+            // Push buffers after this draw from this vertex buffer alone
+            ctx.accessors = VertexAccessors::default();
 
             let res_bytes = virtual_res
                 .get_bytes(min as usize, res_size)
@@ -289,11 +594,66 @@ pub fn create_gltf_node(
             let gb = gltf::Buffer::new(&res_bytes);
             let buffer_index = ctx.gltf.add_buffer(gb);
 
+            // Normals and colours are decoded to floats, so are only attached to meshes once the
+            // number of vertices is known to match
+            let mut normals = None;
+            let mut colours = None;
+
             for res_view in resource_views {
                 if res_view.is_empty() {
                     continue;
                 }
 
+                let view_type = res_view.view_type();
+
+                if matches!(
+                    view_type,
+                    VertexBufferViewType::Unknown10 | VertexBufferViewType::Unknown11
+                ) {
+                    if !ctx.guessed_attributes {
+                        continue;
+                    }
+
+                    let view_bytes = res_bytes
+                        .get((res_view.start() - min) as usize..(res_view.end() - min) as usize)
+                        .unwrap_or_default();
+
+                    let decoded = match view_type {
+                        VertexBufferViewType::Unknown10 => res_view
+                            .decode_normals(view_bytes)
+                            .filter(|_| normals.is_none())
+                            .map(|decoded| {
+                                let index = add_f32_accessor(
+                                    ctx,
+                                    &decoded,
+                                    gltf::AccessorComponentCount::VEC3,
+                                );
+                                normals = Some((index, decoded.len()));
+                            }),
+                        _ => res_view
+                            .decode_colours(view_bytes)
+                            .filter(|_| colours.is_none())
+                            .map(|decoded| {
+                                let index = add_f32_accessor(
+                                    ctx,
+                                    &decoded,
+                                    gltf::AccessorComponentCount::VEC4,
+                                );
+                                colours = Some((index, decoded.len()));
+                            }),
+                    };
+
+                    if decoded.is_none() {
+                        eprintln!(
+                            "Unable to decode {:?} view with stride {}.",
+                            view_type,
+                            res_view.stride()
+                        );
+                    }
+
+                    continue;
+                }
+
                 let buffer_view_index = ctx.gltf.add_buffer_view(gltf::BufferView::new(
                     buffer_index,
                     res_view.start() as usize,
@@ -303,7 +663,7 @@ pub fn create_gltf_node(
                 ));
 
                 if res_view.view_type() == VertexBufferViewType::Vertex
-                    && ctx.positions_accessor.is_none()
+                    && ctx.accessors.positions.is_none()
                 {
                     let accessor_index = ctx.gltf.add_accessor(gltf::Accessor::new(
                         buffer_view_index,
@@ -313,12 +673,13 @@ pub fn create_gltf_node(
                         gltf::AccessorComponentCount::VEC3,
                     ));
 
-                    ctx.positions_accessor = Some(accessor_index);
+                    ctx.accessors.positions = Some(accessor_index);
+                    ctx.accessors.vertex_count = Some(res_view.num_entries());
                 } else {
                     match res_view.add_to_gltf(&mut ctx.gltf, buffer_view_index) {
                         Ok(accessor_index) => {
                             if res_view.view_type() == VertexBufferViewType::UV
-                                && ctx.uv_accessor.is_none()
+                                && ctx.accessors.uv.is_none()
                             {
                                 /*
                                 let accessor_index = ctx.gltf.add_accessor(gltf::Accessor::new(
@@ -330,14 +691,14 @@ pub fn create_gltf_node(
                                 ));
                                 */
 
-                                ctx.uv_accessor = Some(accessor_index);
+                                ctx.accessors.uv = Some(accessor_index);
                             } else if res_view.view_type() == VertexBufferViewType::Skin {
-                                ctx.skin_accessor = Some(accessor_index)
+                                ctx.accessors.skin = Some(accessor_index)
                             } else if res_view.view_type() == VertexBufferViewType::SkinWeight {
-                                ctx.skin_weight_accessor = Some(accessor_index)
+                                ctx.accessors.skin_weight = Some(accessor_index)
                             } /*
-                            else if res_view.view_type() == VertexBufferViewType::Normal {
-                            ctx.normal_accessor = Some(accessor_index)
+                            else if res_view.view_type() == VertexBufferViewType::Unknown10 {
+                            ctx.accessors.normal = Some(accessor_index)
                             }
                              */
                         }
@@ -351,6 +712,14 @@ pub fn create_gltf_node(
                 }
             }
 
+            let vertex_count = ctx.accessors.vertex_count;
+            let matches_vertices = |&(_, count): &(GltfIndex, usize)| {
+                vertex_count.is_none_or(|vertex_count| vertex_count == count)
+            };
+
+            ctx.accessors.normal = normals.filter(matches_vertices).map(|(index, _)| index);
+            ctx.accessors.colour = colours.filter(matches_vertices).map(|(index, _)| index);
+
             Ok(None)
         }
         NdData::PushBuffer(nd_push_buffer_data) => {
@@ -390,19 +759,17 @@ pub fn create_gltf_node(
                     .get(texture_slot as usize)
                 {
                     Some(tex_assignment) => {
-                        let material_index = ctx.gltf.add_material(gltf::Material {
-                            name: "Some Material".to_string(),
-                            pbr_metallic_roughness: Some(gltf::PBRMetallicRoughness {
-                                base_color_texture: Some(gltf::TextureInfo {
-                                    texture_index: tex_assignment.texture_index,
-                                    texcoords_accessor: None,
-                                }),
-                                metallic_factor: Some(0.0),
-                                ..Default::default()
-                            }),
-                        });
+                        ctx.current_material = ctx.get_or_add_material(
+                            tex_assignment.texture_index,
+                            main_payload.constants_hash(),
+                        );
 
-                        ctx.current_material = Some(material_index);
+                        if ctx.current_material.is_none() {
+                            eprintln!(
+                                "Texture {} is referenced by an ndShaderParam, but the model only has {} textures.",
+                                tex_assignment.texture_index, ctx.num_textures
+                            );
+                        }
                     }
                     None => eprintln!(
                         "Texture slot {} is referenced by an ndShaderParam, but the param only assigns {} slots.",
@@ -414,20 +781,51 @@ pub fn create_gltf_node(
                 ctx.current_material = None;
             }
 
-            Ok(Some(ctx.gltf.add_node(gltf::Node::new(Some(
-                "ndShaderParam2".to_string(),
-            )))))
+            Ok(Some(
+                ctx.gltf
+                    .add_node(gltf::Node::new(Some(nd.name().to_string()))),
+            ))
         }
-        NdData::Group | NdData::Shader2 | NdData::VertexShader | NdData::Unknown(..) => {
+        NdData::Group | NdData::Shader2(_) | NdData::VertexShader(_) | NdData::Unknown { .. } => {
             let mesh_node_index = ctx
                 .gltf
-                .add_node(gltf::Node::new(Some(nd.nd_type().to_string())));
+                .add_node(gltf::Node::new(Some(nd.name().to_string())));
 
             Ok(Some(mesh_node_index))
         }
     }
 }
 
+/// Adds `values` to the glTF as a new buffer, returning the index of an F32 accessor over them
+fn add_f32_accessor<const N: usize>(
+    ctx: &mut NdGltfContext,
+    values: &[[f32; N]],
+    component_count: gltf::AccessorComponentCount,
+) -> GltfIndex {
+    let bytes: Vec<u8> = values
+        .as_flattened()
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    let buffer_index = ctx.gltf.add_buffer(gltf::Buffer::new(&bytes));
+    let buffer_view_index = ctx.gltf.add_buffer_view(gltf::BufferView::new(
+        buffer_index,
+        0,
+        bytes.len(),
+        None,
+        Some(34962),
+    ));
+
+    ctx.gltf.add_accessor(gltf::Accessor::new(
+        buffer_view_index,
+        0,
+        gltf::AccessorDataType::F32,
+        values.len(),
+        component_count,
+    ))
+}
+
 pub fn insert_into_gltf_heirarchy(
     nd: &Nd,
     virtual_res: &VirtualResource,
@@ -455,7 +853,10 @@ pub fn insert_into_gltf_heirarchy(
     }
 
     if let Some(child) = &nd.first_child {
-        insert_into_gltf_heirarchy(child, virtual_res, ctx)?;
+        ctx.push_accessor_scope();
+        let res = insert_into_gltf_heirarchy(child, virtual_res, ctx);
+        ctx.pop_accessor_scope();
+        res?;
     }
 
     if let Some(node_index) = node_index_opt {
@@ -473,3 +874,36 @@ pub fn insert_into_gltf_heirarchy(
 
     Ok(node_index_opt)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessor_scopes() {
+        let mut ctx = NdGltfContext::default();
+
+        let outer = VertexAccessors {
+            positions: Some(1),
+            vertex_count: Some(3),
+            ..Default::default()
+        };
+        ctx.accessors = outer;
+
+        // A vertex buffer among the children only applies until they are left
+        ctx.push_accessor_scope();
+        ctx.accessors = VertexAccessors {
+            positions: Some(2),
+            vertex_count: Some(6),
+            ..Default::default()
+        };
+        ctx.pop_accessor_scope();
+
+        assert_eq!(ctx.accessors, outer);
+        assert!(ctx.accessor_stack.is_empty());
+
+        // Unbalanced pops keep the current accessors
+        ctx.pop_accessor_scope();
+        assert_eq!(ctx.accessors, outer);
+    }
+}
@@ -1,9 +1,15 @@
 mod push_buffer;
 mod shader;
+mod skeleton;
 mod vertex_buffer;
 
 use binrw::binrw;
-pub use push_buffer::{DrawCall, NdPushBufferData};
+pub use push_buffer::{DrawCall, DrawCallValidity, NdPushBufferData, SkippedDraw};
+pub(crate) use skeleton::{BONE_SIZE, RawBone};
+pub use skeleton::{
+    Bone, MAT4_IDENTITY, Mat4, NO_PARENT, NdSkeleton, mat4_from_translation, mat4_mul,
+    mat4_translation,
+};
 pub use vertex_buffer::*;
 
 pub(crate) mod prelude {
@@ -14,6 +20,7 @@ pub(crate) mod prelude {
     // Internal
     pub use super::ModelSlice;
     pub use crate::asset::AssetParseError;
+    pub use crate::asset::fields::{DescriptorField, DescriptorFields, FieldValue};
     pub use crate::asset::model::gltf::NdGltfContext;
     pub use crate::asset::model::nd::NdNode;
 
@@ -32,7 +39,11 @@ use std::{
 
 use serde::{Serialize, ser::SerializeMap};
 
-use crate::asset::model::nd::shader::NdShaderParam2Payload;
+pub use crate::asset::model::nd::shader::{NdShaderParam2Payload, NdShaderPayload};
+use crate::utils::{
+    layout::{FixedSize, fixed_layout},
+    reader::{Le, ReadError},
+};
 
 use prelude::*;
 
@@ -48,6 +59,12 @@ impl From<io::Error> for NdError {
     }
 }
 
+impl From<ReadError> for NdError {
+    fn from(e: ReadError) -> Self {
+        Self::CreationFailure(e.to_string())
+    }
+}
+
 pub trait NdNode {
     fn add_gltf_node(
         &self,
@@ -74,6 +91,8 @@ impl From<NdType> for String {
 }
 */
 
+/// Every nd is written with its header and the offsets it was read from, followed by a summary of
+/// its payload and then its children, for debugging the parser against the bytes of a model
 impl Serialize for Nd {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -82,6 +101,71 @@ impl Serialize for Nd {
         let mut map = serializer.serialize_map(None)?;
 
         map.serialize_entry("type", &self.nd_type().to_string())?;
+        map.serialize_entry("name", &self.name)?;
+        map.serialize_entry("offset", &self.offset)?;
+        map.serialize_entry("name_ptr", &self.name_ptr)?;
+        map.serialize_entry("unknown_u16", &self.unknown_u16)?;
+        map.serialize_entry("unknown_ptr1", &self.unknown_ptr1)?;
+        map.serialize_entry("unknown_ptr2", &self.unknown_ptr2)?;
+        map.serialize_entry("unknown_u32", &self.unknown_u32)?;
+        map.serialize_entry("first_child_ptr", &self.first_child_ptr)?;
+        map.serialize_entry("next_sibling_ptr", &self.next_sibling_ptr)?;
+        map.serialize_entry("parent_ptr", &self.parent_ptr)?;
+
+        match self.data.as_ref() {
+            NdData::Skeleton(skeleton) => {
+                map.serialize_entry("bone_count", &skeleton.bones().len())?;
+            }
+            NdData::VertexBuffer {
+                resource_views_ptr,
+                resource_views,
+                ..
+            } => {
+                map.serialize_entry("resource_views_ptr", resource_views_ptr)?;
+                map.serialize_entry("resource_view_count", &resource_views.len())?;
+                map.serialize_entry(
+                    "vertex_count",
+                    &resource_views
+                        .iter()
+                        .find(|view| view.view_type() == res_view::VertexBufferViewType::Vertex)
+                        .map_or(0, |view| view.num_entries()),
+                )?;
+            }
+            NdData::PushBuffer(push_buffer) | NdData::BGPushBuffer { push_buffer, .. } => {
+                map.serialize_entry("push_buffer_base", &push_buffer.push_buffer_base)?;
+                map.serialize_entry("draw_count", &push_buffer.draw_calls.len())?;
+                map.serialize_entry("drawable_draw_count", &push_buffer.drawable_calls().count())?;
+                map.serialize_entry("index_count", &push_buffer.indices().len())?;
+            }
+            NdData::ShaderParam2 {
+                main_payload,
+                sub_payload,
+            } => {
+                map.serialize_entry(
+                    "texture_assignment_count",
+                    &main_payload.texture_assignments().len(),
+                )?;
+                map.serialize_entry("has_sub_payload", &sub_payload.is_some())?;
+            }
+            NdData::Shader2(payload) | NdData::VertexShader(payload) => {
+                map.serialize_entry("shader", payload)?;
+            }
+            NdData::Unknown {
+                payload_start,
+                payload,
+                ..
+            } => {
+                map.serialize_entry("payload_start", payload_start)?;
+                map.serialize_entry(
+                    "payload",
+                    &payload
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect::<String>(),
+                )?;
+            }
+            NdData::Group => (),
+        }
 
         let children: Vec<&Nd> = self.children().collect();
         map.serialize_entry("children", &children)?;
@@ -90,10 +174,45 @@ impl Serialize for Nd {
     }
 }
 
+/// The size of the header shared by every nd, which its payload follows
+pub const ND_HEADER_SIZE: usize = NdHeader::SIZE;
+
+fixed_layout! {
+    /// The header shared by every nd. Pointers are offsets into the model's bytes, or 0 if unset.
+    #[derive(Debug, Clone)]
+    pub(crate) struct NdHeader {
+        name_ptr: u32,
+        type_u16: u16,
+        unknown_u16: u16,
+        unknown_ptr1: u32,
+        unknown_ptr2: u32,
+        unknown_u32: u32,
+        first_child_ptr: u32,
+        next_sibling_ptr: u32,
+        parent_ptr: u32,
+    }
+}
+
+/// The deepest an nd tree is allowed to nest before parsing is aborted. Child and sibling pointers
+/// are followed recursively, so a malformed (eg. cyclic) tree would otherwise overflow the stack.
+pub const MAX_ND_DEPTH: usize = 1024;
+
 impl Nd {
     pub fn new(ctx: &mut ModelReadContext, model_slice: ModelSlice) -> Result<Self, NdError> {
+        if ctx.depth >= MAX_ND_DEPTH {
+            return Err(NdError::CreationFailure(format!(
+                "nd tree exceeds the maximum depth of {}",
+                MAX_ND_DEPTH
+            )));
+        }
+
         let slice = model_slice.slice();
-        Nd::from_bytes(ctx, slice, model_slice.read_start as u32)
+
+        ctx.depth += 1;
+        let nd = Nd::from_bytes(ctx, slice, model_slice.read_start as u32);
+        ctx.depth -= 1;
+
+        nd
     }
 
     pub fn from_bytes(
@@ -101,50 +220,28 @@ impl Nd {
         bytes: &[u8],
         nd_start_offset: u32,
     ) -> Result<Nd, NdError> {
-        let mut cur = Cursor::new(bytes);
-
-        cur.seek(SeekFrom::Start(nd_start_offset as u64))?;
-
-        let name_ptr = cur.read_u32::<LittleEndian>()?;
+        let mut reader = Le::new(bytes).at(nd_start_offset as usize);
 
         // TODO: Sanity check name against name ptr
-        let (type_u16, unknown_u16) = (
-            cur.read_u16::<LittleEndian>()?,
-            cur.read_u16::<LittleEndian>()?,
-        );
-
-        let (
+        let NdHeader {
+            name_ptr,
+            type_u16,
+            unknown_u16,
             unknown_ptr1,
             unknown_ptr2,
             unknown_u32,
             first_child_ptr,
             next_sibling_ptr,
             parent_ptr,
-        ) = (
-            cur.read_u32::<LittleEndian>()?,
-            cur.read_u32::<LittleEndian>()?,
-            cur.read_u32::<LittleEndian>()?,
-            cur.read_u32::<LittleEndian>()?,
-            cur.read_u32::<LittleEndian>()?,
-            cur.read_u32::<LittleEndian>()?,
-        );
-
-        // Processing
-        let mut name_cur = cur.clone();
-        name_cur.seek(SeekFrom::Start(name_ptr as u64))?;
-
-        let mut chars = vec![];
-
-        let mut c = name_cur.read_u8()?;
-
-        while c != 0 {
-            chars.push(c);
-            c = name_cur.read_u8()?;
-        }
+        } = reader.struct_of()?;
 
-        let name = String::from_utf8(chars).map_err(|e| {
-            NdError::CreationFailure(format!("Failed to parse nd string name\n{}", e))
-        })?;
+        let name = reader
+            .at(name_ptr as usize)
+            .c_str()
+            .map_err(|e| {
+                NdError::CreationFailure(format!("Failed to parse nd string name\n{}", e))
+            })?
+            .to_string();
 
         let nd_type: NdType = name.parse().unwrap_or(NdType::Other(0));
 
@@ -176,11 +273,68 @@ impl Nd {
             ),
         };
 
+        // Payloads are read with a cursor from just after the header, as their parsers take one
+        let mut cur = Cursor::new(bytes);
+        cur.set_position(reader.position() as u64);
+
+        // Keeps the payload as raw bytes, for nds which aren't understood or don't match the
+        // layout they are expected to have
+        let unknown_payload = || -> Result<NdData, NdError> {
+            // Payloads whose size isn't known are taken to run until the next structure the
+            // header points to. Leaves whose name comes before them have nothing to end the
+            // payload, so it is left empty rather than guessing.
+            let payload_start = nd_start_offset + ND_HEADER_SIZE as u32;
+            let payload_end = match nd_type.payload_size() {
+                Some(size) => payload_start as usize + size,
+                None => [name_ptr, first_child_ptr, next_sibling_ptr, parent_ptr]
+                    .into_iter()
+                    .filter(|&ptr| ptr >= payload_start)
+                    .min()
+                    .map(|ptr| ptr as usize)
+                    .unwrap_or_else(|| {
+                        eprintln!(
+                            "The payload of {} at {:#x} has no known size, and nothing after it to end it. It will be kept empty.",
+                            name, nd_start_offset
+                        );
+
+                        payload_start as usize
+                    }),
+            };
+
+            let payload = bytes
+                .get(payload_start as usize..payload_end)
+                .ok_or_else(|| {
+                    NdError::CreationFailure(format!(
+                        "The payload of {} [{:#x}, {:#x}) is out of bounds for a model of {} bytes",
+                        name,
+                        payload_start,
+                        payload_end,
+                        bytes.len()
+                    ))
+                })?
+                .to_vec();
+
+            Ok(NdData::Unknown {
+                nd_type,
+                name: name.clone(),
+                payload_start,
+                payload,
+            })
+        };
+
         let data: Result<NdData, NdError> = match nd_type {
             NdType::VertexBuffer => {
                 let resource_views_ptr = cur.read_u32::<LittleEndian>()?;
                 let num_resource_views = cur.read_u32::<LittleEndian>()?;
 
+                if num_resource_views as usize > bytes.len() / res_view::RESOURCE_VIEW_SIZE {
+                    return Err(NdError::CreationFailure(format!(
+                        "ndVertexBuffer has {} resource views, which can't fit in {} bytes",
+                        num_resource_views,
+                        bytes.len()
+                    )));
+                }
+
                 let mut resource_views = Vec::with_capacity(num_resource_views as usize);
 
                 for _ in 0..num_resource_views {
@@ -218,6 +372,14 @@ impl Nd {
                     let mut vertex_counts_ptr = cur.clone();
                     vertex_counts_ptr.seek(SeekFrom::Start(vertex_counts_list_ptr as u64))?;
 
+                    if num_draws as usize > bytes.len() / size_of::<u32>() {
+                        return Err(NdError::CreationFailure(format!(
+                            "Push buffer has {} draws, which can't fit in {} bytes",
+                            num_draws,
+                            bytes.len()
+                        )));
+                    }
+
                     let mut draw_calls = Vec::with_capacity(num_draws as usize);
 
                     // TODO: FIGURE OUT IF THIS GOES HERE
@@ -225,30 +387,45 @@ impl Nd {
                     let mut max = u32::MIN;
 
                     for _ in 0..num_draws as usize {
-                        let data_ptr = data_ptr_cur.read_u32::<LittleEndian>()?;
-                        let prim_type = prim_type_ptr.read_u32::<LittleEndian>()?.into();
-                        let num_vertices = vertex_counts_ptr.read_u32::<LittleEndian>()?;
-                        let data_size = num_vertices * size_of::<u16>() as u32;
-
-                        if data_ptr < min {
-                            min = data_ptr;
-                        }
-                        if data_ptr + data_size > max {
-                            max = data_ptr + data_size;
+                        let draw_call = DrawCall::new(
+                            data_ptr_cur.read_u32::<LittleEndian>()?,
+                            prim_type_ptr.read_u32::<LittleEndian>()?.into(),
+                            vertex_counts_ptr.read_u32::<LittleEndian>()?,
+                            bytes.len(),
+                        );
+
+                        // Invalid and truncated draws are kept with their validity so that
+                        // callers can inspect them, but invalid ones don't contribute to the
+                        // index data
+                        if draw_call.is_drawable() {
+                            min = min.min(draw_call.data_ptr);
+                            max = max.max(draw_call.data_end()?);
                         }
 
-                        draw_calls.push(DrawCall {
-                            data_ptr,
-                            prim_type,
-                            num_vertices,
-                        });
+                        draw_calls.push(draw_call);
+                    }
+
+                    // A push buffer without any valid draws doesn't reference any index data
+                    if min > max {
+                        min = 0;
+                        max = 0;
                     }
 
                     let push_buffer_base = min;
                     let push_buffer_size = (max - min) as usize;
 
                     let buffer_bytes = bytes
-                        [push_buffer_base as usize..push_buffer_base as usize + push_buffer_size]
+                        .get(
+                            push_buffer_base as usize..push_buffer_base as usize + push_buffer_size,
+                        )
+                        .ok_or_else(|| {
+                            NdError::CreationFailure(format!(
+                                "Push buffer [{}, {}) is out of bounds for a model of {} bytes",
+                                push_buffer_base,
+                                max,
+                                bytes.len()
+                            ))
+                        })?
                         .to_vec();
 
                     NdPushBufferData {
@@ -312,31 +489,25 @@ impl Nd {
                 })
             }
             NdType::Skeleton => {
-                let num_bones = cur.read_u32::<LittleEndian>()?;
-                let bones_ptr = cur.read_u32::<LittleEndian>()?;
+                let [num_bones, bones_ptr] = reader.array::<u32, 2>()?;
+
+                if num_bones as usize > bytes.len() / BONE_SIZE {
+                    return Err(NdError::CreationFailure(format!(
+                        "ndSkeleton has {} bones, which can't fit in {} bytes",
+                        num_bones,
+                        bytes.len()
+                    )));
+                }
 
                 let bones = if bones_ptr != 0 && num_bones > 0 {
                     let mut bones = Vec::with_capacity(num_bones as usize);
-
-                    cur.seek(SeekFrom::Start(bones_ptr as u64))?;
+                    let mut bone_reader = reader.at(bones_ptr as usize);
 
                     for i in 0..num_bones {
-                        bones.push(Bone {
-                            name: ctx.get_bone_name(i).map(|v| v.into()),
-                            parent_id: cur.read_u16::<LittleEndian>()?,
-                            id: cur.read_u16::<LittleEndian>()?,
-                            local_transform: [
-                                cur.read_f32::<LittleEndian>()?,
-                                cur.read_f32::<LittleEndian>()?,
-                                cur.read_f32::<LittleEndian>()?,
-                            ],
-                            global_transform: [
-                                cur.read_f32::<LittleEndian>()?,
-                                cur.read_f32::<LittleEndian>()?,
-                                cur.read_f32::<LittleEndian>()?,
-                            ],
-                            sentinel: cur.read_u32::<LittleEndian>()?.to_le_bytes(),
-                        });
+                        bones.push(Bone::from_raw(
+                            bone_reader.struct_of::<RawBone>()?,
+                            ctx.get_bone_name(i).map(|v| v.into()),
+                        ));
                     }
 
                     bones
@@ -344,13 +515,21 @@ impl Nd {
                     vec![]
                 };
 
-                Ok(NdData::Skeleton { bones })
+                Ok(NdData::Skeleton(NdSkeleton::new(bones)))
             }
-            NdType::Shader2 => Ok(NdData::Shader2),
-            NdType::VertexShader => Ok(NdData::VertexShader),
-            NdType::RigidSkinIdx | NdType::MtxArray | NdType::BlendShape | NdType::Other(_) => Ok(
-                NdData::Unknown(nd_type, nd_type.to_string(), Vec::default()),
+            // The shader payload layout hasn't been checked against every model, so one which
+            // doesn't read is kept raw rather than failing the whole tree
+            NdType::Shader2 => NdShaderPayload::from_cursor(&mut cur).map_or_else(
+                |_| unknown_payload(),
+                |payload| Ok(NdData::Shader2(payload)),
+            ),
+            NdType::VertexShader => NdShaderPayload::from_cursor(&mut cur).map_or_else(
+                |_| unknown_payload(),
+                |payload| Ok(NdData::VertexShader(payload)),
             ),
+            NdType::RigidSkinIdx | NdType::MtxArray | NdType::BlendShape | NdType::Other(_) => {
+                unknown_payload()
+            }
         };
 
         /*
@@ -371,6 +550,9 @@ impl Nd {
         */
 
         Ok(Self {
+            offset: nd_start_offset,
+            name_ptr,
+            name,
             unknown_u16,
             unknown_ptr1,
             unknown_ptr2,
@@ -401,6 +583,10 @@ impl Nd {
         self.data.nd_type()
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn heirarchy(&self) -> impl Iterator<Item = &Nd> {
         NdIterator::new(self)
     }
@@ -436,11 +622,21 @@ pub enum NdType {
     Other(u32),
 }
 
+impl NdType {
+    /// The size of the payload after the header, for the types which can be kept as
+    /// [`NdData::Unknown`] and whose size is known
+    pub fn payload_size(&self) -> Option<usize> {
+        match self {
+            // The pointers and sizes of the bytecode and constants, see NdShaderPayload
+            NdType::Shader2 | NdType::VertexShader => Some(4 * size_of::<u32>()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub enum NdData {
-    Skeleton {
-        bones: Vec<Bone>,
-    },
+    Skeleton(NdSkeleton),
     VertexBuffer {
         resource_views_ptr: u32,
         num_resource_views: u32,
@@ -455,27 +651,37 @@ pub enum NdData {
         unknown_ptr_2: u32,
     },
     Group,
-    Shader2,
-    VertexShader,
+    Shader2(NdShaderPayload),
+    VertexShader(NdShaderPayload),
     ShaderParam2 {
         main_payload: NdShaderParam2Payload,
         sub_payload: Option<NdShaderParam2Payload>,
     },
-    Unknown(NdType, String, Vec<u8>),
+    /// An nd whose payload isn't understood, kept as the bytes from the end of its header. These
+    /// are [`NdType::payload_size`] bytes where the size is known, or up to the next structure the
+    /// header points to.
+    Unknown {
+        nd_type: NdType,
+        name: String,
+        /// The offset of the payload in the model
+        payload_start: u32,
+        #[serde(skip)]
+        payload: Vec<u8>,
+    },
 }
 
 impl NdData {
     pub fn nd_type(&self) -> NdType {
         match self {
-            NdData::Skeleton { .. } => NdType::Skeleton,
+            NdData::Skeleton(_) => NdType::Skeleton,
             NdData::VertexBuffer { .. } => NdType::VertexBuffer,
             NdData::PushBuffer(_) => NdType::PushBuffer,
             NdData::BGPushBuffer { .. } => NdType::BGPushBuffer,
             NdData::Group => NdType::Group,
-            NdData::Shader2 => NdType::Shader2,
-            NdData::VertexShader => NdType::VertexShader,
+            NdData::Shader2(_) => NdType::Shader2,
+            NdData::VertexShader(_) => NdType::VertexShader,
             NdData::ShaderParam2 { .. } => NdType::ShaderParam2,
-            NdData::Unknown(nd_type, ..) => *nd_type,
+            NdData::Unknown { nd_type, .. } => *nd_type,
         }
     }
 }
@@ -484,6 +690,11 @@ impl NdData {
 pub struct Nd {
     // pub name_ptr: NullString,
     // pub nd_type: NdType,
+    /// The offset of the header in the model
+    pub offset: u32,
+    pub name_ptr: u32,
+    /// The name string pointed to by the header, eg. "ndGroup"
+    pub name: String,
     pub unknown_u16: u16, // Possibly index
     pub unknown_ptr1: u32,
     pub unknown_ptr2: u32,
@@ -499,6 +710,29 @@ pub struct Nd {
     pub data: Box<NdData>,
 }
 
+impl DescriptorFields for Nd {
+    /// The fields of the nd header. The payload which follows it is not included.
+    fn fields(&self) -> Vec<DescriptorField> {
+        vec![
+            DescriptorField::unknown("unknown_u16", 0x6, FieldValue::U16(self.unknown_u16)),
+            DescriptorField::unknown("unknown_ptr1", 0x8, FieldValue::U32(self.unknown_ptr1)),
+            DescriptorField::unknown("unknown_ptr2", 0xc, FieldValue::U32(self.unknown_ptr2)),
+            DescriptorField::unknown("unknown_u32", 0x10, FieldValue::U32(self.unknown_u32)),
+            DescriptorField::new(
+                "first_child_ptr",
+                0x14,
+                FieldValue::U32(self.first_child_ptr),
+            ),
+            DescriptorField::new(
+                "next_sibling_ptr",
+                0x18,
+                FieldValue::U32(self.next_sibling_ptr),
+            ),
+            DescriptorField::new("parent_ptr", 0x1c, FieldValue::U32(self.parent_ptr)),
+        ]
+    }
+}
+
 struct NdIterator<'a> {
     stack: VecDeque<&'a Nd>,
 }
@@ -548,11 +782,16 @@ impl Serialize for Nd {
 
 pub struct ModelReadContext<'a> {
     key_value_map: &'a HashMap<String, Vec<u8>>,
+    /// How many nds deep the current read is
+    depth: usize,
 }
 
 impl<'a> ModelReadContext<'a> {
     pub fn new(key_value_map: &'a HashMap<String, Vec<u8>>) -> Self {
-        Self { key_value_map }
+        Self {
+            key_value_map,
+            depth: 0,
+        }
     }
 
     pub fn get_bone_name(&self, bone_index: u32) -> Option<&str> {
@@ -592,7 +831,7 @@ impl<'a> ModelSlice<'a> {
 
     pub fn new_cursor(&self) -> Cursor<&[u8]> {
         let mut cur = Cursor::new(self.slice);
-        cur.seek(SeekFrom::Start(self.read_start as u64)).unwrap();
+        cur.set_position(self.read_start as u64);
 
         cur
     }
@@ -617,16 +856,6 @@ impl NdNode for NdGroup {
 }
 */
 
-#[derive(Debug, Clone, Serialize)]
-pub struct Bone {
-    pub name: Option<String>,
-    pub parent_id: u16,
-    pub id: u16,
-    pub local_transform: [f32; 3],
-    pub global_transform: [f32; 3],
-    pub sentinel: [u8; 4],
-}
-
 #[path = "./tests.rs"]
 #[cfg(test)]
 mod tests;
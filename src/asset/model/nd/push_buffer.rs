@@ -1,11 +1,112 @@
 use super::prelude::*;
 use crate::d3d::D3DPrimitiveType;
 
+/// Whether a draw call could be read from the model as it was described
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DrawCallValidity {
+    Valid,
+    /// The indices ran past the end of the model, so the vertex count was reduced to fit
+    Truncated {
+        original_num_vertices: u32,
+    },
+    /// The indices lie outside of the model, so the draw is skipped
+    OutOfBounds,
+    /// The primitive type can't be drawn, so the draw is skipped
+    InvalidPrimitiveType,
+}
+
+impl DrawCallValidity {
+    pub fn is_drawable(&self) -> bool {
+        matches!(
+            self,
+            DrawCallValidity::Valid | DrawCallValidity::Truncated { .. }
+        )
+    }
+}
+
+/// A draw call which was left out of a glTF export, with the index of the draw call in its push
+/// buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SkippedDraw {
+    /// The draw call was found to be invalid when the model was read
+    Invalid {
+        draw_call: usize,
+        validity: DrawCallValidity,
+    },
+    /// The draw call uses a vertex past the end of the vertex buffer it draws from
+    VertexOutOfRange {
+        draw_call: usize,
+        max_index: u16,
+        vertex_count: usize,
+    },
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct DrawCall {
     pub data_ptr: u32,
     pub prim_type: D3DPrimitiveType,
     pub num_vertices: u32,
+    pub validity: DrawCallValidity,
+}
+
+impl DrawCall {
+    /**
+    Checks a draw call read from a model of `model_size` bytes, clamping its vertex count if its
+    indices run past the end of the model.
+    */
+    pub fn new(
+        data_ptr: u32,
+        prim_type: D3DPrimitiveType,
+        num_vertices: u32,
+        model_size: usize,
+    ) -> Self {
+        let data_end = num_vertices
+            .checked_mul(size_of::<u16>() as u32)
+            .and_then(|data_size| data_ptr.checked_add(data_size));
+
+        let (num_vertices, validity) = if matches!(
+            prim_type,
+            D3DPrimitiveType::None | D3DPrimitiveType::Max | D3DPrimitiveType::Invalid
+        ) {
+            (num_vertices, DrawCallValidity::InvalidPrimitiveType)
+        } else if data_ptr as usize >= model_size {
+            (num_vertices, DrawCallValidity::OutOfBounds)
+        } else if data_end.is_some_and(|end| end as usize <= model_size) {
+            (num_vertices, DrawCallValidity::Valid)
+        } else {
+            (
+                ((model_size - data_ptr as usize) / size_of::<u16>()) as u32,
+                DrawCallValidity::Truncated {
+                    original_num_vertices: num_vertices,
+                },
+            )
+        };
+
+        Self {
+            data_ptr,
+            prim_type,
+            num_vertices,
+            validity,
+        }
+    }
+
+    #[inline]
+    pub fn is_drawable(&self) -> bool {
+        self.validity.is_drawable()
+    }
+
+    /// The end of the index data of the draw call
+    pub fn data_end(&self) -> Result<u32, NdError> {
+        self.num_vertices
+            .checked_mul(size_of::<u16>() as u32)
+            .and_then(|data_size| self.data_ptr.checked_add(data_size))
+            .ok_or_else(|| {
+                NdError::CreationFailure(format!(
+                    "Draw call of {} vertices at {:#x} ends past the addressable range",
+                    self.num_vertices, self.data_ptr
+                ))
+            })
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -40,6 +141,32 @@ impl NdPushBufferData {
             .collect()
     }
 
+    /// The draw calls which can be drawn, skipping any which were found to be invalid
+    pub fn drawable_calls(&self) -> impl Iterator<Item = &DrawCall> {
+        self.draw_calls
+            .iter()
+            .filter(|draw_call| draw_call.is_drawable())
+    }
+
+    /// The highest index used by `draw_call`, or None if it has no indices in the push buffer
+    pub fn max_index(&self, draw_call: &DrawCall) -> Option<u16> {
+        self.draw_indices(draw_call)?.into_iter().max()
+    }
+
+    /// The indices used by `draw_call`, or None if they lie outside of the push buffer
+    pub fn draw_indices(&self, draw_call: &DrawCall) -> Option<Vec<u16>> {
+        let start = draw_call.data_ptr.checked_sub(self.push_buffer_base)? as usize;
+        let end = start.checked_add(draw_call.num_vertices as usize * 2)?;
+
+        Some(
+            self.buffer_bytes
+                .get(start..end)?
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes(c[0..2].try_into().unwrap()))
+                .collect(),
+        )
+    }
+
     pub fn create_gltf_node(
         &self,
         _virtual_res: &VirtualResource,
@@ -63,96 +190,135 @@ impl NdPushBufferData {
 
         println!("Adding {} draw calls.", self.draw_calls.len());
 
-        self.draw_calls.iter().for_each(|draw_call| {
-            let ib_accessor_index = ctx.gltf.add_accessor(gltf::Accessor::new(
-                ib_view_index,
-                (draw_call.data_ptr - self.push_buffer_base) as usize,
-                gltf::AccessorDataType::U16,
-                draw_call.num_vertices as usize,
-                gltf::AccessorComponentCount::SCALAR,
-            ));
+        for (i, draw_call) in self.draw_calls.iter().enumerate() {
+            if !draw_call.is_drawable() {
+                ctx.skipped_draws.push(SkippedDraw::Invalid {
+                    draw_call: i,
+                    validity: draw_call.validity,
+                });
+                continue;
+            }
 
-            let mut primitive = gltf::Primitive {
-                indices_accessor: Some(ib_accessor_index),
-                topology_type: match draw_call.prim_type.clone().try_into() {
-                    Ok(val) => Some(val),
+            // Indices past the end of the vertex buffer would make the whole gltf invalid
+            if let (Some(vertex_count), Some(max_index)) =
+                (ctx.accessors.vertex_count, self.max_index(draw_call))
+                && max_index as usize >= vertex_count
+            {
+                ctx.skipped_draws.push(SkippedDraw::VertexOutOfRange {
+                    draw_call: i,
+                    max_index,
+                    vertex_count,
+                });
+                continue;
+            }
+
+            let (ib_accessor_index, topology) =
+                match gltf::TopologyMode::try_from(draw_call.prim_type) {
+                    Ok(topology) => (
+                        ctx.gltf.add_accessor(gltf::Accessor::new(
+                            ib_view_index,
+                            (draw_call.data_ptr - self.push_buffer_base) as usize,
+                            gltf::AccessorDataType::U16,
+                            draw_call.num_vertices as usize,
+                            gltf::AccessorComponentCount::SCALAR,
+                        )),
+                        topology,
+                    ),
+                    // Quads and polygons get their own triangulated index buffer
                     Err(e) => {
-                        eprintln!("{}", e);
-                        None
+                        let triangles = self
+                            .draw_indices(draw_call)
+                            .and_then(|indices| draw_call.prim_type.triangulate(&indices))
+                            .ok_or(AssetParseError::InvalidDataViews(e))?;
+
+                        let triangle_bytes: Vec<u8> =
+                            triangles.iter().flat_map(|i| i.to_le_bytes()).collect();
+
+                        let buffer_index = ctx.gltf.add_buffer(gltf::Buffer::new(&triangle_bytes));
+                        let view_index = ctx.gltf.add_buffer_view(gltf::BufferView {
+                            buffer_index,
+                            byte_offset: 0,
+                            byte_length: triangle_bytes.len(),
+                            byte_stride: None,
+                            target: Some(34963),
+                        });
+
+                        (
+                            ctx.gltf.add_accessor(gltf::Accessor::new(
+                                view_index,
+                                0,
+                                gltf::AccessorDataType::U16,
+                                triangles.len(),
+                                gltf::AccessorComponentCount::SCALAR,
+                            )),
+                            gltf::TopologyMode::Triangles,
+                        )
                     }
-                },
+                };
+
+            let mut primitive = gltf::Primitive {
+                indices_accessor: Some(ib_accessor_index),
+                topology_type: Some(topology),
 
                 material: ctx.current_material,
                 attributes: Default::default(),
             };
 
-            if let Some(positions_accessor) = ctx.positions_accessor {
+            if let Some(positions_accessor) = ctx.accessors.positions {
                 primitive.set_attribute(gltf::VertexAttribute::Position, positions_accessor);
             } else {
                 eprintln!("No positions accessor available.");
             }
 
-            if let Some(uv_accessor) = ctx.uv_accessor {
+            if let Some(uv_accessor) = ctx.accessors.uv {
                 primitive.set_attribute(gltf::VertexAttribute::TexCoord(0), uv_accessor);
             } else {
                 eprintln!("No texcoords accessor available.");
             }
 
-            // if let Some(skin_accessor) = ctx.skin_accessor {
+            // if let Some(skin_accessor) = ctx.accessors.skin {
             //     primitive.set_attribute(gltf::VertexAttribute::Joints(0), skin_accessor);
             // }
             //
-            // if let Some(skin_weight_accessor) = ctx.skin_weight_accessor {
+            // if let Some(skin_weight_accessor) = ctx.accessors.skin_weight {
             //     primitive.set_attribute(gltf::VertexAttribute::Weights(0), skin_weight_accessor);
             // }
 
-            if let Some(normal_accessor) = ctx.normal_accessor {
+            if let Some(normal_accessor) = ctx.accessors.normal {
                 primitive.set_attribute(gltf::VertexAttribute::Normal, normal_accessor);
             } else {
                 eprintln!("No normals accessor available.");
             }
 
+            if let Some(colour_accessor) = ctx.accessors.colour {
+                primitive.set_attribute(gltf::VertexAttribute::Color(0), colour_accessor);
+            }
+
             primitives.push(primitive);
-        });
+        }
 
-        let index = ctx.current_node_index().unwrap() as usize;
-
-        let mesh: &mut gltf::Mesh = match ctx.gltf.meshes_mut().get_mut(index) {
-            Some(val) => val,
-            None => {
-                let new_mesh = gltf::Mesh::new("New Mesh".to_string());
-                let new_mesh_index = ctx.gltf.add_mesh(new_mesh);
-
-                let new_node = gltf::Node::new(Some("Mesh Node".to_string()));
-                let new_node_index = ctx.gltf.add_node(new_node);
-
-                ctx.gltf
-                    .nodes_mut()
-                    .get_mut(new_node_index as usize)
-                    .unwrap()
-                    .set_mesh_index(Some(new_mesh_index));
-
-                if let Some(skin_index) = ctx.current_skin {
-                    ctx.gltf
-                        .nodes_mut()
-                        .get_mut(new_node_index as usize)
-                        .unwrap()
-                        .set_skin_index(Some(skin_index));
-                }
-
-                ctx.gltf
-                    .meshes_mut()
-                    .get_mut(new_mesh_index as usize)
-                    .unwrap()
-            }
-        };
+        if ctx.current_node_index().is_none() {
+            return Err(AssetParseError::InvalidDataViews(
+                "Push buffer has no parent node to attach a mesh to.".to_string(),
+            ));
+        }
+
+        let mut mesh = gltf::Mesh::new(format!("mesh{}", ctx.gltf.meshes_mut().len()));
 
         for primitive in primitives {
             mesh.add_primitive(primitive);
         }
 
-        Ok(None)
+        let mesh_index = ctx.gltf.add_mesh(mesh);
+
+        let mut mesh_node = gltf::Node::new(Some(format!("mesh{}_node", mesh_index)));
+        mesh_node.set_mesh_index(Some(mesh_index));
+
+        if let Some(skin_index) = ctx.current_skin {
+            mesh_node.set_skin_index(Some(skin_index));
+        }
 
-        // Ok(Some(ctx.gltf.add_node(node)))
+        // The caller parents the new node to the top of the node stack
+        Ok(Some(ctx.gltf.add_node(mesh_node)))
     }
 }
@@ -1,18 +1,28 @@
 use super::prelude::*;
 
-use indexmap::IndexMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 
-use crate::d3d::{PixelShaderConstant, VertexShaderConstant};
+use indexmap::IndexMap;
 
-#[derive(Debug, Clone, Serialize)]
-pub struct AttributeValue {
-    pub(crate) val1: u32,
-    pub(crate) val2: u32,
-
-    pub(crate) sentinel1: u8,
-    pub(crate) sentinel2: u8,
-    pub(crate) sentinel3: u8,
-    pub(crate) sentinel4: u8,
+use crate::{
+    d3d::{PixelShaderConstant, VertexShaderConstant},
+    utils::{
+        layout::{FixedSize, fixed_layout},
+        reader::Le,
+    },
+};
+
+fixed_layout! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct AttributeValue {
+        pub(crate) val1: u32,
+        pub(crate) val2: u32,
+
+        pub(crate) sentinel1: u8,
+        pub(crate) sentinel2: u8,
+        pub(crate) sentinel3: u8,
+        pub(crate) sentinel4: u8,
+    }
 }
 
 fn serialize_index_map<S>(
@@ -31,64 +41,40 @@ where
     map.end()
 }
 
-pub const TEXTURE_ASSIGNMENT_SIZE: usize = 28;
+fn serialize_hex<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(
+        &bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+    )
+}
 
-#[derive(Debug, Clone, Serialize)]
-pub struct TextureAssignment {
-    pub(crate) texture_index: u32,
-    pub(crate) count_1: u8,
-    pub(crate) count_2: u8,
-    pub(crate) count_3: u8,
-    pub(crate) skip_diffuse_texture: bool,
-    pub(crate) unknown_1: u32,
-    pub(crate) unknown_2: u32,
-    pub(crate) unknown_3: u32,
-    pub(crate) unknown_4: u32,
-    pub(crate) unknown_5: u32,
-    // ORIGINAL FORMAT
-    /*
-       u32 textureIndex;
-    u8 flag1;
-    u8 flag2;
-    u8 flag3;
-    bool skipDiffuseTexture;
-    u32 unknown3;
-    u32 unknown4;
-    u32 unknown5;
-    u32 unknown6;
-    u32 unknown7;
-    */
+pub const TEXTURE_ASSIGNMENT_SIZE: usize = TextureAssignment::SIZE;
+
+fixed_layout! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct TextureAssignment {
+        pub(crate) texture_index: u32,
+        pub(crate) count_1: u8,
+        pub(crate) count_2: u8,
+        pub(crate) count_3: u8,
+        /// Any value other than 0 is set, see [`TextureAssignment::skip_diffuse_texture`]
+        pub(crate) skip_diffuse_texture: u8,
+        pub(crate) unknown_1: u32,
+        pub(crate) unknown_2: u32,
+        pub(crate) unknown_3: u32,
+        pub(crate) unknown_4: u32,
+        pub(crate) unknown_5: u32,
+    }
 }
 
 impl TextureAssignment {
-    fn from_model_slice(model_slice: ModelSlice) -> Result<Self, std::io::Error> {
-        let mut cur = model_slice.new_cursor();
-
-        let texture_index = cur.read_u32::<LittleEndian>()?;
-        let count_1 = cur.read_u8()?;
-        let count_2 = cur.read_u8()?;
-        let count_3 = cur.read_u8()?;
-
-        let skip_diffuse_texture: bool = !matches!(cur.read_u8()?, 0);
-
-        let unknown_1 = cur.read_u32::<LittleEndian>()?;
-        let unknown_2 = cur.read_u32::<LittleEndian>()?;
-        let unknown_3 = cur.read_u32::<LittleEndian>()?;
-        let unknown_4 = cur.read_u32::<LittleEndian>()?;
-        let unknown_5 = cur.read_u32::<LittleEndian>()?;
-
-        Ok(Self {
-            texture_index,
-            count_1,
-            count_2,
-            count_3,
-            skip_diffuse_texture,
-            unknown_1,
-            unknown_2,
-            unknown_3,
-            unknown_4,
-            unknown_5,
-        })
+    pub fn skip_diffuse_texture(&self) -> bool {
+        self.skip_diffuse_texture != 0
     }
 }
 
@@ -134,64 +120,37 @@ pub struct NdShaderParam2Payload {
 
 impl NdShaderParam2Payload {
     pub fn from_model_slice(model_slice: &ModelSlice) -> Result<Self, NdError> {
-        let mut cur = Cursor::new(model_slice.slice);
-
-        cur.seek(SeekFrom::Start(model_slice.read_start as u64))?;
+        let mut reader = Le::new(model_slice.slice).at(model_slice.read_start);
 
-        let pixel_shader_constants_start = cur.read_u32::<LittleEndian>()?;
-        let vertex_shader_constants_start = cur.read_u32::<LittleEndian>()?;
-        let texture_assignments_start = cur.read_u32::<LittleEndian>()?;
-        let num_texture_assignments = cur.read_u32::<LittleEndian>()?;
-        let num_vertex_shader_constants = cur.read_u32::<LittleEndian>()?;
-        let num_pixel_shader_constants = cur.read_u32::<LittleEndian>()?;
+        let [
+            pixel_shader_constants_start,
+            vertex_shader_constants_start,
+            texture_assignments_start,
+            num_texture_assignments,
+            num_vertex_shader_constants,
+            num_pixel_shader_constants,
+        ] = reader.array::<u32, 6>()?;
 
-        let alpha_ref = cur.read_u8()?;
-        let count_1 = cur.read_u8()?;
-        let count_2 = cur.read_u8()?;
-        let some_count = cur.read_u8()?;
+        let [alpha_ref, count_1, count_2, some_count] = reader.array::<u8, 4>()?;
 
-        let unknown_1 = cur.read_u32::<LittleEndian>()?;
-        let next_payload_start = cur.read_u32::<LittleEndian>()?;
-        let attributes_start = cur.read_u32::<LittleEndian>()?;
-        let num_attributes = cur.read_u32::<LittleEndian>()?;
+        let [
+            unknown_1,
+            next_payload_start,
+            attributes_start,
+            num_attributes,
+        ] = reader.array::<u32, 4>()?;
 
         let mut attribute_map = IndexMap::new();
 
-        cur.seek(SeekFrom::Start(attributes_start as u64))?;
+        let mut attributes = reader.at(attributes_start as usize);
 
         for _ in 0..num_attributes {
-            let name_ptr = cur.read_u32::<LittleEndian>()?;
-            let val1 = cur.read_u32::<LittleEndian>()?;
-            let val2 = cur.read_u32::<LittleEndian>()?;
-
-            let sentinel1 = cur.read_u8()?;
-            let sentinel2 = cur.read_u8()?;
-            let sentinel3 = cur.read_u8()?;
-            let sentinel4 = cur.read_u8()?;
-
-            let mut name_cur = cur.clone();
-            name_cur.seek(SeekFrom::Start(name_ptr as u64))?;
-
-            let utf8_chars: Vec<u8> = name_cur
-                .bytes()
-                .map(|b| b.unwrap())
-                .take_while(|b| *b != 0)
-                .collect();
-
-            let name = String::from_utf8(utf8_chars)
-                .map_err(|e| NdError::CreationFailure(e.to_string()))?;
-
-            if let Some(old_val) = attribute_map.insert(
-                name.clone(),
-                AttributeValue {
-                    val1,
-                    val2,
-                    sentinel1,
-                    sentinel2,
-                    sentinel3,
-                    sentinel4,
-                },
-            ) {
+            let name_ptr = attributes.u32()?;
+            let value: AttributeValue = attributes.struct_of()?;
+
+            let name = attributes.at(name_ptr as usize).c_str()?.to_string();
+
+            if let Some(old_val) = attribute_map.insert(name.clone(), value) {
                 println!(
                     "Overriding old entry in attribute map.\n{}: {:?}",
                     name, old_val
@@ -199,7 +158,14 @@ impl NdShaderParam2Payload {
             }
         }
 
-        let vertex_constants_slice = &model_slice.slice[vertex_shader_constants_start as usize..];
+        let vertex_constants_slice = model_slice
+            .slice
+            .get(vertex_shader_constants_start as usize..)
+            .ok_or_else(|| {
+                NdError::CreationFailure(
+                    "Vertex shader constants pointer is out of bounds.".to_string(),
+                )
+            })?;
         let vertex_shader_constants: Vec<VertexShaderConstant> = vertex_constants_slice
             .chunks_exact(size_of::<VertexShaderConstant>())
             .take(num_vertex_shader_constants as usize)
@@ -214,19 +180,34 @@ impl NdShaderParam2Payload {
             })
             .collect();
 
-        let pixel_constants_slice = &model_slice.slice[pixel_shader_constants_start as usize..];
+        let pixel_constants_slice = model_slice
+            .slice
+            .get(pixel_shader_constants_start as usize..)
+            .ok_or_else(|| {
+                NdError::CreationFailure(
+                    "Pixel shader constants pointer is out of bounds.".to_string(),
+                )
+            })?;
         let pixel_shader_constants: Vec<PixelShaderConstant> = pixel_constants_slice
             .chunks_exact(size_of::<PixelShaderConstant>())
             .take(num_pixel_shader_constants as usize)
             .map(|chunk| chunk.try_into().unwrap())
             .collect();
 
+        if num_texture_assignments as usize > model_slice.slice.len() / TEXTURE_ASSIGNMENT_SIZE {
+            return Err(NdError::CreationFailure(format!(
+                "ndShaderParam2 has {} texture assignments, which can't fit in {} bytes",
+                num_texture_assignments,
+                model_slice.slice.len()
+            )));
+        }
+
         let mut texture_assignments = vec![];
 
-        for i in 0..num_texture_assignments as usize {
-            texture_assignments.push(TextureAssignment::from_model_slice(
-                model_slice.at(texture_assignments_start as usize + i * TEXTURE_ASSIGNMENT_SIZE),
-            )?);
+        let mut assignments = reader.at(texture_assignments_start as usize);
+
+        for _ in 0..num_texture_assignments {
+            texture_assignments.push(assignments.struct_of()?);
         }
 
         Ok(NdShaderParam2Payload {
@@ -250,4 +231,140 @@ impl NdShaderParam2Payload {
     pub fn texture_assignments(&self) -> &[TextureAssignment] {
         &self.texture_assignments
     }
+
+    /// A hash of the vertex and pixel shader constants, used to tell apart materials which share a
+    /// texture
+    pub fn constants_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.vertex_shader_constants
+            .iter()
+            .flatten()
+            .for_each(|f| f.to_bits().hash(&mut hasher));
+        self.pixel_shader_constants.hash(&mut hasher);
+
+        hasher.finish()
+    }
+}
+
+impl DescriptorFields for NdShaderParam2Payload {
+    /// The fields of the payload, relative to its start. Pointers aren't kept after parsing, so
+    /// only the counts and flags are included.
+    fn fields(&self) -> Vec<DescriptorField> {
+        vec![
+            DescriptorField::new(
+                "num_texture_assignments",
+                0xc,
+                FieldValue::U32(self.texture_assignments.len() as u32),
+            ),
+            DescriptorField::new(
+                "num_vertex_shader_constants",
+                0x10,
+                FieldValue::U32(self.vertex_shader_constants.len() as u32),
+            ),
+            DescriptorField::new(
+                "num_pixel_shader_constants",
+                0x14,
+                FieldValue::U32(self.pixel_shader_constants.len() as u32),
+            ),
+            DescriptorField::new("alpha_ref", 0x18, FieldValue::U8(self.alpha_ref)),
+            DescriptorField::new("count_1", 0x19, FieldValue::U8(self.count_1)),
+            DescriptorField::new("count_2", 0x1a, FieldValue::U8(self.count_2)),
+            DescriptorField::unknown("some_count", 0x1b, FieldValue::U8(self.some_count)),
+            DescriptorField::unknown("unknown_1", 0x1c, FieldValue::U32(self.unknown_1)),
+            DescriptorField::new("next_payload", 0x20, FieldValue::U32(self.next_payload)),
+        ]
+    }
+}
+
+/// The payload following the header of an ndShader2 or ndVertexShader
+#[derive(Debug, Clone, Serialize)]
+pub struct NdShaderPayload {
+    bytecode_ptr: u32,
+    bytecode_size: u32,
+    constants_ptr: u32,
+    num_constants: u32,
+
+    /// The raw shader microcode
+    #[serde(serialize_with = "serialize_hex")]
+    bytecode: Vec<u8>,
+    /// The constant table declared alongside the microcode, which ndShaderParam2 constants are
+    /// written over
+    constants: Vec<VertexShaderConstant>,
+    /*
+    u32* bytecode: u32 [[pointer_base("section1innersptr")]];
+    u32 bytecodeSize;
+    VertexShaderConstant* constants: u32 [[pointer_base("section1innersptr")]];
+    u32 numConstants;
+    */
+}
+
+impl NdShaderPayload {
+    /// Reads the payload from the cursor, which must be positioned directly after the nd header
+    pub fn from_cursor(cur: &mut Cursor<&[u8]>) -> Result<Self, NdError> {
+        let bytecode_ptr = cur.read_u32::<LittleEndian>()?;
+        let bytecode_size = cur.read_u32::<LittleEndian>()?;
+        let constants_ptr = cur.read_u32::<LittleEndian>()?;
+        let num_constants = cur.read_u32::<LittleEndian>()?;
+
+        let bytes: &[u8] = cur.get_ref();
+
+        let bytecode = match bytecode_ptr {
+            0 => vec![],
+            ptr => bytes
+                .get(ptr as usize..ptr as usize + bytecode_size as usize)
+                .ok_or_else(|| {
+                    NdError::CreationFailure(format!(
+                        "Shader bytecode [{}, {}) is out of bounds for a model of {} bytes",
+                        ptr,
+                        ptr as usize + bytecode_size as usize,
+                        bytes.len()
+                    ))
+                })?
+                .to_vec(),
+        };
+
+        let constants = match constants_ptr {
+            0 => vec![],
+            ptr => {
+                let constants_size = num_constants as usize * size_of::<VertexShaderConstant>();
+
+                bytes
+                    .get(ptr as usize..ptr as usize + constants_size)
+                    .ok_or_else(|| {
+                        NdError::CreationFailure(
+                            "Shader constant table is out of bounds.".to_string(),
+                        )
+                    })?
+                    .chunks_exact(size_of::<VertexShaderConstant>())
+                    .map(|chunk| {
+                        let mut constant: VertexShaderConstant = [0.0, 0.0, 0.0, 0.0];
+
+                        chunk.chunks_exact(4).enumerate().for_each(|(i, ch)| {
+                            constant[i] = f32::from_le_bytes(ch.try_into().unwrap());
+                        });
+
+                        constant
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Self {
+            bytecode_ptr,
+            bytecode_size,
+            constants_ptr,
+            num_constants,
+            bytecode,
+            constants,
+        })
+    }
+
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+
+    pub fn constants(&self) -> &[VertexShaderConstant] {
+        &self.constants
+    }
 }
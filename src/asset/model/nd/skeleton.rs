@@ -0,0 +1,180 @@
+use super::prelude::*;
+
+use crate::utils::layout::{FixedSize, fixed_layout};
+
+/// The serialised size of a [`Bone`] in an ndSkeleton
+pub(crate) const BONE_SIZE: usize = RawBone::SIZE;
+
+fixed_layout! {
+    /// A [`Bone`] as it is stored, without the name which comes from the model
+    pub(crate) struct RawBone {
+        pub parent_id: u16,
+        pub id: u16,
+        pub local_translation: [f32; 3],
+        pub global_translation: [f32; 3],
+        pub sentinel: [u8; 4],
+    }
+}
+
+/// The parent id of the root bone
+pub const NO_PARENT: u16 = 0xffff;
+
+/// A column major 4x4 matrix, matching the layout used by glTF
+pub type Mat4 = [[f32; 4]; 4];
+
+pub const MAT4_IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn mat4_from_translation(translation: [f32; 3]) -> Mat4 {
+    let mut matrix = MAT4_IDENTITY;
+    matrix[3][..3].copy_from_slice(&translation);
+
+    matrix
+}
+
+/// Returns `a * b`
+pub fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut out = [[0f32; 4]; 4];
+
+    for (col, out_col) in out.iter_mut().enumerate() {
+        for (row, value) in out_col.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+
+    out
+}
+
+/// The translation part of `matrix`
+pub fn mat4_translation(matrix: &Mat4) -> [f32; 3] {
+    [matrix[3][0], matrix[3][1], matrix[3][2]]
+}
+
+/**
+A single bone of an ndSkeleton.
+
+Bones only store their rest pose as translations, without any rotation or scale. The global
+translation is the sum of the local translations of the bone and all of its parents.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct Bone {
+    pub name: Option<String>,
+    /// The id of the parent bone, or [`NO_PARENT`] for the root
+    pub parent_id: u16,
+    pub id: u16,
+    /// The translation of the bone relative to its parent
+    pub local_translation: [f32; 3],
+    /// The translation of the bone relative to the model, as stored in the file
+    pub global_translation: [f32; 3],
+    pub sentinel: [u8; 4],
+}
+
+impl Bone {
+    pub(crate) fn from_raw(raw: RawBone, name: Option<String>) -> Self {
+        Self {
+            name,
+            parent_id: raw.parent_id,
+            id: raw.id,
+            local_translation: raw.local_translation,
+            global_translation: raw.global_translation,
+            sentinel: raw.sentinel,
+        }
+    }
+
+    pub fn local_matrix(&self) -> Mat4 {
+        mat4_from_translation(self.local_translation)
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent_id == NO_PARENT
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NdSkeleton {
+    pub bones: Vec<Bone>,
+}
+
+impl NdSkeleton {
+    pub fn new(bones: Vec<Bone>) -> Self {
+        Self { bones }
+    }
+
+    pub fn bones(&self) -> &[Bone] {
+        &self.bones
+    }
+
+    /**
+    Computes the world matrix of each bone by walking its chain of parents, using the local
+    translations.
+
+    # Errors
+    - [`NdError::CreationFailure`] if a bone has a parent which doesn't exist, or the parents form
+      a cycle
+    */
+    pub fn world_transforms(&self) -> Result<Vec<Mat4>, NdError> {
+        let mut world: Vec<Option<Mat4>> = vec![None; self.bones.len()];
+
+        for i in 0..self.bones.len() {
+            // Walk up until a bone with a known transform (or the root) is found
+            let mut chain = vec![];
+            let mut current = i;
+
+            while world[current].is_none() {
+                if chain.len() > self.bones.len() {
+                    return Err(NdError::CreationFailure(format!(
+                        "Bone {} has a cycle in its parents",
+                        i
+                    )));
+                }
+
+                chain.push(current);
+
+                let parent_id = self.bones[current].parent_id;
+
+                if parent_id == NO_PARENT {
+                    break;
+                }
+
+                if parent_id as usize >= self.bones.len() {
+                    return Err(NdError::CreationFailure(format!(
+                        "Bone {} has parent {}, but the skeleton only has {} bones",
+                        current,
+                        parent_id,
+                        self.bones.len()
+                    )));
+                }
+
+                current = parent_id as usize;
+            }
+
+            let mut parent_matrix = world[current].unwrap_or(MAT4_IDENTITY);
+
+            for bone_index in chain.into_iter().rev() {
+                let matrix = mat4_mul(&parent_matrix, &self.bones[bone_index].local_matrix());
+
+                world[bone_index] = Some(matrix);
+                parent_matrix = matrix;
+            }
+        }
+
+        Ok(world
+            .into_iter()
+            .map(|m| m.unwrap_or(MAT4_IDENTITY))
+            .collect())
+    }
+
+    /// The inverse bind matrix of each bone, as used by glTF skins
+    pub fn inverse_bind_matrices(&self) -> Result<Vec<Mat4>, NdError> {
+        // Bones only have translations, so inverting is just negating them
+        Ok(self
+            .world_transforms()?
+            .iter()
+            .map(|matrix| mat4_from_translation(mat4_translation(matrix).map(|v| -v)))
+            .collect())
+    }
+}
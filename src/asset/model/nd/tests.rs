@@ -1,6 +1,7 @@
 use std::fs;
 
 use super::*;
+use crate::d3d::D3DPrimitiveType;
 
 fn get_test_bytes() -> Vec<u8> {
     let test_path = std::path::Path::new(file!())
@@ -87,3 +88,347 @@ fn nd_shader_param2() {
         );
     }
 }
+
+#[test]
+fn nd_vertex_shader_payload() {
+    let mut bytes = vec![0u8; 0x68];
+
+    // nd header, with the name stored at 0x40
+    bytes[0x00..0x04].copy_from_slice(&0x40u32.to_le_bytes());
+
+    // Payload: bytecode [0x50, 0x58), then 1 constant at 0x58
+    bytes[0x20..0x24].copy_from_slice(&0x50u32.to_le_bytes());
+    bytes[0x24..0x28].copy_from_slice(&8u32.to_le_bytes());
+    bytes[0x28..0x2c].copy_from_slice(&0x58u32.to_le_bytes());
+    bytes[0x2c..0x30].copy_from_slice(&1u32.to_le_bytes());
+
+    bytes[0x40..0x4e].copy_from_slice(b"ndVertexShader");
+    bytes[0x50..0x58].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04]);
+
+    [1.0f32, 2.0, 3.0, 4.0]
+        .iter()
+        .enumerate()
+        .for_each(|(i, v)| bytes[0x58 + i * 4..0x5c + i * 4].copy_from_slice(&v.to_le_bytes()));
+
+    let nd = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0,
+        },
+    )
+    .expect("Unable to create ND");
+
+    let NdData::VertexShader(payload) = &*nd.data else {
+        panic!(
+            "nd has wrong type {:?}, expected ndVertexShader.",
+            nd.nd_type()
+        );
+    };
+
+    assert_eq!(
+        payload.bytecode(),
+        &[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04]
+    );
+    assert_eq!(payload.constants(), &[[1.0, 2.0, 3.0, 4.0]]);
+}
+
+#[test]
+fn nd_skeleton_world_transforms() {
+    let bytes = get_test_bytes();
+
+    let nd = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0x34,
+        },
+    )
+    .expect("Unable to create ND");
+
+    let NdData::Skeleton(skeleton) = &*nd.data else {
+        panic!("nd has wrong type {:?}, expected ndSkeleton.", nd.nd_type());
+    };
+
+    assert_eq!(skeleton.bones().len(), 6);
+    assert!(skeleton.bones()[0].is_root());
+
+    let world = skeleton
+        .world_transforms()
+        .expect("Unable to get world transforms");
+
+    // The stored global translations should match the ones found by walking the parents
+    for (bone, matrix) in skeleton.bones().iter().zip(&world) {
+        let translation = mat4_translation(matrix);
+
+        for (a, b) in translation.iter().zip(&bone.global_translation) {
+            assert!((a - b).abs() < 0.001, "{:?} != {:?}", translation, bone);
+        }
+    }
+
+    let inverse_bind = skeleton
+        .inverse_bind_matrices()
+        .expect("Unable to get inverse bind matrices");
+
+    for (matrix, inverse) in world.iter().zip(&inverse_bind) {
+        assert_eq!(
+            mat4_translation(&mat4_mul(matrix, inverse)),
+            [0.0, 0.0, 0.0]
+        );
+    }
+}
+
+#[test]
+fn nd_push_buffer_invalid_draws() {
+    let mut bytes = vec![0u8; 0x7c];
+
+    // nd header, with the name stored at 0x40
+    bytes[0x00..0x04].copy_from_slice(&0x40u32.to_le_bytes());
+    bytes[0x40..0x4c].copy_from_slice(b"ndPushBuffer");
+
+    // Payload: 3 draws, with data pointers at 0x50, types at 0x5c and counts at 0x68
+    bytes[0x20..0x24].copy_from_slice(&3u32.to_le_bytes());
+    bytes[0x30..0x34].copy_from_slice(&0x50u32.to_le_bytes());
+    bytes[0x34..0x38].copy_from_slice(&0x5cu32.to_le_bytes());
+    bytes[0x38..0x3c].copy_from_slice(&0x68u32.to_le_bytes());
+
+    let draws: [(u32, u32, u32); 3] = [
+        // A valid triangle
+        (0x74, 5, 3),
+        // A strip which runs past the end of the model
+        (0x76, 6, 100),
+        // A list which is entirely out of bounds
+        (0x1000, 5, 3),
+    ];
+
+    for (i, (data_ptr, prim_type, num_vertices)) in draws.iter().enumerate() {
+        bytes[0x50 + i * 4..0x54 + i * 4].copy_from_slice(&data_ptr.to_le_bytes());
+        bytes[0x5c + i * 4..0x60 + i * 4].copy_from_slice(&prim_type.to_le_bytes());
+        bytes[0x68 + i * 4..0x6c + i * 4].copy_from_slice(&num_vertices.to_le_bytes());
+    }
+
+    let nd = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0,
+        },
+    )
+    .expect("Unable to create ND");
+
+    let NdData::PushBuffer(push_buffer) = &*nd.data else {
+        panic!(
+            "nd has wrong type {:?}, expected ndPushBuffer.",
+            nd.nd_type()
+        );
+    };
+
+    let validity: Vec<_> = push_buffer
+        .draw_calls
+        .iter()
+        .map(|draw_call| draw_call.validity)
+        .collect();
+
+    assert_eq!(
+        validity,
+        vec![
+            DrawCallValidity::Valid,
+            DrawCallValidity::Truncated {
+                original_num_vertices: 100
+            },
+            DrawCallValidity::OutOfBounds
+        ]
+    );
+
+    assert_eq!(push_buffer.draw_calls[1].num_vertices, 3);
+    assert_eq!(push_buffer.drawable_calls().count(), 2);
+    assert_eq!(push_buffer.push_buffer_base, 0x74);
+    assert_eq!(push_buffer.indices().len(), 4);
+
+    let json = serde_json::to_value(&nd).expect("Unable to serialise ND");
+    assert_eq!(json["name"], "ndPushBuffer");
+    assert_eq!(json["offset"], 0);
+    assert_eq!(json["name_ptr"], 0x40);
+    assert_eq!(json["draw_count"], 3);
+    assert_eq!(json["drawable_draw_count"], 2);
+    assert_eq!(json["index_count"], 4);
+}
+
+#[test]
+fn draw_call_data_end_overflow() {
+    let mut draw_call = DrawCall::new(0x10, D3DPrimitiveType::TriangleList, 3, 0x100);
+    assert_eq!(draw_call.data_end().ok(), Some(0x16));
+
+    draw_call.data_ptr = u32::MAX - 1;
+    assert!(draw_call.data_end().is_err());
+
+    draw_call.data_ptr = 0;
+    draw_call.num_vertices = u32::MAX;
+    assert!(draw_call.data_end().is_err());
+}
+
+#[test]
+fn nd_unknown_payload() {
+    let mut bytes = vec![0u8; 0x40];
+
+    // nd header, with 0x10 bytes of payload before the name at 0x30
+    bytes[0x00..0x04].copy_from_slice(&0x30u32.to_le_bytes());
+    bytes[0x20..0x30].copy_from_slice(&[0xab; 0x10]);
+    bytes[0x30..0x3a].copy_from_slice(b"ndMtxArray");
+
+    let nd = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0,
+        },
+    )
+    .expect("Unable to create ND");
+
+    let NdData::Unknown {
+        nd_type,
+        payload_start,
+        payload,
+        ..
+    } = &*nd.data
+    else {
+        panic!("nd has type {:?}, expected an unknown nd.", nd.nd_type());
+    };
+
+    assert_eq!(*nd_type, NdType::MtxArray);
+    assert_eq!(*payload_start, 0x20);
+    assert_eq!(payload, &vec![0xab; 0x10]);
+
+    let json = serde_json::to_value(&nd).expect("Unable to serialise ND");
+    assert_eq!(json["payload"], "ab".repeat(0x10));
+
+    // A leaf with its name before it has nothing to end a payload of unknown size, so the payload
+    // is kept empty
+    let mut bytes = vec![0u8; 0x50];
+    bytes[0x00..0x0a].copy_from_slice(b"ndMtxArray");
+    bytes[0x10..0x14].copy_from_slice(&0u32.to_le_bytes());
+    bytes[0x30..0x50].fill(0xcd);
+
+    let leaf = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0x10,
+        },
+    )
+    .expect("Unable to create ND");
+
+    let NdData::Unknown {
+        nd_type,
+        payload_start,
+        payload,
+        ..
+    } = &*leaf.data
+    else {
+        panic!("nd has type {:?}, expected an unknown nd.", leaf.nd_type());
+    };
+
+    assert_eq!(*nd_type, NdType::MtxArray);
+    assert_eq!(*payload_start, 0x30);
+    assert!(payload.is_empty());
+    assert!(leaf.first_child.is_none() && leaf.next_sibling.is_none());
+}
+
+#[test]
+fn nd_normal_colour_views() {
+    let view = |stride: u8, view_type: u8| {
+        let mut bytes = [0u8; 24];
+        bytes[0] = stride;
+        bytes[1] = view_type;
+
+        res_view::VertexBufferResourceView::from_cursor(&mut std::io::Cursor::new(&bytes[..]))
+            .expect("Unable to read resource view")
+    };
+
+    let normal_view = view(4, 0xa);
+    assert_eq!(
+        normal_view.view_type(),
+        res_view::VertexBufferViewType::Unknown10
+    );
+
+    // +X, -Y and +Z packed as 11:11:10
+    let packed = [1023u32, ((-1023i32 as u32) & 0x7ff) << 11, 511 << 22];
+    let bytes: Vec<u8> = packed.iter().flat_map(|p| p.to_le_bytes()).collect();
+    let normals = normal_view
+        .decode_normals(&bytes)
+        .expect("Unable to decode normals");
+
+    assert_eq!(
+        normals,
+        vec![[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]]
+    );
+
+    let floats: Vec<u8> = [0.0f32, 2.0, 0.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    assert_eq!(
+        view(12, 0xa).decode_normals(&floats),
+        Some(vec![[0.0, 1.0, 0.0]])
+    );
+    assert_eq!(view(5, 0xa).decode_normals(&[0; 5]), None);
+
+    let colours = view(4, 0xb)
+        .decode_colours(&[0, 0, 255, 128, 255, 255, 255, 255])
+        .expect("Unable to decode colours");
+
+    for (colour, expected) in colours
+        .iter()
+        .zip([[1.0, 0.0, 0.0, 128.0 / 255.0], [1.0; 4]])
+    {
+        for (c, e) in colour.iter().zip(expected) {
+            assert!((c - e).abs() < 1e-5, "{:?} != {:?}", colour, expected);
+        }
+    }
+}
+
+#[test]
+fn nd_shader_bad_payload_kept_raw() {
+    let mut bytes = vec![0u8; 0x50];
+
+    // nd header, with the name stored at 0x40
+    bytes[0x00..0x04].copy_from_slice(&0x40u32.to_le_bytes());
+
+    // Payload: bytecode pointing past the end of the model
+    bytes[0x20..0x24].copy_from_slice(&0x1000u32.to_le_bytes());
+    bytes[0x24..0x28].copy_from_slice(&8u32.to_le_bytes());
+
+    bytes[0x40..0x48].copy_from_slice(b"ndShader");
+    bytes[0x48..0x4a].copy_from_slice(b"2\0");
+
+    let nd = Nd::new(
+        &mut ModelReadContext::new(&Default::default()),
+        ModelSlice {
+            slice: &bytes,
+            read_start: 0,
+        },
+    )
+    .expect("A bad shader payload shouldn't fail the nd");
+
+    let NdData::Unknown { nd_type, .. } = &*nd.data else {
+        panic!("nd should be kept raw, not parsed as {:?}", nd.nd_type());
+    };
+
+    assert_eq!(*nd_type, NdType::Shader2);
+}
+
+#[test]
+fn texture_assignment_flag_kept() -> Result<(), String> {
+    use crate::utils::layout::{ToLeBytes, from_le_slice};
+
+    let mut bytes = vec![0u8; shader::TEXTURE_ASSIGNMENT_SIZE];
+    bytes[0x7] = 2;
+
+    let assignment: shader::TextureAssignment = from_le_slice(&bytes).map_err(|e| e.to_string())?;
+    assert!(assignment.skip_diffuse_texture());
+
+    // Flags other than 1 are written back as they were read
+    assert_eq!(assignment.to_le_vec(), bytes);
+
+    Ok(())
+}
@@ -32,9 +32,16 @@ pub fn get_vertex_positions(
     views: &[VertexBufferResourceView],
 ) -> Option<Vec<[f32; 3]>> {
     views.iter().find_map(|view| {
-        (view.view_type() == res_view::VertexBufferViewType::Vertex).then(|| {
-            resource[view.start() as usize..view.end() as usize]
-                .chunks_exact(12)
+        if view.view_type() != res_view::VertexBufferViewType::Vertex {
+            return None;
+        }
+
+        Some(
+            resource
+                .get(view.start() as usize..view.end() as usize)?
+                // Each position is 12 bytes, but there may be padding between them
+                .chunks(usize::from(view.stride()).max(12))
+                .filter(|chunk| chunk.len() >= 12)
                 .map(|chunk| {
                     [
                         f32::from_le_bytes(chunk[0..4].try_into().unwrap()),
@@ -42,7 +49,7 @@ pub fn get_vertex_positions(
                         f32::from_le_bytes(chunk[8..12].try_into().unwrap()),
                     ]
                 })
-                .collect()
-        })
+                .collect(),
+        )
     })
 }
@@ -1,6 +1,9 @@
 use byteorder::{LittleEndian, ReadBytesExt as _};
 use gltf_writer::gltf::GltfIndex;
 
+/// The serialised size of a [`VertexBufferResourceView`]
+pub(crate) const RESOURCE_VIEW_SIZE: usize = 24;
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct VertexBufferResourceView {
     stride: u8,
@@ -94,18 +97,107 @@ impl VertexBufferResourceView {
     }
 
     pub fn end(&self) -> u32 {
-        self.view_start + self.view_size
+        self.view_start.saturating_add(self.view_size)
     }
 
     /// Number of entries in this resource view
     /// Equal by length / stride
     pub fn num_entries(&self) -> usize {
-        (self.view_size / u32::from(self.stride)) as usize
+        self.view_size
+            .checked_div(u32::from(self.stride))
+            .unwrap_or_default() as usize
     }
 
     pub fn view_type(&self) -> VertexBufferViewType {
         self.view_type
     }
+
+    /**
+    Decodes `bytes`, the contents of a [`VertexBufferViewType::Unknown10`] view, to unit length
+    normals. This is provisional, as the view is only believed to hold normals, and the encodings
+    are guessed from the stride:
+    - 12 bytes: 3 floats
+    - 6 or 8 bytes: 3 signed normalised shorts
+    - 4 bytes: `NORMPACKED3`, the Xbox format packing x and y into 11 bits and z into 10
+
+    Returns None for any other stride. Normals with no length are replaced with +Z.
+    */
+    pub fn decode_normals(&self, bytes: &[u8]) -> Option<Vec<[f32; 3]>> {
+        let decode: fn(&[u8]) -> [f32; 3] = match self.stride {
+            12 => |chunk| std::array::from_fn(|i| read_f32(chunk, i * 4)),
+            6 | 8 => |chunk| {
+                std::array::from_fn(|i| {
+                    i16::from_le_bytes([chunk[i * 2], chunk[i * 2 + 1]]) as f32 / i16::MAX as f32
+                })
+            },
+            4 => |chunk| {
+                let packed = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+
+                // Sign extend each field by shifting it to the top of an i32
+                let x = ((packed << 21) as i32 >> 21) as f32 / 1023.0;
+                let y = ((packed << 10) as i32 >> 21) as f32 / 1023.0;
+                let z = (packed as i32 >> 22) as f32 / 511.0;
+
+                [x, y, z]
+            },
+            _ => return None,
+        };
+
+        Some(
+            bytes
+                .chunks_exact(self.stride.into())
+                .map(|chunk| {
+                    let normal = decode(chunk);
+                    let length = normal.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+                    match length > f32::EPSILON {
+                        true => normal.map(|v| v / length),
+                        false => [0.0, 0.0, 1.0],
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /**
+    Decodes `bytes`, the contents of a [`VertexBufferViewType::Unknown11`] view, to linear RGBA
+    between 0 and 1. A stride of 4 is read as a `D3DCOLOR` (BGRA bytes), and 16 as 4 floats. This
+    is provisional, as the view is only believed to hold vertex colours.
+
+    Returns None for any other stride.
+    */
+    pub fn decode_colours(&self, bytes: &[u8]) -> Option<Vec<[f32; 4]>> {
+        let decode: fn(&[u8]) -> [f32; 4] = match self.stride {
+            4 => |chunk| {
+                let [b, g, r, a] = [chunk[0], chunk[1], chunk[2], chunk[3]];
+
+                // glTF vertex colours are linear, and D3DCOLORs are sRGB
+                let [r, g, b] = [r, g, b].map(|c| srgb_to_linear(c as f32 / 255.0));
+
+                [r, g, b, a as f32 / 255.0]
+            },
+            16 => |chunk| std::array::from_fn(|i| read_f32(chunk, i * 4)),
+            _ => return None,
+        };
+
+        Some(bytes.chunks_exact(self.stride.into()).map(decode).collect())
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
 }
 
 #[repr(u8)]
@@ -114,9 +206,12 @@ pub enum VertexBufferViewType {
     Skin = 0x0,
     SkinWeight = 0x8,
     Vertex = 0x9,
+    /// Believed to be normals, see [`VertexBufferResourceView::decode_normals`]
     Unknown10 = 0xa,
+    /// Believed to be vertex colours, see [`VertexBufferResourceView::decode_colours`]
     Unknown11 = 0xb,
     UV = 0xd,
+    /// Not identified yet, so it is left out of glTF exports
     Unknown14 = 0xe,
     Unknown15 = 0xf,
     Unknown16 = 0x10,
@@ -0,0 +1,54 @@
+/*!
+A minimal Wavefront OBJ writer, for looking at model geometry in any 3D tool without the textures
+and skeleton that glTF exports carry. Only positions and triangles are written.
+*/
+
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjWriter {
+    text: String,
+    /// The number of vertices written so far, as faces refer to vertices across the whole file
+    vertex_count: usize,
+}
+
+impl ObjWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new object, which the following vertices and faces belong to
+    pub fn object(&mut self, name: &str) {
+        let _ = writeln!(self.text, "o {}", name);
+    }
+
+    /// Starts a new group within the current object, for splitting faces that share vertices
+    pub fn group(&mut self, name: &str) {
+        let _ = writeln!(self.text, "g {}", name);
+    }
+
+    /// Writes `vertices`, returning the base to pass to [`ObjWriter::faces`] for faces which index
+    /// into them
+    pub fn vertices(&mut self, vertices: &[[f32; 3]]) -> usize {
+        for [x, y, z] in vertices {
+            let _ = writeln!(self.text, "v {} {} {}", x, y, z);
+        }
+
+        let base = self.vertex_count;
+        self.vertex_count += vertices.len();
+        base
+    }
+
+    /// Writes triangles of indices into the vertices written at `base`
+    pub fn faces(&mut self, base: usize, triangles: &[[u32; 3]]) {
+        for triangle in triangles {
+            // OBJ indices start at 1
+            let [a, b, c] = triangle.map(|index| base + index as usize + 1);
+            let _ = writeln!(self.text, "f {} {} {}", a, b, c);
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
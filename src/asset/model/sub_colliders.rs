@@ -1,5 +1,12 @@
+/*!
+The collision subresource of a model, which the game uses for physics instead of the render
+geometry. Each body is a mesh of triangles which index into a shared list of vertices.
+*/
+
 use std::io::SeekFrom;
 
+use crate::asset::model::obj::ObjWriter;
+
 #[binrw::binrw]
 #[repr(C)]
 #[derive(Debug, Clone)]
@@ -20,6 +27,48 @@ pub struct CollisionSubresource {
 }
 
 impl CollisionSubresource {
+    pub fn vertices(&self) -> &[[f32; 3]] {
+        &self.vertices
+    }
+
+    pub fn bodies(&self) -> &[CollisionBody] {
+        &self.bodies
+    }
+
+    /// Every triangle of every body
+    pub fn triangles(&self) -> impl Iterator<Item = &CollisionTriangle> {
+        self.bodies.iter().flat_map(CollisionBody::triangles)
+    }
+
+    /// The plane of every triangle of every body, skipping triangles with no area or which index
+    /// past the end of the vertices
+    pub fn planes(&self) -> Vec<CollisionPlane> {
+        self.triangles()
+            .filter_map(|triangle| triangle.plane(&self.vertices))
+            .collect()
+    }
+
+    /// Writes the vertices, then each body as its own group of an object named `name`
+    pub fn add_to_obj(&self, obj: &mut ObjWriter, name: &str) {
+        obj.object(name);
+        let base = obj.vertices(&self.vertices);
+
+        for (i, body) in self.bodies.iter().enumerate() {
+            let triangles: Vec<[u32; 3]> = body
+                .triangles()
+                .map(CollisionTriangle::indices)
+                .filter(|indices| {
+                    indices
+                        .iter()
+                        .all(|&index| (index as usize) < self.vertices.len())
+                })
+                .collect();
+
+            obj.group(&format!("{}_body_{}", name, i));
+            obj.faces(base, &triangles);
+        }
+    }
+
     pub fn add_to_gltf(
         &self,
         gltf: &mut gltf_writer::gltf::Gltf,
@@ -166,6 +215,22 @@ pub enum CollisionBody {
     },
 }
 
+impl CollisionBody {
+    pub fn collision_mask(&self) -> u32 {
+        match self {
+            CollisionBody::Mesh { collision_mask, .. } => *collision_mask,
+        }
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = &CollisionTriangle> {
+        match self {
+            CollisionBody::Mesh { primitives, .. } => primitives
+                .iter()
+                .flat_map(|primitive| primitive.triangles.iter()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 #[binrw::binrw]
 pub struct CollisionPrimitive {
@@ -191,3 +256,139 @@ pub struct CollisionTriangle {
     #[br(assert(pad == 0xcccc))]
     pad: u16,
 }
+
+impl CollisionTriangle {
+    pub fn indices(&self) -> [u32; 3] {
+        [self.index1, self.index2, self.index3]
+    }
+
+    pub fn collision_mask(&self) -> u32 {
+        self.collision_mask
+    }
+
+    /// The plane the triangle lies on, with its normal facing the side the vertices wind
+    /// anticlockwise around. None if the triangle has no area or indexes past the end of
+    /// `vertices`.
+    pub fn plane(&self, vertices: &[[f32; 3]]) -> Option<CollisionPlane> {
+        let [a, b, c] = self
+            .indices()
+            .map(|index| vertices.get(index as usize).copied());
+        let (a, b, c) = (a?, b?, c?);
+
+        let ab: [f32; 3] = std::array::from_fn(|i| b[i] - a[i]);
+        let ac: [f32; 3] = std::array::from_fn(|i| c[i] - a[i]);
+
+        let cross = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+
+        let length = cross.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+        if length <= f32::EPSILON {
+            return None;
+        }
+
+        let normal = cross.map(|v| v / length);
+
+        Some(CollisionPlane {
+            normal,
+            distance: (0..3).map(|i| normal[i] * a[i]).sum(),
+        })
+    }
+}
+
+/// A plane of points `p` where `normal · p = distance`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionPlane {
+    pub normal: [f32; 3],
+    pub distance: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use binrw::BinReaderExt as _;
+
+    /// A collision subresource with one body of two triangles making a unit square on the floor
+    fn square_bytes() -> Vec<u8> {
+        let mut bytes = vec![];
+
+        // Header, with the vertices after the triangles
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend([0u8; 4]);
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(108u32.to_le_bytes());
+
+        // Body
+        bytes.extend([1, 0, 0, 0]);
+        bytes.extend(0x10u32.to_le_bytes());
+        bytes.extend([0u8; 24]);
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend([0u8; 10]);
+
+        // Primitive
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(68u32.to_le_bytes());
+
+        for indices in [[0u32, 2, 1], [0, 3, 2]] {
+            indices
+                .iter()
+                .for_each(|index| bytes.extend(index.to_le_bytes()));
+            bytes.extend(0x10u32.to_le_bytes());
+            bytes.extend([0, 0, 0xcc, 0xcc]);
+        }
+
+        for vertex in [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0],
+        ] {
+            vertex.iter().for_each(|v| bytes.extend(v.to_le_bytes()));
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn collision_triangles() -> Result<(), String> {
+        let bytes = square_bytes();
+        assert_eq!(bytes.len(), 108 + 4 * 12);
+
+        let collision: CollisionSubresource = std::io::Cursor::new(&bytes)
+            .read_le()
+            .map_err(|e| e.to_string())?;
+
+        assert_eq!(collision.bodies().len(), 1);
+        assert_eq!(collision.bodies()[0].collision_mask(), 0x10);
+        assert_eq!(
+            collision
+                .triangles()
+                .map(CollisionTriangle::indices)
+                .collect::<Vec<_>>(),
+            vec![[0, 2, 1], [0, 3, 2]]
+        );
+
+        // Both triangles face upwards, on the plane y = 0
+        let planes = collision.planes();
+        assert_eq!(planes.len(), 2);
+        for plane in planes {
+            assert_eq!(plane.normal, [0.0, 1.0, 0.0]);
+            assert_eq!(plane.distance, 0.0);
+        }
+
+        let mut obj = ObjWriter::new();
+        obj.vertices(&[[5.0, 5.0, 5.0]]);
+        collision.add_to_obj(&mut obj, "collision");
+
+        let obj = obj.finish();
+        assert!(obj.contains("o collision\n"));
+        assert!(obj.contains("g collision_body_0\nf 2 4 3\nf 2 5 4\n"));
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 5);
+
+        Ok(())
+    }
+}
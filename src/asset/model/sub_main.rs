@@ -1,11 +1,16 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::{
-    collections::HashMap,
-    io::{self, BufRead, Cursor, Read, Seek, SeekFrom},
+use std::{collections::HashMap, io};
+
+use crate::{
+    asset::{
+        fields::{DescriptorField, DescriptorFields, FieldValue},
+        model::{
+            BoundingSphere,
+            nd::{ModelReadContext, ModelSlice, Nd, NdData, NdSkeleton, get_vertex_positions},
+        },
+    },
+    utils::reader::{Le, ReadError},
 };
 
-use crate::asset::model::nd::{ModelReadContext, ModelSlice, Nd};
-
 #[derive(Debug, strum::Display)]
 pub enum SubresourceError {
     CreationError,
@@ -19,7 +24,15 @@ impl From<io::Error> for SubresourceError {
     }
 }
 
+impl From<ReadError> for SubresourceError {
+    fn from(_: ReadError) -> Self {
+        Self::CreationError
+    }
+}
+
 const MESH_HEADER_SIZE: usize = 40;
+/// The offset of the bounding sphere floats in the mesh header
+pub(crate) const MESH_BOUNDS_OFFSET: usize = 0x18;
 
 #[derive(Debug)]
 pub struct Mesh {
@@ -27,6 +40,61 @@ pub struct Mesh {
     primitives: Vec<Nd>,
 }
 
+/**
+Adds the triangles of `nd`, its children and its following siblings, see
+[`ModelSubresource::render_triangles`]. `vertex_buffer` is the start and length in `positions` of
+the vertex buffer in scope, which the children of an nd can replace without affecting its siblings.
+*/
+fn add_render_triangles(
+    nd: &Nd,
+    resource: &[u8],
+    mut vertex_buffer: Option<(usize, usize)>,
+    positions: &mut Vec<[f32; 3]>,
+    triangles: &mut Vec<[u32; 3]>,
+) {
+    let mut current = Some(nd);
+
+    while let Some(nd) = current {
+        match nd.data.as_ref() {
+            NdData::VertexBuffer { resource_views, .. } => {
+                if let Some(buffer_positions) = get_vertex_positions(resource, resource_views) {
+                    vertex_buffer = Some((positions.len(), buffer_positions.len()));
+                    positions.extend(buffer_positions);
+                }
+            }
+            NdData::PushBuffer(push_buffer) | NdData::BGPushBuffer { push_buffer, .. } => {
+                let (base, vertex_count) = vertex_buffer.unwrap_or_default();
+
+                for draw_call in push_buffer.drawable_calls() {
+                    let Some(indices) = push_buffer
+                        .draw_indices(draw_call)
+                        .and_then(|indices| draw_call.prim_type.triangulate(&indices))
+                    else {
+                        continue;
+                    };
+
+                    triangles.extend(
+                        indices
+                            .chunks_exact(3)
+                            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                            .filter(|triangle| {
+                                triangle.iter().all(|&i| (i as usize) < vertex_count)
+                            })
+                            .map(|triangle| triangle.map(|i| (base + i as usize) as u32)),
+                    );
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(child) = nd.first_child() {
+            add_render_triangles(child, resource, vertex_buffer, positions, triangles);
+        }
+
+        current = nd.next_sibling();
+    }
+}
+
 /*
 impl Mesh {
     pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Mesh, SubresourceError> {
@@ -96,64 +164,64 @@ pub struct ModelSubresource {
 
 impl ModelSubresource {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, SubresourceError> {
-        let mut cur = Cursor::new(bytes);
+        let mut reader = Le::new(bytes);
 
-        let unknown1 = cur.read_u32::<LittleEndian>()?;
-        let unknown2 = cur.read_u32::<LittleEndian>()?;
-        let primitive_ptrs_start = cur.read_u32::<LittleEndian>()?;
-        let primitive_count = cur.read_u32::<LittleEndian>()?;
-        let key_values_ptr = cur.read_u32::<LittleEndian>()?;
-        let unknown3 = cur.read_u32::<LittleEndian>()?;
-
-        let mut floats = [0f32; 4];
+        let [
+            unknown1,
+            unknown2,
+            primitive_ptrs_start,
+            primitive_count,
+            key_values_ptr,
+            unknown3,
+        ] = reader.array::<u32, 6>()?;
+        let floats = reader.array::<f32, 4>()?;
 
-        for float in floats.iter_mut() {
-            *float = cur.read_f32::<LittleEndian>()?;
+        // Each primitive pointer is a u32, so there can't be more of them than the input allows
+        if primitive_count as usize > bytes.len() / size_of::<u32>() {
+            return Err(SubresourceError::CreationError);
         }
 
-        let mut primitive_ptrs = vec![0u32; primitive_count as usize];
-
-        let mut primitive_cur = cur.clone();
-
-        primitive_cur.seek(SeekFrom::Start(primitive_ptrs_start as u64))?;
-
-        for i in 0..primitive_count as usize {
-            primitive_ptrs[i] = primitive_cur.read_u32::<LittleEndian>()?;
-        }
+        let mut primitive_reader = reader.at(primitive_ptrs_start as usize);
+        let primitive_ptrs = (0..primitive_count)
+            .map(|_| primitive_reader.u32())
+            .collect::<Result<Vec<_>, _>>()?;
 
         let mut primitives = Vec::with_capacity(primitive_ptrs.len());
 
         let mut key_value_map = HashMap::<String, Vec<u8>>::default();
 
         if key_values_ptr != 0 {
-            cur.seek(SeekFrom::Start(key_values_ptr.into()))?;
+            let [num_values, data_start_ptr] =
+                reader.at(key_values_ptr as usize).array::<u32, 2>()?;
 
-            let num_values = cur.read_u32::<LittleEndian>()?;
-            let data_start_ptr = cur.read_u32::<LittleEndian>()?;
             if num_values > 0 && data_start_ptr != 0 {
-                cur.seek(SeekFrom::Start(data_start_ptr.into()))?;
+                let mut entries = reader.at(data_start_ptr as usize);
 
                 for _ in 0..num_values as usize {
-                    let key_ptr = cur.read_u32::<LittleEndian>()?;
-                    let value_ptr = cur.read_u32::<LittleEndian>()?;
-                    let value_size = cur.read_u32::<LittleEndian>()?;
+                    let [key_ptr, value_ptr, value_size] = entries.array::<u32, 3>()?;
+
+                    // Keys run to a null terminator or the end of the bytes, one char per byte
+                    let mut key_reader = reader.at(key_ptr as usize);
+                    let key_len = key_reader
+                        .remaining()
+                        .iter()
+                        .position(|&b| b == 0)
+                        .unwrap_or(key_reader.remaining().len());
+                    let key = key_reader
+                        .bytes(key_len)?
+                        .iter()
+                        .map(|&c| c as char)
+                        .collect();
+
+                    if value_size as usize > bytes.len() {
+                        return Err(SubresourceError::CreationError);
+                    }
+
+                    let value = reader
+                        .at(value_ptr as usize)
+                        .bytes(value_size as usize)?
+                        .to_vec();
 
-                    let mut cur = cur.clone();
-
-                    cur.seek(SeekFrom::Start((key_ptr).into()))?;
-
-                    let mut key = vec![];
-                    cur.read_until(0u8, &mut key)?;
-
-                    println!();
-
-                    key.pop();
-
-                    let mut value = vec![0u8; value_size as usize];
-                    cur.seek(SeekFrom::Start((value_ptr).into()))?;
-                    cur.read_exact(&mut value)?;
-
-                    let key = key.into_iter().map(|c| c as char).collect();
                     key_value_map.insert(key, value);
                 }
             }
@@ -192,6 +260,123 @@ impl ModelSubresource {
     pub fn primitives(&self) -> &[Nd] {
         &self.primitives
     }
+
+    /**
+    The bounding sphere of the mesh.
+
+    The 4 floats of the header appear to be a sphere stored as (radius, x, y, z), as every bone of
+    the skeletons seen so far falls inside of it.
+    */
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_floats(self.floats)
+    }
+
+    pub fn set_bounding_sphere(&mut self, sphere: BoundingSphere) {
+        self.floats = sphere.to_floats();
+    }
+
+    /// The positions of every vertex in the vertex buffers of the mesh, read from the model's
+    /// `resource`
+    pub fn vertex_positions(&self, resource: &[u8]) -> Vec<[f32; 3]> {
+        self.primitives
+            .iter()
+            .flat_map(|primitive| primitive.heirarchy())
+            .filter_map(|nd| match nd.data.as_ref() {
+                NdData::VertexBuffer { resource_views, .. } => {
+                    get_vertex_positions(resource, resource_views)
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /**
+    The triangles drawn by the push buffers of the mesh, as indices into the positions of every
+    vertex buffer, which are returned alongside them. Like the glTF export, each push buffer draws
+    from the last vertex buffer before it among its ancestors and their earlier siblings. Draw
+    calls which can't be read, and triangles which index past the end of their vertex buffer, are
+    skipped.
+    */
+    pub fn render_triangles(&self, resource: &[u8]) -> (Vec<[f32; 3]>, Vec<[u32; 3]>) {
+        let mut positions = vec![];
+        let mut triangles = vec![];
+
+        for primitive in &self.primitives {
+            add_render_triangles(primitive, resource, None, &mut positions, &mut triangles);
+        }
+
+        (positions, triangles)
+    }
+
+    /**
+    Recalculates the bounding sphere from the vertices of the mesh, so that modified geometry is
+    culled correctly. Returns the new sphere, or None (leaving the old one) if the mesh has no
+    vertices.
+
+    The descriptor bytes can then be updated using
+    [`ModelDescriptor::write_bounds`](crate::asset::model::ModelDescriptor::write_bounds).
+    */
+    pub fn recompute_bounds(&mut self, resource: &[u8]) -> Option<BoundingSphere> {
+        let sphere = BoundingSphere::from_points(&self.vertex_positions(resource))?;
+        self.set_bounding_sphere(sphere);
+
+        Some(sphere)
+    }
+
+    /// The bones of the first ndSkeleton found in the primitives, if any
+    pub fn skeleton(&self) -> Option<&NdSkeleton> {
+        self.primitives
+            .iter()
+            .flat_map(|primitive| primitive.heirarchy())
+            .find_map(|nd| match nd.data.as_ref() {
+                NdData::Skeleton(skeleton) => Some(skeleton),
+                _ => None,
+            })
+    }
+}
+
+impl DescriptorFields for ModelSubresource {
+    /// The fields of the mesh header. The bounding sphere floats are named as in
+    /// [`ModelSubresource::bounding_sphere`].
+    fn fields(&self) -> Vec<DescriptorField> {
+        let mut fields = vec![
+            DescriptorField::unknown("unknown1", 0x0, FieldValue::U32(self.unknown1)),
+            DescriptorField::unknown("unknown2", 0x4, FieldValue::U32(self.unknown2)),
+            DescriptorField::new(
+                "primitive_ptrs_start",
+                0x8,
+                FieldValue::U32(self.primitive_ptrs_start),
+            ),
+            DescriptorField::new(
+                "primitive_count",
+                0xc,
+                FieldValue::U32(self.primitive_count),
+            ),
+            DescriptorField::new("key_values_ptr", 0x10, FieldValue::U32(self.key_values_ptr)),
+            DescriptorField::unknown("unknown3", 0x14, FieldValue::U32(self.unknown3)),
+        ];
+
+        let names = ["bounds_radius", "bounds_x", "bounds_y", "bounds_z"];
+
+        fields.extend(
+            names
+                .into_iter()
+                .zip(self.floats)
+                .enumerate()
+                .map(|(i, (name, value))| {
+                    DescriptorField::new(name, MESH_BOUNDS_OFFSET + i * 4, FieldValue::F32(value))
+                }),
+        );
+
+        fields
+    }
+}
+
+impl DescriptorFields for Mesh {
+    fn fields(&self) -> Vec<DescriptorField> {
+        self.header.fields()
+    }
 }
 
 #[derive(Debug)]
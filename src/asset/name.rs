@@ -0,0 +1,188 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/**
+A null terminated string stored in a fixed size buffer of `N` bytes, as used for asset ids.
+
+Strings can hold at most `N - 1` characters so that there is always room for the null terminator,
+and are limited to printable ASCII.
+
+# Examples
+```
+use bnl::asset::AssetName;
+
+let name = AssetName::try_from("aid_texture_mytexture_a_b").unwrap();
+assert_eq!(name.as_str(), "aid_texture_mytexture_a_b");
+assert_eq!(name.as_bytes().len(), 128);
+```
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixedString<const N: usize>([u8; N]);
+
+/// The name of an asset as stored in a BNL file, eg. "aid_texture_mytexture_a_b"
+pub type AssetName = FixedString<128>;
+pub const MAX_ASSET_NAME_LENGTH: usize = size_of::<AssetName>() - 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixedStringError {
+    /// The string doesn't fit in the buffer alongside its null terminator
+    TooLong { len: usize, max: usize },
+    /// The string contains a character which isn't printable ASCII
+    InvalidChar(char),
+}
+
+impl fmt::Display for FixedStringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FixedStringError::TooLong { len, max } => write!(
+                f,
+                "String of length {} exceeds the maximum length of {}",
+                len, max
+            ),
+            FixedStringError::InvalidChar(c) => write!(f, "Invalid character {:?} in string", c),
+        }
+    }
+}
+
+impl std::error::Error for FixedStringError {}
+
+impl<const N: usize> FixedString<N> {
+    /// Creates a string from `s`, dropping any characters past the maximum length
+    pub fn from_str_truncated(s: &str) -> Self {
+        let mut bytes = [0u8; N];
+        let len = s.len().min(N.saturating_sub(1));
+
+        bytes[..len].copy_from_slice(&s.as_bytes()[..len]);
+
+        Self(bytes)
+    }
+
+    /// The contents of the string up to the null terminator. If the bytes aren't valid UTF-8, only
+    /// the valid prefix is returned.
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|c| *c == 0).unwrap_or(N);
+
+        match std::str::from_utf8(&self.0[..len]) {
+            Ok(s) => s,
+            Err(e) => std::str::from_utf8(&self.0[..e.valid_up_to()]).unwrap_or_default(),
+        }
+    }
+
+    /// The full buffer, including the null terminator and any padding after it
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+
+    /// Returns true if the buffer contains a null terminator
+    pub fn is_terminated(&self) -> bool {
+        self.0.contains(&0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.first().is_none_or(|c| *c == 0)
+    }
+}
+
+impl<const N: usize> Default for FixedString<N> {
+    fn default() -> Self {
+        Self([0u8; N])
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for FixedString<N> {
+    fn from(value: [u8; N]) -> Self {
+        Self(value)
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for FixedString<N> {
+    type Error = FixedStringError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let max = N.saturating_sub(1);
+
+        if value.len() > max {
+            return Err(FixedStringError::TooLong {
+                len: value.len(),
+                max,
+            });
+        }
+
+        if let Some(c) = value
+            .chars()
+            .find(|c| !c.is_ascii() || c.is_ascii_control())
+        {
+            return Err(FixedStringError::InvalidChar(c));
+        }
+
+        Ok(Self::from_str_truncated(value))
+    }
+}
+
+impl<const N: usize> fmt::Display for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> Serialize for FixedString<N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedString<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::try_from(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_name_validation() {
+        assert!(AssetName::try_from("aid_texture_a").is_ok());
+        assert!(AssetName::try_from("a".repeat(MAX_ASSET_NAME_LENGTH).as_str()).is_ok());
+
+        assert_eq!(
+            AssetName::try_from("a".repeat(128).as_str()),
+            Err(FixedStringError::TooLong {
+                len: 128,
+                max: MAX_ASSET_NAME_LENGTH
+            })
+        );
+        assert_eq!(
+            AssetName::try_from("aid\0"),
+            Err(FixedStringError::InvalidChar('\0'))
+        );
+    }
+
+    #[test]
+    fn asset_name_serde_roundtrip() -> Result<(), String> {
+        let name = AssetName::try_from("aid_script_level").map_err(|e| e.to_string())?;
+
+        let json = serde_json::to_string(&name).map_err(|e| e.to_string())?;
+        assert_eq!(json, "\"aid_script_level\"");
+
+        let parsed: AssetName = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        assert_eq!(parsed, name);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+/*!
+A fallback [`AssetLike`] for asset types which don't have a parser yet.
+
+[`Opaque`] accepts every [`AssetType`] and keeps the descriptor and resource chunks exactly as they
+were read, so unknown assets can still go through the generic [`Asset`](super::Asset) code paths
+(eg. [`BNLFile::modify_asset`](crate::BNLFile::modify_asset)) and be written back unchanged.
+*/
+
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
+
+#[cfg(feature = "fs")]
+use crate::asset::{Dump, Parse};
+use crate::{
+    VirtualResource,
+    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType},
+};
+
+/// The verbatim bytes of a descriptor
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct OpaqueDescriptor {
+    bytes: Vec<u8>,
+}
+
+impl OpaqueDescriptor {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.bytes
+    }
+}
+
+impl AssetDescriptor for OpaqueDescriptor {
+    fn from_bytes(data: &[u8]) -> Result<Self, AssetParseError> {
+        Ok(Self {
+            bytes: data.to_vec(),
+        })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
+        Ok(self.bytes.clone())
+    }
+
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(&self.bytes)
+    }
+
+    /// Opaque descriptors don't belong to any one type. This is only a placeholder, as
+    /// [`Opaque::accepts_type`] allows every type.
+    fn asset_type() -> AssetType {
+        AssetType::ResUnknown3
+    }
+}
+
+/**
+An asset of any type, stored as its raw descriptor and resource chunks.
+
+An asset without any resource chunks is stored as having none, so an asset read with an empty list
+of chunks is written back without one.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Opaque {
+    descriptor: OpaqueDescriptor,
+    resource_chunks: Option<Vec<Vec<u8>>>,
+}
+
+impl Opaque {
+    pub fn new(descriptor_bytes: Vec<u8>, resource_chunks: Option<Vec<Vec<u8>>>) -> Self {
+        Self {
+            descriptor: OpaqueDescriptor::new(descriptor_bytes),
+            resource_chunks,
+        }
+    }
+
+    pub fn descriptor(&self) -> &OpaqueDescriptor {
+        &self.descriptor
+    }
+    pub fn descriptor_mut(&mut self) -> &mut OpaqueDescriptor {
+        &mut self.descriptor
+    }
+
+    pub fn resource_chunks(&self) -> Option<&Vec<Vec<u8>>> {
+        self.resource_chunks.as_ref()
+    }
+    pub fn resource_chunks_mut(&mut self) -> &mut Option<Vec<Vec<u8>>> {
+        &mut self.resource_chunks
+    }
+}
+
+impl AssetLike for Opaque {
+    type Descriptor = OpaqueDescriptor;
+
+    fn new(
+        descriptor: &Self::Descriptor,
+        virtual_res: &VirtualResource,
+    ) -> Result<Self, AssetParseError> {
+        let slices = virtual_res.slices();
+
+        Ok(Self {
+            descriptor: descriptor.clone(),
+            resource_chunks: (!slices.is_empty())
+                .then(|| slices.iter().map(|slice| slice.to_vec()).collect()),
+        })
+    }
+
+    fn accepts_type(_asset_type: AssetType) -> bool {
+        true
+    }
+
+    fn get_descriptor(&self) -> Self::Descriptor {
+        self.descriptor.clone()
+    }
+
+    fn get_resource_chunks(&self) -> Option<Vec<Vec<u8>>> {
+        self.resource_chunks.clone()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Dump for Opaque {
+    /// Writes the asset into the directory `dump_path`, as a `descriptor` file and a
+    /// `resource<n>` file for each chunk
+    fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
+        let path = dump_path.as_ref();
+
+        fs::create_dir_all(path)?;
+        fs::write(path.join("descriptor"), self.descriptor.bytes())?;
+
+        if let Some(chunks) = &self.resource_chunks {
+            for (i, chunk) in chunks.iter().enumerate() {
+                fs::write(path.join(format!("resource{}", i)), chunk)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Parse for Opaque {
+    /// Reads an asset from a directory written by [`Opaque::dump`]
+    fn parse<P: AsRef<Path>>(parse_path: P) -> Result<Self, AssetParseError> {
+        let path = parse_path.as_ref();
+
+        let descriptor_path = path.join("descriptor");
+
+        if !descriptor_path.is_file() {
+            return Err(AssetParseError::FileNotFound("descriptor".to_string()));
+        }
+
+        let descriptor_bytes = fs::read(descriptor_path)?;
+
+        let mut resource_chunks = vec![];
+
+        while let Ok(chunk) = fs::read(path.join(format!("resource{}", resource_chunks.len()))) {
+            resource_chunks.push(chunk);
+        }
+
+        Ok(Self::new(
+            descriptor_bytes,
+            (!resource_chunks.is_empty()).then_some(resource_chunks),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{AssetMetadata, BNLFile, RawAsset};
+
+    #[test]
+    fn opaque_modify_unknown_asset() -> Result<(), String> {
+        let mut bnl = BNLFile::default();
+
+        let raw = RawAsset::new(
+            AssetMetadata::new("aid_unknown_test", AssetType::ResActorGoals, 1, 2),
+            vec![0x01, 0x02, 0x03, 0x04],
+            Some(vec![vec![0xaa; 16], vec![0xbb; 8]]),
+        );
+
+        bnl.append_raw_asset(raw.clone())
+            .map_err(|e| e.to_string())?;
+
+        // Unchanged assets must be written back verbatim
+        bnl.modify_asset::<Opaque, _>("aid_unknown_test", |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+
+        let written = bnl
+            .get_raw_asset("aid_unknown_test")
+            .ok_or("Asset missing")?;
+
+        assert_eq!(written.descriptor_bytes(), raw.descriptor_bytes());
+        assert_eq!(written.resource_chunks(), raw.resource_chunks());
+        assert_eq!(written.metadata().asset_type(), AssetType::ResActorGoals);
+
+        bnl.modify_asset::<Opaque, _>("aid_unknown_test", |asset| {
+            asset.asset_mut().descriptor_mut().bytes_mut()[0] = 0xff;
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            bnl.get_raw_asset("aid_unknown_test")
+                .ok_or("Asset missing")?
+                .descriptor_bytes(),
+            &[0xff, 0x02, 0x03, 0x04]
+        );
+
+        Ok(())
+    }
+}
@@ -1,12 +1,130 @@
+use std::fmt;
+
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 
 pub(crate) type ParamsShape = IndexMap<String, ParamDescriptor>;
 
+/// A parameter of a [`ParamsShape`], with its position in the operands
+#[derive(Debug, Clone, Copy)]
+pub struct ParamSlot<'a> {
+    pub name: &'a str,
+    /// The offset of the parameter from the start of the operands
+    pub offset: usize,
+    pub descriptor: &'a ParamDescriptor,
+}
+
+/// Why operand bytes don't fit their shape, see [`ShapeLayout::validate_operand_bytes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperandError {
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The string parameter with this name has no zero terminator within its field
+    UnterminatedString(String),
+}
+
+impl fmt::Display for OperandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperandError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Expected {:#x} bytes of operands, found {:#x}",
+                expected, actual
+            ),
+            OperandError::UnterminatedString(name) => {
+                write!(f, "String parameter {} is not terminated", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OperandError {}
+
+/// The layout of the parameters of a [`ParamsShape`], which are packed in order with no padding
+pub trait ShapeLayout {
+    /// Every parameter with its offset, in order
+    fn slots(&self) -> Vec<ParamSlot<'_>>;
+
+    fn offset_of(&self, name: &str) -> Option<usize> {
+        self.slots()
+            .into_iter()
+            .find(|slot| slot.name == name)
+            .map(|slot| slot.offset)
+    }
+
+    /// The number of bytes the parameters cover. Operands can be longer than this when the end of
+    /// them isn't understood yet.
+    fn total_size(&self) -> usize;
+
+    /**
+    Checks that `bytes` is long enough to hold every parameter, and that each string parameter is
+    terminated within its field.
+
+    # Errors
+    - [`OperandError::SizeMismatch`] if `bytes` is shorter than [`ShapeLayout::total_size`]
+    - [`OperandError::UnterminatedString`] for the first string without a terminator
+    */
+    fn validate_operand_bytes(&self, bytes: &[u8]) -> Result<(), OperandError> {
+        if bytes.len() < self.total_size() {
+            return Err(OperandError::SizeMismatch {
+                expected: self.total_size(),
+                actual: bytes.len(),
+            });
+        }
+
+        for slot in self.slots() {
+            let field = &bytes[slot.offset..slot.offset + slot.descriptor.param_type.size()];
+
+            let terminated = match slot.descriptor.param_type {
+                ParamType::String(_) => field.contains(&0),
+                ParamType::WString(_) => field.chunks_exact(2).any(|unit| unit == [0, 0]),
+                _ => true,
+            };
+
+            if !terminated {
+                return Err(OperandError::UnterminatedString(slot.name.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ShapeLayout for ParamsShape {
+    fn slots(&self) -> Vec<ParamSlot<'_>> {
+        let mut offset = 0;
+
+        self.iter()
+            .map(|(name, descriptor)| {
+                let slot = ParamSlot {
+                    name,
+                    offset,
+                    descriptor,
+                };
+                offset += descriptor.param_type.size();
+
+                slot
+            })
+            .collect()
+    }
+
+    fn total_size(&self) -> usize {
+        self.values()
+            .map(|descriptor| descriptor.param_type.size())
+            .sum()
+    }
+}
+
 pub trait HasParams {
     fn get_shape(&self) -> ParamsShape;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// A value which is either one of the recognised values `K`, or the raw value `U` it was read
+/// from. Serialised as `{"known": ...}` or `{"unknown": ...}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum KnownUnknown<K, U: Clone>
 where
     K: TryFrom<U>,
@@ -16,6 +134,54 @@ where
     Unknown(U),
 }
 
+impl<K, U> KnownUnknown<K, U>
+where
+    K: TryFrom<U>,
+    U: Clone + From<K>,
+{
+    pub fn is_known(&self) -> bool {
+        matches!(self, Self::Known(_))
+    }
+
+    pub fn known(&self) -> Option<&K> {
+        match self {
+            Self::Known(known) => Some(known),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    pub fn unknown(&self) -> Option<&U> {
+        match self {
+            Self::Known(_) => None,
+            Self::Unknown(unknown) => Some(unknown),
+        }
+    }
+
+    /// Converts the known value with `f`, keeping unknown values as they are
+    pub fn map_known<K2, F>(self, f: F) -> KnownUnknown<K2, U>
+    where
+        K2: TryFrom<U>,
+        U: From<K2>,
+        F: FnOnce(K) -> K2,
+    {
+        match self {
+            Self::Known(known) => KnownUnknown::Known(f(known)),
+            Self::Unknown(unknown) => KnownUnknown::Unknown(unknown),
+        }
+    }
+
+    /// The raw value, converting known values back
+    pub fn raw(&self) -> U
+    where
+        K: Clone,
+    {
+        match self {
+            Self::Known(known) => known.clone().into(),
+            Self::Unknown(unknown) => unknown.clone(),
+        }
+    }
+}
+
 impl<K, U> HasParams for KnownUnknown<K, U>
 where
     K: HasParams + TryFrom<U>,
@@ -129,3 +295,135 @@ impl ParamDescriptor {
         &self.description
     }
 }
+
+/// A parameter decoded according to its [`ParamType`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ParamValue {
+    F32(f32),
+    F64(f64),
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+
+    /// A string, up to the first zero byte of its field
+    String(String),
+    /// A UTF-16 string, up to the first zero code unit of its field
+    WString(String),
+    Bytes(Vec<u8>),
+}
+
+impl ParamValue {
+    /// Decodes `bytes` as `param_type`, returning `None` if it isn't exactly the size of the type
+    pub fn from_bytes(param_type: &ParamType, bytes: &[u8]) -> Option<ParamValue> {
+        if bytes.len() != param_type.size() {
+            return None;
+        }
+
+        Some(match param_type {
+            ParamType::F32 => ParamValue::F32(f32::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::F64 => ParamValue::F64(f64::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::U8 => ParamValue::U8(bytes[0]),
+            ParamType::I8 => ParamValue::I8(bytes[0] as i8),
+            ParamType::U16 => ParamValue::U16(u16::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::I16 => ParamValue::I16(i16::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::U32 => ParamValue::U32(u32::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::I32 => ParamValue::I32(i32::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::U64 => ParamValue::U64(u64::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::I64 => ParamValue::I64(i64::from_le_bytes(bytes.try_into().ok()?)),
+            ParamType::String(_) => {
+                let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+                ParamValue::String(String::from_utf8_lossy(&bytes[..len]).into_owned())
+            }
+            ParamType::WString(_) => ParamValue::WString(String::from_utf16_lossy(
+                &bytes
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .take_while(|&unit| unit != 0)
+                    .collect::<Vec<u16>>(),
+            )),
+            ParamType::Bytes(_) => ParamValue::Bytes(bytes.to_vec()),
+        })
+    }
+
+    /**
+    Encodes the value as `param_type`, padding strings with zeroes to the size of their field.
+
+    Returns `None` if the value is a different type to `param_type`, or doesn't fit in it. Strings
+    must leave room for at least one zero terminator.
+    */
+    pub fn to_bytes(&self, param_type: &ParamType) -> Option<Vec<u8>> {
+        let size = param_type.size();
+
+        let mut bytes = match (param_type, self) {
+            (ParamType::F32, ParamValue::F32(v)) => return Some(v.to_param_bytes()),
+            (ParamType::F64, ParamValue::F64(v)) => return Some(v.to_param_bytes()),
+            (ParamType::U8, ParamValue::U8(v)) => return Some(v.to_param_bytes()),
+            (ParamType::I8, ParamValue::I8(v)) => return Some(v.to_param_bytes()),
+            (ParamType::U16, ParamValue::U16(v)) => return Some(v.to_param_bytes()),
+            (ParamType::I16, ParamValue::I16(v)) => return Some(v.to_param_bytes()),
+            (ParamType::U32, ParamValue::U32(v)) => return Some(v.to_param_bytes()),
+            (ParamType::I32, ParamValue::I32(v)) => return Some(v.to_param_bytes()),
+            (ParamType::U64, ParamValue::U64(v)) => return Some(v.to_param_bytes()),
+            (ParamType::I64, ParamValue::I64(v)) => return Some(v.to_param_bytes()),
+            (ParamType::Bytes(_), ParamValue::Bytes(bytes)) => {
+                return (bytes.len() == size).then(|| bytes.clone());
+            }
+            (ParamType::String(_), ParamValue::String(string)) => string.as_bytes().to_vec(),
+            (ParamType::WString(_), ParamValue::WString(string)) => string
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect(),
+            _ => return None,
+        };
+
+        let terminator_size = match param_type {
+            ParamType::WString(_) => 2,
+            _ => 1,
+        };
+
+        // The terminator is required, otherwise the game would read past the field
+        if bytes.len() + terminator_size > size {
+            return None;
+        }
+
+        bytes.resize(size, 0);
+
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::script::ops::{KnownOpcode, ScriptOpcode};
+
+    #[test]
+    fn known_unknown_serde() -> Result<(), String> {
+        let known = ScriptOpcode::from(0x1);
+        let unknown = ScriptOpcode::from(0x11);
+
+        assert!(known.is_known() && !unknown.is_known());
+        assert_eq!(known.known(), Some(&KnownOpcode::SetBackground));
+        assert_eq!(unknown.unknown(), Some(&0x11));
+        assert_eq!(unknown.raw(), 0x11);
+        assert_eq!(
+            known.map_known(|opcode| opcode.operands_size() as u32),
+            KnownUnknown::<u32, u32>::Known(0x80)
+        );
+
+        let json = serde_json::to_string(&[known, unknown]).map_err(|e| e.to_string())?;
+        assert_eq!(json, r#"[{"known":"SetBackground"},{"unknown":17}]"#);
+
+        let read: Vec<ScriptOpcode> = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        assert_eq!(read, vec![known, unknown]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+/*!
+Finding what a script depends on and sets up, without running it.
+
+Asset ids are recognised from the parameters of known opcodes, so operations with unknown opcodes
+are skipped. Scripts can refer to assets in other BNL files (backgrounds are usually stored with
+their level), so a reference which isn't in the same file isn't necessarily broken.
+*/
+
+use serde::Serialize;
+
+use crate::asset::{
+    AssetType,
+    param::KnownUnknown::Known,
+    script::{ScriptDescriptor, ScriptOperation, ops::KnownOpcode},
+};
+
+/// What a script refers to an asset as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum ReferenceKind {
+    Background,
+    Cutscene,
+    Ghoulybox,
+    ActorAttribs,
+}
+
+impl ReferenceKind {
+    /// The parameters of known opcodes which hold asset ids
+    const PARAMS: [(&'static str, ReferenceKind); 4] = [
+        ("background_aid", ReferenceKind::Background),
+        ("cutscene_aid", ReferenceKind::Cutscene),
+        ("ghoulybox_aid", ReferenceKind::Ghoulybox),
+        ("actor_attribs_aid", ReferenceKind::ActorAttribs),
+    ];
+
+    /// The type the referenced asset should have, if known
+    pub fn asset_type(&self) -> Option<AssetType> {
+        match self {
+            ReferenceKind::Background => None,
+            ReferenceKind::Cutscene => Some(AssetType::ResCutscene),
+            ReferenceKind::Ghoulybox => Some(AssetType::ResGhoulybox),
+            ReferenceKind::ActorAttribs => Some(AssetType::ResActorAttribs),
+        }
+    }
+}
+
+/// An asset id found in the parameters of a script operation
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct AssetReference {
+    /// The index of the operation in [`ScriptDescriptor::operations`]
+    pub operation: usize,
+    pub kind: ReferenceKind,
+    pub asset_name: String,
+}
+
+/// A challenge created by a script operation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Challenge {
+    TimeLimit { duration: f32 },
+    KillAllByTag { actor_tag: String },
+    FindTheGhoulieKey,
+    WeaponsOnly,
+    FindTheKey,
+    NoBreakHouse,
+}
+
+impl Challenge {
+    fn from_operation(op: &ScriptOperation) -> Option<Challenge> {
+        let Known(opcode) = op.opcode() else {
+            return None;
+        };
+
+        Some(match opcode {
+            KnownOpcode::CreateTimeLimitChallenge => Challenge::TimeLimit {
+                duration: f32::from_le_bytes(op.param_bytes("duration")?.try_into().ok()?),
+            },
+            KnownOpcode::CreateKillAllByTagChallenge => Challenge::KillAllByTag {
+                actor_tag: op.string_param("actor_tag")?,
+            },
+            KnownOpcode::CreateFindTheGhoulieKeyChallenge => Challenge::FindTheGhoulieKey,
+            KnownOpcode::CreateWeaponsOnlyChallenge => Challenge::WeaponsOnly,
+            KnownOpcode::CreateFindTheKeyChallenge => Challenge::FindTheKey,
+            KnownOpcode::CreateNoBreakHouseChallenge => Challenge::NoBreakHouse,
+            _ => return None,
+        })
+    }
+}
+
+impl ScriptDescriptor {
+    /// Every non-empty asset id in the parameters of the operations, in order
+    pub fn referenced_assets(&self) -> Vec<AssetReference> {
+        let mut references = vec![];
+
+        for (operation, op) in self.operations.iter().enumerate() {
+            for (param, kind) in ReferenceKind::PARAMS {
+                if let Some(asset_name) = op.string_param(param)
+                    && !asset_name.is_empty()
+                {
+                    references.push(AssetReference {
+                        operation,
+                        kind,
+                        asset_name,
+                    });
+                }
+            }
+        }
+
+        references
+    }
+
+    /// The challenges created by the script, in order, paired with the index of the operation
+    /// which creates each
+    pub fn challenges(&self) -> Vec<(usize, Challenge)> {
+        self.operations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, op)| Some((i, Challenge::from_operation(op)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::script::text::assemble;
+
+    #[test]
+    fn script_references_and_challenges() -> Result<(), String> {
+        let descriptor = assemble(
+            r#"
+            SetBackground "aid_background_scullery"@0x80
+            SpawnGhoulieWithBox "aid_ghoulybox_zombie"@0x80 02000000 "aid_actorattribs_zombie"@0x80 00000000
+            CreateTimeLimitChallenge 0000f042
+            CreateWeaponsOnlyChallenge
+            0x11 01000000
+            EndScript
+            "#,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let references = descriptor.referenced_assets();
+        assert_eq!(
+            references
+                .iter()
+                .map(|r| (r.operation, r.kind, r.asset_name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, ReferenceKind::Background, "aid_background_scullery"),
+                (1, ReferenceKind::Ghoulybox, "aid_ghoulybox_zombie"),
+                (1, ReferenceKind::ActorAttribs, "aid_actorattribs_zombie"),
+            ]
+        );
+
+        assert_eq!(
+            descriptor.challenges(),
+            vec![
+                (2, Challenge::TimeLimit { duration: 120.0 }),
+                (3, Challenge::WeaponsOnly),
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -1,23 +1,27 @@
+pub mod analysis;
 pub mod ops;
+pub mod text;
 
-use std::io::{Cursor, Read, Write};
+use std::io::{Cursor, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 
 use crate::{
     VirtualResource,
     asset::{
-        AssetDescriptor, AssetError, AssetLike, AssetParseError, AssetType,
-        param::{HasParams, Param, ParamsShape},
+        AssetDescriptor, AssetError, AssetLike, AssetParseError, AssetType, OriginalBytes,
+        param::{HasParams, OperandError, Param, ParamValue, ParamsShape, ShapeLayout},
         script::ops::{KnownOpcode, ScriptOpcode},
     },
+    utils::reader::Le,
 };
 
 use super::param::KnownUnknown::{Known, Unknown};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ScriptDescriptor {
     operations: Vec<ScriptOperation>,
+    original_bytes: OriginalBytes,
 }
 
 impl ScriptDescriptor {
@@ -28,6 +32,33 @@ impl ScriptDescriptor {
     pub fn operations_mut(&mut self) -> &mut Vec<ScriptOperation> {
         &mut self.operations
     }
+
+    /**
+    Reads a script like [`AssetDescriptor::from_bytes`], also returning the operations whose
+    operands don't fit their opcode, see [`ScriptDescriptor::operand_issues`]. Such operations are
+    kept as they were read, so these aren't errors.
+
+    # Errors
+    - The same as [`AssetDescriptor::from_bytes`]
+    */
+    pub fn from_bytes_with_issues(
+        data: &[u8],
+    ) -> Result<(Self, Vec<(usize, OperandError)>), AssetParseError> {
+        let descriptor = Self::from_bytes(data)?;
+        let issues = descriptor.operand_issues();
+
+        Ok((descriptor, issues))
+    }
+
+    /// The index and error of every operation which fails [`ScriptOperation::validate`], which
+    /// usually means the script was misread
+    pub fn operand_issues(&self) -> Vec<(usize, OperandError)> {
+        self.operations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, op)| op.validate().err().map(|e| (i, e)))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,12 +75,16 @@ pub struct Script {
 }
 
 impl Script {
+    pub fn descriptor(&self) -> &ScriptDescriptor {
+        &self.descriptor
+    }
+
     pub fn descriptor_mut(&mut self) -> &mut ScriptDescriptor {
         &mut self.descriptor
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ScriptOperation {
     size: u32,
     opcode: ScriptOpcode,
@@ -114,23 +149,114 @@ impl ScriptOperation {
         bytes
     }
 
+    /// Checks the operands against the opcode, see [`KnownOpcode::validate_operand_bytes`].
+    /// Operations with unknown opcodes are always valid.
+    pub fn validate(&self) -> Result<(), OperandError> {
+        match self.opcode {
+            Known(opcode) => opcode.validate_operand_bytes(&self.operand_bytes),
+            Unknown(_) => Ok(()),
+        }
+    }
+
+    /// The operand bytes of the parameter called `name`, if the opcode has one and the operands
+    /// are long enough to contain it
+    pub fn param_bytes(&self, name: &str) -> Option<&[u8]> {
+        let shape = self.get_shape();
+        let offset = shape.offset_of(name)?;
+
+        self.operand_bytes
+            .get(offset..offset + shape.get(name)?.param_type.size())
+    }
+
+    /// Decodes every parameter of the opcode which fits in the operands. Unknown opcodes have no
+    /// parameters.
+    pub fn params(&self) -> Vec<(String, ParamValue)> {
+        let shape = self.get_shape();
+
+        shape
+            .slots()
+            .into_iter()
+            .filter_map(|slot| {
+                let param_type = &slot.descriptor.param_type;
+                let bytes = self
+                    .operand_bytes
+                    .get(slot.offset..slot.offset + param_type.size())?;
+
+                Some((
+                    slot.name.to_string(),
+                    ParamValue::from_bytes(param_type, bytes)?,
+                ))
+            })
+            .collect()
+    }
+
+    /**
+    Encodes each value into the parameter with its name. Nothing is written unless every value can
+    be.
+
+    # Errors
+    - [`ScriptError::UnsupportedOutputType`] if the opcode has no parameter with a given name
+    - [`ScriptError::InvalidInput`] if a value is a different type to its parameter, or doesn't fit
+    - [`ScriptError::SizeMismatch`] if the operands are too short to contain a parameter
+    */
+    pub fn set_params<I, S>(&mut self, params: I) -> Result<(), ScriptError>
+    where
+        I: IntoIterator<Item = (S, ParamValue)>,
+        S: AsRef<str>,
+    {
+        let shape = self.get_shape();
+        let mut writes = vec![];
+
+        for (name, value) in params {
+            let (Some(offset), Some(details)) =
+                (shape.offset_of(name.as_ref()), shape.get(name.as_ref()))
+            else {
+                return Err(ScriptError::UnsupportedOutputType);
+            };
+
+            let bytes = value
+                .to_bytes(&details.param_type)
+                .ok_or(ScriptError::InvalidInput)?;
+
+            if offset + bytes.len() > self.operand_bytes.len() {
+                return Err(ScriptError::SizeMismatch);
+            }
+
+            writes.push((offset, bytes));
+        }
+
+        for (offset, bytes) in writes {
+            self.operand_bytes[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+
+        Ok(())
+    }
+
+    /// The string parameter called `name`, up to its first zero byte
+    pub fn string_param(&self, name: &str) -> Option<String> {
+        let bytes = self.param_bytes(name)?;
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+        Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
     pub fn operand_bytes_mut(&mut self) -> &mut Vec<u8> {
         &mut self.operand_bytes
     }
 
     pub fn set_param_by_name<T: Param>(&mut self, name: &str, val: T) -> Result<(), ScriptError> {
         let shape = self.get_shape();
-        if let Some(details) = shape.get(name) {
+        if let (Some(details), Some(offset)) = (shape.get(name), shape.offset_of(name)) {
             if size_of::<T>() != details.param_type.size() {
                 return Err(ScriptError::SizeMismatch);
             }
 
             let bytes = val.to_param_bytes();
 
-            // TODO: Make this based on the parameter's actual offset
-            let offset = 0;
-
-            self.operand_bytes_mut()[offset..].copy_from_slice(&bytes);
+            self.operand_bytes_mut()
+                .get_mut(offset..offset + bytes.len())
+                .ok_or(ScriptError::SizeMismatch)?
+                .copy_from_slice(&bytes);
 
             Ok(())
         } else {
@@ -145,29 +271,31 @@ impl AssetDescriptor for ScriptDescriptor {
             return Err(AssetParseError::InputTooSmall);
         }
 
-        let mut cur = Cursor::new(data);
+        let mut reader = Le::new(data);
 
         let mut operations = Vec::new();
 
-        let mut size = cur.read_u32::<LittleEndian>()?;
-        let mut opcode = cur.read_u32::<LittleEndian>()?;
+        let [mut size, mut opcode] = reader.array::<u32, 2>()?;
 
         while opcode != 0 {
             if size < 8 {
                 return Err(AssetParseError::ErrorParsingDescriptor);
             }
 
-            let mut operand_bytes = vec![0x00; (size as usize) - 8];
-            cur.read_exact(&mut operand_bytes)?;
+            if size as usize - 8 > reader.remaining().len() {
+                return Err(AssetParseError::InputTooSmall);
+            }
+
+            let operand_bytes = reader.bytes(size as usize - 8)?.to_vec();
 
+            // Operands which don't fit their opcode are kept, see `operand_issues`
             operations.push(ScriptOperation {
                 size,
                 opcode: opcode.into(),
                 operand_bytes,
             });
 
-            size = cur.read_u32::<LittleEndian>()?;
-            opcode = cur.read_u32::<LittleEndian>()?;
+            [size, opcode] = reader.array::<u32, 2>()?;
         }
 
         if size == 8 && opcode == 0 {
@@ -182,18 +310,24 @@ impl AssetDescriptor for ScriptDescriptor {
         }
 
         // TODO: Sanity check the read length here
-        Ok(ScriptDescriptor { operations })
+        Ok(ScriptDescriptor {
+            operations,
+            original_bytes: OriginalBytes::new(data),
+        })
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
-        let mut bytes = Vec::new();
+        let mut bytes = Cursor::new(self.original_bytes.base(self.size()));
 
-        self.operations
-            .iter()
-            .map(|op| op.to_bytes())
-            .for_each(|b| bytes.extend_from_slice(&b));
+        for op in &self.operations {
+            bytes.write_all(&op.to_bytes())?;
+        }
 
-        Ok(bytes)
+        Ok(bytes.into_inner())
+    }
+
+    fn original_bytes(&self) -> Option<&[u8]> {
+        Some(self.original_bytes.as_bytes())
     }
 
     fn size(&self) -> usize {
@@ -233,3 +367,170 @@ impl AssetLike for Script {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::asset::param::ParamType;
+
+    fn operation() -> impl Strategy<Value = ScriptOperation> {
+        // Opcode 0 ends the script, so it can't appear before the end
+        (1u32.., prop::collection::vec(any::<u8>(), 0..0x40)).prop_map(|(opcode, mut operands)| {
+            let opcode = ScriptOpcode::from(opcode);
+
+            if let Known(known) = opcode {
+                operands.resize(known.operands_size(), 0);
+
+                // Known opcodes must have terminated strings to be read back
+                for slot in known.get_shape().slots() {
+                    if let ParamType::String(size) = slot.descriptor.param_type() {
+                        operands[slot.offset + size - 1] = 0;
+                    }
+                }
+            }
+
+            ScriptOperation::new(opcode, operands).expect("Operands are sized for the opcode")
+        })
+    }
+
+    #[test]
+    fn script_operation_params() -> Result<(), String> {
+        let mut op = ScriptOperation::new(Known(KnownOpcode::UpdateDoor), [0u8; 0x10])
+            .map_err(|e| format!("{:?}", e))?;
+
+        op.set_params([
+            ("door_id", ParamValue::U32(3)),
+            ("open_status", ParamValue::U32(1)),
+        ])
+        .map_err(|e| format!("{:?}", e))?;
+
+        assert_eq!(&op.operand_bytes()[..8], &[3, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(
+            op.params()[1],
+            ("open_status".to_string(), ParamValue::U32(1))
+        );
+
+        let mut op = ScriptOperation::new(Known(KnownOpcode::SetBackground), [0u8; 0x80])
+            .map_err(|e| format!("{:?}", e))?;
+
+        op.set_params([(
+            "background_aid",
+            ParamValue::String("aid_background_scullery".to_string()),
+        )])
+        .map_err(|e| format!("{:?}", e))?;
+
+        assert_eq!(
+            op.params(),
+            vec![(
+                "background_aid".to_string(),
+                ParamValue::String("aid_background_scullery".to_string())
+            )]
+        );
+
+        // Wrong types, unknown names and strings without room for a terminator are rejected
+        // without writing anything
+        assert!(
+            op.set_params([("background_aid", ParamValue::U32(1))])
+                .is_err()
+        );
+        assert!(op.set_params([("door_id", ParamValue::U32(1))]).is_err());
+        assert!(
+            op.set_params([("background_aid", ParamValue::String("a".repeat(0x80)))])
+                .is_err()
+        );
+        assert_eq!(
+            op.string_param("background_aid").as_deref(),
+            Some("aid_background_scullery")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_operand_layout() -> Result<(), String> {
+        let shape = KnownOpcode::SpawnGhoulieWithBox.get_shape();
+
+        assert_eq!(
+            shape
+                .slots()
+                .iter()
+                .map(|slot| (slot.name, slot.offset))
+                .collect::<Vec<_>>(),
+            vec![
+                ("ghoulybox_aid", 0x0),
+                ("spawn_count", 0x80),
+                ("actor_attribs_aid", 0x84)
+            ]
+        );
+        assert_eq!(shape.total_size(), 0x104);
+
+        // The operands may run past the known parameters, but must be the size of the opcode
+        let mut operands = vec![0u8; 0x108];
+        assert!(
+            KnownOpcode::SpawnGhoulieWithBox
+                .validate_operand_bytes(&operands)
+                .is_ok()
+        );
+        assert_eq!(
+            KnownOpcode::SpawnGhoulieWithBox.validate_operand_bytes(&operands[..0x104]),
+            Err(OperandError::SizeMismatch {
+                expected: 0x108,
+                actual: 0x104
+            })
+        );
+
+        operands[0x84..0x104].fill(b'a');
+        assert_eq!(
+            KnownOpcode::SpawnGhoulieWithBox.validate_operand_bytes(&operands),
+            Err(OperandError::UnterminatedString(
+                "actor_attribs_aid".to_string()
+            ))
+        );
+
+        // Scripts containing the malformed operation are still read, with the issue reported
+        let op = ScriptOperation::new(Known(KnownOpcode::SpawnGhoulieWithBox), &operands)
+            .map_err(|e| format!("{:?}", e))?;
+        let end = ScriptOperation::new(Known(KnownOpcode::EndScript), [0u8; 0])
+            .map_err(|e| format!("{:?}", e))?;
+
+        let bytes = [op.to_bytes(), end.to_bytes()].concat();
+        let (script, issues) =
+            ScriptDescriptor::from_bytes_with_issues(&bytes).map_err(|e| e.to_string())?;
+
+        assert_eq!(script.operations(), [op, end]);
+        assert_eq!(
+            issues,
+            vec![(
+                0,
+                OperandError::UnterminatedString("actor_attribs_aid".to_string())
+            )]
+        );
+        assert_eq!(script.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn script_descriptor_roundtrip(mut operations in prop::collection::vec(operation(), 0..16)) {
+            operations.push(
+                ScriptOperation::new(Known(KnownOpcode::EndScript), [0u8; 0])
+                    .expect("EndScript has no operands"),
+            );
+
+            let descriptor = ScriptDescriptor { operations, original_bytes: Default::default() };
+            let bytes = descriptor.to_bytes().expect("Scripts can always be written");
+
+            prop_assert_eq!(bytes.len(), descriptor.size());
+
+            let read = ScriptDescriptor::from_bytes(&bytes)
+                .map_err(|e| TestCaseError::fail(e.to_string()))?;
+            prop_assert_eq!(read.original_bytes(), Some(&bytes[..]));
+            prop_assert_eq!(read.to_bytes().ok(), Some(bytes));
+            prop_assert_eq!(read, descriptor);
+        }
+    }
+}
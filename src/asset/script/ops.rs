@@ -1,7 +1,10 @@
 use indexmap::IndexMap;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
 
-use crate::asset::param::{HasParams, KnownUnknown, ParamDescriptor, ParamType, ParamsShape};
+use crate::asset::param::{
+    HasParams, KnownUnknown, OperandError, ParamDescriptor, ParamType, ParamsShape, ShapeLayout,
+};
 
 pub type ScriptOpcode = KnownUnknown<KnownOpcode, u32>;
 
@@ -14,7 +17,20 @@ impl From<ScriptOpcode> for u32 {
     }
 }
 
-#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive, PartialEq)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    TryFromPrimitive,
+    IntoPrimitive,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::EnumString,
+    strum::Display,
+)]
 #[repr(u32)]
 pub enum KnownOpcode {
     EndScript = 0x0,
@@ -267,4 +283,21 @@ impl KnownOpcode {
             KnownOpcode::UpdateDoor => 0x10,
         }
     }
+
+    /**
+    Checks that `bytes` is [`KnownOpcode::operands_size`] long and fits the shape of the opcode.
+
+    # Errors
+    See [`ShapeLayout::validate_operand_bytes`]
+    */
+    pub fn validate_operand_bytes(&self, bytes: &[u8]) -> Result<(), OperandError> {
+        if bytes.len() != self.operands_size() {
+            return Err(OperandError::SizeMismatch {
+                expected: self.operands_size(),
+                actual: bytes.len(),
+            });
+        }
+
+        self.get_shape().validate_operand_bytes(bytes)
+    }
 }
@@ -0,0 +1,297 @@
+/*!
+A line based text format for scripts, so that they can be edited without writing Rust.
+
+Each line is a single operation: the name of its opcode (or its number, for opcodes which aren't
+known yet) followed by its operands. Operands are written as hex bytes (`0a0b0c0d`), or as a quoted
+string padded with zeroes to a fixed size (`"aid_background"@0x80`). Anything after a `#` outside
+of a string is a comment, and blank lines are ignored.
+
+```text
+SetBackground "aid_background_scullery"@0x80
+CreateTimeLimitChallenge 0000f042
+0x11 01000000
+EndScript
+```
+*/
+
+use std::{fmt, str::FromStr};
+
+use crate::asset::{
+    param::{HasParams, ParamType},
+    script::{
+        ScriptDescriptor, ScriptOperation,
+        ops::{KnownOpcode, ScriptOpcode},
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptTextError {
+    /// The line the error was found on, starting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptTextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptTextError {}
+
+/// Writes each operation of `descriptor` on its own line
+pub fn disassemble(descriptor: &ScriptDescriptor) -> String {
+    let mut text = String::new();
+
+    for op in descriptor.operations() {
+        match op.opcode() {
+            ScriptOpcode::Known(known) => text.push_str(&known.to_string()),
+            ScriptOpcode::Unknown(value) => text.push_str(&format!("{:#x}", value)),
+        }
+
+        let mut operands = op.operand_bytes();
+
+        if let Some(size) = leading_string_size(op)
+            && let Some(string) = padded_string(&operands[..size])
+        {
+            text.push_str(&format!(" \"{}\"@{:#x}", string, size));
+            operands = &operands[size..];
+        }
+
+        for word in operands.chunks(4) {
+            text.push(' ');
+            word.iter()
+                .for_each(|b| text.push_str(&format!("{:02x}", b)));
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+/**
+Parses the text written by [`disassemble`] back into a [`ScriptDescriptor`].
+
+# Errors
+- [`ScriptTextError`] if a line can't be parsed, the operands of a known opcode have the wrong
+  size, or the script doesn't end with `EndScript`
+*/
+pub fn assemble(text: &str) -> Result<ScriptDescriptor, ScriptTextError> {
+    let mut operations = vec![];
+    let mut last_line = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| ScriptTextError {
+            line: line_number,
+            message,
+        };
+
+        let tokens = tokenise(line).map_err(error)?;
+
+        let Some((opcode_token, operand_tokens)) = tokens.split_first() else {
+            continue;
+        };
+
+        let opcode = parse_opcode(opcode_token).map_err(error)?;
+
+        let mut operands = vec![];
+
+        for token in operand_tokens {
+            operands.extend(parse_operand(token).map_err(error)?);
+        }
+
+        let op = ScriptOperation::new(opcode, &operands).map_err(|_| {
+            error(format!(
+                "{} takes {:#x} bytes of operands, but {:#x} were given",
+                opcode_token,
+                match opcode {
+                    ScriptOpcode::Known(known) => known.operands_size(),
+                    ScriptOpcode::Unknown(_) => operands.len(),
+                },
+                operands.len()
+            ))
+        })?;
+
+        operations.push(op);
+        last_line = line_number;
+    }
+
+    if !matches!(
+        operations.last().map(ScriptOperation::opcode),
+        Some(ScriptOpcode::Known(KnownOpcode::EndScript))
+    ) {
+        return Err(ScriptTextError {
+            line: last_line,
+            message: "Scripts must end with EndScript".to_string(),
+        });
+    }
+
+    Ok(ScriptDescriptor {
+        operations,
+        original_bytes: Default::default(),
+    })
+}
+
+/// The size of the string parameter at the start of the operands, if the opcode has one
+fn leading_string_size(op: &ScriptOperation) -> Option<usize> {
+    // Parameters are currently always read from the start of the operands
+    match op.get_shape().first()?.1.param_type() {
+        ParamType::String(size) if *size <= op.operand_bytes().len() => Some(*size),
+        _ => None,
+    }
+}
+
+/// Returns the string in `bytes` if it is printable and only followed by zeroes
+fn padded_string(bytes: &[u8]) -> Option<&str> {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let (string, padding) = bytes.split_at(len);
+
+    let printable = string
+        .iter()
+        .all(|&b| (0x20..0x7f).contains(&b) && b != b'"');
+
+    // The terminator is required, otherwise the string couldn't be assembled again
+    (printable && !padding.is_empty() && padding.iter().all(|&b| b == 0))
+        .then(|| std::str::from_utf8(string).ok())
+        .flatten()
+}
+
+/// Splits a line into whitespace separated tokens, keeping quoted strings whole and dropping
+/// comments
+fn tokenise(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut in_string = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            '#' if !in_string => break,
+            c if c.is_whitespace() && !in_string => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_string {
+        return Err("Unterminated string".to_string());
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn parse_opcode(token: &str) -> Result<ScriptOpcode, String> {
+    if let Ok(known) = KnownOpcode::from_str(token) {
+        return Ok(ScriptOpcode::Known(known));
+    }
+
+    parse_number(token)
+        .map(ScriptOpcode::from)
+        .ok_or_else(|| format!("Unknown opcode {}", token))
+}
+
+fn parse_number(token: &str) -> Option<u32> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+fn parse_operand(token: &str) -> Result<Vec<u8>, String> {
+    if let Some(rest) = token.strip_prefix('"') {
+        let (string, size) = rest
+            .split_once("\"@")
+            .ok_or_else(|| format!("Strings must be followed by @<size>: {}", token))?;
+
+        let size = parse_number(size).ok_or_else(|| format!("Invalid size in {}", token))? as usize;
+
+        if string.len() >= size {
+            return Err(format!(
+                "\"{}\" doesn't fit in {:#x} bytes with its terminator",
+                string, size
+            ));
+        }
+
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize(size, 0);
+
+        return Ok(bytes);
+    }
+
+    if !token.len().is_multiple_of(2) {
+        return Err(format!("Odd number of hex digits in {}", token));
+    }
+
+    (0..token.len())
+        .step_by(2)
+        .map(|i| {
+            token
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("Invalid hex bytes {}", token))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::AssetDescriptor;
+
+    #[test]
+    fn script_text_roundtrip() -> Result<(), String> {
+        let mut background = b"aid_background_test".to_vec();
+        background.resize(0x80, 0);
+
+        let descriptor = ScriptDescriptor {
+            operations: vec![
+                ScriptOperation::new(ScriptOpcode::Known(KnownOpcode::SetBackground), background)
+                    .map_err(|e| format!("{:?}", e))?,
+                ScriptOperation::new(
+                    ScriptOpcode::Known(KnownOpcode::CreateTimeLimitChallenge),
+                    120f32.to_le_bytes(),
+                )
+                .map_err(|e| format!("{:?}", e))?,
+                ScriptOperation::new(ScriptOpcode::Unknown(0x11), [1u8, 2, 3, 4, 5])
+                    .map_err(|e| format!("{:?}", e))?,
+                ScriptOperation::new(ScriptOpcode::Known(KnownOpcode::EndScript), [0u8; 0])
+                    .map_err(|e| format!("{:?}", e))?,
+            ],
+            original_bytes: Default::default(),
+        };
+
+        let text = disassemble(&descriptor);
+        assert!(text.contains("SetBackground \"aid_background_test\"@0x80\n"));
+
+        let assembled = assemble(&text).map_err(|e| e.to_string())?;
+        assert_eq!(
+            assembled.to_bytes().map_err(|e| e.to_string())?,
+            descriptor.to_bytes().map_err(|e| e.to_string())?
+        );
+
+        // Comments and blank lines are ignored, but operand sizes are still checked
+        assert!(assemble("# comment\n\nWaitToMoveOn # wait\nEndScript").is_ok());
+        assert_eq!(
+            assemble("CreateTimeLimitChallenge 00\nEndScript")
+                .err()
+                .map(|e| e.line),
+            Some(1)
+        );
+        assert!(assemble("WaitToMoveOn").is_err());
+
+        Ok(())
+    }
+}
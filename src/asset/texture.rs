@@ -1,22 +1,52 @@
+pub mod atlas;
+pub mod pool;
+pub mod sheet;
+
+#[cfg(feature = "fs")]
+use std::path::Path;
 use std::{
-    fs::File,
-    io::{BufWriter, Cursor, Write},
-    path::Path,
+    borrow::Cow,
+    io::{Read, Write},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-
 use crate::{
     VirtualResource, VirtualResourceError,
-    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType, Dump},
-    d3d::{D3DFormat, LinearColour, PixelBits, StandardFormat, Swizzled},
+    asset::{
+        AssetDescriptor, AssetLike, AssetParseError, AssetRefLike, AssetType,
+        fields::{DescriptorField, DescriptorFields, FieldValue},
+    },
+    d3d::{ColourSpace, D3DFormat, LinearColour, Swizzled},
+    utils::{
+        layout::{FixedSize, ToLeBytes, fixed_layout},
+        reader::Le,
+    },
 };
 
-const TEXTURE_DESCRIPTOR_SIZE: usize = 28;
+#[cfg(feature = "fs")]
+use crate::asset::Dump;
 
-#[derive(Debug, Clone)]
+const TEXTURE_DESCRIPTOR_SIZE: usize = RawTextureDescriptor::SIZE;
+
+fixed_layout! {
+    /// A [`TextureDescriptor`] as it is stored
+    struct RawTextureDescriptor {
+        format_code: u32,
+        header_size: u32,
+        width: u16,
+        height: u16,
+        flags: u32,
+        unknown_3a: u32,
+        texture_offset: u32,
+        texture_size: u32,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TextureDescriptor {
     format: D3DFormat,
+    /// The format as it is stored, kept so that codes shared by several formats are written back
+    /// unchanged
+    format_code: u32,
     header_size: u32, // 0x1c
     width: u16,
     height: u16,
@@ -39,6 +69,7 @@ impl TextureDescriptor {
     ) -> Self {
         Self {
             format,
+            format_code: format_code(format),
             header_size,
             width,
             height,
@@ -53,8 +84,34 @@ impl TextureDescriptor {
         self.format
     }
 
+    pub fn format_code(&self) -> u32 {
+        self.format_code
+    }
+
+    /// Returns false if the format code isn't known, in which case [`TextureDescriptor::format`]
+    /// is only the first of [`TextureDescriptor::format_candidates`]
+    pub fn is_format_known(&self) -> bool {
+        format_from_code(self.format_code).is_some()
+    }
+
+    /// The formats which match the size of the image, from [`D3DFormat::guess`]
+    pub fn format_candidates(&self) -> Vec<D3DFormat> {
+        D3DFormat::guess(
+            self.width as usize,
+            self.height as usize,
+            self.texture_size as usize,
+        )
+    }
+
+    fn set_format(&mut self, format: D3DFormat) {
+        self.format = format;
+        self.format_code = format_code(format);
+    }
+
+    /// The size of the image in bytes, including the padding of any partial blocks
     pub fn required_image_size(&self) -> usize {
-        (self.width as usize * self.height as usize * self.format.bits_per_pixel()).div_ceil(8)
+        self.format
+            .image_size(self.width as usize, self.height as usize)
     }
 
     pub fn width(&self) -> u16 {
@@ -84,6 +141,11 @@ impl TextureDescriptor {
     pub fn texture_size(&self) -> u32 {
         self.texture_size
     }
+
+    /// Moves the image within the resource, for assets which store several images in one resource
+    pub(crate) fn set_texture_offset(&mut self, texture_offset: u32) {
+        self.texture_offset = texture_offset;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -93,6 +155,20 @@ pub enum TextureError {
     UnsupportedOutputType,
 }
 
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::SizeMismatch => write!(f, "The image sizes don't match"),
+            TextureError::InvalidInput => write!(f, "The image data is invalid"),
+            TextureError::UnsupportedOutputType => {
+                write!(f, "The image can't be converted to the texture's format")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
 #[derive(Clone)]
 pub struct Texture {
     descriptor: TextureDescriptor,
@@ -116,34 +192,46 @@ impl Texture {
         }
     }
 
+    /// Decodes the texture to sRGB
     pub fn to_rgba_image(&self) -> Result<RGBAImage, std::io::Error> {
-        let mut bytes: Vec<u8> = self.bytes.clone();
+        self.to_rgba_image_with(TextureExportOptions::default())
+    }
 
-        let desired_format: D3DFormat = match self.descriptor.format {
-            D3DFormat::Linear(LinearColour::R8G8B8A8)
-            | D3DFormat::Swizzled(Swizzled::A8B8G8R8)
-            | D3DFormat::Swizzled(Swizzled::A8R8G8B8) => D3DFormat::Linear(LinearColour::R8G8B8A8),
-            _ => D3DFormat::Linear(LinearColour::R8G8B8A8),
+    /// Decodes the texture, converting it to the colour space in `options`
+    pub fn to_rgba_image_with(
+        &self,
+        options: TextureExportOptions,
+    ) -> Result<RGBAImage, std::io::Error> {
+        let format = match options.format {
+            Some(format) => format,
+            None if self.descriptor.is_format_known() => self.descriptor.format,
+            None => {
+                return Err(std::io::Error::other(format!(
+                    "Unknown texture format {:#x}, which could be any of {:?}",
+                    self.descriptor.format_code,
+                    self.descriptor.format_candidates()
+                )));
+            }
         };
 
-        if desired_format != self.descriptor.format {
-            println!("Attempting transcode.");
-
-            bytes = crate::images::transcode(
-                self.descriptor.width.into(),
-                self.descriptor.height.into(),
-                self.descriptor.format,
-                desired_format,
-                bytes.as_ref(),
-            )?;
+        let source_colour_space = options.source_colour_space.unwrap_or(format.colour_space());
 
-            println!("Transcode succeeded.");
-        }
+        let bytes = crate::images::transcode_colour(
+            self.descriptor.width.into(),
+            self.descriptor.height.into(),
+            (format, source_colour_space),
+            (
+                D3DFormat::Linear(LinearColour::R8G8B8A8),
+                options.colour_space,
+            ),
+            &self.bytes,
+        )?;
 
         Ok(RGBAImage {
             width: self.descriptor.width as usize,
             height: self.descriptor.height as usize,
             bytes,
+            colour_space: options.colour_space,
         })
     }
 
@@ -151,24 +239,93 @@ impl Texture {
         &self.descriptor
     }
 
+    /**
+    Decodes the `width` by `height` rectangle with its top left corner at (`x`, `y`).
+
+    # Errors
+    - [`TextureError::InvalidInput`] if the texture can't be decoded
+    - [`TextureError::SizeMismatch`] if the rectangle doesn't fit in the texture
+    */
+    pub fn extract_region(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RGBAImage, TextureError> {
+        self.to_rgba_image()
+            .map_err(|_| TextureError::InvalidInput)?
+            .crop(x, y, width, height)
+    }
+
     pub fn bytes(&self) -> &[u8] {
         &self.bytes
     }
 }
 
-impl Dump for Texture {
-    // fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
-    fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
-        let path = dump_path.as_ref();
+/// Options for decoding a texture, so that it can be exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TextureExportOptions {
+    /// The format to decode the texture as, which is required if its format code isn't known
+    pub format: Option<D3DFormat>,
+    /// The colour space the texture is stored in, overriding the hint from
+    /// [`D3DFormat::colour_space`]
+    pub source_colour_space: Option<ColourSpace>,
+    /// The colour space of the decoded image
+    pub colour_space: ColourSpace,
+}
 
-        let file = File::create(path)?;
-        let w = &mut BufWriter::new(file);
+#[cfg(feature = "fs")]
+impl Texture {
+    /**
+    Writes the texture to a PNG at `dump_path`, decoded with `options`.
 
-        self.to_rgba_image()?
-            .dump_png_bytes(w)
+    The PNG is encoded before anything is written, then written next to `dump_path` and moved into
+    place. A texture which fails to convert never leaves a partial PNG behind, which
+    [`RawAsset::from_dir`](crate::RawAsset::from_dir) would otherwise read back.
+    */
+    pub fn dump_with<P: AsRef<Path>>(
+        &self,
+        dump_path: P,
+        options: TextureExportOptions,
+    ) -> Result<(), std::io::Error> {
+        let dump_path = dump_path.as_ref();
+
+        let mut png = vec![];
+        self.to_rgba_image_with(options)?
+            .dump_png_bytes(&mut png)
             .map_err(|e| std::io::Error::other(format!("{e:?}")))?;
 
-        Ok(())
+        let mut partial_path = dump_path.as_os_str().to_owned();
+        partial_path.push(".partial");
+
+        std::fs::write(&partial_path, &png)
+            .and_then(|_| std::fs::rename(&partial_path, dump_path))
+            .inspect_err(|_| {
+                let _ = std::fs::remove_file(&partial_path);
+            })
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Dump for Texture {
+    fn dump<P: AsRef<Path>>(&self, dump_path: P) -> Result<(), std::io::Error> {
+        self.dump_with(dump_path, TextureExportOptions::default())
+    }
+}
+
+impl DescriptorFields for TextureDescriptor {
+    fn fields(&self) -> Vec<DescriptorField> {
+        vec![
+            DescriptorField::new("format", 0x0, FieldValue::U32(self.format_code)),
+            DescriptorField::new("header_size", 0x4, FieldValue::U32(self.header_size)),
+            DescriptorField::new("width", 0x8, FieldValue::U16(self.width)),
+            DescriptorField::new("height", 0xa, FieldValue::U16(self.height)),
+            DescriptorField::new("flags", 0xc, FieldValue::U32(self.flags)),
+            DescriptorField::unknown("unknown_3a", 0x10, FieldValue::U32(self.unknown_3a)),
+            DescriptorField::new("texture_offset", 0x14, FieldValue::U32(self.texture_offset)),
+            DescriptorField::new("texture_size", 0x18, FieldValue::U32(self.texture_size)),
+        ]
     }
 }
 
@@ -178,34 +335,28 @@ impl AssetDescriptor for TextureDescriptor {
             return Err(AssetParseError::InputTooSmall);
         }
 
-        let mut cur = Cursor::new(data);
-
-        let format = match cur.read_u32::<LittleEndian>()? {
-            0x00000012 => D3DFormat::Swizzled(Swizzled::B8G8R8A8),
-            0x0000003f => D3DFormat::Swizzled(Swizzled::A8B8G8R8),
-            0x00000040 => D3DFormat::Linear(LinearColour::A8R8G8B8),
-            0x0000000c => D3DFormat::Standard(StandardFormat::DXT1),
-            0x0000000e => D3DFormat::Standard(StandardFormat::DXT2Or3),
-            0x0000000f => D3DFormat::Standard(StandardFormat::DXT4Or5),
-            unknown_format => {
-                println!(
-                    "Unimplemented format found {}. Assuming A8B8G8R8.",
-                    unknown_format
-                );
-                D3DFormat::Linear(LinearColour::A8R8G8B8)
-            }
-        };
+        let RawTextureDescriptor {
+            format_code,
+            header_size,
+            width,
+            height,
+            flags,
+            unknown_3a,
+            texture_offset,
+            texture_size,
+        } = Le::new(data).struct_of()?;
 
-        let header_size = cur.read_u32::<LittleEndian>()?;
-        let width = cur.read_u16::<LittleEndian>()?;
-        let height = cur.read_u16::<LittleEndian>()?;
-        let flags = cur.read_u32::<LittleEndian>()?;
-        let unknown_3a = cur.read_u32::<LittleEndian>()?;
-        let texture_offset = cur.read_u32::<LittleEndian>()?;
-        let texture_size = cur.read_u32::<LittleEndian>()?;
+        // Unknown formats are kept as their best guess, but can't be decoded without choosing one
+        let format = format_from_code(format_code).unwrap_or_else(|| {
+            D3DFormat::guess(width.into(), height.into(), texture_size as usize)
+                .first()
+                .copied()
+                .unwrap_or(D3DFormat::Linear(LinearColour::A8R8G8B8))
+        });
 
         Ok(TextureDescriptor {
             format,
+            format_code,
             header_size,
             width,
             height,
@@ -225,21 +376,40 @@ impl AssetDescriptor for TextureDescriptor {
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, AssetParseError> {
-        let mut bytes = vec![0x00; TEXTURE_DESCRIPTOR_SIZE];
-
-        let mut cur = Cursor::new(&mut bytes[..]);
-
-        cur.write_u32::<LittleEndian>(self.format().into())?;
+        Ok(RawTextureDescriptor {
+            format_code: self.format_code,
+            header_size: self.header_size,
+            width: self.width,
+            height: self.height,
+            flags: self.flags,
+            unknown_3a: self.unknown_3a,
+            texture_offset: self.texture_offset,
+            texture_size: self.texture_size,
+        }
+        .to_le_vec())
+    }
+}
 
-        cur.write_u32::<LittleEndian>(self.header_size)?;
-        cur.write_u16::<LittleEndian>(self.width)?;
-        cur.write_u16::<LittleEndian>(self.height)?;
-        cur.write_u32::<LittleEndian>(self.flags)?;
-        cur.write_u32::<LittleEndian>(self.unknown_3a)?;
-        cur.write_u32::<LittleEndian>(self.texture_offset)?;
-        cur.write_u32::<LittleEndian>(self.texture_size)?;
+/// The format of the code stored in a descriptor, or None if the code isn't known
+fn format_from_code(code: u32) -> Option<D3DFormat> {
+    // These formats are named by the order of their bytes in the game's textures, rather than by
+    // their format codes
+    match code {
+        0x00000012 => Some(D3DFormat::Swizzled(Swizzled::B8G8R8A8)),
+        0x0000003f => Some(D3DFormat::Swizzled(Swizzled::A8B8G8R8)),
+        0x00000040 => Some(D3DFormat::Linear(LinearColour::A8R8G8B8)),
+        code => D3DFormat::from_code(code),
+    }
+}
 
-        Ok(bytes)
+/// The value stored in a descriptor for `format`, the inverse of [`format_from_code`] for formats
+/// which aren't shared
+fn format_code(format: D3DFormat) -> u32 {
+    match format {
+        D3DFormat::Swizzled(Swizzled::B8G8R8A8) => 0x12,
+        D3DFormat::Swizzled(Swizzled::A8B8G8R8) => 0x3f,
+        D3DFormat::Linear(LinearColour::A8R8G8B8) => 0x40,
+        format => format.into(),
     }
 }
 
@@ -259,28 +429,9 @@ impl AssetLike for Texture {
         let offset = descriptor.texture_offset as usize;
         let size = descriptor.texture_size as usize;
 
-        let bytes = match virtual_res.get_bytes(offset, size) {
-            Ok(b) => b,
-            Err(e) => {
-                match e {
-                    VirtualResourceError::OffsetOutOfBounds => {
-                        return Err(AssetParseError::InvalidDataViews(format!(
-                            "Offset {} is out of bounds for virtual resource of size {}",
-                            offset,
-                            virtual_res.len()
-                        )));
-                    }
-
-                    VirtualResourceError::SizeOutOfBounds => {
-                        return Err(AssetParseError::InvalidDataViews(format!(
-                            "Size would reach offset {}, which is out of bounds for virtual resource of size {}",
-                            offset + size,
-                            virtual_res.len()
-                        )));
-                    }
-                };
-            }
-        };
+        let bytes = virtual_res
+            .get_bytes(offset, size)
+            .map_err(|e| resource_error(e, offset, size, virtual_res.len()))?;
 
         Ok(Texture {
             descriptor: descriptor.clone(),
@@ -297,14 +448,238 @@ impl AssetLike for Texture {
     }
 }
 
+fn resource_error(
+    e: VirtualResourceError,
+    offset: usize,
+    size: usize,
+    resource_size: usize,
+) -> AssetParseError {
+    match e {
+        VirtualResourceError::OffsetOutOfBounds => AssetParseError::InvalidDataViews(format!(
+            "Offset {} is out of bounds for virtual resource of size {}",
+            offset, resource_size
+        )),
+        VirtualResourceError::SizeOutOfBounds => AssetParseError::InvalidDataViews(format!(
+            "Size would reach offset {}, which is out of bounds for virtual resource of size {}",
+            offset.saturating_add(size),
+            resource_size
+        )),
+    }
+}
+
+/**
+A [`Texture`] which borrows its image bytes from a [`DecompressedBnl`](crate::DecompressedBnl)
+where possible, so that textures can be inspected without copying them.
+
+The bytes are only copied when the texture spans several resource chunks.
+*/
+#[derive(Clone)]
+pub struct TextureRef<'a> {
+    descriptor: TextureDescriptor,
+    bytes: Cow<'a, [u8]>,
+}
+
+impl std::fmt::Debug for TextureRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextureRef")
+            .field("descriptor", &self.descriptor)
+            .field("bytes", &format!("{} bytes", self.bytes.len()))
+            .field("borrowed", &matches!(self.bytes, Cow::Borrowed(_)))
+            .finish()
+    }
+}
+
+impl TextureRef<'_> {
+    pub fn descriptor(&self) -> &TextureDescriptor {
+        &self.descriptor
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Copies the texture into an owned [`Texture`]
+    pub fn to_texture(&self) -> Texture {
+        Texture::new(self.descriptor.clone(), self.bytes.to_vec())
+    }
+}
+
+impl<'a> AssetRefLike<'a> for TextureRef<'a> {
+    type Descriptor = TextureDescriptor;
+
+    fn new_ref(
+        descriptor: &Self::Descriptor,
+        virtual_res: &VirtualResource<'a>,
+    ) -> Result<Self, AssetParseError> {
+        if virtual_res.is_empty() {
+            return Err(AssetParseError::InvalidDataViews(
+                "Unable to create a Texture using 0 data views".to_string(),
+            ));
+        }
+
+        let offset = descriptor.texture_offset as usize;
+        let size = descriptor.texture_size as usize;
+
+        let bytes = virtual_res
+            .get_slice(offset, size)
+            .map_err(|e| resource_error(e, offset, size, virtual_res.len()))?;
+
+        Ok(TextureRef {
+            descriptor: descriptor.clone(),
+            bytes,
+        })
+    }
+}
+
+/// The filter used to sample an image when resizing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResizeFilter {
+    /// Copies the closest pixel, keeping hard edges. Best for pixel art and masks.
+    Nearest,
+    #[default]
+    Bilinear,
+    /// The sharpest of the filters, although it can ring around hard edges
+    Lanczos3,
+}
+
+/// How to fit an image into a texture which isn't the same size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ImageFit {
+    /// Reject images of a different size
+    #[default]
+    Exact,
+    /// Scale the image to the size of the texture
+    Resize(ResizeFilter),
+    /// Resize the texture to the size of the image, updating its descriptor
+    Adapt,
+}
+
 #[derive(Clone)]
 pub struct RGBAImage {
     width: usize,
     height: usize,
     bytes: Vec<u8>,
+    colour_space: ColourSpace,
 }
 
 impl RGBAImage {
+    /**
+    Creates an image from 8 bit sRGB pixels, stored row by row.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if `bytes` isn't `width * height * 4` bytes long
+    */
+    pub fn new(width: usize, height: usize, bytes: Vec<u8>) -> Result<Self, TextureError> {
+        if bytes.len() != width * height * 4 {
+            return Err(TextureError::SizeMismatch);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bytes,
+            colour_space: ColourSpace::Srgb,
+        })
+    }
+
+    pub fn colour_space(&self) -> ColourSpace {
+        self.colour_space
+    }
+
+    /// Marks the pixels as being in `colour_space`, without converting them
+    pub fn with_colour_space(mut self, colour_space: ColourSpace) -> Self {
+        self.colour_space = colour_space;
+        self
+    }
+
+    /// Converts the pixels to `colour_space`
+    pub fn convert_colour_space(&mut self, colour_space: ColourSpace) {
+        crate::images::convert_colour_space(&mut self.bytes, self.colour_space, colour_space);
+        self.colour_space = colour_space;
+    }
+
+    /// Returns true if a `width` by `height` rectangle at (`x`, `y`) fits inside the image
+    fn contains_rect(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        x.checked_add(width)
+            .is_some_and(|right| right <= self.width)
+            && y.checked_add(height)
+                .is_some_and(|bottom| bottom <= self.height)
+    }
+
+    /**
+    Copies the `width` by `height` rectangle at (`x`, `y`) into a new image.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if the rectangle doesn't fit in the image
+    */
+    pub fn crop(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<RGBAImage, TextureError> {
+        if !self.contains_rect(x, y, width, height) {
+            return Err(TextureError::SizeMismatch);
+        }
+
+        let bytes = (y..y + height)
+            .flat_map(|row| {
+                let start = (row * self.width + x) * 4;
+                &self.bytes[start..start + width * 4]
+            })
+            .copied()
+            .collect();
+
+        Ok(RGBAImage {
+            width,
+            height,
+            bytes,
+            colour_space: self.colour_space,
+        })
+    }
+
+    /**
+    Copies all of `image` into this image, with its top left corner at (`x`, `y`). The pixels are
+    copied as they are, even if the images are in different colour spaces.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if `image` doesn't fit inside this image at that position
+    */
+    pub fn blit(&mut self, x: usize, y: usize, image: &RGBAImage) -> Result<(), TextureError> {
+        if !self.contains_rect(x, y, image.width, image.height) {
+            return Err(TextureError::SizeMismatch);
+        }
+
+        if image.bytes.is_empty() {
+            return Ok(());
+        }
+
+        for (row, src) in image.bytes.chunks_exact(image.width * 4).enumerate() {
+            let start = ((y + row) * self.width + x) * 4;
+            self.bytes[start..start + src.len()].copy_from_slice(src);
+        }
+
+        Ok(())
+    }
+
+    /// Scales the image to `width` by `height` using `filter`
+    pub fn resize(&self, width: usize, height: usize, filter: ResizeFilter) -> RGBAImage {
+        RGBAImage {
+            width,
+            height,
+            bytes: crate::images::resize_rgba(
+                self.width,
+                self.height,
+                &self.bytes,
+                width,
+                height,
+                filter,
+            ),
+            colour_space: self.colour_space,
+        }
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -317,22 +692,62 @@ impl RGBAImage {
         &self.bytes
     }
 
+    /// Reads a PNG of any colour type, converting it to 8 bit RGBA. PNGs with a gamma of 1.0 and no
+    /// sRGB chunk are read as linear, and everything else as sRGB.
+    pub fn from_png_bytes<R: Read>(r: R) -> Result<RGBAImage, TextureError> {
+        let mut decoder = png::Decoder::new(r);
+
+        // Expand palettes and low bit depths, and reduce 16 bit channels to 8 bits
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+        let mut reader = decoder
+            .read_info()
+            .map_err(|_| TextureError::InvalidInput)?;
+
+        let colour_space = match (reader.info().srgb, reader.info().source_gamma) {
+            (None, Some(gamma)) if gamma.into_value() > 0.9 => ColourSpace::Linear,
+            _ => ColourSpace::Srgb,
+        };
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buf)
+            .map_err(|_| TextureError::InvalidInput)?;
+
+        buf.truncate(info.buffer_size());
+
+        let bytes = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xff])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|la| [la[0], la[0], la[0], la[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&l| [l, l, l, 0xff]).collect(),
+            png::ColorType::Indexed => return Err(TextureError::InvalidInput),
+        };
+
+        Ok(RGBAImage {
+            width: info.width as usize,
+            height: info.height as usize,
+            bytes,
+            colour_space,
+        })
+    }
+
     pub fn dump_png_bytes<W: Write>(&self, w: &mut W) -> Result<(), TextureError> {
         let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
 
         encoder.set_color(png::ColorType::Rgba);
         encoder.set_depth(png::BitDepth::Eight);
 
-        // encoder.set_source_gamma(png::ScaledFloat::new(1.0 / 2.2));
-        /*
-        let chroma = png::SourceChromaticities::new(
-            (0.3127, 0.3290), // red
-            (0.6400, 0.3300), // green
-            (0.3000, 0.6000), // blue
-            (0.1500, 0.0600), // white
-        );
-        encoder.set_source_chromaticities(chroma);
-        */
+        match self.colour_space {
+            ColourSpace::Srgb => encoder.set_srgb(png::SrgbRenderingIntent::Perceptual),
+            ColourSpace::Linear => encoder.set_source_gamma(png::ScaledFloat::new(1.0)),
+        }
 
         let mut writer = encoder.write_header().unwrap();
 
@@ -346,11 +761,22 @@ impl RGBAImage {
 }
 
 impl Texture {
+    /// Replaces the image with sRGB pixels of the same size as the texture
     pub fn set_from_rgba(
         &mut self,
         width: usize,
         height: usize,
         data: &[u8],
+    ) -> Result<(), TextureError> {
+        self.set_from_rgba_in(width, height, data, ColourSpace::Srgb)
+    }
+
+    fn set_from_rgba_in(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        colour_space: ColourSpace,
     ) -> Result<(), TextureError> {
         if (data.len() < width * height * 4)
             || width != self.descriptor().width as usize
@@ -359,11 +785,13 @@ impl Texture {
             return Err(TextureError::SizeMismatch);
         }
 
-        let transcoded = crate::images::transcode(
+        let format = self.descriptor().format;
+
+        let transcoded = crate::images::transcode_colour(
             self.descriptor().width as usize,
             self.descriptor().height as usize,
-            D3DFormat::Swizzled(Swizzled::R8G8B8A8),
-            self.descriptor().format,
+            (D3DFormat::Swizzled(Swizzled::R8G8B8A8), colour_space),
+            (format, format.colour_space()),
             data,
         )
         .map_err(|_| {
@@ -378,12 +806,217 @@ impl Texture {
 
         Ok(())
     }
+
+    /**
+    Replaces the image with `image`, fitting it to the texture with `fit` if the sizes differ. The
+    format of the texture is kept, and the image is converted to the colour space of the format.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if the sizes differ and `fit` is [`ImageFit::Exact`], or the
+      image is too large for a texture
+    - [`TextureError::UnsupportedOutputType`] if RGBA can't be converted to the texture's format
+    */
+    pub fn set_from_image(&mut self, image: &RGBAImage, fit: ImageFit) -> Result<(), TextureError> {
+        let (width, height) = (
+            self.descriptor.width as usize,
+            self.descriptor.height as usize,
+        );
+
+        if (image.width, image.height) == (width, height) {
+            return self.set_from_rgba_in(width, height, &image.bytes, image.colour_space);
+        }
+
+        match fit {
+            ImageFit::Exact => Err(TextureError::SizeMismatch),
+            ImageFit::Resize(filter) => {
+                let resized = image.resize(width, height, filter);
+                self.set_from_rgba_in(width, height, &resized.bytes, image.colour_space)
+            }
+            ImageFit::Adapt => self.replace_from_rgba_in(
+                image.width,
+                image.height,
+                &image.bytes,
+                self.descriptor.format,
+                image.colour_space,
+            ),
+        }
+    }
+
+    /**
+    Replaces the image with sRGB pixels in `data`, which don't need to match the current size of
+    the texture, converting them to `format`. The descriptor is updated to match the new image.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if `data` is too small, or the image is too large for a
+      texture
+    - [`TextureError::UnsupportedOutputType`] if RGBA can't be converted to `format`
+    */
+    pub fn replace_from_rgba(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        format: D3DFormat,
+    ) -> Result<(), TextureError> {
+        self.replace_from_rgba_in(width, height, data, format, ColourSpace::Srgb)
+    }
+
+    /// A texture with the pixels of `image` in the format of `descriptor`, which is resized to
+    /// match the image
+    pub fn from_image(
+        descriptor: TextureDescriptor,
+        image: &RGBAImage,
+    ) -> Result<Self, TextureError> {
+        let format = descriptor.format();
+        let mut texture = Texture::new(descriptor, vec![]);
+
+        texture.replace_from_rgba_in(
+            image.width(),
+            image.height(),
+            image.bytes(),
+            format,
+            image.colour_space(),
+        )?;
+
+        Ok(texture)
+    }
+
+    fn replace_from_rgba_in(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        format: D3DFormat,
+        colour_space: ColourSpace,
+    ) -> Result<(), TextureError> {
+        let (Ok(new_width), Ok(new_height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(TextureError::SizeMismatch);
+        };
+
+        if data.len() < width * height * 4 {
+            return Err(TextureError::SizeMismatch);
+        }
+
+        let transcoded = crate::images::transcode_colour(
+            width,
+            height,
+            (D3DFormat::Swizzled(Swizzled::R8G8B8A8), colour_space),
+            (format, format.colour_space()),
+            &data[..width * height * 4],
+        )
+        .map_err(|_| TextureError::UnsupportedOutputType)?;
+
+        self.descriptor.set_format(format);
+        self.descriptor.width = new_width;
+        self.descriptor.height = new_height;
+        // The image is always written as its own resource chunk
+        self.descriptor.texture_offset = 0;
+        self.descriptor.texture_size = transcoded.len() as u32;
+
+        self.bytes = transcoded;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::d3d::StandardFormat;
+
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn texture_descriptor_roundtrip(
+            format in prop::sample::select(vec![
+                D3DFormat::Swizzled(Swizzled::B8G8R8A8),
+                D3DFormat::Swizzled(Swizzled::A8B8G8R8),
+                D3DFormat::Linear(LinearColour::A8R8G8B8),
+                D3DFormat::Standard(StandardFormat::DXT1),
+                D3DFormat::Standard(StandardFormat::DXT2Or3),
+                D3DFormat::Standard(StandardFormat::DXT4Or5),
+            ]),
+            header_size in any::<u32>(),
+            (width, height) in (any::<u16>(), any::<u16>()),
+            (flags, unknown_3a) in (any::<u32>(), any::<u32>()),
+            (texture_offset, texture_size) in (any::<u32>(), any::<u32>()),
+        ) {
+            let descriptor = TextureDescriptor::new(
+                format,
+                header_size,
+                width,
+                height,
+                flags,
+                unknown_3a,
+                texture_offset,
+                texture_size,
+            );
+
+            let bytes = descriptor.to_bytes().expect("Descriptors can always be written");
+
+            prop_assert_eq!(bytes.len(), descriptor.size());
+            prop_assert_eq!(TextureDescriptor::from_bytes(&bytes).ok(), Some(descriptor));
+        }
+    }
+
+    #[test]
+    fn texture_format_codes() -> Result<(), String> {
+        let mut bytes = TextureDescriptor::new(
+            D3DFormat::Standard(StandardFormat::DXT1),
+            0x1c,
+            6,
+            6,
+            0x00000001,
+            0,
+            0,
+            0,
+        )
+        .to_bytes()
+        .map_err(|e| e.to_string())?;
+
+        // DXT images are padded to whole blocks
+        let descriptor = TextureDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(descriptor.required_image_size(), 4 * 8);
+
+        // Swizzled B8G8R8A8 shares its format with the game's 0x12 textures, but keeps its code
+        bytes[..4].copy_from_slice(&0x3bu32.to_le_bytes());
+        let descriptor = TextureDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        assert_eq!(descriptor.format(), D3DFormat::Swizzled(Swizzled::B8G8R8A8));
+        assert_eq!(descriptor.format_code(), 0x3b);
+        assert_eq!(descriptor.to_bytes().map_err(|e| e.to_string())?, bytes);
+
+        // Unknown formats are guessed from the size of the image, but must be chosen to decode
+        let mut bytes = include_bytes!("test_data/texture0_descriptor")[..0x1c].to_vec();
+        bytes[..4].copy_from_slice(&0x1234u32.to_le_bytes());
+
+        let descriptor = TextureDescriptor::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert!(!descriptor.is_format_known());
+        assert_eq!(
+            descriptor.format(),
+            D3DFormat::Standard(StandardFormat::DXT2Or3)
+        );
+        assert_eq!(descriptor.format_code(), 0x1234);
+
+        let texture = Texture::new(
+            descriptor,
+            include_bytes!("test_data/texture0_resource0").to_vec(),
+        );
+        assert!(texture.to_rgba_image().is_err());
+        assert!(
+            texture
+                .to_rgba_image_with(TextureExportOptions {
+                    format: texture.descriptor().format_candidates().first().copied(),
+                    ..Default::default()
+                })
+                .is_ok()
+        );
+
+        Ok(())
+    }
+
     /*
     #[test]
     fn texture_descriptor_size() {
@@ -451,4 +1084,181 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn replace_texture_from_png() -> Result<(), String> {
+        let desc = TextureDescriptor::from_bytes(include_bytes!("test_data/texture0_descriptor"))
+            .map_err(|e| e.to_string())?;
+        let mut texture = Texture::new(
+            desc,
+            include_bytes!("test_data/texture0_resource0").to_vec(),
+        );
+
+        let mut pixels = vec![];
+        (0..8 * 4).for_each(|i| pixels.extend([i as u8 * 8, 0x80, 0xff - i as u8, 0xff]));
+
+        let image = RGBAImage {
+            width: 8,
+            height: 4,
+            bytes: pixels,
+            colour_space: ColourSpace::Srgb,
+        };
+
+        let mut png_bytes = vec![];
+        image
+            .dump_png_bytes(&mut png_bytes)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let read_back =
+            RGBAImage::from_png_bytes(png_bytes.as_slice()).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(read_back.bytes(), image.bytes());
+
+        for format in [
+            D3DFormat::Standard(StandardFormat::DXT1),
+            D3DFormat::Swizzled(Swizzled::B8G8R8A8),
+        ] {
+            texture
+                .replace_from_rgba(8, 4, read_back.bytes(), format)
+                .map_err(|e| format!("{:?}", e))?;
+
+            // The descriptor must describe the new image once written and read back
+            let written = TextureDescriptor::from_bytes(
+                &texture.descriptor().to_bytes().map_err(|e| e.to_string())?,
+            )
+            .map_err(|e| e.to_string())?;
+
+            assert_eq!(written.format(), format);
+            assert_eq!((written.width(), written.height()), (8, 4));
+            assert_eq!(written.texture_offset(), 0);
+            assert_eq!(written.texture_size() as usize, texture.bytes().len());
+            assert_eq!(written.required_image_size(), texture.bytes().len());
+        }
+
+        assert_eq!(
+            texture.to_rgba_image().map_err(|e| e.to_string())?.bytes(),
+            image.bytes()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fit_image_to_texture() -> Result<(), String> {
+        let format = D3DFormat::Swizzled(Swizzled::B8G8R8A8);
+        let mut texture = Texture::new(
+            TextureDescriptor::new(format, 0x1c, 0, 0, 0x00000001, 0, 0, 0),
+            vec![],
+        );
+        texture
+            .replace_from_rgba(8, 4, &[0u8; 8 * 4 * 4], format)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let solid = RGBAImage::new(16, 8, [0x20, 0x80, 0xc0, 0xff].repeat(16 * 8))
+            .map_err(|e| format!("{:?}", e))?;
+
+        assert!(texture.set_from_image(&solid, ImageFit::Exact).is_err());
+
+        // A solid colour stays the same with every filter
+        for filter in [
+            ResizeFilter::Nearest,
+            ResizeFilter::Bilinear,
+            ResizeFilter::Lanczos3,
+        ] {
+            texture
+                .set_from_image(&solid, ImageFit::Resize(filter))
+                .map_err(|e| format!("{:?}", e))?;
+
+            assert_eq!(
+                (texture.descriptor().width(), texture.descriptor().height()),
+                (8, 4)
+            );
+            assert_eq!(
+                texture.to_rgba_image().map_err(|e| e.to_string())?.bytes(),
+                [0x20, 0x80, 0xc0, 0xff].repeat(8 * 4),
+                "{:?}",
+                filter
+            );
+        }
+
+        texture
+            .set_from_image(&solid, ImageFit::Adapt)
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(
+            (texture.descriptor().width(), texture.descriptor().height()),
+            (16, 8)
+        );
+        assert_eq!(texture.descriptor().texture_size() as usize, 16 * 8 * 4);
+
+        // Nearest neighbour copies pixels without blending them
+        let pixels = RGBAImage::new(2, 1, vec![0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff])
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(
+            pixels.resize(4, 1, ResizeFilter::Nearest).bytes(),
+            [[0, 0, 0, 0xff], [0, 0, 0, 0xff], [0xff; 4], [0xff; 4]].concat()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn texture_colour_spaces() -> Result<(), String> {
+        let format = D3DFormat::Swizzled(Swizzled::B8G8R8A8);
+        let mut texture = Texture::new(
+            TextureDescriptor::new(format, 0x1c, 0, 0, 0x00000001, 0, 0, 0),
+            vec![],
+        );
+        texture
+            .replace_from_rgba(
+                2,
+                1,
+                &[0x80, 0x80, 0x80, 0x80, 0xff, 0x00, 0x00, 0xff],
+                format,
+            )
+            .map_err(|e| format!("{:?}", e))?;
+
+        // Colour formats are stored in sRGB, so they are decoded unchanged by default
+        let srgb = texture.to_rgba_image().map_err(|e| e.to_string())?;
+        assert_eq!(srgb.colour_space(), ColourSpace::Srgb);
+        assert_eq!(
+            srgb.bytes(),
+            [0x80, 0x80, 0x80, 0x80, 0xff, 0x00, 0x00, 0xff]
+        );
+
+        // Treating the texture as linear brightens its midtones, but not its alpha or extremes
+        let brightened = texture
+            .to_rgba_image_with(TextureExportOptions {
+                source_colour_space: Some(ColourSpace::Linear),
+                ..Default::default()
+            })
+            .map_err(|e| e.to_string())?;
+        assert_eq!(brightened.bytes()[..4], [0xbc, 0xbc, 0xbc, 0x80]);
+        assert_eq!(brightened.bytes()[4..], [0xff, 0x00, 0x00, 0xff]);
+
+        // Linear images keep their colour space through PNGs, and are converted back on import
+        let linear = texture
+            .to_rgba_image_with(TextureExportOptions {
+                colour_space: ColourSpace::Linear,
+                ..Default::default()
+            })
+            .map_err(|e| e.to_string())?;
+        assert_eq!(linear.bytes()[..4], [0x37, 0x37, 0x37, 0x80]);
+
+        let mut png_bytes = vec![];
+        linear
+            .dump_png_bytes(&mut png_bytes)
+            .map_err(|e| format!("{:?}", e))?;
+        let read_back =
+            RGBAImage::from_png_bytes(png_bytes.as_slice()).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(read_back.colour_space(), ColourSpace::Linear);
+
+        texture
+            .set_from_image(&read_back, ImageFit::Exact)
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(
+            texture.to_rgba_image().map_err(|e| e.to_string())?.bytes(),
+            srgb.bytes()
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,219 @@
+/*!
+Splitting atlas textures into their regions, and putting edited regions back.
+
+The region tables of atlases are stored in other assets, so the regions are supplied by the caller,
+either directly or deserialised from JSON like:
+
+```json
+{ "regions": [{ "name": "button_a", "x": 0, "y": 0, "width": 32, "height": 16 }] }
+```
+*/
+
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{RGBAImage, Texture, TextureError};
+
+/// A named rectangle of an atlas, in pixels from the top left corner
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub name: String,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureAtlas {
+    pub regions: Vec<AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn new(regions: Vec<AtlasRegion>) -> Self {
+        Self { regions }
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /**
+    Copies each region of `atlas` into its own image.
+
+    # Errors
+    - [`TextureError::SizeMismatch`] if a region doesn't fit inside `atlas`
+    */
+    pub fn split<'a>(
+        &'a self,
+        atlas: &RGBAImage,
+    ) -> Result<Vec<(&'a AtlasRegion, RGBAImage)>, TextureError> {
+        self.regions
+            .iter()
+            .map(|region| {
+                atlas
+                    .crop(region.x, region.y, region.width, region.height)
+                    .map(|image| (region, image))
+            })
+            .collect()
+    }
+
+    /**
+    Writes each image of `edited` over the region with the same name. Regions without an edited
+    image are left as they are.
+
+    # Errors
+    - [`TextureError::InvalidInput`] if an image has no matching region
+    - [`TextureError::SizeMismatch`] if an image isn't the same size as its region, or the region
+      doesn't fit inside `atlas`
+    */
+    pub fn compose(
+        &self,
+        atlas: &mut RGBAImage,
+        edited: &HashMap<String, RGBAImage>,
+    ) -> Result<(), TextureError> {
+        for (name, image) in edited {
+            let region = self.region(name).ok_or(TextureError::InvalidInput)?;
+
+            if (image.width(), image.height()) != (region.width, region.height) {
+                return Err(TextureError::SizeMismatch);
+            }
+
+            atlas.blit(region.x, region.y, image)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`TextureAtlas::compose`], but writes the result back into `texture` in its current
+    /// format
+    pub fn compose_texture(
+        &self,
+        texture: &mut Texture,
+        edited: &HashMap<String, RGBAImage>,
+    ) -> Result<(), TextureError> {
+        let mut atlas = texture
+            .to_rgba_image()
+            .map_err(|_| TextureError::InvalidInput)?;
+
+        self.compose(&mut atlas, edited)?;
+
+        texture.set_from_rgba(atlas.width(), atlas.height(), atlas.bytes())
+    }
+
+    /// Writes each region of `texture` to `<name>.png` in `dir`
+    #[cfg(feature = "fs")]
+    pub fn export_pngs<P: AsRef<Path>>(
+        &self,
+        texture: &Texture,
+        dir: P,
+    ) -> Result<(), std::io::Error> {
+        let to_io = |e: TextureError| std::io::Error::other(format!("{e:?}"));
+
+        let atlas = texture.to_rgba_image()?;
+
+        fs::create_dir_all(dir.as_ref())?;
+
+        for (region, image) in self.split(&atlas).map_err(to_io)? {
+            let file = File::create(dir.as_ref().join(format!("{}.png", region.name)))?;
+
+            image
+                .dump_png_bytes(&mut BufWriter::new(file))
+                .map_err(to_io)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads back the PNGs written by [`TextureAtlas::export_pngs`] and composes them into
+    /// `texture`. Regions without a PNG in `dir` are left as they are. Returns the number of
+    /// regions replaced.
+    #[cfg(feature = "fs")]
+    pub fn import_pngs<P: AsRef<Path>>(
+        &self,
+        texture: &mut Texture,
+        dir: P,
+    ) -> Result<usize, std::io::Error> {
+        let to_io = |e: TextureError| std::io::Error::other(format!("{e:?}"));
+
+        let mut edited = HashMap::new();
+
+        for region in &self.regions {
+            let path = dir.as_ref().join(format!("{}.png", region.name));
+
+            if !path.exists() {
+                continue;
+            }
+
+            let image = RGBAImage::from_png_bytes(File::open(&path)?).map_err(to_io)?;
+            edited.insert(region.name.clone(), image);
+        }
+
+        self.compose_texture(texture, &edited).map_err(to_io)?;
+
+        Ok(edited.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlas_split_and_compose() -> Result<(), String> {
+        // A 4x2 image where each pixel's red channel is its index
+        let pixels = (0..8u8).flat_map(|i| [i, 0, 0, 0xff]).collect();
+        let mut image = RGBAImage::new(4, 2, pixels).map_err(|e| format!("{:?}", e))?;
+
+        let atlas: TextureAtlas = serde_json::from_str(
+            r#"{ "regions": [
+                { "name": "left", "x": 0, "y": 0, "width": 2, "height": 2 },
+                { "name": "right", "x": 2, "y": 0, "width": 2, "height": 2 }
+            ] }"#,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let split = atlas.split(&image).map_err(|e| format!("{:?}", e))?;
+        let red = |image: &RGBAImage| image.bytes().chunks(4).map(|p| p[0]).collect::<Vec<_>>();
+
+        assert_eq!(red(&split[0].1), vec![0, 1, 4, 5]);
+        assert_eq!(red(&split[1].1), vec![2, 3, 6, 7]);
+
+        // Swap the halves over
+        let edited = HashMap::from([
+            ("left".to_string(), split[1].1.clone()),
+            ("right".to_string(), split[0].1.clone()),
+        ]);
+        atlas
+            .compose(&mut image, &edited)
+            .map_err(|e| format!("{:?}", e))?;
+        assert_eq!(red(&image), vec![2, 3, 0, 1, 6, 7, 4, 5]);
+
+        // Regions outside of the image and images of the wrong size are rejected
+        let outside = TextureAtlas::new(vec![AtlasRegion {
+            name: "outside".to_string(),
+            x: 3,
+            y: 0,
+            width: 2,
+            height: 1,
+        }]);
+        assert!(outside.split(&image).is_err());
+        assert!(
+            atlas
+                .compose(
+                    &mut image,
+                    &HashMap::from([("left".to_string(), split[0].1.crop(0, 0, 1, 1).unwrap())])
+                )
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+/*!
+Decoding many textures at once across threads.
+
+Unswizzling, DXT decoding and PNG encoding are all CPU bound, so a [`TranscodePool`] splits a batch
+of textures between scoped threads. Results always come back in the order of the input, however
+the work was split.
+*/
+
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+use std::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use super::{RGBAImage, Texture, TextureExportOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodePool {
+    threads: usize,
+}
+
+impl Default for TranscodePool {
+    /// A pool with a thread for each CPU
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl TranscodePool {
+    /// Creates a pool which uses up to `threads` threads, or one for each CPU if `threads` is 0
+    pub fn new(threads: usize) -> Self {
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            threads => threads,
+        };
+
+        Self { threads }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Calls `f` on every item of `items`, returning the results in the same order. Items are
+    /// handed out one at a time, so a few large textures don't hold up the rest of the batch.
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        let threads = self.threads.min(items.len());
+
+        if threads <= 1 {
+            return items.iter().map(f).collect();
+        }
+
+        let next = AtomicUsize::new(0);
+
+        let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = vec![];
+
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+
+                            let Some(item) = items.get(i) else {
+                                break done;
+                            };
+
+                            done.push((i, f(item)));
+                        }
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| match worker.join() {
+                    Ok(done) => done,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect()
+        });
+
+        results.sort_unstable_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Decodes every texture with `options`, see [`Texture::to_rgba_image_with`]
+    pub fn decode_all(
+        &self,
+        textures: &[Texture],
+        options: TextureExportOptions,
+    ) -> Vec<Result<RGBAImage, std::io::Error>> {
+        self.map(textures, |texture| texture.to_rgba_image_with(options))
+    }
+
+    /// Writes each texture to the PNG paired with it, see [`Texture::dump_with`]
+    #[cfg(feature = "fs")]
+    pub fn export_pngs(
+        &self,
+        jobs: &[(&Texture, PathBuf)],
+        options: TextureExportOptions,
+    ) -> Vec<Result<(), std::io::Error>> {
+        self.map(jobs, |(texture, path)| texture.dump_with(path, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcode_pool_keeps_order() -> Result<(), String> {
+        let items: Vec<u64> = (0..1000).collect();
+
+        for threads in [1, 3, 16] {
+            let pool = TranscodePool::new(threads);
+
+            assert_eq!(
+                pool.map(&items, |i| i * i),
+                items.iter().map(|i| i * i).collect::<Vec<_>>()
+            );
+        }
+
+        assert!(TranscodePool::default().threads() >= 1);
+        assert!(TranscodePool::new(4).map(&[] as &[u8], |b| *b).is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+/*!
+Contact sheets, which show many textures at once as a grid of labelled thumbnails.
+
+Each image is scaled down as soon as it is added, so that a sheet of hundreds of textures only
+needs to hold their thumbnails in memory.
+*/
+
+use crate::{
+    d3d::ColourSpace,
+    images::{GLYPH_SIZE, draw_text},
+};
+
+use super::{RGBAImage, ResizeFilter};
+
+/// The space around each thumbnail, in pixels
+const PADDING: usize = 4;
+
+const BACKGROUND: [u8; 3] = [0x30, 0x30, 0x30];
+const LABEL_COLOUR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContactSheetOptions {
+    /// The size of the longest side of each thumbnail
+    pub thumbnail_size: usize,
+    /// The number of thumbnails in each row
+    pub columns: usize,
+    pub filter: ResizeFilter,
+}
+
+impl Default for ContactSheetOptions {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: 128,
+            columns: 8,
+            filter: ResizeFilter::Bilinear,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ContactSheet {
+    options: ContactSheetOptions,
+    thumbnails: Vec<(String, RGBAImage)>,
+}
+
+impl ContactSheet {
+    pub fn new(options: ContactSheetOptions) -> Self {
+        Self {
+            options: ContactSheetOptions {
+                thumbnail_size: options.thumbnail_size.max(1),
+                columns: options.columns.max(1),
+                ..options
+            },
+            thumbnails: vec![],
+        }
+    }
+
+    /// Adds a thumbnail of `image`, keeping its aspect ratio, which is labelled with `label`
+    pub fn add(&mut self, label: &str, image: &RGBAImage) {
+        let thumbnail = self.thumbnail(image);
+        self.add_thumbnail(label, thumbnail);
+    }
+
+    /// Scales `image` down to the thumbnail size of the sheet, keeping its aspect ratio. This only
+    /// needs `&self`, so thumbnails can be made on several threads and added afterwards.
+    pub fn thumbnail(&self, image: &RGBAImage) -> RGBAImage {
+        let size = self.options.thumbnail_size;
+        let longest = image.width().max(image.height()).max(1);
+
+        let (width, height) = match longest > size {
+            true => (
+                (image.width() * size / longest).max(1),
+                (image.height() * size / longest).max(1),
+            ),
+            false => (image.width(), image.height()),
+        };
+
+        image.resize(width, height, self.options.filter)
+    }
+
+    /// Adds a thumbnail made by [`ContactSheet::thumbnail`], which is labelled with `label`
+    pub fn add_thumbnail(&mut self, label: &str, thumbnail: RGBAImage) {
+        self.thumbnails.push((label.to_string(), thumbnail));
+    }
+
+    pub fn len(&self) -> usize {
+        self.thumbnails.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thumbnails.is_empty()
+    }
+
+    /// Lays out every thumbnail in the order they were added, centred in its cell above its label.
+    /// Labels which are too wide for their cell are cut short.
+    pub fn to_image(&self) -> RGBAImage {
+        let cell_width = self.options.thumbnail_size + PADDING * 2;
+        let cell_height = self.options.thumbnail_size + GLYPH_SIZE.1 + PADDING * 3;
+
+        let columns = self.options.columns.min(self.thumbnails.len()).max(1);
+        let rows = self.thumbnails.len().div_ceil(columns);
+
+        let width = columns * cell_width;
+        let height = rows * cell_height;
+
+        let mut bytes: Vec<u8> = BACKGROUND
+            .iter()
+            .copied()
+            .chain([0xff])
+            .cycle()
+            .take(width * height * 4)
+            .collect();
+
+        let max_label_len = (self.options.thumbnail_size + 1) / (GLYPH_SIZE.0 + 1);
+
+        for (i, (label, thumbnail)) in self.thumbnails.iter().enumerate() {
+            let cell_x = (i % columns) * cell_width;
+            let cell_y = (i / columns) * cell_height;
+
+            let x = cell_x + PADDING + (self.options.thumbnail_size - thumbnail.width()) / 2;
+            let y = cell_y + PADDING + (self.options.thumbnail_size - thumbnail.height()) / 2;
+
+            // Blend over the background, so that transparent textures are still visible
+            for (row, src_row) in thumbnail
+                .bytes()
+                .chunks_exact((thumbnail.width() * 4).max(1))
+                .enumerate()
+            {
+                for (column, src) in src_row.chunks_exact(4).enumerate() {
+                    let start = ((y + row) * width + x + column) * 4;
+                    let alpha = src[3] as u32;
+
+                    for c in 0..3 {
+                        bytes[start + c] = ((src[c] as u32 * alpha
+                            + BACKGROUND[c] as u32 * (255 - alpha))
+                            / 255) as u8;
+                    }
+                }
+            }
+
+            let label: String = label.chars().take(max_label_len).collect();
+
+            draw_text(
+                &mut bytes,
+                width,
+                cell_x + PADDING,
+                cell_y + self.options.thumbnail_size + PADDING * 2,
+                &label,
+                LABEL_COLOUR,
+            );
+        }
+
+        RGBAImage {
+            width,
+            height,
+            bytes,
+            colour_space: ColourSpace::Srgb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contact_sheet_layout() -> Result<(), String> {
+        let red =
+            RGBAImage::new(8, 4, [0xff, 0, 0, 0xff].repeat(32)).map_err(|e| format!("{e:?}"))?;
+        let clear = RGBAImage::new(2, 2, vec![0; 16]).map_err(|e| format!("{e:?}"))?;
+
+        let mut sheet = ContactSheet::new(ContactSheetOptions {
+            thumbnail_size: 4,
+            columns: 2,
+            filter: ResizeFilter::Nearest,
+        });
+
+        sheet.add("red", &red);
+        sheet.add("clear", &clear);
+        sheet.add("third", &clear);
+
+        let image = sheet.to_image();
+        let cell = (4 + PADDING * 2, 4 + GLYPH_SIZE.1 + PADDING * 3);
+
+        assert_eq!((image.width(), image.height()), (cell.0 * 2, cell.1 * 2));
+
+        let pixel = |x: usize, y: usize| {
+            let start = (y * image.width() + x) * 4;
+            image.bytes()[start..start + 4].to_vec()
+        };
+
+        // The red image is scaled to 4x2 and centred vertically in its cell
+        assert_eq!(pixel(PADDING, PADDING + 1), vec![0xff, 0, 0, 0xff]);
+        assert_eq!(
+            pixel(PADDING, PADDING),
+            [BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 0xff]
+        );
+
+        // Transparent pixels show the background
+        assert_eq!(
+            pixel(cell.0 + PADDING + 1, PADDING + 1),
+            [BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 0xff]
+        );
+
+        // The label starts below the thumbnail, and the R is a full column on its left
+        let label_y = 4 + PADDING * 2;
+        assert!((0..GLYPH_SIZE.1).all(|row| pixel(PADDING, label_y + row) == LABEL_COLOUR));
+
+        Ok(())
+    }
+}
@@ -1,12 +1,47 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     ffi::OsStr,
-    fs,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use bnl::{BNLFile, RawAsset, asset::AssetType};
-use clap::{Parser, Subcommand};
+use bnl::{
+    AssetOrder, AssetPatch, BNLFile, BNLWriteOptions, BnlPatch, DecompressedBnl, PatchError,
+    RawAsset, TEXTURE_PNG_FILE,
+    asset::{
+        AnyAsset, AssetDescriptor, AssetError, AssetType, Dump,
+        cutscene::Cutscene,
+        font::{
+            Font,
+            bmfont::{BmFont, FontError},
+        },
+        loctext::{
+            LoctextCollision, LoctextResource,
+            codes::ControlCodes,
+            text::{self as loctext_text, LoctextFormat},
+        },
+        model::{Model, ModelStats},
+        script::{
+            Script, ScriptDescriptor,
+            text::{assemble, disassemble},
+        },
+        texture::{
+            RGBAImage, ResizeFilter, Texture, TextureExportOptions,
+            pool::TranscodePool,
+            sheet::{ContactSheet, ContactSheetOptions},
+        },
+    },
+    d3d::{D3DFormat, StandardFormat, Swizzled},
+    debug::{annotate, coverage_map, imhex_pattern, kaitai_struct},
+    game::Aid,
+    json::{AssetJson, BnlJson},
+    manifest::{Manifest, ManifestMismatch},
+    utils::span::{RECORDED_ASSET_TYPES, record_spans},
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
 use walkdir::WalkDir;
 
 #[derive(Parser, Debug)]
@@ -14,11 +49,54 @@ use walkdir::WalkDir;
     version,
     propagate_version = true,
     subcommand_required = true,
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    after_help = "Exit codes: 1 other failure, 2 invalid arguments, 3 IO error, 4 parse error, 5 asset not found, 6 validation failure"
 )]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// How errors are written to stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+    Text,
+    /// A single JSON object per error, with the kind of failure, its exit code and the message,
+    /// and per warning, with the message
+    Json,
+}
+
+static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// The kinds of failure bnltool reports, with the exit code of each. Code 2 is left for clap,
+/// which uses it for invalid arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    /// Anything not covered by the other kinds
+    Other = 1,
+    /// A file couldn't be read or written
+    Io = 3,
+    /// A file or asset couldn't be parsed
+    Parse = 4,
+    /// A named asset doesn't exist, or isn't of the expected type
+    NotFound = 5,
+    /// The input was read, but failed a check
+    Validation = 6,
+}
+
+impl Failure {
+    fn name(self) -> &'static str {
+        match self {
+            Failure::Other => "other",
+            Failure::Io => "io",
+            Failure::Parse => "parse",
+            Failure::NotFound => "not_found",
+            Failure::Validation => "validation",
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -33,10 +111,35 @@ enum Commands {
         /// The output directory for the extracted files
         #[arg(short = 'd', default_value = "./out")]
         output_dir: PathBuf,
+
+        /// Extract every .bnl file under any directories given, mirroring their layout in the
+        /// output directory
+        #[arg(short = 'r', long)]
+        recursive: bool,
+
+        /// Which files to write for each asset. Only textures have a converted format so far, so
+        /// other assets always get their raw files.
+        #[arg(long, value_enum, default_value_t = ExtractLayout::Raw)]
+        layout: ExtractLayout,
+
+        /// Write each asset as a single file named after it instead of a directory, as a PNG for
+        /// textures and JSON for everything else. Textures also get a JSON file with their
+        /// metadata and descriptor, so that create can read them back.
+        #[arg(long, conflicts_with_all = ["layout", "png"])]
+        flat: bool,
+
+        /// Also write each texture as texture.png next to its raw files, the same as --layout both
+        #[arg(long, conflicts_with = "layout")]
+        png: bool,
+
+        /// The number of threads used to convert textures, or 0 for one per CPU
+        #[arg(short = 'j', long, default_value_t = 0)]
+        jobs: usize,
     },
 
     #[command(short_flag = 'c')]
-    /// Create a new BNL file from one or more directories which contain loose assets.
+    /// Create a new BNL file from one or more directories which contain loose assets, in any
+    /// layout written by extract.
     Create {
         /// The directories containing the assets
         #[arg(required = true)]
@@ -45,6 +148,30 @@ enum Commands {
         #[arg(short = 'o', value_name = "FILE")]
         /// The path which the new .bnl file will be written to
         output_file: PathBuf,
+
+        /// Produce byte-identical output for identical inputs, regardless of filesystem order
+        #[arg(long)]
+        reproducible: bool,
+
+        /// Store identical resource chunks only once
+        #[arg(long)]
+        dedup: bool,
+
+        /// Store identical descriptors only once
+        #[arg(long)]
+        dedup_descriptors: bool,
+
+        /// A manifest written by the manifest command, giving the order of the assets in the
+        /// output. Assets which aren't listed are placed after the listed ones.
+        #[arg(long, value_name = "MANIFEST")]
+        order: Option<PathBuf>,
+
+        /// Abort without writing anything if any asset directory can't be read or added
+        #[arg(long)]
+        strict: bool,
+
+        #[command(flatten)]
+        alignment: AlignmentArgs,
     },
 
     #[command(short_flag = 'l')]
@@ -65,8 +192,189 @@ enum Commands {
         /// Print a summary of the contents
         #[arg(short = 's')]
         print_summary: bool,
+
+        /// List every .bnl file under BNL_FILE, which is a directory
+        #[arg(short = 'r', long)]
+        recursive: bool,
+
+        /// Group assets which are variants of the same base asset, like aid_texture_grass_a and
+        /// aid_texture_grass_b, under their shared base id
+        #[arg(short = 'g', long)]
+        group: bool,
+    },
+
+    /// Print the metadata and descriptor layout of a single asset
+    Info {
+        /// The BNL file containing the asset
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The name of the asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
+
+        /// Print a hexdump of the descriptor, annotated with its fields
+        #[arg(long)]
+        hex: bool,
+
+        /// Parse the asset and print which bytes of its descriptor and resource chunks were read,
+        /// to find data that isn't parsed yet. Only the maps of textures and fonts are complete.
+        #[arg(long)]
+        map: bool,
+    },
+
+    /// Write an ImHex pattern or Kaitai Struct type for the descriptor layout of an asset
+    Pattern {
+        /// The BNL file containing the asset
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The name of an asset with the layout to describe
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
+
+        #[arg(short = 'f', long, value_enum, default_value_t = PatternFormat::Imhex)]
+        format: PatternFormat,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the pattern will be written to, instead of stdout
+        output_file: Option<PathBuf>,
+    },
+
+    /// Write the contents of a BNL file to a single JSON document
+    DumpJson {
+        /// The BNL file to dump
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the JSON will be written to
+        output_file: PathBuf,
+
+        /// Include resource data as base64, instead of only its hashes. Required for from-json.
+        #[arg(long)]
+        include_data: bool,
+    },
+
+    /// Create a BNL file from a JSON document written by dump-json --include-data
+    FromJson {
+        /// The JSON document to read
+        #[arg(value_name = "JSON_FILE", required = true)]
+        json_path: PathBuf,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the new .bnl file will be written to
+        output_file: PathBuf,
+
+        #[command(flatten)]
+        alignment: AlignmentArgs,
+    },
+
+    /// Write a manifest of SHA-256 hashes for every asset in an extracted asset tree or BNL file
+    Manifest {
+        /// The directory containing the extracted assets, or a BNL file. Manifests of BNL files
+        /// keep the order of its assets, for create --order.
+        #[arg(required = true)]
+        asset_dir: PathBuf,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the manifest will be written to
+        output_file: PathBuf,
+    },
+
+    /// Check a BNL file against a manifest written by the manifest command
+    Verify {
+        /// The BNL file to check
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The manifest to check against
+        #[arg(value_name = "MANIFEST", required = true)]
+        manifest_path: PathBuf,
+    },
+
+    /// Find which BNL files contain assets with matching names
+    Find {
+        /// A glob of asset names, where * matches any run of characters and ? matches one
+        #[arg(value_name = "PATTERN", required = true)]
+        pattern: String,
+
+        /// A BNL file, or a directory to search for BNL files in
+        #[arg(value_name = "PATH", required = true)]
+        path: PathBuf,
+
+        /// Treat PATTERN as a regular expression instead of a glob
+        #[arg(short = 'e', long)]
+        regex: bool,
+
+        /// Print the type and size of each matching asset
+        #[arg(short = 'l', long)]
+        long: bool,
+    },
+
+    /// Report shared descriptors, duplicated resource chunks and overlapping assets in a BNL file
+    Validate {
+        /// The BNL file to check
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+    },
+
+    /// Print the ranges of each section of a BNL file which no asset refers to, and the ranges
+    /// which more than one asset refers to
+    Coverage {
+        /// The BNL file to check
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+    },
+
+    /// Print the offset and size of each section of a BNL file once decompressed, as read and as
+    /// it would be written back
+    Layout {
+        /// The BNL file to read
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+    },
+
+    /// Print the sizes of the assets in one or more BNL files, by type and overall
+    Stats {
+        /// The .bnl files to measure
+        #[arg(value_name = "BNL FILES", required = true)]
+        bnl_files: Vec<PathBuf>,
+
+        /// Include every .bnl file under any directories given
+        #[arg(short = 'r', long)]
+        recursive: bool,
+
+        /// The number of biggest assets to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
     },
 
+    /// Disassemble or modify script assets
+    #[command(subcommand)]
+    Script(ScriptCommands),
+
+    /// Export or import the text of loctext assets, for translation
+    #[command(subcommand)]
+    Loctext(LoctextCommands),
+
+    /// Export or replace the images of texture assets
+    #[command(subcommand)]
+    Texture(TextureCommands),
+
+    /// Convert font assets to and from BMFont .fnt files, for editing glyphs or adding characters
+    #[command(subcommand)]
+    Font(FontCommands),
+
+    /// Export the geometry of model assets
+    #[command(subcommand)]
+    Model(ModelCommands),
+
+    /// Write the changes between two BNL files to a patch, or apply one
+    #[command(subcommand)]
+    Patch(PatchCommands),
+
+    /// Compare the assets of two BNL files
     Diff {
         /// The first bnl file to compare
         file_1: PathBuf,
@@ -80,273 +388,2921 @@ enum Commands {
         /// Do not verify that the assets are in the same order in the files
         #[arg(short = 'a')]
         ignore_order: bool,
+
+        /// Compare every .bnl file under two directories, pairing them by relative path
+        #[arg(short = 'r', long)]
+        recursive: bool,
     },
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Subcommand, Debug)]
+enum ScriptCommands {
+    /// Write the text disassembly of a script
+    Dump {
+        /// The BNL file containing the script
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-    match cli.command {
-        Commands::Extract {
-            bnl_files,
-            output_dir,
-        } => {
-            if bnl_files.is_empty() {
-                eprintln!("Unable to extract: no bnl files provided.");
-                error_exit();
-            }
+        /// The name of the script asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
 
-            for bnl_file in bnl_files {
-                println!("Extracting BNL file {}", bnl_file.display());
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the disassembly will be written to, instead of stdout
+        output_file: Option<PathBuf>,
+    },
 
-                let bytes: Vec<u8> = match std::fs::read(&bnl_file) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        eprintln!("Unable to open file {}. Error: {}", bnl_file.display(), e);
-                        error_exit();
-                    }
-                };
+    /// Assemble a script written by script dump, and write it back into the BNL file in place
+    Apply {
+        /// The BNL file containing the script
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                let bnl = match BNLFile::from_bytes(&bytes) {
-                    Ok(b) => b,
-                    Err(e) => {
-                        eprintln!("Unable to process BNL file: {:?}", e);
+        /// The name of the script asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
 
-                        error_exit();
-                    }
-                };
+        /// The script text to assemble
+        #[arg(value_name = "SCRIPT_FILE", required = true)]
+        script_path: PathBuf,
+    },
 
-                let raw_assets = bnl.get_raw_assets();
+    /// List the assets a script refers to and the challenges it creates
+    Refs {
+        /// The BNL file containing the script
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                let out_filename = format!(
-                    "{}_bnl",
-                    bnl_file
-                        .file_stem()
-                        .unwrap_or(OsStr::new("unknown"))
-                        .display()
-                );
+        /// The name of the script asset, or every script in the file if not given
+        #[arg(value_name = "ASSET_NAME")]
+        asset_name: Option<String>,
+    },
+}
 
-                // ./out/common_bnl
-                let bnl_out_path = Path::new(&output_dir).join(out_filename);
+#[derive(Subcommand, Debug)]
+enum LoctextCommands {
+    /// Write every loctext asset of a BNL file to its own text file
+    Export {
+        /// The BNL file containing the loctext assets
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                raw_assets.iter().for_each(|raw_asset| {
-                    // ./out/common_bnl/aid_texture_xyz
-                    let asset_path: PathBuf = bnl_out_path.join(raw_asset.name());
+        /// The format of the text files (json, csv or po)
+        #[arg(short = 'f', long, default_value = "json")]
+        format: LoctextFormat,
 
-                    if asset_path.is_file() {
-                        eprintln!(
-                            "Unable to write to {} (A file already exists by that name)",
-                            asset_path.display()
-                        );
+        /// The output directory for the text files
+        #[arg(short = 'd', default_value = "./out")]
+        output_dir: PathBuf,
 
-                        error_exit();
-                    } else if !asset_path.exists() {
-                        match fs::create_dir_all(&asset_path) {
-                            Ok(_) => (),
-                            Err(e) => {
-                                eprintln!(
-                                    "Unable to create directory {}.\nError: {}",
-                                    asset_path.display(),
-                                    e
-                                );
+        /// A JSON file naming control codes, which are written as {name} placeholders
+        #[arg(long, value_name = "FILE")]
+        codes: Option<PathBuf>,
+    },
 
-                                error_exit();
-                            }
-                        }
-                    }
+    /// Read text files written by loctext export back into a copy of a BNL file. Files are matched
+    /// to assets by name, and their format is taken from their extension.
+    Import {
+        /// The BNL file containing the loctext assets
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                    std::fs::write(asset_path.join("metadata"), raw_asset.metadata().to_bytes())
-                        .unwrap_or_else(|e| {
-                            eprintln!(
-                                "Unable to write metadata for {}\nError: {}",
-                                &raw_asset.name(),
-                                e
-                            );
-                        });
-
-                    std::fs::write(asset_path.join("descriptor"), raw_asset.descriptor_bytes())
-                        .unwrap_or_else(|e| {
-                            eprintln!(
-                                "Unable to write descriptor for {}\nError: {}",
-                                &raw_asset.name(),
-                                e
-                            );
-                        });
-
-                    if let Some(data_slices) = raw_asset.resource_chunks() {
-                        data_slices.iter().enumerate().for_each(|(i, slice)| {
-                            std::fs::write(asset_path.join(format!("resource{}", i)), slice)
-                                .unwrap_or_else(|e| {
-                                    eprintln!(
-                                        "Unable to write descriptor for {}\nError: {}",
-                                        raw_asset.name(),
-                                        e
-                                    );
-                                });
-                        });
-                    }
-                });
-            }
-        }
+        /// The directory containing the text files
+        #[arg(value_name = "TEXT_DIR", required = true)]
+        input_dir: PathBuf,
 
-        Commands::Create {
-            asset_dirs,
-            output_file,
-        } => {
-            let mut bnl = BNLFile::default();
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the new .bnl file will be written to
+        output_file: PathBuf,
 
-            let mut asset_paths = vec![];
+        /// Write every key which was given a substitute hash to this file, as JSON
+        #[arg(long, value_name = "FILE")]
+        collision_report: Option<PathBuf>,
 
-            for dir in &asset_dirs {
-                let walker = WalkDir::new(dir).into_iter();
-                for asset_dir in walker
-                    .filter_map(|val| val.ok())
-                    .filter(|entry| {
-                        if let Ok(entries) = fs::read_dir(entry.path()) {
-                            entries
-                                .filter_map(|e| e.ok())
-                                .map(|e| e.path())
-                                .all(|path| !path.is_dir())
-                        } else {
-                            false
-                        }
-                    })
-                    .map(|dir_entry| dir_entry.path().to_owned())
-                {
-                    asset_paths.push(asset_dir.clone());
-                }
-                /*
-                    if !dir.exists() {
-                        eprintln!(
-                            "ERROR: Provided asset dir {} does not exist.",
-                            dir.display()
-                        );
-                        error_exit();
-                    }
+        /// A JSON file naming control codes, the same as given to loctext export
+        #[arg(long, value_name = "FILE")]
+        codes: Option<PathBuf>,
+    },
+}
 
-                        subwalker.filter_map(|e|e.ok()).filter_entry(|entry|entry.into_path.is_ok).all(|entry|)
-                    }) {
-                        println!("{}", entry?.path().display());
-                    }
-                }
-                */
-            }
+#[derive(Subcommand, Debug)]
+enum TextureCommands {
+    /// Write a texture to a PNG file
+    Export {
+        /// The BNL file containing the texture
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-            let raw_assets: Vec<RawAsset> = asset_paths
-                .iter()
-                .map(|asset_path| {
-                    println!("Reading raw asset from {}", asset_path.display());
-                    RawAsset::from_dir(asset_path).unwrap()
-                })
-                .collect();
+        /// The name of the texture asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
 
-            for raw_asset in raw_assets {
-                println!("Adding {} to {}", raw_asset.name(), output_file.display());
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the PNG will be written to
+        output_file: PathBuf,
 
-                bnl.append_raw_asset(raw_asset);
-            }
+        /// The format to decode the texture as, for textures whose format isn't known
+        #[arg(short = 'f', long, value_enum)]
+        format: Option<TextureFormat>,
+    },
 
-            println!(
-                "\nSuccessfully wrote all assets. Outputting to {}",
-                output_file.display()
-            );
-            if let Err(e) = fs::write(output_file, bnl.to_bytes()) {
-                eprintln!("Failed to write output bnl file. Error: {}", e);
+    /// Replace a texture with a PNG file, and write it back into the BNL file in place. The PNG
+    /// doesn't need to be the same size as the original texture, the texture is resized to match
+    /// unless --resize is given.
+    Import {
+        /// The BNL file containing the texture
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                error_exit();
-            } else {
-                println!("\nSuccessfully wrote bnl file.");
-            }
-        }
+        /// The name of the texture asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
 
-        Commands::List {
-            bnl_path,
-            alphabetical_order,
-            asset_type_filter,
-            print_summary,
-        } => {
-            let bytes: Vec<u8> = match std::fs::read(&bnl_path) {
-                Ok(f) => f,
-                Err(e) => {
-                    eprintln!("Unable to open file {}. Error: {}", bnl_path.display(), e);
-                    error_exit();
-                }
-            };
+        /// The PNG to read
+        #[arg(value_name = "PNG_FILE", required = true)]
+        png_path: PathBuf,
 
-            let bnl = match BNLFile::from_bytes(&bytes) {
-                Ok(b) => b,
-                Err(e) => {
-                    eprintln!("Unable to process BNL file: {:?}", e);
-                    error_exit();
-                }
-            };
+        /// The format to store the texture in. Defaults to the format of the original texture.
+        #[arg(short = 'f', long, value_enum)]
+        format: Option<TextureFormat>,
 
-            let mut raw_assets = bnl
-                .get_raw_assets()
-                .iter()
-                .filter(|raw_asset| {
-                    if let Some(type_filter) = &asset_type_filter {
-                        raw_asset.metadata().asset_type.to_string() == type_filter.as_str()
-                    } else {
-                        true
-                    }
-                })
-                .collect::<Vec<&RawAsset>>();
+        /// Scale the PNG to the size of the original texture with this filter, instead of
+        /// resizing the texture
+        #[arg(short = 'r', long, value_enum, value_name = "FILTER")]
+        resize: Option<TextureResizeFilter>,
+    },
 
-            // Sort by asset type
-            raw_assets.sort_by_key(|raw| raw.metadata().asset_type);
+    /// Write a PNG showing a labelled thumbnail of every texture in a BNL file
+    Sheet {
+        /// The BNL file containing the textures
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-            if alphabetical_order {
-                // Since sort by key is stable, we can alphabetical sort after
-                raw_assets.sort_by_key(|raw| raw.metadata().asset_type.to_string());
-            }
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the PNG will be written to
+        output_file: PathBuf,
 
-            raw_assets.iter().for_each(|raw_asset| {
-                println!("{}", raw_asset.name());
-            });
+        /// The size of the longest side of each thumbnail
+        #[arg(long, default_value_t = 128)]
+        size: usize,
 
-            if print_summary {
-                println!("{} assets found.", raw_assets.len());
+        /// The number of thumbnails in each row
+        #[arg(long, default_value_t = 8)]
+        columns: usize,
 
-                // Print the list of types found if theres no filter
-                if asset_type_filter.is_none() {
-                    let types_found =
-                        raw_assets
-                            .iter()
-                            .fold(HashSet::<AssetType>::new(), |mut acc, val| {
-                                acc.insert(val.metadata().asset_type);
-                                acc
-                            });
+        /// The number of threads used to decode textures, or 0 for one per CPU
+        #[arg(short = 'j', long, default_value_t = 0)]
+        jobs: usize,
+    },
+}
 
-                    let mut types_str = types_found
-                        .iter()
-                        .map(|t| t.to_string())
-                        .collect::<Vec<String>>();
+#[derive(Subcommand, Debug)]
+enum FontCommands {
+    /// Write one variant of a font as a BMFont .fnt file and a PNG page, named after the asset
+    Export {
+        /// The BNL file containing the font
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
 
-                    types_str.sort();
+        /// The name of the font asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
 
-                    println!(
-                        "{num_types} Asset types: {}",
-                        types_str.join(" "),
-                        num_types = types_str.len(),
-                    );
-                }
-            }
-        }
+        #[arg(short = 'o', value_name = "DIR")]
+        /// The directory which the files will be written to
+        output_dir: PathBuf,
+
+        /// The variant of the glyphs to export
+        #[arg(long, default_value_t = 0)]
+        variant: usize,
+    },
+
+    /// Replace the glyphs of a font with the chars of a BMFont .fnt file, adding any glyphs the
+    /// font doesn't have, and write it back into the BNL file in place
+    Import {
+        /// The BNL file containing the font
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The name of the font asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
+
+        /// The .fnt file to read. Its pages are read relative to it.
+        #[arg(value_name = "FNT_FILE", required = true)]
+        fnt_path: PathBuf,
+
+        /// The variant of the glyphs to replace
+        #[arg(long, default_value_t = 0)]
+        variant: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelCommands {
+    /// Write the render and collision geometry of a model to an OBJ file, as separate objects
+    Obj {
+        /// The BNL file containing the model
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The name of the model asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the OBJ will be written to
+        output_file: PathBuf,
+
+        /// Only write the collision mesh
+        #[arg(long)]
+        collision_only: bool,
+    },
+
+    /// Print the nd trees of a model as JSON, with the offset and pointers of every nd
+    Hierarchy {
+        /// The BNL file containing the model
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The name of the model asset
+        #[arg(value_name = "ASSET_NAME", required = true)]
+        asset_name: String,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// Write the JSON to this file instead of stdout
+        output_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PatchCommands {
+    /// Write the changes which turn one BNL file into another to a patch file
+    Create {
+        /// The original BNL file
+        #[arg(value_name = "BASE_FILE", required = true)]
+        base_path: PathBuf,
+
+        /// The changed BNL file
+        #[arg(value_name = "TARGET_FILE", required = true)]
+        target_path: PathBuf,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the patch will be written to
+        output_file: PathBuf,
+    },
+
+    /// Apply a patch written by patch create to the BNL file it was made from
+    Apply {
+        /// The BNL file to patch
+        #[arg(value_name = "BNL_FILE", required = true)]
+        bnl_path: PathBuf,
+
+        /// The patch file
+        #[arg(value_name = "PATCH_FILE", required = true)]
+        patch_path: PathBuf,
+
+        #[arg(short = 'o', value_name = "FILE")]
+        /// The path which the patched .bnl file will be written to
+        output_file: PathBuf,
+    },
+}
+
+/// The files written for each asset by extract
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExtractLayout {
+    /// The metadata, descriptor and resource files, which are read back unchanged
+    Raw,
+    /// The metadata and descriptor, with textures as texture.png instead of their resource files
+    Converted,
+    /// Everything written by raw and converted
+    Both,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PatternFormat {
+    Imhex,
+    Kaitai,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TextureResizeFilter {
+    Nearest,
+    Bilinear,
+    Lanczos,
+}
+
+impl From<TextureResizeFilter> for ResizeFilter {
+    fn from(filter: TextureResizeFilter) -> Self {
+        match filter {
+            TextureResizeFilter::Nearest => ResizeFilter::Nearest,
+            TextureResizeFilter::Bilinear => ResizeFilter::Bilinear,
+            TextureResizeFilter::Lanczos => ResizeFilter::Lanczos3,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TextureFormat {
+    Dxt1,
+    Dxt3,
+    Rgba8,
+}
+
+impl From<TextureFormat> for D3DFormat {
+    fn from(format: TextureFormat) -> Self {
+        match format {
+            TextureFormat::Dxt1 => D3DFormat::Standard(StandardFormat::DXT1),
+            TextureFormat::Dxt3 => D3DFormat::Standard(StandardFormat::DXT2Or3),
+            TextureFormat::Rgba8 => D3DFormat::Swizzled(Swizzled::B8G8R8A8),
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    ERROR_FORMAT.get_or_init(|| cli.error_format);
+
+    match cli.command {
+        Commands::Extract {
+            bnl_files,
+            output_dir,
+            recursive,
+            layout,
+            flat,
+            png,
+            jobs,
+        } => {
+            let pool = TranscodePool::new(jobs);
+
+            let layout = match png {
+                true => ExtractLayout::Both,
+                false => layout,
+            };
+
+            if bnl_files.is_empty() {
+                error_exit(
+                    Failure::Other,
+                    "Unable to extract: no bnl files provided.".to_string(),
+                );
+            }
+
+            let mut unreadable = UnreadableBnls::default();
+
+            for bnl_file in bnl_files {
+                if recursive && bnl_file.is_dir() {
+                    for (found, relative) in find_bnl_files(&bnl_file) {
+                        let Some(bnl) = unreadable.read(&found) else {
+                            continue;
+                        };
+
+                        extract_bnl(
+                            &found,
+                            &bnl,
+                            &output_dir.join(relative.parent().unwrap_or(Path::new(""))),
+                            layout,
+                            flat,
+                            &pool,
+                        );
+                    }
+                } else {
+                    let (_, bnl) = read_bnl(&bnl_file);
+                    extract_bnl(&bnl_file, &bnl, &output_dir, layout, flat, &pool);
+                }
+            }
+
+            unreadable.exit_if_any();
+        }
+
+        Commands::Create {
+            asset_dirs,
+            output_file,
+            reproducible,
+            dedup,
+            dedup_descriptors,
+            order,
+            strict,
+            alignment,
+        } => {
+            let mut bnl = BNLFile::default();
+
+            if reproducible {
+                bnl.set_asset_order(AssetOrder::NameSorted);
+            }
+
+            let order = order.map(|order_path| match read_asset_order(&order_path) {
+                Ok(names) => {
+                    bnl.set_asset_order(AssetOrder::Original);
+                    names
+                }
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to read {}: {}", order_path.display(), e),
+                    );
+                }
+            });
+
+            bnl.set_write_options(alignment.apply(BNLWriteOptions {
+                dedup_chunks: dedup,
+                dedup_descriptors,
+                ..Default::default()
+            }));
+
+            let mut asset_paths = vec![];
+
+            for dir in &asset_dirs {
+                let mut walker = WalkDir::new(dir);
+
+                if reproducible {
+                    walker = walker.sort_by_file_name();
+                }
+
+                let walker = walker.into_iter();
+                for asset_dir in walker
+                    .filter_map(|val| val.ok())
+                    .filter(|entry| {
+                        if let Ok(entries) = fs::read_dir(entry.path()) {
+                            entries
+                                .filter_map(|e| e.ok())
+                                .map(|e| e.path())
+                                .all(|path| !path.is_dir())
+                        } else {
+                            false
+                        }
+                    })
+                    .map(|dir_entry| dir_entry.path().to_owned())
+                {
+                    asset_paths.push(asset_dir.clone());
+                }
+                /*
+                    if !dir.exists() {
+                        eprintln!(
+                            "ERROR: Provided asset dir {} does not exist.",
+                            dir.display()
+                        );
+                        error_exit();
+                    }
+
+                        subwalker.filter_map(|e|e.ok()).filter_entry(|entry|entry.into_path.is_ok).all(|entry|)
+                    }) {
+                        println!("{}", entry?.path().display());
+                    }
+                }
+                */
+            }
+
+            // Failures are reported together at the end, unless strict mode stops at the first
+            let mut failures: Vec<(String, String)> = vec![];
+            let mut fail = |what: String, reason: String| {
+                let message = format!("Unable to add {}: {}", what, reason);
+
+                if strict {
+                    error_exit(Failure::Validation, message);
+                }
+
+                report_error(Failure::Validation, &message);
+                failures.push((what, reason));
+            };
+
+            let mut raw_assets: Vec<RawAsset> = vec![];
+
+            for asset_path in &asset_paths {
+                let json_paths = flat_asset_files(asset_path);
+
+                // Directories written by extract --flat have a JSON file for each asset
+                if !asset_path.join("metadata").is_file() && !json_paths.is_empty() {
+                    for json_path in json_paths {
+                        println!("Reading raw asset from {}", json_path.display());
+
+                        match RawAsset::from_flat_file(&json_path) {
+                            Ok(raw_asset) => raw_assets.push(raw_asset),
+                            Err(e) => fail(json_path.display().to_string(), e.to_string()),
+                        }
+                    }
+
+                    continue;
+                }
+
+                println!("Reading raw asset from {}", asset_path.display());
+
+                match RawAsset::from_dir(asset_path) {
+                    Ok(raw_asset) => raw_assets.push(raw_asset),
+                    Err(e) => fail(asset_path.display().to_string(), e.to_string()),
+                }
+            }
+
+            if let Some(order) = &order {
+                let position = |name: &str| order.iter().position(|listed| listed == name);
+
+                for name in order {
+                    if !raw_assets.iter().any(|raw_asset| raw_asset.name() == name) {
+                        fail(
+                            name.clone(),
+                            "listed in the order file, but not found".into(),
+                        );
+                    }
+                }
+
+                for raw_asset in &raw_assets {
+                    if position(raw_asset.name()).is_none() {
+                        println!(
+                            "{} isn't in the order file, placing it after the listed assets",
+                            raw_asset.name()
+                        );
+                    }
+                }
+
+                raw_assets
+                    .sort_by_key(|raw_asset| position(raw_asset.name()).unwrap_or(usize::MAX));
+            }
+
+            let mut added: Vec<String> = vec![];
+
+            for raw_asset in raw_assets {
+                let name = raw_asset.name().to_string();
+
+                println!("Adding {} to {}", name, output_file.display());
+
+                match bnl.append_raw_asset(raw_asset) {
+                    Ok(()) => added.push(name),
+                    Err(e) => fail(name, e.to_string()),
+                }
+            }
+
+            println!("\nAdded {} assets, {} failed.", added.len(), failures.len());
+            added.iter().for_each(|name| println!("  OK     {}", name));
+            failures
+                .iter()
+                .for_each(|(what, reason)| println!("  FAILED {}: {}", what, reason));
+
+            println!("\nOutputting to {}", output_file.display());
+            if let Err(e) = fs::write(output_file, bnl.to_bytes()) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write output bnl file. Error: {}", e),
+                );
+            } else {
+                println!("\nSuccessfully wrote bnl file.");
+            }
+
+            // The file is still written without them, but a repack missing assets isn't a success
+            if !failures.is_empty() {
+                error_exit(
+                    Failure::Validation,
+                    format!("{} assets could not be added", failures.len()),
+                );
+            }
+        }
+
+        Commands::List {
+            bnl_path,
+            alphabetical_order,
+            asset_type_filter,
+            print_summary,
+            recursive,
+            group,
+        } => {
+            if recursive && bnl_path.is_dir() {
+                let mut unreadable = UnreadableBnls::default();
+
+                for (found, relative) in find_bnl_files(&bnl_path) {
+                    println!("{}:", relative.display());
+
+                    let Some(bnl) = unreadable.read(&found) else {
+                        continue;
+                    };
+
+                    list_bnl(
+                        &bnl,
+                        asset_type_filter.as_deref(),
+                        alphabetical_order,
+                        print_summary,
+                        group,
+                    );
+
+                    println!();
+                }
+
+                unreadable.exit_if_any();
+            } else {
+                let (_, bnl) = read_bnl(&bnl_path);
+                list_bnl(
+                    &bnl,
+                    asset_type_filter.as_deref(),
+                    alphabetical_order,
+                    print_summary,
+                    group,
+                );
+            }
+        }
+
+        Commands::Info {
+            bnl_path,
+            asset_name,
+            hex,
+            map,
+        } => {
+            let bytes: Vec<u8> = match std::fs::read(&bnl_path) {
+                Ok(f) => f,
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to open file {}. Error: {}", bnl_path.display(), e),
+                    );
+                }
+            };
+
+            let bnl = match BNLFile::from_bytes(&bytes) {
+                Ok(b) => b,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to process BNL file: {:?}", e),
+                    );
+                }
+            };
+
+            let Some(raw_asset) = bnl.get_raw_asset(&asset_name) else {
+                error_exit(
+                    Failure::NotFound,
+                    format!("No asset named {} in {}", asset_name, bnl_path.display()),
+                );
+            };
+
+            let metadata = raw_asset.metadata();
+
+            println!("Name: {}", raw_asset.name());
+            println!("Type: {}", metadata.asset_type());
+            println!("Descriptor size: {:#x}", raw_asset.descriptor_bytes().len());
+            println!(
+                "Resource chunks: {}",
+                raw_asset.resource_chunks().map(|c| c.len()).unwrap_or(0)
+            );
+
+            match raw_asset.descriptor_fields() {
+                Ok(Some(fields)) if hex => {
+                    println!("\nDescriptor:");
+                    print!("{}", annotate(raw_asset.descriptor_bytes(), &fields));
+                }
+                Ok(Some(fields)) => {
+                    println!("\nDescriptor fields:");
+                    fields.iter().for_each(|field| println!("  {}", field));
+                }
+                Ok(None) => {
+                    println!("\nDescriptor layout is not known for this asset type.");
+
+                    if hex {
+                        print!("\n{}", annotate(raw_asset.descriptor_bytes(), &[]));
+                    }
+                }
+                Err(e) => {
+                    error_exit(Failure::Parse, format!("Unable to parse descriptor: {}", e));
+                }
+            }
+
+            if metadata.asset_type() == AssetType::ResModel {
+                match bnl.get_asset::<Model>(&asset_name) {
+                    Ok(model) => print_model_stats(&model.asset().stats()),
+                    Err(e) => {
+                        report_error(Failure::Parse, &format!("\nUnable to read model: {}", e))
+                    }
+                }
+            }
+
+            if map {
+                let (parsed, recording) = record_spans(|| bnl.parse_any(&asset_name));
+
+                // The map still shows how far parsing got before it failed
+                match parsed {
+                    Err(e) => {
+                        report_error(Failure::Parse, &format!("\nUnable to parse asset: {}", e))
+                    }
+                    Ok(AnyAsset::Other(_)) => println!(
+                        "\n{} assets have no parser, so none of their bytes are read.",
+                        metadata.asset_type()
+                    ),
+                    Ok(_) if !RECORDED_ASSET_TYPES.contains(&metadata.asset_type()) => println!(
+                        "\nThe parser of {} assets reads some bytes which aren't recorded, so \
+                        bytes shown as unparsed may still have been read.",
+                        metadata.asset_type()
+                    ),
+                    Ok(_) => (),
+                }
+
+                let descriptor = raw_asset.descriptor_bytes();
+                println!("\nDescriptor coverage:");
+                print!(
+                    "{}",
+                    coverage_map(descriptor, &recording.coverage(descriptor))
+                );
+
+                for (i, chunk) in raw_asset
+                    .resource_chunks()
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                {
+                    println!("\nResource chunk {} coverage:", i);
+                    print!("{}", coverage_map(chunk, &recording.coverage(chunk)));
+                }
+            }
+        }
+
+        Commands::DumpJson {
+            bnl_path,
+            output_file,
+            include_data,
+        } => {
+            let bytes: Vec<u8> = match std::fs::read(&bnl_path) {
+                Ok(f) => f,
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to open file {}. Error: {}", bnl_path.display(), e),
+                    );
+                }
+            };
+
+            let bnl = match BNLFile::from_bytes(&bytes) {
+                Ok(b) => b,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to process BNL file: {:?}", e),
+                    );
+                }
+            };
+
+            let json = match serde_json::to_vec_pretty(&bnl.to_json(include_data)) {
+                Ok(json) => json,
+                Err(e) => {
+                    error_exit(
+                        Failure::Other,
+                        format!("Unable to serialise BNL file. Error: {}", e),
+                    );
+                }
+            };
+
+            if let Err(e) = fs::write(&output_file, json) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+        }
+
+        Commands::FromJson {
+            json_path,
+            output_file,
+            alignment,
+        } => {
+            let text = match fs::read_to_string(&json_path) {
+                Ok(text) => text,
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to open file {}. Error: {}", json_path.display(), e),
+                    );
+                }
+            };
+
+            let json: BnlJson = match serde_json::from_str(&text) {
+                Ok(json) => json,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to parse {}. Error: {}", json_path.display(), e),
+                    );
+                }
+            };
+
+            let mut bnl = match BNLFile::from_json(&json) {
+                Ok(bnl) => bnl,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to create BNL file: {:?}", e),
+                    );
+                }
+            };
+
+            bnl.set_write_options(alignment.apply(bnl.write_options().clone()));
+
+            if let Err(e) = fs::write(&output_file, bnl.to_bytes()) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write output bnl file. Error: {}", e),
+                );
+            }
+        }
+
+        Commands::Manifest {
+            asset_dir,
+            output_file,
+        } => {
+            let manifest = match asset_dir.is_file() {
+                true => Manifest::from_bnl(&read_bnl(&asset_dir).1),
+                false => match Manifest::from_dir(&asset_dir) {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        error_exit(
+                            Failure::Parse,
+                            format!("Unable to read assets in {}: {}", asset_dir.display(), e),
+                        );
+                    }
+                },
+            };
+
+            let json = match serde_json::to_vec_pretty(&manifest) {
+                Ok(json) => json,
+                Err(e) => {
+                    error_exit(
+                        Failure::Other,
+                        format!("Unable to serialise manifest. Error: {}", e),
+                    );
+                }
+            };
+
+            if let Err(e) = fs::write(&output_file, json) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+
+            for name in manifest.duplicates() {
+                report_warning(&format!(
+                    "{} is used by more than one asset, only the last is hashed",
+                    name
+                ));
+            }
+
+            println!(
+                "Wrote hashes for {} assets to {}",
+                manifest.assets.len(),
+                output_file.display()
+            );
+        }
+
+        Commands::Verify {
+            bnl_path,
+            manifest_path,
+        } => {
+            let bytes: Vec<u8> = match std::fs::read(&bnl_path) {
+                Ok(f) => f,
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to open file {}. Error: {}", bnl_path.display(), e),
+                    );
+                }
+            };
+
+            let bnl = match BNLFile::from_bytes(&bytes) {
+                Ok(b) => b,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to process BNL file: {:?}", e),
+                    );
+                }
+            };
+
+            let expected: Manifest = match fs::read_to_string(&manifest_path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+            {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to read manifest {}: {}", manifest_path.display(), e),
+                    );
+                }
+            };
+
+            let mismatches = expected.verify(&Manifest::from_bnl(&bnl));
+
+            if mismatches.is_empty() {
+                println!("{} matches the manifest.", bnl_path.display());
+            } else {
+                mismatches.iter().for_each(|m| println!("{}", m));
+
+                error_exit(
+                    Failure::Validation,
+                    format!("\n{} mismatches found.", mismatches.len()),
+                );
+            }
+        }
+
+        Commands::Find {
+            pattern,
+            path,
+            regex,
+            long,
+        } => {
+            let pattern = if regex {
+                pattern
+            } else {
+                glob_to_regex(&pattern)
+            };
+
+            let pattern = match Regex::new(&pattern) {
+                Ok(pattern) => pattern,
+                Err(e) => error_exit(Failure::Parse, format!("Invalid pattern: {}", e)),
+            };
+
+            let bnl_paths = if path.is_dir() {
+                find_bnl_files(&path)
+            } else {
+                vec![(path.clone(), path.clone())]
+            };
+
+            let mut matches = 0;
+
+            for (bnl_path, relative) in bnl_paths {
+                // One unreadable archive shouldn't stop the search of the others
+                let bnl = match fs::read(&bnl_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| BNLFile::from_bytes(&bytes).map_err(|e| format!("{:?}", e)))
+                {
+                    Ok(bnl) => bnl,
+                    Err(e) => {
+                        report_warning(&format!("Skipping {}: {}", bnl_path.display(), e));
+                        continue;
+                    }
+                };
+
+                for raw_asset in bnl
+                    .get_raw_assets()
+                    .iter()
+                    .filter(|raw_asset| pattern.is_match(raw_asset.name()))
+                {
+                    matches += 1;
+
+                    if long {
+                        let size = raw_asset.descriptor_bytes().len()
+                            + raw_asset
+                                .resource_chunks()
+                                .map(|chunks| chunks.iter().map(Vec::len).sum())
+                                .unwrap_or(0);
+
+                        println!(
+                            "{}: {} {} {:#x}",
+                            relative.display(),
+                            raw_asset.name(),
+                            raw_asset.metadata().asset_type(),
+                            size
+                        );
+                    } else {
+                        println!("{}: {}", relative.display(), raw_asset.name());
+                    }
+                }
+            }
+
+            if matches == 0 {
+                error_exit(
+                    Failure::NotFound,
+                    format!("No assets matching {} in {}", pattern, path.display()),
+                );
+            }
+        }
+
+        Commands::Pattern {
+            bnl_path,
+            asset_name,
+            format,
+            output_file,
+        } => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let Some(raw_asset) = bnl.get_raw_asset(&asset_name) else {
+                error_exit(
+                    Failure::NotFound,
+                    format!("No asset named {} in {}", asset_name, bnl_path.display()),
+                );
+            };
+
+            let fields = match raw_asset.descriptor_fields() {
+                Ok(Some(fields)) => fields,
+                Ok(None) => error_exit(
+                    Failure::Other,
+                    format!(
+                        "The descriptor layout of {} is not known",
+                        raw_asset.metadata().asset_type()
+                    ),
+                ),
+                Err(e) => error_exit(Failure::Parse, format!("Unable to parse descriptor: {}", e)),
+            };
+
+            // eg. ResTexture -> TextureDescriptor
+            let struct_name = format!(
+                "{}Descriptor",
+                raw_asset
+                    .metadata()
+                    .asset_type()
+                    .to_string()
+                    .trim_start_matches("Res")
+            );
+            let size = Some(raw_asset.descriptor_bytes().len());
+
+            let pattern = match format {
+                PatternFormat::Imhex => imhex_pattern(&struct_name, &fields, size),
+                PatternFormat::Kaitai => kaitai_struct(&struct_name, &fields, size),
+            };
+
+            match output_file {
+                Some(output_file) => {
+                    if let Err(e) = fs::write(&output_file, pattern) {
+                        error_exit(
+                            Failure::Io,
+                            format!("Failed to write {}. Error: {}", output_file.display(), e),
+                        );
+                    }
+                }
+                None => print!("{}", pattern),
+            }
+        }
+
+        Commands::Validate { bnl_path } => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let issues = bnl.validate();
+
+            if issues.is_empty() {
+                println!("No issues found in {}.", bnl_path.display());
+            } else {
+                issues.iter().for_each(|issue| println!("{}", issue));
+
+                println!("\n{} issues found.", issues.len());
+            }
+        }
+
+        Commands::Coverage { bnl_path } => {
+            let (bytes, _) = read_bnl(&bnl_path);
+
+            let coverage = match DecompressedBnl::from_bytes(&bytes).and_then(|d| d.coverage()) {
+                Ok(coverage) => coverage,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to process BNL file: {:?}", e),
+                    );
+                }
+            };
+
+            for section in coverage {
+                println!(
+                    "{} ({:#x} bytes): {:#x} unreferenced, {:#x} shared",
+                    section.section,
+                    section.size,
+                    section.unreferenced_bytes(),
+                    section.shared_bytes()
+                );
+
+                for range in &section.unreferenced {
+                    println!("  {:#x}..{:#x} unreferenced", range.start, range.end);
+                }
+
+                for shared in &section.shared {
+                    println!(
+                        "  {:#x}..{:#x} referenced {} times by {}",
+                        shared.range.start,
+                        shared.range.end,
+                        shared.references,
+                        shared.asset_names.join(", ")
+                    );
+                }
+            }
+        }
+
+        Commands::Layout { bnl_path } => {
+            let (_, bnl) = read_bnl(&bnl_path);
+            let layout = bnl.layout();
+
+            println!("{:<20} {:>10} {:>10}", "Section", "Offset", "Size");
+
+            if let Some(source) = &layout.source {
+                println!("As read:");
+                print!("{}", source);
+            }
+
+            match layout.is_changed() {
+                true => {
+                    println!("As written:");
+                    print!("{}", layout.written);
+                }
+                false => println!("Writing the file back keeps the same layout"),
+            }
+        }
+
+        Commands::Stats {
+            bnl_files,
+            recursive,
+            top,
+        } => {
+            let paths: Vec<PathBuf> = bnl_files
+                .into_iter()
+                .flat_map(|path| match recursive && path.is_dir() {
+                    true => find_bnl_files(&path)
+                        .into_iter()
+                        .map(|(found, _)| found)
+                        .collect(),
+                    false => vec![path],
+                })
+                .collect();
+
+            let mut stats = BnlStats::default();
+
+            for path in &paths {
+                let (bytes, bnl) = read_bnl(path);
+                stats.add(path, bytes.len(), &bnl);
+            }
+
+            stats.print(top);
+        }
+
+        Commands::Script(ScriptCommands::Dump {
+            bnl_path,
+            asset_name,
+            output_file,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let script = match bnl.get_asset::<Script>(&asset_name) {
+                Ok(script) => script,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read script {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            for (operation, issue) in script.asset().descriptor().operand_issues() {
+                report_warning(&format!("operation {} is malformed. {}", operation, issue));
+            }
+
+            let text = disassemble(script.asset().descriptor());
+
+            match output_file {
+                Some(output_file) => {
+                    if let Err(e) = fs::write(&output_file, text) {
+                        error_exit(
+                            Failure::Io,
+                            format!("Failed to write {}. Error: {}", output_file.display(), e),
+                        );
+                    }
+                }
+                None => print!("{}", text),
+            }
+        }
+
+        Commands::Script(ScriptCommands::Refs {
+            bnl_path,
+            asset_name,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let script_names: Vec<String> = match asset_name {
+                Some(asset_name) => vec![asset_name],
+                None => bnl
+                    .get_raw_assets()
+                    .iter()
+                    .filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResScript)
+                    .map(|raw_asset| raw_asset.name().to_string())
+                    .collect(),
+            };
+
+            for name in script_names {
+                let script = match bnl.get_asset::<Script>(&name) {
+                    Ok(script) => script,
+                    Err(e) => {
+                        error_exit(
+                            asset_failure(&e),
+                            format!("Unable to read script {}: {}", name, e),
+                        );
+                    }
+                };
+                let descriptor = script.asset().descriptor();
+
+                println!("{}:", name);
+
+                for reference in descriptor.referenced_assets() {
+                    println!(
+                        "  {:?} {} (operation {})",
+                        reference.kind, reference.asset_name, reference.operation
+                    );
+                }
+
+                for (operation, challenge) in descriptor.challenges() {
+                    println!("  Challenge {:?} (operation {})", challenge, operation);
+                }
+
+                for problem in reference_problems(&bnl, descriptor) {
+                    println!("  Warning: {}", problem);
+                }
+
+                for (operation, issue) in descriptor.operand_issues() {
+                    report_warning(&format!(
+                        "operation {} of {} is malformed. {}",
+                        operation, name, issue
+                    ));
+                }
+            }
+        }
+
+        Commands::Script(ScriptCommands::Apply {
+            bnl_path,
+            asset_name,
+            script_path,
+        }) => {
+            let (bytes, mut bnl) = read_bnl(&bnl_path);
+
+            let descriptor = match fs::read_to_string(&script_path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| assemble(&text).map_err(|e| e.to_string()))
+            {
+                Ok(descriptor) => descriptor,
+                Err(e) => {
+                    error_exit(
+                        Failure::Parse,
+                        format!("Unable to assemble {}: {}", script_path.display(), e),
+                    );
+                }
+            };
+
+            let Some(raw_asset) = bnl.get_raw_asset(&asset_name) else {
+                error_exit(
+                    Failure::NotFound,
+                    format!("No asset named {} in {}", asset_name, bnl_path.display()),
+                );
+            };
+
+            if raw_asset.metadata().asset_type() != AssetType::ResScript {
+                error_exit(Failure::NotFound, format!("{} is not a script", asset_name));
+            }
+
+            for problem in reference_problems(&bnl, &descriptor) {
+                report_warning(&problem);
+            }
+
+            let new_asset = match descriptor.to_bytes() {
+                Ok(descriptor_bytes) => RawAsset::new(
+                    raw_asset.metadata().clone(),
+                    descriptor_bytes,
+                    raw_asset.resource_chunks().cloned(),
+                ),
+                Err(e) => {
+                    error_exit(Failure::Other, format!("Unable to write script: {}", e));
+                }
+            };
+
+            if let Err(e) = bnl.patch_asset(&asset_name, new_asset) {
+                error_exit(
+                    asset_failure(&e),
+                    format!("Unable to replace {}: {}", asset_name, e),
+                );
+            }
+
+            let patched = match bnl.write_patched(&bytes) {
+                Ok(patched) => patched,
+                Err(e) => {
+                    error_exit(Failure::Other, format!("Unable to write BNL file: {:?}", e));
+                }
+            };
+
+            if let Err(e) = fs::write(&bnl_path, patched) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", bnl_path.display(), e),
+                );
+            }
+
+            println!("Wrote {} to {}", asset_name, bnl_path.display());
+        }
+
+        Commands::Texture(TextureCommands::Export {
+            bnl_path,
+            asset_name,
+            output_file,
+            format,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let texture = match bnl.get_asset::<Texture>(&asset_name) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read texture {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            let options = TextureExportOptions {
+                format: format.map(D3DFormat::from),
+                ..Default::default()
+            };
+
+            if let Err(e) = texture.asset().dump_with(&output_file, options) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+        }
+
+        Commands::Texture(TextureCommands::Sheet {
+            bnl_path,
+            output_file,
+            size,
+            columns,
+            jobs,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let mut sheet = ContactSheet::new(ContactSheetOptions {
+                thumbnail_size: size,
+                columns,
+                ..Default::default()
+            });
+
+            let names: Vec<&str> = bnl
+                .get_raw_assets()
+                .iter()
+                .filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResTexture)
+                .map(|raw_asset| raw_asset.name())
+                .collect();
+
+            // Each texture is parsed by the job which decodes it, so only the textures being
+            // worked on are held in memory
+            let thumbnails = TranscodePool::new(jobs).map(&names, |name| {
+                let image = bnl
+                    .get_asset::<Texture>(name)
+                    .map_err(|e| e.to_string())?
+                    .asset()
+                    .to_rgba_image()
+                    .map_err(|e| e.to_string())?;
+
+                Ok::<_, String>(sheet.thumbnail(&image))
+            });
+
+            for (name, thumbnail) in names.iter().zip(thumbnails) {
+                match thumbnail {
+                    Ok(thumbnail) => sheet.add_thumbnail(name, thumbnail),
+                    Err(e) => report_warning(&format!("Skipping texture {}: {}", name, e)),
+                }
+            }
+
+            if sheet.is_empty() {
+                error_exit(
+                    Failure::NotFound,
+                    format!("No textures could be read from {}", bnl_path.display()),
+                );
+            }
+
+            if let Err(e) = File::create(&output_file)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    sheet
+                        .to_image()
+                        .dump_png_bytes(&mut BufWriter::new(file))
+                        .map_err(|e| format!("{:?}", e))
+                })
+            {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+
+            println!(
+                "Wrote {} textures to {}",
+                sheet.len(),
+                output_file.display()
+            );
+        }
+
+        Commands::Texture(TextureCommands::Import {
+            bnl_path,
+            asset_name,
+            png_path,
+            format,
+            resize,
+        }) => {
+            let (bytes, mut bnl) = read_bnl(&bnl_path);
+
+            let image = match File::open(&png_path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    RGBAImage::from_png_bytes(BufReader::new(file)).map_err(|e| format!("{:?}", e))
+                }) {
+                Ok(image) => image,
+                Err(e) => {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to read {}: {}", png_path.display(), e),
+                    );
+                }
+            };
+
+            let mut texture = match bnl.get_asset::<Texture>(&asset_name) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read texture {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            let format = format.map_or(texture.asset().descriptor().format(), D3DFormat::from);
+
+            let image = match resize {
+                Some(filter) => {
+                    let descriptor = texture.asset().descriptor();
+                    image.resize(
+                        descriptor.width() as usize,
+                        descriptor.height() as usize,
+                        filter.into(),
+                    )
+                }
+                None => image,
+            };
+
+            if let Err(e) = texture.asset_mut().replace_from_rgba(
+                image.width(),
+                image.height(),
+                image.bytes(),
+                format,
+            ) {
+                error_exit(
+                    Failure::Other,
+                    format!("Unable to convert the image to {:?}: {:?}", format, e),
+                );
+            }
+
+            if let Err(e) = texture
+                .to_raw_asset()
+                .and_then(|raw_asset| bnl.patch_asset(&asset_name, raw_asset))
+            {
+                error_exit(
+                    asset_failure(&e),
+                    format!("Unable to replace {}: {}", asset_name, e),
+                );
+            }
+
+            let patched = match bnl.write_patched(&bytes) {
+                Ok(patched) => patched,
+                Err(e) => {
+                    error_exit(Failure::Other, format!("Unable to write BNL file: {:?}", e));
+                }
+            };
+
+            if let Err(e) = fs::write(&bnl_path, patched) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", bnl_path.display(), e),
+                );
+            }
+
+            println!("Wrote {} to {}", asset_name, bnl_path.display());
+        }
+
+        Commands::Font(FontCommands::Export {
+            bnl_path,
+            asset_name,
+            output_dir,
+            variant,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let font = match bnl.get_asset::<Font>(&asset_name) {
+                Ok(font) => font,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read font {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            let page_file = format!("{}_{}.png", asset_name, variant);
+
+            let (bmfont, page) = match font.asset().to_bmfont(variant, &asset_name, &page_file) {
+                Ok(exported) => exported,
+                Err(e) => error_exit(font_failure(&e), format!("Unable to export font: {}", e)),
+            };
+
+            if let Err(e) = fs::create_dir_all(&output_dir) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to create {}. Error: {}", output_dir.display(), e),
+                );
+            }
+
+            let fnt_path = output_dir.join(format!("{}.fnt", asset_name));
+            let page_path = output_dir.join(&page_file);
+
+            if let Err(e) = fs::write(&fnt_path, bmfont.to_text()) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", fnt_path.display(), e),
+                );
+            }
+
+            if let Err(e) = File::create(&page_path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    page.dump_png_bytes(&mut BufWriter::new(file))
+                        .map_err(|e| format!("{:?}", e))
+                })
+            {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", page_path.display(), e),
+                );
+            }
+
+            println!(
+                "Wrote {} glyphs to {}",
+                bmfont.chars.len(),
+                fnt_path.display()
+            );
+        }
+
+        Commands::Font(FontCommands::Import {
+            bnl_path,
+            asset_name,
+            fnt_path,
+            variant,
+        }) => {
+            let (bytes, mut bnl) = read_bnl(&bnl_path);
+
+            let bmfont = match fs::read_to_string(&fnt_path)
+                .map_err(FontError::from)
+                .and_then(|text| BmFont::parse(&text))
+            {
+                Ok(bmfont) => bmfont,
+                Err(e) => {
+                    error_exit(
+                        font_failure(&e),
+                        format!("Unable to read {}: {}", fnt_path.display(), e),
+                    );
+                }
+            };
+
+            let fnt_dir = fnt_path.parent().unwrap_or(Path::new(""));
+
+            let pages: Vec<RGBAImage> = bmfont
+                .pages
+                .iter()
+                .map(|page| {
+                    let page_path = fnt_dir.join(page);
+
+                    match File::open(&page_path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|file| {
+                            RGBAImage::from_png_bytes(BufReader::new(file))
+                                .map_err(|e| format!("{:?}", e))
+                        }) {
+                        Ok(image) => image,
+                        Err(e) => {
+                            error_exit(
+                                Failure::Io,
+                                format!("Unable to read {}: {}", page_path.display(), e),
+                            );
+                        }
+                    }
+                })
+                .collect();
+
+            let mut font = match bnl.get_asset::<Font>(&asset_name) {
+                Ok(font) => font,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read font {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            let imported = match font.asset_mut().import_bmfont(&bmfont, &pages, variant) {
+                Ok(imported) => imported,
+                Err(e) => error_exit(font_failure(&e), format!("Unable to import font: {}", e)),
+            };
+
+            if let Err(e) = font
+                .to_raw_asset()
+                .and_then(|raw_asset| bnl.patch_asset(&asset_name, raw_asset))
+            {
+                error_exit(
+                    asset_failure(&e),
+                    format!("Unable to replace {}: {}", asset_name, e),
+                );
+            }
+
+            let patched = match bnl.write_patched(&bytes) {
+                Ok(patched) => patched,
+                Err(e) => {
+                    error_exit(Failure::Other, format!("Unable to write BNL file: {:?}", e));
+                }
+            };
+
+            if let Err(e) = fs::write(&bnl_path, patched) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", bnl_path.display(), e),
+                );
+            }
+
+            println!(
+                "Imported {} glyphs into {} in {}",
+                imported,
+                asset_name,
+                bnl_path.display()
+            );
+        }
+
+        Commands::Model(ModelCommands::Obj {
+            bnl_path,
+            asset_name,
+            output_file,
+            collision_only,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let model = match bnl.get_asset::<Model>(&asset_name) {
+                Ok(model) => model,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read model {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            if model.asset().descriptor().collision_subresource.is_none() {
+                report_warning(&format!("{} has no collision mesh.", asset_name));
+            }
+
+            if let Err(e) = fs::write(&output_file, model.asset().to_obj(!collision_only)) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+        }
+
+        Commands::Model(ModelCommands::Hierarchy {
+            bnl_path,
+            asset_name,
+            output_file,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+
+            let model = match bnl.get_asset::<Model>(&asset_name) {
+                Ok(model) => model,
+                Err(e) => {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to read model {}: {}", asset_name, e),
+                    );
+                }
+            };
+
+            let json = match model.asset().dump_hierarchy_json() {
+                Ok(json) => json,
+                Err(e) => {
+                    error_exit(
+                        Failure::Other,
+                        format!("Unable to serialise hierarchy. Error: {}", e),
+                    );
+                }
+            };
+
+            match output_file {
+                Some(output_file) => {
+                    if let Err(e) = fs::write(&output_file, json) {
+                        error_exit(
+                            Failure::Io,
+                            format!("Failed to write {}. Error: {}", output_file.display(), e),
+                        );
+                    }
+                }
+                None => println!("{}", json),
+            }
+        }
+
+        Commands::Loctext(LoctextCommands::Export {
+            bnl_path,
+            format,
+            output_dir,
+            codes,
+        }) => {
+            let (_, bnl) = read_bnl(&bnl_path);
+            let codes = read_control_codes(codes.as_deref());
+
+            if let Err(e) = fs::create_dir_all(&output_dir) {
+                error_exit(
+                    Failure::Io,
+                    format!(
+                        "Unable to create directory {}.\nError: {}",
+                        output_dir.display(),
+                        e
+                    ),
+                );
+            }
+
+            for raw_asset in bnl
+                .get_raw_assets()
+                .iter()
+                .filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResLoctext)
+            {
+                let loctext = match LoctextResource::from_raw_asset_with_violations(raw_asset) {
+                    Ok((loctext, violations)) => {
+                        // The strings can still be read, so these are only warned about
+                        for violation in violations {
+                            report_warning(&format!(
+                                "loctext {} is malformed. {}",
+                                raw_asset.name(),
+                                violation
+                            ));
+                        }
+
+                        loctext
+                    }
+                    Err(e) => {
+                        report_error(
+                            Failure::Parse,
+                            &format!("Unable to read loctext {}: {}", raw_asset.name(), e),
+                        );
+                        continue;
+                    }
+                };
+
+                let out_path =
+                    output_dir.join(format!("{}.{}", raw_asset.name(), format.extension()));
+
+                match fs::write(&out_path, loctext_text::export(&loctext, format, &codes)) {
+                    Ok(_) => println!("Wrote {}", out_path.display()),
+                    Err(e) => {
+                        error_exit(
+                            Failure::Io,
+                            format!("Failed to write {}. Error: {}", out_path.display(), e),
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::Loctext(LoctextCommands::Import {
+            bnl_path,
+            input_dir,
+            output_file,
+            collision_report,
+            codes,
+        }) => {
+            let (bytes, mut bnl) = read_bnl(&bnl_path);
+            let codes = read_control_codes(codes.as_deref());
+
+            let loctext_names: Vec<String> = bnl
+                .get_raw_assets()
+                .iter()
+                .filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResLoctext)
+                .map(|raw_asset| raw_asset.name().to_string())
+                .collect();
+
+            let mut report: BTreeMap<String, Vec<LoctextCollision>> = BTreeMap::new();
+
+            for entry in fs::read_dir(&input_dir)
+                .unwrap_or_else(|e| {
+                    error_exit(
+                        Failure::Io,
+                        format!("Unable to read {}. Error: {}", input_dir.display(), e),
+                    );
+                })
+                .filter_map(|entry| entry.ok())
+            {
+                let path = entry.path();
+
+                let (Some(name), Some(format)) = (
+                    path.file_stem().and_then(|stem| stem.to_str()),
+                    LoctextFormat::from_path(&path),
+                ) else {
+                    continue;
+                };
+
+                if !loctext_names.iter().any(|n| n == name) {
+                    report_warning(&format!(
+                        "Skipping {}: no loctext asset named {}",
+                        path.display(),
+                        name
+                    ));
+                    continue;
+                }
+
+                // The text only has the keys and values, so the rest comes from the asset it
+                // replaces
+                let existing = match bnl.get_raw_asset(name).map(LoctextResource::from_raw_asset) {
+                    Some(Ok(existing)) => Some(existing),
+                    Some(Err(e)) => {
+                        report_warning(&format!(
+                            "{} couldn't be read ({}), so only the imported text is kept",
+                            name, e
+                        ));
+                        None
+                    }
+                    None => None,
+                };
+
+                let loctext = match fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| {
+                        match &existing {
+                            Some(existing) => {
+                                loctext_text::import_over(&text, format, &codes, existing)
+                            }
+                            None => loctext_text::import(&text, format, &codes),
+                        }
+                        .map_err(|e| e.to_string())
+                    }) {
+                    Ok(loctext) => loctext,
+                    Err(e) => {
+                        error_exit(
+                            Failure::Parse,
+                            format!("Unable to import {}: {}", path.display(), e),
+                        );
+                    }
+                };
+
+                let (resource, collisions) = match loctext.dump_with_collisions() {
+                    Ok(dumped) => dumped,
+                    Err(e) => {
+                        error_exit(
+                            Failure::Other,
+                            format!("Unable to write loctext {}: {}", name, e),
+                        );
+                    }
+                };
+
+                for collision in &collisions {
+                    println!(
+                        "{}: key {} has the same hash as another key (0x{:04x}), so 0x{:04x} was used instead",
+                        name, collision.key, collision.original_hash, collision.substituted_hash
+                    );
+                }
+
+                // Only the resource data changes, the descriptor is kept as is
+                let new_asset = bnl.get_raw_asset(name).map(|raw_asset| {
+                    RawAsset::new(
+                        raw_asset.metadata().clone(),
+                        raw_asset.descriptor_bytes().to_vec(),
+                        Some(vec![resource]),
+                    )
+                });
+
+                if let Err(e) = new_asset
+                    .ok_or(AssetError::NotFound)
+                    .and_then(|new_asset| bnl.patch_asset(name, new_asset))
+                {
+                    error_exit(
+                        asset_failure(&e),
+                        format!("Unable to replace {}: {}", name, e),
+                    );
+                }
+
+                println!("Imported {}", path.display());
+                report.insert(name.to_string(), collisions);
+            }
+
+            let patched = match bnl.write_patched(&bytes) {
+                Ok(patched) => patched,
+                Err(e) => {
+                    error_exit(Failure::Other, format!("Unable to write BNL file: {:?}", e));
+                }
+            };
+
+            if let Err(e) = fs::write(&output_file, patched) {
+                error_exit(
+                    Failure::Io,
+                    format!("Failed to write {}. Error: {}", output_file.display(), e),
+                );
+            }
+
+            if let Some(report_path) = collision_report {
+                let written = serde_json::to_vec_pretty(&report)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| fs::write(&report_path, json).map_err(|e| e.to_string()));
+
+                if let Err(e) = written {
+                    error_exit(
+                        Failure::Io,
+                        format!("Failed to write {}. Error: {}", report_path.display(), e),
+                    );
+                }
+            }
+        }
+
+        Commands::Patch(PatchCommands::Create {
+            base_path,
+            target_path,
+            output_file,
+        }) => {
+            let (_, base) = read_bnl(&base_path);
+            let (_, target) = read_bnl(&target_path);
+
+            let patch = match BnlPatch::diff(&base, &target) {
+                Ok(patch) => patch,
+                Err(e) => error_exit(
+                    Failure::Validation,
+                    format!("Unable to create patch: {}", e),
+                ),
+            };
+
+            for asset_patch in patch.assets() {
+                let change = match asset_patch {
+                    AssetPatch::Added { .. } => "ADDED",
+                    AssetPatch::Removed { .. } => "REMOVED",
+                    AssetPatch::Modified(_) => "MODIFIED",
+                };
+
+                match asset_patch.name() {
+                    Ok(name) => println!("  {:<8} {}", change, name),
+                    Err(e) => error_exit(Failure::Other, format!("Unable to create patch: {}", e)),
+                }
+            }
+
+            let bytes = match patch.to_bytes() {
+                Ok(bytes) => bytes,
+                Err(e) => error_exit(Failure::Other, format!("Unable to write patch: {}", e)),
+            };
+
+            if let Err(e) = fs::write(&output_file, &bytes) {
+                error_exit(
+                    Failure::Io,
+                    format!("Unable to write {}: {}", output_file.display(), e),
+                );
+            }
+
+            println!(
+                "\n{} assets changed, wrote {} bytes to {}",
+                patch.assets().len(),
+                bytes.len(),
+                output_file.display()
+            );
+        }
+
+        Commands::Patch(PatchCommands::Apply {
+            bnl_path,
+            patch_path,
+            output_file,
+        }) => {
+            let (_, base) = read_bnl(&bnl_path);
+
+            let patch = match fs::read(&patch_path) {
+                Ok(bytes) => match BnlPatch::from_bytes(&bytes) {
+                    Ok(patch) => patch,
+                    Err(e) => error_exit(
+                        Failure::Parse,
+                        format!("Unable to read {}: {}", patch_path.display(), e),
+                    ),
+                },
+                Err(e) => error_exit(
+                    Failure::Io,
+                    format!("Unable to open {}: {}", patch_path.display(), e),
+                ),
+            };
+
+            let patched = match patch.apply(&base) {
+                Ok(patched) => patched,
+                Err(e) => error_exit(
+                    match e {
+                        PatchError::MissingAsset(_) => Failure::NotFound,
+                        PatchError::InvalidPatch(_) => Failure::Parse,
+                        PatchError::AssetExists(_) | PatchError::BaseMismatch(_) => {
+                            Failure::Validation
+                        }
+                    },
+                    format!(
+                        "Unable to apply {} to {}: {}",
+                        patch_path.display(),
+                        bnl_path.display(),
+                        e
+                    ),
+                ),
+            };
+
+            if let Err(e) = fs::write(&output_file, patched.to_bytes()) {
+                error_exit(
+                    Failure::Io,
+                    format!("Unable to write {}: {}", output_file.display(), e),
+                );
+            }
+
+            println!(
+                "Applied {} changes, wrote {}",
+                patch.assets().len(),
+                output_file.display()
+            );
+        }
 
         Commands::Diff {
             file_1,
             file_2,
             names_only,
             ignore_order,
+            recursive,
         } => {
-            println!("Diff feature coming soon.");
+            let mut differences: Vec<String> = vec![];
+            let mut unreadable = UnreadableBnls::default();
+
+            if recursive && file_1.is_dir() && file_2.is_dir() {
+                let files_1: BTreeMap<PathBuf, PathBuf> = find_bnl_files(&file_1)
+                    .into_iter()
+                    .map(|(found, relative)| (relative, found))
+                    .collect();
+                let files_2: BTreeMap<PathBuf, PathBuf> = find_bnl_files(&file_2)
+                    .into_iter()
+                    .map(|(found, relative)| (relative, found))
+                    .collect();
+
+                for (relative, path_1) in &files_1 {
+                    let Some(path_2) = files_2.get(relative) else {
+                        differences.push(format!(
+                            "{}: only in {}",
+                            relative.display(),
+                            file_1.display()
+                        ));
+                        continue;
+                    };
+
+                    let (Some(bnl_1), Some(bnl_2)) =
+                        (unreadable.read(path_1), unreadable.read(path_2))
+                    else {
+                        continue;
+                    };
+
+                    differences.extend(
+                        diff_bnl(&bnl_1, &bnl_2, names_only, ignore_order)
+                            .into_iter()
+                            .map(|difference| format!("{}: {}", relative.display(), difference)),
+                    );
+                }
+
+                differences.extend(
+                    files_2
+                        .keys()
+                        .filter(|relative| !files_1.contains_key(*relative))
+                        .map(|relative| {
+                            format!("{}: only in {}", relative.display(), file_2.display())
+                        }),
+                );
+            } else {
+                let (_, bnl_1) = read_bnl(&file_1);
+                let (_, bnl_2) = read_bnl(&file_2);
+
+                differences = diff_bnl(&bnl_1, &bnl_2, names_only, ignore_order);
+            }
+
+            unreadable.exit_if_any();
+
+            if differences.is_empty() {
+                println!("No differences found.");
+            } else {
+                differences
+                    .iter()
+                    .for_each(|difference| println!("{}", difference));
+
+                error_exit(
+                    Failure::Validation,
+                    format!("\n{} differences found.", differences.len()),
+                );
+            }
+        }
+    }
+}
+
+/// Extracts every asset of `bnl_file` into `<output_dir>/<file stem>_bnl`, as a directory per asset
+/// with the files of `layout`, or as files named after each asset if `flat` is set. Textures are
+/// converted to PNGs on `pool`.
+fn extract_bnl(
+    bnl_file: &Path,
+    bnl: &BNLFile,
+    output_dir: &Path,
+    layout: ExtractLayout,
+    flat: bool,
+    pool: &TranscodePool,
+) {
+    println!("Extracting BNL file {}", bnl_file.display());
+
+    let raw_assets = bnl.get_raw_assets();
+
+    let out_filename = format!(
+        "{}_bnl",
+        bnl_file
+            .file_stem()
+            .unwrap_or(OsStr::new("unknown"))
+            .display()
+    );
+
+    // ./out/common_bnl
+    let bnl_out_path = Path::new(&output_dir).join(out_filename);
+
+    let is_texture =
+        |raw_asset: &RawAsset| raw_asset.metadata().asset_type() == AssetType::ResTexture;
+
+    if flat {
+        create_extract_dir(&bnl_out_path);
+
+        // Textures are written without their image, which is read back from their PNG
+        for raw_asset in raw_assets {
+            write_asset_json(raw_asset, &bnl_out_path, !is_texture(raw_asset));
+        }
+    } else {
+        raw_assets.iter().for_each(|raw_asset| {
+            // ./out/common_bnl/aid_texture_xyz
+            let asset_path: PathBuf = bnl_out_path.join(raw_asset.name());
+
+            create_extract_dir(&asset_path);
+
+            std::fs::write(asset_path.join("metadata"), raw_asset.metadata_file_bytes())
+                .unwrap_or_else(|e| {
+                    report_error(
+                        Failure::Io,
+                        &format!(
+                            "Unable to write metadata for {}\nError: {}",
+                            &raw_asset.name(),
+                            e
+                        ),
+                    );
+                });
+
+            std::fs::write(asset_path.join("descriptor"), raw_asset.descriptor_bytes())
+                .unwrap_or_else(|e| {
+                    report_error(
+                        Failure::Io,
+                        &format!(
+                            "Unable to write descriptor for {}\nError: {}",
+                            &raw_asset.name(),
+                            e
+                        ),
+                    );
+                });
+
+            // Only textures have a converted format that can be read back
+            if layout != ExtractLayout::Converted || !is_texture(raw_asset) {
+                write_resource_files(raw_asset, &asset_path);
+            }
+
+            // Cutscene layouts aren't fully known, so write an annotated view next to the
+            // raw files. Extra files are ignored when the asset is read back.
+            if raw_asset.metadata().asset_type() == AssetType::ResCutscene {
+                match raw_asset.clone().to_asset::<Cutscene>() {
+                    Ok(cutscene) => {
+                        if let Err(e) = cutscene.asset().dump(asset_path.join("cutscene.txt")) {
+                            report_error(
+                                Failure::Io,
+                                &format!(
+                                    "Unable to write cutscene dump for {}\nError: {}",
+                                    raw_asset.name(),
+                                    e
+                                ),
+                            );
+                        }
+                    }
+                    Err(e) => report_error(
+                        Failure::Parse,
+                        &format!(
+                            "Unable to parse cutscene {}\nError: {}",
+                            raw_asset.name(),
+                            e
+                        ),
+                    ),
+                }
+            }
+        });
+    }
+
+    if !flat && layout == ExtractLayout::Raw {
+        return;
+    }
+
+    let png_path = |raw_asset: &RawAsset| match flat {
+        true => bnl_out_path.join(format!("{}.png", raw_asset.name())),
+        false => bnl_out_path.join(raw_asset.name()).join(TEXTURE_PNG_FILE),
+    };
+
+    // A texture which can't be converted is written raw instead, so that nothing is lost. A PNG
+    // left from an earlier extract would be read back in place of the raw data, so it is removed.
+    let fall_back = |raw_asset: &RawAsset| {
+        let _ = fs::remove_file(png_path(raw_asset));
+
+        if flat {
+            write_asset_json(raw_asset, &bnl_out_path, true);
+        } else if layout == ExtractLayout::Converted {
+            write_resource_files(raw_asset, &bnl_out_path.join(raw_asset.name()));
+        }
+    };
+
+    // Decoding is by far the slowest part of extracting, so every texture is converted at once
+    let textures: Vec<(&RawAsset, Texture)> = raw_assets
+        .iter()
+        .filter(|raw_asset| is_texture(raw_asset))
+        .filter_map(|raw_asset| match raw_asset.clone().to_asset::<Texture>() {
+            Ok(texture) => Some((raw_asset, texture.asset().clone())),
+            Err(e) => {
+                report_error(
+                    Failure::Parse,
+                    &format!("Unable to parse texture {}\nError: {}", raw_asset.name(), e),
+                );
+                fall_back(raw_asset);
+                None
+            }
+        })
+        .collect();
+
+    let jobs: Vec<(&Texture, PathBuf)> = textures
+        .iter()
+        .map(|(raw_asset, texture)| (texture, png_path(raw_asset)))
+        .collect();
+
+    for ((raw_asset, _), result) in textures
+        .iter()
+        .zip(pool.export_pngs(&jobs, TextureExportOptions::default()))
+    {
+        if let Err(e) = result {
+            report_error(
+                Failure::Io,
+                &format!("Unable to write PNG for {}\nError: {}", raw_asset.name(), e),
+            );
+            fall_back(raw_asset);
+        }
+    }
+}
+
+/// Creates a directory to extract into, exiting if a file is in the way
+fn create_extract_dir(path: &Path) {
+    if path.is_file() {
+        error_exit(
+            Failure::Io,
+            format!(
+                "Unable to write to {} (A file already exists by that name)",
+                path.display()
+            ),
+        );
+    }
+
+    if let Err(e) = fs::create_dir_all(path) {
+        error_exit(
+            Failure::Io,
+            format!(
+                "Unable to create directory {}.\nError: {}",
+                path.display(),
+                e
+            ),
+        );
+    }
+}
+
+/// Writes the resource chunks of `raw_asset` into `asset_path` as resource0, resource1, ...
+fn write_resource_files(raw_asset: &RawAsset, asset_path: &Path) {
+    if let Some(data_slices) = raw_asset.resource_chunks() {
+        data_slices.iter().enumerate().for_each(|(i, slice)| {
+            std::fs::write(asset_path.join(format!("resource{}", i)), slice).unwrap_or_else(|e| {
+                report_error(
+                    Failure::Io,
+                    &format!(
+                        "Unable to write resource {} for {}\nError: {}",
+                        i,
+                        raw_asset.name(),
+                        e
+                    ),
+                );
+            });
+        });
+    }
+}
+
+/// Writes `raw_asset` into `dir` as `<name>.json`, in the format read by
+/// [`RawAsset::from_flat_file`]
+fn write_asset_json(raw_asset: &RawAsset, dir: &Path, include_data: bool) {
+    let json_path = dir.join(format!("{}.json", raw_asset.name()));
+
+    let written = serde_json::to_vec_pretty(&AssetJson::from_raw_asset(raw_asset, include_data))
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&json_path, json).map_err(|e| e.to_string()));
+
+    if let Err(e) = written {
+        report_error(
+            Failure::Io,
+            &format!("Unable to write {}\nError: {}", json_path.display(), e),
+        );
+    }
+}
+
+fn list_bnl(
+    bnl: &BNLFile,
+    asset_type_filter: Option<&str>,
+    alphabetical_order: bool,
+    print_summary: bool,
+    group: bool,
+) {
+    let mut raw_assets = bnl
+        .get_raw_assets()
+        .iter()
+        .filter(|raw_asset| {
+            if let Some(type_filter) = &asset_type_filter {
+                raw_asset.metadata().asset_type.to_string() == *type_filter
+            } else {
+                true
+            }
+        })
+        .collect::<Vec<&RawAsset>>();
+
+    // Sort by asset type
+    raw_assets.sort_by_key(|raw| raw.metadata().asset_type);
+
+    if alphabetical_order {
+        // Since sort by key is stable, we can alphabetical sort after
+        raw_assets.sort_by_key(|raw| raw.metadata().asset_type.to_string());
+    }
+
+    if group {
+        // Groups are listed in the order their first asset appears. Names which don't follow the
+        // naming convention are a group of their own.
+        let mut groups: Vec<(String, Vec<&str>)> = vec![];
+
+        for raw_asset in &raw_assets {
+            let base = Aid::parse(raw_asset.name())
+                .map(|aid| aid.base_aid())
+                .unwrap_or_else(|_| raw_asset.name().to_string());
+
+            match groups
+                .iter_mut()
+                .find(|(group_base, _)| *group_base == base)
+            {
+                Some((_, names)) => names.push(raw_asset.name()),
+                None => groups.push((base, vec![raw_asset.name()])),
+            }
+        }
+
+        for (base, names) in groups {
+            println!("{}", base);
+
+            for name in names.iter().filter(|name| **name != base) {
+                println!("    {}", name);
+            }
+        }
+    } else {
+        raw_assets.iter().for_each(|raw_asset| {
+            println!("{}", raw_asset.name());
+        });
+    }
+
+    if print_summary {
+        println!("{} assets found.", raw_assets.len());
+
+        // Print the list of types found if theres no filter
+        if asset_type_filter.is_none() {
+            let types_found =
+                raw_assets
+                    .iter()
+                    .fold(HashSet::<AssetType>::new(), |mut acc, val| {
+                        acc.insert(val.metadata().asset_type);
+                        acc
+                    });
+
+            let mut types_str = types_found
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<String>>();
+
+            types_str.sort();
+
+            println!(
+                "{num_types} Asset types: {}",
+                types_str.join(" "),
+                num_types = types_str.len(),
+            );
         }
     }
 }
 
-fn error_exit() -> ! {
-    eprintln!("\nUnable to continue.");
+/// The sizes of the assets of one asset type, see [`BnlStats`]
+#[derive(Default)]
+struct TypeStats {
+    count: usize,
+    descriptor_bytes: usize,
+    resource_bytes: usize,
+}
+
+/// Sizes totalled over every file given to the stats command
+#[derive(Default)]
+struct BnlStats {
+    files: usize,
+    compressed_bytes: usize,
+    decompressed_bytes: usize,
+    descriptor_section_bytes: usize,
+    descriptor_bytes: usize,
+    /// The bytes which would be saved by storing each duplicated resource chunk once
+    duplicate_chunk_bytes: usize,
+    /// The bytes which would be saved by storing each shared descriptor once
+    shared_descriptor_bytes: usize,
+    by_type: BTreeMap<String, TypeStats>,
+    /// The total size of each asset, along with its name and file
+    assets: Vec<(usize, String, PathBuf)>,
+}
+
+impl BnlStats {
+    fn add(&mut self, path: &Path, compressed_size: usize, bnl: &BNLFile) {
+        self.files += 1;
+        self.compressed_bytes += compressed_size;
+        self.decompressed_bytes += bnl.decompressed_size();
+        self.descriptor_section_bytes += bnl.descriptor_section_size();
+
+        self.duplicate_chunk_bytes += bnl
+            .find_duplicate_chunks()
+            .iter()
+            .map(|duplicate| duplicate.wasted_bytes())
+            .sum::<usize>();
+        self.shared_descriptor_bytes += bnl
+            .find_shared_descriptors()
+            .iter()
+            .map(|shared| shared.size * shared.asset_names.len().saturating_sub(1))
+            .sum::<usize>();
+
+        for raw_asset in bnl.get_raw_assets() {
+            let descriptor_size = raw_asset.descriptor_bytes().len();
+            let resource_size = raw_asset
+                .resource_chunks()
+                .map(|chunks| chunks.iter().map(Vec::len).sum())
+                .unwrap_or(0);
+
+            self.descriptor_bytes += descriptor_size;
+
+            let type_stats = self
+                .by_type
+                .entry(raw_asset.metadata().asset_type().to_string())
+                .or_default();
+            type_stats.count += 1;
+            type_stats.descriptor_bytes += descriptor_size;
+            type_stats.resource_bytes += resource_size;
+
+            self.assets.push((
+                descriptor_size + resource_size,
+                raw_asset.name().to_string(),
+                path.to_path_buf(),
+            ));
+        }
+    }
+
+    fn print(&mut self, top: usize) {
+        let percent = |part: usize, whole: usize| match whole {
+            0 => 0.0,
+            whole => part as f64 * 100.0 / whole as f64,
+        };
+
+        println!("Files: {}", self.files);
+        println!("Compressed size: {} bytes", self.compressed_bytes);
+        println!(
+            "Decompressed size: {} bytes (compressed to {:.1}%)",
+            self.decompressed_bytes,
+            percent(self.compressed_bytes, self.decompressed_bytes)
+        );
+        println!(
+            "Descriptor section: {} bytes, {} bytes of descriptors ({:.1}%)",
+            self.descriptor_section_bytes,
+            self.descriptor_bytes,
+            percent(self.descriptor_bytes, self.descriptor_section_bytes)
+        );
+        println!(
+            "Duplicate resource chunks: {} bytes could be saved with --dedup",
+            self.duplicate_chunk_bytes
+        );
+        println!(
+            "Shared descriptors: {} bytes could be saved with --dedup-descriptors",
+            self.shared_descriptor_bytes
+        );
+
+        println!(
+            "\n{:<24} {:>8} {:>14} {:>14}",
+            "Type", "Count", "Descriptors", "Resources"
+        );
+        for (asset_type, stats) in &self.by_type {
+            println!(
+                "{:<24} {:>8} {:>14} {:>14}",
+                asset_type, stats.count, stats.descriptor_bytes, stats.resource_bytes
+            );
+        }
+
+        if top == 0 || self.assets.is_empty() {
+            return;
+        }
+
+        // Biggest first, then by name so that ties are listed consistently
+        self.assets
+            .sort_by(|(size_1, name_1, _), (size_2, name_2, _)| {
+                size_2.cmp(size_1).then(name_1.cmp(name_2))
+            });
+
+        println!("\nBiggest assets:");
+        for (size, name, path) in self.assets.iter().take(top) {
+            match self.files {
+                1 => println!("{:>14} {}", size, name),
+                _ => println!("{:>14} {} ({})", size, name, path.display()),
+            }
+        }
+    }
+}
+
+/// Compares two BNL files by asset, with `names_only` skipping the contents of assets present in
+/// both and `ignore_order` skipping the order they appear in
+fn diff_bnl(bnl_1: &BNLFile, bnl_2: &BNLFile, names_only: bool, ignore_order: bool) -> Vec<String> {
+    let mut differences: Vec<String> = Manifest::from_bnl(bnl_1)
+        .verify(&Manifest::from_bnl(bnl_2))
+        .into_iter()
+        .filter(|mismatch| {
+            !names_only
+                || matches!(
+                    mismatch,
+                    ManifestMismatch::Missing(_)
+                        | ManifestMismatch::Unexpected(_)
+                        | ManifestMismatch::Duplicate(_)
+                )
+        })
+        .map(|mismatch| mismatch.to_string())
+        .collect();
+
+    if !ignore_order {
+        let names = |bnl: &BNLFile, other: &BNLFile| -> Vec<String> {
+            bnl.get_raw_assets()
+                .iter()
+                .map(|raw_asset| raw_asset.name().to_string())
+                .filter(|name| other.get_raw_asset(name).is_some())
+                .collect()
+        };
+
+        if names(bnl_1, bnl_2) != names(bnl_2, bnl_1) {
+            differences.push("the shared assets are in a different order".to_string());
+        }
+    }
+
+    differences
+}
+
+/// Converts a glob of asset names into an anchored regular expression
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    pattern.push('$');
+    pattern
+}
+
+/// Finds every .bnl file under `dir`, sorted by path, along with its path relative to `dir`
+fn find_bnl_files(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry
+                    .path()
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("bnl"))
+        })
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(dir).ok()?.to_path_buf();
+            Some((entry.into_path(), relative))
+        })
+        .collect()
+}
+
+/// Describes each asset referred to by `descriptor` which is missing from `bnl`, or has the wrong
+/// type. Scripts can refer to assets in other files, so these are only warnings.
+fn reference_problems(bnl: &BNLFile, descriptor: &ScriptDescriptor) -> Vec<String> {
+    descriptor
+        .referenced_assets()
+        .into_iter()
+        .filter_map(|reference| {
+            let Some(raw_asset) = bnl.get_raw_asset(&reference.asset_name) else {
+                return Some(format!("{} is not in this file", reference.asset_name));
+            };
+
+            let expected = reference.kind.asset_type()?;
+            let actual = raw_asset.metadata().asset_type();
+
+            (actual != expected).then(|| {
+                format!(
+                    "{} is a {}, but is used as a {}",
+                    reference.asset_name, actual, expected
+                )
+            })
+        })
+        .collect()
+}
+
+/// Reads and parses a BNL file, exiting if either fails. The original bytes are returned too, for
+/// writing patches in place.
+fn read_bnl(bnl_path: &Path) -> (Vec<u8>, BNLFile) {
+    try_read_bnl(bnl_path).unwrap_or_else(|(failure, message)| error_exit(failure, message))
+}
+
+/// Reads and parses a BNL file, returning the failure and its message if either fails
+fn try_read_bnl(bnl_path: &Path) -> Result<(Vec<u8>, BNLFile), (Failure, String)> {
+    let bytes: Vec<u8> = std::fs::read(bnl_path).map_err(|e| {
+        (
+            Failure::Io,
+            format!("Unable to open file {}. Error: {}", bnl_path.display(), e),
+        )
+    })?;
+
+    match BNLFile::from_bytes(&bytes) {
+        Ok(bnl) => Ok((bytes, bnl)),
+        Err(e) => Err((
+            Failure::Parse,
+            format!("Unable to process BNL file {}: {:?}", bnl_path.display(), e),
+        )),
+    }
+}
+
+/// The BNL files which couldn't be read during a recursive walk, which carries on past them
+#[derive(Default)]
+struct UnreadableBnls {
+    count: usize,
+    /// The failure of the first unreadable file, which the command exits with
+    failure: Option<Failure>,
+}
+
+impl UnreadableBnls {
+    /// Reads `bnl_path`, reporting and counting it if it can't be read
+    fn read(&mut self, bnl_path: &Path) -> Option<BNLFile> {
+        match try_read_bnl(bnl_path) {
+            Ok((_, bnl)) => Some(bnl),
+            Err((failure, message)) => {
+                report_error(failure, &message);
+
+                self.count += 1;
+                self.failure.get_or_insert(failure);
+
+                None
+            }
+        }
+    }
+
+    /// Exits with the failure of the first unreadable file, if there were any
+    fn exit_if_any(&self) {
+        if let Some(failure) = self.failure {
+            error_exit(
+                failure,
+                format!("{} BNL files could not be read", self.count),
+            );
+        }
+    }
+}
+
+/// The JSON files in `dir`, sorted by name
+fn flat_asset_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("json")))
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+/// The section alignments of a BNL file being written, see [`BNLWriteOptions`]
+#[derive(Args, Debug)]
+struct AlignmentArgs {
+    /// Use the alignments of an existing BNL file, such as the retail file being replaced. The
+    /// other alignment options override single sections.
+    #[arg(long, value_name = "BNL_FILE")]
+    align_like: Option<PathBuf>,
+
+    /// The alignment of the buffer views section and each DataView list in it, in bytes
+    #[arg(long, value_name = "BYTES", value_parser = parse_alignment)]
+    align_buffer_views: Option<usize>,
+
+    /// The alignment of the buffer section and each resource chunk in it, in bytes
+    #[arg(long, value_name = "BYTES", value_parser = parse_alignment)]
+    align_resources: Option<usize>,
+
+    /// The alignment of the descriptor section and each descriptor in it, in bytes
+    #[arg(long, value_name = "BYTES", value_parser = parse_alignment)]
+    align_descriptors: Option<usize>,
+}
+
+impl AlignmentArgs {
+    /// `options` with the alignments given on the command line, exiting if the file given by
+    /// --align-like can't be read
+    fn apply(&self, mut options: BNLWriteOptions) -> BNLWriteOptions {
+        if let Some(path) = &self.align_like {
+            let detected = fs::read(path)
+                .map_err(|e| (Failure::Io, e.to_string()))
+                .and_then(|bytes| {
+                    BNLWriteOptions::detect(&bytes).map_err(|e| (Failure::Parse, e.to_string()))
+                });
+
+            match detected {
+                Ok(detected) => {
+                    options.buffer_view_alignment = detected.buffer_view_alignment;
+                    options.resource_alignment = detected.resource_alignment;
+                    options.descriptor_alignment = detected.descriptor_alignment;
+                }
+                Err((failure, e)) => {
+                    error_exit(
+                        failure,
+                        format!("Unable to read alignments from {}: {}", path.display(), e),
+                    );
+                }
+            }
+        }
+
+        options.buffer_view_alignment = self
+            .align_buffer_views
+            .unwrap_or(options.buffer_view_alignment);
+        options.resource_alignment = self.align_resources.unwrap_or(options.resource_alignment);
+        options.descriptor_alignment = self
+            .align_descriptors
+            .unwrap_or(options.descriptor_alignment);
+
+        options
+    }
+}
+
+/// Parses an alignment, which must be a power of two
+fn parse_alignment(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(alignment) if alignment.is_power_of_two() => Ok(alignment),
+        Ok(alignment) => Err(format!("{} is not a power of two", alignment)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Reads the asset order from a manifest. Manifests without a recorded order give their assets
+/// in name order.
+fn read_asset_order(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let manifest: Manifest = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+
+    Ok(match manifest.order.is_empty() {
+        true => manifest.assets.into_keys().collect(),
+        false => manifest.order,
+    })
+}
+
+/// Reads the control code names given to the loctext commands, or none if no file was given
+fn read_control_codes(path: Option<&Path>) -> ControlCodes {
+    let Some(path) = path else {
+        return ControlCodes::default();
+    };
+
+    let text = fs::read_to_string(path).unwrap_or_else(|e| {
+        error_exit(
+            Failure::Io,
+            format!("Unable to read {}. Error: {}", path.display(), e),
+        );
+    });
+
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        error_exit(
+            Failure::Parse,
+            format!(
+                "Unable to read control codes from {}: {}",
+                path.display(),
+                e
+            ),
+        );
+    })
+}
 
-    std::process::exit(1);
+fn asset_failure(e: &AssetError) -> Failure {
+    match e {
+        AssetError::NotFound | AssetError::TypeMismatch => Failure::NotFound,
+        // Parse errors are usually wrapped with the asset they came from
+        e if e.parse_error().is_some() => Failure::Parse,
+        _ => Failure::Validation,
+    }
+}
+
+fn font_failure(e: &FontError) -> Failure {
+    match e {
+        FontError::Io(_) => Failure::Io,
+        FontError::InvalidFnt(_) => Failure::Parse,
+        FontError::UnsupportedPage(_) | FontError::InvalidVariant(_) | FontError::NoGlyphs => {
+            Failure::Validation
+        }
+        FontError::Texture(_) => Failure::Other,
+    }
+}
+
+/// Reports `message` in the format chosen by `--error-format` and exits with the code of `failure`
+fn error_exit(failure: Failure, message: String) -> ! {
+    report_error(failure, &message);
+
+    if ERROR_FORMAT.get().copied().unwrap_or(ErrorFormat::Text) == ErrorFormat::Text {
+        eprintln!("\nUnable to continue.");
+    }
+
+    std::process::exit(failure as i32);
+}
+
+/// Reports `message` in the format chosen by `--error-format` without exiting, for failures which
+/// don't stop the command
+fn report_error(failure: Failure, message: &str) {
+    match ERROR_FORMAT.get().copied().unwrap_or(ErrorFormat::Text) {
+        ErrorFormat::Text => eprintln!("{}", message),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({
+                "error": failure.name(),
+                "code": failure as i32,
+                "message": message.trim(),
+            })
+        ),
+    }
+}
+
+/// Reports a warning in the format chosen by `--error-format`
+fn report_warning(message: &str) {
+    match ERROR_FORMAT.get().copied().unwrap_or(ErrorFormat::Text) {
+        ErrorFormat::Text => eprintln!("Warning: {}", message),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({
+                "warning": message.trim(),
+            })
+        ),
+    }
+}
+
+fn print_model_stats(stats: &ModelStats) {
+    println!("\nModel stats:");
+    println!("  Vertices: {}", stats.vertex_count);
+    println!("  Triangles: {}", stats.triangle_count());
+
+    for (topology, count) in &stats.triangles_per_topology {
+        println!("    {:?}: {}", topology, count);
+    }
+
+    println!(
+        "  Draw calls: {} ({} invalid)",
+        stats.draw_call_count, stats.invalid_draw_call_count
+    );
+    println!("  Texture memory: {:#x} bytes", stats.texture_memory);
+    println!("  Bones: {}", stats.bone_count);
+
+    if let Some(bounds) = &stats.bounding_box {
+        println!("  Bounds: {:?} to {:?}", bounds.min, bounds.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bnl::AssetMetadata;
+
+    #[test]
+    fn corrupt_asset_is_a_parse_failure() -> Result<(), String> {
+        let mut bnl = BNLFile::default();
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_texture_corrupt", AssetType::ResTexture, 0, 0),
+            vec![0; 2],
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let error = bnl
+            .get_asset::<Texture>("aid_texture_corrupt")
+            .err()
+            .ok_or("the corrupt texture shouldn't parse")?;
+
+        assert!(matches!(error, AssetError::InAsset { .. }));
+        assert_eq!(asset_failure(&error) as i32, 4);
+
+        let missing = bnl
+            .get_asset::<Texture>("aid_texture_missing")
+            .err()
+            .ok_or("the missing texture shouldn't be found")?;
+        assert_eq!(asset_failure(&missing) as i32, 5);
+
+        Ok(())
+    }
 }
@@ -15,9 +15,17 @@ fn main() {
 
     let loctext = LoctextResource::from_hashmap(json).expect("Failed to read LoctextResource.");
 
-    std::fs::write(
-        out_path.to_str().unwrap(),
-        loctext.dump().expect("Failed to dump loctext"),
-    )
-    .expect("Failed to write new loctext resource.");
+    let (bytes, collisions) = loctext
+        .dump_with_collisions()
+        .expect("Failed to dump loctext");
+
+    for collision in collisions {
+        println!(
+            "Key {} resolves to duplicate hash: 0x{:04x}. Using substituted hash 0x{:04x} instead.",
+            collision.key, collision.original_hash, collision.substituted_hash
+        );
+    }
+
+    std::fs::write(out_path.to_str().unwrap(), bytes)
+        .expect("Failed to write new loctext resource.");
 }
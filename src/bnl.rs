@@ -1,28 +1,355 @@
+mod cache;
+mod coverage;
+mod layout;
+mod patch;
+
 use std::{
-    fs::{self, File},
-    io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::HashMap,
+    io::{BufRead, Cursor, Read, Write},
     ops::Range,
-    path::{self, Path, PathBuf},
+    sync::Arc,
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
 };
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use miniz_oxide::inflate::TINFLStatus;
+use sha2::{Digest, Sha256};
+
+use cache::AssetCache;
+pub use coverage::{BnlSection, SectionCoverage, SharedRange};
+pub use layout::{BnlLayout, SectionLayout};
+pub use patch::{
+    AssetPatch, BnlPatch, ByteEdit, DataPatch, HeaderFields, MAX_PATCH_SIZE, ModifiedAsset,
+    PatchError,
+};
 
 use crate::{
-    VirtualResource,
+    BnlVariant, VirtualResource,
     asset::{
-        ASSET_DESCRIPTION_SIZE, Asset, AssetDescription, AssetDescriptor, AssetError, AssetLike,
-        AssetName, AssetParseError, AssetType, DataViewList,
+        ASSET_DESCRIPTION_SIZE, AnyAsset, Asset, AssetDescription, AssetDescriptor, AssetError,
+        AssetLike, AssetName, AssetParseError, AssetRefLike, AssetStage, AssetType, DataViewList,
+        anim::AnimDescriptor,
+        check_descriptor,
+        cutscene::CutsceneDescriptor,
+        fields::{DescriptorField, DescriptorFields},
+        model::ModelDescriptor,
+        texture::TextureDescriptor,
     },
+    utils::zlib::{self, ZlibWriter},
+};
+#[cfg(feature = "fs")]
+use crate::{
+    asset::texture::{RGBAImage, Texture},
+    json::AssetJson,
 };
 
+/// The zlib level used when compressing a BNL. Kept fixed so that identical assets always produce
+/// identical files.
+pub const ZLIB_COMPRESSION_LEVEL: u8 = 1;
+
+/// The converted image of a texture in an extracted asset directory, which
+/// [`RawAsset::from_dir`] reads when the directory has no resource files
+#[cfg(feature = "fs")]
+pub const TEXTURE_PNG_FILE: &str = "texture.png";
+
+/// The order assets are laid out in when a [`BNLFile`] is serialised
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssetOrder {
+    /// Sorted by asset name, matching the files shipped with the game
+    #[default]
+    NameSorted,
+    /// The order the assets were read or appended in, or the order set through
+    /// [`BNLFile::assets_mut`] or [`BNLFile::sort_assets_by`]
+    Original,
+    /// The order of the file the assets were read from, with assets that weren't read from a file
+    /// placed after them in the order they were added
+    FileOrder,
+}
+
+impl AssetOrder {
+    /// Sorts `assets` into this order. Both sorts are stable, so assets sharing a key keep their
+    /// relative order.
+    fn sort<A: Borrow<RawAsset>>(&self, assets: &mut [A]) {
+        match self {
+            AssetOrder::NameSorted => {
+                assets.sort_by(|a, b| a.borrow().name().cmp(b.borrow().name()))
+            }
+            AssetOrder::FileOrder => {
+                assets.sort_by_key(|asset| asset.borrow().original_index.unwrap_or(usize::MAX))
+            }
+            AssetOrder::Original => (),
+        }
+    }
+}
+
+/// What happens when an edit changes resource data which is shared with other assets, see
+/// [`BNLFile::get_overlaps`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditPolicy {
+    /// The edited asset gets its own copy of the data, and the other assets keep the original
+    #[default]
+    CopyOnWrite,
+    /// The edit fails with [`AssetError::SharedData`]
+    Error,
+}
+
+/// How much [`BNLFile::append_raw_asset`] checks an asset before adding it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AppendValidation {
+    /// Every asset is accepted
+    None,
+    /// Assets must not share a name with an existing asset
+    #[default]
+    UniqueNames,
+    /// Names must be unique, and the descriptor must be readable as the [`AssetType`] in the
+    /// metadata of the asset
+    Strict,
+}
+
+/// The largest alignment [`BNLWriteOptions::detect`] will report
+pub const MAX_DETECTED_ALIGNMENT: usize = 2048;
+
+/**
+Options controlling how [`BNLFile::to_bytes`] lays out the file.
+
+Alignments are in bytes, relative to the start of the file, and apply to the start of the section
+as well as to each item inside of it. An alignment of 1 packs everything back-to-back.
+
+Files read with [`BNLFile::from_bytes`] start with the alignments detected from the file, so that
+they are written back with the same layout. The retail alignment hasn't been confirmed, so files
+built from scratch default to the packed layout; use [`BNLWriteOptions::detect`] to match a retail
+file.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BNLWriteOptions {
+    /// Store identical resource chunks once, with every DataView pointing at the same copy
+    pub dedup_chunks: bool,
+    /// Store byte-identical descriptors once, with every asset description pointing at the same
+    /// copy
+    pub dedup_descriptors: bool,
+    /// The alignment of the buffer views section and each DataView list in it
+    pub buffer_view_alignment: usize,
+    /// The alignment of the buffer section and each resource chunk in it
+    pub resource_alignment: usize,
+    /// The alignment of the descriptor section and each descriptor in it
+    pub descriptor_alignment: usize,
+}
+
+impl Default for BNLWriteOptions {
+    fn default() -> Self {
+        Self {
+            dedup_chunks: false,
+            dedup_descriptors: false,
+            buffer_view_alignment: 1,
+            resource_alignment: 1,
+            descriptor_alignment: 1,
+        }
+    }
+}
+
+impl BNLWriteOptions {
+    /**
+    Finds the alignments used by an existing BNL file, as the largest power of two (up to
+    [`MAX_DETECTED_ALIGNMENT`]) which every offset of that kind is a multiple of.
+
+    # Errors
+    See [`BNLFile::from_bytes`]
+    */
+    pub fn detect(bnl_bytes: &[u8]) -> Result<Self, BNLError> {
+        Self::detect_decompressed(&DecompressedBnl::from_bytes(bnl_bytes)?)
+    }
+
+    /// See [`BNLWriteOptions::detect`]
+    fn detect_decompressed(decompressed: &DecompressedBnl) -> Result<Self, BNLError> {
+        let header = &decompressed.header;
+        let variant = decompressed.variant;
+
+        let mut buffer_view_offsets = vec![header.buffer_views_loc.offset as usize];
+        let mut resource_offsets = vec![header.buffer_loc.offset as usize];
+        let mut descriptor_offsets = vec![header.descriptor_loc.offset as usize];
+
+        let buffer_views_bytes =
+            decompressed.section_bytes(&header.buffer_views_loc, "Buffer views")?;
+        let asset_desc_bytes =
+            decompressed.section_bytes(&header.asset_desc_loc, "Asset description")?;
+
+        for description_bytes in asset_desc_bytes.chunks_exact(ASSET_DESCRIPTION_SIZE) {
+            let description = AssetDescription::from_bytes_as(description_bytes, variant)?;
+
+            descriptor_offsets
+                .push(header.descriptor_loc.offset as usize + description.descriptor_ptr as usize);
+
+            if description.resource_size == 0 {
+                continue;
+            }
+
+            buffer_view_offsets.push(
+                header.buffer_views_loc.offset as usize + description.dataview_list_ptr as usize,
+            );
+
+            if let Some(dvl) = buffer_views_bytes
+                .get(description.dataview_list_ptr as usize..)
+                .and_then(|bytes| DataViewList::from_bytes_as(bytes, variant).ok())
+            {
+                resource_offsets.extend(
+                    dvl.views()
+                        .iter()
+                        .filter(|view| view.size > 0)
+                        .map(|view| header.buffer_loc.offset as usize + view.offset as usize),
+                );
+            }
+        }
+
+        let alignment = |offsets: &[usize]| {
+            let mut alignment = MAX_DETECTED_ALIGNMENT;
+
+            while alignment > 1
+                && offsets
+                    .iter()
+                    .any(|offset| !offset.is_multiple_of(alignment))
+            {
+                alignment /= 2;
+            }
+
+            alignment
+        };
+
+        Ok(Self {
+            dedup_chunks: false,
+            dedup_descriptors: false,
+            buffer_view_alignment: alignment(&buffer_view_offsets),
+            resource_alignment: alignment(&resource_offsets),
+            descriptor_alignment: alignment(&descriptor_offsets),
+        })
+    }
+}
+
+/// Pads `bytes` with zeroes until its length is a multiple of `alignment`
+fn pad_to_alignment(bytes: &mut Vec<u8>, alignment: usize) {
+    bytes.resize(bytes.len().next_multiple_of(alignment.max(1)), 0);
+}
+
+/// The position of a resource chunk within a [`BNLFile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkLocation {
+    pub asset_name: String,
+    pub chunk_index: usize,
+}
+
+/// A resource chunk which is stored more than once, see [`BNLFile::find_duplicate_chunks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateChunks {
+    /// The size of the chunk in bytes
+    pub size: usize,
+    /// Every copy of the chunk, in the order they appear
+    pub locations: Vec<ChunkLocation>,
+}
+
+impl DuplicateChunks {
+    /// The number of bytes which would be saved by only storing the chunk once
+    pub fn wasted_bytes(&self) -> usize {
+        self.size * self.locations.len().saturating_sub(1)
+    }
+}
+
+/// A descriptor which more than one asset has identical bytes for, see
+/// [`BNLFile::find_shared_descriptors`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedDescriptor {
+    /// The size of the descriptor in bytes
+    pub size: usize,
+    /// Every asset with this descriptor, in the order they appear
+    pub asset_names: Vec<String>,
+}
+
+/// Something in a [`BNLFile`] which is valid but worth knowing about, see [`BNLFile::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// Assets have byte-identical descriptors, which
+    /// [`BNLWriteOptions::dedup_descriptors`] would store once
+    SharedDescriptor(SharedDescriptor),
+    /// A resource chunk is stored more than once, which [`BNLWriteOptions::dedup_chunks`] would
+    /// store once
+    DuplicateChunks(DuplicateChunks),
+    /// Two assets share resource data in the file they were read from
+    Overlap(AssetOverlap),
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::SharedDescriptor(shared) => write!(
+                f,
+                "Descriptor of {:#x} bytes is shared by {}",
+                shared.size,
+                shared.asset_names.join(", ")
+            ),
+            ValidationIssue::DuplicateChunks(duplicate) => write!(
+                f,
+                "Resource chunk of {:#x} bytes is stored {} times ({})",
+                duplicate.size,
+                duplicate.locations.len(),
+                duplicate
+                    .locations
+                    .iter()
+                    .map(|location| format!("{}[{}]", location.asset_name, location.chunk_index))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ValidationIssue::Overlap(overlap) => write!(
+                f,
+                "{} and {} share resource data",
+                overlap.first, overlap.second
+            ),
+        }
+    }
+}
+
+/// Two assets whose resource data share bytes, see [`BNLFile::get_overlaps`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetOverlap {
+    pub first: String,
+    pub second: String,
+    /// The shared ranges, relative to the start of the buffer section
+    pub ranges: Vec<Range<usize>>,
+}
+
 #[derive(Debug, Default)]
 pub struct BNLFile {
     header: BNLHeader,
+    variant: BnlVariant,
+    asset_order: AssetOrder,
+    write_options: BNLWriteOptions,
+    edit_policy: EditPolicy,
+    append_validation: AppendValidation,
     assets: Vec<RawAsset>,
+    /// Names of assets replaced through [`BNLFile::patch_asset`] since the last write
+    patched_assets: Vec<String>,
+    /// Whether nothing but [`BNLFile::patch_asset`] has changed the file since it was read or last
+    /// written by [`BNLFile::write_patched`], so that it can still be written in place
+    unchanged_since_read: bool,
+    /// Assets parsed by [`BNLFile::get_asset_cached`]
+    asset_cache: AssetCache,
 }
 
-#[derive(Debug, Default)]
+/// The sections of a [`BNLFile`] as they will be written, see [`BNLFile::write_to`]
+struct LaidOutSections<'a> {
+    header: BNLHeader,
+    asset_desc_section: Vec<u8>,
+    buffer_views_section: Vec<u8>,
+    /// The resource chunks in the order they will be written, with their offsets in the buffer
+    /// section. The chunks themselves aren't copied until they are compressed.
+    buffer_chunks: Vec<(usize, &'a [u8])>,
+    descriptors_section: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct BNLHeader {
     pub(crate) file_count: u16,
     pub(crate) flags: u8,
@@ -34,23 +361,43 @@ pub struct BNLHeader {
     pub(crate) descriptor_loc: DataView,
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
 pub struct DataView {
     pub(crate) offset: u32,
     pub(crate) size: u32,
 }
 
 impl DataView {
+    pub fn new(offset: u32, size: u32) -> Self {
+        DataView { offset, size }
+    }
+
+    /// The offset of the view from the start of the buffer section
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
     pub fn from_reader<R: Read>(reader: &mut R) -> Result<DataView, std::io::Error> {
-        let offset = reader.read_u32::<LittleEndian>()?;
-        let size = reader.read_u32::<LittleEndian>()?;
+        Self::from_reader_as(reader, BnlVariant::default())
+    }
+
+    pub fn from_reader_as<R: Read>(
+        reader: &mut R,
+        variant: BnlVariant,
+    ) -> Result<DataView, std::io::Error> {
+        let offset = variant.read_u32(reader)?;
+        let size = variant.read_u32(reader)?;
 
         Ok(DataView { offset, size })
     }
 
     pub fn as_range<T: From<u32>>(&self) -> Range<T> {
         let start: T = self.offset.into();
-        let end: T = (self.offset + self.size).into();
+        let end: T = self.offset.saturating_add(self.size).into();
 
         start..end
     }
@@ -74,42 +421,104 @@ impl DataView {
 }
 
 impl BNLHeader {
-    pub fn to_bytes(&self) -> [u8; 40] {
-        let mut bytes = [0x00; 40];
+    /// Reads the uncompressed header from the start of a BNL file laid out as `variant`
+    pub fn from_reader<R: Read>(
+        reader: &mut R,
+        variant: BnlVariant,
+    ) -> Result<BNLHeader, std::io::Error> {
+        let mut header = BNLHeader {
+            file_count: variant.read_u16(reader)?,
+            flags: reader.read_u8()?,
+            ..Default::default()
+        };
+
+        reader.read_exact(&mut header.unknown_2)?;
+
+        header.asset_desc_loc = DataView::from_reader_as(reader, variant)?;
+        header.buffer_views_loc = DataView::from_reader_as(reader, variant)?;
+        header.buffer_loc = DataView::from_reader_as(reader, variant)?;
+        header.descriptor_loc = DataView::from_reader_as(reader, variant)?;
+
+        Ok(header)
+    }
+
+    pub fn to_bytes(&self, variant: BnlVariant) -> Vec<u8> {
+        let mut bytes = vec![0x00; variant.header_size()];
 
         let mut cur = Cursor::new(&mut bytes[..]);
 
-        cur.write_u16::<LittleEndian>(self.file_count).unwrap();
+        variant.write_u16(&mut cur, self.file_count).unwrap();
         cur.write_u8(self.flags).unwrap();
 
         self.unknown_2.iter().for_each(|val| {
             cur.write_u8(*val).unwrap();
         });
 
-        cur.write_u32::<LittleEndian>(self.asset_desc_loc.offset)
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.asset_desc_loc.size)
-            .unwrap();
+        [
+            self.asset_desc_loc,
+            self.buffer_views_loc,
+            self.buffer_loc,
+            self.descriptor_loc,
+        ]
+        .iter()
+        .for_each(|loc| {
+            variant.write_u32(&mut cur, loc.offset).unwrap();
+            variant.write_u32(&mut cur, loc.size).unwrap();
+        });
+
+        bytes
+    }
+}
+
+/**
+One of the unknown words of an [`AssetMetadata`], treated as a set of bits until its meaning is
+known. Single bits can be read and changed through it without disturbing the others.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AssetFlags(u32);
 
-        cur.write_u32::<LittleEndian>(self.buffer_views_loc.offset)
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.buffer_views_loc.size)
-            .unwrap();
+impl AssetFlags {
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
 
-        cur.write_u32::<LittleEndian>(self.buffer_loc.offset)
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.buffer_loc.size).unwrap();
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
 
-        cur.write_u32::<LittleEndian>(self.descriptor_loc.offset)
-            .unwrap();
-        cur.write_u32::<LittleEndian>(self.descriptor_loc.size)
-            .unwrap();
+    /// Whether `bit` (0 being the least significant) is set. Bits past 31 are never set.
+    pub fn is_set(self, bit: u32) -> bool {
+        bit < u32::BITS && self.0 & (1 << bit) != 0
+    }
 
-        bytes
+    /// Sets or clears `bit`, ignoring bits past 31
+    pub fn set(&mut self, bit: u32, value: bool) {
+        if bit >= u32::BITS {
+            return;
+        }
+
+        if value {
+            self.0 |= 1 << bit;
+        } else {
+            self.0 &= !(1 << bit);
+        }
+    }
+
+    /// The indices of the set bits, from least to most significant
+    pub fn set_bits(self) -> impl Iterator<Item = u32> {
+        (0..u32::BITS).filter(move |&bit| self.is_set(bit))
     }
 }
 
-#[derive(Debug, Clone)]
+/**
+The name, type and flags of an asset, as stored in its [`AssetDescription`].
+
+The meaning of `unk_1` and `unk_2` is still unknown. They are kept verbatim through every read and
+write (including the `metadata` file of [`RawAsset::to_dir`]), so that repacked files keep every
+bit of the original. [`AssetMetadata::flags_1`] and [`AssetMetadata::flags_2`] give typed access
+to them as [`AssetFlags`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AssetMetadata {
     pub name: AssetName,
     pub asset_type: AssetType,
@@ -138,15 +547,11 @@ impl From<AssetMetadata> for AssetDescription {
 }
 
 impl AssetMetadata {
+    /// Creates new metadata for an asset. Names longer than [`crate::asset::MAX_ASSET_NAME_LENGTH`]
+    /// are truncated.
     pub fn new(name: &str, asset_type: AssetType, unk_1: u32, unk_2: u32) -> Self {
-        let mut name_bytes: AssetName = [0x00; 128];
-
-        let bytes: Vec<u8> = name.bytes().take(128).collect();
-
-        name_bytes[0..bytes.len()].copy_from_slice(&bytes);
-
         Self {
-            name: name_bytes,
+            name: AssetName::from_str_truncated(name),
             asset_type,
             unk_1,
             unk_2,
@@ -154,21 +559,51 @@ impl AssetMetadata {
     }
 
     pub fn name(&self) -> &str {
-        std::str::from_utf8(&self.name)
-            .unwrap_or("")
-            .split('\0')
-            .next()
-            .unwrap_or("")
+        self.name.as_str()
     }
 
     pub fn asset_type(&self) -> AssetType {
         self.asset_type
     }
 
+    pub fn set_asset_type(&mut self, asset_type: AssetType) {
+        self.asset_type = asset_type;
+    }
+
     pub fn unk_1(&self) -> u32 {
         self.unk_1
     }
 
+    pub fn set_unk_1(&mut self, unk_1: u32) {
+        self.unk_1 = unk_1;
+    }
+
+    pub fn unk_2(&self) -> u32 {
+        self.unk_2
+    }
+
+    pub fn set_unk_2(&mut self, unk_2: u32) {
+        self.unk_2 = unk_2;
+    }
+
+    /// `unk_1` as [`AssetFlags`]
+    pub fn flags_1(&self) -> AssetFlags {
+        AssetFlags::from_bits(self.unk_1)
+    }
+
+    pub fn set_flags_1(&mut self, flags: AssetFlags) {
+        self.unk_1 = flags.bits();
+    }
+
+    /// `unk_2` as [`AssetFlags`]
+    pub fn flags_2(&self) -> AssetFlags {
+        AssetFlags::from_bits(self.unk_2)
+    }
+
+    pub fn set_flags_2(&mut self, flags: AssetFlags) {
+        self.unk_2 = flags.bits();
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, AssetParseError> {
         if bytes.len() < size_of::<AssetMetadata>() {
             return Err(AssetParseError::InputTooSmall);
@@ -184,8 +619,9 @@ impl AssetMetadata {
 
         let mut cur = Cursor::new(bytes);
 
-        let mut name: AssetName = [0u8; 128];
-        cur.read_exact(&mut name)?;
+        let mut name_bytes = [0u8; size_of::<AssetName>()];
+        cur.read_exact(&mut name_bytes)?;
+        let name = AssetName::from(name_bytes);
 
         let asset_type_raw = cur.read_u32::<LittleEndian>()?;
         let asset_type: AssetType = asset_type_raw.try_into().map_err(|_| {
@@ -209,7 +645,7 @@ impl AssetMetadata {
         */
 
         let mut v = vec![0u8; 0x80];
-        v[0..0x80].copy_from_slice(&self.name);
+        v[0..0x80].copy_from_slice(self.name.as_bytes());
 
         v.write_u32::<LittleEndian>(self.asset_type.into())
             .expect("Failed to write to buffer");
@@ -222,11 +658,28 @@ impl AssetMetadata {
     }
 }
 
+/// The SHA-256 of `bytes` as lowercase hex
+fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[derive(Debug, Clone)]
 pub struct RawAsset {
     metadata: AssetMetadata,
     descriptor_bytes: Vec<u8>,
     resource_chunks: Option<Vec<Vec<u8>>>,
+    /// The position of the asset in the file it was read from
+    original_index: Option<usize>,
+    /// The buffer views the resource chunks were read from. Replacing the resource data clears
+    /// these, as it no longer comes from the file.
+    data_views: Option<DataViewList>,
+    /// The chunk count of the asset description the asset was read from, and the number of
+    /// resource chunks it had then
+    read_chunk_count: Option<(u32, usize)>,
 }
 
 impl RawAsset {
@@ -239,10 +692,14 @@ impl RawAsset {
             metadata,
             descriptor_bytes,
             resource_chunks,
+            original_index: None,
+            data_views: None,
+            read_chunk_count: None,
         }
     }
 
-    pub fn from_dir<P: AsRef<path::Path>>(path: P) -> Result<Self, AssetParseError> {
+    #[cfg(feature = "fs")]
+    pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, AssetParseError> {
         let path_ref = path.as_ref();
 
         let contents: Vec<PathBuf> = fs::read_dir(path_ref)?
@@ -272,13 +729,22 @@ impl RawAsset {
             })
             .ok_or(AssetParseError::FileNotFound("metadata".to_string()))?;
 
-        let resource_paths = contents.iter().filter(|p| {
-            if let Some(file_name) = p.file_name() {
-                file_name.to_str().unwrap().starts_with("resource")
-            } else {
-                false
-            }
-        });
+        // read_dir makes no guarantees about order, so sort by chunk index (resource2 < resource10)
+        let mut resource_paths: Vec<(usize, &PathBuf)> = contents
+            .iter()
+            .filter_map(|p| {
+                let index = p
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix("resource")?
+                    .parse::<usize>()
+                    .ok()?;
+
+                Some((index, p))
+            })
+            .collect();
+
+        resource_paths.sort_by_key(|(index, _)| *index);
 
         let metadata_bytes =
             fs::read(metadata_path).map_err(|_| AssetParseError::ErrorParsingDescriptor)?;
@@ -288,7 +754,7 @@ impl RawAsset {
 
         let resource_files: Vec<Vec<u8>> = resource_paths
             .into_iter()
-            .map(fs::read)
+            .map(|(_, p)| fs::read(p))
             .collect::<Result<_, _>>()?;
 
         let resource_chunks = match resource_files.is_empty() {
@@ -296,19 +762,145 @@ impl RawAsset {
             false => Some(resource_files),
         };
 
-        let metadata = AssetMetadata::from_bytes(&metadata_bytes)?;
+        // The chunk count follows the metadata when it was written by RawAsset::metadata_file_bytes
+        let (metadata_bytes, chunk_count_bytes) =
+            metadata_bytes.split_at(metadata_bytes.len().min(size_of::<AssetMetadata>()));
 
-        Ok(Self {
+        let metadata = AssetMetadata::from_bytes(metadata_bytes)?;
+
+        let chunk_count = match chunk_count_bytes.is_empty() {
+            true => None,
+            false => Some(Cursor::new(chunk_count_bytes).read_u32::<LittleEndian>()?),
+        };
+
+        let mut raw_asset = Self {
             metadata,
             descriptor_bytes,
             resource_chunks,
-        })
+            original_index: None,
+            data_views: None,
+            read_chunk_count: None,
+        };
+
+        // Textures extracted with only their converted image are rebuilt from it
+        let png_path = path_ref.join(TEXTURE_PNG_FILE);
+
+        if raw_asset.resource_chunks.is_none()
+            && raw_asset.metadata.asset_type() == AssetType::ResTexture
+            && png_path.is_file()
+        {
+            raw_asset = raw_asset.with_texture_png(&png_path)?;
+        }
+
+        if let Some(chunk_count) = chunk_count {
+            raw_asset.set_chunk_count(chunk_count);
+        }
+
+        Ok(raw_asset)
+    }
+
+    /**
+    Reads an asset from a single [`AssetJson`] file. A texture is rebuilt from the PNG next to the
+    file with the same stem if there is one, in which case the JSON doesn't need its resource data.
+
+    # Errors
+    - [`AssetParseError::InvalidDataViews`] if the file isn't valid JSON, or the asset can't be
+      rebuilt from it
+    */
+    #[cfg(feature = "fs")]
+    pub fn from_flat_file<P: AsRef<Path>>(json_path: P) -> Result<Self, AssetParseError> {
+        let json_path = json_path.as_ref();
+
+        let json: AssetJson = serde_json::from_slice(&fs::read(json_path)?).map_err(|e| {
+            AssetParseError::InvalidDataViews(format!(
+                "Unable to read {}: {}",
+                json_path.display(),
+                e
+            ))
+        })?;
+
+        let png_path = json_path.with_extension("png");
+
+        if json.asset_type == u32::from(AssetType::ResTexture) && png_path.is_file() {
+            let chunk_count = json.chunk_count;
+            let json = AssetJson {
+                resource_chunks: None,
+                ..json
+            };
+
+            let mut raw_asset = json
+                .to_raw_asset()
+                .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))?
+                .with_texture_png(&png_path)?;
+
+            if let Some(chunk_count) = chunk_count {
+                raw_asset.set_chunk_count(chunk_count);
+            }
+
+            return Ok(raw_asset);
+        }
+
+        json.to_raw_asset()
+            .map_err(|e| AssetParseError::InvalidDataViews(e.to_string()))
+    }
+
+    /// Replaces the image of a texture with the PNG at `png_path`, converted to the format of its
+    /// descriptor. Compressed formats are encoded again, so their bytes won't match the original.
+    #[cfg(feature = "fs")]
+    fn with_texture_png(mut self, png_path: &Path) -> Result<Self, AssetParseError> {
+        let descriptor = TextureDescriptor::from_bytes(&self.descriptor_bytes)?;
+
+        let texture = RGBAImage::from_png_bytes(BufReader::new(File::open(png_path)?))
+            .and_then(|image| Texture::from_image(descriptor, &image))
+            .map_err(|e| {
+                AssetParseError::InvalidDataViews(format!(
+                    "Unable to convert {} to a texture: {:?}",
+                    png_path.display(),
+                    e
+                ))
+            })?;
+
+        self.descriptor_bytes = texture.descriptor().to_bytes()?;
+        self.resource_chunks = texture.get_resource_chunks();
+
+        Ok(self)
+    }
+
+    /// Writes the asset out as loose files in the layout read by [`RawAsset::from_dir`]
+    #[cfg(feature = "fs")]
+    pub fn to_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        let path_ref = path.as_ref();
+
+        fs::create_dir_all(path_ref)?;
+
+        fs::write(path_ref.join("metadata"), self.metadata_file_bytes())?;
+        fs::write(path_ref.join("descriptor"), &self.descriptor_bytes)?;
+
+        if let Some(chunks) = &self.resource_chunks {
+            for (i, chunk) in chunks.iter().enumerate() {
+                fs::write(path_ref.join(format!("resource{}", i)), chunk)?;
+            }
+        }
+
+        Ok(())
     }
 
     pub fn name(&self) -> &str {
         self.metadata.name()
     }
 
+    /// The position of the asset in the [`BNLFile`] it was read from, or `None` if it wasn't read
+    /// from a file
+    pub fn original_index(&self) -> Option<usize> {
+        self.original_index
+    }
+
+    /// The buffer views the resource chunks were read from, or `None` if the asset wasn't read
+    /// from a file (or has since been replaced)
+    pub fn data_views(&self) -> Option<&DataViewList> {
+        self.data_views.as_ref()
+    }
+
     pub fn metadata(&self) -> &AssetMetadata {
         &self.metadata
     }
@@ -316,6 +908,18 @@ impl RawAsset {
         &mut self.metadata
     }
 
+    /// The contents of the `metadata` file of [`RawAsset::to_dir`]. This is the
+    /// [`AssetMetadata`], followed by the chunk count if it isn't the number of resource chunks.
+    pub fn metadata_file_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.metadata.to_bytes();
+
+        if let Some(chunk_count) = self.stored_chunk_count() {
+            bytes.extend_from_slice(&chunk_count.to_le_bytes());
+        }
+
+        bytes
+    }
+
     pub fn descriptor_bytes(&self) -> &[u8] {
         &self.descriptor_bytes
     }
@@ -326,18 +930,54 @@ impl RawAsset {
     pub fn resource_chunks(&self) -> Option<&Vec<Vec<u8>>> {
         self.resource_chunks.as_ref()
     }
+
+    /// The chunk count written to the asset's description. This is the count the asset was read
+    /// with, unless its number of resource chunks has changed since, in which case it is the
+    /// number of resource chunks.
+    pub fn chunk_count(&self) -> u32 {
+        let num_chunks = self.resource_chunks.as_ref().map_or(0, Vec::len);
+
+        match self.read_chunk_count {
+            Some((chunk_count, num_read)) if num_chunks == num_read => chunk_count,
+            _ => num_chunks as u32,
+        }
+    }
+
+    /// The chunk count if it isn't the number of resource chunks, so has to be stored alongside
+    /// the asset when it is extracted
+    pub fn stored_chunk_count(&self) -> Option<u32> {
+        let chunk_count = self.chunk_count();
+
+        (chunk_count as usize != self.resource_chunks.as_ref().map_or(0, Vec::len))
+            .then_some(chunk_count)
+    }
+
+    /// Sets the chunk count written to the asset's description, for as long as the number of
+    /// resource chunks stays the same
+    pub fn set_chunk_count(&mut self, chunk_count: u32) {
+        self.read_chunk_count = Some((
+            chunk_count,
+            self.resource_chunks.as_ref().map_or(0, Vec::len),
+        ));
+    }
     pub fn resource_chunks_mut(&mut self) -> &mut Option<Vec<Vec<u8>>> {
         &mut self.resource_chunks
     }
 
     pub fn to_asset<AL: AssetLike>(self) -> Result<Asset<AL>, AssetError> {
+        self.parse_as()
+    }
+
+    /// Parses a copy of the asset, leaving the raw asset as it is
+    pub(crate) fn parse_as<AL: AssetLike>(&self) -> Result<Asset<AL>, AssetError> {
         let description = &self.metadata;
 
-        if description.asset_type() != AL::asset_type() {
+        if !AL::accepts_type(description.asset_type()) {
             return Err(AssetError::TypeMismatch);
         }
 
-        let descriptor = AL::Descriptor::from_bytes(&self.descriptor_bytes)?;
+        let descriptor = AL::Descriptor::from_bytes(&self.descriptor_bytes)
+            .map_err(|e| AssetError::in_asset(description, AssetStage::Descriptor, e))?;
 
         let slices: Vec<&[u8]> = match &self.resource_chunks {
             Some(slices) => slices.iter().map(|slice| slice.as_ref()).collect(),
@@ -346,161 +986,517 @@ impl RawAsset {
 
         let vr = VirtualResource::from_slices(&slices);
 
-        let asset = AL::new(&descriptor, &vr)?;
+        let asset = AL::new(&descriptor, &vr)
+            .map_err(|e| AssetError::in_asset(description, AssetStage::of_construction(&e), e))?;
 
         Ok(Asset {
             metadata: description.clone(),
             asset,
         })
     }
-}
 
-impl BNLFile {
     /**
-    Parses a BNL file in memory, loading embedded [`PartialAssetDescription`] data.
-
-    # Errors
-    - [`BNLError::DecompressionFailure`] when the zlib compression section of the file could not be parsed
-    - [`BNLError::DataReadError`] when any other part of the file could not be parsed
-
-    # Examples
-    ```
-    use bnl::BNLFile;
-    use std::path::PathBuf;
-
-    let path = PathBuf::new("./my_bnl.bnl");
-    let bytes = fs::read(&path).expect("Unable to read BNL.");
+    The SHA-256 of the descriptor, as lowercase hex.
 
-    let bnl = BNLFile::from_bytes(&bytes).expect("Unable to parse BNL.");
-    ```
+    This and [`RawAsset::chunk_hashes`] are used wherever descriptors and chunks are compared by
+    their contents, such as when deduplicating them.
     */
-    pub fn from_bytes(bnl_bytes: &[u8]) -> Result<Self, BNLError> {
-        if bnl_bytes.len() < 40 {
-            return Err(BNLError::DataReadError(format!(
-                "Length of BNL file must be at least 40 bytes (received {})",
-                bnl_bytes.len()
-            )));
-        }
+    pub fn descriptor_hash(&self) -> String {
+        sha256_hex(&self.descriptor_bytes)
+    }
 
-        let mut bytes = bnl_bytes[..40].to_vec();
+    /// The SHA-256 of each resource chunk, as lowercase hex
+    pub fn chunk_hashes(&self) -> Vec<String> {
+        self.resource_chunks
+            .iter()
+            .flatten()
+            .map(|chunk| sha256_hex(chunk))
+            .collect()
+    }
 
-        let mut cur = Cursor::new(bnl_bytes);
+    /**
+    The SHA-256 of the contents of the asset, as lowercase hex. Assets with the same hash can be
+    used in place of each other.
 
-        let mut header = BNLHeader {
-            file_count: cur.read_u16::<LittleEndian>()?,
-            flags: cur.read_u8()?,
-            ..Default::default()
-        };
+    Only the asset type, descriptor and resource chunks are hashed, so the name and unknown
+    metadata fields don't change the hash. Each part is preceded by its length, so moving bytes
+    from one chunk to the next does. An asset without resource data hashes the same as one with
+    no chunks.
+    */
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
 
-        cur.read_exact(&mut header.unknown_2)?;
+        hasher.update(u32::from(self.metadata.asset_type()).to_le_bytes());
+        hasher.update((self.descriptor_bytes.len() as u64).to_le_bytes());
+        hasher.update(&self.descriptor_bytes);
 
-        header.asset_desc_loc = DataView::from_reader(&mut cur)?;
-        header.buffer_views_loc = DataView::from_reader(&mut cur)?;
-        header.buffer_loc = DataView::from_reader(&mut cur)?;
-        header.descriptor_loc = DataView::from_reader(&mut cur)?;
+        let chunks = self.resource_chunks.as_deref().unwrap_or_default();
+        hasher.update((chunks.len() as u64).to_le_bytes());
 
-        let decompressed_bytes = miniz_oxide::inflate::decompress_to_vec_zlib(&bnl_bytes[40..])?;
-        bytes.extend_from_slice(&decompressed_bytes);
+        for chunk in chunks {
+            hasher.update((chunk.len() as u64).to_le_bytes());
+            hasher.update(chunk);
+        }
 
-        cur = Cursor::new(&bytes);
+        to_hex(&hasher.finalize())
+    }
 
-        let mut new_bnl = Self {
-            header,
-            ..Default::default()
-        };
+    /// Returns the raw layout of the asset's descriptor, or `None` if its type doesn't implement
+    /// [`DescriptorFields`]
+    pub fn descriptor_fields(&self) -> Result<Option<Vec<DescriptorField>>, AssetParseError> {
+        let bytes = &self.descriptor_bytes;
+
+        Ok(match self.metadata.asset_type() {
+            AssetType::ResTexture => Some(TextureDescriptor::from_bytes(bytes)?.fields()),
+            AssetType::ResAnim => Some(AnimDescriptor::from_bytes(bytes)?.fields()),
+            AssetType::ResModel => Some(ModelDescriptor::from_bytes(bytes)?.fields()),
+            AssetType::ResCutscene => Some(CutsceneDescriptor::from_bytes(bytes)?.fields()),
+            _ => None,
+        })
+    }
+}
 
-        let num_descriptions = new_bnl.header.asset_desc_loc.size as usize / ASSET_DESCRIPTION_SIZE;
+/**
+A BNL file which has been decompressed, but not split into owned assets.
 
-        let mut asset_desc_bytes = Vec::new();
-        let mut buffer_views_bytes = Vec::new();
-        let mut buffer_bytes = Vec::new();
-        let mut descriptor_bytes = Vec::new();
+Assets are borrowed from the decompressed buffer as [`RawAssetRef`]s, so scanning a whole archive
+read-only doesn't copy any descriptor or resource data. Use [`BNLFile`] when the archive needs to be
+modified.
 
-        let loc = &new_bnl.header.asset_desc_loc;
-        cur.seek(SeekFrom::Start(loc.offset.into()))?;
-        asset_desc_bytes.resize(loc.size as usize, 0);
-        cur.read_exact(&mut asset_desc_bytes)?;
+# Examples
+```no_run
+use bnl::DecompressedBnl;
 
-        let loc = &new_bnl.header.buffer_views_loc;
-        cur.seek(SeekFrom::Start(loc.offset.into()))?;
-        buffer_views_bytes.resize(loc.size as usize, 0);
-        cur.read_exact(&mut buffer_views_bytes)?;
+let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+let bnl = DecompressedBnl::from_bytes(&bytes).expect("Unable to parse BNL.");
 
-        let loc = &new_bnl.header.buffer_loc;
-        cur.seek(SeekFrom::Start(loc.offset.into()))?;
-        buffer_bytes.resize(loc.size as usize, 0);
-        cur.read_exact(&mut buffer_bytes)?;
+for raw_asset in bnl.raw_assets().expect("Invalid BNL.") {
+    println!("{}: {} bytes", raw_asset.name(), raw_asset.descriptor_bytes().len());
+}
+```
+*/
+#[derive(Debug)]
+pub struct DecompressedBnl {
+    header: BNLHeader,
+    variant: BnlVariant,
+    /// The header followed by the decompressed data, so that section offsets can be used directly
+    bytes: Vec<u8>,
+}
 
-        let loc = &new_bnl.header.descriptor_loc;
-        cur.seek(SeekFrom::Start(loc.offset.into()))?;
-        descriptor_bytes.resize(loc.size as usize, 0);
-        cur.read_exact(&mut descriptor_bytes)?;
+impl DecompressedBnl {
+    /// Decompresses a BNL file in memory, sniffing its [`BnlVariant`] from the header
+    ///
+    /// # Errors
+    /// See [`BNLFile::from_bytes`]
+    pub fn from_bytes(bnl_bytes: &[u8]) -> Result<Self, BNLError> {
+        let variant = BnlVariant::detect(bnl_bytes).unwrap_or_default();
 
-        cur.seek(SeekFrom::Start(new_bnl.header.asset_desc_loc.offset as u64))?;
+        Self::from_bytes_as(bnl_bytes, variant)
+    }
 
-        for _ in 0..num_descriptions {
-            let mut bytes = [0x00; ASSET_DESCRIPTION_SIZE];
-            cur.read_exact(&mut bytes)?;
+    pub fn from_bytes_as(bnl_bytes: &[u8], variant: BnlVariant) -> Result<Self, BNLError> {
+        let header_size = variant.header_size();
 
-            let description = AssetDescription::from_bytes(&bytes)?;
+        if bnl_bytes.len() < header_size {
+            return Err(BNLError::DataReadError(format!(
+                "Length of BNL file must be at least {} bytes (received {})",
+                header_size,
+                bnl_bytes.len()
+            )));
+        }
+
+        let mut bytes = bnl_bytes[..header_size].to_vec();
+
+        let header = BNLHeader::from_reader(&mut Cursor::new(bnl_bytes), variant)?;
+
+        let decompressed_bytes = zlib::decompress(&bnl_bytes[header_size..])?;
+        bytes.extend_from_slice(&decompressed_bytes);
+
+        Ok(Self {
+            header,
+            variant,
+            bytes,
+        })
+    }
+
+    pub fn variant(&self) -> BnlVariant {
+        self.variant
+    }
+
+    fn section_bytes(&self, loc: &DataView, section_name: &str) -> Result<&[u8], BNLError> {
+        let start = loc.offset as usize;
+        let end = start + loc.size as usize;
+
+        self.bytes.get(start..end).ok_or_else(|| {
+            BNLError::DataReadError(format!(
+                "{} section [{}, {}) is out of bounds for a BNL of {} bytes",
+                section_name,
+                start,
+                end,
+                self.bytes.len()
+            ))
+        })
+    }
+
+    /// Borrows every asset in the file, in the order they are stored
+    ///
+    /// # Errors
+    /// - [`BNLError::DataReadError`] if any section or asset is out of bounds
+    pub fn raw_assets(&self) -> Result<Vec<RawAssetRef<'_>>, BNLError> {
+        let variant = self.variant;
+
+        let num_descriptions = self.header.asset_desc_loc.size as usize / ASSET_DESCRIPTION_SIZE;
+
+        let asset_desc_bytes =
+            self.section_bytes(&self.header.asset_desc_loc, "Asset description")?;
+        let buffer_views_bytes =
+            self.section_bytes(&self.header.buffer_views_loc, "Buffer views")?;
+        let buffer_bytes = self.section_bytes(&self.header.buffer_loc, "Buffer")?;
+        let descriptor_bytes = self.section_bytes(&self.header.descriptor_loc, "Descriptor")?;
+
+        if asset_desc_bytes.len() < num_descriptions * ASSET_DESCRIPTION_SIZE {
+            return Err(BNLError::DataReadError(
+                "Asset description section is smaller than the number of descriptions.".to_string(),
+            ));
+        }
+
+        let mut assets = Vec::with_capacity(num_descriptions);
+
+        for description_bytes in asset_desc_bytes
+            .chunks_exact(ASSET_DESCRIPTION_SIZE)
+            .take(num_descriptions)
+        {
+            let description = AssetDescription::from_bytes_as(description_bytes, variant)?;
 
             let desc_start: usize = description.descriptor_ptr as usize;
             let desc_end: usize = desc_start + description.descriptor_size as usize;
-            let desc_bytes = descriptor_bytes[desc_start..desc_end].to_vec();
-
-            let resource_chunks: Option<Vec<Vec<u8>>> = match description.resource_size {
+            let desc_bytes = descriptor_bytes.get(desc_start..desc_end).ok_or_else(|| {
+                BNLError::DataReadError(format!(
+                    "Descriptor for {} is out of bounds of the descriptor section.",
+                    description.name()
+                ))
+            })?;
+
+            let data_views = match description.resource_size {
                 0 => None,
                 _size => Some(
-                    DataViewList::from_bytes(
-                        &buffer_views_bytes[description.dataview_list_ptr as usize..],
+                    DataViewList::from_bytes_as(
+                        buffer_views_bytes
+                            .get(description.dataview_list_ptr as usize..)
+                            .ok_or_else(|| {
+                                BNLError::DataReadError(
+                                    "BufferView list pointer is out of bounds.".to_string(),
+                                )
+                            })?,
+                        variant,
                     )
                     .map_err(|_| {
                         BNLError::DataReadError("Unable to read BufferViews.".to_string())
-                    })?
-                    .slices(&buffer_bytes)?
-                    .iter()
-                    .map(|slice| slice.to_vec())
-                    .collect(),
+                    })?,
                 ),
             };
 
-            // TODO: Resize this then push into it
-            new_bnl.assets.push(RawAsset {
+            let resource_chunks: Option<Vec<&[u8]>> = data_views
+                .as_ref()
+                .map(|dvl| dvl.slices(buffer_bytes))
+                .transpose()?;
+
+            assets.push(RawAssetRef {
                 metadata: description.metadata,
                 descriptor_bytes: desc_bytes,
                 resource_chunks,
+                data_views,
+                chunk_count: description.chunk_count,
             });
         }
 
-        Ok(new_bnl)
+        Ok(assets)
+    }
+}
+
+/// A [`RawAsset`] which borrows its data from a [`DecompressedBnl`]
+#[derive(Debug, Clone)]
+pub struct RawAssetRef<'a> {
+    metadata: AssetMetadata,
+    descriptor_bytes: &'a [u8],
+    resource_chunks: Option<Vec<&'a [u8]>>,
+    data_views: Option<DataViewList>,
+    chunk_count: u32,
+}
+
+impl<'a> RawAssetRef<'a> {
+    pub fn name(&self) -> &str {
+        self.metadata.name()
+    }
+
+    pub fn metadata(&self) -> &AssetMetadata {
+        &self.metadata
+    }
+
+    pub fn descriptor_bytes(&self) -> &'a [u8] {
+        self.descriptor_bytes
+    }
+
+    pub fn resource_chunks(&self) -> Option<&[&'a [u8]]> {
+        self.resource_chunks.as_deref()
+    }
+
+    /// The buffer views the resource chunks were read from
+    pub fn data_views(&self) -> Option<&DataViewList> {
+        self.data_views.as_ref()
+    }
+
+    /// Copies the asset's data into a [`RawAsset`]
+    pub fn to_raw_asset(&self) -> RawAsset {
+        RawAsset {
+            metadata: self.metadata.clone(),
+            descriptor_bytes: self.descriptor_bytes.to_vec(),
+            resource_chunks: self
+                .resource_chunks
+                .as_ref()
+                .map(|chunks| chunks.iter().map(|chunk| chunk.to_vec()).collect()),
+            original_index: None,
+            data_views: self.data_views.clone(),
+            read_chunk_count: Some((
+                self.chunk_count,
+                self.resource_chunks.as_ref().map_or(0, Vec::len),
+            )),
+        }
+    }
+
+    fn virtual_resource(&self) -> VirtualResource<'a> {
+        VirtualResource::from_slices(self.resource_chunks.as_deref().unwrap_or_default())
+    }
+
+    /// Parses the asset as an owned [`AssetLike`], without copying the whole [`RawAsset`] first
+    pub fn to_asset<AL: AssetLike>(&self) -> Result<Asset<AL>, AssetError> {
+        if !AL::accepts_type(self.metadata.asset_type()) {
+            return Err(AssetError::TypeMismatch);
+        }
+
+        let descriptor = AL::Descriptor::from_bytes(self.descriptor_bytes)
+            .map_err(|e| AssetError::in_asset(&self.metadata, AssetStage::Descriptor, e))?;
+
+        let asset = AL::new(&descriptor, &self.virtual_resource()).map_err(|e| {
+            AssetError::in_asset(&self.metadata, AssetStage::of_construction(&e), e)
+        })?;
+
+        Ok(Asset {
+            metadata: self.metadata.clone(),
+            asset,
+        })
+    }
+
+    /// Parses the asset as an [`AssetRefLike`], which can borrow from the decompressed BNL
+    pub fn to_asset_ref<AR: AssetRefLike<'a>>(&self) -> Result<AR, AssetError> {
+        if self.metadata.asset_type() != AR::asset_type() {
+            return Err(AssetError::TypeMismatch);
+        }
+
+        let descriptor = AR::Descriptor::from_bytes(self.descriptor_bytes)
+            .map_err(|e| AssetError::in_asset(&self.metadata, AssetStage::Descriptor, e))?;
+
+        AR::new_ref(&descriptor, &self.virtual_resource())
+            .map_err(|e| AssetError::in_asset(&self.metadata, AssetStage::of_construction(&e), e))
+    }
+}
+
+impl BNLFile {
+    /**
+    Parses a BNL file in memory, loading embedded [`PartialAssetDescription`] data.
+
+    # Errors
+    - [`BNLError::DecompressionFailure`] when the zlib compression section of the file could not be parsed
+    - [`BNLError::DataReadError`] when any other part of the file could not be parsed
+
+    # Examples
+    ```no_run
+    use bnl::BNLFile;
+
+    let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+
+    let bnl = BNLFile::from_bytes(&bytes).expect("Unable to parse BNL.");
+    ```
+    */
+    pub fn from_bytes(bnl_bytes: &[u8]) -> Result<Self, BNLError> {
+        let variant = BnlVariant::detect(bnl_bytes).unwrap_or_default();
+
+        Self::from_bytes_as(bnl_bytes, variant)
+    }
+
+    /// Parses a BNL file in memory using an explicit [`BnlVariant`] rather than sniffing it from
+    /// the header.
+    pub fn from_bytes_as(bnl_bytes: &[u8], variant: BnlVariant) -> Result<Self, BNLError> {
+        let decompressed = DecompressedBnl::from_bytes_as(bnl_bytes, variant)?;
+
+        let assets = decompressed
+            .raw_assets()?
+            .iter()
+            .enumerate()
+            .map(|(i, raw_asset_ref)| RawAsset {
+                original_index: Some(i),
+                ..raw_asset_ref.to_raw_asset()
+            })
+            .collect();
+
+        Ok(Self {
+            write_options: BNLWriteOptions::detect_decompressed(&decompressed)?,
+            header: decompressed.header,
+            variant,
+            assets,
+            unchanged_since_read: true,
+            ..Default::default()
+        })
+    }
+
+    /// Serialises the file in memory. See [`BNLFile::write_to`] to write it without holding the
+    /// uncompressed data in memory.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec can't fail");
+
+        bytes
+    }
+
+    /**
+    Serialises the file into `w`. The sections are compressed as they are written, so only the
+    small sections (asset descriptions, data view lists and descriptors) are built in memory, and
+    resource chunks are compressed straight from the assets.
+
+    Writing leaves the file untouched, so its header still describes the file it was read from. Use
+    [`BNLFile::commit_layout`] to take on the written layout.
+
+    # Errors
+    - Any error from writing to `w`
+    */
+    pub fn write_to<W: Write>(&self, mut w: W) -> Result<(), std::io::Error> {
+        let variant = self.variant;
+        let sections = self.lay_out();
+        let header = &sections.header;
+
+        w.write_all(&header.to_bytes(variant))?;
+
+        let mut encoder = ZlibWriter::new(w, ZLIB_COMPRESSION_LEVEL);
+
+        // The offset in the file that has been written up to, so that the gaps between sections
+        // and chunks can be zeroed
+        let mut position = variant.header_size();
+
+        let mut write_at = |encoder: &mut ZlibWriter<W>, offset: usize, bytes: &[u8]| {
+            encoder.write_zeroes(offset - position)?;
+            encoder.write_all(bytes)?;
+            position = offset + bytes.len();
+
+            Ok::<(), std::io::Error>(())
+        };
+
+        let buffer_offset = header.buffer_loc.offset as usize;
+
+        write_at(
+            &mut encoder,
+            header.asset_desc_loc.offset as usize,
+            &sections.asset_desc_section,
+        )?;
+        write_at(
+            &mut encoder,
+            header.buffer_views_loc.offset as usize,
+            &sections.buffer_views_section,
+        )?;
+
+        // An empty buffer section still moves the position to its start
+        write_at(&mut encoder, buffer_offset, &[])?;
+        for (offset, chunk) in &sections.buffer_chunks {
+            write_at(&mut encoder, buffer_offset + offset, chunk)?;
+        }
+
+        write_at(
+            &mut encoder,
+            header.descriptor_loc.offset as usize,
+            &sections.descriptors_section,
+        )?;
+
+        encoder.finish()?;
+
+        Ok(())
+    }
+
+    /// Puts the assets in the order they are written in, and stores the header that
+    /// [`BNLFile::to_bytes`] writes, so that [`BNLFile::decompressed_size`] and
+    /// [`BNLFile::descriptor_section_size`] describe the written file
+    pub fn commit_layout(&mut self) {
+        let header = self.lay_out().header;
+
+        self.asset_order.sort(&mut self.assets);
+        self.header = header;
     }
 
-    pub fn to_bytes(&mut self) -> Vec<u8> {
-        let mut asset_desc_section: Vec<u8> =
-            vec![0x00; ASSET_DESCRIPTION_SIZE * self.assets.len()];
+    /// Builds the small sections of the file and works out where every section and resource chunk
+    /// goes, without touching the file
+    fn lay_out(&self) -> LaidOutSections<'_> {
+        let variant = self.variant;
+
+        let mut assets: Vec<&RawAsset> = self.assets.iter().collect();
+        self.asset_order.sort(&mut assets);
+
+        let mut asset_desc_section: Vec<u8> = vec![0x00; ASSET_DESCRIPTION_SIZE * assets.len()];
         let mut buffer_views_section: Vec<u8> = vec![];
-        let mut buffer_section: Vec<u8> = vec![];
         let mut descriptors_section: Vec<u8> = vec![];
 
-        self.assets.sort_by_key(|v| v.name().to_string());
+        // Items are aligned relative to the start of their section, and each section starts on
+        // the same alignment, so they end up aligned relative to the start of the file
+        let options = &self.write_options;
 
-        for (i, asset) in self.assets.iter().enumerate() {
+        // The resource chunks in the order they will be written, with their offsets in the buffer
+        // section. The chunks themselves aren't copied until they are compressed.
+        let mut buffer_chunks: Vec<(usize, &[u8])> = vec![];
+        let mut buffer_size: usize = 0;
+
+        // The offset of each chunk already in the buffer section, when deduplicating
+        let mut written_chunks: HashMap<String, usize> = HashMap::new();
+        // Likewise for descriptors in the descriptor section
+        let mut written_descriptors: HashMap<String, usize> = HashMap::new();
+
+        for (i, &asset) in assets.iter().enumerate() {
             let metadata = asset.metadata.clone();
             let mut asset_desc: AssetDescription = metadata.into();
+            asset_desc.chunk_count = asset.chunk_count();
 
             if let Some(chunks) = &asset.resource_chunks {
                 let num_chunks = chunks.len();
+                let chunk_hashes = match options.dedup_chunks {
+                    true => asset.chunk_hashes(),
+                    false => vec![],
+                };
 
                 let dvl = DataViewList {
                     size: (8 + 8 * num_chunks) as u32,
                     num_views: num_chunks as u32,
                     views: chunks
                         .iter()
-                        .map(|chunk| {
-                            let offset = buffer_section.len();
+                        .enumerate()
+                        .map(|(j, chunk)| {
+                            let hash = chunk_hashes.get(j);
+
+                            let offset = match hash.and_then(|hash| written_chunks.get(hash)) {
+                                Some(&offset) => offset,
+                                None => {
+                                    let offset = buffer_size
+                                        .next_multiple_of(options.resource_alignment.max(1));
+
+                                    buffer_chunks.push((offset, chunk.as_slice()));
+                                    buffer_size = offset + chunk.len();
 
-                            // TODO: Find a way to propagate this, or safely ignore it
-                            let _ = buffer_section.write_all(chunk);
+                                    if let Some(hash) = hash
+                                        && !chunk.is_empty()
+                                    {
+                                        written_chunks.insert(hash.clone(), offset);
+                                    }
+
+                                    offset
+                                }
+                            };
 
                             DataView {
                                 offset: offset as u32,
@@ -510,40 +1506,65 @@ impl BNLFile {
                         .collect(),
                 };
 
-                let dvl_bytes = dvl.to_bytes();
+                let dvl_bytes = dvl.to_bytes_as(variant);
+
+                pad_to_alignment(&mut buffer_views_section, options.buffer_view_alignment);
 
                 // Write buffer view information into asset desc
                 asset_desc.dataview_list_ptr = buffer_views_section.len() as u32;
                 asset_desc.resource_size = dvl.bytes_required() as u32;
-                buffer_views_section
-                    .write_all(&dvl_bytes)
-                    .expect("Unable to write buffer view.");
+                buffer_views_section.extend_from_slice(&dvl_bytes);
             }
 
-            asset_desc.descriptor_ptr = descriptors_section.len() as u32;
-            asset_desc.descriptor_size = asset.descriptor_bytes.len() as u32;
-            descriptors_section.extend_from_slice(&asset.descriptor_bytes);
+            let descriptor = asset.descriptor_bytes.as_slice();
+            let descriptor_hash = options.dedup_descriptors.then(|| asset.descriptor_hash());
+
+            asset_desc.descriptor_ptr = match descriptor_hash
+                .as_ref()
+                .and_then(|hash| written_descriptors.get(hash))
+            {
+                Some(&offset) => offset,
+                None => {
+                    pad_to_alignment(&mut descriptors_section, options.descriptor_alignment);
+
+                    let offset = descriptors_section.len();
+                    descriptors_section.extend_from_slice(descriptor);
+
+                    if let Some(hash) = descriptor_hash
+                        && !descriptor.is_empty()
+                    {
+                        written_descriptors.insert(hash, offset);
+                    }
+
+                    offset
+                }
+            } as u32;
+            asset_desc.descriptor_size = descriptor.len() as u32;
 
             let start = i * ASSET_DESCRIPTION_SIZE;
             let end = start + ASSET_DESCRIPTION_SIZE;
 
-            asset_desc_section[start..end].copy_from_slice(&asset_desc.to_bytes());
+            asset_desc_section[start..end].copy_from_slice(&asset_desc.to_bytes_as(variant));
         }
 
-        let asset_desc_offset: usize = 40;
+        let header_size = variant.header_size();
+
+        let asset_desc_offset: usize = header_size;
         let asset_desc_size: usize = asset_desc_section.len();
 
-        let buffer_views_offset: usize = asset_desc_offset + asset_desc_size;
+        let buffer_views_offset: usize = (asset_desc_offset + asset_desc_size)
+            .next_multiple_of(options.buffer_view_alignment.max(1));
         let buffer_views_size: usize = buffer_views_section.len();
 
-        let buffer_offset: usize = buffer_views_offset + buffer_views_size;
-        let buffer_size: usize = buffer_section.len();
+        let buffer_offset: usize = (buffer_views_offset + buffer_views_size)
+            .next_multiple_of(options.resource_alignment.max(1));
 
-        let descriptors_offset: usize = buffer_offset + buffer_size;
+        let descriptors_offset: usize =
+            (buffer_offset + buffer_size).next_multiple_of(options.descriptor_alignment.max(1));
         let descriptors_size: usize = descriptors_section.len();
 
-        let new_header = BNLHeader {
-            file_count: self.assets.len() as u16,
+        let header = BNLHeader {
+            file_count: assets.len() as u16,
             asset_desc_loc: DataView {
                 offset: asset_desc_offset as u32,
                 size: asset_desc_size as u32,
@@ -563,196 +1584,476 @@ impl BNLFile {
             ..self.header
         };
 
-        self.header = new_header;
+        LaidOutSections {
+            header,
+            asset_desc_section,
+            buffer_views_section,
+            buffer_chunks,
+            descriptors_section,
+        }
+    }
 
-        let mut decompressed_bytes = Vec::new();
+    /// The container layout this file was read as, and will be written as
+    pub fn variant(&self) -> BnlVariant {
+        self.variant
+    }
 
-        decompressed_bytes.extend_from_slice(&asset_desc_section);
-        decompressed_bytes.extend_from_slice(&buffer_views_section);
-        decompressed_bytes.extend_from_slice(&buffer_section);
-        decompressed_bytes.extend_from_slice(&descriptors_section);
+    /// Changes the container layout used by [`BNLFile::to_bytes`]
+    pub fn set_variant(&mut self, variant: BnlVariant) {
+        self.variant = variant;
+        self.unchanged_since_read = false;
+    }
 
-        let compressed_bytes = miniz_oxide::deflate::compress_to_vec_zlib(&decompressed_bytes, 1);
+    /// The flags and unknown bytes of the header, which are written back unchanged
+    pub fn header_fields(&self) -> HeaderFields {
+        HeaderFields::new(&self.header)
+    }
 
-        let mut bytes = vec![0; compressed_bytes.len() + 40];
+    pub fn set_header_fields(&mut self, fields: HeaderFields) {
+        self.header.flags = fields.flags;
+        self.header.unknown_2 = fields.unknown_2;
+        self.unchanged_since_read = false;
+    }
 
-        bytes[0..40].copy_from_slice(&self.header.to_bytes());
-        bytes[40..].copy_from_slice(&compressed_bytes);
+    pub fn asset_order(&self) -> AssetOrder {
+        self.asset_order
+    }
 
-        bytes
+    /// Changes the order assets are laid out in by [`BNLFile::to_bytes`]
+    pub fn set_asset_order(&mut self, asset_order: AssetOrder) {
+        self.asset_order = asset_order;
+        self.unchanged_since_read = false;
     }
 
-    /// Retrieves an asset by name and type, converting it to the target format if it matches the
-    /// format of the asset's descriptor.
-    ///
-    /// # Errors
-    /// - [`AssetError::NotFound`] when the given name can't be found
-    /// - [`AssetError::TypeMismatch`] when the asset is found, but doesn't match the requested type
-    /// - [`AssetError::ParseError`] when the asset is found, the type matches but an error occurs while parsing the asset
-    ///
-    /// # Examples
-    /// ```
-    /// use bnl::BNLFile;
-    /// use bnl::asset::Texture;
-    ///
-    /// let bnl_file = BNLFile::from_bytes(...);
-    /// let tex = bnl_file.get_asset::<Texture>("aid_texture_mytexture_a_b")
-    ///                   .expect("Unable to get texture.");
-    /// ```
-    pub fn get_asset<AL: AssetLike>(&self, name: &str) -> Result<Asset<AL>, AssetError> {
-        let raw_asset = self.get_raw_asset(name).ok_or(AssetError::NotFound)?;
+    /// How edits to assets sharing resource data are handled, see [`EditPolicy`]
+    pub fn edit_policy(&self) -> EditPolicy {
+        self.edit_policy
+    }
 
-        let description = &raw_asset.metadata;
+    /// Changes how [`BNLFile::modify_asset`] and [`BNLFile::patch_asset`] treat assets which share
+    /// resource data with other assets
+    pub fn set_edit_policy(&mut self, edit_policy: EditPolicy) {
+        self.edit_policy = edit_policy;
+    }
 
-        if description.asset_type() != AL::asset_type() {
-            return Err(AssetError::TypeMismatch);
-        }
+    pub fn append_validation(&self) -> AppendValidation {
+        self.append_validation
+    }
 
-        let descriptor = AL::Descriptor::from_bytes(&raw_asset.descriptor_bytes)?;
+    pub fn set_append_validation(&mut self, append_validation: AppendValidation) {
+        self.append_validation = append_validation;
+    }
 
-        let slices: Vec<&[u8]> = match &raw_asset.resource_chunks {
-            Some(slices) => slices.iter().map(|slice| slice.as_ref()).collect(),
-            None => vec![],
-        };
+    pub fn write_options(&self) -> &BNLWriteOptions {
+        &self.write_options
+    }
 
-        let vr = VirtualResource::from_slices(&slices);
+    /// Changes how [`BNLFile::to_bytes`] lays out the file
+    pub fn set_write_options(&mut self, write_options: BNLWriteOptions) {
+        self.write_options = write_options;
+        self.unchanged_since_read = false;
+    }
 
-        let asset = AL::new(&descriptor, &vr)?;
+    /// The size of the data after the header once decompressed, in the file this was read from or
+    /// the layout last stored by [`BNLFile::commit_layout`]
+    pub fn decompressed_size(&self) -> usize {
+        let header = &self.header;
+
+        [
+            header.asset_desc_loc,
+            header.buffer_views_loc,
+            header.buffer_loc,
+            header.descriptor_loc,
+        ]
+        .iter()
+        .map(|loc| (loc.offset + loc.size) as usize)
+        .max()
+        .unwrap_or(0)
+        .saturating_sub(self.variant.header_size())
+    }
 
-        Ok(Asset {
-            metadata: description.clone(),
-            asset,
-        })
+    /// The size of the descriptor section, in the file this was read from or the layout last stored
+    /// by [`BNLFile::commit_layout`]
+    pub fn descriptor_section_size(&self) -> usize {
+        self.header.descriptor_loc.size as usize
     }
 
-    /// Returns all assets of a given type from this [`BNLFile`].
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use bnl::BNLFile;
-    /// use bnl::asset::Texture;
-    ///
-    /// let bnl_file = BNLFile::from_bytes(...);
-    /// let textures = bnl_file.get_assets::<Texture>();
-    ///
-    /// // Dump all of the textures here
-    /// ```
-    pub fn get_assets<AL: AssetLike>(&self) -> Vec<AL> {
-        let mut assets = Vec::new();
+    /// Finds every non-empty resource chunk which is stored more than once, in the order each
+    /// chunk first appears
+    pub fn find_duplicate_chunks(&self) -> Vec<DuplicateChunks> {
+        let mut first_seen: HashMap<String, usize> = HashMap::new();
+        let mut duplicates: Vec<DuplicateChunks> = vec![];
 
         for asset in &self.assets {
-            let asset_desc = &asset.metadata;
-
-            if asset_desc.asset_type() != AL::asset_type() {
+            let Some(chunks) = &asset.resource_chunks else {
                 continue;
-            }
+            };
 
-            if let Ok(descriptor) = AL::Descriptor::from_bytes(&asset.descriptor_bytes) {
-                let slices: Vec<&[u8]> = match &asset.resource_chunks {
-                    Some(slices) => slices.iter().map(|slice| slice.as_ref()).collect(),
-                    None => vec![],
+            for (chunk_index, (chunk, hash)) in chunks.iter().zip(asset.chunk_hashes()).enumerate()
+            {
+                if chunk.is_empty() {
+                    continue;
+                }
+
+                let location = ChunkLocation {
+                    asset_name: asset.name().to_string(),
+                    chunk_index,
                 };
 
-                let vr = VirtualResource::from_slices(&slices);
+                let index = *first_seen.entry(hash).or_insert_with(|| {
+                    duplicates.push(DuplicateChunks {
+                        size: chunk.len(),
+                        locations: vec![],
+                    });
 
-                if let Ok(asset) = AL::new(&descriptor, &vr) {
-                    assets.push(asset);
-                }
+                    duplicates.len() - 1
+                });
+
+                duplicates[index].locations.push(location);
             }
         }
 
-        assets
-    }
-
-    /// Retrieves a [`RawAsset`] by name, or None if it can't be found.
-    ///
-    /// # Examples
-    /// ```
-    /// use bnl::BNLFile;
-    /// use bnl::asset::Texture;
-    ///
-    /// let bnl_file = BNLFile::from_bytes(...);
-    /// let raw_asset = bnl_file.get_raw_asset().expect("Unable to extract asset.");
-    ///
-    /// // Dump the data from the RawAsset
-    /// std::fs::write("./descriptor", &raw_asset.descriptor_bytes).expect("Unable to write
-    /// descriptor.");
-    /// raw_asset.data_slices.iter().enumerate().for_each(|(i, slice)| {
-    ///     std::fs::write(format!("./resource{}", i), &slice).expect("Unable to write resource.");
-    /// });
-    /// ```
-    pub fn get_raw_asset(&self, name: &str) -> Option<&RawAsset> {
-        self.assets
-            .iter()
-            .find(|&asset| asset.metadata.name() == name)
-    }
+        duplicates.retain(|duplicate| duplicate.locations.len() > 1);
 
-    pub(crate) fn get_raw_asset_mut(&mut self, name: &str) -> Option<&mut RawAsset> {
-        self.assets
-            .iter_mut()
-            .find(|asset| asset.metadata.name() == name)
+        duplicates
     }
 
-    /*
-    pub fn get_overlaps(&self) -> Result<Vec<Range<usize>>, BNLError> {
-        let mut dvls = Vec::with_capacity(self.asset_descriptions().len());
-
-        self.asset_descriptions()
-            .iter()
-            .filter(|asset_desc| asset_desc.dataview_list_ptr != 0)
-            .map(|asset_desc| {
-                DataViewList::from_bytes(
-                    &self.buffer_views_bytes[asset_desc.dataview_list_ptr as usize..],
-                )
-            });
+    /// Finds every non-empty descriptor which more than one asset has identical bytes for, in the
+    /// order each descriptor first appears
+    pub fn find_shared_descriptors(&self) -> Vec<SharedDescriptor> {
+        let mut first_seen: HashMap<String, usize> = HashMap::new();
+        let mut shared: Vec<SharedDescriptor> = vec![];
 
-        for asset_desc in self.asset_descriptions() {
-            if asset_desc.dataview_list_ptr != 0 {
-                dvls.push(
-                    DataViewList::from_bytes(
-                        &self.buffer_views_bytes[asset_desc.dataview_list_ptr as usize..],
-                    )
-                    .map_err(|_| {
-                        BNLError::DataReadError(format!(
-                            "Unable to read Data View List for asset {}",
-                            asset_desc.name()
-                        ))
-                    })?,
-                );
-            }
-        }
+        for asset in &self.assets {
+            let descriptor = asset.descriptor_bytes.as_slice();
 
-        for pair in dvls.iter().zip(&dvls) {
-            if std::ptr::eq(pair.0, pair.1) {
+            if descriptor.is_empty() {
                 continue;
             }
+
+            let index = *first_seen
+                .entry(asset.descriptor_hash())
+                .or_insert_with(|| {
+                    shared.push(SharedDescriptor {
+                        size: descriptor.len(),
+                        asset_names: vec![],
+                    });
+
+                    shared.len() - 1
+                });
+
+            shared[index].asset_names.push(asset.name().to_string());
         }
 
-        Ok(vec![])
+        shared.retain(|descriptor| descriptor.asset_names.len() > 1);
+
+        shared
     }
+
+    /**
+    Checks the file for things which don't stop it being written, but may not be intended:
+    descriptors and resource chunks which are stored more than once, and assets sharing resource
+    data.
     */
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
 
-    /// Retrieves all [`RawAsset`] entries.
+        issues.extend(
+            self.find_shared_descriptors()
+                .into_iter()
+                .map(ValidationIssue::SharedDescriptor),
+        );
+        issues.extend(
+            self.find_duplicate_chunks()
+                .into_iter()
+                .map(ValidationIssue::DuplicateChunks),
+        );
+        issues.extend(
+            self.get_overlaps()
+                .into_iter()
+                .map(ValidationIssue::Overlap),
+        );
+
+        issues
+    }
+
+    /// The assets in their current order. Reordering them only affects [`BNLFile::to_bytes`] when
+    /// the asset order is [`AssetOrder::Original`].
+    ///
+    /// Any of the assets could be changed through this, so the whole asset cache is cleared.
+    pub fn assets_mut(&mut self) -> &mut [RawAsset] {
+        self.asset_cache.clear();
+        self.unchanged_since_read = false;
+        &mut self.assets
+    }
+
+    /// Stably sorts the assets with `compare`, and switches to [`AssetOrder::Original`] so that
+    /// [`BNLFile::to_bytes`] keeps the new order
+    pub fn sort_assets_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&RawAsset, &RawAsset) -> Ordering,
+    {
+        self.assets.sort_by(compare);
+        self.asset_order = AssetOrder::Original;
+        self.unchanged_since_read = false;
+    }
+
+    /// Puts assets back in the order of the file they were read from. Assets which weren't read
+    /// from a file are placed after them, keeping their relative order.
+    pub fn restore_file_order(&mut self) {
+        AssetOrder::FileOrder.sort(&mut self.assets);
+        self.unchanged_since_read = false;
+    }
+
+    /// Retrieves an asset by name and type, converting it to the target format if it matches the
+    /// format of the asset's descriptor.
+    ///
+    /// # Errors
+    /// - [`AssetError::NotFound`] when the given name can't be found
+    /// - [`AssetError::TypeMismatch`] when the asset is found, but doesn't match the requested type
+    /// - [`AssetError::InAsset`] when the asset is found, the type matches but an error occurs while parsing the asset
+    ///
+    /// # Examples
+    /// ```
+    /// use bnl::BNLFile;
+    /// use bnl::asset::Texture;
+    ///
+    /// let bnl_file = BNLFile::from_bytes(...);
+    /// let tex = bnl_file.get_asset::<Texture>("aid_texture_mytexture_a_b")
+    ///                   .expect("Unable to get texture.");
+    /// ```
+    pub fn get_asset<AL: AssetLike>(&self, name: &str) -> Result<Asset<AL>, AssetError> {
+        self.get_raw_asset(name)
+            .ok_or(AssetError::NotFound)?
+            .parse_as()
+    }
+
+    /**
+    Retrieves an asset by name, parsing it as whichever type matches its [`AssetType`]. Types
+    without a parser give [`AnyAsset::Other`].
+
+    # Errors
+    - [`AssetError::NotFound`] when the given name can't be found
+    - [`AssetError::InAsset`] when the asset can't be parsed as the type it is stored as
+
+    # Examples
+    ```ignore
+    use bnl::asset::AnyAsset;
+
+    match bnl_file.parse_any("aid_texture_mytexture_a_b")? {
+        AnyAsset::Texture(texture) => println!("{}", texture.asset().descriptor().width()),
+        other => println!("{:?}", other.asset_type()),
+    }
+    ```
+    */
+    pub fn parse_any(&self, name: &str) -> Result<AnyAsset, AssetError> {
+        let raw_asset = self.get_raw_asset(name).ok_or(AssetError::NotFound)?;
+
+        Ok(match raw_asset.metadata.asset_type() {
+            AssetType::ResTexture => AnyAsset::Texture(self.get_asset(name)?),
+            AssetType::ResModel => AnyAsset::Model(self.get_asset(name)?),
+            AssetType::ResAnim => AnyAsset::Anim(self.get_asset(name)?),
+            AssetType::ResScript => AnyAsset::Script(self.get_asset(name)?),
+            AssetType::ResAidList => AnyAsset::AidList(self.get_asset(name)?),
+            AssetType::ResXCueList => AnyAsset::CueList(self.get_asset(name)?),
+            AssetType::ResFont => AnyAsset::Font(self.get_asset(name)?),
+            AssetType::ResCutscene => AnyAsset::Cutscene(self.get_asset(name)?),
+            _ => AnyAsset::Other(self.get_asset(name)?),
+        })
+    }
+
+    /**
+    Like [`BNLFile::get_asset`], but keeps the parsed asset so that asking for the same name and
+    type again returns it without parsing anything. This is meant for viewers which show the same
+    assets over and over.
+
+    Cached assets are dropped whenever they are changed through this file. Assets which fail to
+    parse aren't cached.
+
+    # Errors
+    - The same as [`BNLFile::get_asset`]
+    */
+    pub fn get_asset_cached<AL>(&self, name: &str) -> Result<Arc<Asset<AL>>, AssetError>
+    where
+        AL: AssetLike + Send + Sync + 'static,
+    {
+        if let Some(asset) = self.asset_cache.get::<Asset<AL>>(name) {
+            return Ok(asset);
+        }
+
+        let asset = Arc::new(self.get_asset::<AL>(name)?);
+        self.asset_cache.insert(name, asset.clone());
+
+        Ok(asset)
+    }
+
+    /// Drops every cached copy of the asset `name`, see [`BNLFile::get_asset_cached`]. This is
+    /// only needed if the asset's bytes were changed by something other than this file.
+    pub fn invalidate_cached_asset(&self, name: &str) {
+        self.asset_cache.invalidate(name);
+    }
+
+    /// Drops every asset kept by [`BNLFile::get_asset_cached`]
+    pub fn clear_asset_cache(&self) {
+        self.asset_cache.clear();
+    }
+
+    /// The number of assets kept by [`BNLFile::get_asset_cached`], counting each type an asset was
+    /// parsed as separately
+    pub fn cached_asset_count(&self) -> usize {
+        self.asset_cache.len()
+    }
+
+    /**
+    Iterates over every asset which `AL` accepts, paired with its metadata. Each asset is only
+    parsed when the iterator reaches it, and unlike [`BNLFile::get_assets`], assets which fail to
+    parse are kept with their error.
+
+    # Examples
+    ```ignore
+    use bnl::asset::texture::Texture;
+
+    for (metadata, texture) in bnl_file.iter_assets::<Texture>() {
+        if let Err(e) = texture {
+            eprintln!("Unable to parse {}: {}", metadata.name(), e);
+        }
+    }
+    ```
+    */
+    pub fn iter_assets<AL: AssetLike>(
+        &self,
+    ) -> impl Iterator<Item = (&AssetMetadata, Result<Asset<AL>, AssetError>)> {
+        self.assets
+            .iter()
+            .filter(|asset| AL::accepts_type(asset.metadata.asset_type()))
+            .map(|asset| (&asset.metadata, asset.parse_as()))
+    }
+
+    /// Returns all assets of a given type from this [`BNLFile`].
     ///
     /// # Examples
+    ///
     /// ```
     /// use bnl::BNLFile;
     /// use bnl::asset::Texture;
     ///
     /// let bnl_file = BNLFile::from_bytes(...);
-    /// let raw_assets = bnl_file.get_raw_assets().expect("Unable to extract.");
+    /// let textures = bnl_file.get_assets::<Texture>();
+    ///
+    /// // Dump all of the textures here
+    /// ```
+    pub fn get_assets<AL: AssetLike>(&self) -> Vec<AL> {
+        let mut assets = Vec::new();
+
+        for asset in &self.assets {
+            let asset_desc = &asset.metadata;
+
+            if !AL::accepts_type(asset_desc.asset_type()) {
+                continue;
+            }
+
+            if let Ok(descriptor) = AL::Descriptor::from_bytes(&asset.descriptor_bytes) {
+                let slices: Vec<&[u8]> = match &asset.resource_chunks {
+                    Some(slices) => slices.iter().map(|slice| slice.as_ref()).collect(),
+                    None => vec![],
+                };
+
+                let vr = VirtualResource::from_slices(&slices);
+
+                if let Ok(asset) = AL::new(&descriptor, &vr) {
+                    assets.push(asset);
+                }
+            }
+        }
+
+        assets
+    }
+
+    /// Retrieves a [`RawAsset`] by name, or None if it can't be found.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use bnl::BNLFile;
+    ///
+    /// let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+    /// let bnl_file = BNLFile::from_bytes(&bytes).expect("Unable to parse BNL.");
+    /// let raw_asset = bnl_file
+    ///     .get_raw_asset("aid_texture_mytexture_a_b")
+    ///     .expect("Unable to extract asset.");
     ///
     /// // Dump the data from the RawAsset
+    /// std::fs::write("./descriptor", raw_asset.descriptor_bytes())
+    ///     .expect("Unable to write descriptor.");
+    /// for (i, chunk) in raw_asset.resource_chunks().into_iter().flatten().enumerate() {
+    ///     std::fs::write(format!("./resource{}", i), chunk).expect("Unable to write resource.");
+    /// }
+    /// ```
+    pub fn get_raw_asset(&self, name: &str) -> Option<&RawAsset> {
+        self.assets
+            .iter()
+            .find(|&asset| asset.metadata.name() == name)
+    }
+
+    pub(crate) fn get_raw_asset_mut(&mut self, name: &str) -> Option<&mut RawAsset> {
+        self.asset_cache.invalidate(name);
+
+        self.assets
+            .iter_mut()
+            .find(|asset| asset.metadata.name() == name)
+    }
+
+    /**
+    Finds every pair of assets whose resource data shares part of the buffer section. Editing one
+    of these assets and writing the file again separates their data, so the other asset keeps the
+    original bytes.
+
+    Only assets which still have the [`DataViewList`] they were read with are compared, see
+    [`RawAsset::data_views`].
+    */
+    pub fn get_overlaps(&self) -> Vec<AssetOverlap> {
+        let with_views: Vec<(&RawAsset, &DataViewList)> = self
+            .assets
+            .iter()
+            .filter_map(|asset| Some((asset, asset.data_views()?)))
+            .collect();
+
+        let mut overlaps = vec![];
+
+        for (i, (first, first_views)) in with_views.iter().enumerate() {
+            for (second, second_views) in &with_views[i + 1..] {
+                let ranges = first_views.overlapping_ranges(second_views);
+
+                if !ranges.is_empty() {
+                    overlaps.push(AssetOverlap {
+                        first: first.name().to_string(),
+                        second: second.name().to_string(),
+                        ranges,
+                    });
+                }
+            }
+        }
+
+        overlaps
+    }
+
+    /// Retrieves all [`RawAsset`] entries.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use bnl::BNLFile;
+    ///
+    /// let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+    /// let bnl_file = BNLFile::from_bytes(&bytes).expect("Unable to parse BNL.");
     ///
-    /// for raw_asset in raw_assets {
-    ///     std::fs::write("./descriptor", &raw_asset.descriptor_bytes)
-    ///                         .expect("Unable to write descriptor.");
+    /// // Dump the data from each RawAsset
+    /// for raw_asset in bnl_file.get_raw_assets() {
+    ///     std::fs::write(format!("./{}_descriptor", raw_asset.name()), raw_asset.descriptor_bytes())
+    ///         .expect("Unable to write descriptor.");
     ///
-    ///     raw_asset.data_slices.iter().enumerate().for_each(|(i, slice)| {
-    ///         std::fs::write(format!("./resource{}", i), &slice)
-    ///                         .expect("Unable to write resource.");;
-    ///     });
+    ///     for (i, chunk) in raw_asset.resource_chunks().into_iter().flatten().enumerate() {
+    ///         std::fs::write(format!("./{}_resource{}", raw_asset.name(), i), chunk)
+    ///             .expect("Unable to write resource.");
+    ///     }
     /// }
     /// ```
     pub fn get_raw_assets(&self) -> &Vec<RawAsset> {
@@ -778,17 +2079,74 @@ impl BNLFile {
         AL: AssetLike,
         F: FnOnce(&mut Asset<AL>) -> Result<(), AssetError>,
     {
-        let raw_asset = self.get_raw_asset_mut(name).ok_or(AssetError::NotFound)?;
+        let raw_asset = self.get_raw_asset(name).ok_or(AssetError::NotFound)?;
 
         let mut asset = raw_asset.clone().to_asset::<AL>()?;
 
         f(&mut asset)?;
 
-        *raw_asset = asset.to_raw_asset()?;
+        self.replace_checked(name, asset.to_raw_asset()?)?;
+        self.unchanged_since_read = false;
 
         Ok(())
     }
 
+    /// The names of the other assets whose resource data shares bytes with the asset `name`
+    pub fn assets_sharing_data(&self, name: &str) -> Vec<String> {
+        let Some(views) = self.get_raw_asset(name).and_then(RawAsset::data_views) else {
+            return vec![];
+        };
+
+        self.assets
+            .iter()
+            .filter(|asset| asset.name() != name)
+            .filter(|asset| {
+                asset
+                    .data_views()
+                    .is_some_and(|other| views.overlaps(other))
+            })
+            .map(|asset| asset.name().to_string())
+            .collect()
+    }
+
+    /// Replaces the asset `name`, following the [`EditPolicy`] if its resource data changes while
+    /// being shared with other assets
+    fn replace_checked(
+        &mut self,
+        name: &str,
+        new_asset: RawAsset,
+    ) -> Result<&mut RawAsset, AssetError> {
+        let shared_with = self.assets_sharing_data(name);
+        let edit_policy = self.edit_policy;
+
+        // The replacement may be named differently, and any asset cached under that name is stale
+        self.asset_cache.invalidate(new_asset.name());
+
+        let asset = self.get_raw_asset_mut(name).ok_or(AssetError::NotFound)?;
+
+        let data_changed = asset.resource_chunks != new_asset.resource_chunks;
+
+        if data_changed && !shared_with.is_empty() && edit_policy == EditPolicy::Error {
+            return Err(AssetError::SharedData {
+                name: name.to_string(),
+                shared_with,
+            });
+        }
+
+        *asset = RawAsset {
+            original_index: asset.original_index,
+            read_chunk_count: asset.read_chunk_count,
+            // Unchanged data is still shared, otherwise the asset now has its own copy
+            data_views: match data_changed {
+                true => None,
+                false => asset.data_views.take(),
+            },
+            ..new_asset
+        };
+
+        Ok(asset)
+    }
+
     pub fn remove_asset(&mut self, name: &str) -> Result<RawAsset, AssetError> {
         let mut index: Option<usize> = None;
 
@@ -800,6 +2158,8 @@ impl BNLFile {
         }
 
         if let Some(ind) = index {
+            self.asset_cache.invalidate(name);
+            self.unchanged_since_read = false;
             return Ok(self.assets.remove(ind));
         }
 
@@ -816,6 +2176,7 @@ impl BNLFile {
     }
     */
 
+    /// Serialises `new_asset` and appends it, see [`BNLFile::append_raw_asset`]
     pub fn append_asset<AL: AssetLike>(
         &mut self,
         metadata: AssetMetadata,
@@ -825,118 +2186,389 @@ impl BNLFile {
             metadata,
             new_asset.get_descriptor().to_bytes()?,
             new_asset.get_resource_chunks(),
-        ));
-
-        Ok(())
+        ))
     }
 
-    pub fn append_raw_asset(&mut self, new_raw_asset: RawAsset) {
+    /**
+    Appends an asset, after checking it according to the [`AppendValidation`] of the file.
+
+    # Errors
+    - [`AssetError::Duplicate`] when an asset with the same name already exists
+    - [`AssetError::TypeMismatch`] when the validation is [`AppendValidation::Strict`] and the
+      descriptor can't be read as the type in the asset's metadata
+    */
+    pub fn append_raw_asset(&mut self, new_raw_asset: RawAsset) -> Result<(), AssetError> {
+        if self.append_validation != AppendValidation::None
+            && self.get_raw_asset(new_raw_asset.name()).is_some()
+        {
+            return Err(AssetError::Duplicate(new_raw_asset.name().to_string()));
+        }
+
+        if self.append_validation == AppendValidation::Strict
+            && check_descriptor(
+                new_raw_asset.metadata().asset_type(),
+                new_raw_asset.descriptor_bytes(),
+            )
+            .is_err()
+        {
+            return Err(AssetError::TypeMismatch);
+        }
+
+        self.asset_cache.invalidate(new_raw_asset.name());
         self.assets.push(new_raw_asset);
+        self.unchanged_since_read = false;
+
+        Ok(())
     }
 
     /// Inserts a RawAsset into a BNLFile, replacing it if it already exists.
     pub fn upsert_raw_asset(&mut self, new_raw_asset: RawAsset) {
+        self.asset_cache.invalidate(new_raw_asset.name());
+        self.unchanged_since_read = false;
+
         if let Some(asset) = self
             .assets
             .iter_mut()
             .find(|asset| asset.name() == new_raw_asset.name())
         {
-            *asset = new_raw_asset;
+            *asset = RawAsset {
+                original_index: asset.original_index,
+                read_chunk_count: asset.read_chunk_count,
+                ..new_raw_asset
+            };
         } else {
             self.assets.push(new_raw_asset);
         }
     }
-}
 
-#[derive(Debug)]
-pub enum BNLError {
-    /// The ZLIB portion of the BNL file could not be decompressed successfully.
-    DecompressionFailure,
-    /// An error occurred when parsing the [`AssetDescription`] data of the BNL file.
-    DataReadError(String),
-}
+    /// Replaces an existing asset and marks it to be written in place by
+    /// [`BNLFile::write_patched`].
+    ///
+    /// # Errors
+    /// - [`AssetError::NotFound`] when no asset named `name` exists
+    /// - [`AssetError::SharedData`] when the resource data changes, but is shared with other
+    ///   assets and the [`EditPolicy`] is [`EditPolicy::Error`]
+    pub fn patch_asset(&mut self, name: &str, raw_asset: RawAsset) -> Result<(), AssetError> {
+        let asset = self.replace_checked(name, raw_asset)?;
 
-impl From<std::io::Error> for BNLError {
-    fn from(value: std::io::Error) -> Self {
-        BNLError::DataReadError(format!("File error: {}", value))
-    }
-}
+        // Track the name the asset will be written under, which may differ from `name`
+        let new_name = asset.name().to_string();
 
-impl From<miniz_oxide::inflate::DecompressError> for BNLError {
-    fn from(_: miniz_oxide::inflate::DecompressError) -> Self {
-        BNLError::DecompressionFailure
-    }
-}
+        self.patched_assets.retain(|patched| patched != name);
+        self.patched_assets.push(new_name);
 
-impl std::fmt::Display for BNLError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                BNLError::DecompressionFailure => "Decompression failure".to_owned(),
-                BNLError::DataReadError(e) => format!("Data read error: {e}"),
-            }
-        )
+        Ok(())
     }
-}
 
-pub fn get_asset_names_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>, BNLError> {
-    let file = File::open(path.as_ref())?;
+    /**
+    Writes the assets changed by [`BNLFile::patch_asset`] over the top of `original_bytes`, which
+    must be the file this [`BNLFile`] was loaded from. Every byte belonging to an untouched asset
+    keeps its original offset.
 
-    let mut reader = BufReader::new(file);
+    Patching in place requires each new descriptor and resource chunk to fit inside the region it
+    replaces, the number of resource chunks to be unchanged, and the region not to be shared with
+    another asset. Any other change since the file was loaded, such as adding, removing or
+    modifying assets any other way, or changing the variant or write options, also needs the whole
+    file to be rewritten. When any of these don't hold, this falls back to a full
+    [`BNLFile::to_bytes`].
 
-    {
-        /*
-        reader.read_exact(&mut header.unknown_2)?;
-        header.asset_desc_loc = DataView::from_reader(&mut reader)?;
-        header.buffer_views_loc = DataView::from_reader(&mut reader)?;
-        header.buffer_loc = DataView::from_reader(&mut reader)?;
-        header.descriptor_loc = DataView::from_reader(&mut reader)?;
+    # Errors
+    - [`BNLError::DecompressionFailure`] when `original_bytes` could not be decompressed
+    - [`BNLError::DataReadError`] when the sections of `original_bytes` could not be read
+    */
+    pub fn write_patched(&mut self, original_bytes: &[u8]) -> Result<Vec<u8>, BNLError> {
+        let patched = match self.patch_in_place(original_bytes)? {
+            Some(bytes) => bytes,
+            None => self.to_bytes(),
+        };
 
-        let mut compressed_bytes = vec![0u8; header.asset_desc_loc.size as usize];
-        reader.seek(SeekFrom::Start(header.asset_desc_loc.offset as u64))?;
-        reader.read_exact(&mut compressed_bytes)?;
-        */
+        self.patched_assets.clear();
+        self.unchanged_since_read = true;
+
+        Ok(patched)
     }
 
-    let mut header = BNLHeader {
-        file_count: reader.read_u16::<LittleEndian>()?,
-        flags: reader.read_u8()?,
-        ..Default::default()
-    };
+    fn patch_in_place(&self, original_bytes: &[u8]) -> Result<Option<Vec<u8>>, BNLError> {
+        if !self.unchanged_since_read {
+            return Ok(None);
+        }
 
-    reader.read_exact(&mut header.unknown_2)?;
+        let variant = self.variant;
+        let header_size = variant.header_size();
 
-    header.asset_desc_loc = DataView::from_reader(&mut reader)?;
-    header.buffer_views_loc = DataView::from_reader(&mut reader)?;
-    header.buffer_loc = DataView::from_reader(&mut reader)?;
-    header.descriptor_loc = DataView::from_reader(&mut reader)?;
+        if original_bytes.len() < header_size {
+            return Err(BNLError::DataReadError(format!(
+                "Length of BNL file must be at least {} bytes (received {})",
+                header_size,
+                original_bytes.len()
+            )));
+        }
 
-    let mut end_bytes = vec![0u8; header.asset_desc_loc.size as usize];
-    reader.read_exact(&mut end_bytes)?;
+        let header = BNLHeader::from_reader(&mut Cursor::new(original_bytes), variant)?;
 
-    let decompressed_bytes = match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(
-        &end_bytes,
-        size_of::<AssetDescription>() * header.file_count as usize,
-    ) {
-        Ok(v) => v,
-        Err(e) => match e.status {
-            // Too much input is ok
-            TINFLStatus::HasMoreOutput => e.output,
-            TINFLStatus::FailedCannotMakeProgress
-            | TINFLStatus::BadParam
-            | TINFLStatus::Adler32Mismatch
-            | TINFLStatus::Failed
-            | TINFLStatus::Done
-            | TINFLStatus::NeedsMoreInput => {
-                return Err(BNLError::DecompressionFailure);
-            }
-        },
-    };
+        // Only the assets which were in the original file can be written into it
+        if header.file_count as usize != self.assets.len() {
+            return Ok(None);
+        }
 
-    decompressed_bytes
-        .chunks_exact(size_of::<AssetDescription>())
+        let mut bytes = original_bytes[..header_size].to_vec();
+        bytes.extend(zlib::decompress(&original_bytes[header_size..])?);
+
+        let section_range = |loc: &DataView| -> Result<Range<usize>, BNLError> {
+            let range: Range<usize> = loc.offset as usize..loc.offset as usize + loc.size as usize;
+
+            match range.end <= bytes.len() {
+                true => Ok(range),
+                false => Err(BNLError::DataReadError(format!(
+                    "Section [{}, {}) is out of bounds for a BNL of {} bytes",
+                    range.start,
+                    range.end,
+                    bytes.len()
+                ))),
+            }
+        };
+
+        let asset_desc_range = section_range(&header.asset_desc_loc)?;
+        let buffer_views_range = section_range(&header.buffer_views_loc)?;
+        let buffer_range = section_range(&header.buffer_loc)?;
+        let descriptor_range = section_range(&header.descriptor_loc)?;
+
+        let mut descriptions = bytes[asset_desc_range.clone()]
+            .chunks_exact(ASSET_DESCRIPTION_SIZE)
+            .map(|chunk| AssetDescription::from_bytes_as(chunk, variant))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let dataview_lists = descriptions
+            .iter()
+            .map(|description| -> Result<Option<DataViewList>, BNLError> {
+                if description.resource_size == 0 {
+                    return Ok(None);
+                }
+
+                let dvl_bytes = bytes[buffer_views_range.clone()]
+                    .get(description.dataview_list_ptr as usize..)
+                    .ok_or_else(|| {
+                        BNLError::DataReadError(
+                            "BufferView list pointer is out of bounds.".to_string(),
+                        )
+                    })?;
+
+                DataViewList::from_bytes_as(dvl_bytes, variant)
+                    .map(Some)
+                    .map_err(|_| BNLError::DataReadError("Unable to read BufferViews.".to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for name in &self.patched_assets {
+            let Some(asset) = self.get_raw_asset(name) else {
+                return Ok(None);
+            };
+
+            let Some(index) = descriptions.iter().position(|desc| desc.name() == name) else {
+                return Ok(None);
+            };
+
+            // Descriptor
+            let old_descriptor = DataView {
+                offset: descriptions[index].descriptor_ptr,
+                size: descriptions[index].descriptor_size,
+            };
+
+            let descriptor_shared = descriptions.iter().enumerate().any(|(i, desc)| {
+                i != index
+                    && old_descriptor.overlaps(&DataView {
+                        offset: desc.descriptor_ptr,
+                        size: desc.descriptor_size,
+                    })
+            });
+
+            if asset.descriptor_bytes.len() > old_descriptor.size as usize || descriptor_shared {
+                return Ok(None);
+            }
+
+            let start = descriptor_range.start + old_descriptor.offset as usize;
+            let Some(region) = bytes.get_mut(start..start + old_descriptor.size as usize) else {
+                return Ok(None);
+            };
+
+            region.fill(0x00);
+            region[..asset.descriptor_bytes.len()].copy_from_slice(&asset.descriptor_bytes);
+
+            descriptions[index].descriptor_size = asset.descriptor_bytes.len() as u32;
+
+            // Resources
+            match (&asset.resource_chunks, &dataview_lists[index]) {
+                (None, None) => {}
+                (Some(chunks), Some(dvl)) if chunks.len() == dvl.views.len() => {
+                    let mut new_dvl = dvl.clone();
+
+                    for (chunk, view) in chunks.iter().zip(new_dvl.views.iter_mut()) {
+                        let view_shared = dataview_lists
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| *i != index)
+                            .filter_map(|(_, other)| other.as_ref())
+                            .any(|other| other.views.iter().any(|v| v.overlaps(view)));
+
+                        if chunk.len() > view.size as usize || view_shared {
+                            return Ok(None);
+                        }
+
+                        let start = buffer_range.start + view.offset as usize;
+                        let Some(region) = bytes.get_mut(start..start + view.size as usize) else {
+                            return Ok(None);
+                        };
+
+                        region.fill(0x00);
+                        region[..chunk.len()].copy_from_slice(chunk);
+
+                        view.size = chunk.len() as u32;
+                    }
+
+                    let start =
+                        buffer_views_range.start + descriptions[index].dataview_list_ptr as usize;
+                    let dvl_bytes = new_dvl.to_bytes_as(variant);
+
+                    bytes[start..start + dvl_bytes.len()].copy_from_slice(&dvl_bytes);
+
+                    descriptions[index].resource_size = new_dvl.bytes_required() as u32;
+                }
+                _ => return Ok(None),
+            }
+
+            descriptions[index].metadata = asset.metadata.clone();
+
+            let start = asset_desc_range.start + index * ASSET_DESCRIPTION_SIZE;
+            bytes[start..start + ASSET_DESCRIPTION_SIZE]
+                .copy_from_slice(&descriptions[index].to_bytes_as(variant));
+        }
+
+        let compressed_bytes = zlib::compress(&bytes[header_size..], ZLIB_COMPRESSION_LEVEL);
+
+        let mut patched = original_bytes[..header_size].to_vec();
+        patched.extend_from_slice(&compressed_bytes);
+
+        Ok(Some(patched))
+    }
+}
+
+/// Async versions of [`BNLFile::from_bytes`] and [`BNLFile::to_bytes`]. Decompression runs on
+/// tokio's blocking thread pool, and compression is marked as blocking on multi-threaded runtimes,
+/// so the other tasks of a runtime thread are moved off of it while it runs.
+#[cfg(feature = "tokio")]
+impl BNLFile {
+    /// Reads `reader` to its end, then parses it as a BNL file
+    ///
+    /// # Errors
+    /// See [`BNLFile::from_bytes`]
+    pub async fn from_async_reader<R: tokio::io::AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<Self, BNLError> {
+        use tokio::io::AsyncReadExt;
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        tokio::task::spawn_blocking(move || BNLFile::from_bytes(&bytes))
+            .await
+            .map_err(|e| BNLError::TaskFailed(e.to_string()))?
+    }
+
+    /// Serialises the file with [`BNLFile::to_bytes`] and writes it to `writer`.
+    ///
+    /// A current thread runtime can't hand its other tasks to another thread, so on one of those
+    /// the file is serialised in place.
+    pub async fn write_async<W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), BNLError> {
+        use tokio::{io::AsyncWriteExt, runtime::RuntimeFlavor};
+
+        let bytes = match tokio::runtime::Handle::current().runtime_flavor() {
+            RuntimeFlavor::MultiThread => tokio::task::block_in_place(|| self.to_bytes()),
+            _ => self.to_bytes(),
+        };
+
+        writer.write_all(&bytes).await?;
+        writer.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum BNLError {
+    /// The ZLIB portion of the BNL file could not be decompressed successfully.
+    DecompressionFailure,
+    /// An error occurred when parsing the [`AssetDescription`] data of the BNL file.
+    DataReadError(String),
+    /// The blocking task an async function ran its work on panicked or was cancelled
+    TaskFailed(String),
+}
+
+impl From<std::io::Error> for BNLError {
+    fn from(value: std::io::Error) -> Self {
+        BNLError::DataReadError(format!("File error: {}", value))
+    }
+}
+
+impl From<zlib::ZlibError> for BNLError {
+    fn from(_: zlib::ZlibError) -> Self {
+        BNLError::DecompressionFailure
+    }
+}
+
+impl std::fmt::Display for BNLError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BNLError::DecompressionFailure => "Decompression failure".to_owned(),
+                BNLError::DataReadError(e) => format!("Data read error: {e}"),
+                BNLError::TaskFailed(e) => format!("Blocking task failed: {e}"),
+            }
+        )
+    }
+}
+
+#[cfg(feature = "fs")]
+pub fn get_asset_names_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>, BNLError> {
+    let file = File::open(path.as_ref())?;
+
+    let mut reader = BufReader::new(file);
+
+    {
+        /*
+        reader.read_exact(&mut header.unknown_2)?;
+        header.asset_desc_loc = DataView::from_reader(&mut reader)?;
+        header.buffer_views_loc = DataView::from_reader(&mut reader)?;
+        header.buffer_loc = DataView::from_reader(&mut reader)?;
+        header.descriptor_loc = DataView::from_reader(&mut reader)?;
+
+        let mut compressed_bytes = vec![0u8; header.asset_desc_loc.size as usize];
+        reader.seek(SeekFrom::Start(header.asset_desc_loc.offset as u64))?;
+        reader.read_exact(&mut compressed_bytes)?;
+        */
+    }
+
+    let mut header_bytes = vec![0u8; BnlVariant::default().header_size()];
+    reader.read_exact(&mut header_bytes)?;
+
+    let variant = BnlVariant::detect(&header_bytes).unwrap_or_default();
+    let header = BNLHeader::from_reader(&mut Cursor::new(&header_bytes), variant)?;
+
+    let mut end_bytes = vec![0u8; header.asset_desc_loc.size as usize];
+    reader.read_exact(&mut end_bytes)?;
+
+    // Too much input is ok
+    let decompressed_bytes = zlib::decompress_prefix(
+        &end_bytes,
+        size_of::<AssetDescription>() * header.file_count as usize,
+    )?;
+
+    decompressed_bytes
+        .chunks_exact(size_of::<AssetDescription>())
         .map(|chunk| -> Result<String, BNLError> {
             let mut string_bytes = Vec::new();
             chunk
@@ -956,38 +2588,23 @@ pub fn get_asset_names_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>, BNLE
 }
 
 pub fn get_aid_list(compressed_bnl: &[u8]) -> Result<Vec<String>, BNLError> {
-    if compressed_bnl.len() < 40 {
+    let variant = BnlVariant::detect(compressed_bnl).unwrap_or_default();
+    let header_size = variant.header_size();
+
+    if compressed_bnl.len() < header_size {
         return Err(BNLError::DataReadError(format!(
-            "Length of BNL file must be at least 40 bytes (received {})",
+            "Length of BNL file must be at least {} bytes (received {})",
+            header_size,
             compressed_bnl.len()
         )));
     }
 
-    let mut cur = Cursor::new(compressed_bnl);
-
-    let mut header = BNLHeader {
-        file_count: cur.read_u16::<LittleEndian>()?,
-        flags: cur.read_u8()?,
-        ..Default::default()
-    };
-
-    cur.read_exact(&mut header.unknown_2)?;
-
-    header.asset_desc_loc = DataView::from_reader(&mut cur)?;
-    header.buffer_views_loc = DataView::from_reader(&mut cur)?;
-    header.buffer_loc = DataView::from_reader(&mut cur)?;
-    header.descriptor_loc = DataView::from_reader(&mut cur)?;
+    let header = BNLHeader::from_reader(&mut Cursor::new(compressed_bnl), variant)?;
 
-    let asset_descriptions = match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(
-        &compressed_bnl[40..],
+    let asset_descriptions = zlib::decompress_prefix(
+        &compressed_bnl[header_size..],
         header.asset_desc_loc.size as usize,
-    ) {
-        Ok(v) => v,
-        Err(miniz_oxide::inflate::DecompressError { status, output }) => match status {
-            TINFLStatus::HasMoreOutput => output,
-            _ => return Err(BNLError::DecompressionFailure),
-        },
-    };
+    )?;
 
     Ok(asset_descriptions
         .chunks_exact(size_of::<AssetDescription>())
@@ -1010,18 +2627,20 @@ pub fn get_aid_list(compressed_bnl: &[u8]) -> Result<Vec<String>, BNLError> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn new_bnl_from_raw() -> Result<(), String> {
-        let tex_descriptor = include_bytes!("asset/test_data/texture0_descriptor").to_vec();
-        let tex_image_bytes = include_bytes!("asset/test_data/texture0_resource0").to_vec();
-
-        let metadata = AssetMetadata::new("aid_sometexture", AssetType::ResTexture, 0, 0);
-        let raw_asset = RawAsset::new(metadata, tex_descriptor, Some(vec![tex_image_bytes]));
+    use proptest::prelude::*;
 
-        let mut new_bnl = BNLFile::default();
-        new_bnl.append_raw_asset(raw_asset);
+    use crate::{
+        asset::texture::{Texture, TextureExportOptions, TextureRef},
+        d3d::{D3DFormat, StandardFormat},
+        json::AssetJson,
+        testing::{BnlFixture, SAMPLE_TEXTURE_DESCRIPTOR, SAMPLE_TEXTURE_RESOURCE},
+    };
 
-        let serialised = new_bnl.to_bytes();
+    #[test]
+    fn new_bnl_from_raw() -> Result<(), String> {
+        let serialised = BnlFixture::new()
+            .sample_texture("aid_sometexture")
+            .to_bytes();
         let deserialised = BNLFile::from_bytes(&serialised)
             .map_err(|_| "Failed to deserialise the BNL file which was just created in memory.")?;
 
@@ -1038,4 +2657,1049 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn asset_flags_bits() {
+        let mut flags = AssetFlags::default();
+
+        flags.set(0, true);
+        flags.set(31, true);
+        assert_eq!(flags.bits(), 0x8000_0001);
+        assert_eq!(flags.set_bits().collect::<Vec<_>>(), [0, 31]);
+
+        flags.set(0, false);
+        assert_eq!(flags.bits(), 0x8000_0000);
+        assert!(flags.is_set(31));
+        assert!(!flags.is_set(0));
+
+        // Out of range bits are never set, and setting them does nothing
+        flags.set(32, true);
+        assert!(!flags.is_set(32));
+        assert_eq!(flags.bits(), 0x8000_0000);
+    }
+
+    #[test]
+    fn asset_metadata_flags() -> Result<(), String> {
+        let mut metadata = AssetMetadata::new("aid_flags", AssetType::ResTexture, 0, 0);
+
+        let mut flags_2 = metadata.flags_2();
+        flags_2.set(31, true);
+        metadata.set_flags_2(flags_2);
+        metadata.set_flags_1(AssetFlags::from_bits(0x0102_0304));
+
+        assert_eq!(
+            (metadata.unk_1(), metadata.unk_2()),
+            (0x0102_0304, 0x8000_0000)
+        );
+
+        let bytes = BnlFixture::new()
+            .raw_asset(RawAsset::new(
+                metadata,
+                vec![0; 0x10],
+                Some(vec![vec![1, 2, 3]]),
+            ))
+            .to_bytes();
+        let read = BNLFile::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        let metadata = read
+            .get_raw_asset("aid_flags")
+            .ok_or("aid_flags is missing")?
+            .metadata();
+        assert_eq!(metadata.flags_1(), AssetFlags::from_bits(0x0102_0304));
+        assert!(metadata.flags_2().is_set(31));
+        assert_eq!(metadata.flags_2().set_bits().count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn asset_metadata_flags_in_dir() -> Result<(), String> {
+        let raw_asset = RawAsset::new(
+            AssetMetadata::new("aid_flags", AssetType::ResTexture, 0x8000_0001, 0xdead_beef),
+            vec![0; 0x10],
+            Some(vec![vec![1, 2, 3]]),
+        );
+
+        let out_dir = std::env::temp_dir().join(format!("bnl_flags_{}", std::process::id()));
+        raw_asset.to_dir(&out_dir).map_err(|e| e.to_string())?;
+        let read = RawAsset::from_dir(&out_dir).map_err(|e| e.to_string());
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let read = read?;
+        assert_eq!(read.metadata().flags_1().bits(), 0x8000_0001);
+        assert_eq!(read.metadata().flags_2().bits(), 0xdead_beef);
+
+        Ok(())
+    }
+
+    #[test]
+    fn section_coverage_of_deduped_chunks() -> Result<(), String> {
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let mut bnl = BnlFixture::new()
+            .texture("aid_texture_a", 8, 8, dxt1)
+            .texture("aid_texture_b", 8, 8, dxt1)
+            .build();
+
+        bnl.set_write_options(BNLWriteOptions {
+            dedup_chunks: true,
+            ..Default::default()
+        });
+
+        let decompressed =
+            DecompressedBnl::from_bytes(&bnl.to_bytes()).map_err(|e| e.to_string())?;
+        let coverage = decompressed.coverage().map_err(|e| e.to_string())?;
+
+        assert_eq!(coverage.len(), 4);
+
+        // The packed layout leaves no gaps
+        assert!(
+            coverage
+                .iter()
+                .all(|section| section.unreferenced.is_empty())
+        );
+
+        for section in &coverage {
+            match section.section {
+                BnlSection::Buffer => {
+                    assert_eq!(section.shared.len(), 1);
+                    assert_eq!(section.shared_bytes(), section.size);
+                    assert_eq!(
+                        section.shared[0].asset_names,
+                        ["aid_texture_a", "aid_texture_b"]
+                    );
+                }
+                _ => assert!(section.shared.is_empty()),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cached_assets_invalidated() -> Result<(), String> {
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let mut bnl = BnlFixture::new()
+            .texture("aid_texture_a", 8, 8, dxt1)
+            .texture("aid_texture_b", 8, 8, dxt1)
+            .build();
+
+        let first = bnl
+            .get_asset_cached::<Texture>("aid_texture_a")
+            .map_err(|e| e.to_string())?;
+        let second = bnl
+            .get_asset_cached::<Texture>("aid_texture_a")
+            .map_err(|e| e.to_string())?;
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(bnl.get_asset_cached::<Texture>("aid_missing").is_err());
+
+        bnl.get_asset_cached::<Texture>("aid_texture_b")
+            .map_err(|e| e.to_string())?;
+        assert_eq!(bnl.cached_asset_count(), 2);
+
+        let replacement = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .build()
+            .get_raw_asset("aid_texture_a")
+            .cloned()
+            .ok_or("Missing replacement")?;
+        bnl.upsert_raw_asset(replacement);
+
+        // Only the replaced asset is parsed again
+        assert_eq!(bnl.cached_asset_count(), 1);
+
+        let replaced = bnl
+            .get_asset_cached::<Texture>("aid_texture_a")
+            .map_err(|e| e.to_string())?;
+        assert_eq!(replaced.asset().descriptor().width(), 16);
+
+        bnl.assets_mut();
+        assert_eq!(bnl.cached_asset_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn iter_assets_keeps_errors() -> Result<(), String> {
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let mut broken = BnlFixture::new()
+            .texture("aid_texture_b", 8, 8, dxt1)
+            .build()
+            .get_raw_asset("aid_texture_b")
+            .cloned()
+            .ok_or("Missing texture")?;
+        broken.descriptor_bytes_mut().truncate(4);
+
+        let bnl = BnlFixture::new()
+            .texture("aid_texture_a", 8, 8, dxt1)
+            .raw_asset(broken)
+            .loctext("aid_loctext_a", &[("key", "value")])
+            .build();
+
+        let results: Vec<(String, bool)> = bnl
+            .iter_assets::<Texture>()
+            .map(|(metadata, texture)| (metadata.name().to_string(), texture.is_ok()))
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                ("aid_texture_a".to_string(), true),
+                ("aid_texture_b".to_string(), false)
+            ]
+        );
+        assert_eq!(bnl.get_assets::<Texture>().len(), 1);
+
+        let error = bnl
+            .get_asset::<Texture>("aid_texture_b")
+            .err()
+            .ok_or("Truncated texture parsed")?;
+        assert_eq!(error.asset_name(), Some("aid_texture_b"));
+        assert!(matches!(
+            error,
+            AssetError::InAsset {
+                asset_type: AssetType::ResTexture,
+                stage: AssetStage::Descriptor,
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("aid_texture_b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_any_by_type() -> Result<(), String> {
+        let bnl = BnlFixture::new()
+            .texture(
+                "aid_texture_a",
+                8,
+                8,
+                D3DFormat::Standard(StandardFormat::DXT1),
+            )
+            .loctext("aid_loctext_a", &[("key", "value")])
+            .build();
+
+        match bnl.parse_any("aid_texture_a").map_err(|e| e.to_string())? {
+            AnyAsset::Texture(texture) => assert_eq!(texture.asset().descriptor().width(), 8),
+            other => return Err(format!("Texture parsed as {:?}", other.asset_type())),
+        }
+
+        let loctext = bnl.parse_any("aid_loctext_a").map_err(|e| e.to_string())?;
+        assert!(!loctext.is_parsed());
+        assert_eq!(loctext.asset_type(), AssetType::ResLoctext);
+
+        let raw = loctext.to_raw_asset().map_err(|e| e.to_string())?;
+        assert_eq!(
+            raw.resource_chunks(),
+            bnl.get_raw_asset("aid_loctext_a")
+                .and_then(|asset| asset.resource_chunks())
+        );
+
+        assert!(matches!(
+            bnl.parse_any("aid_missing"),
+            Err(AssetError::NotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunk_count_roundtrip() -> Result<(), String> {
+        let mut raw_asset = RawAsset::new(
+            AssetMetadata::new("aid_chunks", AssetType::ResTexture, 0, 0),
+            vec![0; 4],
+            Some(vec![vec![1; 8]]),
+        );
+        assert_eq!(raw_asset.chunk_count(), 1);
+
+        // As if the asset was read with an unusual chunk count
+        raw_asset.read_chunk_count = Some((5, 1));
+
+        let mut bnl = BNLFile::default();
+        bnl.append_raw_asset(raw_asset).map_err(|e| e.to_string())?;
+
+        let mut read = BNLFile::from_bytes(&bnl.to_bytes()).map_err(|e| format!("{:?}", e))?;
+        let asset = &mut read.assets_mut()[0];
+        assert_eq!(asset.chunk_count(), 5);
+        assert_eq!(asset.stored_chunk_count(), Some(5));
+
+        // Extracting to JSON keeps the count
+        let from_json = AssetJson::from_raw_asset(asset, true)
+            .to_raw_asset()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(from_json.chunk_count(), 5);
+
+        // Once the chunks change, the count follows them
+        asset
+            .resource_chunks_mut()
+            .get_or_insert_default()
+            .push(vec![2; 8]);
+        assert_eq!(asset.chunk_count(), 2);
+        assert_eq!(asset.stored_chunk_count(), None);
+
+        let reread = BNLFile::from_bytes(&read.to_bytes()).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(reread.get_raw_assets()[0].chunk_count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_asset_content_hash() -> Result<(), String> {
+        let asset = |name: &str, asset_type: AssetType, chunks: &[&[u8]]| {
+            RawAsset::new(
+                AssetMetadata::new(name, asset_type, 0, 0),
+                vec![1, 2, 3, 4],
+                Some(chunks.iter().map(|chunk| chunk.to_vec()).collect()),
+            )
+        };
+
+        let original = asset("aid_a", AssetType::ResTexture, &[b"ab", b"c"]);
+
+        // Names and unknown fields don't matter
+        let mut renamed = asset("aid_b", AssetType::ResTexture, &[b"ab", b"c"]);
+        renamed.metadata_mut().set_unk_1(0xffff);
+        assert_eq!(original.content_hash(), renamed.content_hash());
+
+        // But the type and chunk boundaries do
+        assert_ne!(
+            original.content_hash(),
+            asset("aid_a", AssetType::ResModel, &[b"ab", b"c"]).content_hash()
+        );
+        assert_ne!(
+            original.content_hash(),
+            asset("aid_a", AssetType::ResTexture, &[b"a", b"bc"]).content_hash()
+        );
+
+        assert_eq!(original.chunk_hashes().len(), 2);
+        assert_eq!(original.chunk_hashes()[1], sha256_hex(b"c"));
+        assert_eq!(original.descriptor_hash(), sha256_hex(&[1, 2, 3, 4]));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_roundtrip() -> Result<(), String> {
+        let bnl = crate::testing::BnlFixture::new()
+            .script("aid_script_test", "WaitToMoveOn\nEndScript")
+            .build();
+
+        // Serialising only moves off of the runtime thread on a multi-threaded runtime
+        for mut builder in [
+            tokio::runtime::Builder::new_current_thread(),
+            tokio::runtime::Builder::new_multi_thread(),
+        ] {
+            let runtime = builder.build().map_err(|e| e.to_string())?;
+
+            runtime.block_on(async {
+                let mut written = vec![];
+                bnl.write_async(&mut written)
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                assert_eq!(written, bnl.to_bytes());
+
+                let read = BNLFile::from_async_reader(&mut written.as_slice())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                assert!(read.get_raw_asset("aid_script_test").is_some());
+
+                Ok::<_, String>(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn borrowed_texture_from_decompressed() -> Result<(), String> {
+        let new_bnl = BnlFixture::new().sample_texture("aid_sometexture").build();
+
+        let serialised = new_bnl.to_bytes();
+        let decompressed = DecompressedBnl::from_bytes(&serialised).map_err(|e| e.to_string())?;
+
+        let raw_assets = decompressed.raw_assets().map_err(|e| e.to_string())?;
+        assert_eq!(raw_assets.len(), 1);
+
+        let texture_ref: TextureRef = raw_assets[0].to_asset_ref().map_err(|e| e.to_string())?;
+        let texture: Asset<Texture> = raw_assets[0].to_asset().map_err(|e| e.to_string())?;
+
+        assert_eq!(texture_ref.bytes(), texture.asset().bytes());
+        assert!(format!("{:?}", texture_ref).contains("borrowed: true"));
+
+        // Copying the borrowed asset gives back the original data
+        assert_eq!(
+            raw_assets[0].to_raw_asset().descriptor_bytes(),
+            new_bnl.assets[0].descriptor_bytes()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn asset_order_controls() -> Result<(), String> {
+        let mut new_bnl = BnlFixture::new()
+            .sample_texture("aid_texture_c")
+            .sample_texture("aid_texture_a")
+            .sample_texture("aid_texture_b")
+            .build();
+        new_bnl.set_asset_order(AssetOrder::Original);
+
+        let original = new_bnl.to_bytes();
+        let mut bnl = BNLFile::from_bytes(&original).map_err(|e| e.to_string())?;
+
+        let names = |bnl: &BNLFile| -> Vec<String> {
+            bnl.get_raw_assets()
+                .iter()
+                .map(|asset| asset.name().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            bnl.get_raw_assets()
+                .iter()
+                .map(RawAsset::original_index)
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(1), Some(2)]
+        );
+
+        bnl.sort_assets_by(|a, b| b.name().cmp(a.name()));
+        assert_eq!(bnl.asset_order(), AssetOrder::Original);
+        assert_eq!(
+            names(&bnl),
+            vec!["aid_texture_c", "aid_texture_b", "aid_texture_a"]
+        );
+
+        bnl.assets_mut().swap(0, 2);
+        assert_eq!(
+            names(&bnl),
+            vec!["aid_texture_a", "aid_texture_b", "aid_texture_c"]
+        );
+
+        // Replacing an asset keeps its place in the file order
+        bnl.upsert_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_texture_c", AssetType::ResTexture, 0, 0),
+            SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+            Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+        ));
+
+        bnl.set_asset_order(AssetOrder::FileOrder);
+        assert_eq!(bnl.to_bytes(), original);
+
+        // Writing leaves the assets in place until the layout is committed
+        assert_eq!(
+            names(&bnl),
+            vec!["aid_texture_a", "aid_texture_b", "aid_texture_c"]
+        );
+
+        bnl.commit_layout();
+        assert_eq!(
+            names(&bnl),
+            vec!["aid_texture_c", "aid_texture_a", "aid_texture_b"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_identical_chunks() -> Result<(), String> {
+        let mut new_bnl = BnlFixture::new()
+            .sample_texture("aid_texture_a")
+            .sample_texture("aid_texture_b")
+            .sample_texture("aid_texture_c")
+            .build();
+
+        let duplicates = new_bnl.find_duplicate_chunks();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].locations.len(), 3);
+        assert_eq!(
+            duplicates[0].wasted_bytes(),
+            SAMPLE_TEXTURE_RESOURCE.len() * 2
+        );
+
+        let packed = new_bnl.to_bytes();
+        new_bnl.commit_layout();
+        let packed_buffer_size = new_bnl.header.buffer_loc.size;
+
+        new_bnl.set_write_options(BNLWriteOptions {
+            dedup_chunks: true,
+            ..Default::default()
+        });
+
+        let deduped = new_bnl.to_bytes();
+        new_bnl.commit_layout();
+        assert_eq!(
+            new_bnl.header.buffer_loc.size as usize,
+            packed_buffer_size as usize - SAMPLE_TEXTURE_RESOURCE.len() * 2
+        );
+
+        // Both layouts must read back as the same assets
+        let packed = BNLFile::from_bytes(&packed).map_err(|e| e.to_string())?;
+        let deduped = BNLFile::from_bytes(&deduped).map_err(|e| e.to_string())?;
+
+        for (a, b) in packed.get_raw_assets().iter().zip(deduped.get_raw_assets()) {
+            assert_eq!(a.resource_chunks(), b.resource_chunks());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_identical_descriptors() -> Result<(), String> {
+        let mut new_bnl = BnlFixture::new()
+            .sample_texture_with_chunks("aid_texture_a", None)
+            .sample_texture_with_chunks("aid_texture_b", None)
+            .sample_texture_with_chunks("aid_texture_c", None)
+            .build();
+
+        let shared = new_bnl.find_shared_descriptors();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].size, SAMPLE_TEXTURE_DESCRIPTOR.len());
+        assert_eq!(
+            shared[0].asset_names,
+            vec!["aid_texture_a", "aid_texture_b", "aid_texture_c"]
+        );
+        assert!(
+            new_bnl
+                .validate()
+                .contains(&ValidationIssue::SharedDescriptor(shared[0].clone()))
+        );
+
+        let packed = new_bnl.to_bytes();
+        new_bnl.commit_layout();
+        assert_eq!(
+            new_bnl.header.descriptor_loc.size as usize,
+            SAMPLE_TEXTURE_DESCRIPTOR.len() * 3
+        );
+
+        new_bnl.set_write_options(BNLWriteOptions {
+            dedup_descriptors: true,
+            ..Default::default()
+        });
+
+        let deduped = new_bnl.to_bytes();
+        new_bnl.commit_layout();
+        assert_eq!(
+            new_bnl.header.descriptor_loc.size as usize,
+            SAMPLE_TEXTURE_DESCRIPTOR.len()
+        );
+
+        let packed = BNLFile::from_bytes(&packed).map_err(|e| e.to_string())?;
+        let deduped = BNLFile::from_bytes(&deduped).map_err(|e| e.to_string())?;
+
+        for (a, b) in packed.get_raw_assets().iter().zip(deduped.get_raw_assets()) {
+            assert_eq!(a.descriptor_bytes(), b.descriptor_bytes());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_data_overlaps() -> Result<(), String> {
+        let inverted = SAMPLE_TEXTURE_RESOURCE.iter().map(|b| !b).collect();
+
+        let mut new_bnl = BnlFixture::new()
+            .sample_texture("aid_texture_a")
+            .sample_texture("aid_texture_b")
+            .sample_texture_with_chunks("aid_texture_c", Some(vec![inverted]))
+            .build();
+
+        // Nothing is shared until the assets come from a file
+        assert!(new_bnl.get_overlaps().is_empty());
+
+        new_bnl.set_write_options(BNLWriteOptions {
+            dedup_chunks: true,
+            ..Default::default()
+        });
+
+        let bnl = BNLFile::from_bytes(&new_bnl.to_bytes()).map_err(|e| e.to_string())?;
+
+        let overlaps = bnl.get_overlaps();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(
+            (overlaps[0].first.as_str(), overlaps[0].second.as_str()),
+            ("aid_texture_a", "aid_texture_b")
+        );
+        assert_eq!(overlaps[0].ranges, vec![0..SAMPLE_TEXTURE_RESOURCE.len()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_data_edit_policy() -> Result<(), String> {
+        let mut new_bnl = BnlFixture::new()
+            .sample_texture("aid_texture_a")
+            .sample_texture("aid_texture_b")
+            .build();
+
+        new_bnl.set_write_options(BNLWriteOptions {
+            dedup_chunks: true,
+            ..Default::default()
+        });
+
+        let mut bnl = BNLFile::from_bytes(&new_bnl.to_bytes()).map_err(|e| e.to_string())?;
+        assert_eq!(
+            bnl.assets_sharing_data("aid_texture_a"),
+            vec!["aid_texture_b"]
+        );
+
+        let mut edited = bnl
+            .get_raw_asset("aid_texture_a")
+            .ok_or("aid_texture_a is missing")?
+            .clone();
+        edited
+            .resource_chunks_mut()
+            .as_mut()
+            .ok_or("No resources")?[0][0] ^= 0xff;
+
+        bnl.set_edit_policy(EditPolicy::Error);
+
+        // Descriptor only edits leave the shared data alone, so they are always allowed
+        let unchanged = bnl.get_raw_asset("aid_texture_a").cloned();
+        bnl.patch_asset(
+            "aid_texture_a",
+            unchanged.ok_or("aid_texture_a is missing")?,
+        )
+        .map_err(|e| e.to_string())?;
+
+        match bnl.patch_asset("aid_texture_a", edited.clone()) {
+            Err(AssetError::SharedData { name, shared_with }) => {
+                assert_eq!(name, "aid_texture_a");
+                assert_eq!(shared_with, vec!["aid_texture_b"]);
+            }
+            other => return Err(format!("Expected a SharedData error, got {:?}", other)),
+        }
+
+        bnl.set_edit_policy(EditPolicy::CopyOnWrite);
+        bnl.patch_asset("aid_texture_a", edited.clone())
+            .map_err(|e| e.to_string())?;
+        assert!(bnl.assets_sharing_data("aid_texture_b").is_empty());
+
+        let bnl = BNLFile::from_bytes(&bnl.to_bytes()).map_err(|e| e.to_string())?;
+
+        for (name, expected) in [
+            ("aid_texture_a", edited.resource_chunks()),
+            (
+                "aid_texture_b",
+                Some(&vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+            ),
+        ] {
+            let asset = bnl
+                .get_raw_asset(name)
+                .ok_or(format!("{} is missing", name))?;
+            assert_eq!(asset.resource_chunks(), expected, "{}", name);
+        }
+
+        Ok(())
+    }
+
+    fn raw_assets() -> impl Strategy<Value = Vec<RawAsset>> {
+        let contents = (
+            prop::sample::select(vec![
+                AssetType::ResTexture,
+                AssetType::ResModel,
+                AssetType::ResScript,
+                AssetType::ResLoctext,
+            ]),
+            (any::<u32>(), any::<u32>()),
+            prop::collection::vec(any::<u8>(), 1..64),
+            prop::option::of(prop::collection::vec(
+                prop::collection::vec(any::<u8>(), 1..256),
+                1..4,
+            )),
+        );
+
+        prop::collection::btree_map("aid_[a-z0-9_]{1,32}", contents, 1..8).prop_map(|assets| {
+            assets
+                .into_iter()
+                .map(|(name, (asset_type, (unk_1, unk_2), descriptor, chunks))| {
+                    RawAsset::new(
+                        AssetMetadata::new(&name, asset_type, unk_1, unk_2),
+                        descriptor,
+                        chunks,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn bnl_container_roundtrip(
+            assets in raw_assets(),
+            variant in prop::sample::select(vec![BnlVariant::LittleEndian, BnlVariant::BigEndian]),
+            dedup_chunks in any::<bool>(),
+            dedup_descriptors in any::<bool>(),
+        ) {
+            let mut bnl = BNLFile::default();
+            bnl.set_variant(variant);
+            bnl.set_write_options(BNLWriteOptions {
+                dedup_chunks,
+                dedup_descriptors,
+                ..Default::default()
+            });
+
+            for asset in &assets {
+                bnl.append_raw_asset(asset.clone()).expect("Names are unique");
+            }
+
+            let bytes = bnl.to_bytes();
+            let read = BNLFile::from_bytes(&bytes).expect("Written files can be read");
+
+            prop_assert_eq!(read.variant(), variant);
+            prop_assert_eq!(read.get_raw_assets().len(), assets.len());
+
+            for asset in &assets {
+                let read_asset = read.get_raw_asset(asset.name()).expect("Every asset is written");
+
+                prop_assert_eq!(read_asset.metadata(), asset.metadata());
+                prop_assert_eq!(read_asset.descriptor_bytes(), asset.descriptor_bytes());
+                prop_assert_eq!(read_asset.resource_chunks(), asset.resource_chunks());
+            }
+        }
+    }
+
+    #[test]
+    fn append_validation() -> Result<(), String> {
+        let tex_descriptor = SAMPLE_TEXTURE_DESCRIPTOR.to_vec();
+
+        let texture = |name: &str, descriptor: Vec<u8>| {
+            RawAsset::new(
+                AssetMetadata::new(name, AssetType::ResTexture, 0, 0),
+                descriptor,
+                None,
+            )
+        };
+
+        let mut bnl = BnlFixture::new()
+            .sample_texture_with_chunks("aid_texture_a", None)
+            .build();
+
+        assert!(matches!(
+            bnl.append_raw_asset(texture("aid_texture_a", tex_descriptor.clone())),
+            Err(AssetError::Duplicate(name)) if name == "aid_texture_a"
+        ));
+
+        // Descriptors are only checked when strict
+        bnl.append_raw_asset(texture("aid_texture_b", vec![0; 4]))
+            .map_err(|e| e.to_string())?;
+
+        bnl.set_append_validation(AppendValidation::Strict);
+        assert!(matches!(
+            bnl.append_raw_asset(texture("aid_texture_c", vec![0; 4])),
+            Err(AssetError::TypeMismatch)
+        ));
+        bnl.append_raw_asset(texture("aid_texture_c", tex_descriptor.clone()))
+            .map_err(|e| e.to_string())?;
+
+        bnl.set_append_validation(AppendValidation::None);
+        bnl.append_raw_asset(texture("aid_texture_a", tex_descriptor))
+            .map_err(|e| e.to_string())?;
+        assert_eq!(bnl.get_raw_assets().len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aligned_sections() -> Result<(), String> {
+        // Odd sizes everywhere, so that every section needs padding
+        let odd_texture = |name: &str, resource_size: usize| {
+            RawAsset::new(
+                AssetMetadata::new(name, AssetType::ResTexture, 0, 0),
+                SAMPLE_TEXTURE_DESCRIPTOR[..SAMPLE_TEXTURE_DESCRIPTOR.len() - 1].to_vec(),
+                Some(vec![
+                    SAMPLE_TEXTURE_RESOURCE[..resource_size].to_vec(),
+                    SAMPLE_TEXTURE_RESOURCE.to_vec(),
+                ]),
+            )
+        };
+
+        let mut new_bnl = BnlFixture::new()
+            .raw_asset(odd_texture("aid_texture_a", 3))
+            .raw_asset(odd_texture("aid_texture_b", 5))
+            .build();
+
+        let options = BNLWriteOptions {
+            buffer_view_alignment: 16,
+            resource_alignment: 2048,
+            descriptor_alignment: 32,
+            ..Default::default()
+        };
+
+        new_bnl.set_write_options(options.clone());
+
+        let serialised = new_bnl.to_bytes();
+
+        let detected = BNLWriteOptions::detect(&serialised).map_err(|e| e.to_string())?;
+        assert!(detected.buffer_view_alignment >= options.buffer_view_alignment);
+        assert!(detected.resource_alignment >= options.resource_alignment);
+        assert!(detected.descriptor_alignment >= options.descriptor_alignment);
+
+        let deserialised = BNLFile::from_bytes(&serialised).map_err(|e| e.to_string())?;
+
+        // Files which are read keep their alignment when they are written back
+        assert_eq!(deserialised.write_options(), &detected);
+        assert_eq!(deserialised.to_bytes(), serialised);
+
+        for (a, b) in new_bnl
+            .get_raw_assets()
+            .iter()
+            .zip(deserialised.get_raw_assets())
+        {
+            assert_eq!(a.descriptor_bytes(), b.descriptor_bytes());
+            assert_eq!(a.resource_chunks(), b.resource_chunks());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn big_endian_variant_roundtrip() -> Result<(), String> {
+        let serialised = BnlFixture::new()
+            .variant(BnlVariant::BigEndian)
+            .sample_texture("aid_sometexture")
+            .to_bytes();
+
+        assert_eq!(
+            BnlVariant::detect(&serialised),
+            Some(BnlVariant::BigEndian),
+            "The header of a big endian BNL should be detected as big endian"
+        );
+
+        let deserialised = BNLFile::from_bytes(&serialised)
+            .map_err(|e| format!("Failed to deserialise big endian BNL: {e}"))?;
+
+        assert_eq!(deserialised.variant(), BnlVariant::BigEndian);
+        assert!(
+            deserialised.get_raw_asset("aid_sometexture").is_some(),
+            "No asset exists in the new bnl file with the name aid_sometexture"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn patch_asset_preserves_layout() -> Result<(), String> {
+        let original = BnlFixture::new()
+            .sample_texture("aid_texture_a")
+            .sample_texture("aid_texture_b")
+            .to_bytes();
+        let mut bnl = BNLFile::from_bytes(&original).map_err(|e| e.to_string())?;
+
+        let patched_resource: Vec<u8> = SAMPLE_TEXTURE_RESOURCE.iter().map(|b| !b).collect();
+
+        bnl.patch_asset(
+            "aid_texture_b",
+            RawAsset::new(
+                AssetMetadata::new("aid_texture_b", AssetType::ResTexture, 0, 0),
+                SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+                Some(vec![patched_resource.clone()]),
+            ),
+        )
+        .map_err(|_| "Unable to patch aid_texture_b")?;
+
+        let patched = bnl.write_patched(&original).map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            original[..40],
+            patched[..40],
+            "Patching in place should leave the header untouched"
+        );
+
+        let reread = BNLFile::from_bytes(&patched).map_err(|e| e.to_string())?;
+
+        let get_chunks = |bnl: &BNLFile, name: &str| {
+            bnl.get_raw_asset(name)
+                .and_then(|asset| asset.resource_chunks().cloned())
+        };
+
+        assert_eq!(
+            get_chunks(&reread, "aid_texture_a"),
+            Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+            "The untouched asset should be unchanged"
+        );
+        assert_eq!(
+            get_chunks(&reread, "aid_texture_b"),
+            Some(vec![patched_resource]),
+            "The patched asset should contain the new resource"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_patched_keeps_other_changes() -> Result<(), String> {
+        let original = BnlFixture::new()
+            .script("aid_script_a", "EndScript")
+            .to_bytes();
+        let mut bnl = BNLFile::from_bytes(&original).map_err(|e| e.to_string())?;
+
+        let descriptor = bnl
+            .get_raw_asset("aid_script_a")
+            .ok_or("aid_script_a is missing")?
+            .descriptor_bytes()
+            .to_vec();
+
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_script_b", AssetType::ResScript, 0, 0),
+            descriptor,
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let written = bnl.write_patched(&original).map_err(|e| e.to_string())?;
+        assert_eq!(written, bnl.to_bytes());
+
+        let reread = BNLFile::from_bytes(&written).map_err(|e| e.to_string())?;
+        assert!(reread.get_raw_asset("aid_script_b").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn extract_create_roundtrip_is_reproducible() -> Result<(), String> {
+        // Enough chunks to catch resource10 < resource2
+        let chunks = || {
+            (0..12u8)
+                .map(|i| SAMPLE_TEXTURE_RESOURCE.iter().map(|b| b ^ i).collect())
+                .collect()
+        };
+
+        // Appended out of order on purpose
+        let original_bytes = BnlFixture::new()
+            .sample_texture_with_chunks("aid_texture_b", Some(chunks()))
+            .sample_texture_with_chunks("aid_texture_a", Some(chunks()))
+            .to_bytes();
+        let extracted = BNLFile::from_bytes(&original_bytes).map_err(|e| e.to_string())?;
+
+        let out_dir = std::env::temp_dir().join(format!("bnl_roundtrip_{}", std::process::id()));
+
+        let mut recreated = BNLFile::default();
+
+        for raw_asset in extracted.get_raw_assets() {
+            let asset_dir = out_dir.join(raw_asset.name());
+            raw_asset.to_dir(&asset_dir).map_err(|e| e.to_string())?;
+
+            recreated
+                .append_raw_asset(RawAsset::from_dir(&asset_dir).map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+        }
+
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let recreated_bytes = recreated.to_bytes();
+
+        assert_eq!(
+            original_bytes, recreated_bytes,
+            "Extracting and recreating a BNL should produce an identical file"
+        );
+        assert_eq!(
+            recreated_bytes,
+            recreated.to_bytes(),
+            "Serialising the same BNL twice should produce an identical file"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn texture_read_back_from_png() -> Result<(), String> {
+        let format = D3DFormat::Swizzled(crate::d3d::Swizzled::B8G8R8A8);
+        let bnl = BnlFixture::new()
+            .texture("aid_texture_a", 8, 8, format)
+            .build();
+
+        let mut raw_asset = bnl
+            .get_raw_asset("aid_texture_a")
+            .ok_or("aid_texture_a is missing")?
+            .clone();
+        // The chunk count is stored alongside the image, as it can't be worked out from it
+        raw_asset.set_chunk_count(3);
+        let raw_asset = &raw_asset;
+
+        let texture = bnl
+            .get_asset::<Texture>("aid_texture_a")
+            .map_err(|e| e.to_string())?;
+
+        let mut png = vec![];
+        texture
+            .asset()
+            .to_rgba_image()
+            .map_err(|e| e.to_string())?
+            .dump_png_bytes(&mut png)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let out_dir = std::env::temp_dir().join(format!("bnl_texture_png_{}", std::process::id()));
+        let write = |path: PathBuf, bytes: &[u8]| fs::write(path, bytes).map_err(|e| e.to_string());
+
+        // An asset directory with the converted image in place of its resource
+        let asset_dir = out_dir.join("aid_texture_a");
+        fs::create_dir_all(&asset_dir).map_err(|e| e.to_string())?;
+        write(asset_dir.join("metadata"), raw_asset.metadata_file_bytes())?;
+        write(asset_dir.join("descriptor"), raw_asset.descriptor_bytes())?;
+        texture
+            .asset()
+            .dump_with(
+                asset_dir.join(TEXTURE_PNG_FILE),
+                TextureExportOptions::default(),
+            )
+            .map_err(|e| e.to_string())?;
+
+        // The PNG is moved into place once it is written, leaving nothing else behind
+        let dir_files = fs::read_dir(&asset_dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .count();
+        assert_eq!(dir_files, 3);
+
+        // A flat JSON file without the resource data, next to the image
+        let json = serde_json::to_vec(&AssetJson::from_raw_asset(raw_asset, false))
+            .map_err(|e| e.to_string())?;
+        write(out_dir.join("aid_texture_a.json"), &json)?;
+        write(out_dir.join("aid_texture_a.png"), &png)?;
+
+        let from_dir = RawAsset::from_dir(&asset_dir);
+        let from_flat = RawAsset::from_flat_file(out_dir.join("aid_texture_a.json"));
+
+        let _ = fs::remove_dir_all(&out_dir);
+
+        for read in [from_dir, from_flat] {
+            let read = read.map_err(|e| e.to_string())?;
+
+            assert_eq!(read.metadata(), raw_asset.metadata());
+            assert_eq!(read.descriptor_bytes(), raw_asset.descriptor_bytes());
+            assert_eq!(read.resource_chunks(), raw_asset.resource_chunks());
+            assert_eq!(read.chunk_count(), 3);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_bnl_returns_error() {
+        let serialised = BnlFixture::new()
+            .sample_texture("aid_sometexture")
+            .to_bytes();
+
+        assert!(
+            BNLFile::from_bytes(&serialised[..20]).is_err(),
+            "A truncated header should fail to parse"
+        );
+
+        // Point the descriptor section well past the end of the file
+        let mut corrupted = serialised.clone();
+        corrupted[36..40].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(
+            BNLFile::from_bytes(&corrupted).is_err(),
+            "An out of bounds descriptor section should fail to parse"
+        );
+    }
 }
@@ -0,0 +1,63 @@
+/*!
+Parsed assets kept by a [`BNLFile`](super::BNLFile), see
+[`BNLFile::get_asset_cached`](super::BNLFile::get_asset_cached).
+
+Entries are keyed by asset name and the type they were parsed as, so the same asset can be cached
+as several types at once. The cache sits behind a mutex so that it can be filled through `&self`.
+*/
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+type CachedAsset = Arc<dyn Any + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct AssetCache {
+    assets: Mutex<HashMap<(String, TypeId), CachedAsset>>,
+}
+
+impl fmt::Debug for AssetCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl AssetCache {
+    pub(crate) fn get<T: Any + Send + Sync>(&self, name: &str) -> Option<Arc<T>> {
+        self.lock()
+            .get(&(name.to_string(), TypeId::of::<T>()))?
+            .clone()
+            .downcast()
+            .ok()
+    }
+
+    pub(crate) fn insert<T: Any + Send + Sync>(&self, name: &str, value: Arc<T>) {
+        self.lock()
+            .insert((name.to_string(), TypeId::of::<T>()), value);
+    }
+
+    /// Drops every type the asset `name` was cached as
+    pub(crate) fn invalidate(&self, name: &str) {
+        self.lock()
+            .retain(|(cached_name, _), _| cached_name != name);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Nothing is left half written while the lock is held, so a poisoned cache is still usable
+    fn lock(&self) -> MutexGuard<'_, HashMap<(String, TypeId), CachedAsset>> {
+        self.assets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,266 @@
+/*!
+Which bytes of each section of a BNL file its assets refer to, see
+[`DecompressedBnl::coverage`](super::DecompressedBnl::coverage).
+
+Bytes which no asset refers to are padding, or data that the packer leaked into the file. Bytes
+which several assets refer to are shared, which the game's own files do for resource data and
+[`BNLWriteOptions`](super::BNLWriteOptions) can do when deduplicating.
+*/
+
+use std::{collections::BTreeMap, fmt, ops::Range};
+
+use crate::asset::{ASSET_DESCRIPTION_SIZE, AssetDescription, DataViewList};
+
+use super::{BNLError, DataView, DecompressedBnl};
+
+/// A section of the decompressed data of a BNL file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BnlSection {
+    AssetDescriptions,
+    BufferViews,
+    Buffer,
+    Descriptors,
+}
+
+impl fmt::Display for BnlSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BnlSection::AssetDescriptions => "Asset descriptions",
+            BnlSection::BufferViews => "Buffer views",
+            BnlSection::Buffer => "Buffer",
+            BnlSection::Descriptors => "Descriptors",
+        })
+    }
+}
+
+/// A range of a section which more than one reference points into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedRange {
+    /// Relative to the start of the section
+    pub range: Range<usize>,
+    /// The number of references to the range, which can be more than the number of assets when
+    /// an asset refers to the same bytes twice
+    pub references: usize,
+    /// The assets referring to the range, in the order they are stored
+    pub asset_names: Vec<String>,
+}
+
+/// How the bytes of one section are referred to by the assets of a BNL file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionCoverage {
+    pub section: BnlSection,
+    pub size: usize,
+    /// Ranges which nothing refers to, relative to the start of the section
+    pub unreferenced: Vec<Range<usize>>,
+    /// Ranges which are referred to more than once
+    pub shared: Vec<SharedRange>,
+}
+
+impl SectionCoverage {
+    /// Works out the coverage of a section of `size` bytes from references to it by the asset at
+    /// each index. Parts of references past the end of the section are ignored.
+    fn new(
+        section: BnlSection,
+        size: usize,
+        references: &[(Range<usize>, usize)],
+        asset_names: &[String],
+    ) -> Self {
+        // The change in the number of references to each asset at each offset
+        let mut events: BTreeMap<usize, Vec<(usize, isize)>> = BTreeMap::new();
+        events.entry(0).or_default();
+        events.entry(size).or_default();
+
+        for (range, asset) in references {
+            let range = range.start.min(size)..range.end.min(size);
+
+            if !range.is_empty() {
+                events.entry(range.start).or_default().push((*asset, 1));
+                events.entry(range.end).or_default().push((*asset, -1));
+            }
+        }
+
+        let mut unreferenced: Vec<Range<usize>> = vec![];
+        let mut shared: Vec<SharedRange> = vec![];
+
+        let mut active: BTreeMap<usize, isize> = BTreeMap::new();
+        let mut events = events.into_iter().peekable();
+
+        while let Some((start, changes)) = events.next() {
+            for (asset, change) in changes {
+                let count = active.entry(asset).or_default();
+                *count += change;
+
+                if *count == 0 {
+                    active.remove(&asset);
+                }
+            }
+
+            let Some(&(end, _)) = events.peek() else {
+                break;
+            };
+
+            let references: usize = active.values().map(|&count| count as usize).sum();
+
+            if references == 0 {
+                match unreferenced.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => unreferenced.push(start..end),
+                }
+            } else if references > 1 {
+                let names: Vec<String> = active
+                    .keys()
+                    .map(|&asset| asset_names[asset].clone())
+                    .collect();
+
+                match shared.last_mut() {
+                    Some(last)
+                        if last.range.end == start
+                            && last.references == references
+                            && last.asset_names == names =>
+                    {
+                        last.range.end = end
+                    }
+                    _ => shared.push(SharedRange {
+                        range: start..end,
+                        references,
+                        asset_names: names,
+                    }),
+                }
+            }
+        }
+
+        Self {
+            section,
+            size,
+            unreferenced,
+            shared,
+        }
+    }
+
+    /// The number of bytes which nothing refers to
+    pub fn unreferenced_bytes(&self) -> usize {
+        self.unreferenced.iter().map(|range| range.len()).sum()
+    }
+
+    /// The number of bytes which are referred to more than once
+    pub fn shared_bytes(&self) -> usize {
+        self.shared.iter().map(|shared| shared.range.len()).sum()
+    }
+}
+
+impl DecompressedBnl {
+    /**
+    Reports which bytes of each section are referred to by no asset, and which are referred to
+    more than once. Descriptions and descriptors are referred to by their asset, buffer views by the
+    asset whose DataView list they are in, and the buffer by the assets whose views point into it.
+
+    # Errors
+    - [`BNLError::DataReadError`] if a section or asset description is out of bounds
+    */
+    pub fn coverage(&self) -> Result<Vec<SectionCoverage>, BNLError> {
+        let header = &self.header;
+
+        let asset_desc_bytes = self.section_bytes(&header.asset_desc_loc, "Asset description")?;
+        let buffer_views_bytes = self.section_bytes(&header.buffer_views_loc, "Buffer views")?;
+
+        let mut asset_names = vec![];
+        let mut descriptions = vec![];
+        let mut buffer_views = vec![];
+        let mut buffer = vec![];
+        let mut descriptors = vec![];
+
+        for (i, description_bytes) in asset_desc_bytes
+            .chunks_exact(ASSET_DESCRIPTION_SIZE)
+            .enumerate()
+        {
+            let description = AssetDescription::from_bytes_as(description_bytes, self.variant)?;
+            asset_names.push(description.name().to_string());
+
+            let start = i * ASSET_DESCRIPTION_SIZE;
+            descriptions.push((start..start + ASSET_DESCRIPTION_SIZE, i));
+
+            let descriptor_start = description.descriptor_ptr as usize;
+            descriptors.push((
+                descriptor_start..descriptor_start + description.descriptor_size as usize,
+                i,
+            ));
+
+            if description.resource_size == 0 {
+                continue;
+            }
+
+            let dvl_start = description.dataview_list_ptr as usize;
+
+            // An unreadable list can't be followed, so only what it points to is missed
+            let Some(dvl) = buffer_views_bytes
+                .get(dvl_start..)
+                .and_then(|bytes| DataViewList::from_bytes_as(bytes, self.variant).ok())
+            else {
+                continue;
+            };
+
+            buffer_views.push((dvl_start..dvl_start + dvl.size as usize, i));
+
+            for view in dvl.views() {
+                let offset = view.offset as usize;
+                buffer.push((offset..offset + view.size as usize, i));
+            }
+        }
+
+        let coverage = |section, loc: &DataView, references: &[(Range<usize>, usize)]| {
+            SectionCoverage::new(section, loc.size as usize, references, &asset_names)
+        };
+
+        Ok(vec![
+            coverage(
+                BnlSection::AssetDescriptions,
+                &header.asset_desc_loc,
+                &descriptions,
+            ),
+            coverage(
+                BnlSection::BufferViews,
+                &header.buffer_views_loc,
+                &buffer_views,
+            ),
+            coverage(BnlSection::Buffer, &header.buffer_loc, &buffer),
+            coverage(
+                BnlSection::Descriptors,
+                &header.descriptor_loc,
+                &descriptors,
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn section_coverage_ranges() {
+        let names = vec!["a".to_string(), "b".to_string()];
+        let coverage = SectionCoverage::new(
+            BnlSection::Buffer,
+            0x40,
+            &[
+                (0x4..0x10, 0),
+                (0x8..0x10, 1),
+                (0x10..0x18, 1),
+                (0x30..0x50, 0),
+            ],
+            &names,
+        );
+
+        assert_eq!(coverage.unreferenced, [0x0..0x4, 0x18..0x30]);
+        assert_eq!(
+            coverage.shared,
+            [SharedRange {
+                range: 0x8..0x10,
+                references: 2,
+                asset_names: names,
+            }]
+        );
+        assert_eq!(coverage.unreferenced_bytes(), 0x1c);
+        assert_eq!(coverage.shared_bytes(), 0x8);
+    }
+}
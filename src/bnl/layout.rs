@@ -0,0 +1,174 @@
+/*!
+Where the sections of a BNL file are, see [`BNLFile::layout`](super::BNLFile::layout).
+
+Offsets are from the start of the file with everything after the header decompressed, which is how
+the header stores them. Decompressing a file and opening it in a hex editor shows each section at
+the offsets given here.
+*/
+
+use std::fmt;
+
+use crate::BnlVariant;
+
+use super::{BNLFile, BNLHeader, BnlSection, DataView, DecompressedBnl};
+
+/// Where each section of one BNL file starts and how big it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionLayout {
+    /// The size of the uncompressed header, which the sections follow
+    pub header_size: usize,
+    /// The number of assets given by the header
+    pub asset_count: usize,
+    pub asset_descriptions: DataView,
+    pub buffer_views: DataView,
+    pub buffer: DataView,
+    pub descriptors: DataView,
+}
+
+impl SectionLayout {
+    fn new(header: &BNLHeader, variant: BnlVariant) -> Self {
+        Self {
+            header_size: variant.header_size(),
+            asset_count: header.file_count as usize,
+            asset_descriptions: header.asset_desc_loc,
+            buffer_views: header.buffer_views_loc,
+            buffer: header.buffer_loc,
+            descriptors: header.descriptor_loc,
+        }
+    }
+
+    /// Each section with where it is, in the order they are stored
+    pub fn sections(&self) -> [(BnlSection, DataView); 4] {
+        [
+            (BnlSection::AssetDescriptions, self.asset_descriptions),
+            (BnlSection::BufferViews, self.buffer_views),
+            (BnlSection::Buffer, self.buffer),
+            (BnlSection::Descriptors, self.descriptors),
+        ]
+    }
+
+    /// The size of the file once decompressed, including the header
+    pub fn decompressed_size(&self) -> usize {
+        self.sections()
+            .iter()
+            .map(|(_, loc)| loc.as_range::<u32>().end as usize)
+            .fold(self.header_size, usize::max)
+    }
+}
+
+impl fmt::Display for SectionLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<20} {:#10x} {:#10x}", "Header", 0, self.header_size)?;
+
+        for (section, loc) in self.sections() {
+            writeln!(
+                f,
+                "{:<20} {:#10x} {:#10x}",
+                section.to_string(),
+                loc.offset(),
+                loc.size()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The layout of a [`BNLFile`] before and after writing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BnlLayout {
+    /// The layout of the file the assets were read from, or of the last
+    /// [`BNLFile::commit_layout`]. None for files built from scratch which haven't been committed.
+    pub source: Option<SectionLayout>,
+    /// The layout [`BNLFile::to_bytes`] would write with the current assets and write options
+    pub written: SectionLayout,
+}
+
+impl BnlLayout {
+    /// Whether writing the file would move or resize any section
+    pub fn is_changed(&self) -> bool {
+        self.source != Some(self.written)
+    }
+}
+
+impl BNLFile {
+    /// Where each section is in the file this was read from, and where it would be if the file
+    /// were written now. Working out the written layout builds the small sections, but doesn't
+    /// compress anything.
+    pub fn layout(&self) -> BnlLayout {
+        // Every written file has its asset descriptions straight after the header, so a zeroed
+        // header is one that no file has been read into
+        let source = (self.header.asset_desc_loc.offset != 0)
+            .then(|| SectionLayout::new(&self.header, self.variant));
+
+        BnlLayout {
+            source,
+            written: SectionLayout::new(&self.lay_out().header, self.variant),
+        }
+    }
+}
+
+impl DecompressedBnl {
+    /// Where each section is, as given by the header
+    pub fn layout(&self) -> SectionLayout {
+        SectionLayout::new(&self.header, self.variant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{AssetMetadata, RawAsset, asset::AssetType, testing::BnlFixture};
+
+    #[test]
+    fn layout_before_and_after_writing() -> Result<(), String> {
+        let bytes = BnlFixture::new()
+            .script("aid_script_a", "EndScript")
+            .script("aid_script_b", "WaitToMoveOn\nEndScript")
+            .to_bytes();
+
+        let decompressed = DecompressedBnl::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        let mut bnl = BNLFile::from_bytes(&bytes).map_err(|e| e.to_string())?;
+
+        let layout = bnl.layout();
+        assert_eq!(layout.source, Some(decompressed.layout()));
+        assert!(!layout.is_changed());
+
+        let written = layout.written;
+        assert_eq!(written.asset_count, 2);
+        assert_eq!(
+            written.asset_descriptions.offset() as usize,
+            written.header_size
+        );
+        assert_eq!(
+            written.decompressed_size() - written.header_size,
+            bnl.decompressed_size()
+        );
+
+        let descriptor = bnl
+            .get_raw_asset("aid_script_b")
+            .ok_or("aid_script_b should exist")?
+            .descriptor_bytes()
+            .to_vec();
+
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_script_c", AssetType::ResScript, 0, 0),
+            descriptor,
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let layout = bnl.layout();
+        assert!(layout.is_changed());
+        assert_eq!(layout.source, Some(written));
+        assert_eq!(layout.written.asset_count, 3);
+
+        bnl.commit_layout();
+        assert!(!bnl.layout().is_changed());
+
+        assert_eq!(BNLFile::default().layout().source, None);
+
+        Ok(())
+    }
+}
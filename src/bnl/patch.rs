@@ -0,0 +1,802 @@
+/*!
+The difference between two BNL files, so that an edit can be shared without the whole archive.
+
+A [`BnlPatch`] lists the assets added, removed and modified between a base file and a target file.
+Modified assets only store the descriptors and resource chunks which changed, each as the runs of
+bytes which differ from the base, or whole when that is smaller. Each modified asset keeps the
+[`RawAsset::content_hash`] of the asset it was made from, so applying a patch to the wrong file
+fails instead of producing a broken one.
+
+```ignore
+let patch = BnlPatch::diff(&original, &translated)?;
+fs::write("translation.bnlpatch", patch.to_bytes()?)?;
+
+let patched = BnlPatch::from_bytes(&fs::read("translation.bnlpatch")?)?.apply(&original)?;
+```
+*/
+
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::zlib;
+
+use super::{AssetMetadata, BNLFile, BNLHeader, RawAsset, ZLIB_COMPRESSION_LEVEL};
+
+const PATCH_SIGNATURE: [u8; 4] = *b"BNLP";
+const PATCH_VERSION: u32 = 1;
+
+/// The largest a patch is allowed to be once decompressed, so that a small malicious patch can't
+/// use up all of the memory when it is read
+pub const MAX_PATCH_SIZE: usize = 1 << 30;
+
+/// Runs of changed bytes closer together than this are stored as one edit, as each edit has a few
+/// bytes of overhead
+const EDIT_MERGE_GAP: usize = 8;
+
+#[derive(Debug)]
+pub enum PatchError {
+    /// The bytes aren't a patch written by [`BnlPatch::to_bytes`]
+    InvalidPatch(String),
+    /// An asset which the patch removes or modifies isn't in the base file
+    MissingAsset(String),
+    /// An asset which the patch adds is already in the base file
+    AssetExists(String),
+    /// An asset isn't the one the patch was made from
+    BaseMismatch(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::InvalidPatch(reason) => write!(f, "Invalid patch: {}", reason),
+            PatchError::MissingAsset(name) => {
+                write!(f, "{} is not in the file being patched", name)
+            }
+            PatchError::AssetExists(name) => {
+                write!(f, "{} is already in the file being patched", name)
+            }
+            PatchError::BaseMismatch(name) => write!(
+                f,
+                "{} doesn't match the asset the patch was made from",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Bytes to write over a copy of the original, starting at `offset`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteEdit {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// How to make a descriptor or resource chunk from the one at the same position in the base asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DataPatch {
+    Unchanged,
+    /// The original resized to `len` bytes, with the edits written over it
+    Edited {
+        len: u32,
+        edits: Vec<ByteEdit>,
+    },
+    Replaced(Vec<u8>),
+}
+
+impl DataPatch {
+    /// The patch making `new` from `old`, which is `None` when the base asset has nothing at the
+    /// same position
+    fn new(old: Option<&[u8]>, new: &[u8]) -> Self {
+        let Some(old) = old else {
+            return DataPatch::Replaced(new.to_vec());
+        };
+
+        if old == new {
+            return DataPatch::Unchanged;
+        }
+
+        let mut edits: Vec<ByteEdit> = vec![];
+        let mut offset = 0;
+
+        while offset < new.len() {
+            if old.get(offset) == Some(&new[offset]) {
+                offset += 1;
+                continue;
+            }
+
+            // Extend the run until enough unchanged bytes follow it
+            let start = offset;
+            let mut end = offset + 1;
+
+            while end < new.len()
+                && (end..(end + EDIT_MERGE_GAP).min(new.len())).any(|i| old.get(i) != Some(&new[i]))
+            {
+                end += 1;
+            }
+
+            edits.push(ByteEdit {
+                offset: start as u32,
+                bytes: new[start..end].to_vec(),
+            });
+
+            offset = end;
+        }
+
+        let edited_size: usize = edits.iter().map(|edit| edit.bytes.len() + 8).sum();
+
+        match edited_size < new.len() {
+            true => DataPatch::Edited {
+                len: new.len() as u32,
+                edits,
+            },
+            false => DataPatch::Replaced(new.to_vec()),
+        }
+    }
+
+    fn apply(&self, old: Option<&[u8]>, name: &str) -> Result<Vec<u8>, PatchError> {
+        match (self, old) {
+            (DataPatch::Replaced(bytes), _) => Ok(bytes.clone()),
+            (DataPatch::Unchanged, Some(old)) => Ok(old.to_vec()),
+            (DataPatch::Edited { len, edits }, Some(old)) => {
+                let mut bytes = old.to_vec();
+                bytes.resize(*len as usize, 0);
+
+                for edit in edits {
+                    let range = edit.offset as usize..edit.offset as usize + edit.bytes.len();
+
+                    bytes
+                        .get_mut(range)
+                        .ok_or_else(|| {
+                            PatchError::InvalidPatch(format!(
+                                "An edit of {} is past the end of its data",
+                                name
+                            ))
+                        })?
+                        .copy_from_slice(&edit.bytes);
+                }
+
+                Ok(bytes)
+            }
+            (_, None) => Err(PatchError::BaseMismatch(name.to_string())),
+        }
+    }
+}
+
+/// A change made to one asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssetPatch {
+    Added {
+        /// The bytes of the [`AssetMetadata`]
+        metadata: Vec<u8>,
+        descriptor: Vec<u8>,
+        resource_chunks: Option<Vec<Vec<u8>>>,
+    },
+    Removed {
+        name: String,
+    },
+    Modified(ModifiedAsset),
+}
+
+impl AssetPatch {
+    /**
+    The name of the asset which is changed.
+
+    # Errors
+    - [`PatchError::InvalidPatch`] if the metadata of an added asset can't be read
+    */
+    pub fn name(&self) -> Result<String, PatchError> {
+        match self {
+            AssetPatch::Added { metadata, .. } => AssetMetadata::from_bytes(metadata)
+                .map(|metadata| metadata.name().to_string())
+                .map_err(|e| PatchError::InvalidPatch(e.to_string())),
+            AssetPatch::Removed { name } => Ok(name.clone()),
+            AssetPatch::Modified(modified) => Ok(modified.name.clone()),
+        }
+    }
+}
+
+/// The assets of `bnl` by name, where `file` says which file it is for errors
+fn assets_by_name<'a>(
+    bnl: &'a BNLFile,
+    file: &str,
+) -> Result<HashMap<&'a str, &'a RawAsset>, PatchError> {
+    let mut assets = HashMap::new();
+
+    for asset in &bnl.assets {
+        if assets.insert(asset.name(), asset).is_some() {
+            return Err(PatchError::InvalidPatch(format!(
+                "{} is in the {} file more than once",
+                asset.name(),
+                file
+            )));
+        }
+    }
+
+    Ok(assets)
+}
+
+/// The changes to an asset which is in both files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModifiedAsset {
+    pub name: String,
+    /// The [`RawAsset::content_hash`] of the base asset
+    pub base_hash: String,
+    /// The bytes of the new [`AssetMetadata`], if it changed
+    pub metadata: Option<Vec<u8>>,
+    pub descriptor: DataPatch,
+    /// A patch for each chunk of the new asset, against the base chunk with the same index. `None`
+    /// if the new asset has no resource data.
+    pub resource_chunks: Option<Vec<DataPatch>>,
+}
+
+impl ModifiedAsset {
+    fn new(base: &RawAsset, target: &RawAsset) -> Self {
+        let base_chunks = base.resource_chunks.as_deref().unwrap_or_default();
+
+        ModifiedAsset {
+            name: target.name().to_string(),
+            base_hash: base.content_hash(),
+            metadata: (base.metadata != target.metadata).then(|| target.metadata.to_bytes()),
+            descriptor: DataPatch::new(Some(&base.descriptor_bytes), &target.descriptor_bytes),
+            resource_chunks: target.resource_chunks.as_ref().map(|chunks| {
+                chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, chunk)| DataPatch::new(base_chunks.get(i).map(Vec::as_slice), chunk))
+                    .collect()
+            }),
+        }
+    }
+
+    /// Makes the modified asset from `base`, keeping where it was read from
+    fn apply(&self, base: &RawAsset) -> Result<RawAsset, PatchError> {
+        let name = &self.name;
+
+        if base.content_hash() != self.base_hash {
+            return Err(PatchError::BaseMismatch(name.clone()));
+        }
+
+        let metadata = match &self.metadata {
+            Some(bytes) => AssetMetadata::from_bytes(bytes)
+                .map_err(|e| PatchError::InvalidPatch(e.to_string()))?,
+            None => base.metadata.clone(),
+        };
+
+        let base_chunks = base.resource_chunks.as_deref().unwrap_or_default();
+
+        let resource_chunks = match &self.resource_chunks {
+            None => None,
+            Some(patches) => Some(
+                patches
+                    .iter()
+                    .enumerate()
+                    .map(|(i, patch)| patch.apply(base_chunks.get(i).map(Vec::as_slice), name))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+
+        let data_changed = resource_chunks.as_ref() != base.resource_chunks.as_ref();
+
+        Ok(RawAsset {
+            original_index: base.original_index,
+            read_chunk_count: base.read_chunk_count,
+            data_views: match data_changed {
+                true => None,
+                false => base.data_views.clone(),
+            },
+            ..RawAsset::new(
+                metadata,
+                self.descriptor.apply(Some(&base.descriptor_bytes), name)?,
+                resource_chunks,
+            )
+        })
+    }
+}
+
+/// The fields of the [`BNLHeader`] which aren't worked out when the file is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderFields {
+    pub flags: u8,
+    pub unknown_2: [u8; 5],
+}
+
+impl HeaderFields {
+    pub(crate) fn new(header: &BNLHeader) -> Self {
+        Self {
+            flags: header.flags,
+            unknown_2: header.unknown_2,
+        }
+    }
+}
+
+/// The changes which turn one BNL file into another, see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BnlPatch {
+    /// The header fields of the target file, if they differ from those of the base file
+    header: Option<HeaderFields>,
+    /// Removed assets first, then added and modified assets in the order of the target file
+    assets: Vec<AssetPatch>,
+    /// The names of every asset of the target file in order, if applying the patch wouldn't
+    /// already put them in that order
+    order: Option<Vec<String>>,
+}
+
+impl BnlPatch {
+    /**
+    The changes which turn `base` into `target`.
+
+    # Errors
+    - [`PatchError::InvalidPatch`] if either file has two assets with the same name, as assets are
+      matched up by name
+    */
+    pub fn diff(base: &BNLFile, target: &BNLFile) -> Result<Self, PatchError> {
+        let base_assets = assets_by_name(base, "base")?;
+        let target_assets = assets_by_name(target, "target")?;
+
+        let mut assets: Vec<AssetPatch> = base
+            .assets
+            .iter()
+            .filter(|asset| !target_assets.contains_key(asset.name()))
+            .map(|asset| AssetPatch::Removed {
+                name: asset.name().to_string(),
+            })
+            .collect();
+
+        for target_asset in &target.assets {
+            match base_assets.get(target_asset.name()) {
+                None => assets.push(AssetPatch::Added {
+                    metadata: target_asset.metadata.to_bytes(),
+                    descriptor: target_asset.descriptor_bytes.clone(),
+                    resource_chunks: target_asset.resource_chunks.clone(),
+                }),
+                Some(base_asset) => {
+                    // The content hash doesn't tell an asset without resource data from one
+                    // with no chunks, which are written differently
+                    if base_asset.metadata != target_asset.metadata
+                        || base_asset.resource_chunks.is_some()
+                            != target_asset.resource_chunks.is_some()
+                        || base_asset.content_hash() != target_asset.content_hash()
+                    {
+                        assets.push(AssetPatch::Modified(ModifiedAsset::new(
+                            base_asset,
+                            target_asset,
+                        )));
+                    }
+                }
+            }
+        }
+
+        let target_header = HeaderFields::new(&target.header);
+
+        let mut patch = BnlPatch {
+            header: (HeaderFields::new(&base.header) != target_header).then_some(target_header),
+            assets,
+            order: None,
+        };
+
+        let target_order: Vec<String> = target
+            .assets
+            .iter()
+            .map(|asset| asset.name().to_string())
+            .collect();
+
+        if patch.applied_order(base)? != target_order {
+            patch.order = Some(target_order);
+        }
+
+        Ok(patch)
+    }
+
+    /// The names of the assets in the order applying the patch to `base` gives without
+    /// [`BnlPatch::order`]: the remaining base assets, then the added ones
+    fn applied_order(&self, base: &BNLFile) -> Result<Vec<String>, PatchError> {
+        let removed: Vec<String> = self
+            .assets
+            .iter()
+            .filter(|asset| matches!(asset, AssetPatch::Removed { .. }))
+            .map(AssetPatch::name)
+            .collect::<Result<_, _>>()?;
+
+        let added: Vec<String> = self
+            .assets
+            .iter()
+            .filter(|asset| matches!(asset, AssetPatch::Added { .. }))
+            .map(AssetPatch::name)
+            .collect::<Result<_, _>>()?;
+
+        Ok(base
+            .assets
+            .iter()
+            .map(|asset| asset.name().to_string())
+            .filter(|name| !removed.contains(name))
+            .chain(added)
+            .collect())
+    }
+
+    /// The changes to each asset
+    pub fn assets(&self) -> &[AssetPatch] {
+        &self.assets
+    }
+
+    /// The header fields the patch sets, if they changed
+    pub fn header(&self) -> Option<HeaderFields> {
+        self.header
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty() && self.order.is_none() && self.header.is_none()
+    }
+
+    /**
+    Applies the patch to a copy of `base`, which should be the file the patch was made from. The
+    new file takes its settings and header from `base`, apart from any header fields the patch
+    changes, and keeps the original position of each asset which was read from it.
+
+    # Errors
+    - [`PatchError::MissingAsset`] if an asset which is removed or modified isn't in `base`
+    - [`PatchError::AssetExists`] if an asset which is added is already in `base`
+    - [`PatchError::BaseMismatch`] if a modified asset doesn't match the one the patch was made
+      from
+    - [`PatchError::InvalidPatch`] if the patch refers to data it doesn't have
+    */
+    pub fn apply(&self, base: &BNLFile) -> Result<BNLFile, PatchError> {
+        let mut assets: Vec<RawAsset> = base.assets.clone();
+
+        let position = |assets: &[RawAsset], name: &str| {
+            assets
+                .iter()
+                .position(|asset| asset.name() == name)
+                .ok_or_else(|| PatchError::MissingAsset(name.to_string()))
+        };
+
+        for asset_patch in &self.assets {
+            match asset_patch {
+                AssetPatch::Removed { name } => {
+                    let index = position(&assets, name)?;
+                    assets.remove(index);
+                }
+                AssetPatch::Modified(modified) => {
+                    let index = position(&assets, &modified.name)?;
+                    assets[index] = modified.apply(&assets[index])?;
+                }
+                AssetPatch::Added {
+                    metadata,
+                    descriptor,
+                    resource_chunks,
+                } => {
+                    let metadata = AssetMetadata::from_bytes(metadata)
+                        .map_err(|e| PatchError::InvalidPatch(e.to_string()))?;
+
+                    if assets.iter().any(|asset| asset.name() == metadata.name()) {
+                        return Err(PatchError::AssetExists(metadata.name().to_string()));
+                    }
+
+                    assets.push(RawAsset::new(
+                        metadata,
+                        descriptor.clone(),
+                        resource_chunks.clone(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(order) = &self.order {
+            let order: HashMap<&str, usize> = order
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), i))
+                .collect();
+
+            assets.sort_by_key(|asset| order.get(asset.name()).copied().unwrap_or(usize::MAX));
+        }
+
+        let mut header = base.header.clone();
+
+        if let Some(fields) = self.header {
+            header.flags = fields.flags;
+            header.unknown_2 = fields.unknown_2;
+        }
+
+        Ok(BNLFile {
+            header,
+            variant: base.variant,
+            asset_order: base.asset_order,
+            write_options: base.write_options.clone(),
+            edit_policy: base.edit_policy,
+            append_validation: base.append_validation,
+            assets,
+            ..Default::default()
+        })
+    }
+
+    /// Writes the patch in its compact form: a signature and version, followed by the compressed
+    /// changes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PatchError> {
+        let encoded = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .map_err(|e| PatchError::InvalidPatch(e.to_string()))?;
+
+        let mut bytes = PATCH_SIGNATURE.to_vec();
+        bytes.extend(PATCH_VERSION.to_le_bytes());
+        bytes.extend(zlib::compress(&encoded, ZLIB_COMPRESSION_LEVEL));
+
+        Ok(bytes)
+    }
+
+    /**
+    Reads a patch written by [`BnlPatch::to_bytes`].
+
+    # Errors
+    - [`PatchError::InvalidPatch`] if the signature or version don't match, the changes are larger
+      than [`MAX_PATCH_SIZE`] or they can't be read
+    */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PatchError> {
+        if bytes.get(..4) != Some(&PATCH_SIGNATURE[..]) {
+            return Err(PatchError::InvalidPatch(
+                "The patch signature does not match".to_string(),
+            ));
+        }
+
+        let version = bytes
+            .get(4..8)
+            .map(|version| u32::from_le_bytes([version[0], version[1], version[2], version[3]]))
+            .ok_or_else(|| PatchError::InvalidPatch("The patch has no version".to_string()))?;
+
+        if version != PATCH_VERSION {
+            return Err(PatchError::InvalidPatch(format!(
+                "Version {} patches aren't supported (expected version {})",
+                version, PATCH_VERSION
+            )));
+        }
+
+        let encoded = zlib::decompress_prefix(&bytes[8..], MAX_PATCH_SIZE + 1)
+            .map_err(|e| PatchError::InvalidPatch(e.to_string()))?;
+
+        if encoded.len() > MAX_PATCH_SIZE {
+            return Err(PatchError::InvalidPatch(format!(
+                "The patch is larger than {} bytes",
+                MAX_PATCH_SIZE
+            )));
+        }
+
+        // Lengths in the encoded patch are checked against the limit before anything is allocated
+        let config = bincode::config::standard().with_limit::<MAX_PATCH_SIZE>();
+
+        bincode::serde::decode_from_slice(&encoded, config)
+            .map(|(patch, _)| patch)
+            .map_err(|e| PatchError::InvalidPatch(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        d3d::{D3DFormat, StandardFormat},
+        testing::BnlFixture,
+    };
+
+    #[test]
+    fn data_patch_edits() -> Result<(), String> {
+        let old: Vec<u8> = (0..64).collect();
+
+        let mut new = old.clone();
+        new[4] = 0xff;
+        new[6] = 0xff;
+        new[40] = 0xff;
+        new.extend([1, 2, 3]);
+
+        let patch = DataPatch::new(Some(&old), &new);
+
+        assert_eq!(
+            patch,
+            DataPatch::Edited {
+                len: 67,
+                edits: vec![
+                    ByteEdit {
+                        offset: 4,
+                        bytes: vec![0xff, 5, 0xff],
+                    },
+                    ByteEdit {
+                        offset: 40,
+                        bytes: vec![0xff],
+                    },
+                    ByteEdit {
+                        offset: 64,
+                        bytes: vec![1, 2, 3],
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            patch.apply(Some(&old), "a").map_err(|e| e.to_string())?,
+            new
+        );
+
+        // Truncating only needs the new length
+        let patch = DataPatch::new(Some(&old), &old[..16]);
+        assert_eq!(
+            patch,
+            DataPatch::Edited {
+                len: 16,
+                edits: vec![]
+            }
+        );
+
+        // Data with nothing in common is stored whole
+        assert_eq!(
+            DataPatch::new(Some(&old), &[9; 8]),
+            DataPatch::Replaced(vec![9; 8])
+        );
+        assert_eq!(DataPatch::new(Some(&old), &old), DataPatch::Unchanged);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bnl_patch_roundtrip() -> Result<(), String> {
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let base = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .texture("aid_texture_b", 8, 8, dxt1)
+            .loctext("aid_loctext_a", &[("greeting", "hello")])
+            .build();
+
+        let mut target = BnlFixture::new()
+            .loctext("aid_loctext_a", &[("greeting", "hi there")])
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .texture("aid_texture_c", 4, 4, dxt1)
+            .build();
+
+        // A change of a few bytes in a large chunk is stored as an edit
+        if let Some(chunks) = target.assets[1].resource_chunks.as_mut() {
+            chunks[0][3] ^= 0xff;
+        }
+
+        let patch = BnlPatch::diff(&base, &target).map_err(|e| e.to_string())?;
+
+        let names: Vec<String> = patch
+            .assets()
+            .iter()
+            .map(AssetPatch::name)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(
+            names,
+            [
+                "aid_texture_b",
+                "aid_loctext_a",
+                "aid_texture_a",
+                "aid_texture_c"
+            ]
+        );
+
+        match &patch.assets()[2] {
+            AssetPatch::Modified(modified) => {
+                assert_eq!(modified.metadata, None);
+                assert_eq!(modified.descriptor, DataPatch::Unchanged);
+                assert!(matches!(
+                    modified.resource_chunks.as_deref(),
+                    Some([DataPatch::Edited { .. }])
+                ));
+            }
+            other => return Err(format!("aid_texture_a should be modified, not {:?}", other)),
+        }
+
+        let read = BnlPatch::from_bytes(&patch.to_bytes().map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(read, patch);
+
+        let patched = read.apply(&base).map_err(|e| e.to_string())?;
+        assert_eq!(patched.to_bytes(), target.to_bytes());
+
+        // The patch only applies to the file it was made from
+        assert!(matches!(
+            patch.apply(&target),
+            Err(PatchError::AssetExists(_) | PatchError::MissingAsset(_))
+        ));
+
+        let other = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .texture("aid_texture_b", 8, 8, dxt1)
+            .loctext("aid_loctext_a", &[("greeting", "bonjour")])
+            .build();
+        assert!(matches!(
+            patch.apply(&other),
+            Err(PatchError::BaseMismatch(_))
+        ));
+
+        assert!(
+            BnlPatch::diff(&base, &base)
+                .map_err(|e| e.to_string())?
+                .is_empty()
+        );
+
+        // Assets are matched by name, so files with duplicate names can't be diffed
+        let mut duplicated = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .build();
+        duplicated.assets.push(duplicated.assets[0].clone());
+        assert!(matches!(
+            BnlPatch::diff(&duplicated, &target),
+            Err(PatchError::InvalidPatch(_))
+        ));
+        assert!(matches!(
+            BnlPatch::diff(&base, &duplicated),
+            Err(PatchError::InvalidPatch(_))
+        ));
+
+        let unreadable = AssetPatch::Added {
+            metadata: vec![0; 4],
+            descriptor: vec![],
+            resource_chunks: None,
+        };
+        assert!(matches!(
+            unreadable.name(),
+            Err(PatchError::InvalidPatch(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bnl_patch_keeps_header() -> Result<(), String> {
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+
+        let mut base = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .build();
+        base.header.flags = 0x3;
+        base.header.unknown_2 = [1, 2, 3, 4, 5];
+
+        let mut target = BnlFixture::new()
+            .texture("aid_texture_a", 16, 16, dxt1)
+            .build();
+        target.header.flags = 0x5;
+        target.header.unknown_2 = [5, 4, 3, 2, 1];
+
+        let patch = BnlPatch::diff(&base, &target).map_err(|e| e.to_string())?;
+        assert_eq!(
+            patch.header(),
+            Some(HeaderFields {
+                flags: 0x5,
+                unknown_2: [5, 4, 3, 2, 1],
+            })
+        );
+
+        let read = BnlPatch::from_bytes(&patch.to_bytes().map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let patched = read.apply(&base).map_err(|e| e.to_string())?;
+        assert_eq!(patched.to_bytes(), target.to_bytes());
+
+        // A patch which doesn't change the header keeps the one of the base file
+        let patch = BnlPatch::diff(&base, &base).map_err(|e| e.to_string())?;
+        assert!(patch.is_empty());
+        assert_eq!(
+            patch.apply(&base).map_err(|e| e.to_string())?.to_bytes(),
+            base.to_bytes()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_patch_rejected() {
+        // No header changes, followed by a list of assets claiming to be far too long
+        let mut encoded = vec![0, 253];
+        encoded.extend(u64::MAX.to_le_bytes());
+
+        let mut bytes = PATCH_SIGNATURE.to_vec();
+        bytes.extend(PATCH_VERSION.to_le_bytes());
+        bytes.extend(zlib::compress(&encoded, 9));
+
+        assert!(matches!(
+            BnlPatch::from_bytes(&bytes),
+            Err(PatchError::InvalidPatch(_))
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+/*!
+A C ABI over the core API, so that C and C++ tools can read BNL files without reimplementing the
+parsers. The matching header is `include/bnl.h`.
+
+The library can be built for C with:
+
+```sh
+cargo rustc --release --lib --features capi --crate-type cdylib
+```
+
+Every function returns a [`BnlStatus`] unless it can't fail. Handles returned by [`bnl_open`] must
+be released with [`bnl_close`], and images filled by [`bnl_extract_texture_rgba`] with
+[`bnl_free_image`]. A handle must not be used from more than one thread at a time.
+*/
+
+use std::{
+    ffi::c_char,
+    panic::{self, UnwindSafe},
+    ptr, slice,
+};
+
+use crate::{BNLFile, asset::texture::Texture};
+
+/// The result of a C API call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BnlStatus {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullPointer = 1,
+    /// The BNL file, or the requested asset, couldn't be read
+    InvalidData = 2,
+    /// The asset index is out of range
+    NotFound = 3,
+    /// The buffer given is too small, the required size has been written instead
+    BufferTooSmall = 4,
+    /// The asset isn't of the type required by the call
+    TypeMismatch = 5,
+    /// The library panicked. The handle should not be used again.
+    Panic = 6,
+}
+
+/// An opened BNL file, only used through a pointer from C
+pub struct BnlHandle {
+    bnl: BNLFile,
+}
+
+/// A decoded image, with 4 bytes per pixel in RGBA order
+#[repr(C)]
+#[derive(Debug)]
+pub struct BnlImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Runs `f`, turning any panic into [`BnlStatus::Panic`] so that it doesn't unwind into C
+fn guard<F: FnOnce() -> BnlStatus + UnwindSafe>(f: F) -> BnlStatus {
+    panic::catch_unwind(f).unwrap_or(BnlStatus::Panic)
+}
+
+/**
+Parses the `len` bytes at `data` as a BNL file. On success, `*out_handle` is set to a new handle.
+The bytes are copied, so they can be freed as soon as this returns.
+
+# Safety
+`data` must point to `len` readable bytes, and `out_handle` must be valid for writes.
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_open(
+    data: *const u8,
+    len: usize,
+    out_handle: *mut *mut BnlHandle,
+) -> BnlStatus {
+    if data.is_null() || out_handle.is_null() {
+        return BnlStatus::NullPointer;
+    }
+
+    // SAFETY: The caller guarantees that `data` points to `len` bytes
+    let bytes = unsafe { slice::from_raw_parts(data, len) };
+
+    guard(|| match BNLFile::from_bytes(bytes) {
+        Ok(bnl) => {
+            // SAFETY: Checked for null above, and the caller guarantees it is writable
+            unsafe { *out_handle = Box::into_raw(Box::new(BnlHandle { bnl })) };
+            BnlStatus::Ok
+        }
+        Err(_) => BnlStatus::InvalidData,
+    })
+}
+
+/**
+Releases a handle returned by [`bnl_open`]. Passing null does nothing.
+
+# Safety
+`handle` must be null or a handle from [`bnl_open`] which hasn't been closed yet.
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_close(handle: *mut BnlHandle) {
+    if !handle.is_null() {
+        // SAFETY: The caller guarantees the handle came from bnl_open and is only freed once
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/**
+The number of assets in the file, or 0 if `handle` is null.
+
+# Safety
+`handle` must be null or a live handle from [`bnl_open`].
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_asset_count(handle: *const BnlHandle) -> usize {
+    // SAFETY: The caller guarantees the handle is null or live
+    unsafe { handle.as_ref() }.map_or(0, |handle| handle.bnl.get_raw_assets().len())
+}
+
+/**
+Copies the name of the asset at `index` into `buf` as a null terminated string. `*out_len` is set
+to the length of the name without its terminator, even if `buf` is too small, so that the call can
+be repeated with a large enough buffer.
+
+# Safety
+`handle` must be a live handle from [`bnl_open`], `buf` must be valid for `buf_len` bytes of writes
+(it may be null if `buf_len` is 0), and `out_len` must be valid for writes.
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_get_asset_name(
+    handle: *const BnlHandle,
+    index: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> BnlStatus {
+    // SAFETY: The caller guarantees the handle is null or live
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return BnlStatus::NullPointer;
+    };
+
+    if out_len.is_null() || (buf.is_null() && buf_len > 0) {
+        return BnlStatus::NullPointer;
+    }
+
+    let Some(raw_asset) = handle.bnl.get_raw_assets().get(index) else {
+        return BnlStatus::NotFound;
+    };
+
+    let name = raw_asset.name().as_bytes();
+
+    // SAFETY: Checked for null above
+    unsafe { *out_len = name.len() };
+
+    if buf_len <= name.len() {
+        return BnlStatus::BufferTooSmall;
+    }
+
+    // SAFETY: `buf` has room for the name and its terminator, checked above
+    unsafe {
+        ptr::copy_nonoverlapping(name.as_ptr(), buf.cast::<u8>(), name.len());
+        *buf.add(name.len()) = 0;
+    }
+
+    BnlStatus::Ok
+}
+
+/**
+Sets `*out_type` to the numeric [`AssetType`](crate::asset::AssetType) of the asset at `index`.
+
+# Safety
+`handle` must be a live handle from [`bnl_open`], and `out_type` must be valid for writes.
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_get_asset_type(
+    handle: *const BnlHandle,
+    index: usize,
+    out_type: *mut u32,
+) -> BnlStatus {
+    // SAFETY: The caller guarantees the handle is null or live
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return BnlStatus::NullPointer;
+    };
+
+    if out_type.is_null() {
+        return BnlStatus::NullPointer;
+    }
+
+    match handle.bnl.get_raw_assets().get(index) {
+        Some(raw_asset) => {
+            // SAFETY: Checked for null above
+            unsafe { *out_type = raw_asset.metadata().asset_type().into() };
+            BnlStatus::Ok
+        }
+        None => BnlStatus::NotFound,
+    }
+}
+
+/**
+Decodes the texture at `index` to RGBA, filling `*out_image`. The image must be released with
+[`bnl_free_image`].
+
+# Safety
+`handle` must be a live handle from [`bnl_open`], and `out_image` must be valid for writes.
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_extract_texture_rgba(
+    handle: *const BnlHandle,
+    index: usize,
+    out_image: *mut BnlImage,
+) -> BnlStatus {
+    // SAFETY: The caller guarantees the handle is null or live
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return BnlStatus::NullPointer;
+    };
+
+    if out_image.is_null() {
+        return BnlStatus::NullPointer;
+    }
+
+    let Some(raw_asset) = handle.bnl.get_raw_assets().get(index) else {
+        return BnlStatus::NotFound;
+    };
+
+    guard(|| {
+        let texture = match raw_asset.clone().to_asset::<Texture>() {
+            Ok(texture) => texture,
+            Err(crate::asset::AssetError::TypeMismatch) => return BnlStatus::TypeMismatch,
+            Err(_) => return BnlStatus::InvalidData,
+        };
+
+        let Ok(image) = texture.asset().to_rgba_image() else {
+            return BnlStatus::InvalidData;
+        };
+
+        let data = image.bytes().to_vec().into_boxed_slice();
+        let len = data.len();
+
+        // SAFETY: Checked for null above, and the caller guarantees it is writable
+        unsafe {
+            *out_image = BnlImage {
+                width: image.width() as u32,
+                height: image.height() as u32,
+                data: Box::into_raw(data).cast::<u8>(),
+                len,
+            }
+        };
+
+        BnlStatus::Ok
+    })
+}
+
+/**
+Releases the pixels of an image filled by [`bnl_extract_texture_rgba`], and resets it. Passing null,
+or an image which has already been freed, does nothing.
+
+# Safety
+`image` must be null or point to an image filled by [`bnl_extract_texture_rgba`].
+*/
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bnl_free_image(image: *mut BnlImage) {
+    // SAFETY: The caller guarantees the image is null or was filled by this library
+    let Some(image) = (unsafe { image.as_mut() }) else {
+        return;
+    };
+
+    if !image.data.is_null() {
+        // SAFETY: `data` and `len` came from a boxed slice in bnl_extract_texture_rgba
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(image.data, image.len)) });
+    }
+
+    image.data = ptr::null_mut();
+    image.len = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{asset::AssetType, testing::BnlFixture};
+
+    #[test]
+    fn capi_reads_texture() {
+        let bytes = BnlFixture::new()
+            .sample_texture("aid_texture_test")
+            .to_bytes();
+
+        let mut handle = ptr::null_mut();
+        let mut name = [0 as c_char; 32];
+        let mut name_len = 0;
+        let mut asset_type = 0;
+        let mut image = BnlImage {
+            width: 0,
+            height: 0,
+            data: ptr::null_mut(),
+            len: 0,
+        };
+
+        unsafe {
+            assert_eq!(
+                bnl_open(bytes.as_ptr(), bytes.len(), &mut handle),
+                BnlStatus::Ok
+            );
+            assert_eq!(bnl_asset_count(handle), 1);
+
+            assert_eq!(
+                bnl_get_asset_name(handle, 0, name.as_mut_ptr(), 4, &mut name_len),
+                BnlStatus::BufferTooSmall
+            );
+            assert_eq!(name_len, "aid_texture_test".len());
+            assert_eq!(
+                bnl_get_asset_name(handle, 0, name.as_mut_ptr(), name.len(), &mut name_len),
+                BnlStatus::Ok
+            );
+            assert_eq!(
+                std::ffi::CStr::from_ptr(name.as_ptr()).to_str(),
+                Ok("aid_texture_test")
+            );
+
+            assert_eq!(
+                bnl_get_asset_type(handle, 0, &mut asset_type),
+                BnlStatus::Ok
+            );
+            assert_eq!(asset_type, u32::from(AssetType::ResTexture));
+            assert_eq!(
+                bnl_get_asset_type(handle, 1, &mut asset_type),
+                BnlStatus::NotFound
+            );
+
+            assert_eq!(
+                bnl_extract_texture_rgba(handle, 0, &mut image),
+                BnlStatus::Ok
+            );
+            assert_eq!(image.len, image.width as usize * image.height as usize * 4);
+
+            bnl_free_image(&mut image);
+            assert!(image.data.is_null());
+
+            bnl_close(handle);
+        }
+    }
+}
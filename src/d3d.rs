@@ -2,6 +2,9 @@ use gltf_writer::gltf::{self};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use serde::Serialize;
 
+#[cfg(feature = "shader_disasm")]
+pub mod shader_disasm;
+
 type BitCount = usize;
 
 pub trait PixelBits {
@@ -11,7 +14,7 @@ pub trait PixelBits {
 pub type PixelShaderConstant = [u8; 4];
 pub type VertexShaderConstant = [f32; 4];
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 #[repr(u32)]
 pub enum LinearColour {
     A1R5G5B5 = 0x00000010,
@@ -58,7 +61,7 @@ impl PixelBits for LinearColour {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 #[repr(u32)]
 pub enum LinearLuminance {
     A8L8 = 0x00000020,
@@ -103,7 +106,7 @@ impl PixelBits for LinearLuminance {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 #[repr(u32)]
 pub enum Swizzled {
     /* Swizzled formats */
@@ -152,7 +155,7 @@ impl PixelBits for Swizzled {
 }
 
 // TODO: Fix portability issue with enum
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 #[repr(u32)]
 pub enum StandardFormat {
     Unknown = 0xFFFFFFFF,
@@ -220,7 +223,7 @@ impl PixelBits for StandardFormat {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum D3DFormat {
     Swizzled(Swizzled),
@@ -247,6 +250,101 @@ impl From<D3DFormat> for u32 {
     }
 }
 
+impl D3DFormat {
+    /**
+    Looks up the format with the Xbox format code `code`.
+
+    Some codes are shared between formats, such as `0x28` for both swizzled `G8B8` and `V8U8`. The
+    colour formats are preferred, in the order swizzled, linear, standard then luminance.
+    */
+    pub fn from_code(code: u32) -> Option<D3DFormat> {
+        if let Ok(format) = Swizzled::try_from(code) {
+            return Some(D3DFormat::Swizzled(format));
+        }
+
+        if let Ok(format) = LinearColour::try_from(code) {
+            return Some(D3DFormat::Linear(format));
+        }
+
+        match StandardFormat::try_from(code) {
+            Ok(StandardFormat::Unknown) => return None,
+            Ok(format) => return Some(D3DFormat::Standard(format)),
+            Err(_) => (),
+        }
+
+        LinearLuminance::try_from(code)
+            .ok()
+            .map(D3DFormat::Luminance)
+    }
+
+    /**
+    Guesses the formats that a `width` by `height` image of `data_len` bytes could be stored in,
+    for textures whose format code isn't known. Candidates are ordered from most to least likely:
+    compressed formats, then 32 bit colour, then other colour formats, then data formats such as
+    bump maps and depth buffers.
+
+    Mipmaps aren't considered, so `data_len` must be the size of the top level alone.
+    */
+    pub fn guess(width: usize, height: usize, data_len: usize) -> Vec<D3DFormat> {
+        let mut candidates: Vec<D3DFormat> = (0..=0x41)
+            .filter_map(D3DFormat::from_code)
+            .filter(|format| {
+                format.bits_per_pixel() > 0 && format.image_size(width, height) == data_len
+            })
+            .collect();
+
+        candidates.sort_by_key(|format| {
+            if format.is_compressed() {
+                0
+            } else if format.colour_space() == ColourSpace::Linear {
+                3
+            } else if format.bits_per_pixel() == 32 {
+                1
+            } else {
+                2
+            }
+        });
+
+        candidates
+    }
+
+    /// Returns true for the DXT formats, which are stored in blocks of 4x4 pixels
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            D3DFormat::Standard(
+                StandardFormat::DXT1 | StandardFormat::DXT2Or3 | StandardFormat::DXT4Or5
+            )
+        )
+    }
+
+    /// Returns true if the pixels are stored in Morton (Z) order rather than row by row
+    pub fn is_swizzled(&self) -> bool {
+        match self {
+            D3DFormat::Swizzled(_) => true,
+            D3DFormat::Standard(
+                StandardFormat::Unknown | StandardFormat::YUY2 | StandardFormat::UYVY,
+            ) => false,
+            D3DFormat::Standard(_) => !self.is_compressed(),
+            _ => false,
+        }
+    }
+
+    /// The width and height of the blocks that pixels are stored in
+    pub fn block_size(&self) -> usize {
+        if self.is_compressed() { 4 } else { 1 }
+    }
+
+    /// The number of bytes needed to store a `width` by `height` image, rounding each side up to
+    /// a whole number of blocks
+    pub fn image_size(&self, width: usize, height: usize) -> usize {
+        let block_size = self.block_size();
+        let blocks = width.div_ceil(block_size) * height.div_ceil(block_size);
+
+        (blocks * block_size * block_size * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
 impl PixelBits for D3DFormat {
     fn bits_per_pixel(&self) -> BitCount {
         match self {
@@ -261,24 +359,176 @@ impl PixelBits for D3DFormat {
     }
 }
 
+/**
+The colour space that the values of a texture are stored in.
+
+The Xbox samples textures without any conversion, so this is a hint for tools rather than something
+stored in the BNL file. Colour textures are painted in sRGB, while bump maps and depth buffers hold
+linear data which must not be gamma corrected.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub enum ColourSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl D3DFormat {
+    /// The colour space which textures of this format are usually stored in
+    pub fn colour_space(&self) -> ColourSpace {
+        match self {
+            D3DFormat::Luminance(
+                LinearLuminance::V16U16
+                | LinearLuminance::V8U8
+                | LinearLuminance::L6V5U5
+                | LinearLuminance::X8L8V8U8
+                | LinearLuminance::Q8W8V8U8
+                | LinearLuminance::D24S8
+                | LinearLuminance::F24S8
+                | LinearLuminance::D16
+                | LinearLuminance::F16,
+            )
+            | D3DFormat::Standard(
+                StandardFormat::V8U8
+                | StandardFormat::L6V5U5
+                | StandardFormat::X8L8V8U8
+                | StandardFormat::Q8W8V8U8
+                | StandardFormat::V16U16
+                | StandardFormat::D16
+                | StandardFormat::D24S8
+                | StandardFormat::F16
+                | StandardFormat::F24S8,
+            )
+            | D3DFormat::VertexData
+            | D3DFormat::Index16
+            | D3DFormat::ForceDWORD => ColourSpace::Linear,
+            _ => ColourSpace::Srgb,
+        }
+    }
+}
+
+/// The topology of the vertices of a draw call, matching `D3DPRIMITIVETYPE` on the Xbox
 #[repr(u32)]
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum D3DPrimitiveType {
     None = 0,
+    /// Each vertex is a point
     PointList = 1,
+    /// Each pair of vertices is a line
     LineList = 2,
+    /// Each vertex is joined to the next, and the last is joined back to the first
     LineLoop = 3,
+    /// Each vertex is joined to the next
     LineStrip = 4,
+    /// Each group of 3 vertices is a triangle
     TriangleList = 5,
+    /// Each vertex forms a triangle with the 2 before it
     TriangleStrip = 6,
+    /// Each vertex forms a triangle with the one before it and the first
     TriangleFan = 7,
+    /// Each group of 4 vertices is a quad, wound in order
     QuadList = 8,
+    /// Each pair of vertices forms a quad with the pair before it
     QuadStrip = 9,
+    /// All of the vertices form a single convex polygon
     Polygon = 10,
     Max = 11,
     Invalid = 0x7ffffff,
 }
 
+impl D3DPrimitiveType {
+    /// The number of primitives (points, lines, triangles, quads or polygons) drawn using
+    /// `num_vertices` vertices
+    pub fn primitive_count(&self, num_vertices: usize) -> usize {
+        match self {
+            D3DPrimitiveType::PointList => num_vertices,
+            D3DPrimitiveType::LineList => num_vertices / 2,
+            D3DPrimitiveType::LineLoop => {
+                if num_vertices < 2 {
+                    0
+                } else {
+                    num_vertices
+                }
+            }
+            D3DPrimitiveType::LineStrip => num_vertices.saturating_sub(1),
+            D3DPrimitiveType::TriangleList => num_vertices / 3,
+            D3DPrimitiveType::TriangleStrip | D3DPrimitiveType::TriangleFan => {
+                num_vertices.saturating_sub(2)
+            }
+            D3DPrimitiveType::QuadList => num_vertices / 4,
+            D3DPrimitiveType::QuadStrip => num_vertices.saturating_sub(2) / 2,
+            D3DPrimitiveType::Polygon => (num_vertices >= 3) as usize,
+            D3DPrimitiveType::None | D3DPrimitiveType::Max | D3DPrimitiveType::Invalid => 0,
+        }
+    }
+
+    /// Returns true if the primitives are filled faces rather than points or lines
+    pub fn is_faces(&self) -> bool {
+        matches!(
+            self,
+            D3DPrimitiveType::TriangleList
+                | D3DPrimitiveType::TriangleStrip
+                | D3DPrimitiveType::TriangleFan
+                | D3DPrimitiveType::QuadList
+                | D3DPrimitiveType::QuadStrip
+                | D3DPrimitiveType::Polygon
+        )
+    }
+
+    /**
+    Converts the `indices` of a draw call using this primitive type into a triangle list, keeping
+    the winding order of each face. Any incomplete primitive at the end is dropped.
+
+    Returns None for primitive types which aren't faces.
+    */
+    pub fn triangulate(&self, indices: &[u16]) -> Option<Vec<u16>> {
+        let mut triangles = Vec::with_capacity(self.primitive_count(indices.len()) * 3);
+
+        match self {
+            D3DPrimitiveType::TriangleList => {
+                triangles.extend_from_slice(&indices[..indices.len() - indices.len() % 3]);
+            }
+            D3DPrimitiveType::TriangleStrip => {
+                for (i, w) in indices.windows(3).enumerate() {
+                    // Every other triangle of a strip is wound the other way
+                    if i.is_multiple_of(2) {
+                        triangles.extend_from_slice(&[w[0], w[1], w[2]]);
+                    } else {
+                        triangles.extend_from_slice(&[w[1], w[0], w[2]]);
+                    }
+                }
+            }
+            D3DPrimitiveType::TriangleFan | D3DPrimitiveType::Polygon => {
+                if let Some((first, rest)) = indices.split_first() {
+                    for w in rest.windows(2) {
+                        triangles.extend_from_slice(&[*first, w[0], w[1]]);
+                    }
+                }
+            }
+            D3DPrimitiveType::QuadList => {
+                for q in indices.chunks_exact(4) {
+                    triangles.extend_from_slice(&[q[0], q[1], q[2], q[0], q[2], q[3]]);
+                }
+            }
+            D3DPrimitiveType::QuadStrip => {
+                // Quad i is made of vertices (2i, 2i + 1, 2i + 3, 2i + 2)
+                for q in indices.windows(4).step_by(2) {
+                    triangles.extend_from_slice(&[q[0], q[1], q[3], q[0], q[3], q[2]]);
+                }
+            }
+            D3DPrimitiveType::PointList
+            | D3DPrimitiveType::LineList
+            | D3DPrimitiveType::LineLoop
+            | D3DPrimitiveType::LineStrip
+            | D3DPrimitiveType::None
+            | D3DPrimitiveType::Max
+            | D3DPrimitiveType::Invalid => return None,
+        }
+
+        Some(triangles)
+    }
+}
+
 impl From<D3DPrimitiveType> for u32 {
     fn from(value: D3DPrimitiveType) -> Self {
         match value {
@@ -299,13 +549,15 @@ impl From<D3DPrimitiveType> for u32 {
     }
 }
 
+/// Quads and polygons have no glTF equivalent, and must be converted with
+/// [`D3DPrimitiveType::triangulate`] first.
 impl TryFrom<D3DPrimitiveType> for gltf::TopologyMode {
     type Error = String;
 
     fn try_from(value: D3DPrimitiveType) -> Result<Self, String> {
         match value {
             D3DPrimitiveType::PointList => Ok(Self::Points),
-            D3DPrimitiveType::LineList => Ok(Self::LineLoop),
+            D3DPrimitiveType::LineList => Ok(Self::Lines),
             D3DPrimitiveType::LineLoop => Ok(Self::LineLoop),
             D3DPrimitiveType::LineStrip => Ok(Self::LineStrip),
             D3DPrimitiveType::TriangleList => Ok(Self::Triangles),
@@ -314,16 +566,14 @@ impl TryFrom<D3DPrimitiveType> for gltf::TopologyMode {
 
             D3DPrimitiveType::QuadList
             | D3DPrimitiveType::QuadStrip
-            | D3DPrimitiveType::Polygon
-            | D3DPrimitiveType::Max
-            | D3DPrimitiveType::Invalid
-            | D3DPrimitiveType::None => {
-                eprintln!(
-                    "Unknown primitive type encountered: {:?}. Using triangles anyway.",
-                    value
-                );
-                Err(format!("Failed to convert {:?} into a gltf::TopologyMode.", value).to_string())
-            }
+            | D3DPrimitiveType::Polygon => Err(format!(
+                "{:?} has no gltf::TopologyMode, and must be triangulated.",
+                value
+            )),
+
+            D3DPrimitiveType::Max | D3DPrimitiveType::Invalid | D3DPrimitiveType::None => Err(
+                format!("Failed to convert {:?} into a gltf::TopologyMode.", value),
+            ),
         }
     }
 }
@@ -348,3 +598,91 @@ impl From<u32> for D3DPrimitiveType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangulate_primitives() {
+        let indices = [0u16, 1, 2, 3, 4, 5];
+
+        assert_eq!(
+            D3DPrimitiveType::TriangleStrip.triangulate(&indices[..4]),
+            Some(vec![0, 1, 2, 2, 1, 3])
+        );
+        assert_eq!(
+            D3DPrimitiveType::TriangleFan.triangulate(&indices[..4]),
+            Some(vec![0, 1, 2, 0, 2, 3])
+        );
+        assert_eq!(
+            D3DPrimitiveType::QuadList.triangulate(&indices),
+            Some(vec![0, 1, 2, 0, 2, 3])
+        );
+        assert_eq!(
+            D3DPrimitiveType::QuadStrip.triangulate(&indices),
+            Some(vec![0, 1, 3, 0, 3, 2, 2, 3, 5, 2, 5, 4])
+        );
+        assert_eq!(D3DPrimitiveType::LineList.triangulate(&indices), None);
+
+        for prim_type in (0..=11).map(D3DPrimitiveType::from) {
+            // Quads and polygons take more than 1 triangle each
+            let triangles_per_primitive = match prim_type {
+                D3DPrimitiveType::QuadList | D3DPrimitiveType::QuadStrip => 2,
+                D3DPrimitiveType::Polygon => indices.len() - 2,
+                _ => 1,
+            };
+
+            if let Some(triangles) = prim_type.triangulate(&indices) {
+                assert_eq!(
+                    triangles.len(),
+                    prim_type.primitive_count(indices.len()) * triangles_per_primitive * 3
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn format_catalogue() {
+        // Every format which can be looked up writes back the same code
+        for code in (0..=0x41).chain([100, 101, 0x7fffffff, 0xffffffff]) {
+            if let Some(format) = D3DFormat::from_code(code) {
+                assert_eq!(u32::from(format), code, "{:?}", format);
+            }
+        }
+
+        assert_eq!(
+            D3DFormat::from_code(0x28),
+            Some(D3DFormat::Swizzled(Swizzled::G8B8))
+        );
+        assert_eq!(D3DFormat::from_code(0xffffffff), None);
+
+        let dxt1 = D3DFormat::Standard(StandardFormat::DXT1);
+        assert!(dxt1.is_compressed() && !dxt1.is_swizzled());
+        assert_eq!(dxt1.image_size(4, 4), 8);
+        assert_eq!(dxt1.image_size(5, 3), 16);
+
+        let dxt5 = D3DFormat::Standard(StandardFormat::DXT4Or5);
+        assert_eq!(dxt5.image_size(1, 1), 16);
+
+        // A 128x128 image of 16 KiB is most likely DXT3 or DXT5
+        let guesses = D3DFormat::guess(128, 128, 0x4000);
+        assert_eq!(
+            guesses[..2],
+            [
+                D3DFormat::Standard(StandardFormat::DXT2Or3),
+                D3DFormat::Standard(StandardFormat::DXT4Or5)
+            ]
+        );
+        assert!(guesses.contains(&D3DFormat::Swizzled(Swizzled::A8)));
+        assert_eq!(
+            D3DFormat::guess(16, 16, 16 * 16 * 4).first(),
+            Some(&D3DFormat::Swizzled(Swizzled::A8R8G8B8))
+        );
+        assert!(D3DFormat::guess(16, 16, 7).is_empty());
+
+        let a8 = D3DFormat::Swizzled(Swizzled::A8);
+        assert!(a8.is_swizzled() && !a8.is_compressed());
+        assert_eq!(a8.image_size(5, 3), 15);
+    }
+}
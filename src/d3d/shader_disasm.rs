@@ -0,0 +1,615 @@
+//! Disassembly of Xbox (NV2A) vertex shader microcode and pixel shader combiner definitions.
+//!
+//! Field layouts follow the ones documented by the Cxbx-Reloaded and xemu projects.
+
+use std::fmt::{self, Display, Write};
+
+/// The size of a single vertex shader instruction in bytes
+pub const VERTEX_INSTRUCTION_SIZE: usize = 16;
+
+/// The size of a serialised `D3DPIXELSHADERDEF` in bytes
+pub const PIXEL_SHADER_DEF_SIZE: usize = 0xf0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderDisasmError {
+    /// The input was too small to contain the data it claimed to
+    InputTooSmall,
+    /// The vertex shader header had a type byte which isn't known
+    UnknownShaderType(u8),
+}
+
+impl Display for ShaderDisasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderDisasmError::InputTooSmall => write!(f, "Input too small"),
+            ShaderDisasmError::UnknownShaderType(t) => {
+                write!(f, "Unknown vertex shader type 0x{:02x}", t)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderDisasmError {}
+
+/*
+    Vertex shaders
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexShaderType {
+    Regular = 0x20,
+    ReadWrite = 0x73,
+    State = 0xf8,
+}
+
+impl TryFrom<u8> for VertexShaderType {
+    type Error = ShaderDisasmError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x20 => Ok(VertexShaderType::Regular),
+            0x73 => Ok(VertexShaderType::ReadWrite),
+            0xf8 => Ok(VertexShaderType::State),
+            _ => Err(ShaderDisasmError::UnknownShaderType(value)),
+        }
+    }
+}
+
+const MAC_OPCODES: [&str; 14] = [
+    "nop", "mov", "mul", "add", "mad", "dp3", "dph", "dp4", "dst", "min", "max", "slt", "sge",
+    "arl",
+];
+
+const ILU_OPCODES: [&str; 8] = ["nop", "mov", "rcp", "rcc", "rsq", "expp", "logp", "lit"];
+
+const OUTPUT_REGISTERS: [&str; 13] = [
+    "oPos", "o1", "o2", "oD0", "oD1", "oFog", "oPts", "oB0", "oB1", "oT0", "oT1", "oT2", "oT3",
+];
+
+const COMPONENTS: [char; 4] = ['x', 'y', 'z', 'w'];
+
+/// Xbox vertex shader constants are addressed from c-96 to c95
+const CONSTANT_BIAS: i32 = 96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceMux {
+    Temp,
+    Input,
+    Constant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SourceOperand {
+    negate: bool,
+    swizzle: [u8; 4],
+    temp_register: u32,
+    mux: SourceMux,
+}
+
+/// A single decoded 128 bit vertex shader instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexInstruction {
+    dwords: [u32; 4],
+}
+
+impl VertexInstruction {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ShaderDisasmError> {
+        let bytes = bytes
+            .get(..VERTEX_INSTRUCTION_SIZE)
+            .ok_or(ShaderDisasmError::InputTooSmall)?;
+
+        let mut dwords = [0u32; 4];
+
+        bytes
+            .chunks_exact(4)
+            .zip(dwords.iter_mut())
+            .for_each(|(chunk, dword)| *dword = u32::from_le_bytes(chunk.try_into().unwrap()));
+
+        Ok(Self { dwords })
+    }
+
+    #[inline]
+    fn field(&self, dword: usize, shift: u32, bits: u32) -> u32 {
+        (self.dwords[dword] >> shift) & ((1 << bits) - 1)
+    }
+
+    pub fn mac_opcode(&self) -> u32 {
+        self.field(1, 21, 4)
+    }
+
+    pub fn ilu_opcode(&self) -> u32 {
+        self.field(1, 25, 3)
+    }
+
+    /// Whether this is the last instruction of the program
+    pub fn is_final(&self) -> bool {
+        self.field(3, 0, 1) == 1
+    }
+
+    fn constant_index(&self) -> i32 {
+        self.field(1, 13, 8) as i32 - CONSTANT_BIAS
+    }
+
+    fn input_index(&self) -> u32 {
+        self.field(1, 9, 4)
+    }
+
+    fn uses_relative_addressing(&self) -> bool {
+        self.field(3, 1, 1) == 1
+    }
+
+    fn mux(value: u32) -> SourceMux {
+        match value {
+            2 => SourceMux::Input,
+            3 => SourceMux::Constant,
+            _ => SourceMux::Temp,
+        }
+    }
+
+    fn source_a(&self) -> SourceOperand {
+        SourceOperand {
+            negate: self.field(1, 8, 1) == 1,
+            swizzle: [
+                self.field(1, 6, 2) as u8,
+                self.field(1, 4, 2) as u8,
+                self.field(1, 2, 2) as u8,
+                self.field(1, 0, 2) as u8,
+            ],
+            temp_register: self.field(2, 28, 4),
+            mux: Self::mux(self.field(2, 26, 2)),
+        }
+    }
+
+    fn source_b(&self) -> SourceOperand {
+        SourceOperand {
+            negate: self.field(2, 25, 1) == 1,
+            swizzle: [
+                self.field(2, 23, 2) as u8,
+                self.field(2, 21, 2) as u8,
+                self.field(2, 19, 2) as u8,
+                self.field(2, 17, 2) as u8,
+            ],
+            temp_register: self.field(2, 13, 4),
+            mux: Self::mux(self.field(2, 11, 2)),
+        }
+    }
+
+    fn source_c(&self) -> SourceOperand {
+        SourceOperand {
+            negate: self.field(2, 10, 1) == 1,
+            swizzle: [
+                self.field(2, 8, 2) as u8,
+                self.field(2, 6, 2) as u8,
+                self.field(2, 4, 2) as u8,
+                self.field(2, 2, 2) as u8,
+            ],
+            temp_register: (self.field(2, 0, 2) << 2) | self.field(3, 30, 2),
+            mux: Self::mux(self.field(3, 28, 2)),
+        }
+    }
+
+    fn format_source(&self, operand: &SourceOperand) -> String {
+        let mut s = String::new();
+
+        if operand.negate {
+            s.push('-');
+        }
+
+        match operand.mux {
+            SourceMux::Temp => {
+                let _ = write!(s, "r{}", operand.temp_register);
+            }
+            SourceMux::Input => {
+                let _ = write!(s, "v{}", self.input_index());
+            }
+            SourceMux::Constant => match self.uses_relative_addressing() {
+                true => {
+                    let _ = write!(s, "c[a0.x{:+}]", self.constant_index());
+                }
+                false => {
+                    let _ = write!(s, "c{}", self.constant_index());
+                }
+            },
+        }
+
+        let swizzle = operand.swizzle;
+
+        if swizzle != [0, 1, 2, 3] {
+            s.push('.');
+
+            if swizzle.iter().all(|c| *c == swizzle[0]) {
+                s.push(COMPONENTS[swizzle[0] as usize]);
+            } else {
+                swizzle.iter().for_each(|c| s.push(COMPONENTS[*c as usize]));
+            }
+        }
+
+        s
+    }
+
+    fn format_mask(mask: u32) -> String {
+        match mask {
+            0b1111 => String::new(),
+            _ => {
+                let mut s = String::from(".");
+
+                COMPONENTS
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (0b1000 >> i) != 0)
+                    .for_each(|(_, c)| s.push(*c));
+
+                s
+            }
+        }
+    }
+
+    /// The destinations written by the MAC and ILU units respectively
+    fn destinations(&self) -> (Vec<String>, Vec<String>) {
+        let mac_active = self.mac_opcode() != 0;
+        let ilu_active = self.ilu_opcode() != 0;
+
+        let mut mac_dests = vec![];
+        let mut ilu_dests = vec![];
+
+        let temp_register = self.field(3, 20, 4);
+
+        let mac_mask = self.field(3, 24, 4);
+        if mac_active && mac_mask != 0 {
+            match self.mac_opcode() {
+                // arl writes the address register
+                13 => mac_dests.push("a0.x".to_string()),
+                _ => mac_dests.push(format!("r{}{}", temp_register, Self::format_mask(mac_mask))),
+            }
+        }
+
+        let ilu_mask = self.field(3, 16, 4);
+        if ilu_active && ilu_mask != 0 {
+            // When paired with a MAC op, the ILU can only write to r1
+            let ilu_register = if mac_active { 1 } else { temp_register };
+            ilu_dests.push(format!("r{}{}", ilu_register, Self::format_mask(ilu_mask)));
+        }
+
+        let output_mask = self.field(3, 12, 4);
+        if output_mask != 0 {
+            let address = self.field(3, 3, 8);
+
+            let output = match self.field(3, 11, 1) {
+                1 => OUTPUT_REGISTERS
+                    .get(address as usize)
+                    .map(|o| o.to_string())
+                    .unwrap_or_else(|| format!("o{}", address)),
+                _ => format!("c{}", address as i32 - CONSTANT_BIAS),
+            } + &Self::format_mask(output_mask);
+
+            match self.field(3, 2, 1) {
+                0 if mac_active => mac_dests.push(output),
+                1 if ilu_active => ilu_dests.push(output),
+                _ => {}
+            }
+        }
+
+        (mac_dests, ilu_dests)
+    }
+}
+
+impl Display for VertexInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mac_dests, ilu_dests) = self.destinations();
+
+        let mut lines: Vec<String> = vec![];
+
+        let mac_opcode = self.mac_opcode() as usize;
+        if mac_opcode != 0 {
+            let name = MAC_OPCODES.get(mac_opcode).copied().unwrap_or("mac??");
+
+            let sources: Vec<SourceOperand> = match mac_opcode {
+                // mov, arl
+                1 | 13 => vec![self.source_a()],
+                // add
+                3 => vec![self.source_a(), self.source_c()],
+                // mad
+                4 => vec![self.source_a(), self.source_b(), self.source_c()],
+                _ => vec![self.source_a(), self.source_b()],
+            };
+
+            let sources = sources
+                .iter()
+                .map(|s| self.format_source(s))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            mac_dests
+                .iter()
+                .for_each(|dest| lines.push(format!("{} {}, {}", name, dest, sources)));
+        }
+
+        let ilu_opcode = self.ilu_opcode() as usize;
+        if ilu_opcode != 0 {
+            let name = ILU_OPCODES[ilu_opcode];
+            let source = self.format_source(&self.source_c());
+
+            // Prefix ops which are co-issued with the MAC op
+            let prefix = if lines.is_empty() { "" } else { "+" };
+
+            ilu_dests
+                .iter()
+                .for_each(|dest| lines.push(format!("{}{} {}, {}", prefix, name, dest, source)));
+        }
+
+        if lines.is_empty() {
+            lines.push("nop".to_string());
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Decodes raw vertex shader instructions (without a header) until the final instruction or the
+/// end of the input.
+pub fn decode_vertex_program(bytes: &[u8]) -> Result<Vec<VertexInstruction>, ShaderDisasmError> {
+    let mut instructions = vec![];
+
+    for chunk in bytes.chunks_exact(VERTEX_INSTRUCTION_SIZE) {
+        let instruction = VertexInstruction::from_bytes(chunk)?;
+        instructions.push(instruction);
+
+        if instruction.is_final() {
+            break;
+        }
+    }
+
+    Ok(instructions)
+}
+
+/// Disassembles a vertex shader which begins with its 4 byte header (type, version, instruction
+/// count, unknown).
+pub fn disassemble_vertex_shader(bytes: &[u8]) -> Result<String, ShaderDisasmError> {
+    let header = bytes.get(..4).ok_or(ShaderDisasmError::InputTooSmall)?;
+
+    let shader_type = VertexShaderType::try_from(header[0])?;
+    let version = header[1];
+    let num_instructions = header[2] as usize;
+
+    let program = bytes
+        .get(4..4 + num_instructions * VERTEX_INSTRUCTION_SIZE)
+        .ok_or(ShaderDisasmError::InputTooSmall)?;
+
+    let mut listing = format!(
+        "; {:?} vertex shader, version 0x{:02x}, {} instructions\n",
+        shader_type, version, num_instructions
+    );
+
+    listing.push_str(&disassemble_vertex_program(program)?);
+
+    Ok(listing)
+}
+
+/// Disassembles raw vertex shader instructions (without a header)
+pub fn disassemble_vertex_program(bytes: &[u8]) -> Result<String, ShaderDisasmError> {
+    let mut listing = String::new();
+
+    for instruction in decode_vertex_program(bytes)? {
+        let _ = writeln!(listing, "{}", instruction);
+    }
+
+    Ok(listing)
+}
+
+/*
+    Pixel shaders
+*/
+
+const COMBINER_REGISTERS: [&str; 16] = [
+    "zero", "c0", "c1", "fog", "v0", "v1", "r?6", "r?7", "t0", "t1", "t2", "t3", "r0", "r1",
+    "v1r0_sum", "ef_prod",
+];
+
+const TEXTURE_MODES: [&str; 19] = [
+    "none",
+    "project2d",
+    "project3d",
+    "cubemap",
+    "passthru",
+    "clipplane",
+    "bumpenvmap",
+    "bumpenvmap_lum",
+    "brdf",
+    "dot_st",
+    "dot_zw",
+    "dot_rflct_diff",
+    "dot_rflct_spec",
+    "dot_str_3d",
+    "dot_str_cube",
+    "dpndnt_ar",
+    "dpndnt_gb",
+    "dotproduct",
+    "dot_rflct_spec_const",
+];
+
+const OUTPUT_MAPPINGS: [&str; 8] = [
+    "",
+    " (bias)",
+    " (x2)",
+    " (bias, x2)",
+    " (x4)",
+    " (?5)",
+    " (/2)",
+    " (?7)",
+];
+
+fn format_combiner_input(input: u8, alpha_portion: bool) -> String {
+    let register = COMBINER_REGISTERS[(input & 0x0f) as usize];
+
+    let channel = match (input & 0x10 != 0, alpha_portion) {
+        (false, false) => "rgb",
+        (false, true) => "b",
+        (true, _) => "a",
+    };
+
+    let operand = format!("{}.{}", register, channel);
+
+    match input >> 5 {
+        0 => operand,
+        1 => format!("1-{}", operand),
+        2 => format!("expand({})", operand),
+        3 => format!("-expand({})", operand),
+        4 => format!("halfbias({})", operand),
+        5 => format!("-halfbias({})", operand),
+        6 => format!("signed({})", operand),
+        _ => format!("-signed({})", operand),
+    }
+}
+
+fn combiner_inputs(dword: u32) -> [u8; 4] {
+    dword.to_be_bytes()
+}
+
+fn format_combiner_stage(listing: &mut String, portion: &str, inputs: u32, outputs: u32) {
+    let alpha_portion = portion == "alpha";
+
+    let [a, b, c, d] = combiner_inputs(inputs).map(|i| format_combiner_input(i, alpha_portion));
+
+    let cd_register = outputs & 0x0f;
+    let ab_register = (outputs >> 4) & 0x0f;
+    let sum_register = (outputs >> 8) & 0x0f;
+
+    let cd_dot = outputs & (1 << 12) != 0;
+    let ab_dot = outputs & (1 << 13) != 0;
+    let uses_mux = outputs & (1 << 14) != 0;
+    let mapping = OUTPUT_MAPPINGS[((outputs >> 15) & 0b111) as usize];
+
+    let ab = format!("{} {} {}", a, if ab_dot { "dot" } else { "*" }, b);
+    let cd = format!("{} {} {}", c, if cd_dot { "dot" } else { "*" }, d);
+
+    if ab_register != 0 {
+        let _ = writeln!(
+            listing,
+            "  {:<5} {} = {}{}",
+            portion, COMBINER_REGISTERS[ab_register as usize], ab, mapping
+        );
+    }
+
+    if cd_register != 0 {
+        let _ = writeln!(
+            listing,
+            "  {:<5} {} = {}{}",
+            portion, COMBINER_REGISTERS[cd_register as usize], cd, mapping
+        );
+    }
+
+    if sum_register != 0 {
+        let sum = match uses_mux {
+            true => format!("mux({}, {})", ab, cd),
+            false => format!("{} + {}", ab, cd),
+        };
+
+        let _ = writeln!(
+            listing,
+            "  {:<5} {} = {}{}",
+            portion, COMBINER_REGISTERS[sum_register as usize], sum, mapping
+        );
+    }
+}
+
+/// Disassembles a `D3DPIXELSHADERDEF`, the register combiner setup Xbox pixel shaders are
+/// compiled to.
+pub fn disassemble_pixel_shader(bytes: &[u8]) -> Result<String, ShaderDisasmError> {
+    let bytes = bytes
+        .get(..PIXEL_SHADER_DEF_SIZE)
+        .ok_or(ShaderDisasmError::InputTooSmall)?;
+
+    let dword = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let dwords = |offset: usize| -> [u32; 8] { std::array::from_fn(|i| dword(offset + i * 4)) };
+
+    let alpha_inputs = dwords(0x00);
+    let final_inputs_abcd = dword(0x20);
+    let final_inputs_efg = dword(0x24);
+    let constants_0 = dwords(0x28);
+    let constants_1 = dwords(0x48);
+    let alpha_outputs = dwords(0x68);
+    let rgb_inputs = dwords(0x88);
+    let rgb_outputs = dwords(0xb4);
+    let combiner_count = dword(0xd4);
+    let texture_modes = dword(0xd8);
+
+    let num_stages = ((combiner_count & 0xff) as usize).min(8);
+
+    let mut listing = format!("; Pixel shader, {} combiner stages\n", num_stages);
+
+    for stage in 0..4 {
+        let mode = ((texture_modes >> (stage * 5)) & 0x1f) as usize;
+
+        if mode != 0 {
+            let _ = writeln!(
+                listing,
+                "tex t{} ({})",
+                stage,
+                TEXTURE_MODES.get(mode).copied().unwrap_or("unknown")
+            );
+        }
+    }
+
+    for stage in 0..num_stages {
+        let _ = writeln!(
+            listing,
+            "stage {} (c0 = {:08x}, c1 = {:08x})",
+            stage, constants_0[stage], constants_1[stage]
+        );
+
+        format_combiner_stage(&mut listing, "rgb", rgb_inputs[stage], rgb_outputs[stage]);
+        format_combiner_stage(
+            &mut listing,
+            "alpha",
+            alpha_inputs[stage],
+            alpha_outputs[stage],
+        );
+    }
+
+    // The final combiner computes rgb = A * B + (1 - A) * C + D, alpha = G
+    let [a, b, c, d] = combiner_inputs(final_inputs_abcd).map(|i| format_combiner_input(i, false));
+    let [e, f, g, _] = combiner_inputs(final_inputs_efg).map(|i| format_combiner_input(i, false));
+
+    let _ = writeln!(listing, "final");
+    let _ = writeln!(listing, "  ef_prod = {} * {}", e, f);
+    let _ = writeln!(listing, "  rgb   = lerp({}, {}, {}) + {}", a, b, c, d);
+    let _ = writeln!(listing, "  alpha = {}", g);
+
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(dwords: [u32; 4]) -> Vec<u8> {
+        dwords.iter().flat_map(|d| d.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn disassemble_mov_to_opos() -> Result<(), ShaderDisasmError> {
+        // mov oPos, v0 (final)
+        let mov = instruction([
+            0,
+            (1 << 21) | 0x1b, // mac mov, identity swizzle on A
+            2 << 26,          // A from an input register
+            (0b1111 << 12) | (1 << 11) | 1,
+        ]);
+
+        let listing = disassemble_vertex_program(&mov)?;
+
+        assert_eq!(listing.trim(), "mov oPos, v0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn decoding_stops_at_final_instruction() -> Result<(), ShaderDisasmError> {
+        let mut program = instruction([0, 1 << 21, 0, 1]);
+        program.extend(instruction([0, 1 << 21, 0, 0]));
+
+        assert_eq!(decode_vertex_program(&program)?.len(), 1);
+
+        Ok(())
+    }
+}
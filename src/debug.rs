@@ -0,0 +1,392 @@
+/*!
+Helpers for reverse engineering descriptors.
+
+[`annotate`] writes a hexdump of a descriptor with its known fields listed beside the rows they
+start on, so that the bytes and their meaning can be read side by side:
+
+```text
+0x0000  01 00 00 00 12 00 00 00  40 00 40 00 01 00 00 00  ........@.@.....  format (0x4, 4): 0x00000012 (18)
+                                                                             width (0x8, 2): 0x0040 (64)
+```
+
+[`imhex_pattern`] and [`kaitai_struct`] turn the same field lists into templates for ImHex and
+Kaitai Struct, so that external templates can be regenerated whenever a layout here changes.
+
+[`coverage_map`] lists which bytes a parser read and which it skipped, from a
+[`Coverage`] recorded with [`record_spans`](crate::utils::span::record_spans).
+*/
+
+use std::fmt::Write;
+
+use crate::{
+    asset::fields::{DescriptorField, FieldValue},
+    utils::span::Coverage,
+};
+
+/// The number of bytes shown on each row of a hexdump
+pub const BYTES_PER_ROW: usize = 16;
+
+/**
+Writes a hexdump of `bytes`, annotating each row with the fields of `fields` which start in it.
+
+The offsets of `fields` are taken as relative to the start of `bytes`. Fields starting past the
+end of `bytes` are listed after the last row.
+*/
+pub fn annotate(bytes: &[u8], fields: &[DescriptorField]) -> String {
+    let mut fields: Vec<&DescriptorField> = fields.iter().collect();
+    fields.sort_by_key(|field| field.offset);
+
+    let mut out = String::new();
+    let mut remaining = fields.as_slice();
+
+    // The width of everything before the annotations, for aligning the continuation lines
+    let margin = 8 + BYTES_PER_ROW * 3 + 1 + 1 + BYTES_PER_ROW + 2;
+
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        let row_start = row * BYTES_PER_ROW;
+
+        let mut line = format!("{:#06x}  ", row_start);
+
+        for i in 0..BYTES_PER_ROW {
+            if i == BYTES_PER_ROW / 2 {
+                line.push(' ');
+            }
+
+            match chunk.get(i) {
+                Some(b) => write!(line, "{:02x} ", b).expect("Writing to a String can't fail"),
+                None => line.push_str("   "),
+            }
+        }
+
+        line.push(' ');
+        line.extend(chunk.iter().map(|&b| match b {
+            0x20..0x7f => b as char,
+            _ => '.',
+        }));
+        line.push_str(&" ".repeat(BYTES_PER_ROW - chunk.len() + 2));
+
+        let row_fields = remaining
+            .iter()
+            .take_while(|field| field.offset < row_start + BYTES_PER_ROW)
+            .count();
+        let (in_row, rest) = remaining.split_at(row_fields);
+        remaining = rest;
+
+        match in_row.split_first() {
+            Some((first, others)) => {
+                out.push_str(&line);
+                out.push_str(&field_annotation(first));
+                out.push('\n');
+
+                for field in others {
+                    out.push_str(&" ".repeat(margin));
+                    out.push_str(&field_annotation(field));
+                    out.push('\n');
+                }
+            }
+            None => {
+                out.push_str(line.trim_end());
+                out.push('\n');
+            }
+        }
+    }
+
+    for field in remaining {
+        out.push_str(&format!("(past the end) {}\n", field_annotation(field)));
+    }
+
+    out
+}
+
+/// A field of a template, or a run of bytes between the known fields
+enum TemplateItem<'a> {
+    Field(&'a DescriptorField),
+    Gap {
+        offset: usize,
+        size: usize,
+    },
+    /// A field which starts inside of an earlier one, so can't be part of a sequential layout
+    Overlap(&'a DescriptorField),
+}
+
+/// Lays `fields` out in order, with gaps before, between and (if `size` is given) after them
+fn template_items(fields: &[DescriptorField], size: Option<usize>) -> Vec<TemplateItem<'_>> {
+    let mut fields: Vec<&DescriptorField> = fields.iter().collect();
+    fields.sort_by_key(|field| field.offset);
+
+    let mut items = vec![];
+    let mut position = 0;
+
+    for field in fields {
+        if field.offset < position {
+            items.push(TemplateItem::Overlap(field));
+            continue;
+        }
+
+        if field.offset > position {
+            items.push(TemplateItem::Gap {
+                offset: position,
+                size: field.offset - position,
+            });
+        }
+
+        items.push(TemplateItem::Field(field));
+        position = field.offset + field.value.size();
+    }
+
+    if let Some(size) = size
+        && size > position
+    {
+        items.push(TemplateItem::Gap {
+            offset: position,
+            size: size - position,
+        });
+    }
+
+    items
+}
+
+/**
+Writes an ImHex pattern declaring a struct called `name` with the layout of `fields`. Unknown
+bytes between the fields become padding, as do any bytes up to `size`.
+*/
+pub fn imhex_pattern(name: &str, fields: &[DescriptorField], size: Option<usize>) -> String {
+    let mut out = format!("struct {} {{\n", name);
+
+    for item in template_items(fields, size) {
+        let line = match item {
+            TemplateItem::Field(field) => match &field.value {
+                FieldValue::U8(_) => format!("u8 {};", field.name),
+                FieldValue::U16(_) => format!("u16 {};", field.name),
+                FieldValue::U32(_) => format!("u32 {};", field.name),
+                FieldValue::F32(_) => format!("float {};", field.name),
+                FieldValue::Bytes(bytes) => format!("u8 {}[{:#x}];", field.name, bytes.len()),
+            },
+            TemplateItem::Gap { offset, size } => {
+                format!("padding[{:#x}]; // {:#x}", size, offset)
+            }
+            TemplateItem::Overlap(field) => format!(
+                "// {} at {:#x} overlaps an earlier field",
+                field.name, field.offset
+            ),
+        };
+
+        out.push_str(&format!("    {}\n", line));
+    }
+
+    out.push_str("};\n");
+    out
+}
+
+/**
+Writes a little endian Kaitai Struct type with the layout of `fields`, with an id made from `name`
+in snake case. Unknown bytes between the fields become `gap_<offset>` fields, as do any bytes up
+to `size`.
+*/
+pub fn kaitai_struct(name: &str, fields: &[DescriptorField], size: Option<usize>) -> String {
+    let mut id = String::new();
+
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            id.push('_');
+        }
+        id.push(c.to_ascii_lowercase());
+    }
+
+    let mut out = format!("meta:\n  id: {}\n  endian: le\nseq:\n", id);
+
+    for item in template_items(fields, size) {
+        match item {
+            TemplateItem::Field(field) => {
+                out.push_str(&format!("  - id: {}\n", field.name));
+                out.push_str(&match &field.value {
+                    FieldValue::U8(_) => "    type: u1\n".to_string(),
+                    FieldValue::U16(_) => "    type: u2\n".to_string(),
+                    FieldValue::U32(_) => "    type: u4\n".to_string(),
+                    FieldValue::F32(_) => "    type: f4\n".to_string(),
+                    FieldValue::Bytes(bytes) => format!("    size: {:#x}\n", bytes.len()),
+                });
+            }
+            TemplateItem::Gap { offset, size } => {
+                out.push_str(&format!(
+                    "  - id: gap_{:#x}\n    size: {:#x}\n",
+                    offset, size
+                ));
+            }
+            TemplateItem::Overlap(field) => out.push_str(&format!(
+                "  # {} at {:#x} overlaps an earlier field\n",
+                field.name, field.offset
+            )),
+        }
+    }
+
+    out
+}
+
+/**
+Writes the parsed and unparsed regions of `bytes` in order, followed by how much of it was parsed.
+Unparsed regions are followed by a hexdump of up to a row of their bytes, and regions past the end
+of `bytes` are left out.
+
+```text
+0x0000..0x0020  parsed    0x20
+0x0020..0x0028  UNPARSED  0x8   00 00 80 3f 00 00 00 00
+```
+*/
+pub fn coverage_map(bytes: &[u8], coverage: &Coverage) -> String {
+    let mut regions: Vec<(std::ops::Range<usize>, bool)> = coverage
+        .parsed()
+        .iter()
+        .map(|span| (span.clone(), true))
+        .chain(coverage.unparsed().into_iter().map(|span| (span, false)))
+        .collect();
+    regions.sort_by_key(|(span, _)| span.start);
+
+    let mut out = String::new();
+
+    for (span, parsed) in regions {
+        let size = format!("{:#x}", span.len());
+        let line = format!(
+            "{:#06x}..{:#06x}  {:<8}  {:<5}",
+            span.start,
+            span.end,
+            if parsed { "parsed" } else { "UNPARSED" },
+            size
+        );
+
+        let preview: Vec<String> = match bytes.get(span.clone()) {
+            Some(region) if !parsed => region
+                .iter()
+                .take(BYTES_PER_ROW)
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            _ => vec![],
+        };
+
+        let ellipsis = if preview.len() < span.len() && !parsed {
+            " .."
+        } else {
+            ""
+        };
+
+        if preview.is_empty() {
+            out.push_str(line.trim_end());
+        } else {
+            write!(out, "{} {}{}", line, preview.join(" "), ellipsis)
+                .expect("Writing to a String can't fail");
+        }
+
+        out.push('\n');
+    }
+
+    let percent = match coverage.size() {
+        0 => 100.0,
+        size => coverage.parsed_bytes() as f64 * 100.0 / size as f64,
+    };
+
+    writeln!(
+        out,
+        "{:#x} of {:#x} bytes parsed ({:.1}%)",
+        coverage.parsed_bytes(),
+        coverage.size(),
+        percent
+    )
+    .expect("Writing to a String can't fail");
+
+    out
+}
+
+fn field_annotation(field: &DescriptorField) -> String {
+    format!(
+        "{} ({:#x}, {}): {}",
+        field.name,
+        field.offset,
+        field.value.size(),
+        field.value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::fields::FieldValue;
+
+    #[test]
+    fn annotated_hexdump() {
+        let bytes: Vec<u8> = (0..20).collect();
+        let fields = vec![
+            DescriptorField::new("second", 0x4, FieldValue::U32(0x07060504)),
+            DescriptorField::new("first", 0x0, FieldValue::U32(0x03020100)),
+            DescriptorField::new("tail", 0x10, FieldValue::U8(0x10)),
+            DescriptorField::new("missing", 0x40, FieldValue::U8(0)),
+        ];
+
+        let dump = annotate(&bytes, &fields);
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with(
+            "0x0000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  ................  first (0x0, 4)"
+        ));
+        assert!(lines[1].trim_start().starts_with("second (0x4, 4)"));
+        assert_eq!(lines[0].find("first"), lines[1].find("second"));
+        assert!(lines[2].starts_with("0x0010  10 11 12 13"));
+        assert!(lines[2].ends_with("tail (0x10, 1): 0x10 (16)"));
+        assert!(lines[3].starts_with("(past the end) missing"));
+    }
+
+    #[test]
+    fn coverage_regions() {
+        use crate::utils::{reader::Le, span::record_spans};
+
+        let bytes: Vec<u8> = (0..0x20).collect();
+
+        let (_, recording) = record_spans(|| {
+            let mut reader = Le::new(&bytes);
+            let _ = reader.u32();
+            let _ = reader.at(0x18).array::<u32, 2>();
+        });
+
+        let map = coverage_map(&bytes, &recording.coverage(&bytes));
+        let lines: Vec<&str> = map.lines().collect();
+
+        assert_eq!(
+            lines,
+            [
+                "0x0000..0x0004  parsed    0x4",
+                "0x0004..0x0018  UNPARSED  0x14  04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10 11 12 13 ..",
+                "0x0018..0x0020  parsed    0x8",
+                "0x10 of 0x20 bytes parsed (50.0%)",
+            ]
+        );
+    }
+
+    #[test]
+    fn pattern_templates() {
+        let fields = vec![
+            DescriptorField::new("width", 0x8, FieldValue::U16(0)),
+            DescriptorField::new("format", 0x4, FieldValue::U32(0)),
+            DescriptorField::new("format_low", 0x5, FieldValue::U8(0)),
+            DescriptorField::new("scale", 0xa, FieldValue::F32(0.0)),
+        ];
+
+        assert_eq!(
+            imhex_pattern("TextureDescriptor", &fields, Some(0x10)),
+            "struct TextureDescriptor {
+    padding[0x4]; // 0x0
+    u32 format;
+    // format_low at 0x5 overlaps an earlier field
+    u16 width;
+    float scale;
+    padding[0x2]; // 0xe
+};
+"
+        );
+
+        let kaitai = kaitai_struct("TextureDescriptor", &fields, None);
+        assert!(kaitai.starts_with("meta:\n  id: texture_descriptor\n  endian: le\nseq:\n"));
+        assert!(kaitai.contains("  - id: gap_0x0\n    size: 0x4\n  - id: format\n    type: u4\n"));
+        assert!(kaitai.ends_with("  - id: scale\n    type: f4\n"));
+    }
+}
@@ -1 +1,410 @@
+pub mod aid;
+pub mod localization;
 
+use std::fmt;
+
+use gltf_writer::gltf::{self, Gltf, GltfIndex};
+
+use crate::{
+    BNLFile, RawAsset, VirtualResource,
+    asset::{
+        AssetDescriptor, AssetParseError, AssetType,
+        aidlist::AidList,
+        model::{
+            ModelDescriptor,
+            gltf::{GltfOptions, GltfTextureMode, add_model_to_gltf},
+        },
+        script::{
+            Script, ScriptOperation,
+            analysis::ReferenceKind,
+            ops::{KnownOpcode, ScriptOpcode},
+        },
+    },
+};
+
+pub use aid::{Aid, AidBuilder, AidError};
+pub use localization::{LocalizationSet, MissingTranslation};
+
+/// Where a node of a [`Scene`] is placed relative to the root of the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl From<Placement> for gltf::NodeTransform {
+    fn from(placement: Placement) -> Self {
+        gltf::NodeTransform::TRS(placement.translation, placement.rotation, placement.scale)
+    }
+}
+
+/// A box of ghoulies spawned by a `SpawnGhoulieWithBox` script operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhoulieSpawn {
+    pub ghoulybox_aid: String,
+    pub spawn_count: u32,
+    pub actor_attribs_aid: String,
+    /// Scripts don't say where a box is spawned, so this is left at the origin until it is set
+    pub placement: Placement,
+}
+
+/// The state a door is left in by the `UpdateDoor` operations of a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoorState {
+    pub door_id: u32,
+    pub shut: bool,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    /// A script operation was too small to hold the parameter with this name
+    InvalidOperands(KnownOpcode, &'static str),
+    /// A model referenced by the scene could not be converted
+    ModelError(String, AssetParseError),
+    /// The combined gltf could not be finalised
+    ExportError(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::InvalidOperands(opcode, param) => write!(
+                f,
+                "Invalid operands for script operation {:?}: no room for {}",
+                opcode, param
+            ),
+            SceneError::ModelError(aid, e) => write!(f, "Unable to convert model {}: {}", aid, e),
+            SceneError::ExportError(e) => write!(f, "Unable to export scene: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/**
+A level, assembled from the operations of its script.
+
+The background, ghoulie spawns and door states are collected from the script, and can then be
+combined into a single glTF scene using the models found in one or more [`BNLFile`]s.
+
+# Examples
+```no_run
+use bnl::{BNLFile, asset::{aidlist::AidList, script::Script}, game::Scene};
+
+let bnl = BNLFile::from_bytes(&std::fs::read("./level.bnl").unwrap()).unwrap();
+
+let script = bnl.get_asset::<Script>("aid_script_level").unwrap();
+let aidlist = bnl.get_asset::<AidList>("aid_aidlist_level").unwrap();
+
+let scene = Scene::new(script.asset(), aidlist.asset()).unwrap();
+let gltf = scene.to_gltf(&[bnl]).unwrap();
+```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    name: Option<String>,
+    background_aid: Option<String>,
+    spawns: Vec<GhoulieSpawn>,
+    doors: Vec<DoorState>,
+
+    /// Asset ids referenced by the script which are missing from the level's aidlist
+    unlisted_aids: Vec<String>,
+}
+
+impl Scene {
+    pub fn new(script: &Script, aidlist: &AidList) -> Result<Self, SceneError> {
+        let mut scene = Scene::default();
+
+        for op in script.descriptor().operations() {
+            let ScriptOpcode::Known(opcode) = *op.opcode() else {
+                continue;
+            };
+
+            match opcode {
+                KnownOpcode::SetSceneName => {
+                    scene.name = Some(string_param(op, opcode, "scene_name")?);
+                }
+                KnownOpcode::SetBackground => {
+                    scene.background_aid = Some(string_param(op, opcode, "background_aid")?);
+                }
+                KnownOpcode::SpawnGhoulieWithBox => scene.spawns.push(GhoulieSpawn {
+                    ghoulybox_aid: string_param(op, opcode, "ghoulybox_aid")?,
+                    spawn_count: u32_param(op, opcode, "spawn_count")?,
+                    actor_attribs_aid: string_param(op, opcode, "actor_attribs_aid")?,
+                    placement: Placement::default(),
+                }),
+                KnownOpcode::UpdateDoor => {
+                    let door = DoorState {
+                        door_id: u32_param(op, opcode, "door_id")?,
+                        shut: u32_param(op, opcode, "open_status")? != 0,
+                    };
+
+                    // Only the last update of each door matters
+                    match scene.doors.iter_mut().find(|d| d.door_id == door.door_id) {
+                        Some(existing) => *existing = door,
+                        None => scene.doors.push(door),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let referenced = scene.background_aid.iter().chain(
+            scene
+                .spawns
+                .iter()
+                .flat_map(|spawn| [&spawn.ghoulybox_aid, &spawn.actor_attribs_aid].into_iter()),
+        );
+
+        for aid in referenced {
+            if !aidlist.asset_ids().contains(aid) && !scene.unlisted_aids.contains(aid) {
+                scene.unlisted_aids.push(aid.clone());
+            }
+        }
+
+        Ok(scene)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn background_aid(&self) -> Option<&str> {
+        self.background_aid.as_deref()
+    }
+
+    pub fn spawns(&self) -> &[GhoulieSpawn] {
+        &self.spawns
+    }
+
+    /// The spawns of the scene, to set where each one is placed
+    pub fn spawns_mut(&mut self) -> &mut [GhoulieSpawn] {
+        &mut self.spawns
+    }
+
+    pub fn doors(&self) -> &[DoorState] {
+        &self.doors
+    }
+
+    pub fn unlisted_aids(&self) -> &[String] {
+        &self.unlisted_aids
+    }
+
+    /**
+    Builds a glTF containing a single scene, with the background model, each ghoulie spawn and
+    each door as named nodes under one root node.
+
+    Assets are looked up with [`Scene::find_asset`], and only models are converted. Assets which
+    can't be found or aren't models (such as ghoulyboxes, which aren't understood yet) still get a
+    named node, but without any meshes. Each spawn node is moved to its [`Placement`]. Textures
+    are embedded in the document.
+    */
+    pub fn to_gltf(&self, bnls: &[BNLFile]) -> Result<Gltf, SceneError> {
+        let mut gltf = Gltf::default();
+
+        let scene_name = self.name.clone().unwrap_or("scene".to_string());
+        let root_index = gltf.add_node(gltf::Node::new(Some(scene_name.clone())));
+
+        if let Some(background_aid) = &self.background_aid {
+            let asset = Self::find_asset(bnls, ReferenceKind::Background, background_aid);
+            let node = add_model_node(&mut gltf, asset, "background", None)?;
+            add_child(&mut gltf, root_index, node);
+        }
+
+        for (i, spawn) in self.spawns.iter().enumerate() {
+            let node_name = format!("spawn{}_{}", i, spawn.ghoulybox_aid);
+            let asset = Self::find_asset(bnls, ReferenceKind::Ghoulybox, &spawn.ghoulybox_aid);
+            let node = add_model_node(&mut gltf, asset, &node_name, Some(spawn.placement))?;
+            add_child(&mut gltf, root_index, node);
+        }
+
+        for door in &self.doors {
+            let state = if door.shut { "shut" } else { "open" };
+            let node = gltf.add_node(gltf::Node::new(Some(format!(
+                "door{}_{}",
+                door.door_id, state
+            ))));
+            add_child(&mut gltf, root_index, node);
+        }
+
+        let mut scene = gltf::Scene::new(scene_name);
+        scene.add_node(root_index);
+        gltf.add_scene(scene);
+
+        gltf.prepare_for_export()
+            .map_err(|e| SceneError::ExportError(format!("{:?}", e)))?;
+
+        Ok(gltf)
+    }
+
+    /// The first asset in `bnls` named `aid` with the type that `kind` refers to. References
+    /// without a known type, such as backgrounds, match an asset of any type.
+    pub fn find_asset<'a>(
+        bnls: &'a [BNLFile],
+        kind: ReferenceKind,
+        aid: &str,
+    ) -> Option<&'a RawAsset> {
+        bnls.iter()
+            .filter_map(|bnl| bnl.get_raw_asset(aid))
+            .find(|raw_asset| {
+                kind.asset_type()
+                    .is_none_or(|asset_type| raw_asset.metadata().asset_type() == asset_type)
+            })
+    }
+}
+
+/// Adds a node named `node_name` at `placement`, parenting `raw_asset` to it if it is a model
+fn add_model_node(
+    gltf: &mut Gltf,
+    raw_asset: Option<&RawAsset>,
+    node_name: &str,
+    placement: Option<Placement>,
+) -> Result<GltfIndex, SceneError> {
+    let mut node = gltf::Node::new(Some(node_name.to_string()));
+    node.set_transform(placement.map(gltf::NodeTransform::from));
+
+    let node_index = gltf.add_node(node);
+
+    let Some(raw_asset) =
+        raw_asset.filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResModel)
+    else {
+        return Ok(node_index);
+    };
+
+    let aid = raw_asset.name();
+
+    let descriptor = ModelDescriptor::from_bytes(raw_asset.descriptor_bytes())
+        .map_err(|e| SceneError::ModelError(aid.to_string(), e))?;
+
+    let slices: Vec<&[u8]> = match raw_asset.resource_chunks() {
+        Some(chunks) => chunks.iter().map(|chunk| chunk.as_ref()).collect(),
+        None => vec![],
+    };
+    let virtual_res = VirtualResource::from_slices(&slices);
+
+    let added = add_model_to_gltf(
+        gltf,
+        &descriptor,
+        &virtual_res,
+        GltfOptions {
+            texture_mode: GltfTextureMode::Embedded,
+            ..Default::default()
+        },
+        &format!("{}_", node_name),
+        Some(aid),
+        &mut vec![],
+    )
+    .map_err(|e| SceneError::ModelError(aid.to_string(), e))?;
+
+    for model_root in added.root_nodes {
+        add_child(gltf, node_index, model_root);
+    }
+
+    Ok(node_index)
+}
+
+fn add_child(gltf: &mut Gltf, parent: GltfIndex, child: GltfIndex) {
+    if let Some(node) = gltf.nodes_mut().get_mut(parent as usize) {
+        node.add_child(child);
+    }
+}
+
+fn u32_param(
+    op: &ScriptOperation,
+    opcode: KnownOpcode,
+    name: &'static str,
+) -> Result<u32, SceneError> {
+    op.param_bytes(name)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(SceneError::InvalidOperands(opcode, name))
+}
+
+fn string_param(
+    op: &ScriptOperation,
+    opcode: KnownOpcode,
+    name: &'static str,
+) -> Result<String, SceneError> {
+    op.string_param(name)
+        .ok_or(SceneError::InvalidOperands(opcode, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{AssetMetadata, testing::BnlFixture};
+
+    #[test]
+    fn scene_spawns_ghoulybox() -> Result<(), String> {
+        let level = BnlFixture::new()
+            .script(
+                "aid_script_level",
+                r#"
+                SetSceneName "Scummy Scullery"@0x40 00000000 00000000
+                SpawnGhoulieWithBox "aid_ghoulybox_zombie"@0x80 02000000 "aid_actorattribs_zombie"@0x80 00000000
+                UpdateDoor 03000000 01000000 00000000 00000000
+                UpdateDoor 03000000 00000000 00000000 00000000
+                EndScript
+                "#,
+            )
+            .raw_asset(RawAsset::new(
+                AssetMetadata::new("aid_ghoulybox_zombie", AssetType::ResGhoulybox, 0, 0),
+                vec![0; 4],
+                None,
+            ))
+            .build();
+
+        // A script of the same name elsewhere shouldn't be taken for the ghoulybox
+        let other = BnlFixture::new()
+            .script("aid_ghoulybox_zombie", "EndScript")
+            .build();
+
+        let script = level
+            .get_asset::<Script>("aid_script_level")
+            .map_err(|e| e.to_string())?;
+        let aidlist = AidList::from_bnl(&level, |_| true);
+
+        let mut scene = Scene::new(script.asset(), &aidlist).map_err(|e| e.to_string())?;
+
+        assert_eq!(scene.name(), Some("Scummy Scullery"));
+        assert_eq!(
+            scene.spawns(),
+            &[GhoulieSpawn {
+                ghoulybox_aid: "aid_ghoulybox_zombie".to_string(),
+                spawn_count: 2,
+                actor_attribs_aid: "aid_actorattribs_zombie".to_string(),
+                placement: Placement::default(),
+            }]
+        );
+        assert_eq!(
+            scene.doors(),
+            &[DoorState {
+                door_id: 3,
+                shut: false
+            }]
+        );
+        assert_eq!(scene.unlisted_aids(), &["aid_actorattribs_zombie"]);
+
+        let bnls = [other, level];
+        let ghoulybox = Scene::find_asset(&bnls, ReferenceKind::Ghoulybox, "aid_ghoulybox_zombie")
+            .ok_or("The ghoulybox should be found")?;
+        assert_eq!(ghoulybox.metadata().asset_type(), AssetType::ResGhoulybox);
+
+        scene.spawns_mut()[0].placement.translation = [1.0, 0.0, -2.0];
+        scene.to_gltf(&bnls).map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+/*!
+Asset ids, and the conventions the game names them with.
+
+Every asset id starts with `aid_`, followed by the kind of asset and then its name:
+
+```text
+aid_texture_mytexture_a_b
+    ^^^^^^^ ^^^^^^^^^ ^^^
+    kind    base name variant
+```
+
+Names can end with a variant, a run of single character parts like `_a_b`, which is given to
+assets that are alternatives of the same base asset. The base name always keeps at least one part,
+so `aid_texture_a` has the base name `a` and no variant.
+*/
+
+use std::{fmt, str::FromStr};
+
+use crate::asset::MAX_ASSET_NAME_LENGTH;
+
+pub const AID_PREFIX: &str = "aid_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AidError {
+    /// The id doesn't start with `aid_`
+    MissingPrefix,
+    /// The id has no kind, or no name after its kind
+    MissingPart,
+    /// A part given to [`AidBuilder`] contains a character other than a lowercase letter, digit
+    /// or (within the base name) an underscore
+    InvalidPart(String),
+    /// The id is longer than an [`AssetName`](crate::asset::AssetName) can hold
+    TooLong(usize),
+}
+
+impl fmt::Display for AidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AidError::MissingPrefix => write!(f, "Asset id doesn't start with {}", AID_PREFIX),
+            AidError::MissingPart => write!(f, "Asset id is missing its kind or name"),
+            AidError::InvalidPart(part) => write!(f, "Invalid asset id part {:?}", part),
+            AidError::TooLong(len) => write!(
+                f,
+                "Asset id of length {} exceeds the maximum length of {}",
+                len, MAX_ASSET_NAME_LENGTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AidError {}
+
+/**
+An asset id split into its parts.
+
+# Examples
+```
+use bnl::game::Aid;
+
+let aid: Aid = "aid_texture_mytexture_a_b".parse().unwrap();
+assert_eq!(aid.kind(), "texture");
+assert_eq!(aid.base_name(), "mytexture");
+assert_eq!(aid.variant(), Some("a_b"));
+
+let other = Aid::builder("texture", "mytexture").variant("c").build().unwrap();
+assert_eq!(other.to_string(), "aid_texture_mytexture_c");
+assert_eq!(other.base_aid(), aid.base_aid());
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Aid {
+    kind: String,
+    base_name: String,
+    variant: Option<String>,
+}
+
+impl Aid {
+    /**
+    Splits `aid` into its parts. Ids are only checked for the `aid_` prefix and a kind and name, as
+    the game doesn't keep to any stricter rules.
+
+    # Errors
+    - [`AidError::MissingPrefix`] if `aid` doesn't start with `aid_`
+    - [`AidError::MissingPart`] if the kind or name is empty
+    */
+    pub fn parse(aid: &str) -> Result<Self, AidError> {
+        let rest = aid
+            .strip_prefix(AID_PREFIX)
+            .ok_or(AidError::MissingPrefix)?;
+
+        let (kind, name) = rest.split_once('_').ok_or(AidError::MissingPart)?;
+
+        if kind.is_empty() || name.is_empty() {
+            return Err(AidError::MissingPart);
+        }
+
+        let parts: Vec<&str> = name.split('_').collect();
+
+        // Every trailing single character part is part of the variant, leaving at least one part
+        // for the base name
+        let base_len = parts
+            .iter()
+            .rposition(|part| part.chars().count() != 1)
+            .map_or(1, |i| i + 1);
+
+        let variant = match base_len < parts.len() {
+            true => Some(parts[base_len..].join("_")),
+            false => None,
+        };
+
+        Ok(Self {
+            kind: kind.to_string(),
+            base_name: parts[..base_len].join("_"),
+            variant,
+        })
+    }
+
+    /// Starts building an id of `kind` named `base_name`, like `Aid::builder("texture", "grass")`
+    pub fn builder(kind: &str, base_name: &str) -> AidBuilder {
+        AidBuilder {
+            kind: kind.to_string(),
+            base_name: base_name.to_string(),
+            variant: None,
+        }
+    }
+
+    /// The kind of asset, like `texture` or `anim`
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The name of the asset without its kind or variant
+    pub fn base_name(&self) -> &str {
+        &self.base_name
+    }
+
+    /// The single character parts at the end of the name, joined with underscores
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// The id without its variant, which every variant of the same asset shares
+    pub fn base_aid(&self) -> String {
+        format!("{}{}_{}", AID_PREFIX, self.kind, self.base_name)
+    }
+}
+
+impl fmt::Display for Aid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.base_aid())?;
+
+        if let Some(variant) = &self.variant {
+            write!(f, "_{}", variant)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Aid {
+    type Err = AidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Aid::parse(s)
+    }
+}
+
+/// Builds an [`Aid`] which follows the naming conventions, see [`Aid::builder`]
+#[derive(Debug, Clone)]
+pub struct AidBuilder {
+    kind: String,
+    base_name: String,
+    variant: Option<String>,
+}
+
+impl AidBuilder {
+    /// Sets the variant, which is one or more single characters joined with underscores like `a_b`
+    pub fn variant(mut self, variant: &str) -> Self {
+        self.variant = Some(variant.to_string());
+        self
+    }
+
+    /**
+    Checks the parts and builds the id.
+
+    # Errors
+    - [`AidError::MissingPart`] if the kind or base name is empty
+    - [`AidError::InvalidPart`] if a part contains anything other than lowercase letters and
+      digits, with underscores only allowed between the parts of the base name. Variants must
+      be single characters joined by underscores, and the last part of the base name can only be
+      a single character if it is the only part, or it would be read back as part of the variant.
+    - [`AidError::TooLong`] if the id doesn't fit in an asset name
+    */
+    pub fn build(self) -> Result<Aid, AidError> {
+        if self.kind.is_empty() || self.base_name.is_empty() {
+            return Err(AidError::MissingPart);
+        }
+
+        let is_valid = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        };
+
+        if !is_valid(&self.kind) {
+            return Err(AidError::InvalidPart(self.kind));
+        }
+
+        let base_parts: Vec<&str> = self.base_name.split('_').collect();
+
+        if !base_parts.iter().all(|part| is_valid(part))
+            || (base_parts.len() > 1 && base_parts.last().is_some_and(|part| part.len() == 1))
+        {
+            return Err(AidError::InvalidPart(self.base_name));
+        }
+
+        let is_valid_variant = |variant: &str| {
+            variant
+                .split('_')
+                .all(|part| is_valid(part) && part.len() == 1)
+        };
+
+        if let Some(variant) = self.variant.as_deref().filter(|v| !is_valid_variant(v)) {
+            return Err(AidError::InvalidPart(variant.to_string()));
+        }
+
+        let aid = Aid {
+            kind: self.kind,
+            base_name: self.base_name,
+            variant: self.variant,
+        };
+
+        let len = aid.to_string().len();
+        if len > MAX_ASSET_NAME_LENGTH {
+            return Err(AidError::TooLong(len));
+        }
+
+        Ok(aid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aid_parts() -> Result<(), String> {
+        for (aid, kind, base_name, variant) in [
+            (
+                "aid_texture_mytexture_a_b",
+                "texture",
+                "mytexture",
+                Some("a_b"),
+            ),
+            ("aid_texture_a", "texture", "a", None),
+            ("aid_texture_a_b", "texture", "a", Some("b")),
+            (
+                "aid_cutscene_ghoulies_roomwalkins_walkina",
+                "cutscene",
+                "ghoulies_roomwalkins_walkina",
+                None,
+            ),
+            ("aid_loctext_level_en", "loctext", "level_en", None),
+        ] {
+            let parsed = Aid::parse(aid).map_err(|e| e.to_string())?;
+
+            assert_eq!(
+                (parsed.kind(), parsed.base_name(), parsed.variant()),
+                (kind, base_name, variant),
+                "{}",
+                aid
+            );
+            assert_eq!(parsed.to_string(), aid);
+        }
+
+        assert_eq!(Aid::parse("texture_a"), Err(AidError::MissingPrefix));
+        assert_eq!(Aid::parse("aid_texture"), Err(AidError::MissingPart));
+        assert_eq!(Aid::parse("aid__a"), Err(AidError::MissingPart));
+
+        let built = Aid::builder("anim", "walk")
+            .variant("a_b")
+            .build()
+            .map_err(|e| e.to_string())?;
+        assert_eq!(built.to_string(), "aid_anim_walk_a_b");
+        assert_eq!(built.base_aid(), "aid_anim_walk");
+
+        assert!(Aid::builder("anim", "Walk").build().is_err());
+        assert!(Aid::builder("anim", "walk_a").build().is_err());
+        assert!(Aid::builder("anim", "walk").variant("ab").build().is_err());
+        assert_eq!(
+            Aid::builder("anim", &"a".repeat(MAX_ASSET_NAME_LENGTH)).build(),
+            Err(AidError::TooLong(MAX_ASSET_NAME_LENGTH + 9))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+/*!
+Loctext assets grouped across languages.
+
+Levels ship one loctext asset per language, named `<base>_<language>` like `aid_loctext_level_en`
+and `aid_loctext_level_fr`. A [`LocalizationSet`] groups these by their base name, so that the
+translations of each key can be compared side by side.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    BNLFile,
+    asset::{
+        AssetParseError, AssetType,
+        loctext::{LoctextResource, codes::ControlCodes, text::csv_quote},
+    },
+};
+
+/// The keys of a base which one language doesn't have a value for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingTranslation {
+    pub base: String,
+    pub language: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LocalizationSet {
+    languages: Vec<String>,
+
+    /// The loctext of each language, by base name
+    bundles: BTreeMap<String, BTreeMap<String, LoctextResource>>,
+}
+
+impl LocalizationSet {
+    /// Creates an empty set which groups loctexts ending in `_<language>` for each of `languages`
+    pub fn new<S: AsRef<str>>(languages: &[S]) -> Self {
+        Self {
+            languages: languages.iter().map(|l| l.as_ref().to_string()).collect(),
+            bundles: BTreeMap::new(),
+        }
+    }
+
+    /**
+    Adds every loctext asset of `bnl` whose name ends with one of the languages of the set, and
+    returns how many were added. Loctexts already in the set are replaced.
+
+    # Errors
+    - [`AssetParseError`] if one of the loctexts can't be read
+    */
+    pub fn add_bnl(&mut self, bnl: &BNLFile) -> Result<usize, AssetParseError> {
+        let mut added = 0;
+
+        for raw_asset in bnl
+            .get_raw_assets()
+            .iter()
+            .filter(|raw_asset| raw_asset.metadata().asset_type() == AssetType::ResLoctext)
+        {
+            if self.split_language(raw_asset.name()).is_none() {
+                continue;
+            }
+
+            let loctext = LoctextResource::from_raw_asset(raw_asset)?;
+            self.insert(raw_asset.name(), loctext);
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Adds `loctext` under the base and language of `asset_name`. Returns `false` if the name
+    /// doesn't end with one of the languages of the set.
+    pub fn insert(&mut self, asset_name: &str, loctext: LoctextResource) -> bool {
+        let Some((base, language)) = self.split_language(asset_name) else {
+            return false;
+        };
+
+        self.bundles
+            .entry(base.to_string())
+            .or_default()
+            .insert(language.to_string(), loctext);
+
+        true
+    }
+
+    /// Splits `asset_name` into its base and language, if it ends with one of the languages of the
+    /// set
+    pub fn split_language<'a>(&self, asset_name: &'a str) -> Option<(&'a str, &'a str)> {
+        let (base, language) = asset_name.rsplit_once('_')?;
+
+        match !base.is_empty() && self.languages.iter().any(|l| l == language) {
+            true => Some((base, language)),
+            false => None,
+        }
+    }
+
+    pub fn languages(&self) -> &[String] {
+        &self.languages
+    }
+
+    /// The base names of every loctext in the set, in order
+    pub fn bases(&self) -> impl Iterator<Item = &str> {
+        self.bundles.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, base: &str, language: &str) -> Option<&LoctextResource> {
+        self.bundles.get(base)?.get(language)
+    }
+
+    /// Every key which is in any language of `base`, in order
+    pub fn keys(&self, base: &str) -> BTreeSet<&str> {
+        self.bundles
+            .get(base)
+            .into_iter()
+            .flat_map(|bundle| bundle.values())
+            .flat_map(|loctext| loctext.values().keys().map(String::as_str))
+            .collect()
+    }
+
+    /// For each base and language of the set, the keys which another language has a value for
+    /// but it doesn't. A language without a loctext for a base is missing every key.
+    pub fn missing_translations(&self) -> Vec<MissingTranslation> {
+        let mut missing = vec![];
+
+        for (base, bundle) in &self.bundles {
+            let keys = self.keys(base);
+
+            for language in &self.languages {
+                let missing_keys: Vec<String> = keys
+                    .iter()
+                    .filter(|key| {
+                        bundle
+                            .get(language)
+                            .is_none_or(|loctext| !loctext.values().contains_key(**key))
+                    })
+                    .map(|key| key.to_string())
+                    .collect();
+
+                if !missing_keys.is_empty() {
+                    missing.push(MissingTranslation {
+                        base: base.clone(),
+                        language: language.clone(),
+                        keys: missing_keys,
+                    });
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Writes every key of every base as one CSV, with a `base,key` header followed by a column for
+    /// each language. Missing values are left empty, and control codes are escaped with `codes`.
+    pub fn to_csv(&self, codes: &ControlCodes) -> String {
+        let mut text = String::from("base,key");
+
+        for language in &self.languages {
+            text.push(',');
+            text.push_str(language);
+        }
+        text.push('\n');
+
+        for (base, bundle) in &self.bundles {
+            for key in self.keys(base) {
+                text.push_str(&format!("{},{}", csv_quote(base), csv_quote(key)));
+
+                for language in &self.languages {
+                    let value = bundle
+                        .get(language)
+                        .and_then(|loctext| loctext.values().get(key))
+                        .map(|value| csv_quote(&codes.escape(value)))
+                        .unwrap_or_default();
+
+                    text.push(',');
+                    text.push_str(&value);
+                }
+
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::BnlFixture;
+
+    #[test]
+    fn localization_set() -> Result<(), String> {
+        let bnl = BnlFixture::new()
+            .loctext(
+                "aid_loctext_level_en",
+                &[("chaptername__1", "The Haunting"), ("menu_quit", "Quit")],
+            )
+            .loctext("aid_loctext_level_fr", &[("chaptername__1", "La Hantise")])
+            .loctext("aid_loctext_menu_en", &[("menu_start", "Start")])
+            .loctext("aid_loctext_ui", &[("ignored", "Not a language")])
+            .build();
+
+        let mut set = LocalizationSet::new(&["en", "fr"]);
+        assert_eq!(set.add_bnl(&bnl).map_err(|e| e.to_string())?, 3);
+
+        assert_eq!(
+            set.bases().collect::<Vec<_>>(),
+            vec!["aid_loctext_level", "aid_loctext_menu"]
+        );
+        assert_eq!(
+            set.get("aid_loctext_level", "fr")
+                .and_then(|loctext| loctext.values().get("chaptername__1"))
+                .map(String::as_str),
+            Some("La Hantise")
+        );
+
+        assert_eq!(
+            set.missing_translations(),
+            vec![
+                MissingTranslation {
+                    base: "aid_loctext_level".to_string(),
+                    language: "fr".to_string(),
+                    keys: vec!["menu_quit".to_string()],
+                },
+                MissingTranslation {
+                    base: "aid_loctext_menu".to_string(),
+                    language: "fr".to_string(),
+                    keys: vec!["menu_start".to_string()],
+                },
+            ]
+        );
+
+        assert_eq!(
+            set.to_csv(&ControlCodes::default()),
+            concat!(
+                "base,key,en,fr\n",
+                "\"aid_loctext_level\",\"chaptername__1\",\"The Haunting\",\"La Hantise\"\n",
+                "\"aid_loctext_level\",\"menu_quit\",\"Quit\",\n",
+                "\"aid_loctext_menu\",\"menu_start\",\"Start\",\n",
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -1,13 +1,118 @@
-use crate::d3d::{D3DFormat, LinearColour, StandardFormat, Swizzled};
+use std::f32::consts::PI;
+
+use crate::{
+    asset::texture::ResizeFilter,
+    d3d::{ColourSpace, D3DFormat, LinearColour, StandardFormat, Swizzled},
+};
 
 use texpresso::{Format::Bc1, Format::Bc2};
 
+#[cfg(feature = "simd")]
+mod simd;
+
+/// Converts `bytes` from `src_format` to `dst_format`, gamma correcting the colours if the formats
+/// are usually stored in different colour spaces
 pub fn transcode(
     width: usize,
     height: usize,
     src_format: D3DFormat,
     dst_format: D3DFormat,
     bytes: &[u8],
+) -> Result<Vec<u8>, std::io::Error> {
+    transcode_colour(
+        width,
+        height,
+        (src_format, src_format.colour_space()),
+        (dst_format, dst_format.colour_space()),
+        bytes,
+    )
+}
+
+/// Like [`transcode`], but with the colour space of each side given explicitly
+pub fn transcode_colour(
+    width: usize,
+    height: usize,
+    (src_format, src_space): (D3DFormat, ColourSpace),
+    (dst_format, dst_space): (D3DFormat, ColourSpace),
+    bytes: &[u8],
+) -> Result<Vec<u8>, std::io::Error> {
+    // Colour spaces can only be converted while the image is 8 bit RGBA
+    let is_rgba = |format| {
+        matches!(
+            format,
+            D3DFormat::Linear(LinearColour::R8G8B8A8) | D3DFormat::Swizzled(Swizzled::R8G8B8A8)
+        )
+    };
+
+    if src_space == dst_space {
+        transcode_pixels(width, height, src_format, dst_format, bytes)
+    } else if is_rgba(src_format) {
+        let mut converted = bytes.to_vec();
+        convert_colour_space(&mut converted, src_space, dst_space);
+
+        transcode_pixels(width, height, src_format, dst_format, &converted)
+    } else if is_rgba(dst_format) {
+        let mut converted = transcode_pixels(width, height, src_format, dst_format, bytes)?;
+        convert_colour_space(&mut converted, src_space, dst_space);
+
+        Ok(converted)
+    } else {
+        let mut rgba = transcode_pixels(
+            width,
+            height,
+            src_format,
+            D3DFormat::Linear(LinearColour::R8G8B8A8),
+            bytes,
+        )?;
+        convert_colour_space(&mut rgba, src_space, dst_space);
+
+        // Encoding is only implemented from the swizzled format, which has the same layout
+        transcode_pixels(
+            width,
+            height,
+            D3DFormat::Swizzled(Swizzled::R8G8B8A8),
+            dst_format,
+            &rgba,
+        )
+    }
+}
+
+/// Converts the colour channels of 8 bit RGBA pixels from `from` to `to`, leaving alpha as it is
+pub fn convert_colour_space(rgba: &mut [u8], from: ColourSpace, to: ColourSpace) {
+    if from == to {
+        return;
+    }
+
+    let convert = |c: f32| match to {
+        // sRGB to linear
+        ColourSpace::Linear if c <= 0.04045 => c / 12.92,
+        ColourSpace::Linear => ((c + 0.055) / 1.055).powf(2.4),
+        // Linear to sRGB
+        ColourSpace::Srgb if c <= 0.0031308 => c * 12.92,
+        ColourSpace::Srgb => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    };
+
+    let table: Vec<u8> = (0..=255u8)
+        .map(|v| {
+            (convert(v as f32 / 255.0) * 255.0)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+        .collect();
+
+    for pixel in rgba.chunks_exact_mut(4) {
+        for c in &mut pixel[..3] {
+            *c = table[*c as usize];
+        }
+    }
+}
+
+fn transcode_pixels(
+    width: usize,
+    height: usize,
+    src_format: D3DFormat,
+    dst_format: D3DFormat,
+    bytes: &[u8],
 ) -> Result<Vec<u8>, std::io::Error> {
     if src_format == dst_format {
         return Ok(bytes.to_vec().to_owned());
@@ -15,6 +120,9 @@ pub fn transcode(
 
     match src_format {
         D3DFormat::Standard(StandardFormat::DXT1) => match dst_format {
+            #[cfg(feature = "simd")]
+            D3DFormat::Linear(LinearColour::R8G8B8A8) => simd::decode_bc1(bytes, width, height),
+            #[cfg(not(feature = "simd"))]
             D3DFormat::Linear(LinearColour::R8G8B8A8) => {
                 let buf = bcndecode::decode(
                     bytes,
@@ -53,10 +161,7 @@ pub fn transcode(
         D3DFormat::Swizzled(Swizzled::A8B8G8R8) => match dst_format {
             D3DFormat::Linear(LinearColour::R8G8B8A8) => {
                 let mut ret_bytes = bytes.to_vec();
-
-                ret_bytes.chunks_mut(4).for_each(|chunk| {
-                    chunk.reverse();
-                });
+                reorder_channels(&mut ret_bytes, [3, 2, 1, 0]);
 
                 Ok(ret_bytes)
             }
@@ -68,14 +173,7 @@ pub fn transcode(
         D3DFormat::Swizzled(Swizzled::B8G8R8A8) => match dst_format {
             D3DFormat::Linear(LinearColour::R8G8B8A8) => {
                 let mut ret_bytes = bytes.to_vec();
-
-                ret_bytes.chunks_mut(4).for_each(|chunk| {
-                    let b = chunk[0];
-                    let r = chunk[2];
-
-                    chunk[0] = r;
-                    chunk[2] = b;
-                });
+                reorder_channels(&mut ret_bytes, [2, 1, 0, 3]);
 
                 Ok(ret_bytes)
             }
@@ -87,10 +185,7 @@ pub fn transcode(
         D3DFormat::Swizzled(Swizzled::A8R8G8B8) => match dst_format {
             D3DFormat::Linear(LinearColour::R8G8B8A8) => {
                 let mut ret_bytes = bytes.to_vec();
-
-                ret_bytes.chunks_mut(4).for_each(|chunk| {
-                    chunk.rotate_left(1);
-                });
+                reorder_channels(&mut ret_bytes, [1, 2, 3, 0]);
 
                 Ok(ret_bytes)
             }
@@ -101,16 +196,8 @@ pub fn transcode(
 
         D3DFormat::Swizzled(Swizzled::R8G8B8A8) => match dst_format {
             D3DFormat::Standard(StandardFormat::DXT1) => {
-                let mut data_copy = vec![0x00; bytes.len()];
-
-                for (i, chunk) in bytes.chunks_exact(4).enumerate() {
-                    let j = 4 * i;
-
-                    data_copy[j] = chunk[2];
-                    data_copy[j + 1] = chunk[1];
-                    data_copy[j + 2] = chunk[0];
-                    data_copy[j + 3] = chunk[3];
-                }
+                let mut data_copy = bytes.to_vec();
+                reorder_channels(&mut data_copy, [2, 1, 0, 3]);
 
                 let mut converted_bytes = vec![0x00; Bc1.compressed_size(width, height)];
 
@@ -128,16 +215,8 @@ pub fn transcode(
             }
 
             D3DFormat::Standard(StandardFormat::DXT2Or3) => {
-                let mut data_copy = vec![0x00; bytes.len()];
-
-                for (i, chunk) in bytes.chunks_exact(4).enumerate() {
-                    let j = 4 * i;
-
-                    data_copy[j] = chunk[2];
-                    data_copy[j + 1] = chunk[1];
-                    data_copy[j + 2] = chunk[0];
-                    data_copy[j + 3] = chunk[3];
-                }
+                let mut data_copy = bytes.to_vec();
+                reorder_channels(&mut data_copy, [2, 1, 0, 3]);
 
                 let mut converted_bytes = vec![0x00; Bc2.compressed_size(width, height)];
 
@@ -155,16 +234,8 @@ pub fn transcode(
             }
 
             D3DFormat::Swizzled(Swizzled::B8G8R8A8) => {
-                let mut data_copy = vec![0x00; bytes.len()];
-
-                for (i, chunk) in bytes.chunks_exact(4).enumerate() {
-                    let j = 4 * i;
-
-                    data_copy[j] = chunk[2];
-                    data_copy[j + 1] = chunk[1];
-                    data_copy[j + 2] = chunk[0];
-                    data_copy[j + 3] = chunk[3];
-                }
+                let mut data_copy = bytes.to_vec();
+                reorder_channels(&mut data_copy, [2, 1, 0, 3]);
 
                 Ok(data_copy)
             }
@@ -207,16 +278,8 @@ pub fn transcode(
             }
 
             D3DFormat::Swizzled(Swizzled::R8G8B8A8) => {
-                let mut data_copy = vec![0x00; bytes.len()];
-
-                for (i, chunk) in bytes.chunks_exact(4).enumerate() {
-                    let j = 4 * i;
-
-                    data_copy[j] = chunk[2];
-                    data_copy[j + 1] = chunk[1];
-                    data_copy[j + 2] = chunk[0];
-                    data_copy[j + 3] = chunk[3];
-                }
+                let mut data_copy = bytes.to_vec();
+                reorder_channels(&mut data_copy, [2, 1, 0, 3]);
 
                 Ok(data_copy)
             }
@@ -231,3 +294,242 @@ pub fn transcode(
         )),
     }
 }
+
+/// Sets channel `i` of each 4 byte pixel of `bytes` to its channel `order[i]`
+fn reorder_channels(bytes: &mut [u8], order: [u8; 4]) {
+    #[cfg(feature = "simd")]
+    let bytes = simd::reorder_channels(bytes, order);
+
+    for pixel in bytes.chunks_exact_mut(4) {
+        let src = [pixel[0], pixel[1], pixel[2], pixel[3]];
+
+        for (c, i) in pixel.iter_mut().zip(order) {
+            *c = src[i as usize];
+        }
+    }
+}
+
+/**
+Scales a `width` by `height` RGBA image to `new_width` by `new_height`.
+
+The image is resampled one axis at a time. When shrinking, the filter is widened by the scale so
+that every source pixel contributes to the result.
+*/
+pub(crate) fn resize_rgba(
+    width: usize,
+    height: usize,
+    bytes: &[u8],
+    new_width: usize,
+    new_height: usize,
+    filter: ResizeFilter,
+) -> Vec<u8> {
+    if (width, height) == (new_width, new_height) {
+        return bytes[..width * height * 4].to_vec();
+    }
+
+    if width == 0 || height == 0 {
+        return vec![0; new_width * new_height * 4];
+    }
+
+    if filter == ResizeFilter::Nearest {
+        return (0..new_height)
+            .flat_map(|y| {
+                let src_y = y * height / new_height;
+                (0..new_width).map(move |x| (src_y * width + x * width / new_width) * 4)
+            })
+            .flat_map(|start| bytes[start..start + 4].iter().copied())
+            .collect();
+    }
+
+    let columns = contributions(width, new_width, filter);
+    let rows = contributions(height, new_height, filter);
+
+    // Resample each row to the new width
+    let mut horizontal = vec![0f32; new_width * height * 4];
+
+    for y in 0..height {
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            for (i, weight) in weights.iter().enumerate() {
+                let src = (y * width + start + i) * 4;
+                let dst = (y * new_width + x) * 4;
+
+                for c in 0..4 {
+                    horizontal[dst + c] += bytes[src + c] as f32 * weight;
+                }
+            }
+        }
+    }
+
+    // Then each column to the new height
+    let mut resized = vec![0u8; new_width * new_height * 4];
+
+    for (y, (start, weights)) in rows.iter().enumerate() {
+        for x in 0..new_width {
+            let dst = (y * new_width + x) * 4;
+
+            for c in 0..4 {
+                let value: f32 = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| horizontal[((start + i) * new_width + x) * 4 + c] * weight)
+                    .sum();
+
+                resized[dst + c] = value.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    resized
+}
+
+/// The width and height of the characters drawn by [`draw_text`], in pixels
+pub(crate) const GLYPH_SIZE: (usize, usize) = (3, 5);
+
+/**
+Draws `text` into a `width` pixel wide RGBA image, with the top left of the first character at
+(`x`, `y`). Characters are [`GLYPH_SIZE`] with a pixel between them, and anything past the edges of
+the image is clipped.
+
+Only letters (drawn in upper case), digits, `_`, `-`, `.` and spaces have glyphs, everything else
+is drawn as `?`.
+*/
+pub(crate) fn draw_text(
+    bytes: &mut [u8],
+    width: usize,
+    x: usize,
+    y: usize,
+    text: &str,
+    colour: [u8; 4],
+) {
+    let height = bytes.len() / (width * 4).max(1);
+
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * (GLYPH_SIZE.0 + 1);
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_SIZE.0 {
+                let (px, py) = (left + column, y + row);
+
+                if bits & (0b100 >> column) == 0 || px >= width || py >= height {
+                    continue;
+                }
+
+                let start = (py * width + px) * 4;
+                bytes[start..start + 4].copy_from_slice(&colour);
+            }
+        }
+    }
+}
+
+/// The rows of a character, where the lowest 3 bits of each row are its pixels from left to right
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ' ' => [0b000; 5],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// For each destination pixel along an axis, the first source pixel it samples and the normalised
+/// weight of each source pixel from there
+fn contributions(src_len: usize, dst_len: usize, filter: ResizeFilter) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = ((center - support).floor().max(0.0) as usize).min(src_len - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, src_len);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+
+            let total: f32 = weights.iter().sum();
+
+            if total.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            } else {
+                // Only possible when the filter is narrower than a pixel, so take the closest one
+                weights.iter_mut().for_each(|weight| *weight = 0.0);
+                weights[((center as usize).clamp(start, end - 1)) - start] = 1.0;
+            }
+
+            (start, weights)
+        })
+        .collect()
+}
+
+impl ResizeFilter {
+    /// How far from its centre the filter reaches, in source pixels
+    fn support(&self) -> f32 {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        match self {
+            ResizeFilter::Nearest => {
+                if (-0.5..0.5).contains(&x) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x.abs()).max(0.0),
+            ResizeFilter::Lanczos3 => {
+                if x == 0.0 {
+                    1.0
+                } else if x.abs() < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+/*!
+SIMD fast paths for the hottest loops of texture decoding, enabled by the `simd` feature.
+
+The instructions used are checked for when each function is called (SSSE3 on x86_64, NEON on
+aarch64), and the scalar code is used on any other CPU. Both give exactly the same output.
+*/
+
+/**
+Reorders the channels of as many whole 16 byte groups of pixels of `bytes` as possible, see
+[`super::reorder_channels`]. Returns the bytes which are left for the scalar code, which is all of
+them if no SIMD instructions are available.
+*/
+pub(super) fn reorder_channels(bytes: &mut [u8], order: [u8; 4]) -> &mut [u8] {
+    let mask: [u8; 16] = std::array::from_fn(|i| (i / 4 * 4) as u8 + order[i % 4]);
+
+    let split = bytes.len() / 16 * 16;
+
+    match shuffle_all(&mut bytes[..split], &mask) {
+        true => &mut bytes[split..],
+        false => bytes,
+    }
+}
+
+/**
+Decodes `width` by `height` pixels of DXT1 (BC1) blocks to 8 bit RGBA.
+
+The palette of each block is worked out the same way as [`bcndecode`], which is used when this
+feature is disabled. Blocks on the right and bottom edges of images whose sides aren't a multiple
+of 4 are cut short.
+*/
+pub(super) fn decode_bc1(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, std::io::Error> {
+    let expected = width.div_ceil(4) * height.div_ceil(4) * 8;
+
+    if bytes.len() < expected {
+        return Err(std::io::Error::other(format!(
+            "Expected {} bytes of DXT1 data for a {}x{} image, but only found {}",
+            expected,
+            width,
+            height,
+            bytes.len()
+        )));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("ssse3") {
+        // SAFETY: SSSE3 is available
+        return Ok(unsafe { x86::decode_bc1(bytes, width, height) });
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    let shuffle = neon::shuffle_block;
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "neon")))]
+    let shuffle = shuffle_block_scalar;
+
+    Ok(decode_bc1_with(bytes, width, height, shuffle))
+}
+
+/// Decodes each block with `shuffle`, which looks up the 4 rows of pixels of a block from its
+/// palette and index bytes
+#[inline(always)]
+fn decode_bc1_with(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+    shuffle: impl Fn(&[u8; 16], [u8; 4]) -> [[u8; 16]; 4],
+) -> Vec<u8> {
+    let blocks_wide = width.div_ceil(4);
+    let mut rgba = vec![0; width * height * 4];
+
+    for (i, block) in bytes
+        .chunks_exact(8)
+        .take(blocks_wide * height.div_ceil(4))
+        .enumerate()
+    {
+        let palette = bc1_palette(
+            u16::from_le_bytes([block[0], block[1]]),
+            u16::from_le_bytes([block[2], block[3]]),
+        );
+        let rows = shuffle(&palette, [block[4], block[5], block[6], block[7]]);
+
+        let x = (i % blocks_wide) * 4;
+        let y = (i / blocks_wide) * 4;
+        let columns = (width - x).min(4);
+
+        for (row, pixels) in rows.iter().enumerate().take(height - y) {
+            let start = ((y + row) * width + x) * 4;
+            rgba[start..start + columns * 4].copy_from_slice(&pixels[..columns * 4]);
+        }
+    }
+
+    rgba
+}
+
+/// The 4 RGBA colours of a DXT1 block with the endpoints `c0` and `c1`
+fn bc1_palette(c0: u16, c1: u16) -> [u8; 16] {
+    let [r0, g0, b0] = rgb565(c0);
+    let [r1, g1, b1] = rgb565(c1);
+
+    let mix = |a: u8, b: u8, weight_a: u16, weight_b: u16| {
+        ((a as u16 * weight_a + b as u16 * weight_b) / (weight_a + weight_b)) as u8
+    };
+
+    let (p2, p3) = match c0 > c1 {
+        true => (
+            [
+                mix(r0, r1, 2, 1),
+                mix(g0, g1, 2, 1),
+                mix(b0, b1, 2, 1),
+                0xff,
+            ],
+            [
+                mix(r0, r1, 1, 2),
+                mix(g0, g1, 1, 2),
+                mix(b0, b1, 1, 2),
+                0xff,
+            ],
+        ),
+        // The fourth colour is transparent black when the endpoints are in this order
+        false => (
+            [
+                mix(r0, r1, 1, 1),
+                mix(g0, g1, 1, 1),
+                mix(b0, b1, 1, 1),
+                0xff,
+            ],
+            [0, 0, 0, 0],
+        ),
+    };
+
+    let mut palette = [0; 16];
+    palette[..4].copy_from_slice(&[r0, g0, b0, 0xff]);
+    palette[4..8].copy_from_slice(&[r1, g1, b1, 0xff]);
+    palette[8..12].copy_from_slice(&p2);
+    palette[12..].copy_from_slice(&p3);
+
+    palette
+}
+
+fn rgb565(colour: u16) -> [u8; 3] {
+    let r = ((colour & 0xf800) >> 8) as u8;
+    let g = ((colour & 0x07e0) >> 3) as u8;
+    let b = ((colour & 0x001f) << 3) as u8;
+
+    [r | r >> 5, g | g >> 6, b | b >> 5]
+}
+
+/// For each byte of 4 palette indices, the byte shuffle which looks up the pixels they give
+static BC1_MASKS: [[u8; 16]; 256] = bc1_masks();
+
+const fn bc1_masks() -> [[u8; 16]; 256] {
+    let mut masks = [[0; 16]; 256];
+
+    let mut indices = 0;
+    while indices < 256 {
+        let mut i = 0;
+        while i < 16 {
+            let index = (indices >> (i / 4 * 2)) & 3;
+            masks[indices][i] = (index * 4 + i % 4) as u8;
+            i += 1;
+        }
+        indices += 1;
+    }
+
+    masks
+}
+
+#[cfg_attr(
+    all(target_arch = "aarch64", target_feature = "neon"),
+    allow(dead_code)
+)]
+fn shuffle_block_scalar(palette: &[u8; 16], rows: [u8; 4]) -> [[u8; 16]; 4] {
+    rows.map(|indices| BC1_MASKS[indices as usize].map(|i| palette[i as usize]))
+}
+
+/// Shuffles each 16 bytes of `bytes` by `mask`, returning false if there are no SIMD instructions
+/// to do it with
+#[cfg(not(all(target_arch = "aarch64", target_feature = "neon")))]
+fn shuffle_all(bytes: &mut [u8], mask: &[u8; 16]) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("ssse3") {
+        // SAFETY: SSSE3 is available
+        unsafe { x86::shuffle_all(bytes, mask) };
+        return true;
+    }
+
+    let _ = (bytes, mask);
+    false
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+fn shuffle_all(bytes: &mut [u8], mask: &[u8; 16]) -> bool {
+    neon::shuffle_all(bytes, mask);
+    true
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_shuffle_epi8, _mm_storeu_si128};
+
+    use super::{BC1_MASKS, decode_bc1_with};
+
+    /// # Safety
+    /// The CPU must support SSSE3
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn shuffle_all(bytes: &mut [u8], mask: &[u8; 16]) {
+        // SAFETY: Every load and store is of exactly 16 bytes
+        unsafe {
+            let mask = _mm_loadu_si128(mask.as_ptr().cast());
+
+            for chunk in bytes.chunks_exact_mut(16) {
+                let pixels = _mm_loadu_si128(chunk.as_ptr().cast());
+                _mm_storeu_si128(chunk.as_mut_ptr().cast(), _mm_shuffle_epi8(pixels, mask));
+            }
+        }
+    }
+
+    /// # Safety
+    /// The CPU must support SSSE3
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn decode_bc1(bytes: &[u8], width: usize, height: usize) -> Vec<u8> {
+        decode_bc1_with(bytes, width, height, |palette, rows| {
+            // SAFETY: Every load and store is of exactly 16 bytes, and this is only called from a
+            // function which requires SSSE3
+            unsafe {
+                let palette = _mm_loadu_si128(palette.as_ptr().cast());
+
+                rows.map(|indices| {
+                    let mask = _mm_loadu_si128(BC1_MASKS[indices as usize].as_ptr().cast());
+
+                    let mut pixels = [0u8; 16];
+                    _mm_storeu_si128(
+                        pixels.as_mut_ptr().cast::<__m128i>(),
+                        _mm_shuffle_epi8(palette, mask),
+                    );
+                    pixels
+                })
+            }
+        })
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod neon {
+    use std::arch::aarch64::{vld1q_u8, vqtbl1q_u8, vst1q_u8};
+
+    use super::BC1_MASKS;
+
+    pub(super) fn shuffle_all(bytes: &mut [u8], mask: &[u8; 16]) {
+        // SAFETY: NEON is enabled for the target, and every load and store is of exactly 16 bytes
+        unsafe {
+            let mask = vld1q_u8(mask.as_ptr());
+
+            for chunk in bytes.chunks_exact_mut(16) {
+                let pixels = vld1q_u8(chunk.as_ptr());
+                vst1q_u8(chunk.as_mut_ptr(), vqtbl1q_u8(pixels, mask));
+            }
+        }
+    }
+
+    pub(super) fn shuffle_block(palette: &[u8; 16], rows: [u8; 4]) -> [[u8; 16]; 4] {
+        // SAFETY: NEON is enabled for the target, and every load and store is of exactly 16 bytes
+        unsafe {
+            let palette = vld1q_u8(palette.as_ptr());
+
+            rows.map(|indices| {
+                let mut pixels = [0u8; 16];
+                vst1q_u8(
+                    pixels.as_mut_ptr(),
+                    vqtbl1q_u8(palette, vld1q_u8(BC1_MASKS[indices as usize].as_ptr())),
+                );
+                pixels
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn simd_matches_scalar(
+            blocks in prop::collection::vec(any::<u8>(), 8 * 4 * 3),
+            pixels in prop::collection::vec(any::<u8>(), 4 * 37),
+            order in Just(vec![0u8, 1, 2, 3]).prop_shuffle(),
+        ) {
+            // 16x12 pixels, which decodes the same as bcndecode
+            let expected = bcndecode::decode(
+                &blocks,
+                16,
+                12,
+                bcndecode::BcnEncoding::Bc1,
+                bcndecode::BcnDecoderFormat::RGBA,
+            )
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+            let decoded = decode_bc1(&blocks, 16, 12)
+                .map_err(|e| TestCaseError::fail(e.to_string()))?;
+            prop_assert_eq!(&decoded, &expected);
+            prop_assert_eq!(
+                decode_bc1_with(&blocks, 16, 12, shuffle_block_scalar),
+                expected.clone()
+            );
+
+            // Ragged edges are the same pixels, cropped
+            let cropped: Vec<u8> = expected
+                .chunks_exact(16 * 4)
+                .take(10)
+                .flat_map(|row| &row[..13 * 4])
+                .copied()
+                .collect();
+            prop_assert_eq!(
+                decode_bc1(&blocks, 13, 10).map_err(|e| TestCaseError::fail(e.to_string()))?,
+                cropped
+            );
+
+            let mut scalar = pixels.clone();
+            for pixel in scalar.chunks_exact_mut(4) {
+                let src = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                for (c, &o) in pixel.iter_mut().zip(&order) {
+                    *c = src[o as usize];
+                }
+            }
+
+            let mut fast = pixels.clone();
+            super::super::reorder_channels(&mut fast, [order[0], order[1], order[2], order[3]]);
+            prop_assert_eq!(fast, scalar);
+        }
+    }
+
+    #[test]
+    fn bc1_too_small() {
+        assert!(decode_bc1(&[0; 8 * 3], 8, 8).is_err());
+        assert!(decode_bc1(&[0; 8 * 4], 8, 8).is_ok());
+    }
+}
@@ -0,0 +1,241 @@
+/*!
+A text representation of a whole [`BNLFile`], so that archives can be diffed and kept under version
+control.
+
+Descriptors are always stored (as base64) so that the archive can be rebuilt, alongside their
+parsed fields where the asset type supports [`DescriptorFields`](crate::asset::fields::DescriptorFields).
+Resource chunks are stored as SHA-256 hashes, and optionally as base64 as well. Only documents
+which include the resource data can be turned back into a [`BNLFile`].
+*/
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AssetMetadata, AssetOrder, BNLError, BNLFile, BnlVariant, HeaderFields, RawAsset,
+    asset::{AssetName, AssetType, fields::DescriptorField},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BnlJson {
+    pub variant: BnlVariant,
+    /// Documents written before the header was stored get an empty one
+    #[serde(default)]
+    pub header: HeaderFields,
+    pub assets: Vec<AssetJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetJson {
+    pub name: AssetName,
+    /// The numeric [`AssetType`]
+    pub asset_type: u32,
+    /// The name of the asset type. Only informative, this is ignored when reading.
+    #[serde(default, skip_deserializing)]
+    pub type_name: String,
+    pub unk_1: u32,
+    pub unk_2: u32,
+
+    /// The raw descriptor bytes, as base64
+    pub descriptor: String,
+    /// The parsed descriptor fields. Only informative, this is ignored when reading.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<DescriptorField>>,
+
+    /// `None` if the asset has no resource data at all
+    pub resource_chunks: Option<Vec<ResourceChunkJson>>,
+    /// The chunk count of the asset's description, if it isn't the number of resource chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceChunkJson {
+    pub size: usize,
+    /// The SHA-256 of the chunk, as lowercase hex
+    pub sha256: String,
+    /// The bytes of the chunk as base64, if they were included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl AssetJson {
+    pub fn from_raw_asset(raw_asset: &RawAsset, include_data: bool) -> Self {
+        let metadata = raw_asset.metadata();
+
+        AssetJson {
+            name: metadata.name,
+            asset_type: metadata.asset_type().into(),
+            type_name: metadata.asset_type().to_string(),
+            unk_1: metadata.unk_1,
+            unk_2: metadata.unk_2,
+            descriptor: BASE64.encode(raw_asset.descriptor_bytes()),
+            // Descriptors which fail to parse are still kept as bytes, so they can be skipped here
+            fields: raw_asset.descriptor_fields().ok().flatten(),
+            resource_chunks: raw_asset.resource_chunks().map(|chunks| {
+                chunks
+                    .iter()
+                    .zip(raw_asset.chunk_hashes())
+                    .map(|(chunk, sha256)| ResourceChunkJson {
+                        size: chunk.len(),
+                        sha256,
+                        data: include_data.then(|| BASE64.encode(chunk)),
+                    })
+                    .collect()
+            }),
+            chunk_count: raw_asset.stored_chunk_count(),
+        }
+    }
+
+    pub fn to_raw_asset(&self) -> Result<RawAsset, BNLError> {
+        let asset_type = AssetType::try_from(self.asset_type).map_err(|_| {
+            BNLError::DataReadError(format!(
+                "Invalid asset type {} for {}",
+                self.asset_type, self.name
+            ))
+        })?;
+
+        let descriptor_bytes = BASE64.decode(&self.descriptor).map_err(|e| {
+            BNLError::DataReadError(format!("Invalid descriptor for {}: {}", self.name, e))
+        })?;
+
+        let resource_chunks = match &self.resource_chunks {
+            None => None,
+            Some(chunks) => Some(
+                chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, chunk)| self.decode_chunk(i, chunk))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+
+        let mut raw_asset = RawAsset::new(
+            AssetMetadata {
+                name: self.name,
+                asset_type,
+                unk_1: self.unk_1,
+                unk_2: self.unk_2,
+            },
+            descriptor_bytes,
+            resource_chunks,
+        );
+
+        if let Some(chunk_count) = self.chunk_count {
+            raw_asset.set_chunk_count(chunk_count);
+        }
+
+        if let Some(chunks) = &self.resource_chunks {
+            let mismatch = chunks
+                .iter()
+                .zip(raw_asset.chunk_hashes())
+                .position(|(chunk, sha256)| chunk.sha256 != sha256);
+
+            if let Some(index) = mismatch {
+                return Err(self.chunk_mismatch(index));
+            }
+        }
+
+        Ok(raw_asset)
+    }
+
+    fn decode_chunk(&self, index: usize, chunk: &ResourceChunkJson) -> Result<Vec<u8>, BNLError> {
+        let data = chunk.data.as_ref().ok_or_else(|| {
+            BNLError::DataReadError(format!(
+                "Resource chunk {} of {} only has a hash, so the asset can't be rebuilt",
+                index, self.name
+            ))
+        })?;
+
+        let bytes = BASE64.decode(data).map_err(|e| {
+            BNLError::DataReadError(format!(
+                "Invalid resource chunk {} of {}: {}",
+                index, self.name, e
+            ))
+        })?;
+
+        if bytes.len() != chunk.size {
+            return Err(self.chunk_mismatch(index));
+        }
+
+        Ok(bytes)
+    }
+
+    fn chunk_mismatch(&self, index: usize) -> BNLError {
+        BNLError::DataReadError(format!(
+            "Resource chunk {} of {} doesn't match its size or hash",
+            index, self.name
+        ))
+    }
+}
+
+impl BNLFile {
+    /// Converts the [`BNLFile`] to its JSON representation. Resource chunks are only stored as
+    /// hashes unless `include_data` is set.
+    pub fn to_json(&self, include_data: bool) -> BnlJson {
+        BnlJson {
+            variant: self.variant(),
+            header: self.header_fields(),
+            assets: self
+                .get_raw_assets()
+                .iter()
+                .map(|raw_asset| AssetJson::from_raw_asset(raw_asset, include_data))
+                .collect(),
+        }
+    }
+
+    /// Rebuilds a [`BNLFile`] from its JSON representation, keeping the header and the order of
+    /// the assets
+    ///
+    /// # Errors
+    /// - [`BNLError::DataReadError`] if any asset is invalid, or is missing its resource data
+    pub fn from_json(json: &BnlJson) -> Result<BNLFile, BNLError> {
+        let mut bnl = BNLFile::default();
+
+        bnl.set_variant(json.variant);
+        bnl.set_header_fields(json.header);
+        bnl.set_asset_order(AssetOrder::Original);
+
+        for asset in &json.assets {
+            bnl.append_raw_asset(asset.to_raw_asset()?)
+                .map_err(|e| BNLError::DataReadError(e.to_string()))?;
+        }
+
+        Ok(bnl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{SAMPLE_TEXTURE_DESCRIPTOR, SAMPLE_TEXTURE_RESOURCE};
+
+    #[test]
+    fn json_roundtrip() -> Result<(), String> {
+        let mut bnl = BNLFile::default();
+        bnl.set_header_fields(HeaderFields {
+            flags: 0x5,
+            unknown_2: [1, 2, 3, 4, 5],
+        });
+
+        bnl.append_raw_asset(RawAsset::new(
+            AssetMetadata::new("aid_texture_test", AssetType::ResTexture, 1, 2),
+            SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+            Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let text = serde_json::to_string(&bnl.to_json(true)).map_err(|e| e.to_string())?;
+        let json: BnlJson = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+
+        let rebuilt = BNLFile::from_json(&json).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(rebuilt.to_bytes(), bnl.to_bytes());
+
+        // Without the resource data, the archive can't be rebuilt
+        let hashes_only = bnl.to_json(false);
+        assert!(BNLFile::from_json(&hashes_only).is_err());
+
+        Ok(())
+    }
+}
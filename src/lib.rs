@@ -10,19 +10,32 @@ pub mod asset;
 mod bnl;
 pub use bnl::*; // Want to make it just bnl::*, rather than bnl::bnl::*
 
+mod variant;
+pub use variant::BnlVariant;
+
 pub use gltf_writer;
 
-use std::{cmp, fmt::Display};
+use std::{borrow::Cow, cmp, fmt::Display};
 
 use crate::asset::DataViewList;
 
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod debug;
 pub mod game;
+pub mod json;
+pub mod manifest;
 pub mod modding;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod xsb;
 
 #[derive(Debug)]
 pub struct VirtualResource<'a> {
     slices: Vec<&'a [u8]>,
+    /// The offset just past the end of each slice, so that the slice containing an offset can be
+    /// found by binary search
+    ends: Vec<usize>,
 }
 
 #[derive(Debug)]
@@ -60,7 +73,7 @@ impl VirtualResource<'_> {
             slices.push(&bytes[offset..offset + size]);
         }
 
-        Ok(VirtualResource { slices })
+        Ok(VirtualResource::new(slices))
     }
 
     pub fn get_bytes(
@@ -77,39 +90,26 @@ where {
             return Err(VirtualResourceError::SizeOutOfBounds);
         }
 
-        let mut v = vec![0; get_size];
-
-        let mut slice_start = 0usize;
-        let mut total_written = 0usize;
-
-        for slice in &self.slices {
-            let slice_size = slice.len();
-
-            // If this slice is part of the copy in any way
-            if (slice_start + slice_size) > start_offset {
-                let desired_cp_size = get_size - total_written;
-
-                // Get start index
-                let cp_i = start_offset.saturating_sub(slice_start);
-                let cp_size = cmp::min(desired_cp_size, slice_size - cp_i);
+        let mut v = Vec::with_capacity(get_size);
 
-                let cp_j = cp_i + cp_size;
+        let mut offset = start_offset;
+        let first = self.slice_index(start_offset);
 
-                v[total_written..total_written + cp_size].copy_from_slice(&slice[cp_i..cp_j]);
-
-                total_written += cp_size;
-
-                if total_written > get_size {
-                    return Err(VirtualResourceError::SizeOutOfBounds);
-                } else if total_written == get_size {
-                    break;
-                }
+        for (slice, slice_end) in self.slices[first..].iter().zip(&self.ends[first..]) {
+            if v.len() == get_size {
+                break;
             }
 
-            slice_start += slice_size;
+            let cp_i = offset - (slice_end - slice.len());
+            let cp_size = cmp::min(get_size - v.len(), slice.len() - cp_i);
+
+            let copied = &slice[cp_i..cp_i + cp_size];
+            utils::span::record_read(copied);
+            v.extend_from_slice(copied);
+            offset += cp_size;
         }
 
-        if total_written != get_size {
+        if v.len() != get_size {
             return Err(VirtualResourceError::SizeOutOfBounds);
         }
 
@@ -131,16 +131,12 @@ where {
         bytes
     }
 
-    pub fn from_slices<'a>(slices: &'a [&[u8]]) -> VirtualResource<'a> {
-        VirtualResource {
-            slices: slices.to_vec(),
-        }
+    pub fn from_slices<'a>(slices: &[&'a [u8]]) -> VirtualResource<'a> {
+        VirtualResource::new(slices.to_vec())
     }
 
     pub fn len(&self) -> usize {
-        self.slices
-            .iter()
-            .fold(0, |acc, slice: &&[u8]| -> usize { acc + (*slice).len() })
+        self.ends.last().copied().unwrap_or(0)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -152,6 +148,46 @@ where {
     }
 }
 
+impl<'a> VirtualResource<'a> {
+    fn new(slices: Vec<&'a [u8]>) -> Self {
+        let ends = slices
+            .iter()
+            .scan(0, |end, slice| {
+                *end += slice.len();
+                Some(*end)
+            })
+            .collect();
+
+        VirtualResource { slices, ends }
+    }
+
+    /// The index of the slice containing `offset`, skipping empty slices. This is the number of
+    /// slices if `offset` is past the end.
+    fn slice_index(&self, offset: usize) -> usize {
+        self.ends.partition_point(|&end| end <= offset)
+    }
+
+    /// Like [`VirtualResource::get_bytes`], but borrows the bytes when they lie within a single
+    /// slice, only copying them when they span several
+    pub fn get_slice(
+        &self,
+        start_offset: usize,
+        get_size: usize,
+    ) -> Result<Cow<'a, [u8]>, VirtualResourceError> {
+        let i = self.slice_index(start_offset);
+
+        if let Some(slice) = self.slices.get(i).copied() {
+            let start = start_offset - (self.ends[i] - slice.len());
+
+            if start + get_size <= slice.len() {
+                return Ok(Cow::Borrowed(&slice[start..start + get_size]));
+            }
+        }
+
+        self.get_bytes(start_offset, get_size).map(Cow::Owned)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,4 +222,32 @@ mod tests {
         assert_eq!(bytes[20..120], DATA[400..500]);
         assert_eq!(bytes[120..200], DATA[600..680]);
     }
+
+    #[test]
+    fn read_every_range() {
+        let slices = [
+            &DATA[0..10],
+            &DATA[10..10],
+            &DATA[20..27],
+            &DATA[30..31],
+            &DATA[40..40],
+            &DATA[50..65],
+        ];
+
+        let all: Vec<u8> = slices.concat();
+        let virtual_res = VirtualResource::from_slices(&slices);
+
+        assert_eq!(virtual_res.len(), all.len());
+
+        for start in 0..=all.len() {
+            for size in 0..=all.len() - start {
+                let expected = &all[start..start + size];
+
+                assert_eq!(virtual_res.get_bytes(start, size).unwrap(), expected);
+                assert_eq!(&*virtual_res.get_slice(start, size).unwrap(), expected);
+            }
+
+            assert!(virtual_res.get_bytes(start, all.len() - start + 1).is_err());
+        }
+    }
 }
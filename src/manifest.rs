@@ -0,0 +1,289 @@
+/*!
+Checksum manifests for BNL files and extracted asset trees.
+
+A [`Manifest`] records the SHA-256 of every asset's descriptor and resource chunks, keyed by asset
+name. Manifests can be built from a [`BNLFile`] or from a directory written by `bnltool extract`,
+and compared against each other to detect corrupted or modified repacks.
+*/
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use walkdir::WalkDir;
+
+#[cfg(feature = "fs")]
+use crate::asset::AssetParseError;
+use crate::{BNLFile, RawAsset};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetHashes {
+    pub asset_type: u32,
+    /// The SHA-256 of the descriptor
+    pub descriptor: String,
+    /// The SHA-256 of each resource chunk, in order
+    pub resources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub assets: BTreeMap<String, AssetHashes>,
+    /// The asset names in the order the assets were found, which `bnltool create` can use to
+    /// order a repack. Empty for manifests written before the order was recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+}
+
+/// A difference found by [`Manifest::verify`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    /// The asset is in the manifest, but not in the checked files
+    Missing(String),
+    /// The asset is in the checked files, but not in the manifest
+    Unexpected(String),
+    /// The asset name is used more than once in one of the manifests, so only the last asset
+    /// with that name was hashed
+    Duplicate(String),
+    TypeChanged(String),
+    DescriptorChanged(String),
+    ResourceCountChanged {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    ResourceChanged {
+        name: String,
+        index: usize,
+    },
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestMismatch::Missing(name) => write!(f, "{}: missing", name),
+            ManifestMismatch::Unexpected(name) => write!(f, "{}: not in manifest", name),
+            ManifestMismatch::Duplicate(name) => {
+                write!(
+                    f,
+                    "{}: name used more than once, only the last is checked",
+                    name
+                )
+            }
+            ManifestMismatch::TypeChanged(name) => write!(f, "{}: asset type differs", name),
+            ManifestMismatch::DescriptorChanged(name) => write!(f, "{}: descriptor differs", name),
+            ManifestMismatch::ResourceCountChanged {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: expected {} resource chunks, found {}",
+                name, expected, actual
+            ),
+            ManifestMismatch::ResourceChanged { name, index } => {
+                write!(f, "{}: resource chunk {} differs", name, index)
+            }
+        }
+    }
+}
+
+impl AssetHashes {
+    pub fn from_raw_asset(raw_asset: &RawAsset) -> Self {
+        AssetHashes {
+            asset_type: raw_asset.metadata().asset_type().into(),
+            descriptor: raw_asset.descriptor_hash(),
+            resources: raw_asset.chunk_hashes(),
+        }
+    }
+}
+
+impl Manifest {
+    /**
+    Builds a manifest of `raw_assets`, in the order given.
+
+    Assets are keyed by name, so when a name is used more than once only the last asset with
+    that name is hashed. Every occurrence is still kept in [`Manifest::order`], see
+    [`Manifest::duplicates`].
+    */
+    pub fn from_raw_assets<'a, I: IntoIterator<Item = &'a RawAsset>>(raw_assets: I) -> Self {
+        let mut manifest = Manifest::default();
+
+        for raw_asset in raw_assets {
+            let name = raw_asset.name().to_string();
+
+            manifest
+                .assets
+                .insert(name.clone(), AssetHashes::from_raw_asset(raw_asset));
+            manifest.order.push(name);
+        }
+
+        manifest
+    }
+
+    /// The manifest of every asset in `bnl`, in the order they are stored
+    pub fn from_bnl(bnl: &BNLFile) -> Self {
+        Self::from_raw_assets(bnl.get_raw_assets())
+    }
+
+    #[cfg(feature = "fs")]
+    /// Builds a manifest from every asset directory (any directory containing a descriptor file)
+    /// found under `dir`, ordered by path
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, AssetParseError> {
+        let raw_assets = WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_dir() && entry.path().join("descriptor").is_file())
+            .map(|entry| RawAsset::from_dir(entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::from_raw_assets(&raw_assets))
+    }
+
+    /// The names which appear more than once in [`Manifest::order`], in the order they first
+    /// appear
+    pub fn duplicates(&self) -> Vec<&str> {
+        let mut seen = BTreeSet::new();
+        let mut duplicates = vec![];
+
+        for name in &self.order {
+            if !seen.insert(name.as_str()) && !duplicates.contains(&name.as_str()) {
+                duplicates.push(name.as_str());
+            }
+        }
+
+        duplicates
+    }
+
+    /**
+    Compares `actual` against this manifest, returning every difference found.
+
+    Names used more than once in either manifest are reported as
+    [`ManifestMismatch::Duplicate`], as only one asset with each name can be compared.
+    */
+    pub fn verify(&self, actual: &Manifest) -> Vec<ManifestMismatch> {
+        let mut mismatches = vec![];
+
+        for name in self.duplicates().into_iter().chain(actual.duplicates()) {
+            let mismatch = ManifestMismatch::Duplicate(name.to_string());
+
+            if !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+
+        for (name, expected) in &self.assets {
+            let Some(found) = actual.assets.get(name) else {
+                mismatches.push(ManifestMismatch::Missing(name.clone()));
+                continue;
+            };
+
+            if expected.asset_type != found.asset_type {
+                mismatches.push(ManifestMismatch::TypeChanged(name.clone()));
+            }
+
+            if expected.descriptor != found.descriptor {
+                mismatches.push(ManifestMismatch::DescriptorChanged(name.clone()));
+            }
+
+            if expected.resources.len() != found.resources.len() {
+                mismatches.push(ManifestMismatch::ResourceCountChanged {
+                    name: name.clone(),
+                    expected: expected.resources.len(),
+                    actual: found.resources.len(),
+                });
+                continue;
+            }
+
+            expected
+                .resources
+                .iter()
+                .zip(&found.resources)
+                .enumerate()
+                .filter(|(_, (a, b))| a != b)
+                .for_each(|(index, _)| {
+                    mismatches.push(ManifestMismatch::ResourceChanged {
+                        name: name.clone(),
+                        index,
+                    })
+                });
+        }
+
+        for name in actual.assets.keys() {
+            if !self.assets.contains_key(name) {
+                mismatches.push(ManifestMismatch::Unexpected(name.clone()));
+            }
+        }
+
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        AssetMetadata,
+        asset::AssetType,
+        testing::{SAMPLE_TEXTURE_DESCRIPTOR, SAMPLE_TEXTURE_RESOURCE},
+    };
+
+    #[test]
+    fn manifest_detects_changes() {
+        let texture = RawAsset::new(
+            AssetMetadata::new("aid_texture_test", AssetType::ResTexture, 0, 0),
+            SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+            Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+        );
+
+        let manifest = Manifest::from_raw_assets([&texture]);
+        assert!(manifest.verify(&manifest).is_empty());
+        assert_eq!(manifest.order, vec!["aid_texture_test".to_string()]);
+
+        let mut modified = texture.clone();
+        modified.resource_chunks_mut().as_mut().unwrap()[0][0] ^= 0xff;
+
+        assert_eq!(
+            manifest.verify(&Manifest::from_raw_assets([&modified])),
+            vec![ManifestMismatch::ResourceChanged {
+                name: "aid_texture_test".to_string(),
+                index: 0
+            }]
+        );
+
+        assert_eq!(
+            manifest.verify(&Manifest::default()),
+            vec![ManifestMismatch::Missing("aid_texture_test".to_string())]
+        );
+    }
+
+    #[test]
+    fn manifest_reports_duplicates() {
+        let texture = RawAsset::new(
+            AssetMetadata::new("aid_texture_test", AssetType::ResTexture, 0, 0),
+            SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+            Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]),
+        );
+
+        let mut modified = texture.clone();
+        modified.resource_chunks_mut().as_mut().unwrap()[0][0] ^= 0xff;
+
+        let manifest = Manifest::from_raw_assets([&modified, &texture]);
+        assert_eq!(manifest.assets.len(), 1);
+        assert_eq!(manifest.duplicates(), vec!["aid_texture_test"]);
+
+        let single = Manifest::from_raw_assets([&texture]);
+        assert!(single.duplicates().is_empty());
+
+        let duplicate = vec![ManifestMismatch::Duplicate("aid_texture_test".to_string())];
+        assert_eq!(single.verify(&manifest), duplicate);
+        assert_eq!(manifest.verify(&single), duplicate);
+        assert_eq!(manifest.verify(&manifest), duplicate);
+    }
+}
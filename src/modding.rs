@@ -1,14 +1,17 @@
 use std::{
     collections::HashMap,
-    fs,
     io::{self},
-    path::Path,
 };
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
 
+#[cfg(feature = "fs")]
+use crate::asset::{Parse, aidlist::AidList};
 use crate::{
     BNLFile,
-    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType, Parse, aidlist::AidList},
+    asset::{AssetDescriptor, AssetLike, AssetParseError, AssetType},
 };
+#[cfg(feature = "fs")]
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -122,6 +125,7 @@ impl Mod {
     }
 
     /// Reads a mod on disk from a path
+    #[cfg(feature = "fs")]
     pub fn from_dir(mod_dir: impl AsRef<Path>) -> Result<Mod, ModError> {
         // Locate dirs
         let root_dir = fs::read_dir(&mod_dir)?
@@ -416,7 +420,8 @@ impl Mod {
                     continue;
                 };
 
-                bnl.append_raw_asset(raw_asset.clone());
+                bnl.append_raw_asset(raw_asset.clone())
+                    .map_err(|e| e.to_string())?;
                 overrides_applied += 1;
             }
         }
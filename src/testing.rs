@@ -0,0 +1,288 @@
+/*!
+Builds small, valid BNL files from generated data, so that tests don't need files from the game.
+
+This module is always available to the tests of this crate, and to other crates through the
+`testing` feature.
+
+```ignore
+let bytes = BnlFixture::new()
+    .texture("aid_texture_test", 16, 16, D3DFormat::Standard(StandardFormat::DXT1))
+    .script("aid_script_test", "WaitToMoveOn\nEndScript")
+    .loctext("aid_loctext_test", &[("menu_quit", "Quit")])
+    .to_bytes();
+```
+
+Every method panics when its input can't be written, as fixtures are only meant for tests.
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    AssetMetadata, BNLFile, BnlVariant, D3DFormat, RawAsset,
+    asset::{
+        AssetDescriptor, AssetType,
+        loctext::LoctextResource,
+        script::text,
+        texture::{Texture, TextureDescriptor},
+    },
+};
+
+/// The size of a texture descriptor, which is also stored inside it
+const TEXTURE_HEADER_SIZE: u32 = 0x1c;
+
+/// The descriptor of a texture taken from the game, see [`BnlFixture::sample_texture`]
+pub const SAMPLE_TEXTURE_DESCRIPTOR: &[u8] = include_bytes!("asset/test_data/texture0_descriptor");
+/// The resource of a texture taken from the game, see [`BnlFixture::sample_texture`]
+pub const SAMPLE_TEXTURE_RESOURCE: &[u8] = include_bytes!("asset/test_data/texture0_resource0");
+
+#[derive(Debug, Default)]
+pub struct BnlFixture {
+    bnl: BNLFile,
+}
+
+impl BnlFixture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn variant(mut self, variant: BnlVariant) -> Self {
+        self.bnl.set_variant(variant);
+        self
+    }
+
+    /**
+    Adds a `width` by `height` texture in `format`, filled with [`checkerboard_rgba`].
+
+    # Panics
+    If the image can't be converted to `format`, or an asset named `name` already exists
+    */
+    pub fn texture(self, name: &str, width: usize, height: usize, format: D3DFormat) -> Self {
+        self.texture_from_rgba(
+            name,
+            width,
+            height,
+            &checkerboard_rgba(width, height),
+            format,
+        )
+    }
+
+    /**
+    Adds a texture with the pixels of `rgba`, converted to `format`.
+
+    # Panics
+    If `rgba` is smaller than `width * height * 4`, the image can't be converted to `format`, or an
+    asset named `name` already exists
+    */
+    pub fn texture_from_rgba(
+        mut self,
+        name: &str,
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+        format: D3DFormat,
+    ) -> Self {
+        let descriptor =
+            TextureDescriptor::new(format, TEXTURE_HEADER_SIZE, 0, 0, 0x00000001, 0, 0, 0);
+
+        let mut texture = Texture::new(descriptor, vec![]);
+        texture
+            .replace_from_rgba(width, height, rgba, format)
+            .unwrap_or_else(|e| panic!("Unable to create texture {}: {:?}", name, e));
+
+        self.bnl
+            .append_asset(
+                AssetMetadata::new(name, AssetType::ResTexture, 0, 0),
+                texture,
+            )
+            .unwrap_or_else(|e| panic!("Unable to add texture {}: {}", name, e));
+
+        self
+    }
+
+    /**
+    Adds the texture of [`SAMPLE_TEXTURE_DESCRIPTOR`] and [`SAMPLE_TEXTURE_RESOURCE`], which was
+    taken from the game.
+
+    # Panics
+    If an asset named `name` already exists
+    */
+    pub fn sample_texture(self, name: &str) -> Self {
+        self.sample_texture_with_chunks(name, Some(vec![SAMPLE_TEXTURE_RESOURCE.to_vec()]))
+    }
+
+    /**
+    Adds the descriptor of [`BnlFixture::sample_texture`] with `chunks` in place of its resource,
+    for tests which need assets to share or differ in their data.
+
+    # Panics
+    If an asset named `name` already exists
+    */
+    pub fn sample_texture_with_chunks(self, name: &str, chunks: Option<Vec<Vec<u8>>>) -> Self {
+        self.raw_asset(RawAsset::new(
+            AssetMetadata::new(name, AssetType::ResTexture, 0, 0),
+            SAMPLE_TEXTURE_DESCRIPTOR.to_vec(),
+            chunks,
+        ))
+    }
+
+    /**
+    Adds a script assembled from `source`, in the format of [`text::assemble`].
+
+    # Panics
+    If `source` can't be assembled, or an asset named `name` already exists
+    */
+    pub fn script(self, name: &str, source: &str) -> Self {
+        let descriptor = text::assemble(source)
+            .unwrap_or_else(|e| panic!("Unable to assemble script {}: {}", name, e));
+
+        let descriptor_bytes = descriptor
+            .to_bytes()
+            .unwrap_or_else(|e| panic!("Unable to write script {}: {}", name, e));
+
+        self.raw_asset(RawAsset::new(
+            AssetMetadata::new(name, AssetType::ResScript, 0, 0),
+            descriptor_bytes,
+            None,
+        ))
+    }
+
+    /**
+    Adds a loctext containing `values`. The layout of loctext descriptors isn't known yet, so the
+    descriptor is left empty.
+
+    # Panics
+    If a key appears more than once, or an asset named `name` already exists
+    */
+    pub fn loctext(self, name: &str, values: &[(&str, &str)]) -> Self {
+        let map: HashMap<String, String> = values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        assert_eq!(
+            map.len(),
+            values.len(),
+            "Loctext {} has duplicate keys",
+            name
+        );
+
+        let resource = LoctextResource::from_hashmap(map)
+            .and_then(|loctext| loctext.dump())
+            .unwrap_or_else(|e| panic!("Unable to write loctext {}: {}", name, e));
+
+        self.raw_asset(RawAsset::new(
+            AssetMetadata::new(name, AssetType::ResLoctext, 0, 0),
+            vec![],
+            Some(vec![resource]),
+        ))
+    }
+
+    /**
+    Adds an asset which has already been serialised.
+
+    # Panics
+    If an asset named the same as `raw_asset` already exists
+    */
+    pub fn raw_asset(mut self, raw_asset: RawAsset) -> Self {
+        let name = raw_asset.name().to_string();
+
+        self.bnl
+            .append_raw_asset(raw_asset)
+            .unwrap_or_else(|e| panic!("Unable to add {}: {}", name, e));
+
+        self
+    }
+
+    pub fn build(self) -> BNLFile {
+        self.bnl
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        self.bnl.to_bytes()
+    }
+}
+
+/// An RGBA image of 4x4 pixel squares, alternating between opaque black and white
+pub fn checkerboard_rgba(width: usize, height: usize) -> Vec<u8> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x / 4 + y / 4) % 2 == 0))
+        .flat_map(|white| match white {
+            true => [0xff, 0xff, 0xff, 0xff],
+            false => [0x00, 0x00, 0x00, 0xff],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        asset::script::Script,
+        d3d::{StandardFormat, Swizzled},
+    };
+
+    #[test]
+    fn fixture_assets_parse() -> Result<(), String> {
+        let bytes = BnlFixture::new()
+            .texture(
+                "aid_texture_dxt1",
+                16,
+                16,
+                D3DFormat::Standard(StandardFormat::DXT1),
+            )
+            .texture(
+                "aid_texture_bgra",
+                8,
+                8,
+                D3DFormat::Swizzled(Swizzled::B8G8R8A8),
+            )
+            .script("aid_script_test", "WaitToMoveOn\nEndScript")
+            .loctext(
+                "aid_loctext_test",
+                &[("menu_quit", "Quit"), ("menu_start", "Start")],
+            )
+            .to_bytes();
+
+        let bnl = BNLFile::from_bytes(&bytes).map_err(|e| e.to_string())?;
+        assert_eq!(bnl.get_raw_assets().len(), 4);
+
+        for name in ["aid_texture_dxt1", "aid_texture_bgra"] {
+            let texture = bnl
+                .get_asset::<Texture>(name)
+                .map_err(|e| format!("{}: {}", name, e))?;
+            let image = texture.asset().to_rgba_image().map_err(|e| e.to_string())?;
+
+            assert_eq!(image.bytes().len(), image.width() * image.height() * 4);
+        }
+
+        // Uncompressed textures keep their exact pixels
+        let bgra = bnl
+            .get_asset::<Texture>("aid_texture_bgra")
+            .map_err(|e| e.to_string())?;
+        assert_eq!(
+            bgra.asset()
+                .to_rgba_image()
+                .map_err(|e| e.to_string())?
+                .bytes(),
+            checkerboard_rgba(8, 8)
+        );
+
+        let script = bnl
+            .get_asset::<Script>("aid_script_test")
+            .map_err(|e| e.to_string())?;
+        assert_eq!(script.asset().descriptor().operations().len(), 2);
+
+        let loctext = LoctextResource::from_raw_asset(
+            bnl.get_raw_asset("aid_loctext_test")
+                .ok_or("aid_loctext_test is missing")?,
+        )
+        .map_err(|e| e.to_string())?;
+        assert_eq!(
+            loctext.values().get("menu_start").map(String::as_str),
+            Some("Start")
+        );
+
+        Ok(())
+    }
+}
@@ -74,6 +74,10 @@ impl<'a> BitStream<'a> {
     }
 
     pub fn get_bits(&self, mut first_bit: usize, num_bits: usize) -> Result<u32, Error> {
+        if num_bits > u32::BITS as usize {
+            return Err(format!("Unable to read {} bits into a u32", num_bits).into());
+        }
+
         let offset = first_bit % 8;
 
         let mut bits_read = 0;
@@ -94,7 +98,11 @@ impl<'a> BitStream<'a> {
             first_bit += num_read;
         }
 
-        Ok(ret & (2u32.pow(num_bits as u32) - 1))
+        let mask = u32::MAX
+            .checked_shr(u32::BITS - num_bits as u32)
+            .unwrap_or(0);
+
+        Ok(ret & mask)
     }
 
     /// Read up to 32 bits from the bitstream
@@ -110,6 +118,54 @@ impl<'a> BitStream<'a> {
     }
 }
 
+/// The counterpart to [`BitStream`], packing values least significant bit first
+#[derive(Debug, Default, Clone)]
+pub struct BitWriter {
+    bit_cursor: usize,
+    bytes: Vec<u8>,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of bits written so far
+    pub fn len(&self) -> usize {
+        self.bit_cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bit_cursor == 0
+    }
+
+    /// Write the lowest `num_bits` bits of `value` to the bitstream
+    pub fn write(&mut self, value: u32, num_bits: usize) -> Result<(), Error> {
+        if num_bits > u32::BITS as usize {
+            return Err(format!("Unable to write {} bits from a u32", num_bits).into());
+        }
+
+        for i in 0..num_bits {
+            let byte_index = self.bit_cursor / 8;
+
+            if byte_index >= self.bytes.len() {
+                self.bytes.push(0);
+            }
+
+            let bit = ((value >> i) & 1) as u8;
+            self.bytes[byte_index] |= bit << (self.bit_cursor % 8);
+
+            self.bit_cursor += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +211,25 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn bitwriter_roundtrip() -> Result<(), Error> {
+        let mut writer = BitWriter::new();
+
+        writer.write(674, 11)?;
+        writer.write(8028, 13)?;
+        writer.write(154, 9)?;
+        assert_eq!(writer.len(), 33);
+
+        let bytes = writer.into_bytes();
+        assert_eq!(bytes.len(), 5);
+
+        let mut stream = BitStream::new(&bytes);
+
+        assert_eq!(stream.read(11)?, 674);
+        assert_eq!(stream.read(13)?, 8028);
+        assert_eq!(stream.read(9)?, 154);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,163 @@
+/*!
+Structs which are stored as their fields one after another in little endian, with no padding.
+
+[`fixed_layout!`] declares a struct along with its [`FromLeBytes`], [`ToLeBytes`] and [`FixedSize`]
+impls, so the reader, writer and size can't disagree about the order or size of the fields. Every
+field must implement all three, which the primitive types and arrays of them do.
+
+Flags are declared as `u8` rather than `bool`, so a value other than 0 or 1 is written back as it
+was read. Give the struct a `bool` accessor for them instead.
+*/
+
+use crate::utils::reader::{FromLeBytes, Le, ReadError};
+
+/// A value which can be written as little endian bytes
+pub trait ToLeBytes {
+    fn write_le(&self, bytes: &mut Vec<u8>);
+
+    fn to_le_vec(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_le(&mut bytes);
+        bytes
+    }
+}
+
+/// A value which is always serialised as the same number of bytes
+pub trait FixedSize {
+    const SIZE: usize;
+}
+
+/// Reads a fixed layout value from the start of `bytes`
+pub fn from_le_slice<T: FromLeBytes>(bytes: &[u8]) -> Result<T, ReadError> {
+    Le::new(bytes).struct_of()
+}
+
+macro_rules! impl_primitive_layout {
+    ($($t:ty),*) => {
+        $(
+            impl ToLeBytes for $t {
+                fn write_le(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl FixedSize for $t {
+                const SIZE: usize = size_of::<$t>();
+            }
+        )*
+    };
+}
+
+impl_primitive_layout!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<T: ToLeBytes, const N: usize> ToLeBytes for [T; N] {
+    fn write_le(&self, bytes: &mut Vec<u8>) {
+        for value in self {
+            value.write_le(bytes);
+        }
+    }
+}
+
+impl<T: FixedSize, const N: usize> FixedSize for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+}
+
+/**
+Declares a struct which is stored as its fields in order, implementing [`FromLeBytes`],
+[`ToLeBytes`] and [`FixedSize`] for it.
+
+```ignore
+fixed_layout! {
+    #[derive(Debug, Clone)]
+    pub struct Header {
+        pub name_ptr: u32,
+        pub flags: [u8; 4],
+    }
+}
+
+assert_eq!(Header::SIZE, 8);
+```
+*/
+macro_rules! fixed_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::utils::reader::FromLeBytes for $name {
+            fn read_le(
+                reader: &mut $crate::utils::reader::Le,
+            ) -> Result<Self, $crate::utils::reader::ReadError> {
+                // Fields of a struct expression are evaluated in the order they are written
+                Ok(Self {
+                    $($field: reader.struct_of()?,)*
+                })
+            }
+        }
+
+        impl $crate::utils::layout::ToLeBytes for $name {
+            fn write_le(&self, bytes: &mut Vec<u8>) {
+                $($crate::utils::layout::ToLeBytes::write_le(&self.$field, bytes);)*
+            }
+        }
+
+        impl $crate::utils::layout::FixedSize for $name {
+            const SIZE: usize = 0 $(+ <$ty as $crate::utils::layout::FixedSize>::SIZE)*;
+        }
+    };
+}
+
+pub(crate) use fixed_layout;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fixed_layout! {
+        #[derive(Debug, Clone, PartialEq)]
+        struct TestLayout {
+            a: u16,
+            /// Doc comments and other attributes are kept
+            b: [f32; 2],
+            c: u8,
+            d: [u8; 3],
+        }
+    }
+
+    #[test]
+    fn fixed_layout_roundtrip() -> Result<(), String> {
+        assert_eq!(TestLayout::SIZE, 14);
+
+        let value = TestLayout {
+            a: 0x1234,
+            b: [1.0, -2.5],
+            c: 2,
+            d: [7, 8, 9],
+        };
+
+        let bytes = value.to_le_vec();
+        assert_eq!(bytes.len(), TestLayout::SIZE);
+        assert_eq!(bytes[..2], [0x34, 0x12]);
+        assert_eq!(bytes[10..], [2, 7, 8, 9]);
+
+        assert_eq!(
+            from_le_slice::<TestLayout>(&bytes).map_err(|e| e.to_string())?,
+            value
+        );
+        assert!(from_le_slice::<TestLayout>(&bytes[1..]).is_err());
+
+        Ok(())
+    }
+}
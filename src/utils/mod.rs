@@ -1 +1,6 @@
 pub mod bitstream;
+pub mod layout;
+pub mod reader;
+pub mod span;
+pub mod string_table;
+pub mod zlib;
@@ -0,0 +1,243 @@
+/*!
+A bounds checked little endian reader over a byte slice, for parsers which follow pointers around a
+descriptor instead of reading it front to back.
+
+```
+use bnl::utils::reader::Le;
+
+let bytes = [0x08, 0, 0, 0, 0, 0, 0x80, 0x3f, b'n', b'd', 0];
+let mut reader = Le::new(&bytes);
+
+let name_ptr = reader.u32().unwrap();
+assert_eq!(reader.f32().unwrap(), 1.0);
+assert_eq!(reader.at(name_ptr as usize).c_str().unwrap(), "nd");
+
+// Reads past the end are errors rather than panics
+assert!(reader.array::<u32, 4>().is_err());
+```
+*/
+
+use std::fmt;
+
+use crate::utils::span::{self, Spanned};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    /// Reading `len` bytes at `offset` would go past the end of `size` bytes
+    OutOfBounds {
+        offset: usize,
+        len: usize,
+        size: usize,
+    },
+    /// The string at `offset` runs to the end of the bytes without a null terminator
+    Unterminated { offset: usize },
+    /// The string at `offset` isn't valid UTF-8
+    InvalidUtf8 { offset: usize },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::OutOfBounds { offset, len, size } => write!(
+                f,
+                "Unable to read {} bytes at offset {:#x} of {:#x} bytes",
+                len, offset, size
+            ),
+            ReadError::Unterminated { offset } => {
+                write!(f, "String at offset {:#x} has no null terminator", offset)
+            }
+            ReadError::InvalidUtf8 { offset } => {
+                write!(f, "String at offset {:#x} isn't valid UTF-8", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// A value which can be read from little endian bytes by [`Le::struct_of`]
+pub trait FromLeBytes: Sized {
+    fn read_le(reader: &mut Le) -> Result<Self, ReadError>;
+}
+
+macro_rules! impl_from_le_bytes {
+    ($($t:ty),*) => {
+        $(
+            impl FromLeBytes for $t {
+                fn read_le(reader: &mut Le) -> Result<Self, ReadError> {
+                    let bytes = reader.bytes(size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap_or_default()))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_le_bytes!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<T: FromLeBytes, const N: usize> FromLeBytes for [T; N] {
+    fn read_le(reader: &mut Le) -> Result<Self, ReadError> {
+        let values = (0..N)
+            .map(|_| T::read_le(reader))
+            .collect::<Result<Vec<T>, _>>()?;
+
+        Ok(values
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("Exactly N values are read")))
+    }
+}
+
+/// A position within a byte slice, which advances as values are read from it. Copies are cheap,
+/// so following a pointer is [`Le::at`] rather than cloning and seeking a cursor.
+#[derive(Debug, Clone, Copy)]
+pub struct Le<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Le<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// A reader over the same bytes starting at `offset`. Bounds are only checked when reading,
+    /// so pointers to the end of the bytes are fine as long as nothing is read from them.
+    pub fn at(&self, offset: usize) -> Self {
+        Self {
+            bytes: self.bytes,
+            pos: offset,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The length of the whole slice, not just what is left to read
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Everything from the current position to the end, which is empty past the end
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes.get(self.pos..).unwrap_or_default()
+    }
+
+    /// Reads the next `len` bytes
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or(ReadError::OutOfBounds {
+                offset: self.pos,
+                len,
+                size: self.bytes.len(),
+            })?;
+
+        span::record_read(bytes);
+
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), ReadError> {
+        self.bytes(len).map(drop)
+    }
+
+    pub fn struct_of<T: FromLeBytes>(&mut self) -> Result<T, ReadError> {
+        T::read_le(self)
+    }
+
+    /// Reads a value along with the range of bytes it was read from
+    pub fn spanned<T: FromLeBytes>(&mut self) -> Result<Spanned<T>, ReadError> {
+        let start = self.pos;
+        let value = self.struct_of()?;
+
+        Ok(Spanned {
+            value,
+            span: start..self.pos,
+        })
+    }
+
+    pub fn array<T: FromLeBytes, const N: usize>(&mut self) -> Result<[T; N], ReadError> {
+        self.struct_of()
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ReadError> {
+        self.struct_of()
+    }
+
+    pub fn u16(&mut self) -> Result<u16, ReadError> {
+        self.struct_of()
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ReadError> {
+        self.struct_of()
+    }
+
+    pub fn f32(&mut self) -> Result<f32, ReadError> {
+        self.struct_of()
+    }
+
+    /// Reads a null terminated string, leaving the reader after its terminator
+    pub fn c_str(&mut self) -> Result<&'a str, ReadError> {
+        let offset = self.pos;
+
+        let len = self
+            .remaining()
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(ReadError::Unterminated { offset })?;
+
+        let bytes = self.bytes(len + 1)?;
+
+        std::str::from_utf8(&bytes[..len]).map_err(|_| ReadError::InvalidUtf8 { offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn le_reader_bounds() -> Result<(), String> {
+        let bytes = [1, 0, 2, 0, 0, 0, b'a', 0, 0xff, 0];
+        let mut reader = Le::new(&bytes);
+
+        assert_eq!(reader.u16().map_err(|e| e.to_string())?, 1);
+        assert_eq!(reader.u32().map_err(|e| e.to_string())?, 2);
+        assert_eq!(reader.c_str().map_err(|e| e.to_string())?, "a");
+        assert_eq!(reader.position(), 8);
+
+        assert_eq!(reader.c_str(), Err(ReadError::InvalidUtf8 { offset: 8 }));
+        assert_eq!(
+            reader.at(6).array::<u16, 3>(),
+            Err(ReadError::OutOfBounds {
+                offset: 10,
+                len: 2,
+                size: 10
+            })
+        );
+        assert_eq!(reader.at(9).c_str(), Ok(""));
+        assert_eq!(reader.at(8).u8(), Ok(0xff));
+        assert_eq!(
+            reader.at(usize::MAX).u8(),
+            Err(ReadError::OutOfBounds {
+                offset: usize::MAX,
+                len: 1,
+                size: 10
+            })
+        );
+        assert_eq!(
+            reader.at(11).c_str(),
+            Err(ReadError::Unterminated { offset: 11 })
+        );
+
+        Ok(())
+    }
+}
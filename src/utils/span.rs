@@ -0,0 +1,247 @@
+/*!
+Recording which bytes a parser reads, for finding data that nothing parses yet.
+
+Recording is off unless a parser is run inside of [`record_spans`], which then notes every read
+made through [`Le`](crate::utils::reader::Le) and
+[`VirtualResource::get_bytes`](crate::VirtualResource::get_bytes) on the current thread. Reads
+through a `Cursor` aren't seen, so only the parsers of [`RECORDED_ASSET_TYPES`] give a complete
+picture of what they read.
+
+```
+use bnl::utils::{reader::Le, span::record_spans};
+
+let bytes = [1, 0, 0, 0, 0xaa, 0xbb, 2, 0];
+
+let (value, recording) = record_spans(|| Le::new(&bytes).at(6).u16());
+assert_eq!(value.unwrap(), 2);
+
+let coverage = recording.coverage(&bytes);
+assert_eq!(coverage.parsed(), [6..8]);
+assert_eq!(coverage.unparsed(), [0..6]);
+```
+
+[`Le`]: crate::utils::reader::Le
+*/
+
+use std::{
+    cell::RefCell,
+    ops::{Deref, Range},
+};
+
+use crate::asset::AssetType;
+
+/// A value along with the range of bytes it was read from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Range<usize>,
+}
+
+impl<T> Spanned<T> {
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// The asset types whose parsers only read through [`Le`] and
+/// [`VirtualResource::get_bytes`](crate::VirtualResource::get_bytes), so that everything they read
+/// is recorded. The others still read some of their bytes through a `Cursor`.
+pub const RECORDED_ASSET_TYPES: [AssetType; 2] = [AssetType::ResTexture, AssetType::ResFont];
+
+thread_local! {
+    /// The address ranges read so far, when spans are being recorded
+    static RECORDING: RefCell<Option<Vec<Range<usize>>>> = const { RefCell::new(None) };
+}
+
+/// Notes that `bytes` were read, if spans are being recorded on this thread
+pub(crate) fn record_read(bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    RECORDING.with_borrow_mut(|recording| {
+        if let Some(spans) = recording {
+            let start = bytes.as_ptr() as usize;
+            spans.push(start..start + bytes.len());
+        }
+    });
+}
+
+/// Puts back whichever recording was active before [`record_spans`], even if it unwinds
+struct RecordingGuard(Option<Option<Vec<Range<usize>>>>);
+
+impl Drop for RecordingGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            RECORDING.set(previous);
+        }
+    }
+}
+
+/**
+Runs `f`, recording every read it makes on the current thread. Recordings can be nested, in
+which case the outer one also sees the reads of the inner one.
+*/
+pub fn record_spans<R>(f: impl FnOnce() -> R) -> (R, SpanRecording) {
+    let mut guard = RecordingGuard(Some(RECORDING.replace(Some(vec![]))));
+
+    let result = f();
+
+    let spans = RECORDING.take().unwrap_or_default();
+
+    if let Some(Some(outer)) = &mut guard.0 {
+        outer.extend(spans.iter().cloned());
+    }
+
+    (result, SpanRecording { spans })
+}
+
+/// The reads made inside of [`record_spans`]
+#[derive(Debug, Clone, Default)]
+pub struct SpanRecording {
+    /// Address ranges, which only mean anything relative to a slice that was alive while recording
+    spans: Vec<Range<usize>>,
+}
+
+impl SpanRecording {
+    /// The number of reads which were recorded
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Which bytes of `base` were read. `base` must have been alive for the whole recording, as
+    /// otherwise reads of other memory at the same addresses would be counted.
+    pub fn coverage(&self, base: &[u8]) -> Coverage {
+        let base_start = base.as_ptr() as usize;
+        let base_end = base_start + base.len();
+
+        let mut spans: Vec<Range<usize>> = self
+            .spans
+            .iter()
+            .filter(|span| span.start < base_end && span.end > base_start)
+            .map(|span| {
+                span.start.max(base_start) - base_start..span.end.min(base_end) - base_start
+            })
+            .collect();
+
+        spans.sort_by_key(|span| span.start);
+
+        let mut parsed: Vec<Range<usize>> = vec![];
+
+        for span in spans {
+            match parsed.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                _ => parsed.push(span),
+            }
+        }
+
+        Coverage {
+            size: base.len(),
+            parsed,
+        }
+    }
+}
+
+/// The parts of a slice which were and weren't read while recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    size: usize,
+    /// Sorted ranges which don't overlap or touch
+    parsed: Vec<Range<usize>>,
+}
+
+impl Coverage {
+    /// The size of the slice that the coverage is of
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The ranges which were read, in order
+    pub fn parsed(&self) -> &[Range<usize>] {
+        &self.parsed
+    }
+
+    /// The ranges which weren't read, in order
+    pub fn unparsed(&self) -> Vec<Range<usize>> {
+        let mut unparsed = vec![];
+        let mut position = 0;
+
+        for span in &self.parsed {
+            if span.start > position {
+                unparsed.push(position..span.start);
+            }
+
+            position = span.end;
+        }
+
+        if self.size > position {
+            unparsed.push(position..self.size);
+        }
+
+        unparsed
+    }
+
+    /// The number of bytes which were read
+    pub fn parsed_bytes(&self) -> usize {
+        self.parsed.iter().map(|span| span.len()).sum()
+    }
+
+    /// Whether every byte was read
+    pub fn is_complete(&self) -> bool {
+        self.parsed_bytes() == self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::reader::Le;
+
+    #[test]
+    fn span_coverage() -> Result<(), String> {
+        let bytes: Vec<u8> = (0..16).collect();
+
+        let (read, recording) = record_spans(|| -> Result<Spanned<[u8; 2]>, String> {
+            let mut reader = Le::new(&bytes);
+            reader.u32().map_err(|e| e.to_string())?;
+
+            let (_, inner) = record_spans(|| reader.at(2).u32());
+            assert_eq!(inner.coverage(&bytes).parsed(), [2..6]);
+
+            reader.at(10).spanned().map_err(|e| e.to_string())
+        });
+
+        let read = read?;
+        assert_eq!(read.span, 10..12);
+        assert_eq!(*read, [10, 11]);
+
+        let coverage = recording.coverage(&bytes);
+        assert_eq!(coverage.parsed(), [0..6, 10..12]);
+        assert_eq!(coverage.unparsed(), [6..10, 12..16]);
+        assert_eq!(coverage.parsed_bytes(), 8);
+        assert!(!coverage.is_complete());
+
+        // Only reads of the given slice count
+        assert_eq!(recording.coverage(&bytes[4..8]).parsed(), [0..2]);
+
+        // Nothing is recorded outside of record_spans
+        let (_, recording) = record_spans(|| ());
+        Le::new(&bytes).u8().map_err(|e| e.to_string())?;
+        assert!(recording.is_empty());
+
+        Ok(())
+    }
+}
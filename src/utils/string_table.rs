@@ -0,0 +1,152 @@
+/*!
+A shared area of null terminated strings, as written after nd trees, shader params and loctext
+keys, which structures point into by offset.
+
+```
+use bnl::utils::string_table::StringTable;
+
+let mut strings = StringTable::new(4);
+let group = strings.insert("ndGroup").unwrap();
+let bone = strings.insert("bone_root").unwrap();
+
+// Strings are only written once
+assert_eq!(strings.insert("ndGroup").unwrap(), group);
+// and each one starts on a multiple of the alignment
+assert_eq!(bone, 8);
+assert_eq!(strings.as_bytes().len(), 18);
+```
+*/
+
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringTableError {
+    /// The string contains a null character, so would be cut short when read back
+    ContainsNul(String),
+}
+
+impl fmt::Display for StringTableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StringTableError::ContainsNul(s) => {
+                write!(f, "String {:?} can't contain null characters", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StringTableError {}
+
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    bytes: Vec<u8>,
+    /// The offset of every string already in the table
+    offsets: HashMap<Vec<u8>, u32>,
+    alignment: usize,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl StringTable {
+    /// Creates an empty table, where each string starts on a multiple of `alignment` bytes from
+    /// the start of the table. Padding is written as zeroes.
+    pub fn new(alignment: usize) -> Self {
+        Self {
+            bytes: vec![],
+            offsets: HashMap::new(),
+            alignment: alignment.max(1),
+        }
+    }
+
+    /**
+    Adds `s` to the table if it isn't already there, returning its offset from the start of the
+    table.
+
+    # Errors
+    - [`StringTableError::ContainsNul`] if `s` contains a null character
+    */
+    pub fn insert(&mut self, s: &str) -> Result<u32, StringTableError> {
+        self.insert_bytes(s.as_bytes())
+            .ok_or_else(|| StringTableError::ContainsNul(s.to_string()))
+    }
+
+    /// Adds a string which isn't UTF-8, returning its offset, or None if it contains a null byte
+    pub fn insert_bytes(&mut self, s: &[u8]) -> Option<u32> {
+        if s.contains(&0) {
+            return None;
+        }
+
+        if let Some(&offset) = self.offsets.get(s) {
+            return Some(offset);
+        }
+
+        self.bytes
+            .resize(self.bytes.len().next_multiple_of(self.alignment), 0);
+
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(s);
+        self.bytes.push(0);
+
+        self.offsets.insert(s.to_vec(), offset);
+
+        Some(offset)
+    }
+
+    /// The offset of `s`, if it has been added
+    pub fn offset(&self, s: &str) -> Option<u32> {
+        self.offsets.get(s.as_bytes()).copied()
+    }
+
+    /// The number of different strings in the table
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// The strings as they are written, without any padding after the last one
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_table_dedup() -> Result<(), String> {
+        let mut strings = StringTable::default();
+
+        assert_eq!(strings.insert("ab").map_err(|e| e.to_string())?, 0);
+        assert_eq!(strings.insert("c").map_err(|e| e.to_string())?, 3);
+        assert_eq!(strings.insert("ab").map_err(|e| e.to_string())?, 0);
+        assert_eq!(strings.insert("").map_err(|e| e.to_string())?, 5);
+        assert_eq!(strings.as_bytes(), b"ab\0c\0\0");
+        assert_eq!(strings.len(), 3);
+        assert_eq!(strings.offset("c"), Some(3));
+        assert_eq!(strings.offset("d"), None);
+
+        assert_eq!(
+            strings.insert("a\0b"),
+            Err(StringTableError::ContainsNul("a\0b".to_string()))
+        );
+
+        let mut aligned = StringTable::new(8);
+        aligned.insert("abc").map_err(|e| e.to_string())?;
+        assert_eq!(aligned.insert("d").map_err(|e| e.to_string())?, 8);
+        assert_eq!(aligned.into_bytes(), b"abc\0\0\0\0\0d\0");
+
+        Ok(())
+    }
+}
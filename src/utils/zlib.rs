@@ -0,0 +1,156 @@
+/*!
+Compression and decompression of the zlib streams in BNL files.
+
+The implementation is chosen at build time. The `miniz` feature (on by default) uses the pure Rust
+`miniz_oxide`, and `flate2-zlib-ng` uses zlib-ng, which is much faster when packing large archives.
+If both are enabled, zlib-ng is used.
+*/
+
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+#[cfg(feature = "miniz")]
+pub mod miniz;
+#[cfg(feature = "flate2-zlib-ng")]
+pub mod zlib_ng;
+
+#[cfg(not(any(feature = "miniz", feature = "flate2-zlib-ng")))]
+compile_error!("A zlib backend is required, enable either the miniz or flate2-zlib-ng feature");
+
+/// The backend selected by the enabled features
+#[cfg(feature = "flate2-zlib-ng")]
+pub type DefaultBackend = zlib_ng::ZlibNgBackend;
+/// The backend selected by the enabled features
+#[cfg(all(feature = "miniz", not(feature = "flate2-zlib-ng")))]
+pub type DefaultBackend = miniz::MinizBackend;
+
+/// An implementation of zlib compression
+pub trait ZlibBackend {
+    /// An encoder which compresses everything written to it
+    type Encoder<W: Write>: Write;
+
+    /// Decompresses a whole zlib stream
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, ZlibError>;
+
+    /// Decompresses at most `limit` bytes from the start of a zlib stream. Streams which have more
+    /// output than this aren't an error, so `data` may be cut short.
+    fn decompress_prefix(data: &[u8], limit: usize) -> Result<Vec<u8>, ZlibError>;
+
+    /// Compresses `data` at `level`, from 0 (none) to 9 (best)
+    fn compress(data: &[u8], level: u8) -> Vec<u8>;
+
+    fn encoder<W: Write>(inner: W, level: u8) -> Self::Encoder<W>;
+
+    /// Ends the stream of `encoder`, returning the writer it wrapped
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> io::Result<W>;
+}
+
+/// A zlib stream couldn't be decompressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZlibError(pub String);
+
+impl fmt::Display for ZlibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid zlib stream: {}", self.0)
+    }
+}
+
+impl std::error::Error for ZlibError {}
+
+/// See [`ZlibBackend::decompress`]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    DefaultBackend::decompress(data)
+}
+
+/// See [`ZlibBackend::decompress_prefix`]
+pub fn decompress_prefix(data: &[u8], limit: usize) -> Result<Vec<u8>, ZlibError> {
+    DefaultBackend::decompress_prefix(data, limit)
+}
+
+/// See [`ZlibBackend::compress`]
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    DefaultBackend::compress(data, level)
+}
+
+/// A zlib encoder which compresses its input as it is written, so that large files can be written
+/// without holding all of their uncompressed bytes in memory
+pub struct ZlibWriter<W: Write> {
+    encoder: <DefaultBackend as ZlibBackend>::Encoder<W>,
+}
+
+impl<W: Write> ZlibWriter<W> {
+    pub fn new(inner: W, level: u8) -> Self {
+        Self {
+            encoder: DefaultBackend::encoder(inner, level),
+        }
+    }
+
+    /// Writes `count` zero bytes
+    pub fn write_zeroes(&mut self, count: usize) -> io::Result<()> {
+        const ZEROES: [u8; 4096] = [0; 4096];
+
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let len = remaining.min(ZEROES.len());
+            self.write_all(&ZEROES[..len])?;
+            remaining -= len;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the zlib stream, returning the inner writer
+    pub fn finish(self) -> io::Result<W> {
+        DefaultBackend::finish(self.encoder)
+    }
+}
+
+impl<W: Write> Write for ZlibWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zlib_writer_roundtrip() -> Result<(), String> {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = ZlibWriter::new(vec![], 1);
+
+        for chunk in data.chunks(7919) {
+            writer.write_all(chunk).map_err(|e| e.to_string())?;
+        }
+        writer.write_zeroes(10_000).map_err(|e| e.to_string())?;
+
+        let compressed = writer.finish().map_err(|e| e.to_string())?;
+        let decompressed = decompress(&compressed).map_err(|e| e.to_string())?;
+
+        assert_eq!(decompressed.len(), data.len() + 10_000);
+        assert_eq!(&decompressed[..data.len()], data);
+        assert!(decompressed[data.len()..].iter().all(|&b| b == 0));
+
+        // Prefixes stop early without an error, but truncated streams are still rejected
+        assert_eq!(
+            decompress_prefix(&compressed, 1000).map_err(|e| e.to_string())?,
+            data[..1000]
+        );
+        assert!(decompress(&compressed[..compressed.len() / 2]).is_err());
+        assert_eq!(
+            decompress(&compress(&data, 6)).map_err(|e| e.to_string())?,
+            data
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+/*!
+The pure Rust backend, using `miniz_oxide`.
+*/
+
+use std::io::{self, Write};
+
+use miniz_oxide::{
+    deflate::core::{
+        CompressorOxide, TDEFLFlush, TDEFLStatus, compress, create_comp_flags_from_zip_params,
+    },
+    inflate::{DecompressError, TINFLStatus},
+};
+
+use super::{ZlibBackend, ZlibError};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinizBackend;
+
+impl ZlibBackend for MinizBackend {
+    type Encoder<W: Write> = MinizEncoder<W>;
+
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, ZlibError> {
+        miniz_oxide::inflate::decompress_to_vec_zlib(data).map_err(error)
+    }
+
+    fn decompress_prefix(data: &[u8], limit: usize) -> Result<Vec<u8>, ZlibError> {
+        match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit) {
+            Ok(output) => Ok(output),
+            // Stopping at the limit is expected
+            Err(DecompressError {
+                status: TINFLStatus::HasMoreOutput,
+                output,
+            }) => Ok(output),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn compress(data: &[u8], level: u8) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec_zlib(data, level)
+    }
+
+    fn encoder<W: Write>(inner: W, level: u8) -> Self::Encoder<W> {
+        MinizEncoder::new(inner, level)
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> io::Result<W> {
+        encoder.finish()
+    }
+}
+
+fn error(e: DecompressError) -> ZlibError {
+    ZlibError(format!("{:?}", e.status))
+}
+
+/// The size of the buffer compressed bytes are collected in before being written
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Compressed {
+    /// This many bytes of input were compressed, and all of the output has been written
+    Consumed(usize),
+    /// This many bytes of input were compressed, but the compressor still has output to write
+    Pending(usize),
+    /// The stream has ended
+    Finished,
+}
+
+pub struct MinizEncoder<W: Write> {
+    compressor: Box<CompressorOxide>,
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> MinizEncoder<W> {
+    /// Creates an encoder writing to `inner`, using the same compression levels as
+    /// [`miniz_oxide::deflate::compress_to_vec_zlib`]
+    pub fn new(inner: W, level: u8) -> Self {
+        // A positive window size selects the zlib header and checksum
+        let flags = create_comp_flags_from_zip_params(level.into(), 1, 0);
+
+        Self {
+            compressor: Box::new(CompressorOxide::new(flags)),
+            inner,
+            buffer: vec![0; OUTPUT_BUFFER_SIZE],
+        }
+    }
+
+    /// Ends the zlib stream, returning the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        while self.compress(&[], TDEFLFlush::Finish)? != Compressed::Finished {}
+
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    /// Compresses as much of `input` as possible, writing out whatever the compressor produces
+    fn compress(&mut self, input: &[u8], flush: TDEFLFlush) -> io::Result<Compressed> {
+        let (status, bytes_in, bytes_out) =
+            compress(&mut self.compressor, input, &mut self.buffer, flush);
+
+        self.inner.write_all(&self.buffer[..bytes_out])?;
+
+        match status {
+            TDEFLStatus::Done => Ok(Compressed::Finished),
+            // The compressor has more to write once there is room for it
+            TDEFLStatus::Okay if bytes_out == self.buffer.len() => {
+                Ok(Compressed::Pending(bytes_in))
+            }
+            TDEFLStatus::Okay => Ok(Compressed::Consumed(bytes_in)),
+            status => Err(io::Error::other(format!(
+                "Unable to compress data: {:?}",
+                status
+            ))),
+        }
+    }
+}
+
+impl<W: Write> Write for MinizEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Keep going until some input is taken, as the compressor may only have been draining
+        // its own buffers
+        loop {
+            match self.compress(buf, TDEFLFlush::None)? {
+                Compressed::Consumed(0) | Compressed::Pending(0) => (),
+                Compressed::Consumed(n) | Compressed::Pending(n) => return Ok(n),
+                Compressed::Finished => return Ok(0),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while let Compressed::Pending(_) = self.compress(&[], TDEFLFlush::Sync)? {}
+
+        self.inner.flush()
+    }
+}
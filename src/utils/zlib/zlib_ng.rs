@@ -0,0 +1,54 @@
+/*!
+The zlib-ng backend, through `flate2`. It is several times faster than `miniz_oxide`, but needs a
+C compiler to build.
+*/
+
+use std::io::{self, Read, Write};
+
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
+
+use super::{ZlibBackend, ZlibError};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZlibNgBackend;
+
+impl ZlibBackend for ZlibNgBackend {
+    type Encoder<W: Write> = ZlibEncoder<W>;
+
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, ZlibError> {
+        let mut output = vec![];
+
+        ZlibDecoder::new(data)
+            .read_to_end(&mut output)
+            .map_err(|e| ZlibError(e.to_string()))?;
+
+        Ok(output)
+    }
+
+    fn decompress_prefix(data: &[u8], limit: usize) -> Result<Vec<u8>, ZlibError> {
+        let mut output = vec![];
+
+        ZlibDecoder::new(data)
+            .take(limit as u64)
+            .read_to_end(&mut output)
+            .map_err(|e| ZlibError(e.to_string()))?;
+
+        Ok(output)
+    }
+
+    fn compress(data: &[u8], level: u8) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::new(level.into()));
+
+        // Writing to a Vec can't fail
+        let _ = encoder.write_all(data);
+        encoder.finish().unwrap_or_default()
+    }
+
+    fn encoder<W: Write>(inner: W, level: u8) -> Self::Encoder<W> {
+        ZlibEncoder::new(inner, Compression::new(level.into()))
+    }
+
+    fn finish<W: Write>(encoder: Self::Encoder<W>) -> io::Result<W> {
+        encoder.finish()
+    }
+}
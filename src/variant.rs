@@ -0,0 +1,109 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{BE, LE, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+use crate::asset::ASSET_DESCRIPTION_SIZE;
+
+/**
+The container layout used by a BNL file.
+
+Only the container itself (the header, [`AssetDescription`](crate::asset::AssetDescription) table
+and [`DataViewList`](crate::asset::DataViewList) entries) is described by the variant. Descriptor and
+resource bytes are stored as-is and are still interpreted by each asset's own parser.
+
+# Examples
+```no_run
+use bnl::BnlVariant;
+
+let bytes = std::fs::read("./my_bnl.bnl").expect("Unable to read BNL.");
+let variant = BnlVariant::detect(&bytes).unwrap_or_default();
+```
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BnlVariant {
+    /// Little endian with a 40 byte header, as used by the Xbox release
+    #[default]
+    LittleEndian,
+    /// Big endian with a 40 byte header
+    BigEndian,
+}
+
+impl BnlVariant {
+    /// Every known variant, in the order they are tried by [`BnlVariant::detect`]
+    pub const ALL: [BnlVariant; 2] = [BnlVariant::LittleEndian, BnlVariant::BigEndian];
+
+    /// The size of the uncompressed header at the start of the file
+    pub fn header_size(&self) -> usize {
+        match self {
+            BnlVariant::LittleEndian | BnlVariant::BigEndian => 40,
+        }
+    }
+
+    pub fn is_big_endian(&self) -> bool {
+        matches!(self, BnlVariant::BigEndian)
+    }
+
+    /// Sniffs the header of a BNL file and returns the first variant whose header is
+    /// self-consistent, or `None` if no known variant matches.
+    pub fn detect(bytes: &[u8]) -> Option<BnlVariant> {
+        Self::ALL
+            .into_iter()
+            .find(|variant| variant.header_is_plausible(bytes))
+    }
+
+    fn header_is_plausible(&self, bytes: &[u8]) -> bool {
+        let header_size = self.header_size();
+
+        if bytes.len() < header_size {
+            return false;
+        }
+
+        let mut cur = io::Cursor::new(&bytes[..header_size]);
+
+        let Ok(file_count) = self.read_u16(&mut cur) else {
+            return false;
+        };
+
+        // flags + unknown_2
+        cur.set_position(8);
+
+        let (Ok(asset_desc_offset), Ok(asset_desc_size)) =
+            (self.read_u32(&mut cur), self.read_u32(&mut cur))
+        else {
+            return false;
+        };
+
+        asset_desc_offset as usize >= header_size
+            && asset_desc_size as usize == file_count as usize * ASSET_DESCRIPTION_SIZE
+    }
+
+    pub fn read_u16<R: Read>(&self, reader: &mut R) -> io::Result<u16> {
+        match self {
+            BnlVariant::LittleEndian => reader.read_u16::<LE>(),
+            BnlVariant::BigEndian => reader.read_u16::<BE>(),
+        }
+    }
+
+    pub fn read_u32<R: Read>(&self, reader: &mut R) -> io::Result<u32> {
+        match self {
+            BnlVariant::LittleEndian => reader.read_u32::<LE>(),
+            BnlVariant::BigEndian => reader.read_u32::<BE>(),
+        }
+    }
+
+    pub fn write_u16<W: Write>(&self, writer: &mut W, value: u16) -> io::Result<()> {
+        match self {
+            BnlVariant::LittleEndian => writer.write_u16::<LE>(value),
+            BnlVariant::BigEndian => writer.write_u16::<BE>(value),
+        }
+    }
+
+    pub fn write_u32<W: Write>(&self, writer: &mut W, value: u32) -> io::Result<()> {
+        match self {
+            BnlVariant::LittleEndian => writer.write_u32::<LE>(value),
+            BnlVariant::BigEndian => writer.write_u32::<BE>(value),
+        }
+    }
+}
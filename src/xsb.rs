@@ -1,13 +1,15 @@
+use std::error::Error;
+#[cfg(feature = "fs")]
 use std::{
-    error::Error,
-    fs,
-    io::{self, Cursor, Read, Seek, SeekFrom},
+    fs, io,
     path::{Path, PathBuf},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
 use serde::Deserialize;
 
+use crate::utils::reader::Le;
+
+#[cfg(feature = "fs")]
 pub fn dump_wav_files(wav_files: &[WavFile], dump_dir: PathBuf) -> Result<(), Box<dyn Error>> {
     let num_digits = (wav_files.len().checked_ilog10().unwrap_or(0) + 1) as usize;
 
@@ -23,27 +25,41 @@ pub fn dump_wav_files(wav_files: &[WavFile], dump_dir: PathBuf) -> Result<(), Bo
     Ok(())
 }
 
+#[cfg(feature = "fs")]
 pub fn wav_files_from_path(path: PathBuf) -> Result<Vec<WavFile>, Box<dyn Error>> {
-    let bytes = fs::read(path)?;
+    wav_files_from_bytes(&fs::read(path)?)
+}
 
-    let mut cur = Cursor::new(&bytes);
+pub fn wav_files_from_bytes(bytes: &[u8]) -> Result<Vec<WavFile>, Box<dyn Error>> {
+    let mut reader = Le::new(bytes);
 
-    let mut wbnd_string = [0u8; 4];
-    cur.read_exact(&mut wbnd_string)?;
+    let wbnd_string = reader.array::<u8, 4>()?;
 
     println!("Reading XWavebank header.");
 
+    let [
+        unknown_count_1,
+        header_size,
+        wavebanks_ptr,
+        wav_entries_ptr,
+        wav_entries_size,
+        unknown_count_2,
+        unknown_1,
+        wave_data_ptr,
+        wave_data_length,
+    ] = reader.array::<u32, 9>()?;
+
     let header = XWavebankHeader {
         wbnd_string,
-        unknown_count_1: cur.read_u32::<LittleEndian>()?,
-        header_size: cur.read_u32::<LittleEndian>()?,
-        wavebanks_ptr: cur.read_u32::<LittleEndian>()?,
-        wav_entries_ptr: cur.read_u32::<LittleEndian>()?,
-        wav_entries_size: cur.read_u32::<LittleEndian>()?,
-        unknown_count_2: cur.read_u32::<LittleEndian>()?,
-        unknown_1: cur.read_u32::<LittleEndian>()?,
-        wave_data_ptr: cur.read_u32::<LittleEndian>()?,
-        wave_data_length: cur.read_u32::<LittleEndian>()?,
+        unknown_count_1,
+        header_size,
+        wavebanks_ptr,
+        wav_entries_ptr,
+        wav_entries_size,
+        unknown_count_2,
+        unknown_1,
+        wave_data_ptr,
+        wave_data_length,
     };
 
     let num_wav_entries = header.wav_entries_size / (6 * 4);
@@ -55,18 +71,27 @@ pub fn wav_files_from_path(path: PathBuf) -> Result<Vec<WavFile>, Box<dyn Error>
 
     // Read wav entries
     if num_wav_entries != 0 {
-        cur.seek(SeekFrom::Start(header.wav_entries_ptr as u64))?;
+        let mut entries = reader.at(header.wav_entries_ptr as usize);
 
         for i in 0..num_wav_entries as usize {
+            let [
+                unknown_1,
+                raw_format,
+                bytes_ptr,
+                num_bytes,
+                unknown_2,
+                unknown_3,
+            ] = entries.array::<u32, 6>()?;
+
             let raw_entry = RawWavEntry {
-                unknown_1: cur.read_u32::<LittleEndian>()?,
+                unknown_1,
 
-                raw_format: cur.read_u32::<LittleEndian>()?,
+                raw_format,
 
-                bytes_ptr: cur.read_u32::<LittleEndian>()?,
-                num_bytes: cur.read_u32::<LittleEndian>()?,
-                unknown_2: cur.read_u32::<LittleEndian>()?,
-                unknown_3: cur.read_u32::<LittleEndian>()?,
+                bytes_ptr,
+                num_bytes,
+                unknown_2,
+                unknown_3,
             };
 
             raw_wav_entries[i] = raw_entry;
@@ -76,17 +101,12 @@ pub fn wav_files_from_path(path: PathBuf) -> Result<Vec<WavFile>, Box<dyn Error>
     wav_files.resize(raw_wav_entries.len(), Default::default());
 
     // Read wav data
-    let mut res_cursor = cur.clone();
-
     println!("Reading wav files.");
     for (i, raw_entry) in raw_wav_entries.into_iter().enumerate() {
-        let mut audio_bytes = vec![0u8; raw_entry.num_bytes as usize];
-
-        res_cursor.seek(SeekFrom::Start(
-            (raw_entry.bytes_ptr + header.wave_data_ptr) as u64,
-        ))?;
-
-        res_cursor.read_exact(&mut audio_bytes)?;
+        let audio_bytes = reader
+            .at(raw_entry.bytes_ptr as usize + header.wave_data_ptr as usize)
+            .bytes(raw_entry.num_bytes as usize)?
+            .to_vec();
 
         wav_files[i] = WavFile::from_raw(raw_entry, audio_bytes);
     }
@@ -256,6 +276,7 @@ impl WavFile {
         }
     }
 
+    #[cfg(feature = "fs")]
     pub fn dump<P: AsRef<Path>>(&self, out_path: P) -> Result<(), io::Error> {
         fs::create_dir_all(out_path.as_ref().parent().unwrap())?;
 
@@ -286,6 +307,7 @@ impl WavFile {
         .map_err(|_| io::Error::other("Failed to write wav file."))
     }
 
+    #[cfg(feature = "fs")]
     pub fn dump_raw<P: AsRef<Path>>(&self, out_path: P) -> Result<(), io::Error> {
         fs::create_dir_all(out_path.as_ref().parent().unwrap())?;
 